@@ -25,11 +25,21 @@ pub enum DehydrationError {
     MissingSigningKey(#[from] matrix_sdk_crypto::SignatureError),
     #[error(transparent)]
     Json(#[from] serde_json::Error),
+    #[error("The dehydrated device uses an unsupported algorithm: {0}")]
+    UnsupportedAlgorithm(String),
     #[error(transparent)]
     Store(#[from] matrix_sdk_crypto::CryptoStoreError),
     #[error("The pickle key has an invalid length, expected 32 bytes, got {0}")]
     PickleKeyLength(usize),
     #[error(transparent)]
+    PickleKeyBase64(#[from] matrix_sdk_crypto::vodozemac::Base64DecodeError),
+    #[error("The dehydrated device isn't signed by our cross-signing identity")]
+    DeviceSignatureMismatch,
+    #[error("The dehydrated device can't use the ID of our own device: {0}")]
+    DeviceIdCollision(OwnedDeviceId),
+    #[error("The dehydrated device could not be decrypted, the pickle key is incorrect")]
+    IncorrectPickleKey,
+    #[error(transparent)]
     Rand(#[from] rand::Error),
 }
 
@@ -37,6 +47,9 @@ impl From<matrix_sdk_crypto::dehydrated_devices::DehydrationError> for Dehydrati
     fn from(value: matrix_sdk_crypto::dehydrated_devices::DehydrationError) -> Self {
         match value {
             matrix_sdk_crypto::dehydrated_devices::DehydrationError::Json(e) => Self::Json(e),
+            matrix_sdk_crypto::dehydrated_devices::DehydrationError::UnsupportedAlgorithm(a) => {
+                Self::UnsupportedAlgorithm(a)
+            }
             matrix_sdk_crypto::dehydrated_devices::DehydrationError::Pickle(e) => Self::Pickle(e),
             matrix_sdk_crypto::dehydrated_devices::DehydrationError::LegacyPickle(e) => {
                 Self::LegacyPickle(e)
@@ -48,6 +61,18 @@ impl From<matrix_sdk_crypto::dehydrated_devices::DehydrationError> for Dehydrati
             matrix_sdk_crypto::dehydrated_devices::DehydrationError::PickleKeyLength(l) => {
                 Self::PickleKeyLength(l)
             }
+            matrix_sdk_crypto::dehydrated_devices::DehydrationError::PickleKeyBase64(e) => {
+                Self::PickleKeyBase64(e)
+            }
+            matrix_sdk_crypto::dehydrated_devices::DehydrationError::DeviceSignatureMismatch => {
+                Self::DeviceSignatureMismatch
+            }
+            matrix_sdk_crypto::dehydrated_devices::DehydrationError::IncorrectPickleKey => {
+                Self::IncorrectPickleKey
+            }
+            matrix_sdk_crypto::dehydrated_devices::DehydrationError::DeviceIdCollision(d) => {
+                Self::DeviceIdCollision(d)
+            }
         }
     }
 }
@@ -158,13 +183,24 @@ impl RehydratedDevice {
     pub fn receive_events(
         &self,
         events: String,
+        next_batch: Option<String>,
         decryption_settings: &DecryptionSettings,
     ) -> Result<(), crate::CryptoStoreError> {
         let events: Vec<Raw<AnyToDeviceEvent>> = serde_json::from_str(&events)?;
-        self.runtime.block_on(self.inner.receive_events(events, decryption_settings))?;
+        self.runtime.block_on(self.inner.receive_events(
+            events,
+            next_batch.as_deref(),
+            decryption_settings,
+        ))?;
 
         Ok(())
     }
+
+    /// Get the `next_batch` token of the last batch of to-device events
+    /// received by this device, if the rehydration was interrupted.
+    pub fn saved_progress(&self) -> Result<Option<String>, crate::CryptoStoreError> {
+        Ok(self.runtime.block_on(self.inner.saved_progress())?)
+    }
 }
 
 #[derive(uniffi::Object)]
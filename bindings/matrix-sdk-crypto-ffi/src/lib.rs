@@ -472,6 +472,7 @@ fn collect_sessions(
             created_using_fallback_key: session_pickle.created_using_fallback_key,
             creation_time,
             last_use_time,
+            decrypted_message_count: 0,
         };
 
         let session = Session::from_pickle(device_keys.clone(), pickle)?;
@@ -511,6 +512,7 @@ fn collect_sessions(
             backed_up: session.backed_up,
             history_visibility: None,
             shared_history: false,
+            last_used: None,
             algorithm: RustEventEncryptionAlgorithm::MegolmV1AesSha2,
         };
 
@@ -736,7 +736,7 @@ impl Client {
                 // Using an Arc here is mandatory or else the subscriber will never trigger
                 let observer =
                     Arc::new(self.inner.observe_room_events::<RumaRoomAccountDataEvent<$t>, ()>(
-                        &RoomId::parse(&room_id)?,
+                        RoomId::parse(&room_id)?,
                     ));
 
                 Ok(Arc::new(TaskHandle::new(get_runtime_handle().spawn(async move {
@@ -1617,11 +1617,9 @@ impl Client {
         &self,
         listener: Box<dyn MediaPreviewConfigListener>,
     ) -> Result<Arc<TaskHandle>, ClientError> {
-        let (initial_value, stream) = self.inner.account().observe_media_preview_config().await?;
+        let stream = self.inner.account().subscribe_to_media_preview_config().await?;
         Ok(Arc::new(TaskHandle::new(get_runtime_handle().spawn(async move {
-            // Send the initial value to the listener.
-            listener.on_change(initial_value.map(|config| config.into()));
-            // Listen for changes and notify the listener.
+            // The first item is the current value, the following ones are the changes.
             pin_mut!(stream);
             while let Some(media_preview_config) = stream.next().await {
                 listener.on_change(Some(media_preview_config.into()));
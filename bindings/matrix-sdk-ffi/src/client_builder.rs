@@ -165,11 +165,14 @@ impl ClientBuilder {
                 auto_enable_cross_signing: false,
                 backup_download_strategy:
                     matrix_sdk::encryption::BackupDownloadStrategy::AfterDecryptionFailure,
+                backup_download_settings: Default::default(),
                 auto_enable_backups: false,
             },
             room_key_recipient_strategy: Default::default(),
             decryption_settings: DecryptionSettings {
                 sender_device_trust_requirement: TrustRequirement::Untrusted,
+                to_device_trust_requirements: Default::default(),
+                unwedging_policy: Default::default(),
             },
             enable_share_history_on_invite: false,
             request_config: Default::default(),
@@ -82,6 +82,10 @@ pub enum RecoveryError {
     /// Error in the secret storage subsystem.
     #[error("Error in the secret-storage subsystem: {error_message}")]
     SecretStorage { error_message: String },
+
+    /// The given input looks like a recovery key, but it isn't a valid one.
+    #[error("The recovery key is invalid: {error_message}")]
+    InvalidRecoveryKey { error_message: String },
 }
 
 impl From<matrix_sdk::encryption::recovery::RecoveryError> for RecoveryError {
@@ -92,6 +96,9 @@ impl From<matrix_sdk::encryption::recovery::RecoveryError> for RecoveryError {
             recovery::RecoveryError::SecretStorage(e) => {
                 Self::SecretStorage { error_message: e.to_string() }
             }
+            recovery::RecoveryError::InvalidRecoveryKey(e) => {
+                Self::InvalidRecoveryKey { error_message: e.to_string() }
+            }
         }
     }
 }
@@ -127,7 +134,7 @@ impl From<backups::BackupState> for BackupState {
             backups::BackupState::Enabling => Self::Enabling,
             backups::BackupState::Resuming => Self::Resuming,
             backups::BackupState::Enabled => Self::Enabled,
-            backups::BackupState::Downloading => Self::Downloading,
+            backups::BackupState::Downloading(_) => Self::Downloading,
             backups::BackupState::Disabling => Self::Disabling,
         }
     }
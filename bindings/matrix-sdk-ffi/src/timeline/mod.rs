@@ -61,7 +61,7 @@ use uuid::Uuid;
 use self::content::TimelineItemContent;
 pub use self::msg_like::MessageContent;
 use crate::{
-    client::ProgressWatcher,
+    client::{ProgressWatcher, TransmissionProgress},
     error::{ClientError, RoomError},
     event::EventOrTransactionId,
     helpers::unwrap_or_clone_arc,
@@ -992,6 +992,13 @@ pub enum EventSendState {
     /// The local event has not been sent yet.
     NotSentYet,
 
+    /// The local event is being sent.
+    Sending {
+        /// For a media event, the progress of the upload of its media, if
+        /// known.
+        progress: Option<TransmissionProgress>,
+    },
+
     /// The local event has been sent to the server, but unsuccessfully: The
     /// sending has failed.
     SendingFailed {
@@ -1016,6 +1023,7 @@ impl From<&matrix_sdk_ui::timeline::EventSendState> for EventSendState {
 
         match value {
             NotSentYet => Self::NotSentYet,
+            Sending { progress } => Self::Sending { progress: progress.map(Into::into) },
             SendingFailed { error, is_recoverable } => {
                 let as_queue_wedge_error: matrix_sdk::QueueWedgeError = (&**error).into();
                 Self::SendingFailed {
@@ -13,21 +13,23 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-#[cfg(feature = "e2e-encryption")]
-use std::sync::Arc;
 use std::{
     collections::{BTreeMap, BTreeSet, HashMap},
     fmt,
     ops::Deref,
+    sync::Arc,
 };
 
 use eyeball::{SharedObservable, Subscriber};
 use eyeball_im::{Vector, VectorDiff};
 use futures_util::Stream;
 #[cfg(feature = "e2e-encryption")]
+use matrix_sdk_common::deserialized_responses::WithheldCode;
+#[cfg(feature = "e2e-encryption")]
 use matrix_sdk_crypto::{
-    CollectStrategy, DecryptionSettings, EncryptionSettings, OlmError, OlmMachine,
-    TrustRequirement, store::DynCryptoStore, types::requests::ToDeviceRequest,
+    CollectStrategy, DecryptionSettings, DeviceData, EncryptionSettings, KeyQueryConfig, OlmError,
+    OlmMachine, RoomKeyRotationLimits, TrustRequirement, store::DynCryptoStore,
+    types::requests::ToDeviceRequest,
 };
 #[cfg(doc)]
 use ruma::DeviceId;
@@ -59,7 +61,8 @@ use crate::{
     event_cache::store::EventCacheStoreLock,
     response_processors::{self as processors, Context},
     room::{
-        Room, RoomInfoNotableUpdate, RoomInfoNotableUpdateReasons, RoomMembersUpdate, RoomState,
+        Room, RoomDisplayNameOverride, RoomInfoNotableUpdate, RoomInfoNotableUpdateReasons,
+        RoomMembersUpdate, RoomState, UpdatedRoomDisplayName,
     },
     store::{
         BaseStateStore, DynStateStore, MemoryStore, Result as StoreResult, RoomLoadSettings,
@@ -116,6 +119,12 @@ pub struct BaseClient {
     #[cfg(feature = "e2e-encryption")]
     pub room_key_recipient_strategy: CollectStrategy,
 
+    /// The limits on the rotation of the room keys, applied on top of the
+    /// `m.room.encryption` state of the rooms when sending an encrypted
+    /// message.
+    #[cfg(feature = "e2e-encryption")]
+    pub room_key_rotation_limits: RoomKeyRotationLimits,
+
     /// The settings to use for decrypting events.
     #[cfg(feature = "e2e-encryption")]
     pub decryption_settings: DecryptionSettings,
@@ -124,6 +133,21 @@ pub struct BaseClient {
     #[cfg(feature = "e2e-encryption")]
     pub handle_verification_events: bool,
 
+    /// The time after which the verification requests time out, if it differs
+    /// from the default one.
+    #[cfg(feature = "e2e-encryption")]
+    pub verification_request_timeout: Option<std::time::Duration>,
+
+    /// The configuration of the `/keys/query` requests, if it differs from the
+    /// default one.
+    #[cfg(feature = "e2e-encryption")]
+    pub key_query_config: Option<KeyQueryConfig>,
+
+    /// Whether the room key requests should be recorded in the crypto store,
+    /// see [`OlmMachine::set_key_request_log_enabled()`].
+    #[cfg(feature = "e2e-encryption")]
+    pub key_request_log_enabled: bool,
+
     /// Whether the client supports threads or not.
     pub threading_support: ThreadingSupport,
 }
@@ -191,11 +215,21 @@ impl BaseClient {
             #[cfg(feature = "e2e-encryption")]
             room_key_recipient_strategy: Default::default(),
             #[cfg(feature = "e2e-encryption")]
+            room_key_rotation_limits: Default::default(),
+            #[cfg(feature = "e2e-encryption")]
             decryption_settings: DecryptionSettings {
                 sender_device_trust_requirement: TrustRequirement::Untrusted,
+                to_device_trust_requirements: Default::default(),
+                unwedging_policy: Default::default(),
             },
             #[cfg(feature = "e2e-encryption")]
             handle_verification_events: true,
+            #[cfg(feature = "e2e-encryption")]
+            verification_request_timeout: None,
+            #[cfg(feature = "e2e-encryption")]
+            key_query_config: None,
+            #[cfg(feature = "e2e-encryption")]
+            key_request_log_enabled: false,
             threading_support,
         }
     }
@@ -226,8 +260,12 @@ impl BaseClient {
             ignore_user_list_changes: Default::default(),
             room_info_notable_update_sender: self.room_info_notable_update_sender.clone(),
             room_key_recipient_strategy: self.room_key_recipient_strategy.clone(),
+            room_key_rotation_limits: self.room_key_rotation_limits,
             decryption_settings: self.decryption_settings.clone(),
             handle_verification_events,
+            verification_request_timeout: self.verification_request_timeout,
+            key_query_config: self.key_query_config,
+            key_request_log_enabled: self.key_request_log_enabled,
             threading_support: self.threading_support,
         };
 
@@ -385,6 +423,16 @@ impl BaseClient {
         .await
         .map_err(OlmError::from)?;
 
+        if let Some(timeout) = self.verification_request_timeout {
+            olm_machine.set_verification_request_timeout(timeout);
+        }
+
+        if let Some(config) = self.key_query_config {
+            olm_machine.set_key_query_config(config);
+        }
+
+        olm_machine.set_key_request_log_enabled(self.key_request_log_enabled);
+
         *self.olm_machine.write().await = Some(olm_machine);
         Ok(())
     }
@@ -544,6 +592,39 @@ impl BaseClient {
         self.state_store.sync_lock()
     }
 
+    /// Set the hook overriding the computed display names of the rooms, or
+    /// remove it with `None`.
+    ///
+    /// The display names of all the rooms are computed again with the new hook,
+    /// and saved in the store if they changed.
+    pub async fn set_room_display_name_override(
+        &self,
+        display_name_override: Option<Arc<dyn RoomDisplayNameOverride>>,
+    ) -> Result<()> {
+        let _sync_lock = self.sync_lock().lock().await;
+
+        self.state_store.set_display_name_override(display_name_override);
+
+        let mut changes = StateChanges::default();
+
+        for room in self.rooms() {
+            if let UpdatedRoomDisplayName::New(_) = room.compute_display_name().await? {
+                changes.add_room(room.clone_info());
+            }
+        }
+
+        self.state_store.save_changes(&changes).await?;
+
+        for room_id in changes.room_infos.into_keys() {
+            let _ = self.room_info_notable_update_sender.send(RoomInfoNotableUpdate {
+                room_id,
+                reasons: RoomInfoNotableUpdateReasons::DISPLAY_NAME,
+            });
+        }
+
+        Ok(())
+    }
+
     /// Receive a response from a sync call.
     ///
     /// # Arguments
@@ -977,41 +1058,85 @@ impl BaseClient {
     }
 
     /// Get a to-device request that will share a room key with users in a room.
+    ///
+    /// The room keys are shared according to the sharing strategy stored in the
+    /// room settings of the crypto store, if any, or to the client-wide
+    /// [`BaseClient::room_key_recipient_strategy`] otherwise.
     #[cfg(feature = "e2e-encryption")]
     pub async fn share_room_key(&self, room_id: &RoomId) -> Result<Vec<Arc<ToDeviceRequest>>> {
         match self.olm_machine().await.as_ref() {
             Some(o) => {
-                let Some(room) = self.get_room(room_id) else {
-                    return Err(Error::InsufficientData);
-                };
-
-                let history_visibility = room.history_visibility_or_default();
-                let Some(room_encryption_event) = room.encryption_settings() else {
-                    return Err(Error::EncryptionNotEnabled);
-                };
-
-                // Don't share the group session with members that are invited
-                // if the history visibility is set to `Joined`
-                let filter = if history_visibility == HistoryVisibility::Joined {
-                    RoomMemberships::JOIN
-                } else {
-                    RoomMemberships::ACTIVE
-                };
+                let (members, settings) = self.room_key_recipients(o, room_id).await?;
 
-                let members = self.state_store.get_user_ids(room_id, filter).await?;
+                Ok(o.share_room_key(room_id, members.iter().map(Deref::deref), settings).await?)
+            }
+            None => panic!("Olm machine wasn't started"),
+        }
+    }
 
-                let settings = EncryptionSettings::new(
-                    room_encryption_event,
-                    history_visibility,
-                    self.room_key_recipient_strategy.clone(),
-                );
+    /// Get the devices of the members of a room which won't receive the room
+    /// key shared with [`BaseClient::share_room_key()`], alongside the reason
+    /// why they are excluded.
+    #[cfg(feature = "e2e-encryption")]
+    pub async fn devices_withheld_from_room_key(
+        &self,
+        room_id: &RoomId,
+    ) -> Result<Vec<(DeviceData, WithheldCode)>> {
+        match self.olm_machine().await.as_ref() {
+            Some(o) => {
+                let (members, settings) = self.room_key_recipients(o, room_id).await?;
 
-                Ok(o.share_room_key(room_id, members.iter().map(Deref::deref), settings).await?)
+                Ok(o.devices_withheld_from_room_key(
+                    members.iter().map(Deref::deref),
+                    &settings.sharing_strategy,
+                )
+                .await?)
             }
             None => panic!("Olm machine wasn't started"),
         }
     }
 
+    /// Get the members of a room which should receive its room key, and the
+    /// settings to share the room key with.
+    #[cfg(feature = "e2e-encryption")]
+    async fn room_key_recipients(
+        &self,
+        olm_machine: &OlmMachine,
+        room_id: &RoomId,
+    ) -> Result<(Vec<OwnedUserId>, EncryptionSettings)> {
+        let Some(room) = self.get_room(room_id) else {
+            return Err(Error::InsufficientData);
+        };
+
+        let history_visibility = room.history_visibility_or_default();
+        let Some(room_encryption_event) = room.encryption_settings() else {
+            return Err(Error::EncryptionNotEnabled);
+        };
+
+        // Don't share the group session with members that are invited
+        // if the history visibility is set to `Joined`
+        let filter = if history_visibility == HistoryVisibility::Joined {
+            RoomMemberships::JOIN
+        } else {
+            RoomMemberships::ACTIVE
+        };
+
+        let members = self.state_store.get_user_ids(room_id, filter).await?;
+
+        // The strategy of the room, if any, overrides the client-wide one.
+        let sharing_strategy = olm_machine
+            .room_settings(room_id)
+            .await?
+            .and_then(|settings| settings.sharing_strategy)
+            .unwrap_or_else(|| self.room_key_recipient_strategy.clone());
+
+        let settings =
+            EncryptionSettings::new(room_encryption_event, history_visibility, sharing_strategy)
+                .with_rotation_limits(&self.room_key_rotation_limits);
+
+        Ok((members, settings))
+    }
+
     /// Get the room with the given room id.
     ///
     /// # Arguments
@@ -1166,7 +1291,7 @@ impl From<&v5::Request> for RequestedRequiredStates {
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use std::{collections::HashMap, sync::Arc};
 
     use assert_matches2::{assert_let, assert_matches};
     use futures_util::FutureExt as _;
@@ -1183,10 +1308,11 @@ mod tests {
         user_id,
     };
     use serde_json::{json, value::to_raw_value};
+    use stream_assert::{assert_next_eq, assert_pending};
 
     use super::{BaseClient, RequestedRequiredStates};
     use crate::{
-        RoomDisplayName, RoomState, SessionMeta,
+        RoomDisplayName, RoomDisplayNameInputs, RoomState, SessionMeta,
         client::ThreadingSupport,
         store::{RoomLoadSettings, StateStoreExt, StoreConfig},
         test_utils::logged_in_base_client,
@@ -1601,6 +1727,135 @@ mod tests {
         assert_eq!(member.avatar_url().unwrap().to_string(), "mxc://localhost/fewjilfewjil42");
     }
 
+    fn dm_member_event(user_id: &str, display_name: &str) -> StateTestEvent {
+        StateTestEvent::Custom(json!({
+            "content": {
+                "displayname": display_name,
+                "membership": "join",
+            },
+            "event_id": format!("$member_{display_name}"),
+            "origin_server_ts": 151800140,
+            "sender": user_id,
+            "state_key": user_id,
+            "type": "m.room.member",
+        }))
+    }
+
+    #[async_test]
+    async fn test_display_name_stream_when_dm_partner_is_renamed() {
+        let user_id = user_id!("@alice:example.org");
+        let room_id = room_id!("!dm:example.org");
+        let client = logged_in_base_client(Some(user_id)).await;
+
+        let mut sync_builder = SyncResponseBuilder::new();
+        let response = sync_builder
+            .add_joined_room(
+                matrix_sdk_test::JoinedRoomBuilder::new(room_id)
+                    .add_state_event(dm_member_event("@alice:example.org", "Alice"))
+                    .add_state_event(dm_member_event("@bob:example.org", "Bob")),
+            )
+            .build_sync_response();
+        client.receive_sync_response(response).await.unwrap();
+
+        let room = client.get_room(room_id).unwrap();
+        assert_eq!(room.cached_display_name(), Some(RoomDisplayName::Calculated("Bob".to_owned())));
+
+        let mut display_names = room.display_name_stream();
+        assert_pending!(display_names);
+
+        // The other member of the DM changes their display name.
+        let response = sync_builder
+            .add_joined_room(
+                matrix_sdk_test::JoinedRoomBuilder::new(room_id)
+                    .add_state_event(dm_member_event("@bob:example.org", "Robert")),
+            )
+            .build_sync_response();
+        client.receive_sync_response(response).await.unwrap();
+
+        assert_next_eq!(display_names, RoomDisplayName::Calculated("Robert".to_owned()));
+        assert_pending!(display_names);
+
+        // Another change not affecting the display name doesn't emit a new value.
+        let response = sync_builder
+            .add_joined_room(matrix_sdk_test::JoinedRoomBuilder::new(room_id).add_state_event(
+                StateTestEvent::Custom(json!({
+                    "content": {
+                        "topic": "Secret plans",
+                    },
+                    "event_id": "$topic",
+                    "origin_server_ts": 151800141,
+                    "sender": "@bob:example.org",
+                    "state_key": "",
+                    "type": "m.room.topic",
+                })),
+            ))
+            .build_sync_response();
+        client.receive_sync_response(response).await.unwrap();
+
+        assert_pending!(display_names);
+    }
+
+    #[async_test]
+    async fn test_room_display_name_override() {
+        let user_id = user_id!("@alice:example.org");
+        let room_id = room_id!("!dm:example.org");
+        let client = logged_in_base_client(Some(user_id)).await;
+
+        let mut sync_builder = SyncResponseBuilder::new();
+        let response = sync_builder
+            .add_joined_room(
+                matrix_sdk_test::JoinedRoomBuilder::new(room_id)
+                    .add_state_event(dm_member_event("@alice:example.org", "Alice"))
+                    .add_state_event(dm_member_event("@bob:example.org", "Bob")),
+            )
+            .build_sync_response();
+        client.receive_sync_response(response.clone()).await.unwrap();
+
+        let room = client.get_room(room_id).unwrap();
+        let mut display_names = room.display_name_stream();
+
+        // Setting the override computes the display names again.
+        let display_name_override = |inputs: &RoomDisplayNameInputs, computed| match computed {
+            RoomDisplayName::Calculated(_) if inputs.heroes.len() == 1 => {
+                RoomDisplayName::Calculated(format!("DM with {}", inputs.heroes.join(", ")))
+            }
+            computed => computed,
+        };
+        client.set_room_display_name_override(Some(Arc::new(display_name_override))).await.unwrap();
+
+        let expected = RoomDisplayName::Calculated("DM with Bob".to_owned());
+        assert_next_eq!(display_names, expected.clone());
+        assert_eq!(room.cached_display_name(), Some(expected.clone()));
+        assert_eq!(room.display_name().await.unwrap(), expected);
+
+        // The override is used for the following computations too.
+        let response_renamed = sync_builder
+            .add_joined_room(
+                matrix_sdk_test::JoinedRoomBuilder::new(room_id)
+                    .add_state_event(dm_member_event("@bob:example.org", "Robert")),
+            )
+            .build_sync_response();
+        client.receive_sync_response(response_renamed).await.unwrap();
+
+        let expected = RoomDisplayName::Calculated("DM with Robert".to_owned());
+        assert_next_eq!(display_names, expected.clone());
+
+        // Clients derived from this one, e.g. to render notifications, use the override
+        // too.
+        let notification_client =
+            client.clone_with_in_memory_state_store("notifications", false).await.unwrap();
+        notification_client.receive_sync_response(response).await.unwrap();
+        let notification_room = notification_client.get_room(room_id).unwrap();
+        assert_eq!(
+            notification_room.display_name().await.unwrap(),
+            RoomDisplayName::Calculated("DM with Bob".to_owned())
+        );
+
+        // Removing the override restores the computed display name.
+        client.set_room_display_name_override(None).await.unwrap();
+        assert_next_eq!(display_names, RoomDisplayName::Calculated("Robert".to_owned()));
+    }
+
     #[async_test]
     async fn test_reinvited_members_get_a_display_name() {
         let user_id = user_id!("@alice:example.org");
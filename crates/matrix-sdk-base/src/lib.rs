@@ -56,7 +56,8 @@ pub use matrix_sdk_crypto as crypto;
 pub use once_cell;
 pub use room::{
     EncryptionState, InviteAcceptanceDetails, PredecessorRoom, Room,
-    RoomCreateWithCreatorEventContent, RoomDisplayName, RoomHero, RoomInfo, RoomInfoNotableUpdate,
+    RoomCreateWithCreatorEventContent, RoomDisplayName, RoomDisplayNameInputs,
+    RoomDisplayNameOverride, RoomHero, RoomInfo, RoomInfoNotableUpdate,
     RoomInfoNotableUpdateReasons, RoomMember, RoomMembersUpdate, RoomMemberships, RoomState,
     RoomStateFilter, SuccessorRoom, apply_redaction,
 };
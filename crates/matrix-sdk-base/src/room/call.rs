@@ -67,7 +67,7 @@ mod tests {
         let room_id = room_id!("!test:localhost");
         let (sender, _receiver) = tokio::sync::broadcast::channel(1);
 
-        (store.clone(), Room::new(user_id, store, room_id, room_type, sender))
+        (store.clone(), Room::new(user_id, store, room_id, room_type, sender, Default::default()))
     }
 
     fn timestamp(minutes_ago: u32) -> MilliSecondsSinceUnixEpoch {
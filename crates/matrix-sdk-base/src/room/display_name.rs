@@ -12,12 +12,18 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::fmt;
+use std::{
+    fmt,
+    future::ready,
+    sync::{Arc, RwLock as StdRwLock},
+};
 
 use as_variant::as_variant;
+use futures_util::{Stream, StreamExt};
+use matrix_sdk_common::{SendOutsideWasm, SyncOutsideWasm};
 use regex::Regex;
 use ruma::{
-    OwnedMxcUri, OwnedUserId, UserId,
+    OwnedMxcUri, OwnedRoomAliasId, OwnedRoomId, OwnedUserId, UserId,
     events::{SyncStateEvent, member_hints::MemberHintsEventContent},
 };
 use serde::{Deserialize, Serialize};
@@ -30,6 +36,86 @@ use crate::{
     store::{Result as StoreResult, StateStoreExt},
 };
 
+/// A hook to post-process, or fully override, the computed display names of
+/// the rooms.
+///
+/// It's set with
+/// [`BaseClient::set_room_display_name_override`](crate::BaseClient::set_room_display_name_override),
+/// and applies to all the display names computed afterwards, e.g. the ones
+/// returned by [`Room::display_name`], used by the room list or to render
+/// notifications.
+pub trait RoomDisplayNameOverride: SendOutsideWasm + SyncOutsideWasm {
+    /// Return the display name of a room, given the inputs of its computation
+    /// and the display name computed by the SDK.
+    fn override_display_name(
+        &self,
+        inputs: &RoomDisplayNameInputs,
+        computed: RoomDisplayName,
+    ) -> RoomDisplayName;
+}
+
+impl<F> RoomDisplayNameOverride for F
+where
+    F: Fn(&RoomDisplayNameInputs, RoomDisplayName) -> RoomDisplayName
+        + SendOutsideWasm
+        + SyncOutsideWasm,
+{
+    fn override_display_name(
+        &self,
+        inputs: &RoomDisplayNameInputs,
+        computed: RoomDisplayName,
+    ) -> RoomDisplayName {
+        self(inputs, computed)
+    }
+}
+
+/// The inputs of the computation of a room's display name, passed to a
+/// [`RoomDisplayNameOverride`].
+#[derive(Clone, Debug)]
+pub struct RoomDisplayNameInputs {
+    /// The ID of the room.
+    pub room_id: OwnedRoomId,
+
+    /// The name of the room, from its `m.room.name` event.
+    pub name: Option<String>,
+
+    /// The canonical alias of the room.
+    pub canonical_alias: Option<OwnedRoomAliasId>,
+
+    /// The names of the heroes of the room, i.e. of the members used to
+    /// compute its name when it doesn't have an explicit one, sorted.
+    pub heroes: Vec<String>,
+
+    /// The number of joined and invited members of the room, not including the
+    /// service members.
+    pub num_joined_invited: u64,
+}
+
+/// The [`RoomDisplayNameOverride`] shared by all the rooms of a client.
+#[derive(Clone, Default)]
+pub(crate) struct SharedRoomDisplayNameOverride(
+    Arc<StdRwLock<Option<Arc<dyn RoomDisplayNameOverride>>>>,
+);
+
+impl SharedRoomDisplayNameOverride {
+    pub(crate) fn get(&self) -> Option<Arc<dyn RoomDisplayNameOverride>> {
+        self.0.read().unwrap().clone()
+    }
+
+    pub(crate) fn set(&self, display_name_override: Option<Arc<dyn RoomDisplayNameOverride>>) {
+        *self.0.write().unwrap() = display_name_override;
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+impl fmt::Debug for SharedRoomDisplayNameOverride {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedRoomDisplayNameOverride")
+            .field("is_set", &self.0.read().unwrap().is_some())
+            .finish()
+    }
+}
+
 impl Room {
     /// Calculate a room's display name, or return the cached value, taking into
     /// account its name, aliases and members.
@@ -60,6 +146,27 @@ impl Room {
         self.inner.read().cached_display_name.clone()
     }
 
+    /// Get a stream of the computed display names of this room.
+    ///
+    /// The stream emits a new value every time the computed display name
+    /// changes, e.g. when the name of the other member of a DM changes, but not
+    /// the current value: use [`Self::cached_display_name`] to get it.
+    pub fn display_name_stream(&self) -> impl Stream<Item = RoomDisplayName> + use<> {
+        let mut current = self.cached_display_name();
+
+        self.subscribe_info().filter_map(move |info| {
+            let display_name = match info.cached_display_name {
+                Some(display_name) if current.as_ref() != Some(&display_name) => {
+                    current = Some(display_name.clone());
+                    Some(display_name)
+                }
+                _ => None,
+            };
+
+            ready(display_name)
+        })
+    }
+
     /// Force recalculating a room's display name, taking into account its name,
     /// aliases and members.
     ///
@@ -72,36 +179,56 @@ impl Room {
     ///
     /// [spec]: <https://matrix.org/docs/spec/client_server/latest#calculating-the-display-name-for-a-room>
     pub(crate) async fn compute_display_name(&self) -> StoreResult<UpdatedRoomDisplayName> {
-        enum DisplayNameOrSummary {
-            Summary(RoomSummary),
-            DisplayName(RoomDisplayName),
-        }
-
-        let display_name_or_summary = {
+        let (name, canonical_alias, summary) = {
             let inner = self.inner.read();
+            (
+                inner.name().map(|name| name.trim().to_owned()),
+                inner.canonical_alias().map(ToOwned::to_owned),
+                inner.summary.clone(),
+            )
+        };
 
-            match (inner.name(), inner.canonical_alias()) {
-                (Some(name), _) => {
-                    let name = RoomDisplayName::Named(name.trim().to_owned());
-                    DisplayNameOrSummary::DisplayName(name)
-                }
-                (None, Some(alias)) => {
-                    let name = RoomDisplayName::Aliased(alias.alias().trim().to_owned());
-                    DisplayNameOrSummary::DisplayName(name)
-                }
-                // We can't directly compute the display name from the summary here because Rust
-                // thinks that the `inner` lock is still held even if we explicitly call `drop()`
-                // on it. So we introduced the DisplayNameOrSummary type and do the computation in
-                // two steps.
-                (None, None) => DisplayNameOrSummary::Summary(inner.summary.clone()),
-            }
+        let explicit_display_name = match (&name, &canonical_alias) {
+            (Some(name), _) => Some(RoomDisplayName::Named(name.clone())),
+            (None, Some(alias)) => Some(RoomDisplayName::Aliased(alias.alias().trim().to_owned())),
+            (None, None) => None,
         };
 
-        let display_name = match display_name_or_summary {
-            DisplayNameOrSummary::Summary(summary) => {
-                self.compute_display_name_from_summary(summary).await?
-            }
-            DisplayNameOrSummary::DisplayName(display_name) => display_name,
+        let display_name_override = self.display_name_override.get();
+
+        let display_name = if let Some(display_name_override) = display_name_override {
+            // The heroes are always part of the inputs of the override, even if the room
+            // has an explicit name.
+            let (heroes, num_joined_invited) = self.compute_heroes_from_summary(summary).await?;
+
+            let computed = explicit_display_name.unwrap_or_else(|| {
+                compute_display_name_from_heroes(
+                    num_joined_invited,
+                    heroes.iter().map(String::as_str).collect(),
+                )
+            });
+
+            let mut heroes = heroes;
+            heroes.sort_unstable();
+
+            let inputs = RoomDisplayNameInputs {
+                room_id: self.room_id().to_owned(),
+                name,
+                canonical_alias,
+                heroes,
+                num_joined_invited,
+            };
+
+            display_name_override.override_display_name(&inputs, computed)
+        } else if let Some(display_name) = explicit_display_name {
+            display_name
+        } else {
+            let (heroes, num_joined_invited) = self.compute_heroes_from_summary(summary).await?;
+
+            compute_display_name_from_heroes(
+                num_joined_invited,
+                heroes.iter().map(String::as_str).collect(),
+            )
         };
 
         // Update the cached display name before we return the newly computed value.
@@ -125,11 +252,12 @@ impl Room {
         })
     }
 
-    /// Compute a [`RoomDisplayName`] from the given [`RoomSummary`].
-    async fn compute_display_name_from_summary(
+    /// Compute the names of the heroes, and the number of joined and invited
+    /// members, from the given [`RoomSummary`].
+    async fn compute_heroes_from_summary(
         &self,
         summary: RoomSummary,
-    ) -> StoreResult<RoomDisplayName> {
+    ) -> StoreResult<(Vec<String>, u64)> {
         let computed_summary = if !summary.room_heroes.is_empty() {
             self.extract_and_augment_summary(&summary).await?
         } else {
@@ -160,12 +288,7 @@ impl Room {
             "Calculating name for a room based on heroes",
         );
 
-        let display_name = compute_display_name_from_heroes(
-            num_joined_invited,
-            heroes.iter().map(|hero| hero.as_str()).collect(),
-        );
-
-        Ok(display_name)
+        Ok((heroes, num_joined_invited))
     }
 
     /// Extracts and enhances the [`RoomSummary`] provided by the homeserver.
@@ -538,7 +661,7 @@ mod tests {
         let room_id = room_id!("!test:localhost");
         let (sender, _receiver) = tokio::sync::broadcast::channel(1);
 
-        (store.clone(), Room::new(user_id, store, room_id, room_type, sender))
+        (store.clone(), Room::new(user_id, store, room_id, room_type, sender, Default::default()))
     }
 
     fn make_stripped_member_event(user_id: &UserId, name: &str) -> Raw<StrippedRoomMemberEvent> {
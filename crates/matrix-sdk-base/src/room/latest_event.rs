@@ -102,7 +102,7 @@ mod tests_with_e2e_encryption {
         let room_id = room_id!("!test:localhost");
         let (sender, _receiver) = tokio::sync::broadcast::channel(1);
 
-        (store.clone(), Room::new(user_id, store, room_id, room_type, sender))
+        (store.clone(), Room::new(user_id, store, room_id, room_type, sender, Default::default()))
     }
 
     #[async_test]
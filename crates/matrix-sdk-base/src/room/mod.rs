@@ -34,8 +34,8 @@ use std::{
 };
 
 pub use create::*;
-pub use display_name::{RoomDisplayName, RoomHero};
-pub(crate) use display_name::{RoomSummary, UpdatedRoomDisplayName};
+pub use display_name::{RoomDisplayName, RoomDisplayNameInputs, RoomDisplayNameOverride, RoomHero};
+pub(crate) use display_name::{RoomSummary, SharedRoomDisplayNameOverride, UpdatedRoomDisplayName};
 pub use encryption::EncryptionState;
 use eyeball::{AsyncLock, SharedObservable};
 use futures_util::{Stream, StreamExt};
@@ -115,6 +115,10 @@ pub struct Room {
 
     /// A sender that will notify receivers when room member updates happen.
     pub room_member_updates_sender: broadcast::Sender<RoomMembersUpdate>,
+
+    /// The hook overriding the computed display name of the room, shared by
+    /// all the rooms.
+    pub(super) display_name_override: SharedRoomDisplayNameOverride,
 }
 
 impl Room {
@@ -124,9 +128,16 @@ impl Room {
         room_id: &RoomId,
         room_state: RoomState,
         room_info_notable_update_sender: broadcast::Sender<RoomInfoNotableUpdate>,
+        display_name_override: SharedRoomDisplayNameOverride,
     ) -> Self {
         let room_info = RoomInfo::new(room_id, room_state);
-        Self::restore(own_user_id, store, room_info, room_info_notable_update_sender)
+        Self::restore(
+            own_user_id,
+            store,
+            room_info,
+            room_info_notable_update_sender,
+            display_name_override,
+        )
     }
 
     pub(crate) fn restore(
@@ -134,6 +145,7 @@ impl Room {
         store: Arc<DynStateStore>,
         room_info: RoomInfo,
         room_info_notable_update_sender: broadcast::Sender<RoomInfoNotableUpdate>,
+        display_name_override: SharedRoomDisplayNameOverride,
     ) -> Self {
         let (room_member_updates_sender, _) = broadcast::channel(10);
         Self {
@@ -148,6 +160,7 @@ impl Room {
             room_info_notable_update_sender,
             seen_knock_request_ids_map: SharedObservable::new_async(None),
             room_member_updates_sender,
+            display_name_override,
         }
     }
 
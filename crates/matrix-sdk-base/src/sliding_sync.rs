@@ -2540,6 +2540,7 @@ mod tests {
             room_id!("!r:e.co"),
             RoomState::Joined,
             sender,
+            Default::default(),
         )
     }
 
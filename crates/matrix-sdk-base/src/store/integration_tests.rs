@@ -71,6 +71,10 @@ pub trait StateStoreIntegrationTests {
     async fn test_sync_token_saving(&self);
     /// Test UtdHookManagerData saving.
     async fn test_utd_hook_manager_data_saving(&self);
+    /// Test saving the pending updates of the `m.direct` account data.
+    async fn test_pending_direct_updates_saving(&self);
+    /// Test saving the third party identifiers of the account.
+    async fn test_third_party_identifiers_saving(&self);
     /// Test stripped room member saving.
     async fn test_stripped_member_saving(&self);
     /// Test room power levels saving.
@@ -583,6 +587,64 @@ impl StateStoreIntegrationTests for DynStateStore {
         assert_eq!(read_data, data);
     }
 
+    async fn test_pending_direct_updates_saving(&self) {
+        assert_matches!(self.get_kv_data(StateStoreDataKey::PendingDirectUpdates).await, Ok(None));
+
+        let updates = BTreeMap::from([(
+            user_id!("@alice:localhost").to_owned(),
+            vec![room_id!("!dm:localhost").to_owned()],
+        )]);
+        self.set_kv_data(
+            StateStoreDataKey::PendingDirectUpdates,
+            StateStoreDataValue::PendingDirectUpdates(updates.clone()),
+        )
+        .await
+        .expect("Could not save data");
+
+        let read_updates = self
+            .get_kv_data(StateStoreDataKey::PendingDirectUpdates)
+            .await
+            .expect("Could not read data")
+            .expect("no data found")
+            .into_pending_direct_updates()
+            .expect("not PendingDirectUpdates");
+        assert_eq!(read_updates, updates);
+
+        self.remove_kv_data(StateStoreDataKey::PendingDirectUpdates).await.unwrap();
+        assert_matches!(self.get_kv_data(StateStoreDataKey::PendingDirectUpdates).await, Ok(None));
+    }
+
+    async fn test_third_party_identifiers_saving(&self) {
+        assert_matches!(self.get_kv_data(StateStoreDataKey::ThirdPartyIdentifiers).await, Ok(None));
+
+        let threepids = serde_json::from_value(json!([{
+            "address": "alice@example.org",
+            "medium": "email",
+            "added_at": 1_535_176_800_000_u64,
+            "validated_at": 1_535_176_800_000_u64,
+        }]))
+        .unwrap();
+        self.set_kv_data(
+            StateStoreDataKey::ThirdPartyIdentifiers,
+            StateStoreDataValue::ThirdPartyIdentifiers(threepids),
+        )
+        .await
+        .expect("Could not save data");
+
+        let read_threepids = self
+            .get_kv_data(StateStoreDataKey::ThirdPartyIdentifiers)
+            .await
+            .expect("Could not read data")
+            .expect("no data found")
+            .into_third_party_identifiers()
+            .expect("not ThirdPartyIdentifiers");
+        assert_eq!(read_threepids.len(), 1);
+        assert_eq!(read_threepids[0].address, "alice@example.org");
+
+        self.remove_kv_data(StateStoreDataKey::ThirdPartyIdentifiers).await.unwrap();
+        assert_matches!(self.get_kv_data(StateStoreDataKey::ThirdPartyIdentifiers).await, Ok(None));
+    }
+
     async fn test_stripped_member_saving(&self) {
         let room_id = room_id!("!test_stripped_member_saving:localhost");
         let user_id = user_id();
@@ -1906,6 +1968,18 @@ macro_rules! statestore_integration_tests {
                 store.test_utd_hook_manager_data_saving().await;
             }
 
+            #[async_test]
+            async fn test_pending_direct_updates_saving() {
+                let store = get_store().await.expect("creating store failed").into_state_store();
+                store.test_pending_direct_updates_saving().await;
+            }
+
+            #[async_test]
+            async fn test_third_party_identifiers_saving() {
+                let store = get_store().await.expect("creating store failed").into_state_store();
+                store.test_third_party_identifiers_saving().await;
+            }
+
             #[async_test]
             async fn test_stripped_member_saving() {
                 let store = get_store().await.unwrap().into_state_store();
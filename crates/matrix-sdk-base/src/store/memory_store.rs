@@ -32,6 +32,7 @@ use ruma::{
         room::member::{MembershipState, StrippedRoomMemberEvent, SyncRoomMemberEvent},
     },
     serde::Raw,
+    thirdparty::ThirdPartyIdentifier,
     time::Instant,
 };
 use tracing::{debug, instrument, warn};
@@ -83,6 +84,8 @@ struct MemoryStoreInner {
     send_queue_events: BTreeMap<OwnedRoomId, Vec<QueuedRequest>>,
     dependent_send_queue_events: BTreeMap<OwnedRoomId, Vec<DependentQueuedRequest>>,
     seen_knock_requests: BTreeMap<OwnedRoomId, BTreeMap<OwnedEventId, OwnedUserId>>,
+    pending_direct_updates: Option<BTreeMap<OwnedUserId, Vec<OwnedRoomId>>>,
+    third_party_identifiers: Option<Vec<ThirdPartyIdentifier>>,
     thread_subscriptions: BTreeMap<OwnedRoomId, BTreeMap<OwnedEventId, ThreadStatus>>,
 }
 
@@ -176,6 +179,13 @@ impl StateStore for MemoryStore {
                 .get(room_id)
                 .cloned()
                 .map(StateStoreDataValue::SeenKnockRequests),
+            StateStoreDataKey::PendingDirectUpdates => {
+                inner.pending_direct_updates.clone().map(StateStoreDataValue::PendingDirectUpdates)
+            }
+            StateStoreDataKey::ThirdPartyIdentifiers => inner
+                .third_party_identifiers
+                .clone()
+                .map(StateStoreDataValue::ThirdPartyIdentifiers),
         })
     }
 
@@ -236,6 +246,20 @@ impl StateStore for MemoryStore {
                         .expect("Session data is not a set of seen join request ids"),
                 );
             }
+            StateStoreDataKey::PendingDirectUpdates => {
+                inner.pending_direct_updates = Some(
+                    value
+                        .into_pending_direct_updates()
+                        .expect("Session data not the pending m.direct updates"),
+                );
+            }
+            StateStoreDataKey::ThirdPartyIdentifiers => {
+                inner.third_party_identifiers = Some(
+                    value
+                        .into_third_party_identifiers()
+                        .expect("Session data not a list of third party identifiers"),
+                );
+            }
         }
 
         Ok(())
@@ -263,6 +287,8 @@ impl StateStore for MemoryStore {
             StateStoreDataKey::SeenKnockRequests(room_id) => {
                 inner.seen_knock_requests.remove(room_id);
             }
+            StateStoreDataKey::PendingDirectUpdates => inner.pending_direct_updates = None,
+            StateStoreDataKey::ThirdPartyIdentifiers => inner.third_party_identifiers = None,
         }
         Ok(())
     }
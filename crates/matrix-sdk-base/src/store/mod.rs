@@ -71,7 +71,10 @@ use crate::{
     MinimalRoomMemberEvent, Room, RoomCreateWithCreatorEventContent, RoomStateFilter, SessionMeta,
     deserialized_responses::DisplayName,
     event_cache::store as event_cache_store,
-    room::{RoomInfo, RoomInfoNotableUpdate, RoomState},
+    room::{
+        RoomDisplayNameOverride, RoomInfo, RoomInfoNotableUpdate, RoomState,
+        SharedRoomDisplayNameOverride,
+    },
 };
 
 pub(crate) mod ambiguity_map;
@@ -182,6 +185,8 @@ pub(crate) struct BaseStateStore {
     /// A lock to synchronize access to the store, such that data by the sync is
     /// never overwritten.
     sync_lock: Arc<Mutex<()>>,
+    /// The hook overriding the computed display names of the rooms.
+    display_name_override: SharedRoomDisplayNameOverride,
 }
 
 impl BaseStateStore {
@@ -194,6 +199,7 @@ impl BaseStateStore {
             sync_token: Default::default(),
             rooms: Arc::new(StdRwLock::new(ObservableMap::new())),
             sync_lock: Default::default(),
+            display_name_override: Default::default(),
         }
     }
 
@@ -231,6 +237,7 @@ impl BaseStateStore {
                 self.inner.clone(),
                 room_info,
                 room_info_notable_update_sender.clone(),
+                self.display_name_override.clone(),
             );
             let new_room_id = new_room.room_id().to_owned();
 
@@ -296,6 +303,7 @@ impl BaseStateStore {
 
         let room_load_settings = other.room_load_settings.read().await.clone();
 
+        self.display_name_override.set(other.display_name_override.get());
         self.load_rooms(&session_meta.user_id, room_load_settings, room_info_notable_update_sender)
             .await?;
         self.load_sync_token().await?;
@@ -309,6 +317,14 @@ impl BaseStateStore {
         self.session_meta.get()
     }
 
+    /// Set the hook overriding the computed display names of the rooms.
+    pub(crate) fn set_display_name_override(
+        &self,
+        display_name_override: Option<Arc<dyn RoomDisplayNameOverride>>,
+    ) {
+        self.display_name_override.set(display_name_override);
+    }
+
     /// Get all the rooms this store knows about.
     pub fn rooms(&self) -> Vec<Room> {
         self.rooms.read().unwrap().iter().cloned().collect()
@@ -364,6 +380,7 @@ impl BaseStateStore {
                     room_id,
                     room_state,
                     room_info_notable_update_sender,
+                    self.display_name_override.clone(),
                 )
             })
             .clone()
@@ -41,6 +41,7 @@ use ruma::{
         receipt::{Receipt, ReceiptThread, ReceiptType},
     },
     serde::Raw,
+    thirdparty::ThirdPartyIdentifier,
     time::SystemTime,
 };
 use serde::{Deserialize, Serialize};
@@ -1126,6 +1127,15 @@ pub enum StateStoreDataValue {
 
     /// A list of knock request ids marked as seen in a room.
     SeenKnockRequests(BTreeMap<OwnedEventId, OwnedUserId>),
+
+    /// The rooms that must be added to the `m.direct` account data, per user,
+    /// but that couldn't be uploaded yet.
+    PendingDirectUpdates(BTreeMap<OwnedUserId, Vec<OwnedRoomId>>),
+
+    /// The last known list of [Third Party Identifiers][3pid] of the account.
+    ///
+    /// [3pid]: https://spec.matrix.org/latest/appendices/#3pid-types
+    ThirdPartyIdentifiers(Vec<ThirdPartyIdentifier>),
 }
 
 /// Current draft of the composer for the room.
@@ -1197,6 +1207,18 @@ impl StateStoreDataValue {
     pub fn into_seen_knock_requests(self) -> Option<BTreeMap<OwnedEventId, OwnedUserId>> {
         as_variant!(self, Self::SeenKnockRequests)
     }
+
+    /// Get this value if it is the pending updates of the `m.direct` account
+    /// data.
+    pub fn into_pending_direct_updates(self) -> Option<BTreeMap<OwnedUserId, Vec<OwnedRoomId>>> {
+        as_variant!(self, Self::PendingDirectUpdates)
+    }
+
+    /// Get this value if it is the list of third party identifiers of the
+    /// account.
+    pub fn into_third_party_identifiers(self) -> Option<Vec<ThirdPartyIdentifier>> {
+        as_variant!(self, Self::ThirdPartyIdentifiers)
+    }
 }
 
 /// A key for key-value data.
@@ -1229,6 +1251,12 @@ pub enum StateStoreDataKey<'a> {
 
     /// A list of knock request ids marked as seen in a room.
     SeenKnockRequests(&'a RoomId),
+
+    /// The pending updates of the `m.direct` account data.
+    PendingDirectUpdates,
+
+    /// The last known list of third party identifiers of the account.
+    ThirdPartyIdentifiers,
 }
 
 impl StateStoreDataKey<'_> {
@@ -1258,6 +1286,14 @@ impl StateStoreDataKey<'_> {
     /// Key prefix to use for the
     /// [`SeenKnockRequests`][Self::SeenKnockRequests] variant.
     pub const SEEN_KNOCK_REQUESTS: &'static str = "seen_knock_requests";
+
+    /// Key to use for the
+    /// [`PendingDirectUpdates`][Self::PendingDirectUpdates] variant.
+    pub const PENDING_DIRECT_UPDATES: &'static str = "pending_direct_updates";
+
+    /// Key to use for the
+    /// [`ThirdPartyIdentifiers`][Self::ThirdPartyIdentifiers] variant.
+    pub const THIRD_PARTY_IDENTIFIERS: &'static str = "third_party_identifiers";
 }
 
 #[cfg(test)]
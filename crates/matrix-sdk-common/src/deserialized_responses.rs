@@ -48,6 +48,8 @@ const UNKNOWN_DEVICE: &str = "Encrypted by an unknown or deleted device.";
 const MISMATCHED_SENDER: &str = "\
     The sender of the event does not match the owner of the device \
     that created the Megolm session.";
+const PIN_VIOLATION: &str =
+    "Encrypted by a device which doesn't match the pinned devices of the user.";
 pub const SENT_IN_CLEAR: &str = "Not encrypted.";
 
 /// Represents the state of verification for a decrypted message sent by a
@@ -127,6 +129,9 @@ impl VerificationState {
                     code: ShieldStateCode::MismatchedSender,
                     message: MISMATCHED_SENDER,
                 },
+                VerificationLevel::PinViolation => {
+                    ShieldState::Red { code: ShieldStateCode::PinViolation, message: PIN_VIOLATION }
+                }
             },
         }
     }
@@ -185,6 +190,9 @@ impl VerificationState {
                     code: ShieldStateCode::MismatchedSender,
                     message: MISMATCHED_SENDER,
                 },
+                VerificationLevel::PinViolation => {
+                    ShieldState::Red { code: ShieldStateCode::PinViolation, message: PIN_VIOLATION }
+                }
             },
         }
     }
@@ -216,6 +224,11 @@ pub enum VerificationLevel {
     /// The `sender` field on the event does not match the owner of the device
     /// that established the Megolm session.
     MismatchedSender,
+
+    /// The message was sent by a device not linked to any user identity, which
+    /// doesn't match the devices pinned for the user when the device pinning
+    /// mode is enabled.
+    PinViolation,
 }
 
 impl fmt::Display for VerificationLevel {
@@ -230,6 +243,9 @@ impl fmt::Display for VerificationLevel {
             }
             VerificationLevel::None(..) => "The sending device is not known",
             VerificationLevel::MismatchedSender => MISMATCHED_SENDER,
+            VerificationLevel::PinViolation => {
+                "The sending device doesn't match the devices pinned for the sender"
+            }
         };
         write!(f, "{display}")
     }
@@ -293,6 +309,8 @@ pub enum ShieldStateCode {
     /// The `sender` field on the event does not match the owner of the device
     /// that established the Megolm session.
     MismatchedSender,
+    /// The sending device doesn't match the devices pinned for the sender.
+    PinViolation,
 }
 
 /// The algorithm specific information of a decrypted event.
@@ -318,6 +336,21 @@ pub enum AlgorithmInfo {
     OlmV1Curve25519AesSha2 {
         // The sender device key, base64 encoded
         curve25519_public_key_base64: String,
+
+        /// The ID of the Olm session that was used to decrypt this event, or
+        /// None if this info was stored before we collected this data.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        session_id: Option<String>,
+
+        /// The number of messages that were decrypted with this Olm session,
+        /// including this one, or None if this info was stored before we
+        /// collected this data.
+        ///
+        /// This counter is maintained locally and increases with every message
+        /// decrypted with the session, it can be used to order the messages of
+        /// a session or to detect replayed messages.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        message_index: Option<u64>,
     },
 }
 
@@ -1191,6 +1224,14 @@ pub enum ToDeviceUnableToDecryptReason {
     /// trust requirement we were asked to provide.
     UnverifiedSenderDevice,
 
+    /// We refused to decrypt the message because the sender's device did not
+    /// match the trust requirement configured for this specific type of
+    /// to-device event.
+    UnverifiedSenderDeviceForEventType {
+        /// The type of the decrypted event.
+        event_type: String,
+    },
+
     /// We have no `OlmMachine`. This should not happen unless we forget to set
     /// things up by calling `OlmMachine::activate()`.
     NoOlmMachine,
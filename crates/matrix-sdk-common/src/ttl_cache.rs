@@ -49,7 +49,21 @@ where
 {
     /// Create a new, empty, [`TtlCache`].
     pub fn new() -> Self {
-        Self { items: Default::default(), lifetime: DEFAULT_LIFETIME }
+        Self::with_lifetime(DEFAULT_LIFETIME)
+    }
+
+    /// Create a new, empty, [`TtlCache`] whose items expire after the given
+    /// lifetime.
+    pub fn with_lifetime(lifetime: Duration) -> Self {
+        Self { items: Default::default(), lifetime }
+    }
+
+    /// Set the lifetime of the items inserted from now on.
+    ///
+    /// The items already in the cache keep the lifetime they were inserted
+    /// with.
+    pub fn set_lifetime(&mut self, lifetime: Duration) {
+        self.lifetime = lifetime;
     }
 
     /// Does the cache contain an non-expired item with the matching key.
@@ -47,6 +47,10 @@ use crate::{
 
 mod keys;
 
+/// The key under which the list of rooms excluded from the backup is stored in
+/// the crypto store.
+const EXCLUDED_ROOMS_STORE_KEY: &str = "backup_excluded_rooms";
+
 pub use keys::{DecodeError, DecryptionError, MegolmV1BackupKey};
 
 /// A state machine that handles backing up room keys.
@@ -134,6 +138,10 @@ impl SignatureState {
 impl BackupMachine {
     const BACKUP_BATCH_SIZE: usize = 100;
 
+    /// The number of room keys loaded at once when re-enabling the backup of
+    /// a room, see [`BackupMachine::remove_room_exclusion`].
+    const ROOM_KEY_BATCH_SIZE: usize = 1000;
+
     pub(crate) fn new(store: Store, backup_key: Option<MegolmV1BackupKey>) -> Self {
         Self {
             store,
@@ -448,6 +456,96 @@ impl BackupMachine {
         self.store.load_backup_keys().await
     }
 
+    /// Get the rooms whose room keys are never backed up, see
+    /// [`BackupMachine::exclude_room`].
+    pub async fn excluded_rooms(&self) -> Result<BTreeSet<OwnedRoomId>, CryptoStoreError> {
+        Ok(self.store.get_value(EXCLUDED_ROOMS_STORE_KEY).await?.unwrap_or_default())
+    }
+
+    /// Never back up the room keys of the given room.
+    ///
+    /// The room keys of excluded rooms are skipped when creating backup
+    /// requests, and marked as backed up so they aren't considered as pending
+    /// upload anymore, e.g. by [`BackupMachine::room_key_counts`]. Room keys
+    /// which were backed up before the room was excluded are not removed from
+    /// the backup.
+    ///
+    /// The exclusion is persisted in the crypto store, and can be removed with
+    /// [`BackupMachine::remove_room_exclusion`].
+    #[instrument(skip(self))]
+    pub async fn exclude_room(&self, room_id: &RoomId) -> Result<(), CryptoStoreError> {
+        // Holding the lock on the pending request makes sure that no request
+        // containing room keys of this room is created while the exclusion is being
+        // saved.
+        let mut pending_backup = self.pending_backup.write().await;
+
+        let mut excluded_rooms = self.excluded_rooms().await?;
+
+        if excluded_rooms.insert(room_id.to_owned()) {
+            self.store.set_value(EXCLUDED_ROOMS_STORE_KEY, &excluded_rooms).await?;
+            info!("Excluded the room from the key backup");
+        }
+
+        // Drop the pending request if it contains room keys of this room, it will be
+        // recreated without them.
+        if pending_backup.as_ref().is_some_and(|pending| pending.sessions.contains_key(room_id)) {
+            debug!("Discarding the pending backup request containing room keys of the room");
+            *pending_backup = None;
+        }
+
+        Ok(())
+    }
+
+    /// Back up the room keys of the given room again, after it was excluded
+    /// with [`BackupMachine::exclude_room`].
+    ///
+    /// All the room keys of the room are marked as needing to be backed up, so
+    /// that the ones which were skipped while the room was excluded are picked
+    /// up by the next backup requests.
+    #[instrument(skip(self))]
+    pub async fn remove_room_exclusion(&self, room_id: &RoomId) -> Result<(), CryptoStoreError> {
+        let _pending_backup = self.pending_backup.write().await;
+
+        let mut excluded_rooms = self.excluded_rooms().await?;
+
+        if !excluded_rooms.remove(room_id) {
+            return Ok(());
+        }
+
+        self.store.set_value(EXCLUDED_ROOMS_STORE_KEY, &excluded_rooms).await?;
+
+        // We don't know which room keys were skipped while the room was excluded,
+        // so reset the backup state of all the room keys of the room.
+        let mut after = None;
+        let mut reset_count = 0;
+
+        loop {
+            let sessions = self
+                .store
+                .get_inbound_group_sessions_batch(after.take(), Self::ROOM_KEY_BATCH_SIZE)
+                .await?;
+
+            let Some(last) = sessions.last() else { break };
+            after = Some((last.room_id().to_owned(), last.session_id().to_owned()));
+
+            let sessions: Vec<_> = sessions
+                .into_iter()
+                .filter(|session| session.room_id() == room_id && session.backed_up())
+                .collect();
+
+            for session in &sessions {
+                session.reset_backup_state();
+            }
+
+            reset_count += sessions.len();
+            self.store.save_inbound_group_sessions(&sessions).await?;
+        }
+
+        info!(reset_count, "Removed the exclusion of the room from the key backup");
+
+        Ok(())
+    }
+
     /// Encrypt a batch of room keys and return a request that needs to be sent
     /// out to backup the room keys.
     pub async fn backup(
@@ -529,13 +627,45 @@ impl BackupMachine {
             return Ok(None);
         };
 
-        let sessions =
-            self.store.inbound_group_sessions_for_backup(&version, Self::BACKUP_BATCH_SIZE).await?;
+        let excluded_rooms = self.excluded_rooms().await?;
 
-        if sessions.is_empty() {
-            trace!(?backup_key, "No room keys need to be backed up");
-            return Ok(None);
-        }
+        let sessions = loop {
+            let sessions = self
+                .store
+                .inbound_group_sessions_for_backup(&version, Self::BACKUP_BATCH_SIZE)
+                .await?;
+
+            if sessions.is_empty() {
+                trace!(?backup_key, "No room keys need to be backed up");
+                return Ok(None);
+            }
+
+            let (excluded, sessions): (Vec<_>, Vec<_>) = sessions
+                .into_iter()
+                .partition(|session| excluded_rooms.contains(session.room_id()));
+
+            // Mark the room keys of excluded rooms as backed up without uploading
+            // them, so they aren't returned as needing a backup anymore.
+            if !excluded.is_empty() {
+                let room_and_session_ids: Vec<_> = excluded
+                    .iter()
+                    .map(|session| (session.room_id(), session.session_id()))
+                    .collect();
+
+                debug!(
+                    skipped_count = excluded.len(),
+                    "Skipping the room keys of rooms excluded from the backup"
+                );
+
+                self.store
+                    .mark_inbound_group_sessions_as_backed_up(&version, &room_and_session_ids)
+                    .await?;
+            }
+
+            if !sessions.is_empty() {
+                break sessions;
+            }
+        };
 
         let key_count = sessions.len();
         let (backup, session_record) = Self::backup_keys(sessions, backup_key).await;
@@ -637,7 +767,7 @@ impl BackupMachine {
 
 #[cfg(test)]
 mod tests {
-    use std::collections::BTreeMap;
+    use std::collections::{BTreeMap, BTreeSet};
 
     use assert_matches2::assert_let;
     use matrix_sdk_test::async_test;
@@ -757,6 +887,75 @@ mod tests {
         backup_flow(machine).await
     }
 
+    #[async_test]
+    async fn test_excluded_rooms_are_not_backed_up() -> Result<(), OlmError> {
+        let machine = OlmMachine::new(alice_id(), alice_device_id()).await;
+        let backup_machine = machine.backup_machine();
+
+        machine.create_outbound_group_session_with_defaults_test_helper(room_id()).await?;
+        machine.create_outbound_group_session_with_defaults_test_helper(room_id2()).await?;
+
+        let decryption_key = BackupDecryptionKey::new().expect("Can't create new recovery key");
+        let backup_key = decryption_key.megolm_v1_public_key();
+        backup_key.set_version("1".to_owned());
+        backup_machine.enable_backup_v1(backup_key).await?;
+
+        // Exclude the second room, the exclusion is persisted.
+        backup_machine.exclude_room(room_id2()).await?;
+        assert_eq!(backup_machine.excluded_rooms().await?, BTreeSet::from([room_id2().to_owned()]));
+
+        // Only the room key of the first room is backed up.
+        let (request_id, request) =
+            backup_machine.backup().await?.expect("Created a backup request successfully");
+        assert_eq!(request.rooms.keys().collect::<Vec<_>>(), [room_id()]);
+        backup_machine.mark_request_as_sent(&request_id).await?;
+
+        // The room key of the excluded room isn't pending upload.
+        let counts = backup_machine.room_key_counts().await?;
+        assert_eq!(counts.total, 2);
+        assert_eq!(counts.backed_up, 2);
+        assert!(backup_machine.backup().await?.is_none());
+
+        // Once the exclusion is removed, the room key of the second room is backed up
+        // by the next request.
+        backup_machine.remove_room_exclusion(room_id2()).await?;
+        assert!(backup_machine.excluded_rooms().await?.is_empty());
+
+        let counts = backup_machine.room_key_counts().await?;
+        assert_eq!(counts.backed_up, 1);
+
+        let (request_id, request) =
+            backup_machine.backup().await?.expect("Created a backup request successfully");
+        assert_eq!(request.rooms.keys().collect::<Vec<_>>(), [room_id2()]);
+        backup_machine.mark_request_as_sent(&request_id).await?;
+
+        assert_eq!(backup_machine.room_key_counts().await?.backed_up, 2);
+
+        Ok(())
+    }
+
+    #[async_test]
+    async fn test_excluding_a_room_discards_the_pending_request() -> Result<(), OlmError> {
+        let machine = OlmMachine::new(alice_id(), alice_device_id()).await;
+        let backup_machine = machine.backup_machine();
+
+        machine.create_outbound_group_session_with_defaults_test_helper(room_id()).await?;
+
+        let decryption_key = BackupDecryptionKey::new().expect("Can't create new recovery key");
+        let backup_key = decryption_key.megolm_v1_public_key();
+        backup_key.set_version("1".to_owned());
+        backup_machine.enable_backup_v1(backup_key).await?;
+
+        assert!(backup_machine.backup().await?.is_some());
+
+        // The pending request contains the room key of the room, so it's discarded,
+        // and no new request is created.
+        backup_machine.exclude_room(room_id()).await?;
+        assert!(backup_machine.backup().await?.is_none());
+
+        Ok(())
+    }
+
     #[async_test]
     async fn test_verify_auth_data() -> Result<(), OlmError> {
         let machine = OlmMachine::new(alice_id(), alice_device_id()).await;
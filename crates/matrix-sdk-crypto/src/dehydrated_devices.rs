@@ -36,49 +36,73 @@
 //!
 //! After the rehydration process is completed, the user's real device should
 //! create a new dehydrated device.
+//!
+//! Since the rehydrated device might need to download and decrypt a lot of
+//! to-device events, the progress of the rehydration is persisted, allowing to
+//! resume it with [`RehydratedDevice::saved_progress()`].
 
-// TODO: Once a device has been rehydrated it might need to download and decrypt
-// a lot of to-device events. This process might take some time and we should
-// support resuming it.
-
-use std::sync::Arc;
+use std::{mem, sync::Arc};
 
+use eyeball::SharedObservable;
+use matrix_sdk_common::deserialized_responses::ProcessedToDeviceEvent;
 use ruma::{
     api::client::dehydrated_device::{put_dehydrated_device, DehydratedDeviceData},
     assign,
     events::AnyToDeviceEvent,
     serde::Raw,
-    DeviceId,
+    DeviceId, MilliSecondsSinceUnixEpoch, OwnedDeviceId,
 };
 use thiserror::Error;
-use tracing::{instrument, trace};
-use vodozemac::{DehydratedDeviceError, LibolmPickleError};
+use tracing::{instrument, trace, warn};
+use vodozemac::{
+    megolm::SessionOrdering, Base64DecodeError, DehydratedDeviceError, LibolmPickleError,
+};
 
 use crate::{
+    olm::IdentityKeys,
     store::{
         types::{Changes, DehydratedDeviceKey, RoomKeyInfo},
         CryptoStoreWrapper, MemoryStore, Store,
     },
     verification::VerificationMachine,
-    Account, CryptoStoreError, DecryptionSettings, EncryptionSyncChanges, OlmError, OlmMachine,
-    SignatureError,
+    Account, CryptoStoreError, DecryptionSettings, DeviceData, EncryptionSyncChanges, OlmError,
+    OlmMachine, SignatureError,
 };
 
+/// The name of the secret holding the pickle key of the dehydrated devices, as
+/// defined in [MSC3814].
+///
+/// The pickle key is shared between our devices using secret storage and
+/// secret gossiping, encoded as unpadded base64.
+///
+/// [MSC3814]: https://github.com/matrix-org/matrix-spec-proposals/pull/3814
+pub const DEHYDRATED_DEVICE_PICKLE_KEY_SECRET_NAME: &str = "org.matrix.msc3814";
+
 /// Error type for device dehydration issues.
 #[derive(Debug, Error)]
 pub enum DehydrationError {
     /// The legacy dehydrated device could not be unpickled.
     #[error(transparent)]
-    LegacyPickle(#[from] LibolmPickleError),
+    LegacyPickle(LibolmPickleError),
 
     /// The dehydrated device could not be unpickled.
     #[error(transparent)]
-    Pickle(#[from] DehydratedDeviceError),
+    Pickle(DehydratedDeviceError),
+
+    /// The dehydrated device could not be decrypted with the given pickle key,
+    /// most likely because the key is not the one that was used to dehydrate
+    /// the device.
+    #[error("The dehydrated device could not be decrypted, the pickle key is incorrect")]
+    IncorrectPickleKey,
 
     /// The pickle key has an invalid length
     #[error("The pickle key has an invalid length, expected 32 bytes, got {0}")]
     PickleKeyLength(usize),
 
+    /// The exported pickle key isn't valid base64.
+    #[error(transparent)]
+    PickleKeyBase64(#[from] Base64DecodeError),
+
     /// The dehydrated device could not be signed by our user identity,
     /// we're missing the self-signing key.
     #[error("The self-signing key is missing, can't create a dehydrated device")]
@@ -88,24 +112,96 @@ pub enum DehydrationError {
     #[error(transparent)]
     Json(#[from] serde_json::Error),
 
+    /// The dehydrated device data uses an algorithm we don't support, most
+    /// likely because the device was created by a newer client.
+    #[error("The dehydrated device uses an unsupported algorithm: {0}")]
+    UnsupportedAlgorithm(String),
+
     /// The store ran into an error.
     #[error(transparent)]
     Store(#[from] CryptoStoreError),
+
+    /// The device ID given for a new dehydrated device is the one of our own
+    /// device.
+    #[error("The dehydrated device can't use the ID of our own device: {0}")]
+    DeviceIdCollision(OwnedDeviceId),
+
+    /// The identity keys of the rehydrated device don't match the ones of a
+    /// device of our own user signed by our self-signing key.
+    #[error("The dehydrated device isn't signed by our cross-signing identity")]
+    DeviceSignatureMismatch,
+}
+
+impl From<DehydratedDeviceError> for DehydrationError {
+    fn from(error: DehydratedDeviceError) -> Self {
+        match error {
+            // The authenticated encryption of the pickle doesn't let us distinguish a wrong
+            // key from a tampered ciphertext, the former is far more likely though.
+            DehydratedDeviceError::Decryption(_) => Self::IncorrectPickleKey,
+            DehydratedDeviceError::LibolmPickle(error) => error.into(),
+            error => Self::Pickle(error),
+        }
+    }
+}
+
+impl From<LibolmPickleError> for DehydrationError {
+    fn from(error: LibolmPickleError) -> Self {
+        match error {
+            LibolmPickleError::Decryption(_) => Self::IncorrectPickleKey,
+            error => Self::LegacyPickle(error),
+        }
+    }
 }
 
 /// Struct collecting methods to create and rehydrate dehydrated devices.
 #[derive(Debug)]
 pub struct DehydratedDevices {
     pub(crate) inner: OlmMachine,
+    pub(crate) skip_cross_signing_verification: bool,
 }
 
 impl DehydratedDevices {
+    /// Don't check that rehydrated devices are signed by our cross-signing
+    /// identity.
+    ///
+    /// This should only be used to rehydrate legacy devices which were
+    /// dehydrated before the user had set up cross-signing.
+    pub fn skip_cross_signing_verification(mut self) -> Self {
+        self.skip_cross_signing_verification = true;
+        self
+    }
+
     /// Create a new [`DehydratedDevice`] which can be uploaded to the server.
+    ///
+    /// The ID of the device is derived from its identity keys.
     pub async fn create(&self) -> Result<DehydratedDevice, DehydrationError> {
+        self.create_helper(Account::new_dehydrated(self.inner.user_id())).await
+    }
+
+    /// Create a new [`DehydratedDevice`] with the given device ID, which can be
+    /// uploaded to the server.
+    ///
+    /// This is mostly useful for tests and migrations, [`Self::create()`]
+    /// should be preferred otherwise.
+    ///
+    /// Returns a [`DehydrationError::DeviceIdCollision`] error if the given
+    /// device ID is the one of our own device.
+    pub async fn create_with_device_id(
+        &self,
+        device_id: &DeviceId,
+    ) -> Result<DehydratedDevice, DehydrationError> {
+        if device_id == self.inner.device_id() {
+            return Err(DehydrationError::DeviceIdCollision(device_id.to_owned()));
+        }
+
+        self.create_helper(Account::dehydrated_with_device_id(self.inner.user_id(), device_id))
+            .await
+    }
+
+    async fn create_helper(&self, account: Account) -> Result<DehydratedDevice, DehydrationError> {
         let user_id = self.inner.user_id();
         let user_identity = self.inner.store().private_identity();
 
-        let account = Account::new_dehydrated(user_id);
         let store =
             Arc::new(CryptoStoreWrapper::new(user_id, account.device_id(), MemoryStore::new()));
 
@@ -133,6 +229,12 @@ impl DehydratedDevices {
     /// For more info see the example for the
     /// [`RehydratedDevice::receive_events()`] method.
     ///
+    /// The identity keys of the rehydrated device are checked against the
+    /// device keys we know about for our own user, which must be signed by our
+    /// self-signing key. This means that the device keys of our own user
+    /// should be up to date before calling this method. The check can be
+    /// disabled with [`DehydratedDevices::skip_cross_signing_verification()`].
+    ///
     /// # Arguments
     ///
     /// * `pickle_key` - The encryption key that was used to encrypt the private
@@ -151,7 +253,60 @@ impl DehydratedDevices {
         let rehydrated =
             self.inner.rehydrate(pickle_key.inner.as_ref(), device_id, device_data).await?;
 
-        Ok(RehydratedDevice { rehydrated, original: self.inner.to_owned() })
+        let device = self.inner.store().get_device_data(self.inner.user_id(), device_id).await?;
+
+        if !self.skip_cross_signing_verification {
+            self.verify_rehydrated_device(&rehydrated, device.as_ref()).await?;
+        }
+
+        Ok(RehydratedDevice {
+            rehydrated,
+            original: self.inner.to_owned(),
+            first_seen_time: device.map(|device| device.first_time_seen_ts()),
+            progress: Default::default(),
+        })
+    }
+
+    /// Check that the identity keys of the rehydrated device belong to a device
+    /// of our own user which was signed by our self-signing key.
+    async fn verify_rehydrated_device(
+        &self,
+        rehydrated: &OlmMachine,
+        device: Option<&DeviceData>,
+    ) -> Result<(), DehydrationError> {
+        let Some(device) = device else {
+            warn!(
+                device_id = ?rehydrated.device_id(),
+                "The device keys of the dehydrated device are unknown"
+            );
+            return Err(DehydrationError::DeviceSignatureMismatch);
+        };
+
+        let identity_keys = rehydrated.identity_keys();
+
+        if device.curve25519_key() != Some(identity_keys.curve25519)
+            || device.ed25519_key() != Some(identity_keys.ed25519)
+        {
+            warn!(
+                device_id = ?rehydrated.device_id(),
+                "The identity keys of the dehydrated device don't match its device keys"
+            );
+            return Err(DehydrationError::DeviceSignatureMismatch);
+        }
+
+        let self_signing_key =
+            self.inner.store().private_identity().lock().await.self_signing_public_key().await;
+
+        match self_signing_key {
+            Some(self_signing_key) if self_signing_key.verify_device(device).is_ok() => Ok(()),
+            _ => {
+                warn!(
+                    device_id = ?rehydrated.device_id(),
+                    "The dehydrated device isn't signed by our self-signing key"
+                );
+                Err(DehydrationError::DeviceSignatureMismatch)
+            }
+        }
     }
 
     /// Get the cached dehydrated device pickle key if any.
@@ -197,9 +352,98 @@ impl DehydratedDevices {
 pub struct RehydratedDevice {
     rehydrated: OlmMachine,
     original: OlmMachine,
+    first_seen_time: Option<MilliSecondsSinceUnixEpoch>,
+    progress: SharedObservable<RehydrationProgress>,
+}
+
+/// The progress of the rehydration of a dehydrated device, cumulated over all
+/// the calls to [`RehydratedDevice::receive_events()`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RehydrationProgress {
+    /// The number of to-device events that were processed.
+    pub processed_events: usize,
+
+    /// The number of to-device events that couldn't be decrypted.
+    pub undecryptable_events: usize,
+
+    /// The number of room keys that were imported.
+    pub imported_room_keys: usize,
+
+    /// The number of secrets that were imported.
+    pub imported_secrets: usize,
+
+    /// The number of batches of to-device events that were processed.
+    pub completed_batches: usize,
+}
+
+/// The result of feeding a batch of to-device events into a
+/// [`RehydratedDevice`].
+#[derive(Debug, Default)]
+pub struct RehydrationBatch {
+    /// The room keys that were imported into our own [`OlmMachine`].
+    pub room_keys: Vec<RoomKeyInfo>,
+
+    /// The number of secrets, sent in response to our secret requests, that
+    /// were imported into our own [`OlmMachine`].
+    pub imported_secrets: usize,
 }
 
 impl RehydratedDevice {
+    /// The prefix of the key under which the rehydration progress is stored,
+    /// followed by the ID of the dehydrated device.
+    const PROGRESS_STORE_KEY_PREFIX: &'static str = "dehydrated_device_rehydration_progress-";
+
+    fn progress_store_key(&self) -> String {
+        format!("{}{}", Self::PROGRESS_STORE_KEY_PREFIX, self.rehydrated.device_id())
+    }
+
+    /// The unique ID of the dehydrated device.
+    pub fn device_id(&self) -> &DeviceId {
+        self.rehydrated.device_id()
+    }
+
+    /// The public parts of the identity keys of the dehydrated device.
+    pub fn identity_keys(&self) -> IdentityKeys {
+        self.rehydrated.identity_keys()
+    }
+
+    /// The time at which our own device first saw the device keys of the
+    /// dehydrated device, according to the local clock.
+    ///
+    /// The creation time of a dehydrated device isn't part of its data, but
+    /// this is usually close to it. Returns `None` if the device keys of the
+    /// dehydrated device are unknown.
+    pub fn first_seen_time(&self) -> Option<MilliSecondsSinceUnixEpoch> {
+        self.first_seen_time
+    }
+
+    /// Report the progress of the rehydration in the given observable, to be
+    /// able to show it while the events are received.
+    ///
+    /// The current progress is written to the observable right away.
+    pub fn with_progress_observable(
+        mut self,
+        progress: SharedObservable<RehydrationProgress>,
+    ) -> Self {
+        progress.set(self.progress.get());
+        self.progress = progress;
+        self
+    }
+
+    /// Get the current progress of the rehydration.
+    pub fn progress(&self) -> RehydrationProgress {
+        self.progress.get()
+    }
+
+    /// Get the `next_batch` token of the last batch of to-device events
+    /// received by this device, if the rehydration was interrupted.
+    ///
+    /// If this returns a token, the to-device events should be downloaded
+    /// from this token instead of from the start.
+    pub async fn saved_progress(&self) -> Result<Option<String>, CryptoStoreError> {
+        self.original.store().get_value(&self.progress_store_key()).await
+    }
+
     /// Feed to-device events the device was supposed to receive into the
     /// [`RehydratedDevice`].
     ///
@@ -207,10 +451,22 @@ impl RehydratedDevice {
     /// room keys, the rehydrated device will pass these room keys into our
     /// own [`OlmMachine`] which will persist them and make the room keys
     /// available for use using the usual
-    /// [`OlmMachine::decrypt_room_event()`] method.
+    /// [`OlmMachine::decrypt_room_event()`] method. The room keys we already
+    /// have are not returned again, so replayed events are harmless.
+    ///
+    /// The secrets received by the rehydrated device are passed to our own
+    /// [`OlmMachine`] as well, which handles them like secrets it received
+    /// itself: they are only imported if they match one of our secret requests
+    /// and were sent by one of our own verified devices.
+    ///
+    /// The given `next_batch` token is persisted once the events have been
+    /// handled, and is returned by [`RehydratedDevice::saved_progress()`] to
+    /// resume the rehydration if it is interrupted.
     ///
     /// Once the homeserver returns a response without any to-device events, we
     /// can safely delete the current dehydrated device and create a new one.
+    /// Feeding this empty response into the [`RehydratedDevice`] forgets the
+    /// saved progress.
     ///
     /// # Examples
     ///
@@ -246,22 +502,32 @@ impl RehydratedDevice {
     ///     .rehydrate(&pickle_key, &device_id, response.device_data)
     ///     .await?;
     ///
-    /// let mut since_token = None;
+    /// // Resume from the last batch of events, if a previous rehydration was
+    /// // interrupted.
+    /// let mut since_token = rehydrated.saved_progress().await?;
     /// let mut imported_room_keys = 0;
     /// let decryption_settings = DecryptionSettings {
-    ///     sender_device_trust_requirement: TrustRequirement::Untrusted
+    ///     sender_device_trust_requirement: TrustRequirement::Untrusted,
+    ///     to_device_trust_requirements: Default::default(),
+    ///     unwedging_policy: Default::default(),
     /// };
     ///
     /// loop {
     ///     let response =
-    ///         get_events(&device_id, since_token).await?;
+    ///         get_events(&device_id, since_token.as_deref()).await?;
+    ///     let is_done = response.events.is_empty();
+    ///
+    ///     imported_room_keys += rehydrated
+    ///         .receive_events(response.events, response.next_batch.as_deref(), &decryption_settings)
+    ///         .await?
+    ///         .room_keys
+    ///         .len();
     ///
-    ///     if response.events.is_empty() {
+    ///     if is_done {
     ///         break;
     ///     }
     ///
-    ///     since_token = response.next_batch.as_deref();
-    ///     imported_room_keys += rehydrated.receive_events(response.events, &decryption_settings).await?.len();
+    ///     since_token = response.next_batch;
     /// }
     ///
     /// println!("Successfully imported {imported_room_keys} from the dehydrated device.");
@@ -279,10 +545,17 @@ impl RehydratedDevice {
     pub async fn receive_events(
         &self,
         events: Vec<Raw<AnyToDeviceEvent>>,
+        next_batch: Option<&str>,
         decryption_settings: &DecryptionSettings,
-    ) -> Result<Vec<RoomKeyInfo>, OlmError> {
+    ) -> Result<RehydrationBatch, OlmError> {
         trace!("Receiving events for a rehydrated Device");
 
+        if events.is_empty() {
+            trace!("All the events have been received, forgetting the rehydration progress");
+            self.original.store().remove_custom_value(&self.progress_store_key()).await?;
+            return Ok(RehydrationBatch::default());
+        }
+
         let sync_changes = EncryptionSyncChanges {
             to_device_events: events,
             next_batch_token: None,
@@ -295,23 +568,106 @@ impl RehydratedDevice {
         // encrypted to-device events and fetch out the room keys.
         let mut rehydrated_transaction = self.rehydrated.store().transaction().await;
 
-        let (_, changes) = self
+        let (processed_events, changes) = self
             .rehydrated
             .preprocess_sync_changes(&mut rehydrated_transaction, sync_changes, decryption_settings)
             .await?;
 
-        // Now take the room keys and persist them in our original `OlmMachine`.
-        let room_keys = &changes.inbound_group_sessions;
+        // Now take the room keys and persist them in our original `OlmMachine`,
+        // skipping the ones we already have, which might come from replayed
+        // events. Overwriting those would also clobber any `SenderData` which
+        // was upgraded since we first received them.
+        let mut room_keys = Vec::new();
+
+        for room_key in &changes.inbound_group_sessions {
+            let is_duplicate = room_keys.iter().any(|known| {
+                known.room_id() == room_key.room_id() && known.session_id() == room_key.session_id()
+            });
+
+            if is_duplicate {
+                continue;
+            }
+
+            if self.original.store().compare_group_session(room_key).await?
+                == SessionOrdering::Better
+            {
+                room_keys.push(room_key.clone());
+            } else {
+                trace!(
+                    room_id = ?room_key.room_id(),
+                    session_id = room_key.session_id(),
+                    "Skipping a room key from the rehydrated device, we already have it"
+                );
+            }
+        }
+
         let updates = room_keys.iter().map(Into::into).collect();
 
         trace!(room_key_count = room_keys.len(), "Collected room keys from the rehydrated device");
 
-        self.original.store().save_inbound_group_sessions(room_keys).await?;
+        self.original.store().save_inbound_group_sessions(&room_keys).await?;
 
         rehydrated_transaction.commit().await?;
         self.rehydrated.store().save_changes(changes).await?;
 
-        Ok(updates)
+        let imported_secrets = self.import_secrets().await?;
+
+        if let Some(next_batch) = next_batch {
+            self.original.store().set_value(&self.progress_store_key(), &next_batch).await?;
+        }
+
+        let undecryptable_events = processed_events
+            .iter()
+            .filter(|event| matches!(event, ProcessedToDeviceEvent::UnableToDecrypt { .. }))
+            .count();
+
+        self.progress.update(|progress| {
+            progress.processed_events += processed_events.len();
+            progress.undecryptable_events += undecryptable_events;
+            progress.imported_room_keys += room_keys.len();
+            progress.imported_secrets += imported_secrets;
+            progress.completed_batches += 1;
+        });
+
+        Ok(RehydrationBatch { room_keys: updates, imported_secrets })
+    }
+
+    /// Pass the secrets received by the rehydrated device to our own
+    /// [`OlmMachine`], returns the number of secrets that were imported.
+    async fn import_secrets(&self) -> Result<usize, OlmError> {
+        let secrets = self
+            .rehydrated
+            .inner
+            .rehydrated_secrets
+            .as_ref()
+            .map(|secrets| mem::take(&mut *secrets.lock()))
+            .unwrap_or_default();
+
+        if secrets.is_empty() {
+            return Ok(0);
+        }
+
+        let cache = self.original.store().cache().await?;
+        let mut changes = Changes::default();
+        let mut imported_secrets = 0;
+
+        for (sender_key, event) in &secrets {
+            if self
+                .original
+                .inner
+                .key_request_machine
+                .receive_rehydrated_secret_event(&cache, *sender_key, event, &mut changes)
+                .await?
+            {
+                imported_secrets += 1;
+            }
+        }
+
+        trace!(imported_secrets, "Passed the secrets of the rehydrated device to our own device");
+
+        self.original.store().save_changes(changes).await?;
+
+        Ok(imported_secrets)
     }
 }
 
@@ -325,6 +681,22 @@ pub struct DehydratedDevice {
 }
 
 impl DehydratedDevice {
+    /// The unique ID of the dehydrated device.
+    pub fn device_id(&self) -> &DeviceId {
+        &self.store.static_account().device_id
+    }
+
+    /// The public parts of the identity keys of the dehydrated device.
+    pub fn identity_keys(&self) -> IdentityKeys {
+        self.store.static_account().identity_keys()
+    }
+
+    /// The time at which the dehydrated device was created, according to the
+    /// local clock.
+    pub fn creation_time(&self) -> MilliSecondsSinceUnixEpoch {
+        self.store.static_account().creation_local_time()
+    }
+
     /// Get the request to upload the dehydrated device.
     ///
     /// # Arguments
@@ -405,6 +777,8 @@ impl DehydratedDevice {
 mod tests {
     use std::{collections::BTreeMap, iter};
 
+    use assert_matches::assert_matches;
+    use eyeball::SharedObservable;
     use js_option::JsOption;
     use matrix_sdk_test::async_test;
     use ruma::{
@@ -412,23 +786,31 @@ mod tests {
             dehydrated_device::put_dehydrated_device,
             keys::get_keys::v3::Response as KeysQueryResponse,
         },
-        assign,
+        assign, device_id,
         encryption::DeviceKeys,
-        events::AnyToDeviceEvent,
+        events::{secret::request::SecretName, AnyToDeviceEvent},
         room_id,
         serde::Raw,
-        user_id, DeviceId, RoomId, TransactionId, UserId,
+        user_id, DeviceId, MilliSecondsSinceUnixEpoch, RoomId, TransactionId, UserId,
     };
+    use serde_json::json;
+    use vodozemac::{base64_encode, Ed25519PublicKey};
 
     use crate::{
-        dehydrated_devices::DehydratedDevice,
+        dehydrated_devices::{
+            DehydratedDevice, DehydrationError, RehydrationProgress,
+            DEHYDRATED_DEVICE_PICKLE_KEY_SECRET_NAME,
+        },
         machine::{
             test_helpers::{create_session, get_prepared_machine_test_helper},
             tests::to_device_requests_to_content,
         },
-        olm::OutboundGroupSession,
+        olm::{OutboundGroupSession, SenderData, SenderDataType},
         store::types::DehydratedDeviceKey,
-        types::{events::ToDeviceEvent, DeviceKeys as DeviceKeysType},
+        types::{
+            events::{secret_send::SecretSendContent, EventType, ToDeviceEvent},
+            DeviceKeys as DeviceKeysType,
+        },
         utilities::json_convert,
         DecryptionSettings, EncryptionSettings, OlmMachine, TrustRequirement,
     };
@@ -526,6 +908,48 @@ mod tests {
         );
     }
 
+    #[async_test]
+    async fn test_dehydrated_device_metadata() {
+        let olm_machine = get_olm_machine().await;
+        let dehydrated_devices = olm_machine.dehydrated_devices();
+
+        let dehydrated_device = dehydrated_devices.create().await.unwrap();
+        assert!(dehydrated_device.creation_time() <= MilliSecondsSinceUnixEpoch::now());
+        assert_eq!(
+            dehydrated_device.device_id().as_str(),
+            dehydrated_device.identity_keys().curve25519.to_base64()
+        );
+
+        // A device ID can be chosen, as long as it's not the one of our own device.
+        let dehydrated_device =
+            dehydrated_devices.create_with_device_id(device_id!("DEHYDRATED")).await.unwrap();
+        assert_eq!(dehydrated_device.device_id(), device_id!("DEHYDRATED"));
+
+        assert_matches!(
+            dehydrated_devices.create_with_device_id(olm_machine.device_id()).await,
+            Err(DehydrationError::DeviceIdCollision(device_id)) => {
+                assert_eq!(device_id, olm_machine.device_id().to_owned());
+            }
+        );
+
+        let identity_keys = dehydrated_device.identity_keys();
+        let request =
+            dehydrated_device.keys_for_upload("Foo".to_owned(), &pickle_key()).await.unwrap();
+        assert_eq!(request.device_id, device_id!("DEHYDRATED"));
+
+        // The same metadata is available once the device is rehydrated.
+        receive_device_keys(&olm_machine, user_id(), &request.device_id, request.device_keys).await;
+
+        let rehydrated = dehydrated_devices
+            .rehydrate(&pickle_key(), &request.device_id, request.device_data)
+            .await
+            .unwrap();
+
+        assert_eq!(rehydrated.device_id(), device_id!("DEHYDRATED"));
+        assert_eq!(rehydrated.identity_keys(), identity_keys);
+        assert!(rehydrated.first_seen_time().is_some());
+    }
+
     #[async_test]
     async fn test_dehydrated_device_rehydration() {
         let room_id = room_id!("!test:example.org");
@@ -568,6 +992,8 @@ mod tests {
         // Rehydrate the device.
         let rehydrated = bob
             .dehydrated_devices()
+            // Bob has a different cross-signing identity than Alice.
+            .skip_cross_signing_verification()
             .rehydrate(&pickle_key(), &request.device_id, request.device_data)
             .await
             .expect("We should be able to rehydrate the device");
@@ -575,16 +1001,19 @@ mod tests {
         assert_eq!(rehydrated.rehydrated.device_id(), request.device_id);
         assert_eq!(rehydrated.original.device_id(), alice.device_id());
 
-        let decryption_settings =
-            DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+        let decryption_settings = DecryptionSettings {
+            sender_device_trust_requirement: TrustRequirement::Untrusted,
+            to_device_trust_requirements: Default::default(),
+            unwedging_policy: Default::default(),
+        };
 
         // Push the to-device event containing the room key into the rehydrated device.
         let ret = rehydrated
-            .receive_events(vec![event], &decryption_settings)
+            .receive_events(vec![event], None, &decryption_settings)
             .await
             .expect("We should be able to push to-device events into the rehydrated device");
 
-        assert_eq!(ret.len(), 1, "The rehydrated device should have imported a room key");
+        assert_eq!(ret.room_keys.len(), 1, "The rehydrated device should have imported a room key");
 
         // The `OlmMachine` now does know about the room key since the rehydrated device
         // shared it with us.
@@ -602,6 +1031,297 @@ mod tests {
         );
     }
 
+    #[async_test]
+    async fn test_dehydrated_device_rehydration_ignores_unrequested_secrets() {
+        let alice = get_olm_machine().await;
+
+        let dehydrated_device = alice.dehydrated_devices().create().await.unwrap();
+
+        let mut request = dehydrated_device
+            .keys_for_upload("Foo".to_owned(), &pickle_key())
+            .await
+            .expect("We should be able to create a request to upload a dehydrated device");
+
+        let (key_id, one_time_key) = request
+            .one_time_keys
+            .pop_first()
+            .expect("The dehydrated device creation request should contain a one-time key");
+
+        receive_device_keys(&alice, user_id(), &request.device_id, request.device_keys).await;
+        create_session(&alice, user_id(), &request.device_id, key_id, one_time_key).await;
+
+        // Send a secret to the dehydrated device, which no one has requested.
+        let device = alice
+            .get_device(user_id(), &request.device_id, None)
+            .await
+            .unwrap()
+            .expect("We should know about the dehydrated device");
+        let content = SecretSendContent::new(TransactionId::new(), "It's a secret".to_owned());
+        let event_type = content.event_type().to_owned();
+        let (_, content) = device.encrypt(&event_type, content).await.unwrap();
+
+        let event = ToDeviceEvent::new(user_id().to_owned(), content.deserialize().unwrap());
+        let event: Raw<AnyToDeviceEvent> = json_convert(&event).unwrap();
+
+        let bob = get_olm_machine().await;
+        let rehydrated = bob
+            .dehydrated_devices()
+            // Bob has a different cross-signing identity than Alice.
+            .skip_cross_signing_verification()
+            .rehydrate(&pickle_key(), &request.device_id, request.device_data)
+            .await
+            .unwrap();
+
+        let decryption_settings = DecryptionSettings {
+            sender_device_trust_requirement: TrustRequirement::Untrusted,
+            to_device_trust_requirements: Default::default(),
+            unwedging_policy: Default::default(),
+        };
+
+        let ret = rehydrated.receive_events(vec![event], None, &decryption_settings).await.unwrap();
+
+        // The secret was decrypted by the rehydrated device, but it doesn't match any
+        // of our secret requests, so it wasn't imported.
+        assert!(ret.room_keys.is_empty());
+        assert_eq!(ret.imported_secrets, 0);
+        assert_eq!(rehydrated.progress().processed_events, 1);
+        assert_eq!(rehydrated.progress().undecryptable_events, 0);
+        assert_eq!(rehydrated.progress().imported_secrets, 0);
+        assert!(rehydrated.rehydrated.inner.rehydrated_secrets.as_ref().unwrap().lock().is_empty());
+    }
+
+    #[async_test]
+    async fn test_dehydrated_device_rehydration_progress() {
+        let room_id = room_id!("!test:example.org");
+        let alice = get_olm_machine().await;
+
+        let dehydrated_device = alice.dehydrated_devices().create().await.unwrap();
+
+        let mut request = dehydrated_device
+            .keys_for_upload("Foo".to_owned(), &pickle_key())
+            .await
+            .expect("We should be able to create a request to upload a dehydrated device");
+
+        let (key_id, one_time_key) = request
+            .one_time_keys
+            .pop_first()
+            .expect("The dehydrated device creation request should contain a one-time key");
+
+        receive_device_keys(&alice, user_id(), &request.device_id, request.device_keys).await;
+        create_session(&alice, user_id(), &request.device_id, key_id, one_time_key).await;
+
+        let (event, _) = send_room_key(&alice, room_id, user_id()).await;
+
+        let bob = get_olm_machine().await;
+        let decryption_settings = DecryptionSettings {
+            sender_device_trust_requirement: TrustRequirement::Untrusted,
+            to_device_trust_requirements: Default::default(),
+            unwedging_policy: Default::default(),
+        };
+
+        let rehydrated = bob
+            .dehydrated_devices()
+            // Bob has a different cross-signing identity than Alice.
+            .skip_cross_signing_verification()
+            .rehydrate(&pickle_key(), &request.device_id, request.device_data.clone())
+            .await
+            .unwrap();
+
+        // There is no progress initially.
+        assert_eq!(rehydrated.saved_progress().await.unwrap(), None);
+        assert_eq!(rehydrated.progress(), RehydrationProgress::default());
+
+        let ret = rehydrated
+            .receive_events(vec![event.clone()], Some("first_batch"), &decryption_settings)
+            .await
+            .unwrap();
+        assert_eq!(ret.room_keys.len(), 1);
+        assert_eq!(
+            rehydrated.progress(),
+            RehydrationProgress {
+                processed_events: 1,
+                undecryptable_events: 0,
+                imported_room_keys: 1,
+                imported_secrets: 0,
+                completed_batches: 1,
+            }
+        );
+
+        // The progress is saved.
+        assert_eq!(rehydrated.saved_progress().await.unwrap().as_deref(), Some("first_batch"));
+
+        // The rehydration is interrupted and started again, the progress is still
+        // there.
+        let progress = SharedObservable::new(RehydrationProgress::default());
+        let rehydrated = bob
+            .dehydrated_devices()
+            // Bob has a different cross-signing identity than Alice.
+            .skip_cross_signing_verification()
+            .rehydrate(&pickle_key(), &request.device_id, request.device_data)
+            .await
+            .unwrap()
+            .with_progress_observable(progress.clone());
+        assert_eq!(rehydrated.saved_progress().await.unwrap().as_deref(), Some("first_batch"));
+
+        // Replaying the same event doesn't return the room key again.
+        let ret = rehydrated
+            .receive_events(vec![event.clone()], Some("second_batch"), &decryption_settings)
+            .await
+            .unwrap();
+        assert!(ret.room_keys.is_empty());
+        assert_eq!(rehydrated.saved_progress().await.unwrap().as_deref(), Some("second_batch"));
+
+        // The counts are cumulated over the calls.
+        rehydrated
+            .receive_events(vec![event], Some("third_batch"), &decryption_settings)
+            .await
+            .unwrap();
+
+        let current_progress = progress.get();
+        assert_eq!(current_progress.processed_events, 2);
+        assert_eq!(current_progress.imported_room_keys, 0);
+        assert_eq!(current_progress.completed_batches, 2);
+
+        // Once all the events were received, the progress is forgotten.
+        let ret = rehydrated.receive_events(Vec::new(), None, &decryption_settings).await.unwrap();
+        assert!(ret.room_keys.is_empty());
+        assert_eq!(rehydrated.saved_progress().await.unwrap(), None);
+    }
+
+    #[async_test]
+    async fn test_dehydrated_device_rehydration_skips_known_room_keys() {
+        let room_id = room_id!("!test:example.org");
+        let alice = get_olm_machine().await;
+
+        let dehydrated_device = alice.dehydrated_devices().create().await.unwrap();
+
+        let mut request = dehydrated_device
+            .keys_for_upload("Foo".to_owned(), &pickle_key())
+            .await
+            .expect("We should be able to create a request to upload a dehydrated device");
+
+        let (key_id, one_time_key) = request
+            .one_time_keys
+            .pop_first()
+            .expect("The dehydrated device creation request should contain a one-time key");
+
+        receive_device_keys(&alice, user_id(), &request.device_id, request.device_keys).await;
+        create_session(&alice, user_id(), &request.device_id, key_id, one_time_key).await;
+
+        let (event, group_session) = send_room_key(&alice, room_id, user_id()).await;
+
+        let bob = get_olm_machine().await;
+        let decryption_settings = DecryptionSettings {
+            sender_device_trust_requirement: TrustRequirement::Untrusted,
+            to_device_trust_requirements: Default::default(),
+            unwedging_policy: Default::default(),
+        };
+
+        let rehydrated = bob
+            .dehydrated_devices()
+            // Bob has a different cross-signing identity than Alice.
+            .skip_cross_signing_verification()
+            .rehydrate(&pickle_key(), &request.device_id, request.device_data.clone())
+            .await
+            .unwrap();
+
+        let ret = rehydrated
+            .receive_events(vec![event.clone()], None, &decryption_settings)
+            .await
+            .unwrap();
+        assert_eq!(ret.room_keys.len(), 1);
+
+        // The sender of the room key gets verified in the meantime.
+        let mut room_key = bob
+            .store()
+            .get_inbound_group_session(room_id, group_session.session_id())
+            .await
+            .unwrap()
+            .unwrap();
+        room_key.sender_data = SenderData::sender_verified(
+            user_id(),
+            alice.device_id(),
+            Ed25519PublicKey::from_base64("2/5LWJMow5zhJqakV88SIc7q/1pa8fmkfgAzx72w9G4").unwrap(),
+        );
+        bob.store().save_inbound_group_sessions(&[room_key]).await.unwrap();
+
+        // The same dehydrated device is rehydrated again, e.g. after a crash, and the
+        // same batch of events is received again.
+        let rehydrated = bob
+            .dehydrated_devices()
+            .skip_cross_signing_verification()
+            .rehydrate(&pickle_key(), &request.device_id, request.device_data)
+            .await
+            .unwrap();
+
+        let ret = rehydrated.receive_events(vec![event], None, &decryption_settings).await.unwrap();
+        assert!(ret.room_keys.is_empty(), "The known room key should not be imported again");
+        assert_eq!(rehydrated.progress().imported_room_keys, 0);
+
+        // The upgraded sender data wasn't overwritten.
+        let room_key = bob
+            .store()
+            .get_inbound_group_session(room_id, group_session.session_id())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(room_key.sender_data.to_type(), SenderDataType::SenderVerified);
+    }
+
+    #[async_test]
+    async fn test_dehydrated_device_rehydration_signature_check() {
+        let alice = get_olm_machine().await;
+        let dehydrated_devices = alice.dehydrated_devices();
+
+        let request = dehydrated_devices
+            .create()
+            .await
+            .unwrap()
+            .keys_for_upload("Foo".to_owned(), &pickle_key())
+            .await
+            .unwrap();
+        let other_request = dehydrated_devices
+            .create()
+            .await
+            .unwrap()
+            .keys_for_upload("Bar".to_owned(), &pickle_key())
+            .await
+            .unwrap();
+
+        // The device keys of the dehydrated device are unknown.
+        assert_matches!(
+            dehydrated_devices
+                .rehydrate(&pickle_key(), &request.device_id, request.device_data.clone())
+                .await,
+            Err(DehydrationError::DeviceSignatureMismatch)
+        );
+
+        receive_device_keys(&alice, user_id(), &request.device_id, request.device_keys).await;
+
+        // The device keys are known and cross-signed, the device can be rehydrated.
+        dehydrated_devices
+            .rehydrate(&pickle_key(), &request.device_id, request.device_data)
+            .await
+            .expect("We should be able to rehydrate the cross-signed device");
+
+        // The device data was tampered with, it doesn't match the cross-signed device
+        // keys anymore.
+        assert_matches!(
+            dehydrated_devices
+                .rehydrate(&pickle_key(), &request.device_id, other_request.device_data.clone())
+                .await,
+            Err(DehydrationError::DeviceSignatureMismatch)
+        );
+
+        // Unless the check is disabled.
+        alice
+            .dehydrated_devices()
+            .skip_cross_signing_verification()
+            .rehydrate(&pickle_key(), &request.device_id, other_request.device_data)
+            .await
+            .expect("We should be able to rehydrate the device without the signature check");
+    }
+
     #[async_test]
     async fn test_dehydrated_device_pickle_key_cache() {
         let alice = get_olm_machine().await;
@@ -626,6 +1346,8 @@ mod tests {
             .await
             .expect("We should be able to create a request to upload a dehydrated device");
 
+        receive_device_keys(&alice, user_id(), &request.device_id, request.device_keys).await;
+
         // Rehydrate the device.
         dehydrated_manager
             .rehydrate(&stored_key, &request.device_id, request.device_data)
@@ -641,6 +1363,107 @@ mod tests {
         assert!(stored_key.is_none());
     }
 
+    #[async_test]
+    async fn test_dehydrated_device_rehydration_with_incorrect_pickle_key() {
+        let alice = get_olm_machine().await;
+        let dehydrated_devices = alice.dehydrated_devices().skip_cross_signing_verification();
+        let incorrect_pickle_key = DehydratedDeviceKey::from_bytes(&[1u8; 32]);
+
+        let dehydrated_device = dehydrated_devices.create().await.unwrap();
+        let request =
+            dehydrated_device.keys_for_upload("Foo".to_owned(), &pickle_key()).await.unwrap();
+
+        assert_matches!(
+            dehydrated_devices
+                .rehydrate(&incorrect_pickle_key, &request.device_id, request.device_data)
+                .await,
+            Err(DehydrationError::IncorrectPickleKey)
+        );
+
+        // The same goes for the legacy dehydrated devices.
+        let dehydrated_device = dehydrated_devices.create().await.unwrap();
+        let request =
+            legacy_dehydrated_device_keys_for_upload(&dehydrated_device, &pickle_key()).await;
+
+        assert_matches!(
+            dehydrated_devices
+                .rehydrate(&incorrect_pickle_key, &request.device_id, request.device_data)
+                .await,
+            Err(DehydrationError::IncorrectPickleKey)
+        );
+
+        // Corrupted data is not reported as an incorrect pickle key.
+        let device_data = Raw::new(&json!({
+            "algorithm": "org.matrix.msc3814.v2",
+            "device_pickle": "not a pickle",
+            "nonce": "not a nonce",
+        }))
+        .unwrap()
+        .cast_unchecked();
+
+        assert_matches!(
+            dehydrated_devices.rehydrate(&pickle_key(), &request.device_id, device_data).await,
+            Err(DehydrationError::Pickle(_))
+        );
+    }
+
+    #[async_test]
+    async fn test_dehydrated_device_rehydration_with_unsupported_algorithm() {
+        let olm_machine = get_olm_machine().await;
+
+        let device_data = Raw::new(&json!({
+            "algorithm": "org.matrix.msc3814.v3",
+            "device_pickle": "some pickle",
+            "nonce": "some nonce",
+            "some_future_field": true,
+        }))
+        .unwrap()
+        .cast_unchecked();
+
+        assert_matches!(
+            olm_machine
+                .dehydrated_devices()
+                .rehydrate(&pickle_key(), device_id!("DEHYDRATED"), device_data)
+                .await,
+            Err(DehydrationError::UnsupportedAlgorithm(algorithm)) => {
+                assert_eq!(algorithm, "org.matrix.msc3814.v3");
+            }
+        );
+    }
+
+    #[test]
+    fn test_dehydrated_device_pickle_key_base64() {
+        let pickle_key = DehydratedDeviceKey::new().unwrap();
+
+        let exported = pickle_key.to_base64();
+        let imported = DehydratedDeviceKey::from_base64(&exported).unwrap();
+        assert_eq!(imported.to_base64(), exported);
+
+        assert_matches!(
+            DehydratedDeviceKey::from_base64(&base64_encode(&[0u8; 16])),
+            Err(DehydrationError::PickleKeyLength(16))
+        );
+        assert_matches!(
+            DehydratedDeviceKey::from_base64("not base64!"),
+            Err(DehydrationError::PickleKeyBase64(_))
+        );
+    }
+
+    #[async_test]
+    async fn test_dehydrated_device_pickle_key_secret_export() {
+        let alice = get_olm_machine().await;
+        let secret_name = SecretName::from(DEHYDRATED_DEVICE_PICKLE_KEY_SECRET_NAME);
+
+        assert!(alice.store().export_secret(&secret_name).await.unwrap().is_none());
+
+        let pickle_key = DehydratedDeviceKey::new().unwrap();
+        alice.dehydrated_devices().save_dehydrated_device_pickle_key(&pickle_key).await.unwrap();
+
+        // The pickle key can be shared with our other devices.
+        let exported = alice.store().export_secret(&secret_name).await.unwrap();
+        assert_eq!(exported, Some(pickle_key.to_base64()));
+    }
+
     /// Test that we can rehydrate an older version of dehydrated device
     #[async_test]
     async fn test_legacy_dehydrated_device_rehydration() {
@@ -683,6 +1506,8 @@ mod tests {
         // Rehydrate the device.
         let rehydrated = bob
             .dehydrated_devices()
+            // Bob has a different cross-signing identity than Alice.
+            .skip_cross_signing_verification()
             .rehydrate(&pickle_key(), &device_id, request.device_data)
             .await
             .expect("We should be able to rehydrate the device");
@@ -690,16 +1515,19 @@ mod tests {
         assert_eq!(rehydrated.rehydrated.device_id(), &device_id);
         assert_eq!(rehydrated.original.device_id(), alice.device_id());
 
-        let decryption_settings =
-            DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+        let decryption_settings = DecryptionSettings {
+            sender_device_trust_requirement: TrustRequirement::Untrusted,
+            to_device_trust_requirements: Default::default(),
+            unwedging_policy: Default::default(),
+        };
 
         // Push the to-device event containing the room key into the rehydrated device.
         let ret = rehydrated
-            .receive_events(vec![event], &decryption_settings)
+            .receive_events(vec![event], None, &decryption_settings)
             .await
             .expect("We should be able to push to-device events into the rehydrated device");
 
-        assert_eq!(ret.len(), 1, "The rehydrated device should have imported a room key");
+        assert_eq!(ret.room_keys.len(), 1, "The rehydrated device should have imported a room key");
 
         // The `OlmMachine` now does know about the room key since the rehydrated device
         // shared it with us.
@@ -84,6 +84,20 @@ pub enum OlmError {
         verified and 'exclude insecure devices' is enabled."
     )]
     UnverifiedSenderDevice,
+
+    /// Refused to decrypt because the sender device did not meet the trust
+    /// requirement configured for this specific event type, see
+    /// [`DecryptionSettings::to_device_trust_requirements`].
+    ///
+    /// [`DecryptionSettings::to_device_trust_requirements`]: crate::DecryptionSettings::to_device_trust_requirements
+    #[error(
+        "refusing to decrypt the event because the sender device did not meet the \
+        trust requirement configured for events of type {event_type}"
+    )]
+    UnverifiedSenderDeviceForEventType {
+        /// The type of the decrypted event.
+        event_type: String,
+    },
 }
 
 /// Error representing a failure during a group encryption operation.
@@ -384,6 +398,23 @@ pub enum SetRoomSettingsError {
     Store(#[from] CryptoStoreError),
 }
 
+/// Errors that can be returned by
+/// [`crate::machine::OlmMachine::purge_inbound_group_sessions`].
+#[derive(Debug, Error)]
+pub enum PurgeInboundGroupSessionsError {
+    /// Some of the selected sessions aren't backed up to the key backup, and
+    /// the purge wasn't forced. No session was deleted.
+    #[error("{count} of the selected room keys aren't backed up")]
+    NotBackedUp {
+        /// The number of selected sessions which aren't backed up.
+        count: usize,
+    },
+
+    /// The store ran into an error.
+    #[error(transparent)]
+    Store(#[from] CryptoStoreError),
+}
+
 /// Error representing a problem when collecting the recipient devices for the
 /// room key, during an encryption operation.
 #[derive(Error, Debug)]
@@ -0,0 +1,442 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A persistent audit trail of the room key requests we received and sent
+//! out.
+//!
+//! The log is disabled by default, since it grows the crypto store, see
+//! [`OlmMachine::set_key_request_log_enabled()`].
+//!
+//! [`OlmMachine::set_key_request_log_enabled()`]: crate::OlmMachine::set_key_request_log_enabled
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use ruma::{
+    MilliSecondsSinceUnixEpoch, OwnedDeviceId, OwnedRoomId, OwnedTransactionId, OwnedUserId,
+    RoomId, TransactionId, UserId,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tracing::warn;
+
+use super::{GossipRequest, SecretInfo};
+use crate::store::{CryptoStoreError, Store};
+
+/// The key under which the log is stored in the crypto store.
+const KEY_REQUEST_LOG_STORE_KEY: &str = "key_request_log";
+
+/// The decision we took for an incoming room key request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IncomingKeyRequestDecision {
+    /// The room key was forwarded to the requesting device.
+    Forwarded,
+
+    /// The request was rejected, since the requesting device isn't trusted.
+    RejectedUntrusted,
+
+    /// The request was ignored, for instance because we don't have the room
+    /// key, because room key forwarding is disabled, or because the room key
+    /// wasn't shared with the requesting device in the first place.
+    Ignored,
+}
+
+/// A room key request that another device sent to us.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IncomingKeyRequestLogEntry {
+    /// The time at which the request was handled.
+    pub timestamp: MilliSecondsSinceUnixEpoch,
+
+    /// The user who sent the request.
+    pub user_id: OwnedUserId,
+
+    /// The device which sent the request.
+    pub device_id: OwnedDeviceId,
+
+    /// The room of the requested room key.
+    pub room_id: OwnedRoomId,
+
+    /// The session ID of the requested room key.
+    pub session_id: String,
+
+    /// What we did with the request.
+    pub decision: IncomingKeyRequestDecision,
+}
+
+/// The room key we received as an answer to one of our room key requests.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyRequestFulfillment {
+    /// The time at which the room key was received.
+    pub timestamp: MilliSecondsSinceUnixEpoch,
+
+    /// The device which forwarded the room key to us.
+    pub device_id: OwnedDeviceId,
+}
+
+/// A room key request that we sent out to our other devices.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutgoingKeyRequestLogEntry {
+    /// The time at which the request was created.
+    pub timestamp: MilliSecondsSinceUnixEpoch,
+
+    /// The ID of the request.
+    pub request_id: OwnedTransactionId,
+
+    /// The room of the requested room key.
+    pub room_id: OwnedRoomId,
+
+    /// The session ID of the requested room key.
+    pub session_id: String,
+
+    /// The room key we accepted as an answer to this request, if any.
+    pub fulfillment: Option<KeyRequestFulfillment>,
+}
+
+/// An entry of the room key request log.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeyRequestLogEntry {
+    /// A room key request that another device sent to us.
+    Incoming(IncomingKeyRequestLogEntry),
+
+    /// A room key request that we sent out.
+    Outgoing(OutgoingKeyRequestLogEntry),
+}
+
+impl KeyRequestLogEntry {
+    /// The time at which the entry was recorded.
+    pub fn timestamp(&self) -> MilliSecondsSinceUnixEpoch {
+        match self {
+            Self::Incoming(e) => e.timestamp,
+            Self::Outgoing(e) => e.timestamp,
+        }
+    }
+
+    /// The room of the requested room key.
+    pub fn room_id(&self) -> &RoomId {
+        match self {
+            Self::Incoming(e) => &e.room_id,
+            Self::Outgoing(e) => &e.room_id,
+        }
+    }
+
+    /// The session ID of the requested room key.
+    pub fn session_id(&self) -> &str {
+        match self {
+            Self::Incoming(e) => &e.session_id,
+            Self::Outgoing(e) => &e.session_id,
+        }
+    }
+}
+
+/// The direction of a room key request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyRequestDirection {
+    /// Requests that other devices sent to us.
+    Incoming,
+
+    /// Requests that we sent out.
+    Outgoing,
+}
+
+/// A filter for the entries of the room key request log.
+///
+/// The default filter matches all the entries.
+#[derive(Clone, Debug, Default)]
+pub struct KeyRequestLogFilter {
+    /// Only match the requests of the given direction.
+    pub direction: Option<KeyRequestDirection>,
+
+    /// Only match the requests for the room keys of the given room.
+    pub room_id: Option<OwnedRoomId>,
+
+    /// Only match the requests for the room key with the given session ID.
+    pub session_id: Option<String>,
+
+    /// Only match the incoming requests sent by the given user.
+    pub user_id: Option<OwnedUserId>,
+}
+
+impl KeyRequestLogFilter {
+    fn matches(&self, entry: &KeyRequestLogEntry) -> bool {
+        let direction_matches = match (self.direction, entry) {
+            (None, _)
+            | (Some(KeyRequestDirection::Incoming), KeyRequestLogEntry::Incoming(_))
+            | (Some(KeyRequestDirection::Outgoing), KeyRequestLogEntry::Outgoing(_)) => true,
+            (Some(_), _) => false,
+        };
+
+        let user_matches = match (&self.user_id, entry) {
+            (None, _) => true,
+            (Some(user_id), KeyRequestLogEntry::Incoming(e)) => e.user_id == *user_id,
+            (Some(_), KeyRequestLogEntry::Outgoing(_)) => false,
+        };
+
+        direction_matches
+            && user_matches
+            && self.room_id.as_deref().is_none_or(|room_id| room_id == entry.room_id())
+            && self.session_id.as_deref().is_none_or(|session_id| session_id == entry.session_id())
+    }
+}
+
+/// The persistent log of the room key requests, stored as a custom value in
+/// the crypto store.
+#[derive(Debug)]
+pub(crate) struct KeyRequestLog {
+    store: Store,
+
+    /// Whether new entries should be recorded.
+    enabled: AtomicBool,
+
+    /// Lock making sure that the read-modify-write cycles of the log don't
+    /// overlap.
+    lock: Mutex<()>,
+}
+
+impl KeyRequestLog {
+    pub fn new(store: Store) -> Self {
+        Self { store, enabled: AtomicBool::new(false), lock: Mutex::new(()) }
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::SeqCst)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::SeqCst)
+    }
+
+    /// Record the decision we took for an incoming room key request.
+    pub async fn log_incoming(
+        &self,
+        user_id: &UserId,
+        device_id: OwnedDeviceId,
+        room_id: &RoomId,
+        session_id: &str,
+        decision: IncomingKeyRequestDecision,
+    ) {
+        let entry = KeyRequestLogEntry::Incoming(IncomingKeyRequestLogEntry {
+            timestamp: MilliSecondsSinceUnixEpoch::now(),
+            user_id: user_id.to_owned(),
+            device_id,
+            room_id: room_id.to_owned(),
+            session_id: session_id.to_owned(),
+            decision,
+        });
+
+        self.update(|entries| entries.push(entry)).await
+    }
+
+    /// Record a room key request that we sent out.
+    pub async fn log_outgoing(&self, request: &GossipRequest) {
+        let SecretInfo::KeyRequest(info) = &request.info else {
+            return;
+        };
+
+        let entry = KeyRequestLogEntry::Outgoing(OutgoingKeyRequestLogEntry {
+            timestamp: MilliSecondsSinceUnixEpoch::now(),
+            request_id: request.request_id.clone(),
+            room_id: info.room_id().to_owned(),
+            session_id: info.session_id().to_owned(),
+            fulfillment: None,
+        });
+
+        self.update(|entries| entries.push(entry)).await
+    }
+
+    /// Record that we accepted a room key as an answer to the room key request
+    /// with the given ID.
+    pub async fn log_fulfillment(&self, request_id: &TransactionId, device_id: OwnedDeviceId) {
+        self.update(|entries| {
+            let entry = entries.iter_mut().rev().find_map(|entry| match entry {
+                KeyRequestLogEntry::Outgoing(e) if e.request_id == request_id => Some(e),
+                _ => None,
+            });
+
+            if let Some(entry) = entry {
+                entry.fulfillment = Some(KeyRequestFulfillment {
+                    timestamp: MilliSecondsSinceUnixEpoch::now(),
+                    device_id,
+                });
+            }
+        })
+        .await
+    }
+
+    /// Get the most recent entries of the log matching the given filter, the
+    /// newest entries first.
+    pub async fn entries(
+        &self,
+        limit: usize,
+        filter: &KeyRequestLogFilter,
+    ) -> Result<Vec<KeyRequestLogEntry>, CryptoStoreError> {
+        let entries = self.load().await?;
+
+        Ok(entries.into_iter().rev().filter(|entry| filter.matches(entry)).take(limit).collect())
+    }
+
+    /// Remove the entries recorded before the given time.
+    ///
+    /// Returns the number of removed entries.
+    pub async fn prune(
+        &self,
+        before: MilliSecondsSinceUnixEpoch,
+    ) -> Result<usize, CryptoStoreError> {
+        let _guard = self.lock.lock().await;
+
+        let mut entries = self.load().await?;
+        let count = entries.len();
+        entries.retain(|entry| entry.timestamp() >= before);
+
+        let removed = count - entries.len();
+
+        if removed > 0 {
+            self.store.set_value(KEY_REQUEST_LOG_STORE_KEY, &entries).await?;
+        }
+
+        Ok(removed)
+    }
+
+    async fn load(&self) -> Result<Vec<KeyRequestLogEntry>, CryptoStoreError> {
+        Ok(self.store.get_value(KEY_REQUEST_LOG_STORE_KEY).await?.unwrap_or_default())
+    }
+
+    /// Modify the stored log, if it is enabled.
+    ///
+    /// Failures are only logged, the room key requests are handled even if we
+    /// can't record them.
+    async fn update(&self, f: impl FnOnce(&mut Vec<KeyRequestLogEntry>)) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let _guard = self.lock.lock().await;
+
+        let result = async {
+            let mut entries = self.load().await?;
+            f(&mut entries);
+            self.store.set_value(KEY_REQUEST_LOG_STORE_KEY, &entries).await
+        };
+
+        if let Err(e) = result.await {
+            warn!("Couldn't update the room key request log: {e:?}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use matrix_sdk_test::async_test;
+    use ruma::{
+        device_id, owned_device_id, room_id, user_id, MilliSecondsSinceUnixEpoch, TransactionId,
+        UInt,
+    };
+
+    use super::{
+        IncomingKeyRequestDecision, KeyRequestDirection, KeyRequestLog, KeyRequestLogEntry,
+        KeyRequestLogFilter,
+    };
+    use crate::{
+        gossiping::GossipRequest, types::events::room_key_request::MegolmV1AesSha2Content,
+        OlmMachine,
+    };
+
+    #[async_test]
+    async fn test_key_request_log() {
+        let alice = user_id!("@alice:localhost");
+        let bob = user_id!("@bob:localhost");
+        let room_id = room_id!("!test:localhost");
+
+        let machine = OlmMachine::new(alice, device_id!("ALICEDEVICE")).await;
+        let log = KeyRequestLog::new(machine.store().clone());
+
+        // Nothing is recorded while the log is disabled.
+        log.log_incoming(
+            bob,
+            owned_device_id!("BOBDEVICE"),
+            room_id,
+            "first_session",
+            IncomingKeyRequestDecision::Forwarded,
+        )
+        .await;
+        assert!(log.entries(10, &Default::default()).await.unwrap().is_empty());
+
+        log.set_enabled(true);
+
+        log.log_incoming(
+            bob,
+            owned_device_id!("BOBDEVICE"),
+            room_id,
+            "first_session",
+            IncomingKeyRequestDecision::RejectedUntrusted,
+        )
+        .await;
+
+        let request = GossipRequest {
+            request_recipient: alice.to_owned(),
+            request_id: TransactionId::new(),
+            info: MegolmV1AesSha2Content {
+                room_id: room_id.to_owned(),
+                sender_key: machine.identity_keys().curve25519,
+                session_id: "second_session".to_owned(),
+            }
+            .into(),
+            sent_out: false,
+        };
+        log.log_outgoing(&request).await;
+        log.log_fulfillment(&request.request_id, owned_device_id!("ALICEDEVICE2")).await;
+
+        // The newest entries come first.
+        let entries = log.entries(10, &Default::default()).await.unwrap();
+        assert_eq!(entries.len(), 2);
+
+        let KeyRequestLogEntry::Outgoing(outgoing) = &entries[0] else {
+            panic!("The newest entry should be the outgoing request");
+        };
+        assert_eq!(outgoing.request_id, request.request_id);
+        assert_eq!(outgoing.session_id, "second_session");
+        assert_eq!(outgoing.fulfillment.as_ref().unwrap().device_id, device_id!("ALICEDEVICE2"));
+
+        let KeyRequestLogEntry::Incoming(incoming) = &entries[1] else {
+            panic!("The oldest entry should be the incoming request");
+        };
+        assert_eq!(incoming.user_id, bob);
+        assert_eq!(incoming.session_id, "first_session");
+        assert_eq!(incoming.decision, IncomingKeyRequestDecision::RejectedUntrusted);
+
+        // The entries can be filtered and limited.
+        let filter = KeyRequestLogFilter {
+            direction: Some(KeyRequestDirection::Incoming),
+            ..Default::default()
+        };
+        assert_eq!(log.entries(10, &filter).await.unwrap(), [entries[1].clone()]);
+
+        let filter = KeyRequestLogFilter {
+            session_id: Some("second_session".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(log.entries(10, &filter).await.unwrap(), [entries[0].clone()]);
+
+        let filter = KeyRequestLogFilter { user_id: Some(bob.to_owned()), ..Default::default() };
+        assert_eq!(log.entries(10, &filter).await.unwrap(), [entries[1].clone()]);
+
+        assert_eq!(log.entries(1, &Default::default()).await.unwrap(), [entries[0].clone()]);
+
+        // Pruning removes the entries older than the given time.
+        let pruned = log.prune(MilliSecondsSinceUnixEpoch(UInt::MIN)).await.unwrap();
+        assert_eq!(pruned, 0);
+
+        let pruned = log.prune(MilliSecondsSinceUnixEpoch(UInt::MAX)).await.unwrap();
+        assert_eq!(pruned, 2);
+        assert!(log.entries(10, &Default::default()).await.unwrap().is_empty());
+    }
+}
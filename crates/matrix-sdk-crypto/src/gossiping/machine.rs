@@ -41,7 +41,10 @@ use ruma::{
 use tracing::{debug, field::debug, info, instrument, trace, warn, Span};
 use vodozemac::{megolm::SessionOrdering, Curve25519PublicKey};
 
-use super::{GossipRequest, GossippedSecret, RequestEvent, RequestInfo, SecretInfo, WaitQueue};
+use super::{
+    key_request_log::KeyRequestLog, GossipRequest, GossippedSecret, RequestEvent, RequestInfo,
+    SecretInfo, WaitQueue,
+};
 use crate::{
     error::{EventError, OlmError, OlmResult},
     identities::IdentityManager,
@@ -83,6 +86,9 @@ pub(crate) struct GossipMachineInner {
     /// Whether we should send out `m.room_key_request` messages.
     room_key_requests_enabled: AtomicBool,
 
+    /// The audit trail of the room key requests we received and sent out.
+    key_request_log: KeyRequestLog,
+
     identity_manager: IdentityManager,
 }
 
@@ -99,6 +105,8 @@ impl GossipMachine {
         let room_key_requests_enabled =
             AtomicBool::new(cfg!(feature = "automatic-room-key-forwarding"));
 
+        let key_request_log = KeyRequestLog::new(store.clone());
+
         Self {
             inner: Arc::new(GossipMachineInner {
                 store,
@@ -110,6 +118,7 @@ impl GossipMachine {
                 users_for_key_claim,
                 room_key_forwarding_enabled,
                 room_key_requests_enabled,
+                key_request_log,
                 identity_manager,
             }),
         }
@@ -119,6 +128,10 @@ impl GossipMachine {
         &self.inner.identity_manager
     }
 
+    pub(crate) fn key_request_log(&self) -> &KeyRequestLog {
+        &self.inner.key_request_log
+    }
+
     #[cfg(feature = "automatic-room-key-forwarding")]
     pub fn set_room_key_forwarding_enabled(&self, enabled: bool) {
         self.inner.room_key_forwarding_enabled.store(enabled, Ordering::SeqCst)
@@ -393,10 +406,21 @@ impl GossipMachine {
         session: &InboundGroupSession,
         message_index: Option<u32>,
     ) -> OlmResult<Option<Session>> {
+        use super::IncomingKeyRequestDecision;
+
         info!(?message_index, "Serving a room key request",);
 
         match self.forward_room_key(session, &device, message_index).await {
-            Ok(s) => Ok(Some(s)),
+            Ok(s) => {
+                self.log_incoming_key_request(
+                    event,
+                    session,
+                    IncomingKeyRequestDecision::Forwarded,
+                )
+                .await;
+
+                Ok(Some(s))
+            }
             Err(OlmError::MissingSession) => {
                 info!(
                     "Key request is missing an Olm session, putting the request in the wait queue",
@@ -410,6 +434,9 @@ impl GossipMachine {
                     "Can't serve a room key request, the session \
                      can't be exported into a forwarded room key: {e:?}",
                 );
+                self.log_incoming_key_request(event, session, IncomingKeyRequestDecision::Ignored)
+                    .await;
+
                 Ok(None)
             }
             Err(e) => Err(e),
@@ -425,13 +452,15 @@ impl GossipMachine {
         event: &RoomKeyRequestEvent,
         session: &InboundGroupSession,
     ) -> OlmResult<Option<Session>> {
-        use super::KeyForwardDecision;
+        use super::{IncomingKeyRequestDecision, KeyForwardDecision};
 
         let device =
             self.inner.store.get_device(&event.sender, &event.content.requesting_device_id).await?;
 
         let Some(device) = device else {
             warn!("Received a key request from an unknown device");
+            self.log_incoming_key_request(event, session, IncomingKeyRequestDecision::Ignored)
+                .await;
             self.identity_manager()
                 .key_query_manager
                 .synced(cache)
@@ -459,6 +488,13 @@ impl GossipMachine {
                     );
                 }
 
+                let decision = if let KeyForwardDecision::UntrustedDevice = e {
+                    IncomingKeyRequestDecision::RejectedUntrusted
+                } else {
+                    IncomingKeyRequestDecision::Ignored
+                };
+                self.log_incoming_key_request(event, session, decision).await;
+
                 Ok(None)
             }
         }
@@ -488,10 +524,42 @@ impl GossipMachine {
         } else {
             debug!("Received a room key request for an unknown inbound group session",);
 
+            self.inner
+                .key_request_log
+                .log_incoming(
+                    &event.sender,
+                    event.content.requesting_device_id.clone(),
+                    room_id,
+                    session_id,
+                    super::IncomingKeyRequestDecision::Ignored,
+                )
+                .await;
+
             Ok(None)
         }
     }
 
+    /// Record the decision we took for the given room key request in the key
+    /// request log.
+    #[cfg(feature = "automatic-room-key-forwarding")]
+    async fn log_incoming_key_request(
+        &self,
+        event: &RoomKeyRequestEvent,
+        session: &InboundGroupSession,
+        decision: super::IncomingKeyRequestDecision,
+    ) {
+        self.inner
+            .key_request_log
+            .log_incoming(
+                &event.sender,
+                event.content.requesting_device_id.clone(),
+                session.room_id(),
+                session.session_id(),
+                decision,
+            )
+            .await
+    }
+
     /// Handle a single incoming key request.
     #[cfg(feature = "automatic-room-key-forwarding")]
     async fn handle_key_request(
@@ -499,7 +567,7 @@ impl GossipMachine {
         cache: &StoreCache,
         event: &RoomKeyRequestEvent,
     ) -> OlmResult<Option<Session>> {
-        use crate::types::events::room_key_request::{Action, RequestedKeyInfo};
+        use crate::types::events::room_key_request::{Action, RequestedKeyInfo, SupportedKeyInfo};
 
         if self.inner.room_key_forwarding_enabled.load(Ordering::SeqCst) {
             match &event.content.action {
@@ -530,6 +598,22 @@ impl GossipMachine {
                 sender = ?event.sender,
                 "Received a room key request, but room key forwarding has been turned off"
             );
+
+            if let Action::Request(info) = &event.content.action {
+                if let Ok(info) = SupportedKeyInfo::try_from(info.clone()) {
+                    self.inner
+                        .key_request_log
+                        .log_incoming(
+                            &event.sender,
+                            event.content.requesting_device_id.clone(),
+                            info.room_id(),
+                            info.session_id(),
+                            super::IncomingKeyRequestDecision::Ignored,
+                        )
+                        .await;
+                }
+            }
+
             Ok(None)
         }
     }
@@ -728,6 +812,31 @@ impl GossipMachine {
         }
     }
 
+    /// Re-send the room key requests that we have already sent out.
+    ///
+    /// The requests are marked as unsent, so they are returned again by
+    /// [`GossipMachine::outgoing_to_device_requests()`]. This is used when a
+    /// wedged Olm session with one of our own devices got replaced, since the
+    /// responses to those requests might have been sent over the wedged
+    /// session.
+    pub async fn resend_room_key_requests(&self) -> Result<(), CryptoStoreError> {
+        let mut changes = Changes::default();
+
+        for mut request in self.inner.store.get_sent_secret_requests().await? {
+            if let SecretInfo::KeyRequest(_) = request.info {
+                request.sent_out = false;
+                changes.key_requests.push(request);
+            }
+        }
+
+        if !changes.key_requests.is_empty() {
+            info!(count = changes.key_requests.len(), "Re-sending our room key requests");
+            self.inner.store.save_changes(changes).await?;
+        }
+
+        Ok(())
+    }
+
     /// Create outgoing secret requests for the given
     pub fn request_missing_secrets(
         own_user_id: &UserId,
@@ -758,7 +867,8 @@ impl GossipMachine {
         };
 
         let outgoing_request = request.to_request(self.device_id());
-        self.save_outgoing_key_info(request).await?;
+        self.save_outgoing_key_info(request.clone()).await?;
+        self.inner.key_request_log.log_outgoing(&request).await;
 
         Ok(outgoing_request)
     }
@@ -853,11 +963,13 @@ impl GossipMachine {
         Ok(())
     }
 
+    /// Accept the given secret, returns whether the secret was imported or
+    /// put into the secret inbox.
     async fn accept_secret(
         &self,
         secret: GossippedSecret,
         changes: &mut Changes,
-    ) -> Result<(), CryptoStoreError> {
+    ) -> Result<bool, CryptoStoreError> {
         if secret.secret_name != SecretName::RecoveryKey {
             match self.inner.store.import_secret(&secret).await {
                 Ok(_) => self.mark_as_done(&secret.gossip_request).await?,
@@ -871,6 +983,8 @@ impl GossipMachine {
                         error = ?e,
                         "Error while importing a secret"
                     );
+
+                    return Ok(false);
                 }
             }
         } else {
@@ -884,16 +998,18 @@ impl GossipMachine {
             changes.secrets.push(secret);
         }
 
-        Ok(())
+        Ok(true)
     }
 
+    /// Receive a secret with a matching request, returns whether the secret was
+    /// accepted.
     async fn receive_secret(
         &self,
         cache: &StoreCache,
         sender_key: Curve25519PublicKey,
         secret: GossippedSecret,
         changes: &mut Changes,
-    ) -> Result<(), CryptoStoreError> {
+    ) -> Result<bool, CryptoStoreError> {
         debug!("Received a m.secret.send event with a matching request");
 
         if let Some(device) =
@@ -901,7 +1017,7 @@ impl GossipMachine {
         {
             // Only accept secrets from one of our own trusted devices.
             if device.user_id() == self.user_id() && device.is_verified() {
-                self.accept_secret(secret, changes).await?;
+                return self.accept_secret(secret, changes).await;
             } else {
                 warn!("Received a m.secret.send event from another user or from unverified device");
             }
@@ -916,7 +1032,7 @@ impl GossipMachine {
                 .await?;
         }
 
-        Ok(())
+        Ok(false)
     }
 
     #[instrument(skip_all, fields(sender_key, sender = ?event.sender, request_id = ?event.content.request_id, secret_name))]
@@ -927,6 +1043,41 @@ impl GossipMachine {
         event: &DecryptedSecretSendEvent,
         changes: &mut Changes,
     ) -> Result<Option<SecretName>, CryptoStoreError> {
+        Ok(self
+            .receive_secret_event_helper(cache, sender_key, event, changes)
+            .await?
+            .map(|(secret_name, _)| secret_name))
+    }
+
+    /// Receive a `m.secret.send` event which was sent to one of our dehydrated
+    /// devices, and decrypted by the rehydrated device.
+    ///
+    /// The secret is handled like a secret sent to us directly, so it needs to
+    /// match one of our secret requests and to come from one of our own
+    /// verified devices. Returns whether the secret was accepted.
+    #[instrument(skip_all, fields(sender_key, sender = ?event.sender, request_id = ?event.content.request_id, secret_name))]
+    pub(crate) async fn receive_rehydrated_secret_event(
+        &self,
+        cache: &StoreCache,
+        sender_key: Curve25519PublicKey,
+        event: &DecryptedSecretSendEvent,
+        changes: &mut Changes,
+    ) -> Result<bool, CryptoStoreError> {
+        Ok(self
+            .receive_secret_event_helper(cache, sender_key, event, changes)
+            .await?
+            .is_some_and(|(_, accepted)| accepted))
+    }
+
+    /// Receive a `m.secret.send` event, returns the name of the secret if the
+    /// event matches one of our requests, and whether the secret was accepted.
+    async fn receive_secret_event_helper(
+        &self,
+        cache: &StoreCache,
+        sender_key: Curve25519PublicKey,
+        event: &DecryptedSecretSendEvent,
+        changes: &mut Changes,
+    ) -> Result<Option<(SecretName, bool)>, CryptoStoreError> {
         debug!("Received a m.secret.send event");
 
         let request_id = &event.content.request_id;
@@ -951,9 +1102,9 @@ impl GossipMachine {
                         gossip_request: request,
                     };
 
-                    self.receive_secret(cache, sender_key, secret, changes).await?;
+                    let accepted = self.receive_secret(cache, sender_key, secret, changes).await?;
 
-                    Some(secret_name)
+                    Some((secret_name, accepted))
                 }
             }
         } else {
@@ -1007,19 +1158,17 @@ impl GossipMachine {
         }
     }
 
+    /// Get the device which forwarded us a room key, if we should accept the
+    /// forwarded room key.
     async fn should_accept_forward(
         &self,
         info: &GossipRequest,
         sender_key: Curve25519PublicKey,
-    ) -> Result<bool, CryptoStoreError> {
+    ) -> Result<Option<Device>, CryptoStoreError> {
         let device =
             self.inner.store.get_device_from_curve_key(&info.request_recipient, sender_key).await?;
 
-        if let Some(device) = device {
-            Ok(device.user_id() == self.user_id() && device.is_verified())
-        } else {
-            Ok(false)
-        }
+        Ok(device.filter(|device| device.user_id() == self.user_id() && device.is_verified()))
     }
 
     /// Receive a forwarded room key event that was sent using any of our
@@ -1052,8 +1201,17 @@ impl GossipMachine {
             return Ok(None);
         };
 
-        if self.should_accept_forward(&request, sender_key).await? {
-            self.accept_forwarded_room_key(&request, sender_key, event).await
+        if let Some(device) = self.should_accept_forward(&request, sender_key).await? {
+            let session = self.accept_forwarded_room_key(&request, sender_key, event).await?;
+
+            if session.is_some() {
+                self.inner
+                    .key_request_log
+                    .log_fulfillment(&request.request_id, device.device_id().to_owned())
+                    .await;
+            }
+
+            Ok(session)
         } else {
             warn!(
                 ?sender_key,
@@ -1101,6 +1259,8 @@ mod tests {
 
     #[cfg(feature = "automatic-room-key-forwarding")]
     use assert_matches::assert_matches;
+    #[cfg(feature = "automatic-room-key-forwarding")]
+    use assert_matches2::assert_let;
     use matrix_sdk_test::{async_test, message_like_event_content};
     use ruma::{
         device_id, event_id,
@@ -1117,7 +1277,7 @@ mod tests {
     use super::GossipMachine;
     #[cfg(feature = "automatic-room-key-forwarding")]
     use crate::{
-        gossiping::KeyForwardDecision,
+        gossiping::{IncomingKeyRequestDecision, KeyForwardDecision, KeyRequestLogEntry},
         olm::OutboundGroupSession,
         store::{types::DeviceChanges, CryptoStore},
         types::requests::AnyOutgoingRequest,
@@ -1259,6 +1419,9 @@ mod tests {
 
         let bob_machine = gossip_machine_test_helper(other_machine_owner).await;
 
+        alice_machine.key_request_log().set_enabled(true);
+        bob_machine.key_request_log().set_enabled(true);
+
         let bob_device = DeviceData::from_account(
             #[allow(clippy::explicit_auto_deref)] // clippy's wrong
             &*bob_machine.inner.store.cache().await.unwrap().account().await.unwrap(),
@@ -1451,6 +1614,38 @@ mod tests {
         assert!(machine.outgoing_to_device_requests().await.unwrap().is_empty());
     }
 
+    #[async_test]
+    #[cfg(feature = "automatic-room-key-forwarding")]
+    async fn test_resend_room_key_requests() {
+        let machine = get_machine_test_helper().await;
+        let account = account();
+        let second_account = alice_2_account();
+        let alice_device = DeviceData::from_account(&second_account);
+
+        // We need a trusted device, otherwise we won't request keys
+        alice_device.set_trust_state(LocalTrust::Verified);
+        machine.inner.store.save_device_data(&[alice_device]).await.unwrap();
+
+        let (outbound, session) = account.create_group_session_pair_with_defaults(room_id()).await;
+        let content = outbound.encrypt("m.dummy", &message_like_event_content!({})).await;
+        let event = wrap_encrypted_content(machine.user_id(), content);
+
+        machine.create_outgoing_key_request(session.room_id(), &event).await.unwrap();
+
+        let requests = machine.outgoing_to_device_requests().await.unwrap();
+        let request_id = requests[0].request_id.clone();
+
+        machine.mark_outgoing_request_as_sent(&request_id).await.unwrap();
+        assert!(machine.outgoing_to_device_requests().await.unwrap().is_empty());
+
+        // Once re-sent, the same request is queued up again.
+        machine.resend_room_key_requests().await.unwrap();
+
+        let requests = machine.outgoing_to_device_requests().await.unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].request_id, request_id);
+    }
+
     /// We should *not* request keys if that has been disabled
     #[async_test]
     #[cfg(feature = "automatic-room-key-forwarding")]
@@ -1762,6 +1957,8 @@ mod tests {
                         &event,
                         &DecryptionSettings {
                             sender_device_trust_requirement: TrustRequirement::Untrusted,
+                            to_device_trust_requirements: Default::default(),
+                            unwedging_policy: Default::default(),
                         },
                     )
                     .await?;
@@ -1789,7 +1986,22 @@ mod tests {
             .unwrap()
             .unwrap();
 
-        assert_eq!(session.session_id(), group_session.session_id())
+        assert_eq!(session.session_id(), group_session.session_id());
+
+        // Bob recorded that he forwarded the room key to alice.
+        let entries = bob_machine.key_request_log().entries(10, &Default::default()).await.unwrap();
+        assert_let!([KeyRequestLogEntry::Incoming(incoming)] = entries.as_slice());
+        assert_eq!(incoming.user_id, alice_id());
+        assert_eq!(incoming.device_id, alice_device_id());
+        assert_eq!(incoming.session_id, group_session.session_id());
+        assert_eq!(incoming.decision, IncomingKeyRequestDecision::Forwarded);
+
+        // Alice recorded that her request was fulfilled by bob.
+        let entries =
+            alice_machine.key_request_log().entries(10, &Default::default()).await.unwrap();
+        assert_let!([KeyRequestLogEntry::Outgoing(outgoing)] = entries.as_slice());
+        assert_eq!(outgoing.session_id, group_session.session_id());
+        assert_eq!(outgoing.fulfillment.as_ref().unwrap().device_id, bob_machine.device_id());
     }
 
     #[async_test]
@@ -1849,6 +2061,8 @@ mod tests {
                         &event,
                         &DecryptionSettings {
                             sender_device_trust_requirement: TrustRequirement::Untrusted,
+                            to_device_trust_requirements: Default::default(),
+                            unwedging_policy: Default::default(),
                         },
                     )
                     .await?;
@@ -1866,6 +2080,11 @@ mod tests {
             .unwrap();
 
         assert!(session.is_none(), "We should not receive a room key from another user");
+
+        let entries =
+            alice_machine.key_request_log().entries(10, &Default::default()).await.unwrap();
+        assert_let!([KeyRequestLogEntry::Outgoing(outgoing)] = entries.as_slice());
+        assert!(outgoing.fulfillment.is_none(), "The request shouldn't be marked as fulfilled");
     }
 
     #[async_test]
@@ -2064,8 +2283,11 @@ mod tests {
         let stream = bob_machine.store().secrets_stream();
         pin_mut!(stream);
 
-        let decryption_settings =
-            DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+        let decryption_settings = DecryptionSettings {
+            sender_device_trust_requirement: TrustRequirement::Untrusted,
+            to_device_trust_requirements: Default::default(),
+            unwedging_policy: Default::default(),
+        };
 
         bob_machine
             .receive_sync_changes(
@@ -2183,6 +2405,8 @@ mod tests {
                         &event,
                         &DecryptionSettings {
                             sender_device_trust_requirement: TrustRequirement::Untrusted,
+                            to_device_trust_requirements: Default::default(),
+                            unwedging_policy: Default::default(),
                         },
                     )
                     .await?;
@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod key_request_log;
 mod machine;
 
 use std::{
@@ -19,6 +20,10 @@ use std::{
     sync::Arc,
 };
 
+pub use key_request_log::{
+    IncomingKeyRequestDecision, IncomingKeyRequestLogEntry, KeyRequestDirection,
+    KeyRequestFulfillment, KeyRequestLogEntry, KeyRequestLogFilter, OutgoingKeyRequestLogEntry,
+};
 pub(crate) use machine::GossipMachine;
 use matrix_sdk_common::locks::RwLock as StdRwLock;
 use ruma::{
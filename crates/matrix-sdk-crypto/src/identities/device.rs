@@ -100,6 +100,17 @@ pub struct DeviceData {
     /// us.
     #[serde(default)]
     pub(crate) olm_wedging_index: SequenceNumber,
+    /// The identity keys of the device, recorded when the device was pinned.
+    #[serde(default)]
+    pinned_keys: Arc<RwLock<Option<PinnedDeviceKeys>>>,
+}
+
+/// The identity keys of a device at the time it was pinned, see
+/// [`DeviceData::is_pinned()`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct PinnedDeviceKeys {
+    ed25519: Ed25519PublicKey,
+    curve25519: Curve25519PublicKey,
 }
 
 fn default_timestamp() -> MilliSecondsSinceUnixEpoch {
@@ -117,6 +128,7 @@ impl std::fmt::Debug for DeviceData {
             .field("deleted", &self.deleted.load(Ordering::SeqCst))
             .field("trust_state", &self.trust_state)
             .field("withheld_code_sent", &self.withheld_code_sent)
+            .field("pinned_keys", &self.pinned_keys)
             .finish()
     }
 }
@@ -586,6 +598,7 @@ impl DeviceData {
             withheld_code_sent: Arc::new(AtomicBool::new(false)),
             first_time_seen_ts: MilliSecondsSinceUnixEpoch::now(),
             olm_wedging_index: Default::default(),
+            pinned_keys: Default::default(),
         }
     }
 
@@ -936,6 +949,42 @@ impl DeviceData {
         self.deleted.store(true, Ordering::Relaxed);
     }
 
+    /// Is the device pinned, i.e. trusted on first use.
+    ///
+    /// A device is pinned if its identity keys were recorded when it was first
+    /// seen, or when its owner's devices were explicitly re-pinned with
+    /// [`Store::pin_user_devices()`], and they didn't change since.
+    ///
+    /// Devices are only pinned if the device pinning mode has been enabled
+    /// with [`Store::set_device_pinning_enabled()`].
+    ///
+    /// [`Store::pin_user_devices()`]: crate::store::Store::pin_user_devices
+    /// [`Store::set_device_pinning_enabled()`]: crate::store::Store::set_device_pinning_enabled
+    pub fn is_pinned(&self) -> bool {
+        let Some(pinned_keys) = self.pinned_keys.read().clone() else {
+            return false;
+        };
+
+        self.ed25519_key() == Some(pinned_keys.ed25519)
+            && self.curve25519_key() == Some(pinned_keys.curve25519)
+    }
+
+    /// Pin the current identity keys of the device.
+    ///
+    /// Returns `false` if the device doesn't have identity keys to pin.
+    ///
+    /// Note: This should only done in the crypto store where the pinned keys
+    /// can be stored.
+    pub(crate) fn pin(&self) -> bool {
+        let (Some(ed25519), Some(curve25519)) = (self.ed25519_key(), self.curve25519_key()) else {
+            return false;
+        };
+
+        *self.pinned_keys.write() = Some(PinnedDeviceKeys { ed25519, curve25519 });
+
+        true
+    }
+
     #[cfg(any(test, feature = "testing"))]
     #[allow(dead_code)]
     /// Generate the Device from a reference of an OlmMachine.
@@ -989,6 +1038,7 @@ impl TryFrom<&DeviceKeys> for DeviceData {
             withheld_code_sent: Arc::new(AtomicBool::new(false)),
             first_time_seen_ts: MilliSecondsSinceUnixEpoch::now(),
             olm_wedging_index: Default::default(),
+            pinned_keys: Default::default(),
         };
 
         device.verify_device_keys(device_keys)?;
@@ -15,16 +15,16 @@
 use std::{
     collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     ops::Deref,
-    sync::Arc,
+    sync::{Arc, Mutex as StdMutex, RwLock as StdRwLock},
     time::Duration,
 };
 
 use futures_util::future::join_all;
-use itertools::Itertools;
 use matrix_sdk_common::{executor::spawn, failures_cache::FailuresCache};
 use ruma::{
-    api::client::keys::get_keys::v3::Response as KeysQueryResponse, serde::Raw, OwnedDeviceId,
-    OwnedServerName, OwnedTransactionId, OwnedUserId, ServerName, TransactionId, UserId,
+    api::client::keys::get_keys::v3::Response as KeysQueryResponse, serde::Raw, time::Instant,
+    OwnedDeviceId, OwnedServerName, OwnedTransactionId, OwnedUserId, ServerName, TransactionId,
+    UserId,
 };
 use tokio::sync::Mutex;
 use tracing::{debug, enabled, info, instrument, trace, warn, Level};
@@ -38,7 +38,7 @@ use crate::{
     },
     store::{
         caches::{SequenceNumber, StoreCache, StoreCacheGuard},
-        types::{Changes, DeviceChanges, IdentityChanges, UserKeyQueryResult},
+        types::{Changes, DeviceChanges, IdentityChanges, SenderDataUpgrade, UserKeyQueryResult},
         KeyQueryManager, Result as StoreResult, Store,
     },
     types::{
@@ -76,10 +76,18 @@ pub(crate) struct IdentityManager {
 
     /// Details of the current "in-flight" key query request, if any
     keys_query_request_details: Arc<Mutex<Option<KeysQueryRequestDetails>>>,
+
+    /// The configuration of the key query requests returned by
+    /// `users_for_key_query`.
+    config: Arc<StdRwLock<KeyQueryConfig>>,
+
+    /// The time at which the keys of a user were last queried, only recorded
+    /// if [`KeyQueryConfig::min_query_interval`] is set.
+    last_key_queries: Arc<StdMutex<HashMap<OwnedUserId, Instant>>>,
 }
 
 /// Details of an in-flight key query request
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 struct KeysQueryRequestDetails {
     /// The sequence number, to be passed to
     /// `Store.mark_tracked_users_as_up_to_date`.
@@ -89,6 +97,72 @@ struct KeysQueryRequestDetails {
     /// more actual KeysQueryRequests, each with their own request id. We
     /// record the outstanding request ids here.
     request_ids: HashSet<OwnedTransactionId>,
+
+    /// The time at which the batch of queries was created.
+    created_at: Instant,
+}
+
+/// Configuration of the `/keys/query` requests that are created when the
+/// devices of the tracked users change, see
+/// [`OlmMachine::set_key_query_config()`].
+///
+/// The default configuration sends out a request as soon as possible for all
+/// the users whose devices changed.
+///
+/// [`OlmMachine::set_key_query_config()`]: crate::OlmMachine::set_key_query_config
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyQueryConfig {
+    /// The maximum number of users whose keys are queried in a single
+    /// `/keys/query` request, the users are split into several requests
+    /// otherwise.
+    ///
+    /// Defaults to 250.
+    pub max_users_per_request: usize,
+
+    /// The minimum time between two `/keys/query` requests for the same user.
+    ///
+    /// The keys of a user whose devices change again before this interval
+    /// elapsed are only queried once it elapsed.
+    ///
+    /// Defaults to zero, meaning that users are never held back.
+    pub min_query_interval: Duration,
+
+    /// Whether to wait for the responses of the in-flight `/keys/query`
+    /// requests before creating new ones.
+    ///
+    /// The users whose devices change in the meantime are then queried
+    /// together, in the next batch of requests. By default, a new batch
+    /// replaces the in-flight one.
+    pub coalesce_in_flight_queries: bool,
+}
+
+impl Default for KeyQueryConfig {
+    fn default() -> Self {
+        Self {
+            max_users_per_request: IdentityManager::MAX_KEY_QUERY_USERS,
+            min_query_interval: Duration::ZERO,
+            coalesce_in_flight_queries: false,
+        }
+    }
+}
+
+/// The state of the `/keys/query` requests, see
+/// [`OlmMachine::pending_key_queries()`].
+///
+/// [`OlmMachine::pending_key_queries()`]: crate::OlmMachine::pending_key_queries
+#[derive(Clone, Debug, Default)]
+pub struct PendingKeyQueries {
+    /// The users whose keys need to be queried.
+    pub users: BTreeSet<OwnedUserId>,
+
+    /// The users whose keys need to be queried, but which are held back since
+    /// they were queried recently, see
+    /// [`KeyQueryConfig::min_query_interval`].
+    pub throttled_users: BTreeSet<OwnedUserId>,
+
+    /// The IDs of the `/keys/query` requests which were created but whose
+    /// response wasn't received yet.
+    pub in_flight_requests: BTreeSet<OwnedTransactionId>,
 }
 
 // Helper type to handle key query response
@@ -101,6 +175,10 @@ struct KeySetInfo {
 impl IdentityManager {
     const MAX_KEY_QUERY_USERS: usize = 250;
 
+    /// The time after which in-flight key query requests are assumed to be
+    /// lost, and are no longer waited for when coalescing queries.
+    const IN_FLIGHT_KEY_QUERY_TIMEOUT: Duration = Duration::from_secs(60);
+
     pub fn new(store: Store) -> Self {
         let keys_query_request_details = Mutex::new(None);
 
@@ -109,9 +187,51 @@ impl IdentityManager {
             key_query_manager: Default::default(),
             failures: Default::default(),
             keys_query_request_details: keys_query_request_details.into(),
+            config: Default::default(),
+            last_key_queries: Default::default(),
         }
     }
 
+    /// Get the configuration of the key query requests.
+    pub fn config(&self) -> KeyQueryConfig {
+        *self.config.read().unwrap()
+    }
+
+    /// Set the configuration of the key query requests.
+    pub fn set_config(&self, config: KeyQueryConfig) {
+        *self.config.write().unwrap() = config;
+    }
+
+    /// Get the users whose keys need to be queried, and the key query requests
+    /// which are in flight.
+    pub async fn pending_key_queries(&self) -> StoreResult<PendingKeyQueries> {
+        let (users, _) = {
+            let cache = self.store.cache().await?;
+            self.key_query_manager.synced(&cache).await?.users_for_key_query().await
+        };
+
+        let in_flight_requests = self
+            .keys_query_request_details
+            .lock()
+            .await
+            .as_ref()
+            .map(|details| details.request_ids.iter().cloned().collect())
+            .unwrap_or_default();
+
+        let min_query_interval = self.config().min_query_interval;
+        let (throttled_users, users) = {
+            let last_key_queries = self.last_key_queries.lock().unwrap();
+
+            users.into_iter().partition(|user_id| {
+                last_key_queries
+                    .get(user_id)
+                    .is_some_and(|last_query| last_query.elapsed() < min_query_interval)
+            })
+        };
+
+        Ok(PendingKeyQueries { users, throttled_users, in_flight_requests })
+    }
+
     fn user_id(&self) -> &UserId {
         &self.store.static_account().user_id
     }
@@ -362,6 +482,26 @@ impl IdentityManager {
             }
         }
 
+        // Trust on first use: if none of the devices of the user is pinned yet, which
+        // is usually the case when we see them for the first time, pin the current
+        // ones.
+        if store.is_device_pinning_enabled().await?
+            && !stored_devices.values().any(|d| d.is_pinned())
+        {
+            for device in changes.new.iter().chain(&changes.changed) {
+                device.pin();
+            }
+
+            for (device_id, device) in &stored_devices {
+                let is_current = current_devices.contains(device_id);
+                let is_changed = changes.changed.iter().any(|d| d.device_id() == &**device_id);
+
+                if is_current && !is_changed && device.pin() {
+                    changes.changed.push(device.clone());
+                }
+            }
+        }
+
         Ok(changes)
     }
 
@@ -829,8 +969,28 @@ impl IdentityManager {
     pub async fn users_for_key_query(
         &self,
     ) -> StoreResult<BTreeMap<OwnedTransactionId, KeysQueryRequest>> {
-        // Forget about any previous key queries in flight.
-        *self.keys_query_request_details.lock().await = None;
+        let config = self.config();
+
+        {
+            let mut request_details = self.keys_query_request_details.lock().await;
+
+            if config.coalesce_in_flight_queries {
+                if let Some(details) = request_details.as_ref().filter(|details| {
+                    !details.request_ids.is_empty()
+                        && details.created_at.elapsed() < Self::IN_FLIGHT_KEY_QUERY_TIMEOUT
+                }) {
+                    debug!(
+                        request_ids = ?details.request_ids,
+                        "Waiting for the in-flight `/keys/query` requests before creating new ones"
+                    );
+
+                    return Ok(BTreeMap::new());
+                }
+            }
+
+            // Forget about any previous key queries in flight.
+            *request_details = None;
+        }
 
         // We always want to track our own user, but in case we aren't in an encrypted
         // room yet, we won't be tracking ourselves yet. This ensures we are always
@@ -860,6 +1020,10 @@ impl IdentityManager {
             // certain amount of time.
             let users = users.into_iter().filter(|u| !self.failures.contains(u.server_name()));
 
+            // Hold back the users whose keys were queried too recently, they stay in the
+            // list of users waiting for a key query until the interval elapsed.
+            let users = self.throttle_users(users, config.min_query_interval);
+
             // We don't want to create a single `/keys/query` request with an infinite
             // amount of users. Some servers will likely bail out after a
             // certain amount of users and the responses will be large. In the
@@ -868,11 +1032,10 @@ impl IdentityManager {
             //
             // Convert the set of users into multiple /keys/query requests.
             let requests: BTreeMap<_, _> = users
-                .chunks(Self::MAX_KEY_QUERY_USERS)
-                .into_iter()
+                .chunks(config.max_users_per_request.max(1))
                 .map(|user_chunk| {
                     let request_id = TransactionId::new();
-                    let request = KeysQueryRequest::new(user_chunk);
+                    let request = KeysQueryRequest::new(user_chunk.iter().cloned());
 
                     debug!(?request_id, users = ?request.device_keys.keys(), "Created a /keys/query request");
 
@@ -884,7 +1047,11 @@ impl IdentityManager {
             // `receive_keys_query_response()` method to figure out if the user can be
             // marked as up-to-date/non-dirty.
             let request_ids = requests.keys().cloned().collect();
-            let request_details = KeysQueryRequestDetails { sequence_number, request_ids };
+            let request_details = KeysQueryRequestDetails {
+                sequence_number,
+                request_ids,
+                created_at: Instant::now(),
+            };
 
             *self.keys_query_request_details.lock().await = Some(request_details);
 
@@ -892,6 +1059,36 @@ impl IdentityManager {
         }
     }
 
+    /// Filter out the users whose keys were queried less than
+    /// `min_query_interval` ago, and record the query time of the remaining
+    /// ones.
+    fn throttle_users(
+        &self,
+        users: impl Iterator<Item = OwnedUserId>,
+        min_query_interval: Duration,
+    ) -> Vec<OwnedUserId> {
+        let mut last_key_queries = self.last_key_queries.lock().unwrap();
+
+        if min_query_interval.is_zero() {
+            last_key_queries.clear();
+            return users.collect();
+        }
+
+        last_key_queries.retain(|_, last_query| last_query.elapsed() < min_query_interval);
+
+        let now = Instant::now();
+        let (throttled, users): (Vec<_>, Vec<_>) =
+            users.partition(|user_id| last_key_queries.contains_key(user_id));
+
+        if !throttled.is_empty() {
+            debug!(?throttled, "Holding back the key query of users which were queried recently");
+        }
+
+        last_key_queries.extend(users.iter().map(|user_id| (user_id.clone(), now)));
+
+        users
+    }
+
     /// Receive the list of users that contained changed devices from the
     /// `/sync` response.
     ///
@@ -1114,11 +1311,21 @@ impl IdentityManager {
             }
 
             last_session_id = None;
+            let mut upgrades = Vec::new();
+
             for session in &mut sessions {
                 last_session_id = Some(session.session_id().to_owned());
+
+                let old_sender_data = session.sender_data.clone();
                 self.update_sender_data_for_session(session, device).await?;
+
+                if session.sender_data.compare_trust_level(&old_sender_data).is_gt() {
+                    upgrades.push(SenderDataUpgrade::new(session, &old_sender_data));
+                }
             }
+
             self.store.save_inbound_group_sessions(&sessions).await?;
+            self.store.notify_sender_data_upgrades(upgrades);
         }
     }
 
@@ -1520,7 +1727,7 @@ pub(crate) mod testing {
 
 #[cfg(test)]
 pub(crate) mod tests {
-    use std::ops::Deref;
+    use std::{ops::Deref, time::Duration};
 
     use futures_util::pin_mut;
     use matrix_sdk_test::{async_test, ruma_response_from_json, test_json};
@@ -1531,8 +1738,11 @@ pub(crate) mod tests {
     use serde_json::json;
     use stream_assert::{assert_closed, assert_pending, assert_ready};
 
-    use super::testing::{
-        device_id, key_query, manager_test_helper, other_key_query, other_user_id, user_id,
+    use super::{
+        testing::{
+            device_id, key_query, manager_test_helper, other_key_query, other_user_id, user_id,
+        },
+        KeyQueryConfig,
     };
     use crate::{
         identities::manager::testing::{other_key_query_cross_signed, own_key_query},
@@ -1839,6 +2049,89 @@ pub(crate) mod tests {
             .any(|(_, r)| r.device_keys.contains_key(alice)));
     }
 
+    #[async_test]
+    async fn test_key_query_max_users_per_request() {
+        let manager = manager_test_helper(user_id(), device_id()).await;
+        manager.set_config(KeyQueryConfig { max_users_per_request: 1, ..Default::default() });
+        manager.update_tracked_users([user_id(), other_user_id()]).await.unwrap();
+
+        // Each user gets their own request.
+        let requests = manager.users_for_key_query().await.unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests.values().all(|r| r.device_keys.len() == 1));
+    }
+
+    #[async_test]
+    async fn test_key_query_min_interval() {
+        let manager = manager_test_helper(user_id(), device_id()).await;
+        manager.set_config(KeyQueryConfig {
+            min_query_interval: Duration::from_secs(60 * 60),
+            ..Default::default()
+        });
+
+        let alice = other_user_id();
+        manager.update_tracked_users([alice]).await.unwrap();
+
+        let (reqid, req) = manager.users_for_key_query().await.unwrap().pop_first().unwrap();
+        assert!(req.device_keys.contains_key(alice));
+        manager.receive_keys_query_response(&reqid, &other_key_query()).await.unwrap();
+
+        // The devices of alice change again right away.
+        {
+            let cache = manager.store.cache().await.unwrap();
+            manager.receive_device_changes(&cache, [alice].into_iter()).await.unwrap();
+        }
+
+        // She was queried too recently, so she's held back.
+        let requests = manager.users_for_key_query().await.unwrap();
+        assert!(!requests.values().any(|r| r.device_keys.contains_key(alice)));
+
+        let pending = manager.pending_key_queries().await.unwrap();
+        assert!(pending.throttled_users.contains(alice));
+        assert!(!pending.users.contains(alice));
+
+        // Once the throttling is disabled, she's queried again.
+        manager.set_config(KeyQueryConfig::default());
+        let requests = manager.users_for_key_query().await.unwrap();
+        assert!(requests.values().any(|r| r.device_keys.contains_key(alice)));
+    }
+
+    #[async_test]
+    async fn test_key_query_coalescing() {
+        let manager = manager_test_helper(user_id(), device_id()).await;
+        manager
+            .set_config(KeyQueryConfig { coalesce_in_flight_queries: true, ..Default::default() });
+
+        let alice = other_user_id();
+        manager.update_tracked_users([alice]).await.unwrap();
+
+        let (reqid, req) = manager.users_for_key_query().await.unwrap().pop_first().unwrap();
+        assert!(req.device_keys.contains_key(alice));
+
+        // Another invalidation turns up while the request is in flight.
+        {
+            let cache = manager.store.cache().await.unwrap();
+            manager.receive_device_changes(&cache, [alice].into_iter()).await.unwrap();
+        }
+
+        // No new request is created until the response of the in-flight one is
+        // received.
+        assert!(manager.users_for_key_query().await.unwrap().is_empty());
+
+        let pending = manager.pending_key_queries().await.unwrap();
+        assert!(pending.users.contains(alice));
+        assert!(pending.in_flight_requests.contains(&reqid));
+
+        manager.receive_keys_query_response(&reqid, &other_key_query()).await.unwrap();
+
+        // The invalidation is handled in the next batch.
+        let pending = manager.pending_key_queries().await.unwrap();
+        assert!(pending.in_flight_requests.is_empty());
+
+        let (_, req) = manager.users_for_key_query().await.unwrap().pop_first().unwrap();
+        assert!(req.device_keys.contains_key(alice));
+    }
+
     #[async_test]
     async fn test_out_of_band_key_query() {
         // build the request
@@ -1930,6 +2223,33 @@ pub(crate) mod tests {
         assert!(!update.new.is_empty(), "The device update should contain some devices");
     }
 
+    #[async_test]
+    async fn test_devices_stream_contains_deleted_devices() {
+        let manager = manager_test_helper(user_id(), device_id()).await;
+        let (request_id, _) = manager.build_key_query_for_users(vec![other_user_id()]);
+        manager.receive_keys_query_response(&request_id, &other_key_query()).await.unwrap();
+
+        let stream = manager.store.devices_stream();
+        pin_mut!(stream);
+
+        // The other user deleted all of their devices.
+        let response = ruma_response_from_json(&json!({
+            "device_keys": {
+                other_user_id(): {}
+            }
+        }));
+        let (request_id, _) = manager.build_key_query_for_users(vec![other_user_id()]);
+        manager.receive_keys_query_response(&request_id, &response).await.unwrap();
+
+        let update = assert_ready!(stream);
+        assert!(update.new.is_empty());
+        assert!(update.changed.is_empty());
+
+        let deleted = update.deleted.get(other_user_id()).expect("The device should be deleted");
+        let device = deleted.get(device_id!("SKISMLNIMH")).unwrap();
+        assert!(device.is_deleted());
+    }
+
     #[async_test]
     async fn test_identities_stream() {
         let manager = manager_test_helper(user_id(), device_id()).await;
@@ -2428,13 +2748,14 @@ pub(crate) mod tests {
 
     mod update_sender_data {
         use assert_matches::assert_matches;
+        use futures_util::{pin_mut, FutureExt, StreamExt};
         use matrix_sdk_test::async_test;
         use ruma::room_id;
 
         use super::{device_id, manager_test_helper};
         use crate::{
             identities::manager::testing::{other_user_id, user_id},
-            olm::{InboundGroupSession, SenderData},
+            olm::{InboundGroupSession, SenderData, SenderDataType},
             store::types::{Changes, DeviceChanges},
             Account, DeviceData, EncryptionSettings,
         };
@@ -2503,6 +2824,58 @@ pub(crate) mod tests {
             }
         }
 
+        #[async_test]
+        async fn test_notifies_about_sender_data_upgrades() {
+            let manager = manager_test_helper(user_id(), device_id()).await;
+            let upgrades_stream = manager.store.sender_data_upgrades_stream();
+            pin_mut!(upgrades_stream);
+
+            // Given that we have a session from a device we don't know about yet
+            let account = Account::new(user_id());
+            let session = create_inbound_group_session(&account).await;
+            manager
+                .store
+                .save_changes(Changes {
+                    inbound_group_sessions: vec![session.clone()],
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+
+            // When we get an update for the device
+            let device_data = DeviceData::from_account(&account);
+            manager
+                .update_sender_data_from_device_changes(&DeviceChanges {
+                    changed: vec![device_data.clone()],
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+
+            // Then the upgrade of the session should be notified
+            let upgrades = upgrades_stream
+                .next()
+                .now_or_never()
+                .flatten()
+                .expect("We should have been notified about the sender data upgrade");
+            assert_eq!(upgrades.len(), 1);
+            assert_eq!(upgrades[0].session_id, session.session_id());
+            assert_eq!(upgrades[0].room_id, session.room_id());
+            assert_eq!(upgrades[0].old_sender_data_type, SenderDataType::UnknownDevice);
+            assert_eq!(upgrades[0].new_sender_data_type, SenderDataType::DeviceInfo);
+
+            // And when we get the same update again, nothing should be notified since the
+            // sender data didn't change
+            manager
+                .update_sender_data_from_device_changes(&DeviceChanges {
+                    changed: vec![device_data],
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+            assert!(upgrades_stream.next().now_or_never().is_none());
+        }
+
         /// Create an InboundGroupSession sent from the given account
         async fn create_inbound_group_session(account: &Account) -> InboundGroupSession {
             let (_, igs) = account
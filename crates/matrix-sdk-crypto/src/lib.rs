@@ -45,7 +45,10 @@ pub mod testing {
     };
 }
 
-use std::collections::{BTreeMap, BTreeSet};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::Duration,
+};
 
 pub use identities::room_identity_state::{
     IdentityState, IdentityStatusChange, RoomIdentityChange, RoomIdentityProvider,
@@ -78,23 +81,32 @@ impl RoomKeyImportResult {
 }
 
 pub use error::{
-    EventError, MegolmError, OlmError, SessionCreationError, SessionRecipientCollectionError,
-    SetRoomSettingsError, SignatureError,
+    EventError, MegolmError, OlmError, PurgeInboundGroupSessionsError, SessionCreationError,
+    SessionRecipientCollectionError, SetRoomSettingsError, SignatureError,
 };
 pub use file_encryption::{
     decrypt_room_key_export, encrypt_room_key_export, AttachmentDecryptor, AttachmentEncryptor,
     DecryptorError, KeyExportError, MediaEncryptionInfo,
 };
-pub use gossiping::{GossipRequest, GossippedSecret};
+pub use gossiping::{
+    GossipRequest, GossippedSecret, IncomingKeyRequestDecision, IncomingKeyRequestLogEntry,
+    KeyRequestDirection, KeyRequestFulfillment, KeyRequestLogEntry, KeyRequestLogFilter,
+    OutgoingKeyRequestLogEntry,
+};
 pub use identities::{
-    Device, DeviceData, LocalTrust, OtherUserIdentity, OtherUserIdentityData, OwnUserIdentity,
-    OwnUserIdentityData, UserDevices, UserIdentity, UserIdentityData,
+    Device, DeviceData, KeyQueryConfig, LocalTrust, OtherUserIdentity, OtherUserIdentityData,
+    OwnUserIdentity, OwnUserIdentityData, PendingKeyQueries, UserDevices, UserIdentity,
+    UserIdentityData,
+};
+#[cfg(feature = "experimental-send-custom-to-device")]
+pub use machine::EncryptToDeviceOptions;
+pub use machine::{
+    CrossSigningBootstrapRequests, EncryptionSyncChanges, OlmMachine, ToDeviceUtdInfo,
 };
-pub use machine::{CrossSigningBootstrapRequests, EncryptionSyncChanges, OlmMachine};
 use matrix_sdk_common::deserialized_responses::{DecryptedRoomEvent, UnableToDecryptInfo};
 #[cfg(feature = "qrcode")]
 pub use matrix_sdk_qrcode;
-pub use olm::{Account, CrossSigningStatus, EncryptionSettings, Session};
+pub use olm::{Account, CrossSigningStatus, EncryptionSettings, RoomKeyRotationLimits, Session};
 use serde::{Deserialize, Serialize};
 pub use session_manager::CollectStrategy;
 pub use store::{
@@ -103,7 +115,7 @@ pub use store::{
 };
 pub use verification::{
     format_emojis, AcceptSettings, AcceptedProtocols, CancelInfo, Emoji, EmojiShortAuthString, Sas,
-    SasState, Verification, VerificationRequest, VerificationRequestState,
+    SasState, ShortAuthString, Verification, VerificationRequest, VerificationRequestState,
 };
 #[cfg(feature = "qrcode")]
 pub use verification::{QrVerification, QrVerificationState, ScanError};
@@ -141,6 +153,71 @@ pub struct DecryptionSettings {
     /// event. If the sender's device is not sufficiently trusted,
     /// [`MegolmError::SenderIdentityNotTrusted`] will be returned.
     pub sender_device_trust_requirement: TrustRequirement,
+
+    /// Trust requirements for specific types of to-device events, overriding
+    /// [`Self::sender_device_trust_requirement`].
+    ///
+    /// The keys are the types of the decrypted to-device events, for example
+    /// `m.secret.send`. A to-device event of one of those types is only
+    /// accepted if the sender's device satisfies the given requirement, even
+    /// if its type is usually accepted from unverified devices, like
+    /// `m.room_key`. Otherwise the event is reported as undecryptable with
+    /// [`ToDeviceUnableToDecryptReason::UnverifiedSenderDeviceForEventType`].
+    ///
+    /// [`ToDeviceUnableToDecryptReason::UnverifiedSenderDeviceForEventType`]: matrix_sdk_common::deserialized_responses::ToDeviceUnableToDecryptReason::UnverifiedSenderDeviceForEventType
+    #[serde(default)]
+    pub to_device_trust_requirements: BTreeMap<String, TrustRequirement>,
+
+    /// The policy used to recover from wedged Olm sessions, i.e. when a
+    /// to-device event couldn't be decrypted because the Olm session with
+    /// the sender got corrupted.
+    #[serde(default)]
+    pub unwedging_policy: UnwedgingPolicy,
+}
+
+/// Policy controlling how we try to unwedge an Olm session, see
+/// [`DecryptionSettings::unwedging_policy`].
+///
+/// Unwedging an Olm session means creating a new Olm session with the sender
+/// of a to-device event which failed to decrypt, because the existing session
+/// is likely corrupted.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Record))]
+pub struct UnwedgingPolicy {
+    /// The minimum time between two attempts to unwedge the Olm session with
+    /// the same device, identified by its Curve25519 key.
+    ///
+    /// No attempt is made either if the most recent Olm session with the
+    /// device was created more recently than this.
+    pub min_interval: Duration,
+
+    /// Whether to send an encrypted `m.dummy` event to the device once a new
+    /// Olm session has been created, so that the other side learns about the
+    /// new session right away.
+    pub send_dummy_event: bool,
+
+    /// Whether to re-send the room key requests we have sent out before, once
+    /// a new Olm session has been created with one of our own devices.
+    ///
+    /// The room keys our other devices sent us over the wedged session
+    /// couldn't be decrypted, re-requesting them gives those devices a chance
+    /// to send them again over the new session.
+    pub re_request_room_keys: bool,
+}
+
+impl UnwedgingPolicy {
+    /// The default value of [`UnwedgingPolicy::min_interval`].
+    pub const DEFAULT_MIN_INTERVAL: Duration = Duration::from_secs(60 * 60);
+}
+
+impl Default for UnwedgingPolicy {
+    fn default() -> Self {
+        Self {
+            min_interval: Self::DEFAULT_MIN_INTERVAL,
+            send_dummy_event: true,
+            re_request_room_keys: false,
+        }
+    }
 }
 
 /// The result of an attempt to decrypt a room event: either a successful
@@ -620,7 +697,9 @@ pub enum RoomEventDecryptionResult {
 ///     };
 ///
 ///     let decryption_settings = DecryptionSettings {
-///         sender_device_trust_requirement: TrustRequirement::Untrusted
+///         sender_device_trust_requirement: TrustRequirement::Untrusted,
+///         to_device_trust_requirements: Default::default(),
+///         unwedging_policy: Default::default(),
 ///     };
 ///
 ///     // Push the sync changes into the OlmMachine, make sure that this is
@@ -671,7 +750,11 @@ pub enum RoomEventDecryptionResult {
 /// # let encrypted = unimplemented!();
 /// # let room_id = unimplemented!();
 /// # let machine: OlmMachine = unimplemented!();
-/// # let settings = DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+/// # let settings = DecryptionSettings {
+/// #     sender_device_trust_requirement: TrustRequirement::Untrusted,
+/// #     to_device_trust_requirements: Default::default(),
+/// #     unwedging_policy: Default::default(),
+/// # };
 /// // Decrypt your room events now.
 /// let decrypted = machine
 ///     .decrypt_room_event(encrypted, room_id, &settings)
@@ -814,7 +897,9 @@ pub enum RoomEventDecryptionResult {
 ///     };
 ///
 ///     let decryption_settings = DecryptionSettings {
-///         sender_device_trust_requirement: TrustRequirement::Untrusted
+///         sender_device_trust_requirement: TrustRequirement::Untrusted,
+///         to_device_trust_requirements: Default::default(),
+///         unwedging_policy: Default::default(),
 ///     };
 ///
 ///     // Push the sync changes into the OlmMachine, make sure that this is
@@ -980,7 +1065,11 @@ pub enum RoomEventDecryptionResult {
 /// # let room_id = unimplemented!();
 /// # let event = unimplemented!();
 /// # let machine: OlmMachine = unimplemented!();
-/// # let settings = DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+/// # let settings = DecryptionSettings {
+/// #     sender_device_trust_requirement: TrustRequirement::Untrusted,
+/// #     to_device_trust_requirements: Default::default(),
+/// #     unwedging_policy: Default::default(),
+/// # };
 /// let content = AnyMessageLikeEventContent::RoomMessage(RoomMessageEventContent::text_plain("It's a secret to everybody."));
 /// let encrypted_content = machine.encrypt_room_event(room_id, content).await?;
 /// # Ok(())
@@ -1124,6 +1213,8 @@ mod test {
     fn snapshot_decryption_settings() {
         assert_json_snapshot!(DecryptionSettings {
             sender_device_trust_requirement: TrustRequirement::Untrusted,
+            to_device_trust_requirements: Default::default(),
+            unwedging_policy: Default::default(),
         });
     }
 }
@@ -13,22 +13,21 @@
 // limitations under the License.
 
 use std::{
-    collections::{BTreeMap, HashMap, HashSet},
+    collections::{hash_map::Entry, BTreeMap, HashMap, HashSet},
     sync::Arc,
     time::Duration,
 };
 
+use futures_core::Stream;
 use itertools::Itertools;
-#[cfg(feature = "experimental-send-custom-to-device")]
-use matrix_sdk_common::deserialized_responses::WithheldCode;
 use matrix_sdk_common::{
     deserialized_responses::{
         AlgorithmInfo, DecryptedRoomEvent, DeviceLinkProblem, EncryptionInfo,
         ProcessedToDeviceEvent, ToDeviceUnableToDecryptInfo, ToDeviceUnableToDecryptReason,
         UnableToDecryptInfo, UnableToDecryptReason, UnsignedDecryptionResult,
-        UnsignedEventLocation, VerificationLevel, VerificationState,
+        UnsignedEventLocation, VerificationLevel, VerificationState, WithheldCode,
     },
-    locks::RwLock as StdRwLock,
+    locks::{Mutex as StdMutex, RwLock as StdRwLock},
     BoxFuture,
 };
 use ruma::{
@@ -49,7 +48,8 @@ use ruma::{
     },
     serde::{JsonObject, Raw},
     DeviceId, MilliSecondsSinceUnixEpoch, OneTimeKeyAlgorithm, OwnedDeviceId, OwnedDeviceKeyId,
-    OwnedTransactionId, OwnedUserId, RoomId, TransactionId, UInt, UserId,
+    OwnedRoomId, OwnedTransactionId, OwnedUserId, RoomId, SecondsSinceUnixEpoch, TransactionId,
+    UInt, UserId,
 };
 use serde_json::{value::to_raw_value, Value};
 use tokio::sync::Mutex;
@@ -66,9 +66,14 @@ use vodozemac::{
 use crate::{
     backups::{BackupMachine, MegolmV1BackupKey},
     dehydrated_devices::{DehydratedDevices, DehydrationError},
-    error::{EventError, MegolmError, MegolmResult, OlmError, OlmResult, SetRoomSettingsError},
-    gossiping::GossipMachine,
-    identities::{user::UserIdentity, Device, IdentityManager, UserDevices},
+    error::{
+        EventError, MegolmError, MegolmResult, OlmError, OlmResult, PurgeInboundGroupSessionsError,
+        SetRoomSettingsError,
+    },
+    gossiping::{GossipMachine, KeyRequestLogEntry, KeyRequestLogFilter},
+    identities::{
+        user::UserIdentity, Device, IdentityManager, KeyQueryConfig, PendingKeyQueries, UserDevices,
+    },
     olm::{
         Account, CrossSigningStatus, EncryptionSettings, IdentityKeys, InboundGroupSession,
         KnownSenderData, OlmDecryptionInfo, PrivateCrossSigningIdentity, SenderData,
@@ -78,15 +83,19 @@ use crate::{
     store::{
         caches::StoreCache,
         types::{
-            Changes, CrossSigningKeyExport, DeviceChanges, IdentityChanges, PendingChanges,
-            RoomKeyInfo, RoomSettings, StoredRoomKeyBundleData,
+            Changes, CrossSigningKeyExport, DeviceChanges, IdentityChanges,
+            InboundGroupSessionFilter, PendingChanges, RoomKeyInfo, RoomSettings,
+            SenderDataUpgrade, StoredRoomKeyBundleData,
         },
         CryptoStoreWrapper, IntoCryptoStore, MemoryStore, Result as StoreResult, SecretImportError,
         Store, StoreTransaction,
     },
     types::{
         events::{
-            olm_v1::{AnyDecryptedOlmEvent, DecryptedRoomKeyBundleEvent, DecryptedRoomKeyEvent},
+            olm_v1::{
+                AnyDecryptedOlmEvent, DecryptedRoomKeyBundleEvent, DecryptedRoomKeyEvent,
+                DecryptedSecretSendEvent,
+            },
             room::encrypted::{
                 EncryptedEvent, EncryptedToDeviceEvent, RoomEncryptedEventContent,
                 RoomEventEncryptionScheme, SupportedEventEncryptionSchemes,
@@ -111,6 +120,10 @@ use crate::{
     RoomEventDecryptionResult, SignatureError, TrustRequirement,
 };
 
+/// The number of inbound group sessions which are loaded from, or deleted
+/// from, the store at once when counting or purging them.
+const INBOUND_GROUP_SESSIONS_BATCH_SIZE: usize = 1000;
+
 /// State machine implementation of the Olm/Megolm encryption protocol used for
 /// Matrix end to end encryption.
 #[derive(Clone)]
@@ -147,6 +160,14 @@ pub struct OlmMachineInner {
     identity_manager: IdentityManager,
     /// A state machine that handles creating room key backups.
     backup_machine: BackupMachine,
+    /// The `m.secret.send` events received by a rehydrated device, along with
+    /// the Curve25519 key of their sender.
+    ///
+    /// They are collected to be handled by our own [`OlmMachine`], since the
+    /// rehydrated device doesn't know about our secret requests. This is
+    /// `None` if this isn't a rehydrated device.
+    pub(crate) rehydrated_secrets:
+        Option<StdMutex<Vec<(Curve25519PublicKey, DecryptedSecretSendEvent)>>>,
 }
 
 #[cfg(not(tarpaulin_include))]
@@ -209,6 +230,7 @@ impl OlmMachine {
             identity_manager,
             self.store().private_identity(),
             None,
+            true,
         ))
     }
 
@@ -233,6 +255,7 @@ impl OlmMachine {
         identity_manager: IdentityManager,
         user_identity: Arc<Mutex<PrivateCrossSigningIdentity>>,
         maybe_backup_key: Option<MegolmV1BackupKey>,
+        rehydrated: bool,
     ) -> Self {
         let group_session_manager = GroupSessionManager::new(store.clone());
 
@@ -260,6 +283,7 @@ impl OlmMachine {
             key_request_machine,
             identity_manager,
             backup_machine,
+            rehydrated_secrets: rehydrated.then(Default::default),
         });
 
         Self { inner }
@@ -398,6 +422,7 @@ impl OlmMachine {
             identity_manager,
             identity,
             maybe_backup_key,
+            false,
         ))
     }
 
@@ -509,6 +534,53 @@ impl OlmMachine {
         self.inner.key_request_machine.is_room_key_forwarding_enabled()
     }
 
+    /// Enable or disable the room key request log.
+    ///
+    /// While enabled, the incoming `m.room_key_request` messages are recorded
+    /// in the crypto store, together with the decision we took for them, as
+    /// well as the room key requests we send out and the room keys we accept
+    /// as answers. The log is disabled by default, since it grows the store.
+    ///
+    /// See also [`OlmMachine::key_request_log()`] and
+    /// [`OlmMachine::prune_key_request_log()`].
+    pub fn set_key_request_log_enabled(&self, enable: bool) {
+        self.inner.key_request_machine.key_request_log().set_enabled(enable)
+    }
+
+    /// Is the room key request log enabled?
+    ///
+    /// See also [`OlmMachine::set_key_request_log_enabled()`].
+    pub fn is_key_request_log_enabled(&self) -> bool {
+        self.inner.key_request_machine.key_request_log().is_enabled()
+    }
+
+    /// Get the most recent entries of the room key request log which match the
+    /// given filter, the newest entries first.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - The maximum number of entries to return.
+    ///
+    /// * `filter` - The filter the entries need to match.
+    pub async fn key_request_log(
+        &self,
+        limit: usize,
+        filter: &KeyRequestLogFilter,
+    ) -> StoreResult<Vec<KeyRequestLogEntry>> {
+        self.inner.key_request_machine.key_request_log().entries(limit, filter).await
+    }
+
+    /// Remove the entries of the room key request log which were recorded
+    /// before the given time.
+    ///
+    /// Returns the number of removed entries.
+    pub async fn prune_key_request_log(
+        &self,
+        before: MilliSecondsSinceUnixEpoch,
+    ) -> StoreResult<usize> {
+        self.inner.key_request_machine.key_request_log().prune(before).await
+    }
+
     /// Get the outgoing requests that need to be sent out.
     ///
     /// This returns a list of [`OutgoingRequest`]. Those requests need to be
@@ -574,6 +646,32 @@ impl OlmMachine {
         self.inner.identity_manager.build_key_query_for_users(users)
     }
 
+    /// Set the configuration of the `/keys/query` requests returned by
+    /// [`OlmMachine::outgoing_requests()`], when the devices of the tracked
+    /// users change.
+    ///
+    /// This allows to throttle and batch the key queries of busy accounts.
+    /// It doesn't affect the out-of-band requests created with
+    /// [`OlmMachine::query_keys_for_users()`].
+    pub fn set_key_query_config(&self, config: KeyQueryConfig) {
+        self.inner.identity_manager.set_config(config)
+    }
+
+    /// Get the configuration of the `/keys/query` requests.
+    ///
+    /// See also [`OlmMachine::set_key_query_config()`].
+    pub fn key_query_config(&self) -> KeyQueryConfig {
+        self.inner.identity_manager.config()
+    }
+
+    /// Get the users whose keys need to be queried, and the `/keys/query`
+    /// requests which are in flight.
+    ///
+    /// This is mostly useful for debugging.
+    pub async fn pending_key_queries(&self) -> StoreResult<PendingKeyQueries> {
+        self.inner.identity_manager.pending_key_queries().await
+    }
+
     /// Mark the request with the given request id as sent.
     ///
     /// # Arguments
@@ -782,6 +880,21 @@ impl OlmMachine {
         self.inner.session_manager.get_missing_sessions(users).await
     }
 
+    /// Get the time of the last attempt to unwedge the Olm session with the
+    /// device owning the given Curve25519 key.
+    ///
+    /// Attempts are throttled according to
+    /// [`UnwedgingPolicy::min_interval`], the time of the last attempt is
+    /// persisted in the store so the throttling survives restarts.
+    ///
+    /// [`UnwedgingPolicy::min_interval`]: crate::UnwedgingPolicy::min_interval
+    pub async fn last_unwedging_attempt(
+        &self,
+        sender_key: Curve25519PublicKey,
+    ) -> StoreResult<Option<MilliSecondsSinceUnixEpoch>> {
+        self.inner.session_manager.last_unwedging_attempt(sender_key).await
+    }
+
     /// Receive a successful `/keys/query` response.
     ///
     /// Returns a list of newly discovered devices and devices that changed.
@@ -1140,6 +1253,30 @@ impl OlmMachine {
         self.inner.group_session_manager.share_room_key(room_id, users, encryption_settings).await
     }
 
+    /// Get the devices of the given users which won't receive the room keys
+    /// shared with the given [`CollectStrategy`].
+    ///
+    /// This can be used to warn the user, before sending a message, that some
+    /// devices of the room members won't be able to decrypt it, for instance
+    /// because they aren't cross-signed by their owner. The devices are
+    /// returned alongside the [`WithheldCode`] they will be sent.
+    ///
+    /// # Arguments
+    ///
+    /// `users` - The list of users that should receive the room key.
+    ///
+    /// `collect_strategy` - The strategy used to collect the devices which
+    /// receive the room key, usually the
+    /// [`sharing_strategy`](EncryptionSettings::sharing_strategy) of the
+    /// settings passed to [`OlmMachine::share_room_key`].
+    pub async fn devices_withheld_from_room_key(
+        &self,
+        users: impl Iterator<Item = &UserId>,
+        collect_strategy: &CollectStrategy,
+    ) -> OlmResult<Vec<(DeviceData, WithheldCode)>> {
+        self.inner.group_session_manager.devices_withheld_by_strategy(users, collect_strategy).await
+    }
+
     /// Encrypts the given content using Olm for each of the given devices.
     ///
     /// The 1-to-1 session must be established prior to this
@@ -1184,6 +1321,76 @@ impl OlmMachine {
 
         result
     }
+
+    /// Encrypt the given content for all the devices of the given users, and
+    /// build the to-device requests to send it out.
+    ///
+    /// Our own device, as well as the deleted devices, are ignored. The
+    /// blacklisted devices, and the devices skipped because of the given
+    /// `options`, are returned alongside the devices for which no Olm session
+    /// exists, with the reason why they didn't get the event.
+    ///
+    /// The Olm sessions with the recipient devices must be established prior
+    /// to this call, by using the [`OlmMachine::get_missing_sessions`] method
+    /// for the same users, or these devices are returned with the
+    /// [`WithheldCode::NoOlm`] code.
+    ///
+    /// # Returns
+    ///
+    /// A list of `ToDeviceRequest` to send out the event, and the list of
+    /// devices which didn't get the event.
+    #[cfg(feature = "experimental-send-custom-to-device")]
+    pub async fn encrypt_to_device_for_users(
+        &self,
+        users: &[&UserId],
+        event_type: &str,
+        content: &Value,
+        options: EncryptToDeviceOptions,
+    ) -> OlmResult<(Vec<ToDeviceRequest>, Vec<(DeviceData, WithheldCode)>)> {
+        let mut recipients = Vec::new();
+        let mut withheld = Vec::new();
+
+        for user_id in users {
+            let devices = self.get_user_devices(user_id, None).await?;
+
+            for device in devices.devices() {
+                if (device.user_id() == self.user_id() && device.device_id() == self.device_id())
+                    || device.is_deleted()
+                {
+                    continue;
+                }
+
+                let withheld_code = if device.is_blacklisted() {
+                    Some(WithheldCode::Blacklisted)
+                } else if (options.skip_unsigned_devices && !device.is_cross_signed_by_owner())
+                    || (options.skip_unverified_devices && !device.is_verified())
+                {
+                    Some(WithheldCode::Unverified)
+                } else {
+                    None
+                };
+
+                match withheld_code {
+                    Some(code) => {
+                        trace!(
+                            user_id = ?device.user_id(),
+                            device_id = ?device.device_id(),
+                            ?code,
+                            "Not encrypting a custom to-device event for a device"
+                        );
+                        withheld.push((device.inner, code));
+                    }
+                    None => recipients.push(device.inner),
+                }
+            }
+        }
+
+        let (requests, no_olm_devices) =
+            self.encrypt_content_for_devices(recipients, event_type, content).await?;
+        withheld.extend(no_olm_devices);
+
+        Ok((requests, withheld))
+    }
     /// Collect the devices belonging to the given user, and send the details of
     /// a room key bundle to those devices.
     ///
@@ -1260,11 +1467,15 @@ impl OlmMachine {
                 decrypted.inbound_group_session = session;
             }
             AnyDecryptedOlmEvent::SecretSend(e) => {
-                let name = self
-                    .inner
-                    .key_request_machine
-                    .receive_secret_event(cache, decrypted.result.sender_key, e, changes)
-                    .await?;
+                let name = if let Some(rehydrated_secrets) = &self.inner.rehydrated_secrets {
+                    rehydrated_secrets.lock().push((decrypted.result.sender_key, e.clone()));
+                    None
+                } else {
+                    self.inner
+                        .key_request_machine
+                        .receive_secret_event(cache, decrypted.result.sender_key, e, changes)
+                        .await?
+                };
 
                 // Set the secret name so other consumers of the event know
                 // what this event is about.
@@ -1319,6 +1530,25 @@ impl OlmMachine {
         self.inner.verification_machine.get_request(user_id, flow_id)
     }
 
+    /// Set the time after which the verification requests time out.
+    ///
+    /// Verification requests which weren't answered in time are cancelled with
+    /// an `m.timeout` code, and the cancellation is sent to the other side
+    /// for the requests sent over to-device messages. The time is counted
+    /// from the timestamp of the requests.
+    ///
+    /// Defaults to 10 minutes, as defined in the spec.
+    pub fn set_verification_request_timeout(&self, timeout: Duration) {
+        self.inner.verification_machine.set_request_timeout(timeout)
+    }
+
+    /// Get the time after which the verification requests time out.
+    ///
+    /// See also [`OlmMachine::set_verification_request_timeout()`].
+    pub fn verification_request_timeout(&self) -> Duration {
+        self.inner.verification_machine.request_timeout()
+    }
+
     /// Get all the verification requests of a given user.
     pub fn get_verification_requests(&self, user_id: &UserId) -> Vec<VerificationRequest> {
         self.inner.verification_machine.get_requests(user_id)
@@ -1460,23 +1690,55 @@ impl OlmMachine {
         {
             Ok(decrypted) => decrypted,
             Err(DecryptToDeviceError::OlmError(err)) => {
-                let reason = if let OlmError::UnverifiedSenderDevice = &err {
-                    ToDeviceUnableToDecryptReason::UnverifiedSenderDevice
-                } else {
-                    ToDeviceUnableToDecryptReason::DecryptionFailure
+                let reason = match &err {
+                    OlmError::UnverifiedSenderDevice => {
+                        ToDeviceUnableToDecryptReason::UnverifiedSenderDevice
+                    }
+                    OlmError::UnverifiedSenderDeviceForEventType { event_type } => {
+                        ToDeviceUnableToDecryptReason::UnverifiedSenderDeviceForEventType {
+                            event_type: event_type.clone(),
+                        }
+                    }
+                    _ => ToDeviceUnableToDecryptReason::DecryptionFailure,
                 };
 
+                let mut unwedging_triggered = false;
+
                 if let OlmError::SessionWedged(sender, curve_key) = err {
-                    if let Err(e) =
-                        self.inner.session_manager.mark_device_as_wedged(&sender, curve_key).await
+                    match self
+                        .inner
+                        .session_manager
+                        .mark_device_as_wedged(
+                            &sender,
+                            curve_key,
+                            &decryption_settings.unwedging_policy,
+                        )
+                        .await
                     {
-                        error!(
-                            error = ?e,
-                            "Couldn't mark device to be unwedged",
-                        );
+                        Ok(triggered) => unwedging_triggered = triggered,
+                        Err(e) => {
+                            error!(
+                                error = ?e,
+                                "Couldn't mark device to be unwedged",
+                            );
+                        }
                     }
                 }
 
+                let sender_key = match &e.content {
+                    ToDeviceEncryptedEventContent::OlmV1Curve25519AesSha2(c) => Some(c.sender_key),
+                    #[cfg(feature = "experimental-algorithms")]
+                    ToDeviceEncryptedEventContent::OlmV2Curve25519AesSha2(c) => Some(c.sender_key),
+                    ToDeviceEncryptedEventContent::Unknown(_) => None,
+                };
+
+                self.inner.store.crypto_store().notify_to_device_utd(ToDeviceUtdInfo {
+                    sender: e.sender.clone(),
+                    sender_key,
+                    reason: reason.clone(),
+                    unwedging_triggered,
+                });
+
                 return Some(ProcessedToDeviceEvent::UnableToDecrypt {
                     encrypted_event: raw_event,
                     utd_info: ToDeviceUnableToDecryptInfo { reason },
@@ -1613,6 +1875,21 @@ impl OlmMachine {
         Ok((events, room_key_updates))
     }
 
+    /// Receive notifications of the to-device events which couldn't be
+    /// decrypted as a [`Stream`].
+    ///
+    /// Every time a to-device event, received with
+    /// [`OlmMachine::receive_sync_changes()`], can't be decrypted, an update is
+    /// sent to the stream. This is useful to collect metrics, or to keep track
+    /// of the Olm sessions which got wedged.
+    ///
+    /// The stream never holds up the processing of the to-device events: if
+    /// the reader of the stream lags too far behind, a warning will be logged
+    /// and the oldest items will be dropped.
+    pub fn subscribe_to_to_device_utds(&self) -> impl Stream<Item = ToDeviceUtdInfo> {
+        self.inner.store.crypto_store().to_device_utds_stream()
+    }
+
     /// Initial processing of the changes specified within a sync response.
     ///
     /// Returns the to-device events (decrypted where needed and where possible)
@@ -1759,6 +2036,17 @@ impl OlmMachine {
             }
         };
 
+        // Devices which aren't signed by their owner might still be covered by the
+        // device pinning mode.
+        let verification_state = match (verification_state, &device_id) {
+            (VerificationState::Unverified(VerificationLevel::UnsignedDevice), Some(device_id))
+                if self.store().violates_device_pins(sender, device_id).await? =>
+            {
+                VerificationState::Unverified(VerificationLevel::PinViolation)
+            }
+            (verification_state, _) => verification_state,
+        };
+
         Ok((verification_state, device_id))
     }
 
@@ -1832,7 +2120,9 @@ impl OlmMachine {
                 // Yes - save it to the store
                 let mut new_session = session.clone();
                 new_session.sender_data = calculated_sender_data.clone();
+                let upgrade = SenderDataUpgrade::new(&new_session, &session.sender_data);
                 self.store().save_inbound_group_sessions(&[new_session]).await?;
+                self.store().notify_sender_data_upgrades(vec![upgrade]);
 
                 // and use it now.
                 calculated_sender_data
@@ -1943,9 +2233,11 @@ impl OlmMachine {
         event: &EncryptedEvent,
         content: &SupportedEventEncryptionSchemes<'_>,
         decryption_settings: &DecryptionSettings,
+        session_cache: Option<&mut InboundGroupSessionCache>,
     ) -> MegolmResult<(JsonObject, Arc<EncryptionInfo>)> {
-        let session =
-            self.get_inbound_group_session_or_error(room_id, content.session_id()).await?;
+        let session = self
+            .get_inbound_group_session_or_error(room_id, content.session_id(), session_cache)
+            .await?;
 
         // This function is only ever called by decrypt_room_event, so
         // room_id, sender, algorithm and session_id are recorded already
@@ -1957,6 +2249,20 @@ impl OlmMachine {
         let result = session.decrypt(event).await;
         match result {
             Ok((decrypted_event, _)) => {
+                if session.mark_as_used(SecondsSinceUnixEpoch::now()) {
+                    // Failing to record the time of last use isn't a reason to fail the
+                    // decryption.
+                    if let Err(error) = self
+                        .inner
+                        .store
+                        .crypto_store()
+                        .save_inbound_group_session_last_used(&session)
+                        .await
+                    {
+                        warn!(?error, "Couldn't save the time of last use of a room key");
+                    }
+                }
+
                 let encryption_info = self.get_encryption_info(&session, &event.sender).await?;
 
                 self.check_sender_trust_requirement(
@@ -2041,7 +2347,9 @@ impl OlmMachine {
                     (VerificationLevel::UnverifiedIdentity, _) => true,
 
                     // Case 2
-                    (VerificationLevel::UnsignedDevice, true) => true,
+                    (VerificationLevel::UnsignedDevice | VerificationLevel::PinViolation, true) => {
+                        true
+                    }
 
                     // Case 3
                     (VerificationLevel::None(_), true) => true,
@@ -2050,6 +2358,7 @@ impl OlmMachine {
                     (VerificationLevel::VerificationViolation, _)
                     | (VerificationLevel::MismatchedSender, _)
                     | (VerificationLevel::UnsignedDevice, false)
+                    | (VerificationLevel::PinViolation, false)
                     | (VerificationLevel::None(_), false) => false,
                 }
             }
@@ -2062,6 +2371,7 @@ impl OlmMachine {
                 VerificationLevel::VerificationViolation
                 | VerificationLevel::MismatchedSender
                 | VerificationLevel::UnsignedDevice
+                | VerificationLevel::PinViolation
                 | VerificationLevel::None(_) => false,
             },
         };
@@ -2077,12 +2387,26 @@ impl OlmMachine {
     ///
     /// If the session is not found, checks for withheld reports, and returns a
     /// [`MegolmError::MissingRoomKey`] error.
+    ///
+    /// If a `session_cache` is given, the session is only loaded from the store
+    /// if it isn't in the cache already, and added to it afterwards.
     async fn get_inbound_group_session_or_error(
         &self,
         room_id: &RoomId,
         session_id: &str,
+        session_cache: Option<&mut InboundGroupSessionCache>,
     ) -> MegolmResult<InboundGroupSession> {
-        match self.store().get_inbound_group_session(room_id, session_id).await? {
+        let session = match session_cache {
+            Some(cache) => match cache.entry((room_id.to_owned(), session_id.to_owned())) {
+                Entry::Occupied(entry) => entry.get().clone(),
+                Entry::Vacant(entry) => entry
+                    .insert(self.store().get_inbound_group_session(room_id, session_id).await?)
+                    .clone(),
+            },
+            None => self.store().get_inbound_group_session(room_id, session_id).await?,
+        };
+
+        match session {
             Some(session) => Ok(session),
             None => {
                 let withheld_code = self
@@ -2116,7 +2440,10 @@ impl OlmMachine {
         room_id: &RoomId,
         decryption_settings: &DecryptionSettings,
     ) -> Result<RoomEventDecryptionResult, CryptoStoreError> {
-        match self.decrypt_room_event_inner(raw_event, room_id, true, decryption_settings).await {
+        match self
+            .decrypt_room_event_inner(raw_event, room_id, true, decryption_settings, None)
+            .await
+        {
             Ok(decrypted) => Ok(RoomEventDecryptionResult::Decrypted(decrypted)),
             Err(err) => Ok(RoomEventDecryptionResult::UnableToDecrypt(megolm_error_to_utd_info(
                 raw_event, err,
@@ -2124,6 +2451,70 @@ impl OlmMachine {
         }
     }
 
+    /// Attempt to decrypt a batch of events from room timelines, returning
+    /// information on the failure for the ones that couldn't be decrypted.
+    ///
+    /// This is meant to retry the decryption of events which previously failed
+    /// to decrypt because of missing room keys, once the room keys have been
+    /// received, for example from the key backup or from one of our other
+    /// devices.
+    ///
+    /// Every room key is loaded from the store only once for the whole batch,
+    /// which makes this significantly faster than calling
+    /// [`OlmMachine::try_decrypt_room_event()`] for every event, since many of
+    /// them are usually encrypted with the same room keys.
+    ///
+    /// # Arguments
+    ///
+    /// * `events` - The events that should be decrypted, along with the ID of
+    ///   the room they were sent to.
+    ///
+    /// # Returns
+    ///
+    /// The result of the decryption of every event, in the same order as
+    /// `events`. An `Err` result is only returned if an internal error
+    /// occurred.
+    #[instrument(skip_all, fields(num_events = events.len()))]
+    pub async fn try_decrypt_room_events(
+        &self,
+        events: &[(OwnedRoomId, Raw<EncryptedEvent>)],
+        decryption_settings: &DecryptionSettings,
+    ) -> Result<Vec<RoomEventDecryptionResult>, CryptoStoreError> {
+        let mut session_cache = InboundGroupSessionCache::new();
+        let mut results = Vec::with_capacity(events.len());
+
+        for (room_id, raw_event) in events {
+            let result = match self
+                .decrypt_room_event_inner(
+                    raw_event,
+                    room_id,
+                    true,
+                    decryption_settings,
+                    Some(&mut session_cache),
+                )
+                .await
+            {
+                Ok(decrypted) => RoomEventDecryptionResult::Decrypted(decrypted),
+                Err(err) => RoomEventDecryptionResult::UnableToDecrypt(megolm_error_to_utd_info(
+                    raw_event, err,
+                )?),
+            };
+
+            results.push(result);
+        }
+
+        debug!(
+            num_sessions = session_cache.len(),
+            num_decrypted = results
+                .iter()
+                .filter(|r| matches!(r, RoomEventDecryptionResult::Decrypted(_)))
+                .count(),
+            "Decrypted a batch of room events"
+        );
+
+        Ok(results)
+    }
+
     /// Decrypt an event from a room timeline.
     ///
     /// # Arguments
@@ -2137,7 +2528,7 @@ impl OlmMachine {
         room_id: &RoomId,
         decryption_settings: &DecryptionSettings,
     ) -> MegolmResult<DecryptedRoomEvent> {
-        self.decrypt_room_event_inner(event, room_id, true, decryption_settings).await
+        self.decrypt_room_event_inner(event, room_id, true, decryption_settings, None).await
     }
 
     #[instrument(name = "decrypt_room_event", skip_all, fields(?room_id, event_id, origin_server_ts, sender, algorithm, session_id, message_index, sender_key))]
@@ -2147,6 +2538,7 @@ impl OlmMachine {
         room_id: &RoomId,
         decrypt_unsigned: bool,
         decryption_settings: &DecryptionSettings,
+        session_cache: Option<&mut InboundGroupSessionCache>,
     ) -> MegolmResult<DecryptedRoomEvent> {
         let event = event.deserialize()?;
 
@@ -2176,8 +2568,9 @@ impl OlmMachine {
         Span::current().record("session_id", content.session_id());
         Span::current().record("message_index", content.message_index());
 
-        let result =
-            self.decrypt_megolm_events(room_id, &event, &content, decryption_settings).await;
+        let result = self
+            .decrypt_megolm_events(room_id, &event, &content, decryption_settings, session_cache)
+            .await;
 
         if let Err(e) = &result {
             #[cfg(feature = "automatic-room-key-forwarding")]
@@ -2284,7 +2677,7 @@ impl OlmMachine {
 
             let raw_event = serde_json::from_value(event.clone()).ok()?;
             match self
-                .decrypt_room_event_inner(&raw_event, room_id, false, decryption_settings)
+                .decrypt_room_event_inner(&raw_event, room_id, false, decryption_settings, None)
                 .await
             {
                 Ok(decrypted_event) => {
@@ -2398,7 +2791,7 @@ impl OlmMachine {
         session_id: &str,
         sender: &UserId,
     ) -> MegolmResult<Arc<EncryptionInfo>> {
-        let session = self.get_inbound_group_session_or_error(room_id, session_id).await?;
+        let session = self.get_inbound_group_session_or_error(room_id, session_id, None).await?;
         self.get_encryption_info(&session, sender).await
     }
 
@@ -2763,7 +3156,7 @@ impl OlmMachine {
 
     /// Manage dehydrated devices.
     pub fn dehydrated_devices(&self) -> DehydratedDevices {
-        DehydratedDevices { inner: self.to_owned() }
+        DehydratedDevices { inner: self.to_owned(), skip_cross_signing_verification: false }
     }
 
     /// Get the stored encryption settings for the given room, such as the
@@ -2849,6 +3242,131 @@ impl OlmMachine {
         Ok(())
     }
 
+    /// Set the strategy used to collect the devices which receive the room keys
+    /// of the given room, overriding the client-wide strategy.
+    ///
+    /// Unlike the other [`RoomSettings`], the sharing strategy is a user
+    /// preference which can be changed at any time; passing `None` removes the
+    /// override. The strategy can be retrieved via
+    /// [`OlmMachine::room_settings`].
+    pub async fn set_room_sharing_strategy(
+        &self,
+        room_id: &RoomId,
+        sharing_strategy: Option<CollectStrategy>,
+    ) -> StoreResult<()> {
+        let store = &self.inner.store;
+
+        // Make sure that we don't race against a concurrent call to
+        // `set_room_settings`, see the comment there.
+        let _store_transaction = store.transaction().await;
+
+        let mut settings = store.get_room_settings(room_id).await?.unwrap_or_default();
+        settings.sharing_strategy = sharing_strategy;
+
+        store
+            .save_changes(Changes {
+                room_settings: HashMap::from([(room_id.to_owned(), settings)]),
+                ..Default::default()
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Count the inbound group sessions ("room keys") we have, per room.
+    ///
+    /// The sessions are loaded from the store in batches, so this can be used
+    /// with stores containing a large number of room keys.
+    pub async fn inbound_group_session_counts_by_room(
+        &self,
+    ) -> StoreResult<BTreeMap<OwnedRoomId, usize>> {
+        let mut counts = BTreeMap::new();
+        let mut after = None;
+
+        loop {
+            let batch = self
+                .inner
+                .store
+                .get_inbound_group_sessions_batch(after.take(), INBOUND_GROUP_SESSIONS_BATCH_SIZE)
+                .await?;
+
+            let Some(last) = batch.last() else { break };
+            after = Some((last.room_id().to_owned(), last.session_id().to_owned()));
+
+            for session in &batch {
+                *counts.entry(session.room_id().to_owned()).or_default() += 1;
+            }
+        }
+
+        Ok(counts)
+    }
+
+    /// Delete the inbound group sessions ("room keys") matching the given
+    /// filter from the store.
+    ///
+    /// Events encrypted with the deleted sessions can't be decrypted anymore,
+    /// unless the sessions are retrieved again, for example from the key
+    /// backup. For this reason, the purge is refused if some of the selected
+    /// sessions aren't backed up, unless `force` is `true`. In that case, no
+    /// session is deleted.
+    ///
+    /// The sessions are loaded and deleted in batches, so this can be used
+    /// with stores containing a large number of room keys.
+    ///
+    /// Returns the number of deleted sessions.
+    #[instrument(skip(self))]
+    pub async fn purge_inbound_group_sessions(
+        &self,
+        filter: &InboundGroupSessionFilter,
+        force: bool,
+    ) -> Result<usize, PurgeInboundGroupSessionsError> {
+        let store = &self.inner.store;
+
+        let mut selected = Vec::new();
+        let mut not_backed_up = 0;
+        let mut after = None;
+
+        // Collect all the selected sessions first, so we can refuse the purge
+        // before deleting anything.
+        loop {
+            let batch = store
+                .get_inbound_group_sessions_batch(after.take(), INBOUND_GROUP_SESSIONS_BATCH_SIZE)
+                .await?;
+
+            let Some(last) = batch.last() else { break };
+            after = Some((last.room_id().to_owned(), last.session_id().to_owned()));
+
+            for session in batch.iter().filter(|session| filter.matches(session)) {
+                if !session.backed_up() {
+                    not_backed_up += 1;
+                }
+
+                selected.push((session.room_id().to_owned(), session.session_id().to_owned()));
+            }
+        }
+
+        if not_backed_up > 0 {
+            if force {
+                warn!(not_backed_up, "Purging room keys which aren't backed up");
+            } else {
+                return Err(PurgeInboundGroupSessionsError::NotBackedUp { count: not_backed_up });
+            }
+        }
+
+        for chunk in selected.chunks(INBOUND_GROUP_SESSIONS_BATCH_SIZE) {
+            let ids: Vec<_> = chunk
+                .iter()
+                .map(|(room_id, session_id)| (room_id.as_ref(), session_id.as_str()))
+                .collect();
+
+            store.delete_inbound_group_sessions(&ids).await?;
+        }
+
+        info!(count = selected.len(), "Purged room keys");
+
+        Ok(selected.len())
+    }
+
     /// Returns whether this `OlmMachine` is the same another one.
     ///
     /// Useful for testing purposes only.
@@ -2955,6 +3473,50 @@ pub struct EncryptionSyncChanges<'a> {
     pub next_batch_token: Option<String>,
 }
 
+/// Information about a to-device event which couldn't be decrypted, see
+/// [`OlmMachine::subscribe_to_to_device_utds()`].
+#[derive(Clone, Debug)]
+pub struct ToDeviceUtdInfo {
+    /// The user who sent the event.
+    pub sender: OwnedUserId,
+
+    /// The Curve25519 key of the device which sent the event, if the event was
+    /// encrypted with a known algorithm.
+    pub sender_key: Option<Curve25519PublicKey>,
+
+    /// The reason why the event couldn't be decrypted.
+    pub reason: ToDeviceUnableToDecryptReason,
+
+    /// Whether the Olm session with the sender's device was found to be wedged,
+    /// in which case a new session is going to be created to unwedge it,
+    /// according to the [`UnwedgingPolicy`] of the [`DecryptionSettings`].
+    ///
+    /// [`UnwedgingPolicy`]: crate::UnwedgingPolicy
+    pub unwedging_triggered: bool,
+}
+
+/// Options for [`OlmMachine::encrypt_to_device_for_users()`], controlling which
+/// devices of the recipients get the to-device event.
+///
+/// Blacklisted devices never get the event.
+#[cfg(feature = "experimental-send-custom-to-device")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EncryptToDeviceOptions {
+    /// Skip the devices which are neither verified by us, nor cross-signed by
+    /// a verified identity.
+    pub skip_unverified_devices: bool,
+
+    /// Skip the devices which aren't cross-signed by their owner.
+    pub skip_unsigned_devices: bool,
+}
+
+/// The inbound group sessions loaded while decrypting a batch of room events,
+/// keyed by room ID and session ID, see
+/// [`OlmMachine::try_decrypt_room_events()`].
+///
+/// A `None` value means that the session isn't in the store.
+type InboundGroupSessionCache = HashMap<(OwnedRoomId, String), Option<InboundGroupSession>>;
+
 /// Convert a [`MegolmError`] into an [`UnableToDecryptInfo`] or a
 /// [`CryptoStoreError`].
 ///
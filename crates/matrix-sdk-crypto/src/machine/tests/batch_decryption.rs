@@ -0,0 +1,146 @@
+use assert_matches2::{assert_let, assert_matches};
+use matrix_sdk_common::deserialized_responses::UnableToDecryptReason;
+use matrix_sdk_test::async_test;
+use ruma::{
+    events::room::message::RoomMessageEventContent, room_id, serde::Raw, OwnedRoomId, RoomId,
+};
+use serde_json::{json, Value};
+
+use crate::{
+    machine::tests, olm::OutboundGroupSession, types::events::room::encrypted::EncryptedEvent,
+    utilities::json_convert, Account, DecryptionSettings, OlmMachine, RoomEventDecryptionResult,
+    TrustRequirement,
+};
+
+/// The number of events encrypted with every room key.
+const EVENTS_PER_SESSION: usize = 75;
+
+fn room_a() -> &'static RoomId {
+    room_id!("!a:localhost")
+}
+
+fn room_b() -> &'static RoomId {
+    room_id!("!b:localhost")
+}
+
+fn decryption_settings() -> DecryptionSettings {
+    DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    }
+}
+
+async fn encrypt_event(
+    account: &Account,
+    outbound: &OutboundGroupSession,
+    body: &str,
+) -> (OwnedRoomId, Raw<EncryptedEvent>) {
+    let content = RoomMessageEventContent::text_plain(body);
+    let encrypted_content =
+        outbound.encrypt("m.room.message", &Raw::new(&content).unwrap().cast()).await;
+
+    let event = json!({
+        "event_id": format!("${body}:localhost"),
+        "origin_server_ts": 0u64,
+        "sender": account.user_id(),
+        "type": "m.room.encrypted",
+        "content": encrypted_content,
+    });
+
+    (outbound.room_id().to_owned(), json_convert(&event).unwrap())
+}
+
+fn decrypted_body(result: &RoomEventDecryptionResult) -> String {
+    assert_let!(RoomEventDecryptionResult::Decrypted(decrypted) = result);
+    let content = decrypted.event.get_field::<Value>("content").unwrap().unwrap();
+    content["body"].as_str().unwrap().to_owned()
+}
+
+#[async_test]
+async fn test_try_decrypt_room_events() {
+    let machine = OlmMachine::new(tests::user_id(), tests::alice_device_id()).await;
+    let account = Account::with_device_id(tests::user_id(), tests::alice_device_id());
+
+    // A handful of room keys in two rooms, all of them known by the machine but
+    // the last one.
+    let mut outbound_sessions = Vec::new();
+    let mut inbound_sessions = Vec::new();
+    for room_id in [room_a(), room_a(), room_b(), room_b(), room_b()] {
+        let (outbound, inbound) = account.create_group_session_pair_with_defaults(room_id).await;
+        outbound_sessions.push(outbound);
+        inbound_sessions.push(inbound);
+    }
+    let unknown_session_id = inbound_sessions.pop().unwrap().session_id().to_owned();
+    machine.store().save_inbound_group_sessions(&inbound_sessions).await.unwrap();
+
+    // A few hundred events, interleaving the room keys.
+    let mut events = Vec::new();
+    for i in 0..EVENTS_PER_SESSION {
+        for (j, outbound) in outbound_sessions.iter().enumerate() {
+            events.push(encrypt_event(&account, outbound, &format!("event-{j}-{i}")).await);
+        }
+    }
+
+    let results = machine.try_decrypt_room_events(&events, &decryption_settings()).await.unwrap();
+
+    // The results are in the same order as the events, and the ones encrypted with
+    // the unknown room key couldn't be decrypted.
+    assert_eq!(results.len(), EVENTS_PER_SESSION * outbound_sessions.len());
+    for (index, result) in results.iter().enumerate() {
+        let (i, j) = (index / outbound_sessions.len(), index % outbound_sessions.len());
+
+        if j == inbound_sessions.len() {
+            assert_let!(RoomEventDecryptionResult::UnableToDecrypt(utd_info) = result);
+            assert_eq!(utd_info.session_id.as_deref(), Some(unknown_session_id.as_str()));
+            assert_matches!(
+                utd_info.reason,
+                UnableToDecryptReason::MissingMegolmSession { withheld_code: None }
+            );
+        } else {
+            assert_eq!(decrypted_body(result), format!("event-{j}-{i}"));
+        }
+    }
+
+    // The results are the same as when decrypting the events one by one.
+    for ((room_id, event), result) in events.iter().zip(&results) {
+        let single_result =
+            machine.try_decrypt_room_event(event, room_id, &decryption_settings()).await.unwrap();
+
+        match (&single_result, result) {
+            (RoomEventDecryptionResult::Decrypted(_), RoomEventDecryptionResult::Decrypted(_)) => {
+                assert_eq!(decrypted_body(&single_result), decrypted_body(result));
+            }
+            (
+                RoomEventDecryptionResult::UnableToDecrypt(single_utd_info),
+                RoomEventDecryptionResult::UnableToDecrypt(utd_info),
+            ) => {
+                assert_eq!(single_utd_info.session_id, utd_info.session_id);
+            }
+            _ => panic!("The batch and single decryption results should match"),
+        }
+    }
+}
+
+#[async_test]
+async fn test_try_decrypt_room_events_uses_keys_received_after_a_failure() {
+    let machine = OlmMachine::new(tests::user_id(), tests::alice_device_id()).await;
+    let account = Account::with_device_id(tests::user_id(), tests::alice_device_id());
+    let (outbound, inbound) = account.create_group_session_pair_with_defaults(room_a()).await;
+
+    let events = vec![
+        encrypt_event(&account, &outbound, "first").await,
+        encrypt_event(&account, &outbound, "second").await,
+    ];
+
+    // Without the room key, nothing can be decrypted.
+    let results = machine.try_decrypt_room_events(&events, &decryption_settings()).await.unwrap();
+    assert!(results.iter().all(|r| matches!(r, RoomEventDecryptionResult::UnableToDecrypt(_))));
+
+    // Once the room key is received, a new batch decrypts all the events.
+    machine.store().save_inbound_group_sessions(&[inbound]).await.unwrap();
+
+    let results = machine.try_decrypt_room_events(&events, &decryption_settings()).await.unwrap();
+    assert_eq!(decrypted_body(&results[0]), "first");
+    assert_eq!(decrypted_body(&results[1]), "second");
+}
@@ -20,6 +20,7 @@ use matrix_sdk_common::deserialized_responses::{
 };
 use matrix_sdk_test::{async_test, ruma_response_from_json, test_json};
 use ruma::{
+    device_id,
     events::{room::message::RoomMessageEventContent, AnyMessageLikeEventContent},
     room_id,
     serde::Raw,
@@ -79,8 +80,11 @@ async fn test_decryption_verification_state() {
         tests::to_device_requests_to_content(to_device_requests),
     );
 
-    let decryption_settings =
-        DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    };
 
     let group_session = bob
         .store()
@@ -122,8 +126,11 @@ async fn test_decryption_verification_state() {
 
     let event = json_convert(&event).unwrap();
 
-    let decryption_settings =
-        DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    };
     let encryption_info = bob
         .decrypt_room_event(&event, room_id, &decryption_settings)
         .await
@@ -351,7 +358,11 @@ async fn test_verification_states_spoofed_sender(
     set_up_alice_cross_signing(&alice, &bob).await;
 
     let room_id = room_id!("!test:example.org");
-    let decryption_settings = DecryptionSettings { sender_device_trust_requirement };
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    };
 
     // Alice sends a message to Bob.
     let (event, _) = encrypt_message(&alice, room_id, &bob, "Secret message").await;
@@ -480,6 +491,72 @@ async fn test_verification_states_multiple_device() {
     assert_eq!(VerificationState::Unverified(VerificationLevel::UnverifiedIdentity), state);
 }
 
+#[async_test]
+async fn test_verification_states_device_pinning() {
+    let (bob, _) = get_prepared_machine_test_helper(tests::user_id(), false).await;
+    bob.store().set_device_pinning_enabled(true).await.unwrap();
+
+    let other_user_id = user_id!("@web2:localhost:8482");
+    let unsigned_device_id = device_id!("AVXFQWJUQA");
+
+    // The first time we see the user, they only have their signed device, which
+    // gets pinned.
+    let mut json = test_json::KEYS_QUERY_TWO_DEVICES_ONE_SIGNED.clone();
+    json["device_keys"][other_user_id.as_str()]
+        .as_object_mut()
+        .unwrap()
+        .remove(unsigned_device_id.as_str());
+    let response = ruma_response_from_json(&json);
+    bob.receive_keys_query_response(&TransactionId::new(), &response).await.unwrap();
+
+    // Then a new, unsigned, device appears.
+    let response = ruma_response_from_json(&*test_json::KEYS_QUERY_TWO_DEVICES_ONE_SIGNED);
+    bob.receive_keys_query_response(&TransactionId::new(), &response).await.unwrap();
+
+    let devices = bob.store().get_user_devices(other_user_id).await.unwrap();
+    assert!(devices.get(device_id!("JERTCKWUWG")).unwrap().is_pinned());
+    assert!(!devices.get(unsigned_device_id).unwrap().is_pinned());
+
+    let fake_room_id = room_id!("!roomid:example.com");
+    let olm = OutboundGroupSession::new(
+        bob.device_id().into(),
+        Arc::new(bob.identity_keys()),
+        fake_room_id,
+        EncryptionSettings::default(),
+    )
+    .unwrap()
+    .session_key()
+    .await;
+
+    let unsigned_inbound_session = InboundGroupSession::new(
+        Curve25519PublicKey::from_base64("LTpv2DGMhggPAXO02+7f68CNEp6A40F0Yl8B094Y8gc").unwrap(),
+        Ed25519PublicKey::from_base64("loz5i40dP+azDtWvsD0L/xpnCjNkmrcvtXVXzCHX8Vw").unwrap(),
+        fake_room_id,
+        &olm,
+        SenderData::unknown(),
+        EventEncryptionAlgorithm::MegolmV1AesSha2,
+        None,
+        false,
+    )
+    .unwrap();
+
+    // The new device contradicts the pinned devices of the user.
+    let (state, _) = bob
+        .get_room_event_verification_state(&unsigned_inbound_session, other_user_id)
+        .await
+        .unwrap();
+    assert_eq!(VerificationState::Unverified(VerificationLevel::PinViolation), state);
+
+    // Once the user acknowledged the change, the devices can be re-pinned.
+    bob.store().pin_user_devices(other_user_id).await.unwrap();
+
+    let (state, _) = bob
+        .get_room_event_verification_state(&unsigned_inbound_session, other_user_id)
+        .await
+        .unwrap();
+    assert_eq!(VerificationState::Unverified(VerificationLevel::UnsignedDevice), state);
+}
+
 /// Test that the trust requirement is checked when decrypting an event.
 ///
 /// Set the sender data to various values, and test that we can or can't
@@ -643,8 +720,11 @@ async fn encrypt_message(
         tests::to_device_requests_to_content(to_device_requests),
     );
 
-    let decryption_settings =
-        DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    };
 
     let group_session = recipient
         .store()
@@ -698,8 +778,11 @@ async fn check_decryption_trust_requirement(
     tests: &[(TrustRequirement, bool)],
 ) {
     for (trust_requirement, is_ok) in tests {
-        let decryption_settings =
-            DecryptionSettings { sender_device_trust_requirement: *trust_requirement };
+        let decryption_settings = DecryptionSettings {
+            sender_device_trust_requirement: *trust_requirement,
+            to_device_trust_requirements: Default::default(),
+            unwedging_policy: Default::default(),
+        };
         if *is_ok {
             assert!(
                 bob.decrypt_room_event(event, room_id, &decryption_settings).await.is_ok(),
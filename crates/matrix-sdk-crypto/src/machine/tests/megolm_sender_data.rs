@@ -56,8 +56,11 @@ async fn test_receive_megolm_session_from_unknown_device() {
     let room_id = room_id!("!test:example.org");
     let event = create_and_share_session_without_sender_data(&alice, &bob, room_id).await;
 
-    let decryption_settings =
-        DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    };
 
     // Bob receives the to-device message
     receive_to_device_event(&bob, &event, &decryption_settings).await;
@@ -96,8 +99,11 @@ async fn test_receive_megolm_session_from_known_device() {
         ),
     );
 
-    let decryption_settings =
-        DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    };
 
     // Bob receives the to-device message
     receive_to_device_event(&bob, &event, &decryption_settings).await;
@@ -133,8 +139,11 @@ async fn test_update_unknown_device_senderdata_on_keys_query() {
     let room_id = room_id!("!test:example.org");
     let event = create_and_share_session_without_sender_data(&alice, &bob, room_id).await;
 
-    let decryption_settings =
-        DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    };
 
     // Bob receives the to-device message
     receive_to_device_event(&bob, &event, &decryption_settings).await;
@@ -194,8 +203,11 @@ async fn test_update_device_info_senderdata_on_keys_query() {
         to_device_requests_to_content(to_device_requests),
     );
 
-    let decryption_settings =
-        DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    };
 
     // Bob receives the to-device message
     receive_to_device_event(&bob, &event, &decryption_settings).await;
@@ -33,16 +33,20 @@ use ruma::{
     },
     device_id,
     events::{
-        room::message::{
-            AddMentions, MessageType, Relation, ReplyWithinThread, RoomMessageEventContent,
+        room::{
+            encryption::RoomEncryptionEventContent,
+            history_visibility::HistoryVisibility,
+            message::{
+                AddMentions, MessageType, Relation, ReplyWithinThread, RoomMessageEventContent,
+            },
         },
         AnyMessageLikeEvent, AnyMessageLikeEventContent, AnySyncMessageLikeEvent, AnyToDeviceEvent,
         MessageLikeEvent, OriginalMessageLikeEvent, ToDeviceEventType,
     },
     room_id,
     serde::Raw,
-    uint, user_id, DeviceId, DeviceKeyAlgorithm, DeviceKeyId, MilliSecondsSinceUnixEpoch,
-    OneTimeKeyAlgorithm, RoomId, TransactionId, UserId,
+    uint, user_id, DeviceId, DeviceKeyAlgorithm, DeviceKeyId, EventEncryptionAlgorithm,
+    MilliSecondsSinceUnixEpoch, OneTimeKeyAlgorithm, RoomId, TransactionId, UserId,
 };
 use serde_json::json;
 use vodozemac::{
@@ -79,13 +83,15 @@ use crate::{
     utilities::json_convert,
     verification::tests::bob_id,
     Account, DecryptionSettings, DeviceData, EncryptionSettings, LocalTrust, MegolmError, OlmError,
-    RoomEventDecryptionResult, TrustRequirement,
+    RoomEventDecryptionResult, RoomKeyRotationLimits, TrustRequirement,
 };
 
+mod batch_decryption;
 mod decryption_verification_state;
 mod interactive_verification;
 mod megolm_sender_data;
 mod olm_encryption;
+mod room_key_purge;
 mod room_settings;
 mod send_encrypted_to_device;
 
@@ -284,8 +290,11 @@ fn test_one_time_key_signing() {
 async fn test_keys_for_upload() {
     let machine = OlmMachine::new(user_id(), alice_device_id()).await;
 
-    let decryption_settings =
-        DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    };
 
     let key_counts = BTreeMap::from([(OneTimeKeyAlgorithm::SignedCurve25519, 49u8.into())]);
 
@@ -513,8 +522,11 @@ async fn send_room_key_to_device(
     );
     let event = json_convert(&event).unwrap();
 
-    let decryption_settings =
-        DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    };
 
     receiver
         .receive_sync_changes(
@@ -644,8 +656,11 @@ async fn test_megolm_encryption() {
 
     let mut room_keys_received_stream = Box::pin(bob.store().room_keys_received_stream());
 
-    let decryption_settings =
-        DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    };
 
     let group_session = bob
         .store()
@@ -698,8 +713,11 @@ async fn test_megolm_encryption() {
 
     let event = json_convert(&event).unwrap();
 
-    let decryption_settings =
-        DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    };
 
     let decryption_result =
         bob.try_decrypt_room_event(&event, room_id, &decryption_settings).await.unwrap();
@@ -727,6 +745,46 @@ async fn test_megolm_encryption() {
     }
 }
 
+#[async_test]
+async fn test_megolm_session_rotates_at_the_rotation_limits() {
+    let (alice, bob) =
+        get_machine_pair_with_setup_sessions_test_helper(alice_id(), user_id(), false).await;
+    let room_id = room_id!("!test:example.org");
+
+    // The room asks for the room key to be rotated every 1000 messages, but we want
+    // it to be rotated every 3 messages.
+    let mut content = RoomEncryptionEventContent::new(EventEncryptionAlgorithm::MegolmV1AesSha2);
+    content.rotation_period_msgs = Some(uint!(1000));
+
+    let settings =
+        EncryptionSettings::new(content, HistoryVisibility::Shared, CollectStrategy::AllDevices)
+            .with_rotation_limits(&RoomKeyRotationLimits {
+                rotation_period_msgs: Some(3),
+                ..Default::default()
+            });
+
+    let mut session_ids = Vec::new();
+
+    for i in 0..4 {
+        alice.share_room_key(room_id, iter::once(bob.user_id()), settings.clone()).await.unwrap();
+
+        let content = RoomMessageEventContent::text_plain(format!("Message {i}"));
+        let encrypted_content = alice
+            .encrypt_room_event(room_id, AnyMessageLikeEventContent::RoomMessage(content))
+            .await
+            .unwrap();
+
+        session_ids.push(encrypted_content.get_field::<String>("session_id").unwrap().unwrap());
+    }
+
+    // The first 3 messages are encrypted with the same room key…
+    assert_eq!(session_ids[0], session_ids[1]);
+    assert_eq!(session_ids[1], session_ids[2]);
+
+    // …and the 4th one with a new room key.
+    assert_ne!(session_ids[2], session_ids[3]);
+}
+
 #[async_test]
 async fn test_withheld_unverified() {
     let (alice, bob) =
@@ -765,8 +823,11 @@ async fn test_withheld_unverified() {
 
     let event = json_convert(&event).unwrap();
 
-    let decryption_settings =
-        DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    };
 
     bob.receive_sync_changes(
         EncryptionSyncChanges {
@@ -816,8 +877,11 @@ async fn test_withheld_unverified() {
     });
     let room_event = json_convert(&room_event).unwrap();
 
-    let decryption_settings =
-        DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    };
     let decrypt_result = bob.decrypt_room_event(&room_event, room_id, &decryption_settings).await;
 
     assert_matches!(&decrypt_result, Err(MegolmError::MissingRoomKey(Some(_))));
@@ -857,8 +921,11 @@ async fn test_decrypt_unencrypted_event() {
     let event = json_convert(&event).unwrap();
 
     // decrypt_room_event should return an error
-    let decryption_settings =
-        DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    };
     assert_matches!(
         bob.decrypt_room_event(&event, room_id, &decryption_settings).await,
         Err(MegolmError::JsonError(..))
@@ -1031,8 +1098,11 @@ async fn test_query_ratcheted_key() {
         to_device_requests_to_content(to_device_requests),
     );
 
-    let decryption_settings =
-        DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    };
 
     let group_session = bob
         .store()
@@ -1054,8 +1124,11 @@ async fn test_query_ratcheted_key() {
 
     let room_event = json_convert(&room_event).unwrap();
 
-    let decryption_settings =
-        DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    };
     let decrypt_error =
         bob.decrypt_room_event(&room_event, room_id, &decryption_settings).await.unwrap_err();
 
@@ -1093,8 +1166,11 @@ async fn test_room_key_over_megolm() {
     let changed_devices = DeviceLists::new();
     let key_counts: BTreeMap<_, _> = Default::default();
 
-    let decryption_settings =
-        DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    };
 
     let _ = bob
         .receive_sync_changes(
@@ -1131,8 +1207,11 @@ async fn test_room_key_over_megolm() {
 
     let event: EncryptedToDeviceEvent = serde_json::from_value(event).unwrap();
 
-    let decryption_settings =
-        DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    };
 
     let decrypt_result = bob
         .store()
@@ -1153,8 +1232,11 @@ async fn test_room_key_over_megolm() {
 
     let event: Raw<AnyToDeviceEvent> = json_convert(&event).unwrap();
 
-    let decryption_settings =
-        DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    };
 
     bob.receive_sync_changes(
         EncryptionSyncChanges {
@@ -1208,8 +1290,11 @@ async fn test_room_key_with_fake_identity_keys() {
     });
     let event = json_convert(&event).unwrap();
 
-    let decryption_settings =
-        DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    };
     assert_matches!(
         alice.decrypt_room_event(&event, room_id, &decryption_settings).await,
         Err(MegolmError::MismatchedIdentityKeys { .. })
@@ -1446,8 +1531,11 @@ async fn test_unsigned_decryption() {
         to_device_requests_to_content(to_device_requests),
     );
 
-    let decryption_settings =
-        DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    };
 
     // Save the first room key.
     let group_session = bob
@@ -1484,8 +1572,11 @@ async fn test_unsigned_decryption() {
     let raw_encrypted_event = json_convert(&first_message_encrypted_event).unwrap();
 
     // Bob has the room key, so first message should be decrypted successfully.
-    let decryption_settings =
-        DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    };
     let raw_decrypted_event =
         bob.decrypt_room_event(&raw_encrypted_event, room_id, &decryption_settings).await.unwrap();
 
@@ -1559,8 +1650,11 @@ async fn test_unsigned_decryption() {
         })
     );
 
-    let decryption_settings =
-        DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    };
 
     // Give Bob the second room key.
     let group_session = bob
@@ -1676,8 +1770,11 @@ async fn test_unsigned_decryption() {
         })
     );
 
-    let decryption_settings =
-        DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    };
 
     // Give Bob the third room key.
     let group_session = bob
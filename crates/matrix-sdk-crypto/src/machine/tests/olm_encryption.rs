@@ -17,7 +17,8 @@ use std::{
     time::{Duration, SystemTime},
 };
 
-use assert_matches2::assert_let;
+use assert_matches2::{assert_let, assert_matches};
+use futures_util::{pin_mut, FutureExt, StreamExt};
 use matrix_sdk_common::deserialized_responses::{
     ProcessedToDeviceEvent, ToDeviceUnableToDecryptReason,
 };
@@ -213,8 +214,11 @@ async fn olm_encryption_test_helper(use_fallback_key: bool) {
             .expect("We should be able to deserialize the encrypted content"),
     );
 
-    let decryption_settings =
-        DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    };
 
     // Decrypting the first time should succeed.
     let decrypted = bob
@@ -310,8 +314,11 @@ async fn test_decrypt_to_device_message_with_unsigned_sender_keys() {
         alice_session.build_encrypted_event(ciphertext, None).await.unwrap(),
     );
 
-    let decryption_settings =
-        DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    };
 
     // Bob receives the to-device message
     let (to_device_events, _) = receive_to_device_event(&bob, &event, &decryption_settings).await;
@@ -324,3 +331,50 @@ async fn test_decrypt_to_device_message_with_unsigned_sender_keys() {
 
     assert_eq!(utd_info.reason, ToDeviceUnableToDecryptReason::DecryptionFailure);
 }
+
+#[async_test]
+async fn test_to_device_utds_are_notified() {
+    let (alice, bob) =
+        get_machine_pair_with_session(tests::alice_id(), tests::user_id(), false).await;
+    let utds_stream = bob.subscribe_to_to_device_utds();
+    pin_mut!(utds_stream);
+
+    let bob_device = alice.get_device(bob.user_id(), bob.device_id(), None).await.unwrap().unwrap();
+
+    let (_, content) = bob_device
+        .encrypt("m.dummy", ToDeviceDummyEventContent::new())
+        .await
+        .expect("We should be able to encrypt a dummy event.");
+
+    let event = ToDeviceEvent::new(
+        alice.user_id().to_owned(),
+        content
+            .deserialize_as_unchecked()
+            .expect("We should be able to deserialize the encrypted content"),
+    );
+
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    };
+
+    // The event is decrypted the first time, nothing is notified.
+    let (to_device_events, _) = receive_to_device_event(&bob, &event, &decryption_settings).await;
+    assert_matches!(&to_device_events[0], ProcessedToDeviceEvent::Decrypted { .. });
+    assert!(utds_stream.next().now_or_never().is_none());
+
+    // A replayed event can't be decrypted, which is notified.
+    let (to_device_events, _) = receive_to_device_event(&bob, &event, &decryption_settings).await;
+    assert_matches!(&to_device_events[0], ProcessedToDeviceEvent::UnableToDecrypt { .. });
+
+    let utd_info = utds_stream
+        .next()
+        .now_or_never()
+        .flatten()
+        .expect("We should have been notified about the to-device UTD");
+    assert_eq!(utd_info.sender, alice.user_id());
+    assert_eq!(utd_info.sender_key, Some(alice.identity_keys().curve25519));
+    assert_eq!(utd_info.reason, ToDeviceUnableToDecryptReason::DecryptionFailure);
+    assert!(!utd_info.unwedging_triggered);
+}
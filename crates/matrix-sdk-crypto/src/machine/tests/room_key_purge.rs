@@ -0,0 +1,142 @@
+use assert_matches2::assert_matches;
+use matrix_sdk_test::async_test;
+use ruma::{room_id, uint, RoomId, SecondsSinceUnixEpoch};
+
+use crate::{
+    machine::tests, olm::InboundGroupSession, store::types::InboundGroupSessionFilter, Account,
+    OlmMachine, PurgeInboundGroupSessionsError,
+};
+
+fn room_a() -> &'static RoomId {
+    room_id!("!a:localhost")
+}
+
+fn room_b() -> &'static RoomId {
+    room_id!("!b:localhost")
+}
+
+/// Create an [`OlmMachine`] with four backed up room keys: two received
+/// directly in room A, one imported in room A and one received directly in
+/// room B.
+async fn machine_with_room_keys() -> (OlmMachine, Vec<InboundGroupSession>) {
+    let machine = OlmMachine::new(tests::user_id(), tests::alice_device_id()).await;
+    let account = Account::with_device_id(tests::user_id(), tests::alice_device_id());
+
+    let (_, imported) = account.create_group_session_pair_with_defaults(room_a()).await;
+    let imported = InboundGroupSession::from_export(&imported.export().await).unwrap();
+
+    let sessions = vec![
+        account.create_group_session_pair_with_defaults(room_a()).await.1,
+        account.create_group_session_pair_with_defaults(room_a()).await.1,
+        imported,
+        account.create_group_session_pair_with_defaults(room_b()).await.1,
+    ];
+
+    for session in &sessions {
+        session.mark_as_backed_up();
+    }
+
+    machine.store().save_inbound_group_sessions(&sessions).await.unwrap();
+
+    (machine, sessions)
+}
+
+#[async_test]
+async fn test_inbound_group_session_counts_by_room() {
+    let (machine, _) = machine_with_room_keys().await;
+
+    let counts = machine.inbound_group_session_counts_by_room().await.unwrap();
+
+    assert_eq!(counts.len(), 2);
+    assert_eq!(counts[room_a()], 3);
+    assert_eq!(counts[room_b()], 1);
+}
+
+#[async_test]
+async fn test_purge_inbound_group_sessions_of_a_room() {
+    let (machine, _) = machine_with_room_keys().await;
+
+    let filter =
+        InboundGroupSessionFilter { room_id: Some(room_a().to_owned()), ..Default::default() };
+    let purged = machine.purge_inbound_group_sessions(&filter, false).await.unwrap();
+
+    assert_eq!(purged, 3);
+    let counts = machine.inbound_group_session_counts_by_room().await.unwrap();
+    assert!(!counts.contains_key(room_a()));
+    assert_eq!(counts[room_b()], 1);
+}
+
+#[async_test]
+async fn test_purge_imported_inbound_group_sessions() {
+    let (machine, sessions) = machine_with_room_keys().await;
+
+    let filter = InboundGroupSessionFilter { imported: Some(true), ..Default::default() };
+    let purged = machine.purge_inbound_group_sessions(&filter, false).await.unwrap();
+
+    assert_eq!(purged, 1);
+    let store = machine.store();
+    for session in &sessions {
+        let loaded =
+            store.get_inbound_group_session(session.room_id(), session.session_id()).await.unwrap();
+        assert_eq!(loaded.is_some(), !session.has_been_imported());
+    }
+}
+
+#[async_test]
+async fn test_purge_inbound_group_sessions_by_last_use() {
+    let machine = OlmMachine::new(tests::user_id(), tests::alice_device_id()).await;
+    let account = Account::with_device_id(tests::user_id(), tests::alice_device_id());
+
+    let (_, old) = account.create_group_session_pair_with_defaults(room_a()).await;
+    let (_, recent) = account.create_group_session_pair_with_defaults(room_a()).await;
+    let (_, never_used) = account.create_group_session_pair_with_defaults(room_a()).await;
+
+    old.mark_as_used(SecondsSinceUnixEpoch(uint!(1_000_000)));
+    recent.mark_as_used(SecondsSinceUnixEpoch(uint!(3_000_000)));
+    for session in [&old, &recent, &never_used] {
+        session.mark_as_backed_up();
+    }
+
+    machine.store().save_inbound_group_sessions(&[old.clone(), recent, never_used]).await.unwrap();
+
+    // Only the session which was used before the cutoff is purged, the one with
+    // an unknown time of last use is kept.
+    let filter = InboundGroupSessionFilter {
+        last_used_before: Some(SecondsSinceUnixEpoch(uint!(2_000_000))),
+        ..Default::default()
+    };
+    let purged = machine.purge_inbound_group_sessions(&filter, false).await.unwrap();
+
+    assert_eq!(purged, 1);
+    assert!(machine
+        .store()
+        .get_inbound_group_session(old.room_id(), old.session_id())
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(machine.inbound_group_session_counts_by_room().await.unwrap()[room_a()], 2);
+}
+
+#[async_test]
+async fn test_purge_inbound_group_sessions_refuses_if_not_backed_up() {
+    let (machine, _) = machine_with_room_keys().await;
+
+    let account = Account::with_device_id(tests::user_id(), tests::alice_device_id());
+    let (_, not_backed_up) = account.create_group_session_pair_with_defaults(room_b()).await;
+    machine.store().save_inbound_group_sessions(&[not_backed_up]).await.unwrap();
+
+    // The purge is refused, and nothing is deleted.
+    let filter = InboundGroupSessionFilter::default();
+    let result = machine.purge_inbound_group_sessions(&filter, false).await;
+
+    assert_matches!(result, Err(PurgeInboundGroupSessionsError::NotBackedUp { count: 1 }));
+    let counts = machine.inbound_group_session_counts_by_room().await.unwrap();
+    assert_eq!(counts[room_a()], 3);
+    assert_eq!(counts[room_b()], 2);
+
+    // Unless it's forced.
+    let purged = machine.purge_inbound_group_sessions(&filter, true).await.unwrap();
+
+    assert_eq!(purged, 5);
+    assert!(machine.inbound_group_session_counts_by_room().await.unwrap().is_empty());
+}
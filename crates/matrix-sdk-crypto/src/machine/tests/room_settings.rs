@@ -5,8 +5,8 @@ use matrix_sdk_test::async_test;
 use ruma::room_id;
 
 use crate::{
-    machine::tests, store::types::RoomSettings, types::EventEncryptionAlgorithm, OlmMachine,
-    SetRoomSettingsError,
+    machine::tests, store::types::RoomSettings, types::EventEncryptionAlgorithm, CollectStrategy,
+    OlmMachine, SetRoomSettingsError,
 };
 
 #[async_test]
@@ -26,6 +26,7 @@ async fn test_stores_and_returns_room_settings() {
         only_allow_trusted_devices: true,
         session_rotation_period: Some(Duration::from_secs(10)),
         session_rotation_period_messages: Some(1234),
+        sharing_strategy: Some(CollectStrategy::IdentityBasedStrategy),
     };
 
     machine.set_room_settings(room_id, &settings).await.unwrap();
@@ -99,3 +100,35 @@ async fn test_set_room_settings_accepts_noop_changes() {
         .await
         .unwrap();
 }
+
+#[async_test]
+async fn test_set_room_sharing_strategy() {
+    let machine = OlmMachine::new(tests::user_id(), tests::alice_device_id()).await;
+    let room_id = room_id!("!test:localhost");
+
+    // Initial settings
+    machine
+        .set_room_settings(
+            room_id,
+            &RoomSettings { session_rotation_period_messages: Some(100), ..Default::default() },
+        )
+        .await
+        .unwrap();
+
+    // The sharing strategy can be overridden, without touching the other settings.
+    machine
+        .set_room_sharing_strategy(room_id, Some(CollectStrategy::IdentityBasedStrategy))
+        .await
+        .unwrap();
+
+    let settings = machine.room_settings(room_id).await.unwrap().unwrap();
+    assert_eq!(settings.sharing_strategy, Some(CollectStrategy::IdentityBasedStrategy));
+    assert_eq!(settings.session_rotation_period_messages, Some(100));
+
+    // And the override can be removed again.
+    machine.set_room_sharing_strategy(room_id, None).await.unwrap();
+
+    let settings = machine.room_settings(room_id).await.unwrap().unwrap();
+    assert_eq!(settings.sharing_strategy, None);
+    assert_eq!(settings.session_rotation_period_messages, Some(100));
+}
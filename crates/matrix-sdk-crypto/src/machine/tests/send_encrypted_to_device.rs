@@ -12,8 +12,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::BTreeMap;
+
 use assert_matches2::{assert_let, assert_matches};
 use insta::assert_json_snapshot;
+#[cfg(feature = "experimental-send-custom-to-device")]
+use matrix_sdk_common::deserialized_responses::WithheldCode;
 use matrix_sdk_common::deserialized_responses::{
     AlgorithmInfo, ProcessedToDeviceEvent, ToDeviceUnableToDecryptReason, VerificationLevel,
     VerificationState,
@@ -25,6 +29,8 @@ use ruma::{
 };
 use serde_json::{json, value::to_raw_value, Value};
 
+#[cfg(feature = "experimental-send-custom-to-device")]
+use crate::EncryptToDeviceOptions;
 use crate::{
     machine::{
         test_helpers::{
@@ -59,8 +65,11 @@ async fn test_send_encrypted_to_device() {
             "rooms": ["!726s6s6q:example.com"]
     });
 
-    let decryption_settings =
-        DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    };
 
     let processed_event = send_and_receive_encrypted_to_device_test_helper(
         &alice,
@@ -97,7 +106,7 @@ async fn test_send_encrypted_to_device() {
 
     assert_matches!(
         &encryption_info.algorithm_info,
-        AlgorithmInfo::OlmV1Curve25519AesSha2 { curve25519_public_key_base64 }
+        AlgorithmInfo::OlmV1Curve25519AesSha2 { curve25519_public_key_base64, .. }
     );
     let alice_device =
         alice.get_device(alice.user_id(), alice.device_id(), None).await.unwrap().unwrap();
@@ -112,6 +121,81 @@ async fn test_send_encrypted_to_device() {
     );
 }
 
+#[async_test]
+async fn test_olm_encryption_info_contains_the_session_and_message_index() {
+    let (alice, bob) =
+        get_machine_pair_with_session(tests::alice_id(), tests::user_id(), false).await;
+
+    let custom_event_type = "m.new_device";
+
+    let custom_content = json!({
+            "device_id": "XYZABCDE",
+            "rooms": ["!726s6s6q:example.com"]
+    });
+
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    };
+
+    // When Alice sends two events to Bob
+    let first_event = send_and_receive_encrypted_to_device_test_helper(
+        &alice,
+        &bob,
+        custom_event_type,
+        &custom_content,
+        &decryption_settings,
+    )
+    .await;
+    let second_event = send_and_receive_encrypted_to_device_test_helper(
+        &alice,
+        &bob,
+        custom_event_type,
+        &custom_content,
+        &decryption_settings,
+    )
+    .await;
+
+    // Then both were decrypted with the same Olm session, and the message index
+    // increased.
+    assert_let!(
+        ProcessedToDeviceEvent::Decrypted { encryption_info: first_info, .. } = first_event
+    );
+    assert_let!(
+        AlgorithmInfo::OlmV1Curve25519AesSha2 {
+            session_id: Some(first_session_id),
+            message_index: Some(first_message_index),
+            ..
+        } = &first_info.algorithm_info
+    );
+
+    assert_let!(
+        ProcessedToDeviceEvent::Decrypted { encryption_info: second_info, .. } = second_event
+    );
+    assert_let!(
+        AlgorithmInfo::OlmV1Curve25519AesSha2 {
+            session_id: Some(second_session_id),
+            message_index: Some(second_message_index),
+            ..
+        } = &second_info.algorithm_info
+    );
+
+    assert_eq!(first_session_id, second_session_id);
+    assert_eq!(*first_message_index, 1);
+    assert_eq!(*second_message_index, 2);
+
+    insta::with_settings!({ prepend_module_to_snapshot => false }, {
+        assert_json_snapshot!(
+            second_info,
+            {
+                ".algorithm_info.OlmV1Curve25519AesSha2.curve25519_public_key_base64" => "[sender_curve25519_key]",
+                ".algorithm_info.OlmV1Curve25519AesSha2.session_id" => "[olm_session_id]",
+            }
+        );
+    });
+}
+
 #[async_test]
 async fn test_receive_custom_encrypted_to_device_fails_if_device_unknown() {
     // When decrypting a custom to device, we expect the recipient to know the
@@ -136,8 +220,11 @@ async fn test_receive_custom_encrypted_to_device_fails_if_device_unknown() {
             "rooms": ["!726s6s6q:example.com"]
     });
 
-    let decryption_settings =
-        DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    };
 
     let processed_event = send_and_receive_encrypted_to_device_test_helper(
         &alice,
@@ -157,6 +244,8 @@ async fn test_excluding_insecure_means_custom_to_device_events_from_unverified_d
     // Given we are in "exclude insecure devices" mode
     let decryption_settings = DecryptionSettings {
         sender_device_trust_requirement: TrustRequirement::CrossSignedOrLegacy,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
     };
 
     // Bob is the receiver
@@ -209,6 +298,8 @@ async fn test_excluding_insecure_does_not_prevent_key_events_being_processed() {
     // Given we are in "exclude insecure devices" mode
     let decryption_settings = DecryptionSettings {
         sender_device_trust_requirement: TrustRequirement::CrossSignedOrLegacy,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
     };
 
     // Bob is the receiver
@@ -252,6 +343,111 @@ async fn test_excluding_insecure_does_not_prevent_key_events_being_processed() {
     assert_matches!(processed_event, ProcessedToDeviceEvent::Decrypted { .. });
 }
 
+#[async_test]
+async fn test_per_event_type_trust_requirement_overrides_the_allowed_types() {
+    // Given we accept to-device events from any device, except for room keys
+    // which must come from cross-signed devices
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: BTreeMap::from([(
+            "m.room_key".to_owned(),
+            TrustRequirement::CrossSigned,
+        )]),
+        unwedging_policy: Default::default(),
+    };
+
+    let (bob, otk) = get_prepared_machine_test_helper(bob_id(), false).await;
+    let alice = OlmMachine::new(tests::alice_id(), tests::alice_device_id()).await;
+
+    let bob_device = DeviceData::from_machine_test_helper(&bob).await.unwrap();
+    alice.store().save_device_data(&[bob_device]).await.unwrap();
+
+    let (alice, bob) = build_session_for_pair(alice, bob, otk).await;
+
+    // And the receiving device does not consider the sending device verified
+    make_alice_unverified(&alice, &bob).await;
+
+    // When we send a room key event
+    let key_event =
+        create_and_share_session_without_sender_data(&alice, &bob, room_id!("!23:s.co")).await;
+
+    let key_event_content = serde_json::to_value(&key_event.content).unwrap();
+
+    let processed_event = send_and_receive_encrypted_to_device_test_helper(
+        &alice,
+        &bob,
+        "m.room_key",
+        &key_event_content,
+        &decryption_settings,
+    )
+    .await;
+
+    // Then it was rejected, even though room keys are usually allowed from
+    // unverified devices
+    assert_let!(ProcessedToDeviceEvent::UnableToDecrypt { utd_info, .. } = processed_event);
+    assert_eq!(
+        utd_info.reason,
+        ToDeviceUnableToDecryptReason::UnverifiedSenderDeviceForEventType {
+            event_type: "m.room_key".to_owned()
+        }
+    );
+}
+
+#[async_test]
+async fn test_per_event_type_trust_requirement_overrides_the_global_requirement() {
+    // Given we are in "exclude insecure devices" mode, except for a custom event
+    // type which we accept from any device
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::CrossSignedOrLegacy,
+        to_device_trust_requirements: BTreeMap::from([(
+            "m.new_device".to_owned(),
+            TrustRequirement::Untrusted,
+        )]),
+        unwedging_policy: Default::default(),
+    };
+
+    let (bob, otk) = get_prepared_machine_test_helper(bob_id(), false).await;
+    let alice = OlmMachine::new(tests::alice_id(), tests::alice_device_id()).await;
+
+    let bob_device = DeviceData::from_machine_test_helper(&bob).await.unwrap();
+    alice.store().save_device_data(&[bob_device]).await.unwrap();
+
+    let (alice, bob) = build_session_for_pair(alice, bob, otk).await;
+
+    // And the receiving device does not consider the sending device verified
+    make_alice_unverified(&alice, &bob).await;
+
+    let custom_content = json!({
+            "device_id": "XYZABCDE",
+            "rooms": ["!726s6s6q:example.com"]
+    });
+
+    // When we send an event of this custom type, it is decrypted
+    let processed_event = send_and_receive_encrypted_to_device_test_helper(
+        &alice,
+        &bob,
+        "m.new_device",
+        &custom_content,
+        &decryption_settings,
+    )
+    .await;
+
+    assert_matches!(processed_event, ProcessedToDeviceEvent::Decrypted { .. });
+
+    // But an event of another custom type is still rejected
+    let processed_event = send_and_receive_encrypted_to_device_test_helper(
+        &alice,
+        &bob,
+        "m.other_event",
+        &custom_content,
+        &decryption_settings,
+    )
+    .await;
+
+    assert_let!(ProcessedToDeviceEvent::UnableToDecrypt { utd_info, .. } = processed_event);
+    assert_eq!(utd_info.reason, ToDeviceUnableToDecryptReason::UnverifiedSenderDevice);
+}
+
 #[async_test]
 async fn test_send_olm_encryption_info_unverified_identity() {
     let (alice, bob) =
@@ -270,8 +466,11 @@ async fn test_send_olm_encryption_info_unverified_identity() {
             "rooms": ["!726s6s6q:example.com"]
     });
 
-    let decryption_settings =
-        DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    };
 
     let processed_event = send_and_receive_encrypted_to_device_test_helper(
         &alice,
@@ -314,8 +513,11 @@ async fn test_send_olm_encryption_info_verified_identity() {
             "rooms": ["!726s6s6q:example.com"]
     });
 
-    let decryption_settings =
-        DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    };
 
     let processed_event = send_and_receive_encrypted_to_device_test_helper(
         &alice,
@@ -354,8 +556,11 @@ async fn test_send_olm_encryption_info_verified_locally() {
         .await
         .unwrap();
 
-    let decryption_settings =
-        DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    };
 
     let processed_event = send_and_receive_encrypted_to_device_test_helper(
         &alice,
@@ -400,8 +605,11 @@ async fn test_send_olm_encryption_info_verification_violation() {
             "rooms": ["!726s6s6q:example.com"]
     });
 
-    let decryption_settings =
-        DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    };
 
     let processed_event = send_and_receive_encrypted_to_device_test_helper(
         &alice,
@@ -525,8 +733,11 @@ async fn test_processed_to_device_variants() {
         next_batch_token: None,
     };
 
-    let decryption_settings =
-        DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+    let decryption_settings = DecryptionSettings {
+        sender_device_trust_requirement: TrustRequirement::Untrusted,
+        to_device_trust_requirements: Default::default(),
+        unwedging_policy: Default::default(),
+    };
 
     let (processed, _) =
         bob.receive_sync_changes(sync_changes, &decryption_settings).await.unwrap();
@@ -606,6 +817,105 @@ async fn test_send_encrypted_to_device_no_session() {
     assert_matches!(encryption_result, Err(OlmError::MissingSession));
 }
 
+#[cfg(feature = "experimental-send-custom-to-device")]
+#[async_test]
+async fn test_encrypt_to_device_for_users() {
+    let (alice, bob) =
+        get_machine_pair_with_session(tests::alice_id(), tests::user_id(), false).await;
+
+    let custom_event_type = "m.new_device";
+    let custom_content = json!({
+            "device_id": "XYZABCDE",
+            "rooms": ["!726s6s6q:example.com"]
+    });
+
+    // Our own device is ignored, Bob's device gets the event.
+    let (requests, withheld) = alice
+        .encrypt_to_device_for_users(
+            &[alice.user_id(), bob.user_id()],
+            custom_event_type,
+            &custom_content,
+            EncryptToDeviceOptions::default(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(requests.len(), 1);
+    assert!(withheld.is_empty());
+
+    let messages = &requests[0].messages;
+    assert_eq!(messages.len(), 1);
+    assert!(messages[bob.user_id()]
+        .contains_key(&DeviceIdOrAllDevices::DeviceId(bob.device_id().to_owned())));
+
+    // Bob's device isn't verified, nor cross-signed.
+    for options in [
+        EncryptToDeviceOptions { skip_unverified_devices: true, ..Default::default() },
+        EncryptToDeviceOptions { skip_unsigned_devices: true, ..Default::default() },
+    ] {
+        let (requests, withheld) = alice
+            .encrypt_to_device_for_users(
+                &[bob.user_id()],
+                custom_event_type,
+                &custom_content,
+                options,
+            )
+            .await
+            .unwrap();
+
+        assert!(requests.is_empty());
+        assert_eq!(withheld.len(), 1);
+        assert_eq!(withheld[0].0.device_id(), bob.device_id());
+        assert_eq!(withheld[0].1, WithheldCode::Unverified);
+    }
+
+    // Blacklisted devices never get the event.
+    alice
+        .get_device(bob.user_id(), bob.device_id(), None)
+        .await
+        .unwrap()
+        .unwrap()
+        .set_local_trust(LocalTrust::BlackListed)
+        .await
+        .unwrap();
+
+    let (requests, withheld) = alice
+        .encrypt_to_device_for_users(
+            &[bob.user_id()],
+            custom_event_type,
+            &custom_content,
+            EncryptToDeviceOptions::default(),
+        )
+        .await
+        .unwrap();
+
+    assert!(requests.is_empty());
+    assert_eq!(withheld.len(), 1);
+    assert_eq!(withheld[0].1, WithheldCode::Blacklisted);
+}
+
+#[cfg(feature = "experimental-send-custom-to-device")]
+#[async_test]
+async fn test_encrypt_to_device_for_users_no_session() {
+    let (alice, bob, _) = get_machine_pair(tests::alice_id(), tests::user_id(), false).await;
+
+    let (requests, withheld) = alice
+        .encrypt_to_device_for_users(
+            &[bob.user_id()],
+            "m.new_device",
+            &json!({ "device_id": "XYZABCDE" }),
+            EncryptToDeviceOptions::default(),
+        )
+        .await
+        .unwrap();
+
+    // There is no Olm session with Bob's device, it is reported as such.
+    assert!(requests.is_empty());
+    assert_eq!(withheld.len(), 1);
+    assert_eq!(withheld[0].0.device_id(), bob.device_id());
+    assert_eq!(withheld[0].1, WithheldCode::NoOlm);
+}
+
 /// Create a new [`OutboundGroupSession`], and build a to-device event to share
 /// it with another [`OlmMachine`], *without* sending the MSC4147 sender data.
 ///
@@ -41,7 +41,7 @@ use ruma::{
     OneTimeKeyId, OwnedDeviceId, OwnedDeviceKeyId, OwnedOneTimeKeyId, OwnedUserId, RoomId,
     SecondsSinceUnixEpoch, UInt, UserId,
 };
-use serde::{de::Error, Deserialize, Serialize};
+use serde::{Deserialize, Serialize};
 use serde_json::{
     value::{to_raw_value, RawValue as RawJsonValue},
     Value,
@@ -483,6 +483,14 @@ impl Account {
         ret
     }
 
+    /// Create a new random Olm Account for a dehydrated device, using the
+    /// given device ID.
+    pub fn dehydrated_with_device_id(user_id: &UserId, device_id: &DeviceId) -> Self {
+        let mut ret = Self::with_device_id(user_id, device_id);
+        ret.static_data.dehydrated = true;
+        ret
+    }
+
     /// Get the immutable data for this account.
     pub fn static_data(&self) -> &StaticAccountData {
         &self.static_data
@@ -736,10 +744,7 @@ impl Account {
                     InnerAccount::from_dehydrated_device(&d.device_pickle, &d.nonce, pickle_key)?;
                 Ok(Self::new_helper(account, user_id, device_id))
             }
-            _ => Err(DehydrationError::Json(serde_json::Error::custom(format!(
-                "Unsupported dehydrated device algorithm {:?}",
-                data.algorithm()
-            )))),
+            _ => Err(DehydrationError::UnsupportedAlgorithm(data.algorithm().to_string())),
         }
     }
 
@@ -969,6 +974,7 @@ impl Account {
             created_using_fallback_key: fallback_used,
             creation_time: now,
             last_use_time: now,
+            decrypted_message_count: 0,
         }
     }
 
@@ -1097,6 +1103,8 @@ impl Account {
             created_using_fallback_key: false,
             creation_time: now,
             last_use_time: now,
+            // The pre-key message which created the session has been decrypted.
+            decrypted_message_count: 1,
         };
 
         let plaintext = String::from_utf8_lossy(&result.plaintext).to_string();
@@ -1419,11 +1427,14 @@ impl Account {
 
         trace!("Successfully decrypted an Olm message");
 
+        let (SessionType::New(olm_session) | SessionType::Existing(olm_session)) = &session;
+
         match self
             .parse_decrypted_to_device_event(
                 store,
                 sender,
                 sender_key,
+                olm_session,
                 plaintext,
                 decryption_settings,
             )
@@ -1470,12 +1481,14 @@ impl Account {
     ///   received event (which should also have been used to find or establish
     ///   the Olm session that was used to decrypt the event -- so it is
     ///   guaranteed to be correct).
+    /// * `session` - The Olm session that was used to decrypt the event.
     /// * `plaintext` - The decrypted content of the event.
     async fn parse_decrypted_to_device_event(
         &self,
         store: &Store,
         sender: &UserId,
         sender_key: Curve25519PublicKey,
+        session: &Session,
         plaintext: String,
         decryption_settings: &DecryptionSettings,
     ) -> OlmResult<DecryptionResult> {
@@ -1546,7 +1559,8 @@ impl Account {
                 sender_device = Some(device);
             }
 
-            let encryption_info = Self::get_olm_encryption_info(sender_key, sender, &sender_device);
+            let encryption_info =
+                Self::get_olm_encryption_info(sender_key, sender, &sender_device, session);
 
             let result = DecryptionResult {
                 event,
@@ -1555,8 +1569,23 @@ impl Account {
                 encryption_info,
             };
 
-            // Return an error if the sender is unverified (and we care)
-            if !self.is_from_verified_device_or_allowed_type(decryption_settings, &result) {
+            let event_type = result.event.event_type();
+
+            // A trust requirement configured for this specific event type takes
+            // precedence over the global one, and over the list of event types we
+            // usually allow from unverified devices.
+            if let Some(trust_requirement) =
+                decryption_settings.to_device_trust_requirements.get(event_type)
+            {
+                if satisfies_sender_trust_requirement(&result.encryption_info, trust_requirement) {
+                    Ok(result)
+                } else {
+                    Err(OlmError::UnverifiedSenderDeviceForEventType {
+                        event_type: event_type.to_owned(),
+                    })
+                }
+            } else if !self.is_from_verified_device_or_allowed_type(decryption_settings, &result) {
+                // Return an error if the sender is unverified (and we care)
                 Err(OlmError::UnverifiedSenderDevice)
             } else {
                 // Sender is ok - return the decrypted event
@@ -1633,6 +1662,7 @@ impl Account {
         sender_key: Curve25519PublicKey,
         sender_id: &UserId,
         sender_device: &Option<Device>,
+        session: &Session,
     ) -> EncryptionInfo {
         let verification_state = sender_device
             .as_ref()
@@ -1666,6 +1696,8 @@ impl Account {
             sender_device: sender_device.as_ref().map(|d| d.device_id().to_owned()),
             algorithm_info: AlgorithmInfo::OlmV1Curve25519AesSha2 {
                 curve25519_public_key_base64: sender_key.to_base64(),
+                session_id: Some(session.session_id().to_owned()),
+                message_index: Some(session.decrypted_message_count),
             },
             verification_state,
         };
@@ -1827,6 +1859,7 @@ fn satisfies_sender_trust_requirement(
             // The device is unsigned or missing, or the user is in verification violation,
             // or the sender is mismatched: this is not fine.
             VerificationLevel::UnsignedDevice
+            | VerificationLevel::PinViolation
             | VerificationLevel::None(_)
             | VerificationLevel::VerificationViolation
             | VerificationLevel::MismatchedSender => false,
@@ -17,14 +17,15 @@ use std::{
     fmt,
     ops::Deref,
     sync::{
-        atomic::{AtomicBool, Ordering::SeqCst},
+        atomic::{AtomicBool, AtomicU64, Ordering::SeqCst},
         Arc,
     },
+    time::Duration,
 };
 
 use ruma::{
     events::room::history_visibility::HistoryVisibility, serde::JsonObject, DeviceKeyAlgorithm,
-    OwnedRoomId, RoomId,
+    OwnedRoomId, RoomId, SecondsSinceUnixEpoch, UInt,
 };
 use serde::{Deserialize, Serialize};
 use tokio::sync::Mutex;
@@ -59,6 +60,11 @@ use crate::{
         serialize_curve_key, EventEncryptionAlgorithm, SigningKeys,
     },
 };
+
+/// The granularity with which the time of last use of an
+/// [`InboundGroupSession`] is recorded.
+const LAST_USED_GRANULARITY: Duration = Duration::from_secs(60 * 60 * 24);
+
 // TODO: add creation times to the inbound group sessions so we can export
 // sessions that were created between some time period, this should only be set
 // for non-imported sessions.
@@ -217,6 +223,13 @@ pub struct InboundGroupSession {
     ///
     /// [MSC3061]: https://github.com/matrix-org/matrix-spec-proposals/pull/3061
     shared_history: bool,
+
+    /// The time at which this [`InboundGroupSession`] was last used to decrypt
+    /// an event, in seconds since the Unix epoch, or 0 if it is unknown.
+    ///
+    /// This is only recorded with a granularity of [`LAST_USED_GRANULARITY`],
+    /// to avoid saving the session every time an event is decrypted.
+    last_used: Arc<AtomicU64>,
 }
 
 impl InboundGroupSession {
@@ -290,6 +303,7 @@ impl InboundGroupSession {
             imported: false,
             algorithm: encryption_algorithm.into(),
             backed_up: AtomicBool::new(false).into(),
+            last_used: Default::default(),
             shared_history,
         })
     }
@@ -356,6 +370,7 @@ impl InboundGroupSession {
             history_visibility: self.history_visibility.as_ref().clone(),
             algorithm: (*self.algorithm).to_owned(),
             shared_history: self.shared_history,
+            last_used: self.last_used(),
         }
     }
 
@@ -388,6 +403,36 @@ impl InboundGroupSession {
         self.backed_up.store(true, SeqCst)
     }
 
+    /// Get the time at which this session was last used to decrypt an event.
+    ///
+    /// The time is only recorded with a granularity of a day, and is `None`
+    /// if the session wasn't used since it was received, or since the time of
+    /// use started to be recorded.
+    pub fn last_used(&self) -> Option<SecondsSinceUnixEpoch> {
+        match self.last_used.load(SeqCst) {
+            0 => None,
+            secs => UInt::new(secs).map(SecondsSinceUnixEpoch),
+        }
+    }
+
+    /// Record that this session was used to decrypt an event at the given
+    /// time.
+    ///
+    /// Returns `true` if the previous record was missing or older than
+    /// [`LAST_USED_GRANULARITY`], in which case the record was updated and the
+    /// session should be saved to persist it.
+    pub(crate) fn mark_as_used(&self, time: SecondsSinceUnixEpoch) -> bool {
+        let time = u64::from(time.get());
+        let previous = self.last_used.load(SeqCst);
+
+        if previous == 0 || time.saturating_sub(previous) >= LAST_USED_GRANULARITY.as_secs() {
+            self.last_used.store(time, SeqCst);
+            true
+        } else {
+            false
+        }
+    }
+
     /// Get the map of signing keys this session was received from.
     pub fn signing_keys(&self) -> &SigningKeys<DeviceKeyAlgorithm> {
         &self.creator_info.signing_keys
@@ -435,6 +480,7 @@ impl InboundGroupSession {
             history_visibility,
             algorithm,
             shared_history,
+            last_used,
         } = pickle;
 
         let session: InnerSession = pickle.into();
@@ -453,6 +499,7 @@ impl InboundGroupSession {
             first_known_index,
             room_id,
             backed_up: AtomicBool::from(backed_up).into(),
+            last_used: AtomicU64::new(last_used.map_or(0, |time| time.get().into())).into(),
             algorithm: algorithm.into(),
             imported,
             shared_history,
@@ -666,6 +713,10 @@ pub struct PickledInboundGroupSession {
     /// [MSC3061]: https://github.com/matrix-org/matrix-spec-proposals/pull/3061
     #[serde(default)]
     pub shared_history: bool,
+    /// The time at which the session was last used to decrypt an event, if
+    /// known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used: Option<SecondsSinceUnixEpoch>,
 }
 
 fn default_algorithm() -> EventEncryptionAlgorithm {
@@ -705,6 +756,7 @@ impl TryFrom<&HistoricRoomKey> for InboundGroupSession {
             imported: true,
             algorithm: algorithm.to_owned().into(),
             backed_up: AtomicBool::from(false).into(),
+            last_used: Default::default(),
             shared_history: true,
         })
     }
@@ -745,6 +797,7 @@ impl TryFrom<&ExportedRoomKey> for InboundGroupSession {
             imported: true,
             algorithm: algorithm.to_owned().into(),
             backed_up: AtomicBool::from(false).into(),
+            last_used: Default::default(),
             shared_history: *shared_history,
         })
     }
@@ -776,6 +829,7 @@ impl From<&ForwardedMegolmV1AesSha2Content> for InboundGroupSession {
             imported: true,
             algorithm: EventEncryptionAlgorithm::MegolmV1AesSha2.into(),
             backed_up: AtomicBool::from(false).into(),
+            last_used: Default::default(),
             shared_history: false,
         }
     }
@@ -803,6 +857,7 @@ impl From<&ForwardedMegolmV2AesSha2Content> for InboundGroupSession {
             imported: true,
             algorithm: EventEncryptionAlgorithm::MegolmV1AesSha2.into(),
             backed_up: AtomicBool::from(false).into(),
+            last_used: Default::default(),
             shared_history: false,
         }
     }
@@ -830,7 +885,7 @@ mod tests {
     use matrix_sdk_test::async_test;
     use ruma::{
         device_id, events::room::history_visibility::HistoryVisibility, owned_room_id, room_id,
-        user_id, DeviceId, UserId,
+        uint, user_id, DeviceId, SecondsSinceUnixEpoch, UserId,
     };
     use serde_json::json;
     use similar_asserts::assert_eq;
@@ -1056,6 +1111,32 @@ mod tests {
         assert!(!owner_check_failed);
     }
 
+    #[async_test]
+    async fn test_last_used() {
+        let alice = Account::with_device_id(alice_id(), alice_device_id());
+        let room_id = room_id!("!test:localhost");
+
+        let (_, inbound) = alice.create_group_session_pair_with_defaults(room_id).await;
+        assert_eq!(inbound.last_used(), None);
+
+        // The first use is always recorded.
+        let first_use = SecondsSinceUnixEpoch(uint!(1_000_000));
+        assert!(inbound.mark_as_used(first_use));
+        assert_eq!(inbound.last_used(), Some(first_use));
+
+        // Later uses are only recorded if they happen at least a day later.
+        assert!(!inbound.mark_as_used(SecondsSinceUnixEpoch(uint!(1_003_600))));
+        assert_eq!(inbound.last_used(), Some(first_use));
+
+        let next_day = SecondsSinceUnixEpoch(uint!(1_086_400));
+        assert!(inbound.mark_as_used(next_day));
+        assert_eq!(inbound.last_used(), Some(next_day));
+
+        // The time of last use survives a pickling round trip.
+        let unpickled = InboundGroupSession::from_pickle(inbound.pickle().await).unwrap();
+        assert_eq!(unpickled.last_used(), Some(next_day));
+    }
+
     #[async_test]
     async fn test_session_comparison() {
         let alice = Account::with_device_id(alice_id(), alice_device_id());
@@ -139,6 +139,36 @@ impl EncryptionSettings {
             sharing_strategy,
         }
     }
+
+    /// Apply the given rotation limits to these settings.
+    ///
+    /// The stricter value is always chosen, so the limits can only make the
+    /// room keys rotate more often than the settings of the room ask for.
+    pub fn with_rotation_limits(mut self, limits: &RoomKeyRotationLimits) -> Self {
+        if let Some(rotation_period) = limits.rotation_period {
+            self.rotation_period = self.rotation_period.min(rotation_period);
+        }
+
+        if let Some(rotation_period_msgs) = limits.rotation_period_msgs {
+            self.rotation_period_msgs = self.rotation_period_msgs.min(rotation_period_msgs);
+        }
+
+        self
+    }
+}
+
+/// Limits on the rotation of the room keys, which apply on top of the
+/// `m.room.encryption` state of the rooms.
+///
+/// This can be used to force a faster rotation of the room keys than the one
+/// the rooms ask for, see [`EncryptionSettings::with_rotation_limits()`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RoomKeyRotationLimits {
+    /// The maximum time a room key should be used for, before it is rotated.
+    pub rotation_period: Option<Duration>,
+    /// The maximum number of messages a room key should be used for, before it
+    /// is rotated.
+    pub rotation_period_msgs: Option<u64>,
 }
 
 /// Outbound group session.
@@ -804,7 +834,9 @@ mod tests {
         uint, EventEncryptionAlgorithm,
     };
 
-    use super::{EncryptionSettings, ShareState, ROTATION_MESSAGES, ROTATION_PERIOD};
+    use super::{
+        EncryptionSettings, RoomKeyRotationLimits, ShareState, ROTATION_MESSAGES, ROTATION_PERIOD,
+    };
     use crate::CollectStrategy;
 
     #[test]
@@ -833,6 +865,33 @@ mod tests {
         assert_eq!(settings.rotation_period_msgs, 500);
     }
 
+    #[test]
+    fn test_encryption_settings_rotation_limits() {
+        let mut content =
+            RoomEncryptionEventContent::new(EventEncryptionAlgorithm::MegolmV1AesSha2);
+        content.rotation_period_ms = Some(uint!(7_200_000));
+        content.rotation_period_msgs = Some(uint!(50));
+
+        let settings = EncryptionSettings::new(
+            content,
+            HistoryVisibility::Shared,
+            CollectStrategy::AllDevices,
+        );
+
+        // Without limits, the settings of the room are used.
+        let unlimited = settings.clone().with_rotation_limits(&RoomKeyRotationLimits::default());
+        assert_eq!(unlimited.rotation_period, Duration::from_secs(7200));
+        assert_eq!(unlimited.rotation_period_msgs, 50);
+
+        // The stricter value is always chosen.
+        let limited = settings.with_rotation_limits(&RoomKeyRotationLimits {
+            rotation_period: Some(Duration::from_secs(3600)),
+            rotation_period_msgs: Some(100),
+        });
+        assert_eq!(limited.rotation_period, Duration::from_secs(3600));
+        assert_eq!(limited.rotation_period_msgs, 50);
+    }
+
     /// Ensure that the `ShareState` PartialOrd instance orders according to
     /// specificity of the value.
     #[test]
@@ -31,8 +31,9 @@ pub(crate) use group_sessions::{
 };
 pub use group_sessions::{
     BackedUpRoomKey, EncryptionSettings, ExportedRoomKey, InboundGroupSession, KnownSenderData,
-    OutboundGroupSession, PickledInboundGroupSession, PickledOutboundGroupSession, SenderData,
-    SenderDataType, SessionCreationError, SessionExportError, SessionKey, ShareInfo,
+    OutboundGroupSession, PickledInboundGroupSession, PickledOutboundGroupSession,
+    RoomKeyRotationLimits, SenderData, SenderDataType, SessionCreationError, SessionExportError,
+    SessionKey, ShareInfo,
 };
 pub use session::{PickledSession, Session};
 pub use signing::{CrossSigningStatus, PickledCrossSigningIdentity, PrivateCrossSigningIdentity};
@@ -57,6 +57,8 @@ pub struct Session {
     pub creation_time: SecondsSinceUnixEpoch,
     /// When the session was last used
     pub last_use_time: SecondsSinceUnixEpoch,
+    /// The number of messages that were decrypted using this session
+    pub decrypted_message_count: u64,
 }
 
 #[cfg(not(tarpaulin_include))]
@@ -88,6 +90,7 @@ impl Session {
         let plaintext = String::from_utf8_lossy(&plaintext).to_string();
 
         self.last_use_time = SecondsSinceUnixEpoch::now();
+        self.decrypted_message_count += 1;
 
         Ok(plaintext)
     }
@@ -273,6 +276,7 @@ impl Session {
             created_using_fallback_key: self.created_using_fallback_key,
             creation_time: self.creation_time,
             last_use_time: self.last_use_time,
+            decrypted_message_count: self.decrypted_message_count,
         }
     }
 
@@ -308,6 +312,7 @@ impl Session {
             our_device_keys,
             creation_time: pickle.creation_time,
             last_use_time: pickle.last_use_time,
+            decrypted_message_count: pickle.decrypted_message_count,
         })
     }
 }
@@ -336,6 +341,9 @@ pub struct PickledSession {
     pub creation_time: SecondsSinceUnixEpoch,
     /// The Unix timestamp when the session was last used.
     pub last_use_time: SecondsSinceUnixEpoch,
+    /// The number of messages that were decrypted using this session.
+    #[serde(default)]
+    pub decrypted_message_count: u64,
 }
 
 #[cfg(test)]
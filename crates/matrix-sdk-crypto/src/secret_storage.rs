@@ -419,7 +419,10 @@ impl SecretStorageKey {
         // > that whitespace is insignificant in the user’s input.
         //
         // Spec link: https://spec.matrix.org/unstable/client-server-api/#key-representation
-        let value: String = value.chars().filter(|c| !c.is_whitespace()).collect();
+        //
+        // Some clients display the key in dash-separated groups, and dashes aren't part
+        // of the Base58 alphabet, so we ignore them as well.
+        let value: String = value.chars().filter(|c| !c.is_whitespace() && *c != '-').collect();
 
         let mut decoded = bs58::decode(value).with_alphabet(bs58::Alphabet::BITCOIN).into_vec()?;
 
@@ -452,6 +455,17 @@ impl SecretStorageKey {
         }
     }
 
+    /// Check that the given string is a valid Base58 export of a
+    /// [`SecretStorageKey`], i.e. a recovery key, without needing the info
+    /// about the key from the account data.
+    ///
+    /// Whitespace and dashes in the string are ignored. This checks the
+    /// encoding, the length, the prefix and the parity byte of the key, but not
+    /// whether it is the key of the user's secret storage.
+    pub fn validate_base58(value: &str) -> Result<(), DecodeError> {
+        Self::parse_base58_key(value).map(|mut key| key.zeroize())
+    }
+
     /// Try to create a [`SecretStorageKey`] from a Base58 export.
     fn from_base58(
         value: &str,
@@ -760,6 +774,44 @@ mod test {
         );
     }
 
+    #[test]
+    fn validate_base58() {
+        let key = SecretStorageKey::new();
+        let base58_key = key.to_base58();
+
+        SecretStorageKey::validate_base58(&base58_key).expect("The exported key should be valid");
+        SecretStorageKey::validate_base58(&base58_key.replace(' ', "-"))
+            .expect("Dashes should be ignored");
+        SecretStorageKey::validate_base58(&format!("  {}\n", base58_key.replace(' ', "")))
+            .expect("Whitespace should be ignored");
+
+        let base58_key = "EsTj 3yST y93F SLpB jJsz eAXc 2XzA ygD3 w69H fGaN TKBj jXEd";
+        SecretStorageKey::validate_base58(base58_key).unwrap();
+
+        assert_matches!(
+            SecretStorageKey::validate_base58(
+                "EsTj 3yST y93F SLpB jJsz eAXc 2XzA ygD3 w69H fGaN TKBj jXEe"
+            ),
+            Err(DecodeError::Parity(63, 62))
+        );
+        assert_matches!(
+            SecretStorageKey::validate_base58(
+                "EsTj 3yST y93F SLpB jJsz eAXc 2XzA ygD3 w69H fGaN TKBj"
+            ),
+            Err(DecodeError::KeyLength(35, 32))
+        );
+        assert_matches!(
+            SecretStorageKey::validate_base58(
+                "EsUK 2XMz Q91X MHMN dsnA 6YDR pvsE X2dd qzUF hASF 8FFp 2KYc"
+            ),
+            Err(DecodeError::Prefix([0x8b, 0x01], [0x8b, 0x02]))
+        );
+        assert_matches!(
+            SecretStorageKey::validate_base58("It's a secret to everybody"),
+            Err(DecodeError::Base58(_))
+        );
+    }
+
     /// The `iv` and `mac` properties within the `m.secret_storage.key.*`
     /// content are optional, and the spec says we must assume the
     /// passphrase is correct in that case.
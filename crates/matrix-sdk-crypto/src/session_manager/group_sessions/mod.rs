@@ -344,6 +344,26 @@ impl GroupSessionManager {
         share_strategy::collect_session_recipients(&self.store, users, settings, outbound).await
     }
 
+    /// Get the devices of the given users which would not receive a room key
+    /// shared with the given strategy, alongside the reason why they are
+    /// excluded.
+    pub async fn devices_withheld_by_strategy(
+        &self,
+        users: impl Iterator<Item = &UserId>,
+        collect_strategy: &CollectStrategy,
+    ) -> OlmResult<Vec<(DeviceData, WithheldCode)>> {
+        let CollectRecipientsResult { withheld_devices, .. } =
+            share_strategy::collect_recipients_for_share_strategy(
+                &self.store,
+                users,
+                collect_strategy,
+                None,
+            )
+            .await?;
+
+        Ok(withheld_devices)
+    }
+
     async fn encrypt_request(
         store: Arc<CryptoStoreWrapper>,
         chunk: Vec<DeviceData>,
@@ -1539,6 +1559,99 @@ mod tests {
         assert!(has_blacklist);
     }
 
+    #[async_test]
+    async fn test_sharing_withheld_unverified_only_trusted() {
+        let machine = machine().await;
+        let room_id = room_id!("!test:localhost");
+        let keys_claim = keys_claim_response();
+
+        let users = keys_claim.one_time_keys.keys().map(Deref::deref);
+        let settings = EncryptionSettings {
+            sharing_strategy: CollectStrategy::OnlyTrustedDevices,
+            ..Default::default()
+        };
+
+        // Trust only one device
+        let user_id = user_id!("@example:localhost");
+        let verified_device_id = device_id!("MWFXPINOAO");
+        let unverified_device_id = device_id!("MWVTUXDNNM");
+        machine
+            .get_device(user_id, verified_device_id, None)
+            .await
+            .unwrap()
+            .unwrap()
+            .set_local_trust(LocalTrust::Verified)
+            .await
+            .unwrap();
+
+        let requests = machine.share_room_key(room_id, users, settings).await.unwrap();
+
+        let withheld_code_for = |device_id: &DeviceId| {
+            let device_key = DeviceIdOrAllDevices::from(device_id.to_owned());
+
+            requests
+                .iter()
+                .filter(|r| r.event_type == "m.room_key.withheld".into())
+                .find_map(|r| r.messages.get(user_id)?.get(&device_key))
+                .map(|content| {
+                    let withheld = content.deserialize_as_unchecked::<RoomKeyWithheldContent>();
+                    assert_let!(Ok(MegolmV1AesSha2(content)) = withheld);
+                    content.withheld_code()
+                })
+        };
+
+        // The verified device receives the room key, and no withheld notice.
+        let verified_device_key = DeviceIdOrAllDevices::from(verified_device_id.to_owned());
+        assert!(requests.iter().any(|r| r.event_type == "m.room.encrypted".into()
+            && r.messages[user_id].contains_key(&verified_device_key)));
+        assert_eq!(withheld_code_for(verified_device_id), None);
+
+        // The unverified device is told why it didn't receive the room key.
+        assert_eq!(withheld_code_for(unverified_device_id), Some(WithheldCode::Unverified));
+    }
+
+    #[async_test]
+    async fn test_devices_withheld_from_room_key() {
+        let machine = machine().await;
+        let keys_claim = keys_claim_response();
+        let users: Vec<_> = keys_claim.one_time_keys.keys().cloned().collect();
+
+        // Trust only one device
+        let user_id = user_id!("@example:localhost");
+        let verified_device_id = device_id!("MWFXPINOAO");
+        let unverified_device_id = device_id!("MWVTUXDNNM");
+        machine
+            .get_device(user_id, verified_device_id, None)
+            .await
+            .unwrap()
+            .unwrap()
+            .set_local_trust(LocalTrust::Verified)
+            .await
+            .unwrap();
+
+        // Nobody is excluded with the default strategy.
+        let withheld = machine
+            .devices_withheld_from_room_key(
+                users.iter().map(Deref::deref),
+                &CollectStrategy::AllDevices,
+            )
+            .await
+            .unwrap();
+        assert!(withheld.is_empty());
+
+        // Only the unverified device is excluded when sharing with trusted devices.
+        let withheld = machine
+            .devices_withheld_from_room_key(
+                users.iter().map(Deref::deref),
+                &CollectStrategy::OnlyTrustedDevices,
+            )
+            .await
+            .unwrap();
+        assert!(withheld.iter().all(|(_, code)| *code == WithheldCode::Unverified));
+        assert!(withheld.iter().any(|(device, _)| device.device_id() == unverified_device_id));
+        assert!(!withheld.iter().any(|(device, _)| device.device_id() == verified_device_id));
+    }
+
     #[async_test]
     async fn test_no_olm_withheld_only_sent_once() {
         let keys_query = keys_query_response();
@@ -1650,8 +1763,11 @@ mod tests {
                 next_batch_token: None,
             };
 
-            let decryption_settings =
-                DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+            let decryption_settings = DecryptionSettings {
+                sender_device_trust_requirement: TrustRequirement::Untrusted,
+                to_device_trust_requirements: Default::default(),
+                unwedging_policy: Default::default(),
+            };
 
             let (decrypted, _) =
                 machine.receive_sync_changes(sync_changes, &decryption_settings).await.unwrap();
@@ -1720,8 +1836,11 @@ mod tests {
                 next_batch_token: None,
             };
 
-            let decryption_settings =
-                DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+            let decryption_settings = DecryptionSettings {
+                sender_device_trust_requirement: TrustRequirement::Untrusted,
+                to_device_trust_requirements: Default::default(),
+                unwedging_policy: Default::default(),
+            };
 
             let (decrypted, _) =
                 machine.receive_sync_changes(sync_changes, &decryption_settings).await.unwrap();
@@ -1833,8 +1952,11 @@ mod tests {
             next_batch_token: None,
         };
 
-        let decryption_settings =
-            DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+        let decryption_settings = DecryptionSettings {
+            sender_device_trust_requirement: TrustRequirement::Untrusted,
+            to_device_trust_requirements: Default::default(),
+            unwedging_policy: Default::default(),
+        };
 
         let (decrypted, _) =
             bob.receive_sync_changes(sync_changes, &decryption_settings).await.unwrap();
@@ -35,7 +35,7 @@ use crate::{Device, UserIdentity};
 
 /// Strategy to collect the devices that should receive room keys for the
 /// current discussion.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
 #[cfg_attr(feature = "uniffi", derive(uniffi::Enum))]
 #[serde(from = "CollectStrategyDeserializationHelper")]
 pub enum CollectStrategy {
@@ -62,6 +62,9 @@ pub enum CollectStrategy {
     /// Share based on identity. Only distribute to devices signed by their
     /// owner. If a user has no published identity he will not receive
     /// any room keys.
+    ///
+    /// The devices which are excluded are sent an `m.room_key.withheld`
+    /// notice with the [`WithheldCode::Unverified`] code.
     IdentityBasedStrategy,
 
     /// Only share keys with devices that we "trust". A device is trusted if any
@@ -71,6 +74,10 @@ pub enum CollectStrategy {
     ///     - It is signed by its owner identity, and this identity has been
     ///       trusted via interactive verification.
     ///     - It is the current own device of the user.
+    ///
+    /// The devices which are excluded because they aren't trusted are sent an
+    /// `m.room_key.withheld` notice with the [`WithheldCode::Unverified`]
+    /// code, blacklisted devices get the [`WithheldCode::Blacklisted`] code.
     OnlyTrustedDevices,
 }
 
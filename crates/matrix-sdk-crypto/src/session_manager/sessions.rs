@@ -25,8 +25,8 @@ use ruma::{
     },
     assign,
     events::dummy::ToDeviceDummyEventContent,
-    DeviceId, OneTimeKeyAlgorithm, OwnedDeviceId, OwnedOneTimeKeyId, OwnedServerName,
-    OwnedTransactionId, OwnedUserId, SecondsSinceUnixEpoch, ServerName, TransactionId, UserId,
+    DeviceId, MilliSecondsSinceUnixEpoch, OneTimeKeyAlgorithm, OwnedDeviceId, OwnedOneTimeKeyId,
+    OwnedServerName, OwnedTransactionId, OwnedUserId, ServerName, TransactionId, UserId,
 };
 use tracing::{debug, error, info, instrument, warn};
 use vodozemac::Curve25519PublicKey;
@@ -40,7 +40,7 @@ use crate::{
         requests::{OutgoingRequest, ToDeviceRequest},
         EventEncryptionAlgorithm,
     },
-    DeviceData,
+    DeviceData, UnwedgingPolicy,
 };
 
 #[derive(Debug, Clone)]
@@ -62,7 +62,9 @@ pub(crate) struct SessionManager {
     /// user/device paris will be added to the list of users when
     /// [`get_missing_sessions`](#method.get_missing_sessions) is called.
     users_for_key_claim: Arc<StdRwLock<BTreeMap<OwnedUserId, BTreeSet<OwnedDeviceId>>>>,
-    wedged_devices: Arc<StdRwLock<BTreeMap<OwnedUserId, BTreeSet<OwnedDeviceId>>>>,
+    /// The devices whose Olm session we are trying to unwedge, with the
+    /// policy that was in use when the session was found to be wedged.
+    wedged_devices: Arc<StdRwLock<BTreeMap<OwnedUserId, BTreeMap<OwnedDeviceId, UnwedgingPolicy>>>>,
     key_request_machine: GossipMachine,
     outgoing_to_device_requests: Arc<StdRwLock<BTreeMap<OwnedTransactionId, OutgoingRequest>>>,
 
@@ -77,7 +79,6 @@ pub(crate) struct SessionManager {
 
 impl SessionManager {
     const KEY_CLAIM_TIMEOUT: Duration = Duration::from_secs(10);
-    const UNWEDGING_INTERVAL: Duration = Duration::from_secs(60 * 60);
 
     pub fn new(
         users_for_key_claim: Arc<StdRwLock<BTreeMap<OwnedUserId, BTreeSet<OwnedDeviceId>>>>,
@@ -101,24 +102,59 @@ impl SessionManager {
         self.outgoing_to_device_requests.write().remove(id);
     }
 
+    /// The key under which the time of the last attempt to unwedge the Olm
+    /// session with the device owning the given Curve25519 key is stored.
+    fn unwedging_attempt_key(curve_key: Curve25519PublicKey) -> String {
+        format!("olm_unwedging_attempt_{}", curve_key.to_base64())
+    }
+
+    /// Get the time of the last attempt to unwedge the Olm session with the
+    /// device owning the given Curve25519 key.
+    pub async fn last_unwedging_attempt(
+        &self,
+        curve_key: Curve25519PublicKey,
+    ) -> StoreResult<Option<MilliSecondsSinceUnixEpoch>> {
+        self.store.get_value(&Self::unwedging_attempt_key(curve_key)).await
+    }
+
+    /// Mark the device with the given Curve25519 key as wedged.
+    ///
+    /// Returns `true` if a new Olm session is going to be created with the
+    /// device to unwedge it, `false` if it was already attempted recently.
     pub async fn mark_device_as_wedged(
         &self,
         sender: &UserId,
         curve_key: Curve25519PublicKey,
-    ) -> OlmResult<()> {
+        policy: &UnwedgingPolicy,
+    ) -> OlmResult<bool> {
         if let Some(device) = self.store.get_device_from_curve_key(sender, curve_key).await? {
             if let Some(session) = device.get_most_recent_session().await? {
                 info!(sender_key = ?curve_key, "Marking session to be unwedged");
 
                 let creation_time = Duration::from_secs(session.creation_time.get().into());
-                let now = Duration::from_secs(SecondsSinceUnixEpoch::now().get().into());
+                let last_attempt = self
+                    .last_unwedging_attempt(curve_key)
+                    .await?
+                    .map(|time| Duration::from_millis(time.get().into()));
+                let now = Duration::from_millis(MilliSecondsSinceUnixEpoch::now().get().into());
+
+                let is_older_than_interval = |time: Duration| {
+                    now.checked_sub(time)
+                        .map(|elapsed| elapsed > policy.min_interval)
+                        .unwrap_or(true)
+                };
 
-                let should_unwedge = now
-                    .checked_sub(creation_time)
-                    .map(|elapsed| elapsed > Self::UNWEDGING_INTERVAL)
-                    .unwrap_or(true);
+                let should_unwedge = is_older_than_interval(creation_time)
+                    && last_attempt.is_none_or(is_older_than_interval);
 
                 if should_unwedge {
+                    self.store
+                        .set_value(
+                            &Self::unwedging_attempt_key(curve_key),
+                            &MilliSecondsSinceUnixEpoch::now(),
+                        )
+                        .await?;
+
                     self.users_for_key_claim
                         .write()
                         .entry(device.user_id().to_owned())
@@ -128,12 +164,20 @@ impl SessionManager {
                         .write()
                         .entry(device.user_id().to_owned())
                         .or_default()
-                        .insert(device.device_id().into());
+                        .insert(device.device_id().into(), policy.clone());
+
+                    return Ok(true);
+                } else {
+                    debug!(
+                        sender_key = ?curve_key,
+                        ?last_attempt,
+                        "Not unwedging the session, a recent attempt was already made"
+                    );
                 }
             }
         }
 
-        Ok(())
+        Ok(false)
     }
 
     #[allow(dead_code)]
@@ -141,14 +185,25 @@ impl SessionManager {
         self.wedged_devices
             .read()
             .get(device.user_id())
-            .is_some_and(|d| d.contains(device.device_id()))
+            .is_some_and(|d| d.contains_key(device.device_id()))
     }
 
     /// Check if the session was created to unwedge a Device.
     ///
-    /// If the device was wedged this will queue up a dummy to-device message.
+    /// If the device was wedged this will, depending on the unwedging policy,
+    /// queue up a dummy to-device message and re-send our room key requests.
     async fn check_if_unwedged(&self, user_id: &UserId, device_id: &DeviceId) -> OlmResult<()> {
-        if self.wedged_devices.write().get_mut(user_id).is_some_and(|d| d.remove(device_id)) {
+        let policy = self.wedged_devices.write().get_mut(user_id).and_then(|d| d.remove(device_id));
+
+        let Some(policy) = policy else {
+            return Ok(());
+        };
+
+        if policy.re_request_room_keys && user_id == self.store.user_id() {
+            self.key_request_machine.resend_room_key_requests().await?;
+        }
+
+        if policy.send_dummy_event {
             if let Some(device) = self.store.get_device(user_id, device_id).await? {
                 let (_, content) =
                     device.encrypt("m.dummy", ToDeviceDummyEventContent::new()).await?;
@@ -611,6 +666,7 @@ mod tests {
             CryptoStoreWrapper, MemoryStore, Store,
         },
         verification::VerificationMachine,
+        UnwedgingPolicy,
     };
 
     fn user_id() -> &'static UserId {
@@ -820,7 +876,7 @@ mod tests {
     }
 
     // This test doesn't run on macos because we're modifying the session
-    // creation time so we can get around the UNWEDGING_INTERVAL.
+    // creation time so we can get around the unwedging interval.
     #[async_test]
     #[cfg(target_os = "linux")]
     async fn test_session_unwedging() {
@@ -853,7 +909,12 @@ mod tests {
 
         assert!(!manager.users_for_key_claim.read().contains_key(bob.user_id()));
         assert!(!manager.is_device_wedged(&bob_device));
-        manager.mark_device_as_wedged(bob_device.user_id(), curve_key).await.unwrap();
+        assert!(manager.last_unwedging_attempt(curve_key).await.unwrap().is_none());
+        assert!(manager
+            .mark_device_as_wedged(bob_device.user_id(), curve_key, &UnwedgingPolicy::default())
+            .await
+            .unwrap());
+        assert!(manager.last_unwedging_attempt(curve_key).await.unwrap().is_some());
         assert!(manager.is_device_wedged(&bob_device));
         assert!(manager.users_for_key_claim.read().contains_key(bob.user_id()));
 
@@ -884,6 +945,99 @@ mod tests {
         assert!(!manager.outgoing_to_device_requests.read().is_empty())
     }
 
+    // This test doesn't run on macos because we're modifying the session
+    // creation time so we can get around the unwedging interval.
+    #[async_test]
+    #[cfg(target_os = "linux")]
+    async fn test_session_unwedging_policy() {
+        use ruma::{time::SystemTime, SecondsSinceUnixEpoch};
+
+        let (manager, _identity_manager) = session_manager_test_helper().await;
+        let mut bob = bob_account();
+
+        let (_, mut session) = manager
+            .store
+            .with_transaction(|mut tr| async {
+                let manager_account = tr.account().await.unwrap();
+                let res = bob.create_session_for_test_helper(manager_account).await;
+                Ok((tr, res))
+            })
+            .await
+            .unwrap();
+
+        let bob_device = DeviceData::from_account(&bob);
+        let time = SystemTime::now() - Duration::from_secs(120);
+        session.creation_time = SecondsSinceUnixEpoch::from_system_time(time).unwrap();
+
+        let devices = std::slice::from_ref(&bob_device);
+        manager.store.save_device_data(devices).await.unwrap();
+        manager.store.save_sessions(&[session]).await.unwrap();
+
+        let curve_key = bob_device.curve25519_key().unwrap();
+
+        // With the default policy, the session is too recent to be unwedged.
+        manager
+            .mark_device_as_wedged(bob_device.user_id(), curve_key, &UnwedgingPolicy::default())
+            .await
+            .unwrap();
+        assert!(!manager.is_device_wedged(&bob_device));
+        assert!(manager.last_unwedging_attempt(curve_key).await.unwrap().is_none());
+
+        // With a shorter interval, it is.
+        let policy = UnwedgingPolicy {
+            min_interval: Duration::from_secs(60),
+            send_dummy_event: false,
+            re_request_room_keys: false,
+        };
+
+        manager.mark_device_as_wedged(bob_device.user_id(), curve_key, &policy).await.unwrap();
+        assert!(manager.is_device_wedged(&bob_device));
+        assert!(manager.last_unwedging_attempt(curve_key).await.unwrap().is_some());
+
+        // A second attempt right away is throttled, even if the device is still
+        // considered wedged.
+        manager.wedged_devices.write().clear();
+        manager.users_for_key_claim.write().clear();
+
+        manager.mark_device_as_wedged(bob_device.user_id(), curve_key, &policy).await.unwrap();
+        assert!(!manager.is_device_wedged(&bob_device));
+        assert!(!manager.users_for_key_claim.read().contains_key(bob.user_id()));
+
+        // Once the new session is created, no dummy event is sent since the
+        // policy disables it.
+        manager
+            .wedged_devices
+            .write()
+            .entry(bob.user_id().to_owned())
+            .or_default()
+            .insert(bob.device_id().to_owned(), policy.clone());
+        manager
+            .users_for_key_claim
+            .write()
+            .entry(bob.user_id().to_owned())
+            .or_default()
+            .insert(bob.device_id().to_owned());
+
+        let (txn_id, _) =
+            manager.get_missing_sessions(iter::once(bob.user_id())).await.unwrap().unwrap();
+
+        bob.generate_one_time_keys(1);
+        let one_time = bob.signed_one_time_keys();
+        bob.mark_keys_as_published();
+
+        let mut one_time_keys = BTreeMap::new();
+        one_time_keys
+            .entry(bob.user_id().to_owned())
+            .or_insert_with(BTreeMap::new)
+            .insert(bob.device_id().to_owned(), one_time);
+
+        let response = KeyClaimResponse::new(one_time_keys);
+        manager.receive_keys_claim_response(&txn_id, &response).await.unwrap();
+
+        assert!(!manager.is_device_wedged(&bob_device));
+        assert!(manager.outgoing_to_device_requests.read().is_empty());
+    }
+
     #[async_test]
     async fn test_failure_handling() {
         let alice = user_id!("@alice:example.org");
@@ -9,10 +9,12 @@ use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
 use tracing::{debug, trace, warn};
 
 use super::{
-    caches::SessionStore, types::RoomKeyBundleInfo, DeviceChanges, IdentityChanges,
-    LockableCryptoStore,
+    caches::SessionStore,
+    types::{RoomKeyBundleInfo, SenderDataUpgrade},
+    DeviceChanges, IdentityChanges, LockableCryptoStore,
 };
 use crate::{
+    machine::ToDeviceUtdInfo,
     olm::InboundGroupSession,
     store,
     store::{Changes, DynCryptoStore, IntoCryptoStore, RoomKeyInfo, RoomKeyWithheldInfo},
@@ -53,6 +55,14 @@ pub(crate) struct CryptoStoreWrapper {
     /// The sender side of a broadcast channel which sends out information about
     /// historic room key bundles we have received.
     historic_room_key_bundles_broadcaster: broadcast::Sender<RoomKeyBundleInfo>,
+
+    /// The sender side of a broadcast channel which sends out information about
+    /// the to-device events we failed to decrypt.
+    to_device_utds_broadcaster: broadcast::Sender<ToDeviceUtdInfo>,
+
+    /// The sender side of a broadcast channel which sends out the inbound group
+    /// sessions whose sender data got upgraded.
+    sender_data_upgrades_broadcaster: broadcast::Sender<Vec<SenderDataUpgrade>>,
 }
 
 impl CryptoStoreWrapper {
@@ -64,6 +74,10 @@ impl CryptoStoreWrapper {
         // devices, that's why we increase the capacity here.
         let identities_broadcaster = broadcast::Sender::new(20);
         let historic_room_key_bundles_broadcaster = broadcast::Sender::new(10);
+        // To-device UTDs tend to come in bursts, e.g. when an Olm session gets wedged,
+        // so give the listeners some more room before the oldest items are dropped.
+        let to_device_utds_broadcaster = broadcast::Sender::new(50);
+        let sender_data_upgrades_broadcaster = broadcast::Sender::new(10);
 
         Self {
             user_id: user_id.to_owned(),
@@ -75,6 +89,8 @@ impl CryptoStoreWrapper {
             secrets_broadcaster,
             identities_broadcaster,
             historic_room_key_bundles_broadcaster,
+            to_device_utds_broadcaster,
+            sender_data_upgrades_broadcaster,
         }
     }
 
@@ -311,6 +327,33 @@ impl CryptoStoreWrapper {
         Ok(())
     }
 
+    /// Save the time of last use of an inbound group session we already know
+    /// about.
+    ///
+    /// Unlike [`CryptoStoreWrapper::save_inbound_group_sessions`], this doesn't
+    /// notify the listeners of the room keys stream, since no new room key was
+    /// received. The session is only saved if the stored copy has the same
+    /// first known index, so that a better copy of the session which was
+    /// received in the meantime isn't overwritten.
+    pub async fn save_inbound_group_session_last_used(
+        &self,
+        session: &InboundGroupSession,
+    ) -> store::Result<()> {
+        let stored =
+            self.store.get_inbound_group_session(session.room_id(), session.session_id()).await?;
+
+        if stored.is_some_and(|stored| stored.first_known_index() == session.first_known_index()) {
+            self.store
+                .save_changes(Changes {
+                    inbound_group_sessions: vec![session.clone()],
+                    ..Default::default()
+                })
+                .await?;
+        }
+
+        Ok(())
+    }
+
     /// Receive notifications of room keys being received as a [`Stream`].
     ///
     /// Each time a room key is updated in any way, an update will be sent to
@@ -354,6 +397,39 @@ impl CryptoStoreWrapper {
         Self::filter_errors_out_of_stream(stream, "bundle_stream")
     }
 
+    /// Receive notifications of to-device events we failed to decrypt as a
+    /// [`Stream`].
+    pub fn to_device_utds_stream(&self) -> impl Stream<Item = ToDeviceUtdInfo> {
+        let stream = BroadcastStream::new(self.to_device_utds_broadcaster.subscribe());
+        Self::filter_errors_out_of_stream(stream, "to_device_utds_stream")
+    }
+
+    /// Notify the listeners of [`CryptoStoreWrapper::to_device_utds_stream()`]
+    /// that a to-device event couldn't be decrypted.
+    pub(crate) fn notify_to_device_utd(&self, utd_info: ToDeviceUtdInfo) {
+        // It's OK if there are no listeners, and if a listener lags behind, the oldest
+        // items are dropped, so this never blocks the sync processing.
+        let _ = self.to_device_utds_broadcaster.send(utd_info);
+    }
+
+    /// Receive notifications of inbound group sessions whose sender data got
+    /// upgraded as a [`Stream`].
+    pub fn sender_data_upgrades_stream(&self) -> impl Stream<Item = Vec<SenderDataUpgrade>> {
+        let stream = BroadcastStream::new(self.sender_data_upgrades_broadcaster.subscribe());
+        Self::filter_errors_out_of_stream(stream, "sender_data_upgrades_stream")
+    }
+
+    /// Notify the listeners of
+    /// [`CryptoStoreWrapper::sender_data_upgrades_stream`] that the sender
+    /// data of the given sessions got upgraded.
+    ///
+    /// The upgraded sessions should already be saved in the store.
+    pub(super) fn notify_sender_data_upgrades(&self, upgrades: Vec<SenderDataUpgrade>) {
+        if !upgrades.is_empty() {
+            let _ = self.sender_data_upgrades_broadcaster.send(upgrades);
+        }
+    }
+
     /// Returns a stream of newly created or updated cryptographic identities.
     ///
     /// This is just a helper method which allows us to build higher level
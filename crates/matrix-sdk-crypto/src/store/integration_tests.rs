@@ -75,7 +75,7 @@ macro_rules! cryptostore_integration_tests {
                     DeviceKeys,
                     EventEncryptionAlgorithm,
                 },
-                vodozemac::megolm::{GroupSession, SessionConfig}, DeviceData, GossippedSecret, LocalTrust,  SecretInfo,
+                vodozemac::megolm::{GroupSession, SessionConfig}, CollectStrategy, DeviceData, GossippedSecret, LocalTrust,  SecretInfo,
                 TrackedUser,
             };
 
@@ -531,6 +531,54 @@ macro_rules! cryptostore_integration_tests {
                 assert_eq!(to_back_up.len(), 10);
             }
 
+            #[async_test]
+            async fn test_get_inbound_group_sessions_batch_and_delete() {
+                // Given a store containing sessions of two rooms
+                let (account, store) =
+                    get_loaded_store("get_inbound_group_sessions_batch_and_delete").await;
+                let room_a = room_id!("!a:localhost");
+                let room_b = room_id!("!b:localhost");
+                let mut sessions: Vec<InboundGroupSession> = Vec::with_capacity(10);
+                for i in 0..10 {
+                    let room_id = if i % 2 == 0 { room_a } else { room_b };
+                    sessions.push(account.create_group_session_pair_with_defaults(room_id).await.1);
+                }
+                let changes = Changes { inbound_group_sessions: sessions.clone(), ..Default::default() };
+                store.save_changes(changes).await.expect("Can't save group sessions");
+
+                // When we iterate over them in batches
+                let mut fetched = Vec::new();
+                let mut after = None;
+                loop {
+                    let batch = store.get_inbound_group_sessions_batch(after.take(), 3).await.unwrap();
+                    assert!(batch.len() <= 3);
+                    let Some(last) = batch.last() else { break };
+                    after = Some((last.room_id().to_owned(), last.session_id().to_owned()));
+                    fetched.extend(batch);
+                }
+
+                // Then we get all of them, exactly once
+                let mut fetched_ids: Vec<_> = fetched.iter().map(|s| s.session_id().to_owned()).collect();
+                fetched_ids.sort();
+                let mut expected_ids: Vec<_> = sessions.iter().map(|s| s.session_id().to_owned()).collect();
+                expected_ids.sort();
+                assert_eq!(fetched_ids, expected_ids);
+
+                // And when we delete some of them
+                let to_delete: Vec<_> = sessions.iter().take(4).map(session_info).collect();
+                store.delete_inbound_group_sessions(&to_delete).await.unwrap();
+
+                // Then only the others are left
+                assert_eq!(store.inbound_group_session_counts(None).await.unwrap().total, 6);
+                for (i, session) in sessions.iter().enumerate() {
+                    let loaded = store
+                        .get_inbound_group_session(session.room_id(), session.session_id())
+                        .await
+                        .unwrap();
+                    assert_eq!(loaded.is_some(), i >= 4);
+                }
+            }
+
             #[async_test]
             async fn test_load_inbound_group_session() {
                 let dir = "load_inbound_group_session";
@@ -975,6 +1023,7 @@ macro_rules! cryptostore_integration_tests {
                 let stored_request = store.get_secret_request_by_info(&info).await.unwrap();
                 assert_eq!(request, stored_request);
                 assert!(!store.get_unsent_secret_requests().await.unwrap().is_empty());
+                assert!(store.get_sent_secret_requests().await.unwrap().is_empty());
 
                 let request = GossipRequest {
                     request_recipient: account.user_id().to_owned(),
@@ -988,6 +1037,7 @@ macro_rules! cryptostore_integration_tests {
                 store.save_changes(changes).await.unwrap();
 
                 assert!(store.get_unsent_secret_requests().await.unwrap().is_empty());
+                assert_eq!(store.get_sent_secret_requests().await.unwrap(), vec![request.clone()]);
                 let stored_request = store.get_outgoing_secret_requests(&id).await.unwrap();
                 assert_eq!(Some(request), stored_request);
 
@@ -999,6 +1049,7 @@ macro_rules! cryptostore_integration_tests {
                 let stored_request = store.get_secret_request_by_info(&info).await.unwrap();
                 assert_eq!(None, stored_request);
                 assert!(store.get_unsent_secret_requests().await.unwrap().is_empty());
+                assert!(store.get_sent_secret_requests().await.unwrap().is_empty());
             }
 
             #[async_test]
@@ -1166,6 +1217,7 @@ macro_rules! cryptostore_integration_tests {
                     only_allow_trusted_devices: true,
                     session_rotation_period: Some(Duration::from_secs(10)),
                     session_rotation_period_messages: Some(123),
+                    sharing_strategy: Some(CollectStrategy::IdentityBasedStrategy),
                 };
 
                 let room_2 = room_id!("!test_2:localhost");
@@ -363,6 +363,14 @@ impl CryptoStore for MemoryStore {
                     .entry(room_id.to_owned())
                     .or_default()
                     .insert(session_id.to_owned(), BackupVersion::from(backup_version));
+            } else if !backed_up {
+                // The backup state of the session was reset, so forget about the backup it
+                // was uploaded to, if any.
+                if let Some(room_backed_up_to) =
+                    self.inbound_group_sessions_backed_up_to.write().get_mut(room_id)
+                {
+                    room_backed_up_to.remove(session_id);
+                }
             }
 
             let pickle = session.pickle().await;
@@ -506,6 +514,55 @@ impl CryptoStore for MemoryStore {
         Ok(sessions.drain(start_index..).take(limit).collect())
     }
 
+    async fn get_inbound_group_sessions_batch(
+        &self,
+        after: Option<(OwnedRoomId, String)>,
+        limit: usize,
+    ) -> Result<Vec<InboundGroupSession>> {
+        let mut sessions = self.get_inbound_group_sessions().await?;
+
+        // Sort the sessions in order of ascending room ID and session ID, and skip the
+        // ones up to and including `after`.
+        sessions.sort_by(|a, b| (a.room_id(), a.session_id()).cmp(&(b.room_id(), b.session_id())));
+
+        let start_index = match after {
+            None => 0,
+            Some((room_id, session_id)) => sessions
+                .iter()
+                .position(|session| {
+                    (session.room_id(), session.session_id())
+                        > (room_id.as_ref(), session_id.as_str())
+                })
+                .unwrap_or(sessions.len()),
+        };
+
+        Ok(sessions.drain(start_index..).take(limit).collect())
+    }
+
+    async fn delete_inbound_group_sessions(
+        &self,
+        room_and_session_ids: &[(&RoomId, &str)],
+    ) -> Result<()> {
+        let mut sessions = self.inbound_group_sessions.write();
+        let mut backed_up_to = self.inbound_group_sessions_backed_up_to.write();
+
+        for &(room_id, session_id) in room_and_session_ids {
+            if let Some(room_sessions) = sessions.get_mut(room_id) {
+                room_sessions.remove(session_id);
+
+                if room_sessions.is_empty() {
+                    sessions.remove(room_id);
+                }
+            }
+
+            if let Some(room_backed_up_to) = backed_up_to.get_mut(room_id) {
+                room_backed_up_to.remove(session_id);
+            }
+        }
+
+        Ok(())
+    }
+
     async fn inbound_group_sessions_for_backup(
         &self,
         backup_version: &str,
@@ -679,6 +736,10 @@ impl CryptoStore for MemoryStore {
             .collect())
     }
 
+    async fn get_sent_secret_requests(&self) -> Result<Vec<GossipRequest>> {
+        Ok(self.outgoing_key_requests.read().values().filter(|req| req.sent_out).cloned().collect())
+    }
+
     async fn delete_outgoing_secret_requests(&self, request_id: &TransactionId) -> Result<()> {
         let req = self.outgoing_key_requests.write().remove(request_id);
         if let Some(i) = req {
@@ -1239,7 +1300,8 @@ mod integration_tests {
 
     use async_trait::async_trait;
     use ruma::{
-        events::secret::request::SecretName, DeviceId, OwnedDeviceId, RoomId, TransactionId, UserId,
+        events::secret::request::SecretName, DeviceId, OwnedDeviceId, OwnedRoomId, RoomId,
+        TransactionId, UserId,
     };
     use vodozemac::Curve25519PublicKey;
 
@@ -1405,6 +1467,21 @@ mod integration_tests {
                 .await
         }
 
+        async fn get_inbound_group_sessions_batch(
+            &self,
+            after: Option<(OwnedRoomId, String)>,
+            limit: usize,
+        ) -> Result<Vec<InboundGroupSession>, Self::Error> {
+            self.0.get_inbound_group_sessions_batch(after, limit).await
+        }
+
+        async fn delete_inbound_group_sessions(
+            &self,
+            room_and_session_ids: &[(&RoomId, &str)],
+        ) -> Result<(), Self::Error> {
+            self.0.delete_inbound_group_sessions(room_and_session_ids).await
+        }
+
         async fn reset_backup_state(&self) -> Result<(), Self::Error> {
             self.0.reset_backup_state().await
         }
@@ -1489,6 +1566,10 @@ mod integration_tests {
             self.0.get_unsent_secret_requests().await
         }
 
+        async fn get_sent_secret_requests(&self) -> Result<Vec<GossipRequest>, Self::Error> {
+            self.0.get_sent_secret_requests().await
+        }
+
         async fn delete_outgoing_secret_requests(
             &self,
             request_id: &TransactionId,
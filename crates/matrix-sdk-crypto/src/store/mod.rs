@@ -49,11 +49,11 @@ use std::{
 
 use as_variant::as_variant;
 use futures_core::Stream;
-use futures_util::StreamExt;
+use futures_util::{StreamExt, TryStreamExt};
 use itertools::{Either, Itertools};
 use ruma::{
     encryption::KeyUsage, events::secret::request::SecretName, DeviceId, OwnedDeviceId,
-    OwnedUserId, RoomId, UserId,
+    OwnedRoomId, OwnedUserId, RoomId, UserId,
 };
 use serde::{de::DeserializeOwned, Serialize};
 use thiserror::Error;
@@ -64,12 +64,14 @@ use types::RoomKeyBundleInfo;
 use vodozemac::{megolm::SessionOrdering, Curve25519PublicKey};
 
 use self::types::{
-    Changes, CrossSigningKeyExport, DeviceChanges, DeviceUpdates, IdentityChanges, IdentityUpdates,
-    PendingChanges, RoomKeyInfo, RoomKeyWithheldInfo, UserKeyQueryResult,
+    Changes, CrossSigningKeyExport, DehydratedDeviceKey, DeviceChanges, DeviceUpdates,
+    IdentityChanges, IdentityUpdates, PendingChanges, RoomKeyInfo, RoomKeyWithheldInfo,
+    SenderDataUpgrade, UserKeyQueryResult,
 };
 #[cfg(doc)]
 use crate::{backups::BackupMachine, identities::OwnUserIdentity};
 use crate::{
+    dehydrated_devices::DEHYDRATED_DEVICE_PICKLE_KEY_SECRET_NAME,
     gossiping::GossippedSecret,
     identities::{user::UserIdentity, Device, DeviceData, UserDevices, UserIdentityData},
     olm::{
@@ -113,6 +115,14 @@ pub use crate::{
     gossiping::{GossipRequest, SecretInfo},
 };
 
+/// The number of inbound group sessions loaded from the store at once when
+/// exporting room keys.
+const ROOM_KEY_EXPORT_BATCH_SIZE: usize = 1000;
+
+/// The key under which the device pinning flag is stored, see
+/// [`Store::set_device_pinning_enabled()`].
+const DEVICE_PINNING_ENABLED_KEY: &str = "device_pinning_enabled";
+
 /// A wrapper for our CryptoStore trait object.
 ///
 /// This is needed because we want to have a generic interface so we can
@@ -370,6 +380,7 @@ fn collect_device_updates(
 ) -> DeviceUpdates {
     let mut new: BTreeMap<_, BTreeMap<_, _>> = BTreeMap::new();
     let mut changed: BTreeMap<_, BTreeMap<_, _>> = BTreeMap::new();
+    let mut deleted: BTreeMap<_, BTreeMap<_, _>> = BTreeMap::new();
 
     let (new_identities, changed_identities, unchanged_identities) = identities.into_maps();
 
@@ -405,7 +416,16 @@ fn collect_device_updates(
             .insert(device.device_id().to_owned(), device.to_owned());
     }
 
-    DeviceUpdates { new, changed }
+    for device in devices.deleted {
+        let device = map_device(device);
+
+        deleted
+            .entry(device.user_id().to_owned())
+            .or_default()
+            .insert(device.device_id().to_owned(), device);
+    }
+
+    DeviceUpdates { new, changed, deleted }
 }
 
 /// A temporary transaction (that implies a write) to the underlying store.
@@ -485,6 +505,11 @@ struct StoreInner {
     /// Static account data that never changes (and thus can be loaded once and
     /// for all when creating the store).
     static_account: StaticAccountData,
+
+    /// Lock serializing the imports of room keys, so that concurrent imports
+    /// of the same room keys, e.g. from a download of the whole key backup and
+    /// a download of the keys of a single room, don't import them twice.
+    room_key_import_lock: Mutex<()>,
 }
 
 /// Error describing what went wrong when importing private cross signing keys
@@ -504,6 +529,9 @@ pub enum SecretImportError {
     /// The new version of the identity couldn't be stored.
     #[error(transparent)]
     Store(#[from] CryptoStoreError),
+    /// The dehydrated device pickle key that we tried to import was invalid.
+    #[error(transparent)]
+    Dehydration(#[from] DehydrationError),
 }
 
 /// Error describing what went wrong when exporting a [`SecretsBundle`].
@@ -547,6 +575,7 @@ impl Store {
                     loaded_tracked_users: Default::default(),
                     account: Default::default(),
                 })),
+                room_key_import_lock: Default::default(),
             }),
         }
     }
@@ -857,6 +886,9 @@ impl Store {
                     None
                 }
             }
+            name if name.as_str() == DEHYDRATED_DEVICE_PICKLE_KEY_SECRET_NAME => {
+                self.load_dehydrated_device_pickle_key().await?.map(|key| key.to_base64())
+            }
             name => {
                 warn!(secret = ?name, "Unknown secret was requested");
                 None
@@ -1064,6 +1096,17 @@ impl Store {
                 // it will stay until it either gets overwritten
                 // or the user accepts the secret.
             }
+            name if name.as_str() == DEHYDRATED_DEVICE_PICKLE_KEY_SECRET_NAME => {
+                let pickle_key = DehydratedDeviceKey::from_base64(&secret.event.content.secret)?;
+
+                let changes = Changes {
+                    dehydrated_device_pickle_key: Some(pickle_key),
+                    ..Default::default()
+                };
+                self.save_changes(changes).await?;
+
+                info!("Successfully imported the dehydrated device pickle key");
+            }
             name => {
                 warn!(secret = ?name, "Tried to import an unknown secret");
             }
@@ -1088,6 +1131,89 @@ impl Store {
         self.set_value("only_allow_trusted_devices", &block_untrusted_devices).await
     }
 
+    /// Check whether the device pinning mode, enabled with
+    /// [`Store::set_device_pinning_enabled()`], is enabled.
+    pub async fn is_device_pinning_enabled(&self) -> Result<bool> {
+        let value = self.get_value(DEVICE_PINNING_ENABLED_KEY).await?.unwrap_or_default();
+        Ok(value)
+    }
+
+    /// Enable or disable the device pinning mode.
+    ///
+    /// This is a trust-on-first-use mechanism, meant for deployments where
+    /// cross-signing isn't available: the identity keys of the devices of a
+    /// user are pinned the first time we see them, and the events sent by
+    /// unsigned devices which aren't pinned are then marked with
+    /// [`VerificationLevel::PinViolation`], as long as the user has at least
+    /// one pinned device. Devices which are signed by their owner's
+    /// cross-signing identity aren't affected by the pinning.
+    ///
+    /// When enabling the mode, the devices of the users we already know about
+    /// are pinned, unless the user already has pinned devices. If the
+    /// devices of a user change, they can be re-pinned with
+    /// [`Store::pin_user_devices()`].
+    ///
+    /// [`VerificationLevel::PinViolation`]: matrix_sdk_common::deserialized_responses::VerificationLevel::PinViolation
+    pub async fn set_device_pinning_enabled(&self, enabled: bool) -> Result<()> {
+        self.set_value(DEVICE_PINNING_ENABLED_KEY, &enabled).await?;
+
+        if enabled {
+            let tracked_users = self.cache().await?.tracked_users();
+
+            for user_id in tracked_users {
+                let devices = self.get_device_data_for_user(&user_id).await?;
+
+                if !devices.values().any(|d| d.is_pinned()) {
+                    self.pin_devices(devices.into_values()).await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Pin the current identity keys of all the devices of the given user,
+    /// replacing the previously pinned ones.
+    ///
+    /// This should be called once the user acknowledged that the devices of
+    /// the given user changed, see [`Store::set_device_pinning_enabled()`].
+    pub async fn pin_user_devices(&self, user_id: &UserId) -> Result<()> {
+        let devices = self.get_device_data_for_user(user_id).await?;
+        self.pin_devices(devices.into_values()).await
+    }
+
+    async fn pin_devices(&self, devices: impl IntoIterator<Item = DeviceData>) -> Result<()> {
+        let changed: Vec<_> = devices.into_iter().filter(|device| device.pin()).collect();
+
+        if !changed.is_empty() {
+            let changes = Changes {
+                devices: DeviceChanges { changed, ..Default::default() },
+                ..Default::default()
+            };
+            self.save_changes(changes).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Check whether the events sent by the given device contradict the
+    /// devices pinned for its owner, see
+    /// [`Store::set_device_pinning_enabled()`].
+    pub(crate) async fn violates_device_pins(
+        &self,
+        user_id: &UserId,
+        device_id: &DeviceId,
+    ) -> Result<bool> {
+        if !self.is_device_pinning_enabled().await? {
+            return Ok(false);
+        }
+
+        let devices = self.get_device_data_for_user(user_id).await?;
+        let is_pinned = devices.get(device_id).is_some_and(|d| d.is_pinned());
+
+        Ok(!is_pinned && devices.values().any(|d| d.is_pinned()))
+    }
+
     /// Get custom stored value associated with a key
     pub async fn get_value<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
         let Some(value) = self.get_custom_value(key).await? else {
@@ -1360,6 +1486,30 @@ impl Store {
         self.inner.store.historic_room_key_stream()
     }
 
+    /// Receive notifications of room keys whose [`SenderData`] got upgraded as
+    /// a [`Stream`].
+    ///
+    /// A room key can be received before the device or the cross-signing keys
+    /// of its sender are known, in which case its sender data is upgraded once
+    /// they have been fetched. The encryption info, and so the shields, of the
+    /// events decrypted with those room keys should then be refreshed.
+    ///
+    /// Upgrades that happen at the same time are batched into a [`Vec`].
+    ///
+    /// If the reader of the stream lags too far behind, a warning will be
+    /// logged and items will be dropped.
+    ///
+    /// [`SenderData`]: crate::olm::SenderData
+    pub fn sender_data_upgrades_stream(&self) -> impl Stream<Item = Vec<SenderDataUpgrade>> {
+        self.inner.store.sender_data_upgrades_stream()
+    }
+
+    /// Notify the listeners of [`Store::sender_data_upgrades_stream()`] that
+    /// the sender data of some inbound group sessions got upgraded.
+    pub(crate) fn notify_sender_data_upgrades(&self, upgrades: Vec<SenderDataUpgrade>) {
+        self.inner.store.notify_sender_data_upgrades(upgrades)
+    }
+
     /// Import the given room keys into the store.
     ///
     /// # Arguments
@@ -1426,6 +1576,8 @@ impl Store {
         T: TryInto<InboundGroupSession> + RoomKeyExport + Copy,
         T::Error: Debug,
     {
+        let _import_lock = self.inner.room_key_import_lock.lock().await;
+
         let mut sessions = Vec::new();
 
         async fn new_session_better(
@@ -1496,6 +1648,10 @@ impl Store {
 
     /// Export the keys that match the given predicate.
     ///
+    /// The room keys are loaded from the store in batches, see
+    /// [`Store::export_room_keys_stream`], but all the matching keys are
+    /// returned at once.
+    ///
     /// # Arguments
     ///
     /// * `predicate` - A closure that will be called for every known
@@ -1520,21 +1676,42 @@ impl Store {
         &self,
         predicate: impl FnMut(&InboundGroupSession) -> bool,
     ) -> Result<Vec<ExportedRoomKey>> {
-        let mut exported = Vec::new();
-
-        let mut sessions = self.get_inbound_group_sessions().await?;
-        sessions.retain(predicate);
-
-        for session in sessions {
-            let export = session.export().await;
-            exported.push(export);
-        }
+        self.export_room_keys_stream(predicate).try_concat().await
+    }
 
-        Ok(exported)
+    /// Export all the room keys of the given room.
+    ///
+    /// This is a shortcut for [`Store::export_room_keys`] with a predicate
+    /// selecting the room keys of a single room, for example to move them to
+    /// another account.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use matrix_sdk_crypto::{OlmMachine, encrypt_room_key_export};
+    /// # use ruma::{device_id, user_id, room_id};
+    /// # let alice = user_id!("@alice:example.org");
+    /// # async {
+    /// # let machine = OlmMachine::new(&alice, device_id!("DEVICEID")).await;
+    /// let room_id = room_id!("!test:localhost");
+    /// let exported_keys = machine.store().export_room_keys_for_room(room_id).await.unwrap();
+    /// let encrypted_export = encrypt_room_key_export(&exported_keys, "1234", 1);
+    /// # };
+    /// ```
+    pub async fn export_room_keys_for_room(
+        &self,
+        room_id: &RoomId,
+    ) -> Result<Vec<ExportedRoomKey>> {
+        self.export_room_keys(|session| session.room_id() == room_id).await
     }
 
-    /// Export room keys matching a predicate, providing them as an async
-    /// `Stream`.
+    /// Export room keys matching a predicate, providing them in batches as an
+    /// async `Stream`.
+    ///
+    /// The room keys are loaded from the store in batches, so that the whole
+    /// set of room keys never needs to be held in memory at once. Every item
+    /// of the stream contains the matching room keys of one or more of these
+    /// batches; the stream ends after the last batch, or after an error.
     ///
     /// # Arguments
     ///
@@ -1555,24 +1732,61 @@ impl Store {
     /// let alice = user_id!("@alice:example.org");
     /// let machine = OlmMachine::new(&alice, device_id!("DEVICEID")).await;
     /// let room_id = room_id!("!test:localhost");
-    /// let mut keys = pin!(machine
+    /// let mut batches = pin!(machine
     ///     .store()
-    ///     .export_room_keys_stream(|s| s.room_id() == room_id)
-    ///     .await
-    ///     .unwrap());
-    /// while let Some(key) = keys.next().await {
-    ///     println!("{}", key.room_id);
+    ///     .export_room_keys_stream(|s| s.room_id() == room_id));
+    /// while let Some(batch) = batches.next().await {
+    ///     for key in batch.unwrap() {
+    ///         println!("{}", key.room_id);
+    ///     }
     /// }
     /// # };
     /// ```
-    pub async fn export_room_keys_stream(
+    pub fn export_room_keys_stream(
         &self,
         predicate: impl FnMut(&InboundGroupSession) -> bool,
-    ) -> Result<impl Stream<Item = ExportedRoomKey>> {
-        // TODO: if/when there is a get_inbound_group_sessions_stream, use that here.
-        let sessions = self.get_inbound_group_sessions().await?;
-        Ok(futures_util::stream::iter(sessions.into_iter().filter(predicate))
-            .then(|session| async move { session.export().await }))
+    ) -> impl Stream<Item = Result<Vec<ExportedRoomKey>>> {
+        let store = self.inner.store.clone();
+
+        // The state is the predicate and the position of the last session of the
+        // previous batch, or `None` once the stream is exhausted.
+        futures_util::stream::unfold(
+            (predicate, Some(None::<(OwnedRoomId, String)>)),
+            move |(mut predicate, after)| {
+                let store = store.clone();
+
+                async move {
+                    let mut after = after?;
+
+                    // Skip the batches without any matching session, so we don't yield
+                    // empty batches.
+                    loop {
+                        let sessions = match store
+                            .get_inbound_group_sessions_batch(after, ROOM_KEY_EXPORT_BATCH_SIZE)
+                            .await
+                        {
+                            Ok(sessions) => sessions,
+                            Err(error) => return Some((Err(error), (predicate, None))),
+                        };
+
+                        let last = sessions.last()?;
+                        after = Some((last.room_id().to_owned(), last.session_id().to_owned()));
+
+                        let mut exported = Vec::new();
+
+                        for session in sessions {
+                            if predicate(&session) {
+                                exported.push(session.export().await);
+                            }
+                        }
+
+                        if !exported.is_empty() {
+                            return Some((Ok(exported), (predicate, Some(after))));
+                        }
+                    }
+                }
+            },
+        )
     }
 
     /// Assemble a room key bundle for sharing encrypted history, as per
@@ -1703,18 +1917,28 @@ impl matrix_sdk_common::store_locks::BackingStore for LockableCryptoStore {
 mod tests {
     use std::pin::pin;
 
+    use assert_matches2::assert_matches;
     use futures_util::StreamExt;
     use insta::{_macro_support::Content, assert_json_snapshot, internals::ContentPath};
     use matrix_sdk_test::async_test;
-    use ruma::{device_id, room_id, user_id, RoomId};
+    use ruma::{
+        device_id,
+        events::{room::message::RoomMessageEventContent, AnyMessageLikeEventContent},
+        room_id,
+        serde::Raw,
+        user_id, MilliSecondsSinceUnixEpoch, RoomId,
+    };
+    use serde_json::json;
     use vodozemac::megolm::SessionKey;
 
     use crate::{
+        decrypt_room_key_export, encrypt_room_key_export,
         machine::test_helpers::get_machine_pair,
         olm::{InboundGroupSession, SenderData},
         store::types::DehydratedDeviceKey,
-        types::EventEncryptionAlgorithm,
-        OlmMachine,
+        types::{events::room::encrypted::EncryptedEvent, EventEncryptionAlgorithm},
+        utilities::json_convert,
+        DecryptionSettings, OlmMachine, RoomEventDecryptionResult, TrustRequirement,
     };
 
     #[async_test]
@@ -1779,12 +2003,12 @@ mod tests {
         alice.create_outbound_group_session_with_defaults_test_helper(room2_id).await.unwrap();
 
         // When I export the keys as a stream
-        let mut keys = pin!(alice.store().export_room_keys_stream(|_| true).await.unwrap());
+        let mut batches = pin!(alice.store().export_room_keys_stream(|_| true));
 
         // And collect them
         let mut collected = vec![];
-        while let Some(key) = keys.next().await {
-            collected.push(key);
+        while let Some(batch) = batches.next().await {
+            collected.extend(batch.unwrap());
         }
 
         // Then all the keys were provided
@@ -1807,13 +2031,12 @@ mod tests {
         alice.create_outbound_group_session_with_defaults_test_helper(room2_id).await.unwrap();
 
         // When I export the keys as a stream
-        let mut keys =
-            pin!(alice.store().export_room_keys_stream(|s| s.room_id() == room1_id).await.unwrap());
+        let mut batches = pin!(alice.store().export_room_keys_stream(|s| s.room_id() == room1_id));
 
         // And collect them
         let mut collected = vec![];
-        while let Some(key) = keys.next().await {
-            collected.push(key);
+        while let Some(batch) = batches.next().await {
+            collected.extend(batch.unwrap());
         }
 
         // Then all the keys matching our predicate were provided, and no others
@@ -1823,6 +2046,61 @@ mod tests {
         assert_eq!(collected[0].session_key.to_base64().len(), 220);
     }
 
+    #[async_test]
+    async fn test_export_room_keys_for_room_can_be_imported_into_another_account() {
+        // Given an OlmMachine which encrypted an event in two different rooms
+        let (alice, _, _) = get_machine_pair(user_id!("@a:s.co"), user_id!("@b:s.co"), false).await;
+        let room1_id = room_id!("!room1:localhost");
+        let room2_id = room_id!("!room2:localhost");
+        let event1 = encrypt_test_event(&alice, room1_id).await;
+        let event2 = encrypt_test_event(&alice, room2_id).await;
+
+        // When I export the keys of the first room to an encrypted key file
+        let keys = alice.store().export_room_keys_for_room(room1_id).await.unwrap();
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].room_id, room1_id);
+        let export = encrypt_room_key_export(&keys, "passphrase", 1).unwrap();
+
+        // And import them into a fresh OlmMachine
+        let other = OlmMachine::new(user_id!("@c:s.co"), device_id!("OTHERDEVICE")).await;
+        let keys = decrypt_room_key_export(export.as_bytes(), "passphrase").unwrap();
+        let result = other.store().import_exported_room_keys(keys, |_, _| {}).await.unwrap();
+        assert_eq!(result.imported_count, 1);
+
+        // Then the event of the first room can be decrypted, but not the one of the
+        // second room
+        let decryption_settings = DecryptionSettings {
+            sender_device_trust_requirement: TrustRequirement::Untrusted,
+            to_device_trust_requirements: Default::default(),
+            unwedging_policy: Default::default(),
+        };
+        let result =
+            other.try_decrypt_room_event(&event1, room1_id, &decryption_settings).await.unwrap();
+        assert_matches!(result, RoomEventDecryptionResult::Decrypted(_));
+        let result =
+            other.try_decrypt_room_event(&event2, room2_id, &decryption_settings).await.unwrap();
+        assert_matches!(result, RoomEventDecryptionResult::UnableToDecrypt(_));
+    }
+
+    async fn encrypt_test_event(machine: &OlmMachine, room_id: &RoomId) -> Raw<EncryptedEvent> {
+        machine.create_outbound_group_session_with_defaults_test_helper(room_id).await.unwrap();
+
+        let content = RoomMessageEventContent::text_plain("It is a secret to everybody");
+        let encrypted_content = machine
+            .encrypt_room_event(room_id, AnyMessageLikeEventContent::RoomMessage(content))
+            .await
+            .unwrap();
+
+        json_convert(&json!({
+            "event_id": "$xxxxx:example.org",
+            "origin_server_ts": MilliSecondsSinceUnixEpoch::now(),
+            "sender": machine.user_id(),
+            "type": "m.room.encrypted",
+            "content": encrypted_content,
+        }))
+        .unwrap()
+    }
+
     #[async_test]
     async fn test_export_secrets_bundle() {
         let user_id = user_id!("@alice:example.com");
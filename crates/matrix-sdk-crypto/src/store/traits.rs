@@ -17,7 +17,8 @@ use std::{collections::HashMap, fmt, sync::Arc};
 use async_trait::async_trait;
 use matrix_sdk_common::AsyncTraitDeps;
 use ruma::{
-    events::secret::request::SecretName, DeviceId, OwnedDeviceId, RoomId, TransactionId, UserId,
+    events::secret::request::SecretName, DeviceId, OwnedDeviceId, OwnedRoomId, RoomId,
+    TransactionId, UserId,
 };
 use vodozemac::Curve25519PublicKey;
 
@@ -162,6 +163,36 @@ pub trait CryptoStore: AsyncTraitDeps {
         limit: usize,
     ) -> Result<Vec<InboundGroupSession>, Self::Error>;
 
+    /// Get a batch of inbound group sessions, of all the rooms.
+    ///
+    /// Sessions are not necessarily returned in any specific order, but the
+    /// returned batches are consistent: if this function is called repeatedly
+    /// with `after` set to the room ID and session ID of the last result from
+    /// the previous call, until an empty result is returned, then eventually
+    /// all the sessions are returned. (New sessions that are added in the
+    /// course of iteration may or may not be returned.)
+    ///
+    /// # Arguments
+    ///
+    /// * `after` - return the sessions after the session with this room ID and
+    ///   session ID, or start at the earliest if this is None.
+    ///
+    /// * `limit` - return a maximum of this many sessions.
+    async fn get_inbound_group_sessions_batch(
+        &self,
+        after: Option<(OwnedRoomId, String)>,
+        limit: usize,
+    ) -> Result<Vec<InboundGroupSession>, Self::Error>;
+
+    /// Delete the inbound group sessions with the supplied room IDs and session
+    /// IDs.
+    ///
+    /// Sessions which aren't in the store are ignored.
+    async fn delete_inbound_group_sessions(
+        &self,
+        room_and_session_ids: &[(&RoomId, &str)],
+    ) -> Result<(), Self::Error>;
+
     /// Return a batch of ['InboundGroupSession'] ("room keys") that have not
     /// yet been backed up in the supplied backup version.
     ///
@@ -292,6 +323,9 @@ pub trait CryptoStore: AsyncTraitDeps {
     /// Get all outgoing secret requests that we have in the store.
     async fn get_unsent_secret_requests(&self) -> Result<Vec<GossipRequest>, Self::Error>;
 
+    /// Get all the outgoing secret requests that have already been sent out.
+    async fn get_sent_secret_requests(&self) -> Result<Vec<GossipRequest>, Self::Error>;
+
     /// Delete an outgoing key request that we created that matches the given
     /// request id.
     ///
@@ -452,6 +486,21 @@ impl<T: CryptoStore> CryptoStore for EraseCryptoStoreError<T> {
             .map_err(Into::into)
     }
 
+    async fn get_inbound_group_sessions_batch(
+        &self,
+        after: Option<(OwnedRoomId, String)>,
+        limit: usize,
+    ) -> Result<Vec<InboundGroupSession>> {
+        self.0.get_inbound_group_sessions_batch(after, limit).await.map_err(Into::into)
+    }
+
+    async fn delete_inbound_group_sessions(
+        &self,
+        room_and_session_ids: &[(&RoomId, &str)],
+    ) -> Result<()> {
+        self.0.delete_inbound_group_sessions(room_and_session_ids).await.map_err(Into::into)
+    }
+
     async fn inbound_group_session_counts(
         &self,
         backup_version: Option<&str>,
@@ -553,6 +602,10 @@ impl<T: CryptoStore> CryptoStore for EraseCryptoStoreError<T> {
         self.0.get_unsent_secret_requests().await.map_err(Into::into)
     }
 
+    async fn get_sent_secret_requests(&self) -> Result<Vec<GossipRequest>> {
+        self.0.get_sent_secret_requests().await.map_err(Into::into)
+    }
+
     async fn delete_outgoing_secret_requests(&self, request_id: &TransactionId) -> Result<()> {
         self.0.delete_outgoing_secret_requests(request_id).await.map_err(Into::into)
     }
@@ -22,22 +22,23 @@ use std::{
     time::Duration,
 };
 
-use ruma::{OwnedDeviceId, OwnedRoomId, OwnedUserId};
+use ruma::{OwnedDeviceId, OwnedRoomId, OwnedUserId, SecondsSinceUnixEpoch};
 use serde::{Deserialize, Serialize};
-use vodozemac::{base64_encode, Curve25519PublicKey};
-use zeroize::{Zeroize, ZeroizeOnDrop};
+use vodozemac::{base64_decode, base64_encode, Curve25519PublicKey};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
 use super::{DehydrationError, GossipRequest};
 use crate::{
     olm::{
         InboundGroupSession, OlmMessageHash, OutboundGroupSession, PrivateCrossSigningIdentity,
-        SenderData,
+        SenderData, SenderDataType,
     },
     types::{
         events::{room_key_bundle::RoomKeyBundleContent, room_key_withheld::RoomKeyWithheldEvent},
         EventEncryptionAlgorithm,
     },
-    Account, Device, DeviceData, GossippedSecret, Session, UserIdentity, UserIdentityData,
+    Account, CollectStrategy, Device, DeviceData, GossippedSecret, Session, UserIdentity,
+    UserIdentityData,
 };
 
 /// Aggregated changes to be saved in the database.
@@ -209,6 +210,8 @@ pub struct DeviceUpdates {
     pub new: BTreeMap<OwnedUserId, BTreeMap<OwnedDeviceId, Device>>,
     /// The list of changed devices.
     pub changed: BTreeMap<OwnedUserId, BTreeMap<OwnedDeviceId, Device>>,
+    /// The list of devices which have been deleted by their owner.
+    pub deleted: BTreeMap<OwnedUserId, BTreeMap<OwnedDeviceId, Device>>,
 }
 
 /// Updates about [`UserIdentity`]s which got received over the `/keys/query`
@@ -314,6 +317,14 @@ impl DehydratedDeviceKey {
         Self { inner }
     }
 
+    /// Try to create a [`DehydratedDeviceKey`] from a base64 export.
+    ///
+    /// Fail if the decoded key length is not 32.
+    pub fn from_base64(key: &str) -> Result<Self, DehydrationError> {
+        let decoded = Zeroizing::new(base64_decode(key)?);
+        Self::from_slice(&decoded)
+    }
+
     /// Export the [`DehydratedDeviceKey`] as a base64 encoded string.
     pub fn to_base64(&self) -> String {
         base64_encode(self.inner.as_slice())
@@ -362,6 +373,54 @@ pub struct RoomKeyCounts {
     pub backed_up: usize,
 }
 
+/// Criteria selecting inbound group sessions ("room keys"), used by
+/// [`OlmMachine::purge_inbound_group_sessions`].
+///
+/// A session is selected if it matches all the criteria which are set; the
+/// default filter selects all the sessions.
+///
+/// [`OlmMachine::purge_inbound_group_sessions`]: crate::OlmMachine::purge_inbound_group_sessions
+#[derive(Debug, Clone, Default)]
+pub struct InboundGroupSessionFilter {
+    /// Only select the sessions of this room.
+    pub room_id: Option<OwnedRoomId>,
+
+    /// Only select the sessions which were imported, for example from a key
+    /// export or the key backup, if `true`, or the sessions which were received
+    /// directly from their creator, if `false`.
+    pub imported: Option<bool>,
+
+    /// Only select the sessions which were last used to decrypt an event
+    /// before this time.
+    ///
+    /// The time of last use is only recorded with a granularity of a day, and
+    /// sessions for which it is unknown, for example because they were never
+    /// used, are never selected.
+    pub last_used_before: Option<SecondsSinceUnixEpoch>,
+}
+
+impl InboundGroupSessionFilter {
+    /// Does the given session match this filter?
+    pub fn matches(&self, session: &InboundGroupSession) -> bool {
+        if self.room_id.as_deref().is_some_and(|room_id| room_id != session.room_id()) {
+            return false;
+        }
+
+        if self.imported.is_some_and(|imported| imported != session.has_been_imported()) {
+            return false;
+        }
+
+        if let Some(last_used_before) = self.last_used_before {
+            match session.last_used() {
+                Some(last_used) if last_used < last_used_before => {}
+                _ => return false,
+            }
+        }
+
+        true
+    }
+}
+
 /// Stored versions of the backup keys.
 #[derive(Default, Clone, Debug)]
 pub struct BackupKeys {
@@ -422,6 +481,14 @@ pub struct RoomSettings {
     /// The maximum number of messages an encryption session should be used for,
     /// before it is rotated.
     pub session_rotation_period_messages: Option<usize>,
+
+    /// The strategy used to collect the devices that should receive the room
+    /// keys of this room.
+    ///
+    /// If set, this overrides the client-wide strategy, for instance to only
+    /// share the room keys with cross-signed devices in sensitive rooms.
+    #[serde(default)]
+    pub sharing_strategy: Option<CollectStrategy>,
 }
 
 impl Default for RoomSettings {
@@ -431,6 +498,7 @@ impl Default for RoomSettings {
             only_allow_trusted_devices: false,
             session_rotation_period: None,
             session_rotation_period_messages: None,
+            sharing_strategy: None,
         }
     }
 }
@@ -465,6 +533,40 @@ impl From<&InboundGroupSession> for RoomKeyInfo {
     }
 }
 
+/// Information on a room key whose [`SenderData`] got upgraded, for instance
+/// because the device or the cross-signing keys of the sender were fetched
+/// after the room key was received.
+///
+/// The events decrypted with this room key need to have their encryption info
+/// refreshed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SenderDataUpgrade {
+    /// The room where the key is used.
+    pub room_id: OwnedRoomId,
+
+    /// The ID of the session that the key is for.
+    pub session_id: String,
+
+    /// The type of the sender data before the upgrade.
+    pub old_sender_data_type: SenderDataType,
+
+    /// The type of the sender data after the upgrade.
+    pub new_sender_data_type: SenderDataType,
+}
+
+impl SenderDataUpgrade {
+    /// Create a new [`SenderDataUpgrade`] for the given session, whose sender
+    /// data used to be `old_sender_data`.
+    pub(crate) fn new(session: &InboundGroupSession, old_sender_data: &SenderData) -> Self {
+        Self {
+            room_id: session.room_id().to_owned(),
+            session_id: session.session_id().to_owned(),
+            old_sender_data_type: old_sender_data.to_type(),
+            new_sender_data_type: session.sender_data.to_type(),
+        }
+    }
+}
+
 /// Information on a room key that has been withheld
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct RoomKeyWithheldInfo {
@@ -180,9 +180,9 @@ impl UtdCause {
                 VerificationLevel::VerificationViolation,
             ) => UtdCause::VerificationViolation,
 
-            UnableToDecryptReason::SenderIdentityNotTrusted(VerificationLevel::UnsignedDevice) => {
-                UtdCause::UnsignedDevice
-            }
+            UnableToDecryptReason::SenderIdentityNotTrusted(
+                VerificationLevel::UnsignedDevice | VerificationLevel::PinViolation,
+            ) => UtdCause::UnsignedDevice,
 
             UnableToDecryptReason::SenderIdentityNotTrusted(VerificationLevel::None(_)) => {
                 UtdCause::UnknownDevice
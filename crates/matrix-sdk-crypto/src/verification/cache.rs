@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{collections::BTreeMap, sync::Arc};
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
 
 use as_variant::as_variant;
 use matrix_sdk_common::locks::RwLock as StdRwLock;
@@ -21,7 +21,9 @@ use ruma::{DeviceId, OwnedTransactionId, OwnedUserId, TransactionId, UserId};
 use tracing::debug;
 use tracing::{trace, warn};
 
-use super::{event_enums::OutgoingContent, FlowId, Sas, Verification};
+use super::{
+    event_enums::OutgoingContent, requests::VERIFICATION_TIMEOUT, FlowId, Sas, Verification,
+};
 use crate::types::requests::{
     OutgoingRequest, OutgoingVerificationRequest, RoomMessageRequest, ToDeviceRequest,
 };
@@ -54,6 +56,9 @@ struct VerificationCacheInner {
     verification: StdRwLock<BTreeMap<OwnedUserId, BTreeMap<String, Verification>>>,
     outgoing_requests: StdRwLock<BTreeMap<OwnedTransactionId, OutgoingRequest>>,
     flow_ids_waiting_for_response: StdRwLock<BTreeMap<OwnedTransactionId, (OwnedUserId, FlowId)>>,
+    /// The configured timeout of the verification requests, if it differs from
+    /// the default one.
+    request_timeout: StdRwLock<Option<Duration>>,
 }
 
 #[derive(Debug)]
@@ -67,6 +72,16 @@ impl VerificationCache {
         Self::default()
     }
 
+    /// Get the time after which the verification requests time out.
+    pub fn request_timeout(&self) -> Duration {
+        self.inner.request_timeout.read().unwrap_or(VERIFICATION_TIMEOUT)
+    }
+
+    /// Set the time after which the verification requests time out.
+    pub fn set_request_timeout(&self, timeout: Duration) {
+        *self.inner.request_timeout.write() = Some(timeout);
+    }
+
     #[cfg(test)]
     #[allow(dead_code)]
     pub fn is_empty(&self) -> bool {
@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use matrix_sdk_common::locks::RwLock as StdRwLock;
 use ruma::{
@@ -67,6 +67,16 @@ impl VerificationMachine {
         &self.store.account.user_id
     }
 
+    /// Get the time after which the verification requests time out.
+    pub(crate) fn request_timeout(&self) -> Duration {
+        self.verifications.request_timeout()
+    }
+
+    /// Set the time after which the verification requests time out.
+    pub(crate) fn set_request_timeout(&self, timeout: Duration) {
+        self.verifications.set_request_timeout(timeout)
+    }
+
     pub(crate) fn own_device_id(&self) -> &DeviceId {
         &self.store.account.device_id
     }
@@ -381,6 +391,7 @@ impl VerificationMachine {
                     flow_id,
                     r,
                     device_data,
+                    timestamp,
                 );
 
                 self.insert_request(request);
@@ -45,7 +45,9 @@ use ruma::{
     DeviceId, EventId, OwnedDeviceId, OwnedEventId, OwnedRoomId, OwnedTransactionId, RoomId,
     UserId,
 };
-pub use sas::{AcceptSettings, AcceptedProtocols, EmojiShortAuthString, Sas, SasState};
+pub use sas::{
+    AcceptSettings, AcceptedProtocols, EmojiShortAuthString, Sas, SasState, ShortAuthString,
+};
 use tokio::sync::Mutex;
 use tracing::{debug, error, info, warn};
 
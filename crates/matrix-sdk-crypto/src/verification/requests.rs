@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{ops::Add, sync::Arc, time::Duration};
+use std::{cmp::min, sync::Arc, time::Duration};
 
 use as_variant::as_variant;
 use eyeball::{ObservableWriteGuard, SharedObservable, WeakObservable};
@@ -33,7 +33,6 @@ use ruma::{
         room::message::KeyVerificationRequestEventContent,
         AnyMessageLikeEventContent, AnyToDeviceEventContent,
     },
-    time::Instant,
     to_device::DeviceIdOrAllDevices,
     DeviceId, MilliSecondsSinceUnixEpoch, OwnedDeviceId, OwnedUserId, RoomId, TransactionId,
     UserId,
@@ -64,7 +63,9 @@ const SUPPORTED_METHODS: &[VerificationMethod] = &[
     VerificationMethod::ReciprocateV1,
 ];
 
-const VERIFICATION_TIMEOUT: Duration = Duration::from_secs(60 * 10);
+/// The default time after which a verification request times out, as defined
+/// in the spec.
+pub(super) const VERIFICATION_TIMEOUT: Duration = Duration::from_secs(60 * 10);
 
 /// An Enum describing the state the verification request is in.
 #[derive(Debug, Clone)]
@@ -155,7 +156,7 @@ pub struct VerificationRequest {
     flow_id: Arc<FlowId>,
     other_user_id: OwnedUserId,
     inner: SharedObservable<InnerRequest>,
-    creation_time: Arc<Instant>,
+    creation_time: MilliSecondsSinceUnixEpoch,
     we_started: bool,
     recipient_devices: Arc<Vec<OwnedDeviceId>>,
 }
@@ -217,7 +218,7 @@ impl VerificationRequest {
             flow_id: flow_id.into(),
             inner,
             other_user_id: other_user.into(),
-            creation_time: Instant::now().into(),
+            creation_time: MilliSecondsSinceUnixEpoch::now(),
             we_started: true,
             recipient_devices: recipient_devices.into(),
         }
@@ -326,17 +327,29 @@ impl VerificationRequest {
     }
 
     /// Has the verification flow timed out.
+    ///
+    /// The timeout defaults to 10 minutes, as defined in the spec, and can be
+    /// configured using [`OlmMachine::set_verification_request_timeout()`].
+    ///
+    /// [`OlmMachine::set_verification_request_timeout()`]: crate::OlmMachine::set_verification_request_timeout
     pub fn timed_out(&self) -> bool {
-        self.creation_time.elapsed() > VERIFICATION_TIMEOUT
+        self.elapsed() > self.verification_cache.request_timeout()
     }
 
     /// Get the time left before the verification flow will time out, without
     /// further action.
+    ///
+    /// The time is computed from the timestamp of the request, not from the
+    /// time at which this object was created, so it stays correct if the
+    /// request is received again after a restart.
     pub fn time_remaining(&self) -> Duration {
-        self.creation_time
-            .add(VERIFICATION_TIMEOUT)
-            .checked_duration_since(Instant::now())
-            .unwrap_or(Duration::from_secs(0))
+        self.verification_cache.request_timeout().saturating_sub(self.elapsed())
+    }
+
+    /// Get the time elapsed since the verification request was sent.
+    fn elapsed(&self) -> Duration {
+        let now = u64::from(MilliSecondsSinceUnixEpoch::now().get());
+        Duration::from_millis(now.saturating_sub(self.creation_time.get().into()))
     }
 
     /// Get the supported verification methods of the other side.
@@ -468,6 +481,10 @@ impl VerificationRequest {
         Ok(Some(qr_verification))
     }
 
+    /// Create a verification request from a request sent by the other side.
+    ///
+    /// The `timestamp` is the time at which the request was sent, it's used to
+    /// time the request out.
     pub(crate) fn from_request(
         cache: VerificationCache,
         store: VerificationStore,
@@ -475,6 +492,7 @@ impl VerificationRequest {
         flow_id: FlowId,
         content: &RequestContent<'_>,
         device_data: DeviceData,
+        timestamp: MilliSecondsSinceUnixEpoch,
     ) -> Self {
         let account = store.account.clone();
 
@@ -494,7 +512,8 @@ impl VerificationRequest {
             other_user_id: sender.into(),
             flow_id: flow_id.into(),
             we_started: false,
-            creation_time: Instant::now().into(),
+            // Don't let requests from the future stay around longer than the timeout.
+            creation_time: min(timestamp, MilliSecondsSinceUnixEpoch::now()),
             recipient_devices: vec![].into(),
         }
     }
@@ -1633,8 +1652,11 @@ mod tests {
     use matrix_sdk_qrcode::QrVerificationData;
     use matrix_sdk_test::async_test;
     use ruma::{
-        event_id, events::key::verification::VerificationMethod, room_id,
-        to_device::DeviceIdOrAllDevices, UserId,
+        event_id,
+        events::key::verification::{cancel::CancelCode, VerificationMethod},
+        room_id,
+        to_device::DeviceIdOrAllDevices,
+        uint, MilliSecondsSinceUnixEpoch, UserId,
     };
 
     use super::VerificationRequest;
@@ -1691,6 +1713,7 @@ mod tests {
             flow_id,
             &(&content).into(),
             bob_device_data,
+            MilliSecondsSinceUnixEpoch::now(),
         );
 
         assert_matches!(alice_request.state(), VerificationRequestState::Requested { .. });
@@ -1743,6 +1766,53 @@ mod tests {
         assert_matches!(alice_request.state(), VerificationRequestState::Cancelled { .. });
     }
 
+    #[async_test]
+    async fn test_request_timing_out_from_its_timestamp() {
+        let (_alice, alice_store, _bob, bob_store) = setup_stores().await;
+
+        let bob_request = build_test_request(&bob_store, alice_id(), None);
+        let request = bob_request.request_to_device();
+        let content: OutgoingContent = request.try_into().unwrap();
+        let content = RequestContent::try_from(&content).unwrap();
+        let device_data =
+            alice_store.get_device(bob_id(), content.from_device()).await.unwrap().unwrap();
+
+        // The request was sent 9 minutes ago, for instance before a restart.
+        let timestamp =
+            MilliSecondsSinceUnixEpoch(MilliSecondsSinceUnixEpoch::now().get() - uint!(540_000));
+
+        let cache = VerificationCache::new();
+        let alice_request = VerificationRequest::from_request(
+            cache.clone(),
+            alice_store,
+            bob_id(),
+            bob_request.flow_id().clone(),
+            &content,
+            device_data,
+            timestamp,
+        );
+
+        assert!(!alice_request.timed_out());
+        assert!(alice_request.time_remaining() <= Duration::from_secs(60));
+        assert!(alice_request.time_remaining() > Duration::from_secs(50));
+        assert!(alice_request.cancel_if_timed_out().is_none());
+
+        // With a shorter timeout, the request times out and gets cancelled.
+        cache.set_request_timeout(Duration::from_secs(5 * 60));
+
+        assert!(alice_request.timed_out());
+        assert_eq!(alice_request.time_remaining(), Duration::ZERO);
+
+        let outgoing_request = alice_request
+            .cancel_if_timed_out()
+            .expect("The cancellation should be sent to the other side");
+        let content = OutgoingContent::try_from(outgoing_request).unwrap();
+        let content = CancelContent::try_from(&content).unwrap();
+
+        assert_eq!(content.cancel_code(), &CancelCode::Timeout);
+        assert_matches!(alice_request.state(), VerificationRequestState::Cancelled { .. });
+    }
+
     #[async_test]
     async fn test_requesting_until_sas() {
         let event_id = event_id!("$1234localhost");
@@ -1779,6 +1849,7 @@ mod tests {
             flow_id,
             &(&content).into(),
             bob_device_data.clone(),
+            MilliSecondsSinceUnixEpoch::now(),
         );
 
         do_accept_request(&alice_request, alice_device_data.clone(), &bob_request, None);
@@ -2118,6 +2189,7 @@ mod tests {
             outgoing_request.flow_id().clone(),
             &content,
             device_data,
+            MilliSecondsSinceUnixEpoch::now(),
         )
     }
 
@@ -404,7 +404,7 @@ pub fn get_emoji(
         we_started,
     ));
 
-    let indices = bytes.emoji_indices();
+    let indices = emoji_indices_from_bytes(bytes.as_bytes());
 
     [
         emoji_from_index(indices[0]),
@@ -451,7 +451,7 @@ pub fn get_emoji_index(
         we_started,
     ));
 
-    bytes.emoji_indices()
+    emoji_indices_from_bytes(bytes.as_bytes())
 }
 
 /// Get the decimal version of the short authentication string.
@@ -487,7 +487,37 @@ pub fn get_decimal(
         we_started,
     ));
 
-    bytes.decimals()
+    decimals_from_bytes(bytes.as_bytes())
+}
+
+/// Convert the bytes generated from the shared secret into the indices of the
+/// emojis of the short authentication string, as defined in the [spec].
+///
+/// The first 42 bits are split into seven groups of 6 bits, each group being
+/// the index of an emoji.
+///
+/// [spec]: https://spec.matrix.org/unstable/client-server-api/#sas-method-emoji
+fn emoji_indices_from_bytes(bytes: &[u8; 6]) -> [u8; 7] {
+    let [b0, b1, b2, b3, b4, b5] = *bytes;
+    let bits = u64::from_be_bytes([0, 0, b0, b1, b2, b3, b4, b5]) >> 6;
+
+    std::array::from_fn(|i| ((bits >> (6 * (6 - i))) & 0b11_1111) as u8)
+}
+
+/// Convert the bytes generated from the shared secret into the decimal version
+/// of the short authentication string, as defined in the [spec].
+///
+/// The first 39 bits are split into three groups of 13 bits, 1000 is added to
+/// each group.
+///
+/// [spec]: https://spec.matrix.org/unstable/client-server-api/#sas-method-decimal
+fn decimals_from_bytes(bytes: &[u8; 6]) -> (u16, u16, u16) {
+    let [b0, b1, b2, b3, b4, b5] = *bytes;
+    let bits = u64::from_be_bytes([0, 0, b0, b1, b2, b3, b4, b5]) >> 9;
+
+    let group = |shift: u32| ((bits >> shift) & 0x1fff) as u16 + 1000;
+
+    (group(26), group(13), group(0))
 }
 
 #[cfg(all(test, not(target_family = "wasm")))]
@@ -496,9 +526,9 @@ mod tests {
         events::key::verification::start::ToDeviceKeyVerificationStartEventContent, serde::Base64,
     };
     use serde_json::json;
-    use vodozemac::Curve25519PublicKey;
+    use vodozemac::{sas::Sas, Curve25519PublicKey};
 
-    use super::calculate_commitment;
+    use super::{calculate_commitment, decimals_from_bytes, emoji_indices_from_bytes};
     use crate::verification::event_enums::StartContent;
 
     #[test]
@@ -525,4 +555,33 @@ mod tests {
 
         assert_eq!(commitment, calculated_commitment);
     }
+
+    #[test]
+    fn short_auth_string_from_known_bytes() {
+        // The indices and the decimals are defined by the spec, they must never change
+        // for a given shared secret.
+        let bytes = [0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc];
+
+        assert_eq!(emoji_indices_from_bytes(&bytes), [4, 35, 17, 22, 30, 9, 42]);
+        assert_eq!(decimals_from_bytes(&bytes), (1582, 5441, 8245));
+
+        assert_eq!(emoji_indices_from_bytes(&[0; 6]), [0; 7]);
+        assert_eq!(decimals_from_bytes(&[0; 6]), (1000, 1000, 1000));
+
+        assert_eq!(emoji_indices_from_bytes(&[0xff; 6]), [63; 7]);
+        assert_eq!(decimals_from_bytes(&[0xff; 6]), (9191, 9191, 9191));
+    }
+
+    #[test]
+    fn short_auth_string_matches_vodozemac() {
+        let alice = Sas::new();
+        let bob = Sas::new();
+        let bob_public_key = bob.public_key();
+
+        let established = alice.diffie_hellman(bob_public_key).unwrap();
+        let bytes = established.bytes("short auth string");
+
+        assert_eq!(emoji_indices_from_bytes(bytes.as_bytes()), bytes.emoji_indices());
+        assert_eq!(decimals_from_bytes(bytes.as_bytes()), bytes.decimals());
+    }
 }
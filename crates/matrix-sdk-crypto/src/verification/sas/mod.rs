@@ -113,6 +113,25 @@ pub struct EmojiShortAuthString {
     pub emojis: [Emoji; 7],
 }
 
+/// The short auth string of a SAS verification, in a form which doesn't depend
+/// on the English descriptions of the emojis.
+///
+/// The indices and decimals are derived from the shared secret as defined in
+/// the [spec], they are stable across versions of the SDK and can be used to
+/// look up localized emoji descriptions.
+///
+/// [spec]: https://spec.matrix.org/unstable/client-server-api/#short-authentication-string-sas-verification
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShortAuthString {
+    /// The indices of the seven emojis, in the range from 0 to 63 inclusive,
+    /// see [`Sas::emoji_index()`].
+    pub emoji_indices: [u8; 7],
+
+    /// The three 4-digit numbers of the decimal version of the short auth
+    /// string, see [`Sas::decimals()`].
+    pub decimals: (u16, u16, u16),
+}
+
 /// An Enum describing the state the SAS verification is in.
 #[derive(Debug, Clone)]
 pub enum SasState {
@@ -639,6 +658,10 @@ impl Sas {
     /// seven u8 numbers in the range from 0 to 63 inclusive which can be
     /// converted to an emoji using the
     /// [relevant spec entry](https://spec.matrix.org/unstable/client-server-api/#sas-method-emoji).
+    ///
+    /// The indices are defined by the spec, so they are stable across versions
+    /// of the SDK, unlike the English descriptions returned by
+    /// [`Sas::emoji()`].
     pub fn emoji_index(&self) -> Option<[u8; 7]> {
         self.inner.read().emoji_index()
     }
@@ -652,6 +675,16 @@ impl Sas {
         self.inner.read().decimals()
     }
 
+    /// Get both the emoji indices and the decimal version of the short auth
+    /// string.
+    ///
+    /// Returns None if we can't yet present the short auth string.
+    pub fn short_auth_string(&self) -> Option<ShortAuthString> {
+        let inner = self.inner.read();
+
+        Some(ShortAuthString { emoji_indices: inner.emoji_index()?, decimals: inner.decimals()? })
+    }
+
     /// Listen for changes in the SAS verification process.
     ///
     /// The changes are presented as a stream of [`SasState`] values.
@@ -985,6 +1018,11 @@ mod tests {
         assert_eq!(alice.emoji().unwrap(), bob.emoji().unwrap());
         assert_eq!(alice.decimals().unwrap(), bob.decimals().unwrap());
 
+        let short_auth_string = alice.short_auth_string().unwrap();
+        assert_eq!(Some(short_auth_string), bob.short_auth_string());
+        assert_eq!(Some(short_auth_string.emoji_indices), alice.emoji_index());
+        assert_eq!(Some(short_auth_string.decimals), alice.decimals());
+
         let mut requests = alice.confirm().await.unwrap().0;
         assert_matches!(alice.state(), SasState::Confirmed);
         assert!(requests.len() == 1);
@@ -42,7 +42,7 @@ use matrix_sdk_crypto::{
 use matrix_sdk_store_encryption::StoreCipher;
 use ruma::{
     events::secret::request::SecretName, DeviceId, MilliSecondsSinceUnixEpoch, OwnedDeviceId,
-    RoomId, TransactionId, UserId,
+    OwnedRoomId, RoomId, TransactionId, UserId,
 };
 use sha2::Sha256;
 use tokio::sync::Mutex;
@@ -1031,6 +1031,76 @@ impl_crypto_store! {
         Ok(result)
     }
 
+    async fn get_inbound_group_sessions_batch(
+        &self,
+        after: Option<(OwnedRoomId, String)>,
+        limit: usize,
+    ) -> Result<Vec<InboundGroupSession>> {
+        // The empty string is before all keys in Indexed DB - first batch starts there.
+        let after_key: JsValue = after
+            .map(|(room_id, session_id)| self.serializer.encode_key(keys::INBOUND_GROUP_SESSIONS_V3, (room_id, session_id)))
+            .unwrap_or("".into());
+        let range = IdbKeyRange::lower_bound_with_open(&after_key, true).expect("Key was not valid!");
+
+        let tx = self
+            .inner
+            .transaction_on_one_with_mode(
+                keys::INBOUND_GROUP_SESSIONS_V3,
+                IdbTransactionMode::Readonly,
+            )?;
+
+        let store = tx.object_store(keys::INBOUND_GROUP_SESSIONS_V3)?;
+
+        // See `inbound_group_sessions_for_backup` for why we use a cursor here.
+        let Some(cursor) = store.open_cursor_with_range(&range)?.await? else {
+            return Ok(vec![]);
+        };
+
+        let mut serialized_sessions = Vec::with_capacity(limit);
+        for _ in 0..limit {
+            serialized_sessions.push(cursor.value());
+            if !cursor.continue_cursor()?.await? {
+                break;
+            }
+        }
+
+        tx.await.into_result()?;
+
+        // Deserialize and decrypt after the transaction is complete.
+        let result = serialized_sessions.into_iter()
+            .filter_map(|v| match self.deserialize_inbound_group_session(v) {
+                Ok(session) => Some(session),
+                Err(e) => {
+                    warn!("Failed to deserialize inbound group session: {e}");
+                    None
+                }
+            })
+            .collect::<Vec<InboundGroupSession>>();
+
+        Ok(result)
+    }
+
+    async fn delete_inbound_group_sessions(
+        &self,
+        room_and_session_ids: &[(&RoomId, &str)],
+    ) -> Result<()> {
+        let tx = self
+            .inner
+            .transaction_on_one_with_mode(
+                keys::INBOUND_GROUP_SESSIONS_V3,
+                IdbTransactionMode::Readwrite,
+            )?;
+
+        let object_store = tx.object_store(keys::INBOUND_GROUP_SESSIONS_V3)?;
+
+        for (room_id, session_id) in room_and_session_ids {
+            let key = self.serializer.encode_key(keys::INBOUND_GROUP_SESSIONS_V3, (room_id, session_id));
+            object_store.delete(&key)?;
+        }
+
+        Ok(tx.await.into_result()?)
+    }
+
     async fn inbound_group_session_counts(&self, _backup_version: Option<&str>) -> Result<RoomKeyCounts> {
         let tx = self
             .inner
@@ -1303,6 +1373,24 @@ impl_crypto_store! {
         Ok(results)
     }
 
+    async fn get_sent_secret_requests(&self) -> Result<Vec<GossipRequest>> {
+        let results = self
+            .inner
+            .transaction_on_one_with_mode(
+                keys::GOSSIP_REQUESTS,
+                IdbTransactionMode::Readonly,
+            )?
+            .object_store(keys::GOSSIP_REQUESTS)?
+            .get_all()?
+            .await?
+            .iter()
+            .filter_map(|val| self.deserialize_gossip_request(val).ok())
+            .filter(|request| request.sent_out)
+            .collect();
+
+        Ok(results)
+    }
+
     async fn delete_outgoing_secret_requests(&self, request_id: &TransactionId) -> Result<()> {
         let jskey = self.serializer.encode_key(keys::GOSSIP_REQUESTS, request_id);
         let tx = self.inner.transaction_on_one_with_mode(keys::GOSSIP_REQUESTS, IdbTransactionMode::Readwrite)?;
@@ -45,6 +45,7 @@ use ruma::{
         GlobalAccountDataEventType, RoomAccountDataEventType, StateEventType, SyncStateEvent,
     },
     serde::Raw,
+    thirdparty::ThirdPartyIdentifier,
     CanonicalJsonObject, EventId, MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedMxcUri,
     OwnedRoomId, OwnedTransactionId, OwnedUserId, RoomId, TransactionId, UserId,
 };
@@ -434,6 +435,12 @@ impl IndexeddbStateStore {
             StateStoreDataKey::SeenKnockRequests(room_id) => {
                 self.encode_key(keys::KV, (StateStoreDataKey::SEEN_KNOCK_REQUESTS, room_id))
             }
+            StateStoreDataKey::PendingDirectUpdates => {
+                self.encode_key(keys::KV, StateStoreDataKey::PENDING_DIRECT_UPDATES)
+            }
+            StateStoreDataKey::ThirdPartyIdentifiers => {
+                self.encode_key(keys::KV, StateStoreDataKey::THIRD_PARTY_IDENTIFIERS)
+            }
         }
     }
 }
@@ -570,6 +577,14 @@ impl_state_store!({
                 .map(|f| self.deserialize_value::<BTreeMap<OwnedEventId, OwnedUserId>>(&f))
                 .transpose()?
                 .map(StateStoreDataValue::SeenKnockRequests),
+            StateStoreDataKey::PendingDirectUpdates => value
+                .map(|f| self.deserialize_value::<BTreeMap<OwnedUserId, Vec<OwnedRoomId>>>(&f))
+                .transpose()?
+                .map(StateStoreDataValue::PendingDirectUpdates),
+            StateStoreDataKey::ThirdPartyIdentifiers => value
+                .map(|f| self.deserialize_value::<Vec<ThirdPartyIdentifier>>(&f))
+                .transpose()?
+                .map(StateStoreDataValue::ThirdPartyIdentifiers),
         };
 
         Ok(value)
@@ -610,6 +625,16 @@ impl_state_store!({
                     .into_seen_knock_requests()
                     .expect("Session data is not a set of seen knock request ids"),
             ),
+            StateStoreDataKey::PendingDirectUpdates => self.serialize_value(
+                &value
+                    .into_pending_direct_updates()
+                    .expect("Session data not the pending m.direct updates"),
+            ),
+            StateStoreDataKey::ThirdPartyIdentifiers => self.serialize_value(
+                &value
+                    .into_third_party_identifiers()
+                    .expect("Session data not a list of third party identifiers"),
+            ),
         };
 
         let tx =
@@ -39,7 +39,7 @@ use matrix_sdk_crypto::{
 use matrix_sdk_store_encryption::StoreCipher;
 use ruma::{
     events::secret::request::SecretName, DeviceId, MilliSecondsSinceUnixEpoch, OwnedDeviceId,
-    RoomId, TransactionId, UserId,
+    OwnedRoomId, RoomId, TransactionId, UserId,
 };
 use rusqlite::{named_params, params_from_iter, OptionalExtension};
 use tokio::{fs, sync::Mutex};
@@ -559,6 +559,57 @@ trait SqliteObjectCryptoStoreExt: SqliteAsyncConnExt {
             .await?)
     }
 
+    async fn get_inbound_group_sessions_batch(
+        &self,
+        after_session_id: Option<Key>,
+        limit: usize,
+    ) -> Result<Vec<(Vec<u8>, bool)>> {
+        Ok(self
+            .prepare(
+                "
+                SELECT data, backed_up
+                FROM inbound_group_session
+                WHERE session_id > :after_session_id
+                ORDER BY session_id
+                LIMIT :limit
+                ",
+                move |mut stmt| {
+                    // If we are not provided with an `after_session_id`, use a key which will sort
+                    // before all real keys: the empty string.
+                    let after_session_id = after_session_id.unwrap_or(Key::Plain(Vec::new()));
+
+                    stmt.query(named_params! {
+                        ":after_session_id": after_session_id,
+                        ":limit": limit,
+                    })?
+                    .mapped(|row| Ok((row.get(0)?, row.get(1)?)))
+                    .collect()
+                },
+            )
+            .await?)
+    }
+
+    async fn delete_inbound_group_sessions(&self, session_ids: Vec<Key>) -> Result<()> {
+        if session_ids.is_empty() {
+            return Ok(());
+        }
+
+        let session_ids_len = session_ids.len();
+
+        self.chunk_large_query_over(session_ids, None, move |txn, session_ids| {
+            // Safety: placeholders is not generated using any user input except the number
+            // of session IDs, so it is safe from injection.
+            let sql_params = repeat_vars(session_ids_len);
+            let query =
+                format!("DELETE FROM inbound_group_session WHERE session_id IN ({sql_params})");
+            txn.prepare(&query)?.execute(params_from_iter(session_ids.iter()))?;
+            Ok(Vec::<()>::new())
+        })
+        .await?;
+
+        Ok(())
+    }
+
     async fn get_inbound_group_sessions_for_backup(&self, limit: usize) -> Result<Vec<Vec<u8>>> {
         Ok(self
             .prepare(
@@ -695,6 +746,14 @@ trait SqliteObjectCryptoStoreExt: SqliteAsyncConnExt {
             .await?)
     }
 
+    async fn get_sent_secret_requests(&self) -> Result<Vec<Vec<u8>>> {
+        Ok(self
+            .prepare("SELECT data FROM key_requests WHERE sent_out = TRUE", |mut stmt| {
+                stmt.query(())?.mapped(|row| row.get(0)).collect()
+            })
+            .await?)
+    }
+
     async fn delete_key_request(&self, request_id: Key) -> Result<()> {
         self.execute("DELETE FROM key_requests WHERE request_id = ?", (request_id,)).await?;
         Ok(())
@@ -1074,6 +1133,43 @@ impl CryptoStore for SqliteCryptoStore {
             .collect()
     }
 
+    async fn get_inbound_group_sessions_batch(
+        &self,
+        after: Option<(OwnedRoomId, String)>,
+        limit: usize,
+    ) -> Result<Vec<InboundGroupSession>> {
+        // The sessions are keyed by their session ID only, so the room ID isn't needed
+        // to resume the iteration.
+        let after_session_id =
+            after.map(|(_, session_id)| self.encode_key("inbound_group_session", session_id));
+
+        self.acquire()
+            .await?
+            .get_inbound_group_sessions_batch(after_session_id, limit)
+            .await?
+            .into_iter()
+            .map(|(value, backed_up)| {
+                self.deserialize_and_unpickle_inbound_group_session(value, backed_up)
+            })
+            .collect()
+    }
+
+    async fn delete_inbound_group_sessions(
+        &self,
+        room_and_session_ids: &[(&RoomId, &str)],
+    ) -> Result<()> {
+        Ok(self
+            .acquire()
+            .await?
+            .delete_inbound_group_sessions(
+                room_and_session_ids
+                    .iter()
+                    .map(|(_, s)| self.encode_key("inbound_group_session", s))
+                    .collect(),
+            )
+            .await?)
+    }
+
     async fn inbound_group_session_counts(
         &self,
         backup_version: Option<&str>,
@@ -1297,6 +1393,19 @@ impl CryptoStore for SqliteCryptoStore {
             .collect()
     }
 
+    async fn get_sent_secret_requests(&self) -> Result<Vec<GossipRequest>> {
+        self.acquire()
+            .await?
+            .get_sent_secret_requests()
+            .await?
+            .iter()
+            .map(|value| {
+                let request = self.deserialize_key_request(value, true)?;
+                Ok(request)
+            })
+            .collect()
+    }
+
     async fn delete_outgoing_secret_requests(&self, request_id: &TransactionId) -> Result<()> {
         let request_id = self.encode_key("key_requests", request_id.as_bytes());
         Ok(self.acquire().await?.delete_key_request(request_id).await?)
@@ -400,6 +400,12 @@ impl SqliteStateStore {
             StateStoreDataKey::SeenKnockRequests(room_id) => {
                 Cow::Owned(format!("{}:{room_id}", StateStoreDataKey::SEEN_KNOCK_REQUESTS))
             }
+            StateStoreDataKey::PendingDirectUpdates => {
+                Cow::Borrowed(StateStoreDataKey::PENDING_DIRECT_UPDATES)
+            }
+            StateStoreDataKey::ThirdPartyIdentifiers => {
+                Cow::Borrowed(StateStoreDataKey::THIRD_PARTY_IDENTIFIERS)
+            }
         };
 
         self.encode_key(keys::KV_BLOB, &*key_s)
@@ -1018,6 +1024,12 @@ impl StateStore for SqliteStateStore {
                     StateStoreDataKey::SeenKnockRequests(_) => {
                         StateStoreDataValue::SeenKnockRequests(self.deserialize_value(&data)?)
                     }
+                    StateStoreDataKey::PendingDirectUpdates => {
+                        StateStoreDataValue::PendingDirectUpdates(self.deserialize_value(&data)?)
+                    }
+                    StateStoreDataKey::ThirdPartyIdentifiers => {
+                        StateStoreDataValue::ThirdPartyIdentifiers(self.deserialize_value(&data)?)
+                    }
                 })
             })
             .transpose()
@@ -1055,6 +1067,16 @@ impl StateStore for SqliteStateStore {
                     .into_seen_knock_requests()
                     .expect("Session data is not a set of seen knock request ids"),
             )?,
+            StateStoreDataKey::PendingDirectUpdates => self.serialize_value(
+                &value
+                    .into_pending_direct_updates()
+                    .expect("Session data not the pending m.direct updates"),
+            )?,
+            StateStoreDataKey::ThirdPartyIdentifiers => self.serialize_value(
+                &value
+                    .into_third_party_identifiers()
+                    .expect("Session data not a list of third party identifiers"),
+            )?,
         };
 
         self.acquire()
@@ -18,11 +18,17 @@ use std::{
     time::Duration,
 };
 
-use futures_util::{StreamExt as _, pin_mut};
+use futures_util::{StreamExt as _, pin_mut, stream};
 use matrix_sdk::{
-    Client, ClientBuildError, SlidingSyncList, SlidingSyncMode, room::Room, sleep::sleep,
+    Client, ClientBuildError, SlidingSyncList, SlidingSyncMode,
+    invite_filter::InviteClassification,
+    media::{MediaFormat, MediaRequestParameters, MediaThumbnailSettings},
+    room::Room,
+    sleep::sleep,
+};
+use matrix_sdk_base::{
+    RoomState, StoreError, deserialized_responses::TimelineEvent, timeout::timeout,
 };
-use matrix_sdk_base::{RoomState, StoreError, deserialized_responses::TimelineEvent};
 use ruma::{
     EventId, OwnedEventId, OwnedRoomId, RoomId, UserId,
     api::client::sync::sync_events::v5 as http,
@@ -33,14 +39,16 @@ use ruma::{
         AnySyncMessageLikeEvent, AnySyncTimelineEvent, FullStateEventContent, StateEventType,
         TimelineEventType,
         room::{
+            MediaSource,
             join_rules::JoinRule,
             member::{MembershipState, StrippedRoomMemberEvent},
-            message::{Relation, SyncRoomMessageEvent},
+            message::{MessageType, Relation, SyncRoomMessageEvent},
         },
     },
     html::RemoveReplyFallback,
     push::Action,
     serde::Raw,
+    time::Instant,
     uint,
 };
 use thiserror::Error;
@@ -105,11 +113,23 @@ pub struct NotificationClient {
     ///
     /// Same reasoning as [`Self::notification_sync_mutex`].
     encryption_sync_mutex: AsyncMutex<()>,
+
+    /// The maximum number of `/context` requests that can run concurrently,
+    /// when fetching several notifications.
+    max_concurrent_requests: usize,
+
+    /// The maximum time spent fetching a batch of notifications, if any.
+    time_budget: Option<Duration>,
+
+    /// The maximum size of the thumbnails fetched for media notifications,
+    /// if thumbnails are enabled.
+    max_thumbnail_size: Option<u64>,
 }
 
 impl NotificationClient {
     const CONNECTION_ID: &'static str = "notifications";
     const LOCK_ID: &'static str = "notifications";
+    const DEFAULT_MAX_CONCURRENT_REQUESTS: usize = 4;
 
     /// Create a new notification client.
     pub async fn new(
@@ -124,9 +144,47 @@ impl NotificationClient {
             notification_sync_mutex: AsyncMutex::new(()),
             encryption_sync_mutex: AsyncMutex::new(()),
             process_setup,
+            max_concurrent_requests: Self::DEFAULT_MAX_CONCURRENT_REQUESTS,
+            time_budget: None,
+            max_thumbnail_size: None,
         })
     }
 
+    /// Set the maximum number of `/context` requests that can run
+    /// concurrently, when fetching several notifications with
+    /// [`Self::get_notifications`].
+    ///
+    /// Defaults to 4.
+    pub fn with_max_concurrent_requests(mut self, max_concurrent_requests: usize) -> Self {
+        self.max_concurrent_requests = max_concurrent_requests.max(1);
+        self
+    }
+
+    /// Set the maximum time spent fetching a batch of notifications with
+    /// [`Self::get_notifications`].
+    ///
+    /// This is useful when the operating system only gives a limited amount
+    /// of time to process notifications in the background. The notifications
+    /// that couldn't be fetched in time fail with
+    /// [`Error::TimeBudgetExhausted`].
+    ///
+    /// By default, there's no time budget.
+    pub fn with_time_budget(mut self, time_budget: Duration) -> Self {
+        self.time_budget = Some(time_budget);
+        self
+    }
+
+    /// Fetch a thumbnail for the notifications of image messages, in
+    /// [`NotificationItem::thumbnail`].
+    ///
+    /// Thumbnails that are larger than `max_size`, in bytes, are ignored.
+    ///
+    /// By default, no thumbnail is fetched.
+    pub fn with_max_thumbnail_size(mut self, max_size: u64) -> Self {
+        self.max_thumbnail_size = Some(max_size);
+        self
+    }
+
     /// Fetches a room by its ID using the in-memory state store backed client.
     /// Useful to retrieve room information after running the limited
     /// notification client sliding sync loop.
@@ -170,30 +228,107 @@ impl NotificationClient {
     /// something failed when trying to fetch that particular notification
     /// (decryption, fetching push actions, etc.); in that case, a dummy
     /// notification may be displayed instead.
+    ///
+    /// The `/context` queries run concurrently, up to the limit set with
+    /// [`Self::with_max_concurrent_requests`], and the whole operation is
+    /// bounded by the time budget set with [`Self::with_time_budget`], if any.
     pub async fn get_notifications(
         &self,
         requests: &[NotificationItemsRequest],
     ) -> Result<BatchNotificationFetchingResult, Error> {
-        let mut notifications = self.get_notifications_with_sliding_sync(requests).await?;
-
-        for request in requests {
-            for event_id in &request.event_ids {
-                match notifications.get_mut(event_id) {
-                    // If the notification for a given event wasn't found with sliding sync, try
-                    // with a /context for each event.
-                    Some(Ok(NotificationStatus::EventNotFound)) | None => {
-                        notifications.insert(
-                            event_id.to_owned(),
-                            self.get_notification_with_context(&request.room_id, event_id).await,
-                        );
-                    }
+        let deadline = self.time_budget.map(|time_budget| Instant::now() + time_budget);
 
-                    _ => {}
+        let mut notifications =
+            match with_deadline(deadline, self.get_notifications_with_sliding_sync(requests)).await
+            {
+                Ok(notifications) => notifications,
+                Err(Error::TimeBudgetExhausted) => {
+                    warn!("the sliding sync for notifications took too long");
+                    BatchNotificationFetchingResult::new()
                 }
+                Err(err) => return Err(err),
+            };
+
+        // If the notification for a given event wasn't found with sliding sync, try
+        // with a /context for each event.
+        let missing = requests
+            .iter()
+            .flat_map(|request| {
+                request.event_ids.iter().map(|event_id| (&request.room_id, event_id))
+            })
+            .filter(|(_, event_id)| {
+                matches!(
+                    notifications.get(*event_id),
+                    Some(Ok(NotificationStatus::EventNotFound)) | None
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let context_results = stream::iter(missing.into_iter().map(|(room_id, event_id)| async {
+            let result =
+                with_deadline(deadline, self.get_notification_with_context(room_id, event_id))
+                    .await;
+            (event_id.to_owned(), result)
+        }))
+        .buffer_unordered(self.max_concurrent_requests)
+        .collect::<Vec<_>>()
+        .await;
+
+        notifications.extend(context_results);
+
+        Ok(notifications)
+    }
+
+    /// Fetches the content of several notifications, given as pairs of room
+    /// id and event id, across any number of rooms.
+    ///
+    /// The requests are grouped per room, and resolved with
+    /// [`Self::get_notifications`]. The results are returned in the same order
+    /// as the requests, each with its own failure reason, if any; a request
+    /// that's repeated is only returned once.
+    pub async fn get_notifications_in_order(
+        &self,
+        notifications: Vec<(OwnedRoomId, OwnedEventId)>,
+    ) -> Result<Vec<(OwnedRoomId, OwnedEventId, Result<NotificationStatus, Error>)>, Error> {
+        let mut requests: Vec<NotificationItemsRequest> = Vec::new();
+        let mut ordered = Vec::with_capacity(notifications.len());
+
+        for (room_id, event_id) in notifications {
+            if ordered.contains(&(room_id.clone(), event_id.clone())) {
+                continue;
             }
+
+            match requests.iter_mut().find(|request| request.room_id == room_id) {
+                Some(request) => request.event_ids.push(event_id.clone()),
+                None => requests.push(NotificationItemsRequest {
+                    room_id: room_id.clone(),
+                    event_ids: vec![event_id.clone()],
+                }),
+            }
+
+            ordered.push((room_id, event_id));
         }
 
-        Ok(notifications)
+        let mut results = self.get_notifications(&requests).await?;
+
+        Ok(ordered
+            .into_iter()
+            .map(|(room_id, event_id)| {
+                let result =
+                    results.remove(&event_id).unwrap_or(Ok(NotificationStatus::EventNotFound));
+                (room_id, event_id, result)
+            })
+            .collect())
+    }
+
+    /// Whether the notification is for an invite that's filtered out by the
+    /// parent client's invite filter policy.
+    ///
+    /// The classification is made by the parent client, since it knows about
+    /// all the rooms the user is in.
+    async fn is_invite_filtered(&self, room: &Room, event: &NotificationEvent) -> bool {
+        matches!(event, NotificationEvent::Invite(_))
+            && self.parent_client.classify_invite(room).await == InviteClassification::Filtered
     }
 
     /// Run an encryption sync loop, in case an event is still encrypted.
@@ -205,11 +340,15 @@ impl NotificationClient {
     ///
     /// Otherwise, if the event was not encrypted, or couldn't be decrypted
     /// (without causing a fatal error), will return `Ok(None)`.
+    ///
+    /// The encryption sync is only run once per [`DecryptionPass`]: the
+    /// following events are decrypted with the keys fetched by the first one.
     #[instrument(skip_all)]
     async fn retry_decryption(
         &self,
         room: &Room,
         raw_event: &Raw<AnySyncTimelineEvent>,
+        pass: &mut DecryptionPass,
     ) -> Result<Option<TimelineEvent>, Error> {
         let event: AnySyncTimelineEvent =
             raw_event.deserialize().map_err(|_| Error::InvalidRumaEvent)?;
@@ -218,6 +357,27 @@ impl NotificationClient {
             return Ok(None);
         }
 
+        if pass.encryption_synced {
+            // The keys have already been fetched for a previous event of the same batch;
+            // running another encryption sync is unlikely to help.
+            let push_ctx = room.push_context().await?;
+            return match room.decrypt_event(raw_event.cast_ref_unchecked(), push_ctx.as_ref()).await
+            {
+                Ok(new_event) => match new_event.kind {
+                    matrix_sdk::deserialized_responses::TimelineEventKind::UnableToDecrypt {
+                        ..
+                    } => Ok(None),
+                    _ => Ok(Some(new_event)),
+                },
+                Err(err) => {
+                    trace!("Failed to decrypt the event: {err}");
+                    Ok(None)
+                }
+            };
+        }
+
+        pass.encryption_synced = true;
+
         // Serialize calls to this function.
         let _guard = self.encryption_sync_mutex.lock().await;
 
@@ -618,6 +778,7 @@ impl NotificationClient {
         let raw_events = self.try_sliding_sync(requests).await?;
 
         let mut batch_result = BatchNotificationFetchingResult::new();
+        let mut decryption_pass = DecryptionPass::default();
 
         for (event_id, (room_id, raw_event)) in raw_events.into_iter() {
             // At this point it should have been added by the sync, if it's not, give up.
@@ -632,7 +793,7 @@ impl NotificationClient {
             let (raw_event, push_actions) = match &raw_event {
                 RawNotificationEvent::Timeline(timeline_event) => {
                     // Timeline events may be encrypted, so make sure they get decrypted first.
-                    match self.retry_decryption(&room, timeline_event).await {
+                    match self.retry_decryption(&room, timeline_event, &mut decryption_pass).await {
                         Ok(Some(timeline_event)) => {
                             let push_actions = timeline_event.push_actions().map(ToOwned::to_owned);
                             (
@@ -685,22 +846,29 @@ impl NotificationClient {
                 continue;
             }
 
-            let status =
-                match NotificationItem::new(&room, raw_event, push_actions.as_deref(), Vec::new())
-                    .await
-                    .map(|event| NotificationStatus::Event(Box::new(event)))
-                {
-                    Ok(status) => status,
-                    Err(err) => {
-                        // Could not build the notification item, return an error.
-                        batch_result.insert(event_id, Err(err));
-                        continue;
-                    }
-                };
+            let status = match NotificationItem::new(
+                &room,
+                raw_event,
+                push_actions.as_deref(),
+                Vec::new(),
+                self.max_thumbnail_size,
+            )
+            .await
+            .map(|event| NotificationStatus::Event(Box::new(event)))
+            {
+                Ok(status) => status,
+                Err(err) => {
+                    // Could not build the notification item, return an error.
+                    batch_result.insert(event_id, Err(err));
+                    continue;
+                }
+            };
 
             match status {
                 NotificationStatus::Event(event) => {
-                    if self.client.is_user_ignored(event.event.sender()).await {
+                    if self.client.is_user_ignored(event.event.sender()).await
+                        || self.is_invite_filtered(&room, &event.event).await
+                    {
                         batch_result.insert(event_id, Ok(NotificationStatus::EventFilteredOut));
                     } else {
                         batch_result.insert(event_id, Ok(NotificationStatus::Event(event)));
@@ -744,7 +912,10 @@ impl NotificationClient {
         let mut timeline_event = response.event.ok_or(Error::ContextMissingEvent)?;
         let state_events = response.state;
 
-        if let Some(decrypted_event) = self.retry_decryption(&room, timeline_event.raw()).await? {
+        if let Some(decrypted_event) = self
+            .retry_decryption(&room, timeline_event.raw(), &mut DecryptionPass::default())
+            .await?
+        {
             timeline_event = decrypted_event;
         }
 
@@ -760,6 +931,7 @@ impl NotificationClient {
             RawNotificationEvent::Timeline(timeline_event.into_raw()),
             push_actions.as_deref(),
             state_events,
+            self.max_thumbnail_size,
         )
         .await?;
 
@@ -771,6 +943,32 @@ impl NotificationClient {
     }
 }
 
+/// State shared by the decryption of all the events of a batch of
+/// notifications.
+#[derive(Default)]
+struct DecryptionPass {
+    /// Has an encryption sync already run for this batch?
+    encryption_synced: bool,
+}
+
+/// Run the given future, failing with [`Error::TimeBudgetExhausted`] if it
+/// doesn't complete before the deadline, if any.
+async fn with_deadline<T>(
+    deadline: Option<Instant>,
+    future: impl Future<Output = Result<T, Error>>,
+) -> Result<T, Error> {
+    let Some(deadline) = deadline else {
+        return future.await;
+    };
+
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    if remaining.is_zero() {
+        return Err(Error::TimeBudgetExhausted);
+    }
+
+    timeout(future, remaining).await.map_err(|_| Error::TimeBudgetExhausted)?
+}
+
 fn is_event_encrypted(event_type: TimelineEventType) -> bool {
     let is_still_encrypted = matches!(event_type, TimelineEventType::RoomEncrypted);
 
@@ -894,6 +1092,17 @@ pub struct NotificationItem {
     pub is_noisy: Option<bool>,
     pub has_mention: Option<bool>,
     pub thread_id: Option<OwnedEventId>,
+
+    /// The plain-text body of the event, if it's a room message, without the
+    /// reply fallback.
+    pub body: Option<String>,
+    /// The beginning of the plain-text body of the thread root, if the event
+    /// is in a thread, and the thread root could be loaded.
+    pub thread_root_snippet: Option<String>,
+    /// The thumbnail of the image, if the event is an image message, and
+    /// thumbnails have been enabled with
+    /// [`NotificationClient::with_max_thumbnail_size`].
+    pub thumbnail: Option<Vec<u8>>,
 }
 
 impl NotificationItem {
@@ -902,6 +1111,7 @@ impl NotificationItem {
         raw_event: RawNotificationEvent,
         push_actions: Option<&[Action]>,
         state_events: Vec<Raw<AnyStateEvent>>,
+        max_thumbnail_size: Option<u64>,
     ) -> Result<Self, Error> {
         let event = match &raw_event {
             RawNotificationEvent::Timeline(raw_event) => {
@@ -966,6 +1176,22 @@ impl NotificationItem {
         let has_mention = push_actions.map(|actions| actions.iter().any(|a| a.is_highlight()));
         let thread_id = event.thread_id().clone();
 
+        let (body, thumbnail) = match &event {
+            NotificationEvent::Timeline(event) => {
+                let thumbnail = match max_thumbnail_size {
+                    Some(max_size) => fetch_thumbnail(room, event, max_size).await,
+                    None => None,
+                };
+                (plain_text_body(event), thumbnail)
+            }
+            NotificationEvent::Invite(_) => (None, None),
+        };
+
+        let thread_root_snippet = match &thread_id {
+            Some(thread_id) => load_thread_root_snippet(room, thread_id).await,
+            None => None,
+        };
+
         let item = NotificationItem {
             event,
             raw_event,
@@ -987,6 +1213,9 @@ impl NotificationItem {
             is_noisy,
             has_mention,
             thread_id,
+            body,
+            thread_root_snippet,
+            thumbnail,
         };
 
         Ok(item)
@@ -1000,6 +1229,136 @@ impl NotificationItem {
     }
 }
 
+/// The maximum number of characters in
+/// [`NotificationItem::thread_root_snippet`].
+const THREAD_ROOT_SNIPPET_MAX_LENGTH: usize = 100;
+
+/// Returns the plain-text body of a room message, without the reply fallback.
+fn plain_text_body(event: &AnySyncTimelineEvent) -> Option<String> {
+    let AnySyncTimelineEvent::MessageLike(AnySyncMessageLikeEvent::RoomMessage(
+        SyncRoomMessageEvent::Original(ev),
+    )) = event
+    else {
+        return None;
+    };
+
+    let body = ev.content.msgtype.body();
+
+    // The reply fallback of plain replies is removed when sanitizing the content,
+    // but not the one of replies in threads.
+    let body = match &ev.content.relates_to {
+        Some(Relation::Thread(thread))
+            if thread.in_reply_to.is_some() && !thread.is_falling_back =>
+        {
+            strip_plain_reply_fallback(body)
+        }
+        _ => body,
+    };
+
+    Some(body.to_owned())
+}
+
+/// Removes the reply fallback from the plain-text body of a message, i.e. the
+/// leading lines starting with `>`, followed by an empty line.
+fn strip_plain_reply_fallback(body: &str) -> &str {
+    let mut rest = body;
+
+    while rest.starts_with('>') {
+        match rest.split_once('\n') {
+            Some((_, tail)) => rest = tail,
+            // The whole body is a quote, keep it.
+            None => return body,
+        }
+    }
+
+    if rest.len() == body.len() {
+        return body;
+    }
+
+    rest.strip_prefix('\n').unwrap_or(rest)
+}
+
+/// Load the root of the thread from the event cache or the server, and
+/// return the beginning of its plain-text body.
+async fn load_thread_root_snippet(room: &Room, thread_id: &EventId) -> Option<String> {
+    let thread_root = match room.load_or_fetch_event(thread_id, None).await {
+        Ok(event) => event,
+        Err(err) => {
+            debug!("Couldn't load the thread root: {err}");
+            return None;
+        }
+    };
+
+    let mut event = thread_root.raw().deserialize().ok()?;
+    if let AnySyncTimelineEvent::MessageLike(AnySyncMessageLikeEvent::RoomMessage(
+        SyncRoomMessageEvent::Original(ev),
+    )) = &mut event
+    {
+        ev.content.sanitize(DEFAULT_SANITIZER_MODE, RemoveReplyFallback::Yes);
+    }
+
+    let body = plain_text_body(&event)?;
+
+    match body.char_indices().nth(THREAD_ROOT_SNIPPET_MAX_LENGTH) {
+        Some((end, _)) => Some(format!("{}…", body[..end].trim_end())),
+        None => Some(body),
+    }
+}
+
+/// Fetch the thumbnail of an image message, if it's not larger than
+/// `max_size` bytes.
+///
+/// Failures are only logged, since a notification without a thumbnail is
+/// still better than no notification.
+async fn fetch_thumbnail(
+    room: &Room,
+    event: &AnySyncTimelineEvent,
+    max_size: u64,
+) -> Option<Vec<u8>> {
+    let AnySyncTimelineEvent::MessageLike(AnySyncMessageLikeEvent::RoomMessage(
+        SyncRoomMessageEvent::Original(ev),
+    )) = event
+    else {
+        return None;
+    };
+    let MessageType::Image(content) = &ev.content.msgtype else {
+        return None;
+    };
+
+    let info = content.info.as_deref();
+
+    let request = if let Some(source) = info.and_then(|info| info.thumbnail_source.clone()) {
+        let size = info.and_then(|info| info.thumbnail_info.as_ref()?.size);
+        if size.is_some_and(|size| u64::from(size) > max_size) {
+            debug!("The thumbnail is too large, not fetching it");
+            return None;
+        }
+
+        MediaRequestParameters { source, format: MediaFormat::File }
+    } else if let MediaSource::Plain(_) = &content.source {
+        // There's no thumbnail, ask the server to generate one.
+        MediaRequestParameters {
+            source: content.source.clone(),
+            format: MediaFormat::Thumbnail(MediaThumbnailSettings::new(uint!(320), uint!(320))),
+        }
+    } else {
+        // The server can't generate a thumbnail for an encrypted image.
+        return None;
+    };
+
+    match room.client().media().get_media_content(&request, true).await {
+        Ok(thumbnail) if thumbnail.len() as u64 <= max_size => Some(thumbnail),
+        Ok(_) => {
+            debug!("The thumbnail is too large, ignoring it");
+            None
+        }
+        Err(err) => {
+            warn!("Failed to fetch the thumbnail: {err}");
+            None
+        }
+    }
+}
+
 /// An error for the [`NotificationClient`].
 #[derive(Debug, Error)]
 pub enum Error {
@@ -1022,6 +1381,11 @@ pub enum Error {
     #[error("the event was missing in the `/context` query")]
     ContextMissingEvent,
 
+    /// The time budget for fetching a batch of notifications has been
+    /// exhausted before this notification could be fetched.
+    #[error("the time budget for fetching notifications has been exhausted")]
+    TimeBudgetExhausted,
+
     /// An error forwarded from the client.
     #[error(transparent)]
     SdkError(#[from] matrix_sdk::Error),
@@ -1035,10 +1399,17 @@ pub enum Error {
 mod tests {
     use assert_matches2::assert_let;
     use matrix_sdk::test_utils::mocks::MatrixMockServer;
-    use matrix_sdk_test::{async_test, event_factory::EventFactory};
-    use ruma::{event_id, room_id, user_id};
-
-    use crate::notification_client::{NotificationItem, RawNotificationEvent};
+    use matrix_sdk_test::{
+        GlobalAccountDataTestEvent, JoinedRoomBuilder, async_test, event_factory::EventFactory,
+    };
+    use ruma::{
+        event_id, events::room::member::MembershipState, media::Method, mxc_uri, room_id, user_id,
+    };
+    use serde_json::json;
+
+    use crate::notification_client::{
+        NotificationItem, RawNotificationEvent, strip_plain_reply_fallback,
+    };
 
     #[async_test]
     async fn test_notification_item_returns_thread_id() {
@@ -1057,11 +1428,153 @@ mod tests {
 
         let raw_notification_event = RawNotificationEvent::Timeline(message);
         let notification_item =
-            NotificationItem::new(&room, raw_notification_event, None, Vec::new())
+            NotificationItem::new(&room, raw_notification_event, None, Vec::new(), None)
                 .await
                 .expect("Could not create notification item");
 
         assert_let!(Some(thread_id) = notification_item.thread_id);
         assert_eq!(thread_id, thread_root_event_id);
     }
+
+    #[async_test]
+    async fn test_notification_item_for_dm_reply_in_thread_with_image() {
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().no_server_versions().build().await;
+        server.mock_versions().ok_custom(&["v1.11"], &Default::default()).mount().await;
+
+        let room_id = room_id!("!a:b.c");
+        let sender = user_id!("@sender:b.c");
+        let sender_avatar_url = mxc_uri!("mxc://b.c/avatar");
+        let thread_root_event_id = event_id!("$root:b.c");
+        let replied_to_event_id = event_id!("$prev:b.c");
+        let own_user_id = client.user_id().unwrap().to_owned();
+
+        let f = EventFactory::new().room(room_id).sender(sender);
+
+        // The room is a DM with the sender.
+        server
+            .mock_sync()
+            .ok_and_run(&client, |builder| {
+                builder
+                    .add_joined_room(
+                        JoinedRoomBuilder::new(room_id)
+                            .add_state_bulk([
+                                f.member(sender)
+                                    .membership(MembershipState::Join)
+                                    .display_name("Sender")
+                                    .avatar_url(sender_avatar_url)
+                                    .into_raw(),
+                                f.member(&own_user_id).membership(MembershipState::Join).into_raw(),
+                            ])
+                            .set_room_summary(json!({
+                                "m.joined_member_count": 2,
+                                "m.heroes": [sender],
+                            })),
+                    )
+                    .add_global_account_data_event(GlobalAccountDataTestEvent::Custom(json!({
+                        "type": "m.direct",
+                        "content": {
+                            sender: [room_id],
+                        },
+                    })));
+            })
+            .await;
+        let room = client.get_room(room_id).unwrap();
+
+        // The thread root is fetched from the server.
+        let long_body = "Lunch? ".repeat(20);
+        server
+            .mock_room_event()
+            .match_event_id()
+            .ok(f.text_msg(long_body).event_id(thread_root_event_id).into())
+            .mock_once()
+            .mount()
+            .await;
+
+        // The server generates the thumbnail.
+        server
+            .mock_authed_media_thumbnail(Method::Scale, 320, 320, false)
+            .ok()
+            .mock_once()
+            .mount()
+            .await;
+
+        let message = f
+            .image("pizza.jpg".to_owned(), mxc_uri!("mxc://b.c/image").to_owned())
+            .caption(Some("> <@other:b.c> Where?\n\nHere!".to_owned()), None)
+            .in_thread_reply(thread_root_event_id, replied_to_event_id)
+            .into_raw_sync();
+
+        let item = NotificationItem::new(
+            &room,
+            RawNotificationEvent::Timeline(message),
+            None,
+            Vec::new(),
+            Some(1024),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(item.sender_display_name.as_deref(), Some("Sender"));
+        assert_eq!(item.sender_avatar_url.as_deref(), Some(sender_avatar_url.as_str()));
+        assert_eq!(item.room_computed_display_name, "Sender");
+        assert!(item.is_direct_message_room);
+        assert_eq!(item.joined_members_count, 2);
+        assert_eq!(item.thread_id.as_deref(), Some(thread_root_event_id));
+        assert_eq!(item.body.as_deref(), Some("Here!"));
+
+        assert_let!(Some(snippet) = item.thread_root_snippet);
+        assert!(snippet.starts_with("Lunch? Lunch?"));
+        assert!(snippet.ends_with('…'));
+        assert!(snippet.chars().count() <= 101);
+
+        assert_eq!(item.thumbnail.as_deref(), Some(&b"binaryjpegthumbnaildata"[..]));
+    }
+
+    #[async_test]
+    async fn test_notification_item_ignores_large_thumbnails() {
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().no_server_versions().build().await;
+        server.mock_versions().ok_custom(&["v1.11"], &Default::default()).mount().await;
+
+        let room_id = room_id!("!a:b.c");
+        let room = server.sync_joined_room(&client, room_id).await;
+
+        server
+            .mock_authed_media_thumbnail(Method::Scale, 320, 320, false)
+            .ok()
+            .mock_once()
+            .mount()
+            .await;
+
+        let message = EventFactory::new()
+            .room(room_id)
+            .sender(user_id!("@sender:b.c"))
+            .image("pizza.jpg".to_owned(), mxc_uri!("mxc://b.c/image").to_owned())
+            .into_raw_sync();
+
+        let item = NotificationItem::new(
+            &room,
+            RawNotificationEvent::Timeline(message),
+            None,
+            vec![],
+            Some(4),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(item.body.as_deref(), Some("pizza.jpg"));
+        assert!(item.thread_root_snippet.is_none());
+        assert!(item.thumbnail.is_none());
+    }
+
+    #[test]
+    fn test_strip_plain_reply_fallback() {
+        assert_eq!(strip_plain_reply_fallback("> <@a:b.c> Hi\n> there\n\nHello"), "Hello");
+        assert_eq!(
+            strip_plain_reply_fallback("Hello\n> not a fallback"),
+            "Hello\n> not a fallback"
+        );
+        assert_eq!(strip_plain_reply_fallback("> only a quote"), "> only a quote");
+    }
 }
@@ -0,0 +1,269 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use matrix_sdk::{Client, deserialized_responses::SyncOrStrippedState};
+use ruma::{
+    OwnedRoomId, RoomId,
+    events::{SyncStateEvent, space::child::SpaceChildEventContent},
+};
+use tracing::warn;
+
+use super::{
+    super::Error, BoxedFilterFn, RoomCategory, new_filter_all, new_filter_any, new_filter_category,
+    new_filter_favourite, new_filter_in_space, new_filter_invite, new_filter_non_left,
+    new_filter_not, new_filter_unread,
+};
+
+/// A composable expression of room filters.
+///
+/// Contrary to a [`BoxedFilterFn`], an expression can be inspected, e.g. to
+/// know which filter chips are selected in the UI. It's applied to a room list
+/// with [`RoomListDynamicEntriesController::apply_filter`].
+///
+/// The filters only rely on the data of the rooms: when the tags, the direct
+/// targets, the unread counts or the membership of a room change, the room
+/// moves in or out of the filtered list.
+///
+/// [`RoomListDynamicEntriesController::apply_filter`]: super::super::RoomListDynamicEntriesController::apply_filter
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FilterExpr {
+    /// The rooms that haven't been left, see [`new_filter_non_left`].
+    NonLeft,
+
+    /// The rooms marked as favourite, see [`new_filter_favourite`].
+    Favourite,
+
+    /// The direct rooms between the user and one other user, see
+    /// [`RoomCategory::People`].
+    Dm,
+
+    /// The rooms with unread notifications, or marked as unread, see
+    /// [`new_filter_unread`].
+    Unread,
+
+    /// The invites, see [`new_filter_invite`].
+    Invite,
+
+    /// The rooms that are children of the given space, according to the
+    /// `m.space.child` state events of the space when the expression is
+    /// applied.
+    InSpace(OwnedRoomId),
+
+    /// The rooms that match all the expressions.
+    All(Vec<FilterExpr>),
+
+    /// The rooms that match at least one of the expressions.
+    Any(Vec<FilterExpr>),
+
+    /// The rooms that don't match the expression.
+    Not(Box<FilterExpr>),
+}
+
+impl FilterExpr {
+    /// Combine this expression with another one, so that the rooms must match
+    /// both.
+    pub fn and(self, other: FilterExpr) -> Self {
+        match self {
+            Self::All(mut exprs) => {
+                exprs.push(other);
+                Self::All(exprs)
+            }
+            expr => Self::All(vec![expr, other]),
+        }
+    }
+
+    /// Combine this expression with another one, so that the rooms must match
+    /// at least one of them.
+    pub fn or(self, other: FilterExpr) -> Self {
+        match self {
+            Self::Any(mut exprs) => {
+                exprs.push(other);
+                Self::Any(exprs)
+            }
+            expr => Self::Any(vec![expr, other]),
+        }
+    }
+
+    /// Whether this expression, or one of its sub-expressions, is the given
+    /// one.
+    ///
+    /// This is useful to know whether a filter chip is selected.
+    pub fn contains(&self, expr: &FilterExpr) -> bool {
+        if self == expr {
+            return true;
+        }
+
+        match self {
+            Self::All(exprs) | Self::Any(exprs) => exprs.iter().any(|e| e.contains(expr)),
+            Self::Not(inner) => inner.contains(expr),
+            _ => false,
+        }
+    }
+
+    /// The IDs of the spaces this expression refers to.
+    fn spaces(&self) -> BTreeSet<&RoomId> {
+        match self {
+            Self::InSpace(space_id) => BTreeSet::from([&**space_id]),
+            Self::All(exprs) | Self::Any(exprs) => exprs.iter().flat_map(Self::spaces).collect(),
+            Self::Not(inner) => inner.spaces(),
+            _ => BTreeSet::new(),
+        }
+    }
+
+    /// Build the filter of this expression, given the children of the spaces
+    /// it refers to.
+    fn to_filter(
+        &self,
+        space_children: &BTreeMap<OwnedRoomId, BTreeSet<OwnedRoomId>>,
+    ) -> BoxedFilterFn {
+        match self {
+            Self::NonLeft => Box::new(new_filter_non_left()),
+            Self::Favourite => Box::new(new_filter_favourite()),
+            Self::Dm => Box::new(new_filter_category(RoomCategory::People)),
+            Self::Unread => Box::new(new_filter_unread()),
+            Self::Invite => Box::new(new_filter_invite()),
+            Self::InSpace(space_id) => Box::new(new_filter_in_space(
+                space_children.get(space_id).cloned().unwrap_or_default(),
+            )),
+            Self::All(exprs) => Box::new(new_filter_all(
+                exprs.iter().map(|expr| expr.to_filter(space_children)).collect(),
+            )),
+            Self::Any(exprs) => Box::new(new_filter_any(
+                exprs.iter().map(|expr| expr.to_filter(space_children)).collect(),
+            )),
+            Self::Not(inner) => Box::new(new_filter_not(inner.to_filter(space_children))),
+        }
+    }
+
+    /// Build the filter of this expression.
+    ///
+    /// The children of the spaces the expression refers to are loaded from
+    /// the store.
+    pub(in crate::room_list_service) async fn build(
+        &self,
+        client: &Client,
+    ) -> Result<BoxedFilterFn, Error> {
+        let mut space_children = BTreeMap::new();
+
+        for space_id in self.spaces() {
+            space_children
+                .insert(space_id.to_owned(), load_space_children(client, space_id).await?);
+        }
+
+        Ok(self.to_filter(&space_children))
+    }
+}
+
+/// Load the IDs of the children of a space, from its `m.space.child` state
+/// events.
+async fn load_space_children(
+    client: &Client,
+    space_id: &RoomId,
+) -> Result<BTreeSet<OwnedRoomId>, Error> {
+    let space =
+        client.get_room(space_id).ok_or_else(|| Error::RoomNotFound(space_id.to_owned()))?;
+
+    let children = space
+        .get_state_events_static::<SpaceChildEventContent>()
+        .await
+        .map_err(Error::SlidingSync)?
+        .into_iter()
+        .filter_map(|event| match event.deserialize() {
+            // A child without `via` has been removed from the space.
+            Ok(SyncOrStrippedState::Sync(SyncStateEvent::Original(event))) => {
+                (!event.content.via.is_empty()).then_some(event.state_key)
+            }
+            Ok(SyncOrStrippedState::Sync(SyncStateEvent::Redacted(_))) => None,
+            Ok(SyncOrStrippedState::Stripped(event)) => Some(event.state_key),
+            Err(error) => {
+                warn!(?space_id, "Could not deserialize m.space.child: {error}");
+                None
+            }
+        })
+        .collect();
+
+    Ok(children)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Not;
+
+    use matrix_sdk::test_utils::logged_in_client_with_server;
+    use matrix_sdk_test::async_test;
+    use ruma::{owned_room_id, room_id};
+
+    use super::{super::new_rooms, *};
+
+    #[test]
+    fn test_composition() {
+        let expr = FilterExpr::NonLeft.and(FilterExpr::Favourite).and(FilterExpr::Unread);
+        assert_eq!(
+            expr,
+            FilterExpr::All(vec![FilterExpr::NonLeft, FilterExpr::Favourite, FilterExpr::Unread])
+        );
+
+        let expr = FilterExpr::Dm.or(FilterExpr::Invite);
+        assert_eq!(expr, FilterExpr::Any(vec![FilterExpr::Dm, FilterExpr::Invite]));
+
+        let expr = FilterExpr::NonLeft.and(expr);
+        assert!(expr.contains(&FilterExpr::NonLeft));
+        assert!(expr.contains(&FilterExpr::Invite));
+        assert!(expr.contains(&FilterExpr::Favourite).not());
+    }
+
+    #[test]
+    fn test_spaces() {
+        let space_a = owned_room_id!("!space_a:b.c");
+        let space_b = owned_room_id!("!space_b:b.c");
+
+        let expr = FilterExpr::InSpace(space_a.clone())
+            .or(FilterExpr::Not(Box::new(FilterExpr::InSpace(space_b.clone()))))
+            .and(FilterExpr::NonLeft);
+
+        assert_eq!(expr.spaces(), BTreeSet::from([&*space_a, &*space_b]));
+        assert!(FilterExpr::Favourite.spaces().is_empty());
+    }
+
+    #[async_test]
+    async fn test_to_filter() {
+        let (client, server) = logged_in_client_with_server().await;
+        let [room] = new_rooms([room_id!("!a:b.c")], &client, &server).await;
+
+        let space_id = owned_room_id!("!space:b.c");
+        let space_children =
+            BTreeMap::from([(space_id.clone(), BTreeSet::from([room.room_id().to_owned()]))]);
+
+        // The room is joined, it's not a favourite.
+        assert!(FilterExpr::NonLeft.to_filter(&space_children)(&room));
+        assert!(FilterExpr::Favourite.to_filter(&space_children)(&room).not());
+        assert!(FilterExpr::Invite.to_filter(&space_children)(&room).not());
+        assert!(
+            FilterExpr::NonLeft.and(FilterExpr::Favourite).to_filter(&space_children)(&room).not()
+        );
+        assert!(FilterExpr::Favourite.or(FilterExpr::NonLeft).to_filter(&space_children)(&room));
+        assert!(FilterExpr::Not(Box::new(FilterExpr::Favourite)).to_filter(&space_children)(&room));
+
+        // The room is in the space, but not in the unknown one.
+        assert!(FilterExpr::InSpace(space_id).to_filter(&space_children)(&room));
+        assert!(
+            FilterExpr::InSpace(owned_room_id!("!other_space:b.c")).to_filter(&space_children)(
+                &room
+            )
+            .not()
+        );
+    }
+}
@@ -34,10 +34,15 @@ where
 
 /// Create a new filter that will filter out rooms that are not invites (see
 /// [`matrix_sdk_base::RoomState::Invited`]).
+///
+/// The invites filtered out by the client's invite filter policy (see
+/// [`matrix_sdk::Client::set_invite_filter`]) are filtered out too.
 pub fn new_filter() -> impl Filter {
     let matcher = InviteRoomMatcher { state: move |room| room.state() };
 
-    move |room| -> bool { matcher.matches(room) }
+    move |room| -> bool {
+        matcher.matches(room) && !room.client().is_invite_filtered(room.room_id())
+    }
 }
 
 #[cfg(test)]
@@ -55,6 +55,7 @@ mod all;
 mod any;
 mod category;
 mod deduplicate_versions;
+mod expr;
 mod favourite;
 mod fuzzy_match_room_name;
 mod invite;
@@ -63,12 +64,14 @@ mod non_left;
 mod none;
 mod normalized_match_room_name;
 mod not;
+mod space;
 mod unread;
 
 pub use all::new_filter as new_filter_all;
 pub use any::new_filter as new_filter_any;
 pub use category::{RoomCategory, new_filter as new_filter_category};
 pub use deduplicate_versions::new_filter as new_filter_deduplicate_versions;
+pub use expr::FilterExpr;
 pub use favourite::new_filter as new_filter_favourite;
 pub use fuzzy_match_room_name::new_filter as new_filter_fuzzy_match_room_name;
 pub use invite::new_filter as new_filter_invite;
@@ -84,6 +87,7 @@ pub use normalized_match_room_name::new_filter as new_filter_normalized_match_ro
 pub use not::new_filter as new_filter_not;
 #[cfg(test)]
 use ruma::RoomId;
+pub use space::new_filter as new_filter_in_space;
 use unicode_normalization::{UnicodeNormalization, char::is_combining_mark};
 pub use unread::new_filter as new_filter_unread;
 #[cfg(test)]
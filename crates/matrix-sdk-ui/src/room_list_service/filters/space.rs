@@ -0,0 +1,78 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeSet;
+
+use ruma::OwnedRoomId;
+
+use super::{super::Room, Filter};
+
+struct SpaceRoomMatcher {
+    /// The rooms that are children of the space.
+    children: BTreeSet<OwnedRoomId>,
+}
+
+impl SpaceRoomMatcher {
+    fn matches(&self, room: &Room) -> bool {
+        self.children.contains(room.room_id())
+    }
+}
+
+/// Create a new filter that will filter out rooms that are not children of a
+/// space, given the IDs of its children.
+///
+/// See [`FilterExpr::InSpace`](super::FilterExpr::InSpace) to get the children
+/// of a space from its `m.space.child` state events.
+pub fn new_filter(children: BTreeSet<OwnedRoomId>) -> impl Filter {
+    let matcher = SpaceRoomMatcher { children };
+
+    move |room| -> bool { matcher.matches(room) }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Not;
+
+    use matrix_sdk::test_utils::logged_in_client_with_server;
+    use matrix_sdk_test::async_test;
+    use ruma::{owned_room_id, room_id};
+
+    use super::{super::new_rooms, *};
+
+    #[async_test]
+    async fn test_is_in_space() {
+        let (client, server) = logged_in_client_with_server().await;
+        let [room] = new_rooms([room_id!("!a:b.c")], &client, &server).await;
+
+        let matcher = SpaceRoomMatcher {
+            children: BTreeSet::from([owned_room_id!("!a:b.c"), owned_room_id!("!b:b.c")]),
+        };
+
+        assert!(matcher.matches(&room));
+    }
+
+    #[async_test]
+    async fn test_is_not_in_space() {
+        let (client, server) = logged_in_client_with_server().await;
+        let [room] = new_rooms([room_id!("!a:b.c")], &client, &server).await;
+
+        let matcher = SpaceRoomMatcher { children: BTreeSet::from([owned_room_id!("!b:b.c")]) };
+
+        assert!(matcher.matches(&room).not());
+
+        let matcher = SpaceRoomMatcher { children: BTreeSet::new() };
+
+        assert!(matcher.matches(&room).not());
+    }
+}
@@ -34,8 +34,8 @@ use tracing::{error, trace};
 
 use super::{
     Error, Room, State,
-    filters::BoxedFilterFn,
-    sorters::{new_sorter_lexicographic, new_sorter_name, new_sorter_recency},
+    filters::{BoxedFilterFn, FilterExpr},
+    sorters::{RoomListItemSnapshot, RoomListSorting, sort_by_strategy},
 };
 
 /// A `RoomList` represents a list of rooms, from a
@@ -127,13 +127,16 @@ impl RoomList {
     ///
     /// It's possible to provide a filter that will filter out room list
     /// entries, and that it's also possible to “paginate” over the entries by
-    /// `page_size`. The rooms are also sorted.
+    /// `page_size`. The rooms are also sorted, by
+    /// [`RoomListSorting::Recency`] unless another strategy is set with
+    /// [`RoomListDynamicEntriesController::set_sorting`].
     ///
     /// The returned stream will only start yielding diffs once a filter is set
     /// through the returned [`RoomListDynamicEntriesController`]. For every
-    /// call to [`RoomListDynamicEntriesController::set_filter`], the stream
-    /// will yield a [`VectorDiff::Reset`] followed by any updates of the
-    /// room list under that filter (until the next reset).
+    /// call to [`RoomListDynamicEntriesController::set_filter`] or
+    /// [`RoomListDynamicEntriesController::set_sorting`], the stream will
+    /// yield a [`VectorDiff::Reset`] followed by any updates of the room list
+    /// under that filter and sorting (until the next reset).
     pub fn entries_with_dynamic_adapters(
         &self,
         page_size: usize,
@@ -143,31 +146,53 @@ impl RoomList {
 
         let filter_fn_cell = AsyncCell::shared();
 
+        let sorting = SharedObservable::new(RoomListSorting::default());
+        let mut sorting_stream = sorting.subscribe();
+
         let limit = SharedObservable::<usize>::new(page_size);
         let limit_stream = limit.subscribe();
 
         let dynamic_entries_controller = RoomListDynamicEntriesController::new(
+            self.client.clone(),
             filter_fn_cell.clone(),
+            sorting,
             page_size,
             limit,
             list.maximum_number_of_rooms_stream(),
         );
 
         let stream = stream! {
+            let mut filter_fn: Option<Arc<BoxedFilterFn>> = None;
+
             loop {
-                let filter_fn = filter_fn_cell.take().await;
+                // Wait for the filter or the sorting to change.
+                select! {
+                    new_filter_fn = filter_fn_cell.take() => {
+                        filter_fn = Some(Arc::new(new_filter_fn));
+                    }
+
+                    Some(_) = sorting_stream.next() => {}
+                }
+
+                // Nothing is yielded until a filter has been set.
+                let Some(filter_fn) = filter_fn.clone() else {
+                    continue;
+                };
 
                 let (raw_values, raw_stream) = self.entries();
 
                 // Combine normal stream events with other updates from rooms
                 let merged_streams = merge_stream_and_receiver(raw_values.clone(), raw_stream, room_info_notable_update_receiver.resubscribe());
 
-                let (values, stream) = (raw_values, merged_streams)
-                    .filter(filter_fn)
-                    .sort_by(new_sorter_lexicographic(vec![
-                        Box::new(new_sorter_recency()),
-                        Box::new(new_sorter_name())
-                    ]))
+                let (values, stream) =
+                    (raw_values, merged_streams).filter(move |room| filter_fn(room));
+                let (values, stream) = sort_by_strategy(
+                    values,
+                    stream,
+                    sorting_stream.get(),
+                    RoomListItemSnapshot::new,
+                );
+                let (values, stream) = (values, stream)
                     .dynamic_head_with_initial_value(page_size, limit_stream.clone());
 
                 // Clearing the stream before chaining with the real stream.
@@ -286,7 +311,10 @@ pub enum RoomListLoadingState {
 /// To get one value of this type, use
 /// [`RoomList::entries_with_dynamic_adapters`]
 pub struct RoomListDynamicEntriesController {
+    client: Client,
     filter: Arc<AsyncCell<BoxedFilterFn>>,
+    filter_expr: SharedObservable<Option<FilterExpr>>,
+    sorting: SharedObservable<RoomListSorting>,
     page_size: usize,
     limit: SharedObservable<usize>,
     maximum_number_of_rooms: Subscriber<Option<u32>>,
@@ -294,12 +322,22 @@ pub struct RoomListDynamicEntriesController {
 
 impl RoomListDynamicEntriesController {
     fn new(
+        client: Client,
         filter: Arc<AsyncCell<BoxedFilterFn>>,
+        sorting: SharedObservable<RoomListSorting>,
         page_size: usize,
         limit_stream: SharedObservable<usize>,
         maximum_number_of_rooms: Subscriber<Option<u32>>,
     ) -> Self {
-        Self { filter, page_size, limit: limit_stream, maximum_number_of_rooms }
+        Self {
+            client,
+            filter,
+            filter_expr: SharedObservable::new(None),
+            sorting,
+            page_size,
+            limit: limit_stream,
+            maximum_number_of_rooms,
+        }
     }
 
     /// Set the filter.
@@ -314,10 +352,63 @@ impl RoomListDynamicEntriesController {
             false
         } else {
             self.filter.set(filter);
+            // The filter can't be inspected anymore.
+            self.filter_expr.set_if_not_eq(None);
+            true
+        }
+    }
+
+    /// Set the filter from a [`FilterExpr`].
+    ///
+    /// Contrary to [`Self::set_filter`], the applied expression can be
+    /// inspected with [`Self::filter_expr`].
+    ///
+    /// If the associated stream has been dropped, returns `Ok(false)` to
+    /// indicate the operation didn't have an effect.
+    pub async fn apply_filter(&self, expr: FilterExpr) -> Result<bool, Error> {
+        let filter = expr.build(&self.client).await?;
+
+        if !self.set_filter(filter) {
+            return Ok(false);
+        }
+
+        self.filter_expr.set(Some(expr));
+
+        Ok(true)
+    }
+
+    /// Get a subscriber to the filter expression applied with
+    /// [`Self::apply_filter`].
+    ///
+    /// The value is `None` if no expression has been applied, or if a filter
+    /// has been set with [`Self::set_filter`] since then.
+    pub fn filter_expr(&self) -> Subscriber<Option<FilterExpr>> {
+        self.filter_expr.subscribe_reset()
+    }
+
+    /// Set how the rooms are sorted.
+    ///
+    /// The associated stream will yield a [`VectorDiff::Reset`] with the rooms
+    /// sorted with the new strategy, followed by incremental updates, once a
+    /// filter has been set.
+    ///
+    /// If the associated stream has been dropped, returns `false` to indicate
+    /// the operation didn't have an effect.
+    pub fn set_sorting(&self, sorting: RoomListSorting) -> bool {
+        if self.sorting.subscriber_count() == 0 {
+            // The stream, which owns the only subscriber, has been dropped.
+            false
+        } else {
+            self.sorting.set(sorting);
             true
         }
     }
 
+    /// Get how the rooms are sorted.
+    pub fn sorting(&self) -> RoomListSorting {
+        self.sorting.get()
+    }
+
     /// Add one page, i.e. view `page_size` more entries in the room list if
     /// any.
     pub fn add_one_page(&self) {
@@ -17,12 +17,15 @@
 mod lexicographic;
 mod name;
 mod recency;
+mod strategy;
 
 use std::cmp::Ordering;
 
 pub use lexicographic::new_sorter as new_sorter_lexicographic;
 pub use name::new_sorter as new_sorter_name;
 pub use recency::new_sorter as new_sorter_recency;
+pub(super) use strategy::sort_by_strategy;
+pub use strategy::{RoomListItemSnapshot, RoomListSorting, SnapshotSorterFn};
 
 use super::Room;
 
@@ -0,0 +1,338 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{cmp::Ordering, fmt, sync::Arc};
+
+use eyeball_im::{Vector, VectorDiff};
+use eyeball_im_util::vector::VectorObserverExt;
+use futures_util::{Stream, StreamExt as _};
+use ruma::OwnedRoomId;
+
+use super::Room;
+
+/// The data of a room used to sort the room list, as it was when the room was
+/// last updated.
+///
+/// Contrary to [`Room`]s, which all share the same live data, a snapshot
+/// doesn't change. When a room is updated, comparing its new snapshot with
+/// the previous one gives a meaningful result, which allows the room list to
+/// be sorted incrementally.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RoomListItemSnapshot {
+    /// The ID of the room.
+    pub room_id: OwnedRoomId,
+
+    /// The display name of the room, if it's been computed.
+    pub display_name: Option<String>,
+
+    /// The recency stamp of the room, see
+    /// [`RoomInfo::recency_stamp`](matrix_sdk_base::RoomInfo::recency_stamp).
+    pub recency_stamp: Option<u64>,
+
+    /// The number of unread messages in the room.
+    pub num_unread_messages: u64,
+
+    /// The number of unread notifications in the room.
+    pub num_unread_notifications: u64,
+
+    /// The number of unread mentions in the room.
+    pub num_unread_mentions: u64,
+
+    /// Whether the room has been explicitly marked as unread.
+    pub is_marked_unread: bool,
+
+    /// Whether the room is a favourite.
+    pub is_favourite: bool,
+
+    /// Whether the room has a low priority.
+    pub is_low_priority: bool,
+}
+
+impl RoomListItemSnapshot {
+    /// Take a snapshot of the current data of the given room.
+    pub fn new(room: &Room) -> Self {
+        Self {
+            room_id: room.room_id().to_owned(),
+            display_name: room.cached_display_name().map(|name| name.to_string()),
+            recency_stamp: room.recency_stamp(),
+            num_unread_messages: room.num_unread_messages(),
+            num_unread_notifications: room.num_unread_notifications(),
+            num_unread_mentions: room.num_unread_mentions(),
+            is_marked_unread: room.is_marked_unread(),
+            is_favourite: room.is_favourite(),
+            is_low_priority: room.is_low_priority(),
+        }
+    }
+
+    /// Whether the room has unread notifications, or has been marked as
+    /// unread.
+    ///
+    /// This is consistent with
+    /// [`new_filter_unread`](super::super::filters::new_filter_unread).
+    pub fn is_unread(&self) -> bool {
+        self.num_unread_notifications > 0 || self.is_marked_unread
+    }
+}
+
+/// Type alias for a shared function comparing two [`RoomListItemSnapshot`]s.
+#[cfg(not(target_family = "wasm"))]
+pub type SnapshotSorterFn =
+    Arc<dyn Fn(&RoomListItemSnapshot, &RoomListItemSnapshot) -> Ordering + Send + Sync>;
+#[cfg(target_family = "wasm")]
+pub type SnapshotSorterFn = Arc<dyn Fn(&RoomListItemSnapshot, &RoomListItemSnapshot) -> Ordering>;
+
+/// How the rooms of a [`RoomList`](super::super::RoomList) are sorted.
+///
+/// Whatever the strategy, rooms that compare equal are sorted by room ID, so
+/// the order is stable.
+#[derive(Clone, Default)]
+pub enum RoomListSorting {
+    /// The rooms with the most recent activity come first, then the rooms are
+    /// sorted by name.
+    #[default]
+    Recency,
+
+    /// The rooms are sorted by name, case-insensitively; rooms without a name
+    /// come last.
+    Alphabetical,
+
+    /// The rooms with unread notifications, or that are marked as unread,
+    /// come first; then the rooms are sorted as with [`Self::Recency`].
+    UnreadFirstThenRecency,
+
+    /// The rooms are sorted with the given comparator.
+    Custom(SnapshotSorterFn),
+}
+
+impl fmt::Debug for RoomListSorting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Recency => f.write_str("Recency"),
+            Self::Alphabetical => f.write_str("Alphabetical"),
+            Self::UnreadFirstThenRecency => f.write_str("UnreadFirstThenRecency"),
+            Self::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
+impl RoomListSorting {
+    /// Compare two snapshots with this strategy.
+    pub fn compare(&self, left: &RoomListItemSnapshot, right: &RoomListItemSnapshot) -> Ordering {
+        let ordering = match self {
+            Self::Recency => by_recency(left, right).then_with(|| by_name(left, right)),
+            Self::Alphabetical => by_name(left, right).then_with(|| by_recency(left, right)),
+            Self::UnreadFirstThenRecency => right
+                .is_unread()
+                .cmp(&left.is_unread())
+                .then_with(|| by_recency(left, right))
+                .then_with(|| by_name(left, right)),
+            Self::Custom(sorter) => sorter(left, right),
+        };
+
+        ordering.then_with(|| left.room_id.cmp(&right.room_id))
+    }
+}
+
+/// The newest recency stamp comes first, rooms without a recency stamp come
+/// last.
+fn by_recency(left: &RoomListItemSnapshot, right: &RoomListItemSnapshot) -> Ordering {
+    match (left.recency_stamp, right.recency_stamp) {
+        (Some(left), Some(right)) => left.cmp(&right).reverse(),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// Case-insensitive order of the names, rooms without a name come last.
+fn by_name(left: &RoomListItemSnapshot, right: &RoomListItemSnapshot) -> Ordering {
+    match (&left.display_name, &right.display_name) {
+        (Some(left), Some(right)) => left.to_lowercase().cmp(&right.to_lowercase()),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// An item along with its snapshot, taken when the item was last updated.
+#[derive(Clone)]
+struct Snapshotted<T> {
+    item: T,
+    snapshot: RoomListItemSnapshot,
+}
+
+/// Sort a list of items and its stream of updates with the given strategy.
+///
+/// A snapshot of each item is taken when it's added or updated, and the list
+/// is sorted incrementally: an update of a single item results in a `Set`, or
+/// in a `Remove` and an `Insert` if it moves, rather than a reset of the list.
+pub(in crate::room_list_service) fn sort_by_strategy<T, S>(
+    values: Vector<T>,
+    stream: S,
+    sorting: RoomListSorting,
+    take_snapshot: fn(&T) -> RoomListItemSnapshot,
+) -> (Vector<T>, impl Stream<Item = Vec<VectorDiff<T>>>)
+where
+    T: Clone + 'static,
+    S: Stream<Item = Vec<VectorDiff<T>>>,
+{
+    let snapshotted = move |item: T| Snapshotted { snapshot: take_snapshot(&item), item };
+
+    let values: Vector<_> = values.into_iter().map(snapshotted).collect();
+    let stream = stream
+        .map(move |diffs| diffs.into_iter().map(|diff| diff.map(snapshotted)).collect::<Vec<_>>());
+
+    let (values, stream) = (values, stream)
+        .sort_by(move |left, right| sorting.compare(&left.snapshot, &right.snapshot));
+
+    let values = values.into_iter().map(|snapshotted| snapshotted.item).collect();
+    let stream = stream.map(|diffs| {
+        diffs.into_iter().map(|diff| diff.map(|snapshotted| snapshotted.item)).collect::<Vec<_>>()
+    });
+
+    (values, stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cmp::Ordering, sync::Arc};
+
+    use assert_matches::assert_matches;
+    use eyeball_im::{Vector, VectorDiff};
+    use futures_util::{StreamExt as _, pin_mut, stream};
+    use matrix_sdk_test::async_test;
+    use ruma::{OwnedRoomId, room_id};
+
+    use super::{RoomListItemSnapshot, RoomListSorting, sort_by_strategy};
+
+    fn snapshot(room_id: OwnedRoomId, name: &str, recency_stamp: u64) -> RoomListItemSnapshot {
+        RoomListItemSnapshot {
+            room_id,
+            display_name: Some(name.to_owned()),
+            recency_stamp: Some(recency_stamp),
+            num_unread_messages: 0,
+            num_unread_notifications: 0,
+            num_unread_mentions: 0,
+            is_marked_unread: false,
+            is_favourite: false,
+            is_low_priority: false,
+        }
+    }
+
+    fn names(values: &Vector<RoomListItemSnapshot>) -> Vec<&str> {
+        values.iter().map(|snapshot| snapshot.display_name.as_deref().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_strategies() {
+        let alice = snapshot(room_id!("!a:b.c").to_owned(), "alice", 1);
+        let bob = snapshot(room_id!("!b:b.c").to_owned(), "Bob", 3);
+        let mut carol = snapshot(room_id!("!c:b.c").to_owned(), "carol", 2);
+        carol.is_marked_unread = true;
+
+        let mut rooms = vec![alice.clone(), bob.clone(), carol.clone()];
+
+        rooms.sort_by(|l, r| RoomListSorting::Recency.compare(l, r));
+        assert_eq!(rooms, [bob.clone(), carol.clone(), alice.clone()]);
+
+        rooms.sort_by(|l, r| RoomListSorting::Alphabetical.compare(l, r));
+        assert_eq!(rooms, [alice.clone(), bob.clone(), carol.clone()]);
+
+        rooms.sort_by(|l, r| RoomListSorting::UnreadFirstThenRecency.compare(l, r));
+        assert_eq!(rooms, [carol.clone(), bob.clone(), alice.clone()]);
+
+        let by_recency_reversed =
+            RoomListSorting::Custom(Arc::new(|l, r| l.recency_stamp.cmp(&r.recency_stamp)));
+        rooms.sort_by(|l, r| by_recency_reversed.compare(l, r));
+        assert_eq!(rooms, [alice, carol, bob]);
+    }
+
+    #[test]
+    fn test_ties_are_sorted_by_room_id() {
+        let first = snapshot(room_id!("!a:b.c").to_owned(), "same", 1);
+        let second = snapshot(room_id!("!b:b.c").to_owned(), "same", 1);
+
+        for sorting in [
+            RoomListSorting::Recency,
+            RoomListSorting::Alphabetical,
+            RoomListSorting::UnreadFirstThenRecency,
+            RoomListSorting::Custom(Arc::new(|_, _| Ordering::Equal)),
+        ] {
+            assert_eq!(sorting.compare(&first, &second), Ordering::Less);
+            assert_eq!(sorting.compare(&second, &first), Ordering::Greater);
+            assert_eq!(sorting.compare(&first, &first), Ordering::Equal);
+        }
+    }
+
+    #[async_test]
+    async fn test_updates_produce_minimal_diffs() {
+        let room_a = room_id!("!a:b.c").to_owned();
+        let room_b = room_id!("!b:b.c").to_owned();
+        let room_c = room_id!("!c:b.c").to_owned();
+
+        let values: Vector<_> = [
+            snapshot(room_a.clone(), "a", 1),
+            snapshot(room_b.clone(), "b", 2),
+            snapshot(room_c.clone(), "c", 3),
+        ]
+        .into_iter()
+        .collect();
+
+        let updates = stream::iter([
+            // A new message in `room_a`: it moves to the top.
+            vec![VectorDiff::Set { index: 0, value: snapshot(room_a.clone(), "a", 4) }],
+            // `room_b` is renamed: it stays in place.
+            vec![VectorDiff::Set { index: 1, value: snapshot(room_b.clone(), "bb", 2) }],
+            // A new room with the same recency and name as `room_c`: the room ID breaks the tie.
+            vec![VectorDiff::PushBack { value: snapshot(room_id!("!0:b.c").to_owned(), "c", 3) }],
+        ]);
+
+        let (mut values, stream) = sort_by_strategy(
+            values,
+            updates,
+            RoomListSorting::Recency,
+            RoomListItemSnapshot::clone,
+        );
+        pin_mut!(stream);
+
+        assert_eq!(names(&values), ["c", "b", "a"]);
+
+        let diffs = stream.next().await.unwrap();
+        assert_matches!(
+            diffs.as_slice(),
+            [VectorDiff::Remove { index: 2 }, VectorDiff::Insert { index: 0, .. }]
+        );
+        for diff in diffs {
+            diff.apply(&mut values);
+        }
+        assert_eq!(names(&values), ["a", "c", "b"]);
+
+        let diffs = stream.next().await.unwrap();
+        assert_matches!(diffs.as_slice(), [VectorDiff::Set { index: 2, .. }]);
+        for diff in diffs {
+            diff.apply(&mut values);
+        }
+        assert_eq!(names(&values), ["a", "c", "bb"]);
+
+        let diffs = stream.next().await.unwrap();
+        assert_matches!(diffs.as_slice(), [VectorDiff::Insert { index: 1, .. }]);
+        for diff in diffs {
+            diff.apply(&mut values);
+        }
+        assert_eq!(values[1].room_id, room_id!("!0:b.c"));
+        assert_eq!(values[2].room_id, room_c);
+
+        assert!(stream.next().await.is_none());
+    }
+}
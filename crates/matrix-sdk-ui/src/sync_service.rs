@@ -80,6 +80,9 @@ pub enum State {
     /// `/_matrix/client/versions` endpoint, it will go back into the
     /// [`State::Running`] mode and attempt to sync again.
     ///
+    /// While in this state, the client's send queue is paused, see
+    /// [`matrix_sdk::send_queue::SendQueue::set_network_available`].
+    ///
     /// Calling [`SyncService::start()`] while in this state will abort the
     /// `/_matrix/client/versions` checks and attempt to sync immediately.
     ///
@@ -303,11 +306,20 @@ impl SyncTaskSupervisor {
 
                 if report.is_error {
                     if offline_mode {
+                        let client = room_list_service.client();
+
+                        // Don't let the send queue try to send anything while we're offline.
+                        client.send_queue().set_network_available(false);
+
                         state.set(State::Offline);
 
-                        let client = room_list_service.client();
+                        let report = Self::offline_check(client, &mut receiver).await;
+
+                        // Either the server is reachable again, or the offline mode has been
+                        // aborted; in both cases, let the send queue resume.
+                        client.send_queue().set_network_available(true);
 
-                        if let Some(report) = Self::offline_check(client, &mut receiver).await {
+                        if let Some(report) = report {
                             if report.is_error {
                                 state.set(State::Error);
                             } else {
@@ -16,11 +16,11 @@ use std::sync::Arc;
 
 use matrix_sdk::{Room, executor::spawn};
 use matrix_sdk_base::{SendOutsideWasm, SyncOutsideWasm};
-use ruma::{events::AnySyncTimelineEvent, room_version_rules::RoomVersionRules};
+use ruma::{OwnedEventId, events::AnySyncTimelineEvent, room_version_rules::RoomVersionRules};
 use tracing::{Instrument, Span, info_span};
 
 use super::{
-    DateDividerMode, Error, Timeline, TimelineDropHandle, TimelineFocus,
+    DateDividerMode, Error, Timeline, TimelineConfig, TimelineDropHandle, TimelineFocus,
     controller::{TimelineController, TimelineSettings},
 };
 use crate::{
@@ -70,6 +70,46 @@ impl TimelineBuilder {
         self
     }
 
+    /// Focus the timeline on the given event, e.g. after clicking a permalink.
+    ///
+    /// The timeline is seeded with up to `num_context_events` events around
+    /// the focused event, and can be paginated in both directions. Once
+    /// forwards pagination reaches the live edge of the room, the timeline
+    /// receives the new events from sync.
+    pub fn focused_on_event(self, event_id: OwnedEventId, num_context_events: u16) -> Self {
+        self.with_focus(TimelineFocus::Event {
+            target: event_id,
+            num_context_events,
+            hide_threaded_events: false,
+        })
+    }
+
+    /// Focus the timeline on the thread starting at the given root event.
+    ///
+    /// The timeline shows the thread root and its replies, paginates
+    /// backwards with the relations endpoint, and sends the messages
+    /// passed to [`Timeline::send`] as replies in the thread.
+    pub fn thread(self, root_event_id: OwnedEventId) -> Self {
+        self.with_focus(TimelineFocus::Thread { root_event_id })
+    }
+
+    /// Focus the timeline on the pinned events of the room.
+    ///
+    /// The timeline contains the events listed in the room's
+    /// `m.room.pinned_events` state, in the order they've been pinned, with
+    /// their edits and reactions. Items are added or removed as events are
+    /// pinned or unpinned.
+    ///
+    /// Up to 100 pinned events are loaded, with 10 concurrent requests at most;
+    /// use [`Self::with_focus`] with a [`TimelineFocus::PinnedEvents`] to
+    /// change those limits.
+    pub fn pinned_events(self) -> Self {
+        self.with_focus(TimelineFocus::PinnedEvents {
+            max_events_to_load: 100,
+            max_concurrent_requests: 10,
+        })
+    }
+
     /// Sets up a hook to catch unable-to-decrypt (UTD) events for the timeline
     /// we're building.
     ///
@@ -91,7 +131,14 @@ impl TimelineBuilder {
     /// Chose when to insert the date separators, either in between each day
     /// or each month.
     pub fn with_date_divider_mode(mut self, mode: DateDividerMode) -> Self {
-        self.settings.date_divider_mode = mode;
+        self.settings.virtual_items.date_divider_mode = Some(mode);
+        self
+    }
+
+    /// Configure the virtual items inserted in the timeline: the date
+    /// dividers, the timezone they're computed in, and the read marker.
+    pub fn with_config(mut self, config: TimelineConfig) -> Self {
+        self.settings.virtual_items = config;
         self
     }
 
@@ -271,6 +318,7 @@ impl TimelineBuilder {
                 local_echo_listener_handle,
                 _event_cache_drop_handle: event_cache_drop,
             }),
+            expanded_state_groups: Default::default(),
         };
 
         if has_events {
@@ -390,6 +390,19 @@ impl Aggregations {
         self.related_events.entry(related_to).or_default().push(aggregation);
     }
 
+    /// Is the given id the one of a known aggregation to another event?
+    pub fn is_aggregation(&self, id: &TimelineEventItemId) -> bool {
+        self.inverted_map.contains_key(id)
+    }
+
+    /// Get a known aggregation, along with the identifier of the event it
+    /// relates to.
+    pub fn get(&self, id: &TimelineEventItemId) -> Option<(&TimelineEventItemId, &Aggregation)> {
+        let target = self.inverted_map.get(id)?;
+        let aggregation = self.related_events.get(target)?.iter().find(|agg| agg.own_id == *id)?;
+        Some((target, aggregation))
+    }
+
     /// Is the given id one for a known aggregation to another event?
     ///
     /// If so, unapplies it by replacing the corresponding related item, if
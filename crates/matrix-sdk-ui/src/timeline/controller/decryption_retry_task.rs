@@ -29,6 +29,7 @@ use matrix_sdk::{
     event_handler::EventHandlerHandle,
     executor::{JoinHandle, spawn},
 };
+use ruma::{OwnedUserId, UserId};
 use tokio::sync::{
     RwLock,
     mpsc::{self, Receiver, Sender},
@@ -53,6 +54,7 @@ pub(in crate::timeline) struct CryptoDropHandles {
     room_key_from_backups_join_handle: JoinHandle<()>,
     room_keys_received_join_handle: JoinHandle<()>,
     room_key_backup_enabled_join_handle: JoinHandle<()>,
+    identity_updates_join_handle: JoinHandle<()>,
     encryption_changes_handle: JoinHandle<()>,
 }
 
@@ -65,6 +67,7 @@ impl Drop for CryptoDropHandles {
         self.room_key_from_backups_join_handle.abort();
         self.room_keys_received_join_handle.abort();
         self.room_key_backup_enabled_join_handle.abort();
+        self.identity_updates_join_handle.abort();
         self.encryption_changes_handle.abort();
     }
 }
@@ -120,7 +123,7 @@ where
                 | BackupState::Creating
                 | BackupState::Resuming
                 | BackupState::Disabling
-                | BackupState::Downloading
+                | BackupState::Downloading(_)
                 | BackupState::Enabling,
             ) => (),
         }
@@ -165,6 +168,19 @@ async fn room_key_received_task<S>(
     }
 }
 
+/// The task that refreshes the encryption info of the events, when the
+/// identity or the devices of their senders change.
+async fn identity_updates_task<S>(user_ids_stream: S, timeline_controller: TimelineController)
+where
+    S: Stream<Item = BTreeSet<OwnedUserId>>,
+{
+    pin_mut!(user_ids_stream);
+
+    while let Some(user_ids) = user_ids_stream.next().await {
+        timeline_controller.refresh_encryption_info(&user_ids).await;
+    }
+}
+
 /// Spawn all the crypto-related tasks that are used to handle re-decryption of
 /// messages.
 pub(in crate::timeline) async fn spawn_crypto_tasks(
@@ -209,12 +225,37 @@ pub(in crate::timeline) async fn spawn_crypto_tasks(
         ))
     };
 
+    // The verification state of the events, and thus their shields, depend on the
+    // identities and devices of their senders.
+    let identity_updates_join_handle = {
+        let encryption = client.encryption();
+        let identities = encryption.user_identities_stream().await.expect(
+            "We should be logged in by now, so we should have access to an `OlmMachine` \
+                 to be able to listen to this stream",
+        );
+        let devices = encryption.devices_stream().await.expect(
+            "We should be logged in by now, so we should have access to an `OlmMachine` \
+                 to be able to listen to this stream",
+        );
+
+        let user_ids = identities
+            .map(|updates| {
+                updates.new.into_keys().chain(updates.changed.into_keys()).collect::<BTreeSet<_>>()
+            })
+            .merge(devices.map(|updates| {
+                updates.new.into_keys().chain(updates.changed.into_keys()).collect::<BTreeSet<_>>()
+            }));
+
+        spawn(identity_updates_task(user_ids, controller.clone()))
+    };
+
     CryptoDropHandles {
         client,
         event_handler_handles: event_handlers,
         room_key_from_backups_join_handle,
         room_keys_received_join_handle,
         room_key_backup_enabled_join_handle,
+        identity_updates_join_handle,
         encryption_changes_handle: spawn(async move {
             controller.handle_encryption_state_changes().await
         }),
@@ -402,6 +443,50 @@ pub(super) async fn retry_fetch_encryption_info<P: RoomDataProvider>(
     }
 }
 
+/// Re-fetch the [`EncryptionInfo`] of the events sent by the given users, and
+/// update the ones whose verification state changed.
+///
+/// If the own user is part of the given users, the events of all the senders
+/// are refreshed, since their verification state depends on the own identity.
+pub(super) async fn refresh_encryption_info_of_senders<P: RoomDataProvider>(
+    state: &mut TimelineState<P>,
+    user_ids: &BTreeSet<OwnedUserId>,
+    own_user_id: &UserId,
+    room_data_provider: &P,
+) {
+    let refresh_all = user_ids.contains(own_user_id);
+
+    let indices = state
+        .items
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, item)| {
+            let event = item.as_event()?;
+            let remote = event.as_remote()?;
+            let info = remote.encryption_info.as_ref()?;
+            (refresh_all || user_ids.contains(event.sender()))
+                .then(|| (idx, info.verification_state.clone()))
+        })
+        .collect::<Vec<_>>();
+
+    for (idx, previous_state) in indices {
+        let Some(new_item) = make_replacement_for(room_data_provider, state.items.get(idx)).await
+        else {
+            continue;
+        };
+
+        // Only update the items whose shield might have changed.
+        let new_state = new_item
+            .as_event()
+            .and_then(|event| event.as_remote()?.encryption_info.as_ref())
+            .map(|info| &info.verification_state);
+
+        if new_state != Some(&previous_state) {
+            state.items.replace(idx, new_item);
+        }
+    }
+}
+
 /// Create a replacement TimelineItem for the supplied one, with new
 /// [`EncryptionInfo`] from the supplied `room_data_provider`. Returns None if
 /// the supplied item is not a remote event, or if it doesn't have a session ID.
@@ -652,6 +737,8 @@ mod tests {
             original_json: None,
             latest_edit_json: None,
             origin: RemoteEventOrigin::Sync,
+            marked_utd_at: None,
+            time_to_decrypt: None,
         });
 
         TimelineItem::new(
@@ -702,6 +789,8 @@ mod tests {
             original_json: None,
             latest_edit_json: None,
             origin: RemoteEventOrigin::Sync,
+            marked_utd_at: None,
+            time_to_decrypt: None,
         });
 
         let content = RoomMessageEventContent::text_plain("hi");
@@ -12,7 +12,15 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{collections::BTreeSet, fmt, sync::Arc};
+use std::{
+    collections::BTreeSet,
+    fmt,
+    future::Future,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
 
 use as_variant::as_variant;
 use decryption_retry_task::DecryptionRetryTask;
@@ -32,16 +40,20 @@ use matrix_sdk::{
     },
 };
 use ruma::{
-    EventId, MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedTransactionId, TransactionId, UserId,
+    EventId, MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedTransactionId, OwnedUserId,
+    TransactionId, UserId,
     api::client::receipt::create_receipt::v3::ReceiptType as SendReceiptType,
     events::{
         AnyMessageLikeEventContent, AnySyncEphemeralRoomEvent, AnySyncMessageLikeEvent,
-        AnySyncTimelineEvent, MessageLikeEventType,
+        AnySyncStateEvent, AnySyncTimelineEvent, MessageLikeEventType, SyncStateEvent,
         poll::unstable_start::UnstablePollStartEventContent,
         reaction::ReactionEventContent,
         receipt::{Receipt, ReceiptThread, ReceiptType},
         relation::Annotation,
-        room::message::{MessageType, Relation},
+        room::{
+            member::MembershipState,
+            message::{MessageType, Relation},
+        },
     },
     room_version_rules::RoomVersionRules,
     serde::Raw,
@@ -61,9 +73,10 @@ pub(super) use self::{
     state_transaction::TimelineStateTransaction,
 };
 use super::{
-    DateDividerMode, EmbeddedEvent, Error, EventSendState, EventTimelineItem, InReplyToDetails,
-    PaginationError, Profile, TimelineDetails, TimelineEventItemId, TimelineFocus, TimelineItem,
-    TimelineItemContent, TimelineItemKind, VirtualTimelineItem,
+    EmbeddedEvent, Error, EventSendState, EventTimelineItem, FocusedPaginationStatus,
+    InReplyToDetails, LocalActionError, PaginationError, Profile, TimelineConfig, TimelineDetails,
+    TimelineEventItemId, TimelineFocus, TimelineItem, TimelineItemContent, TimelineItemKind,
+    VirtualTimelineItem,
     algorithms::{rfind_event_by_id, rfind_event_item},
     event_item::{ReactionStatus, RemoteEventOrigin},
     item::TimelineUniqueId,
@@ -76,7 +89,7 @@ use crate::{
         algorithms::rfind_event_by_item_id,
         date_dividers::DateDividerAdjuster,
         event_item::TimelineItemHandle,
-        pinned_events_loader::{PinnedEventsLoader, PinnedEventsLoaderError},
+        pinned_events_loader::{PinnedEventsLoader, PinnedEventsLoaderError, pinned_events_diffs},
     },
     unable_to_decrypt_hook::UtdHookManager,
 };
@@ -90,6 +103,7 @@ mod state;
 mod state_transaction;
 
 pub(super) use aggregations::*;
+use decryption_retry_task::refresh_encryption_info_of_senders;
 pub(super) use decryption_retry_task::{CryptoDropHandles, spawn_crypto_tasks};
 
 /// Data associated to the current timeline focus.
@@ -113,6 +127,12 @@ pub(in crate::timeline) enum TimelineFocusKind<P: RoomDataProvider> {
 
         /// Whether to hide in-thread events from the timeline.
         hide_threaded_events: bool,
+
+        /// Whether forward pagination has reached the live edge of the room.
+        ///
+        /// Once it's happened, the timeline receives the new events from the
+        /// sync, like a live timeline.
+        joined_live: AtomicBool,
     },
 
     /// A live timeline for a thread.
@@ -149,6 +169,15 @@ impl<P: RoomDataProvider> TimelineFocusKind<P> {
             TimelineFocusKind::PinnedEvents { .. } => ReceiptThread::Unthreaded,
         }
     }
+
+    /// Whether this is an event-focused timeline that has been paginated
+    /// forwards up to the live edge of the room.
+    pub(super) fn has_joined_live(&self) -> bool {
+        match self {
+            TimelineFocusKind::Event { joined_live, .. } => joined_live.load(Ordering::SeqCst),
+            _ => false,
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -179,13 +208,16 @@ pub(super) struct TimelineSettings {
 
     /// Event filter that controls what's rendered as a timeline item (and thus
     /// what can carry read receipts).
+    ///
+    /// This is only the initial filter; it can be replaced later with
+    /// [`TimelineController::set_event_filter`].
     pub(super) event_filter: Arc<TimelineEventFilterFn>,
 
     /// Are unparsable events added as timeline items of their own kind?
     pub(super) add_failed_to_parse: bool,
 
-    /// Should the timeline items be grouped by day or month?
-    pub(super) date_divider_mode: DateDividerMode,
+    /// Which virtual items are inserted in the timeline, and how.
+    pub(super) virtual_items: TimelineConfig,
 }
 
 #[cfg(not(tarpaulin_include))]
@@ -194,6 +226,7 @@ impl fmt::Debug for TimelineSettings {
         f.debug_struct("TimelineSettings")
             .field("track_read_receipts", &self.track_read_receipts)
             .field("add_failed_to_parse", &self.add_failed_to_parse)
+            .field("virtual_items", &self.virtual_items)
             .finish_non_exhaustive()
     }
 }
@@ -204,7 +237,7 @@ impl Default for TimelineSettings {
             track_read_receipts: false,
             event_filter: Arc::new(default_event_filter),
             add_failed_to_parse: true,
-            date_divider_mode: DateDividerMode::Daily,
+            virtual_items: TimelineConfig::default(),
         }
     }
 }
@@ -290,6 +323,122 @@ pub fn default_event_filter(event: &AnySyncTimelineEvent, rules: &RoomVersionRul
     }
 }
 
+/// A configurable version of [`default_event_filter`].
+///
+/// By default, it lets the same events through as [`default_event_filter`].
+/// It can be used with [`crate::timeline::TimelineBuilder::event_filter`] or
+/// [`crate::timeline::Timeline::set_event_filter`]:
+///
+/// ```
+/// # use matrix_sdk_ui::timeline::DefaultEventFilter;
+/// let filter = DefaultEventFilter::new().show_profile_changes(false);
+/// let event_filter = move |event: &_, rules: &_| filter.filter(event, rules);
+/// ```
+#[derive(Clone, Debug)]
+pub struct DefaultEventFilter {
+    /// Show the changes of the display names and avatars of the room members.
+    pub show_profile_changes: bool,
+
+    /// Show the events that have been redacted.
+    pub show_redacted: bool,
+
+    /// The custom `msgtype`s of the `m.room.message` events to show, in
+    /// addition to the ones from the spec.
+    pub allowed_custom_types: BTreeSet<String>,
+}
+
+impl Default for DefaultEventFilter {
+    fn default() -> Self {
+        Self {
+            show_profile_changes: true,
+            show_redacted: true,
+            allowed_custom_types: BTreeSet::new(),
+        }
+    }
+}
+
+impl DefaultEventFilter {
+    /// Create a filter letting the same events through as
+    /// [`default_event_filter`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether to show the changes of the display names and avatars of the
+    /// room members.
+    pub fn show_profile_changes(mut self, show: bool) -> Self {
+        self.show_profile_changes = show;
+        self
+    }
+
+    /// Whether to show the events that have been redacted.
+    pub fn show_redacted(mut self, show: bool) -> Self {
+        self.show_redacted = show;
+        self
+    }
+
+    /// Show the `m.room.message` events with the given custom `msgtype`.
+    pub fn allow_custom_type(mut self, msgtype: impl Into<String>) -> Self {
+        self.allowed_custom_types.insert(msgtype.into());
+        self
+    }
+
+    /// Whether the event should be added to the timeline.
+    pub fn filter(&self, event: &AnySyncTimelineEvent, rules: &RoomVersionRules) -> bool {
+        if !self.show_redacted && is_redacted(event) {
+            return false;
+        }
+
+        if !self.show_profile_changes && is_profile_change(event) {
+            return false;
+        }
+
+        default_event_filter(event, rules) || self.is_allowed_custom_message(event)
+    }
+
+    /// Whether the event is a message with one of the allowed custom
+    /// `msgtype`s, that isn't an edit.
+    fn is_allowed_custom_message(&self, event: &AnySyncTimelineEvent) -> bool {
+        let AnySyncTimelineEvent::MessageLike(msg) = event else {
+            return false;
+        };
+
+        match msg.original_content() {
+            Some(AnyMessageLikeEventContent::RoomMessage(content)) => {
+                !matches!(content.relates_to, Some(Relation::Replacement(_)))
+                    && self.allowed_custom_types.contains(content.msgtype.msgtype())
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Whether the event has been redacted.
+fn is_redacted(event: &AnySyncTimelineEvent) -> bool {
+    match event {
+        AnySyncTimelineEvent::MessageLike(msg) => msg.is_redacted(),
+        AnySyncTimelineEvent::State(state) => state.is_redacted(),
+    }
+}
+
+/// Whether the event is a change of the display name or avatar of a room
+/// member, i.e. a member event that doesn't change the membership.
+fn is_profile_change(event: &AnySyncTimelineEvent) -> bool {
+    let AnySyncTimelineEvent::State(AnySyncStateEvent::RoomMember(SyncStateEvent::Original(
+        member,
+    ))) = event
+    else {
+        return false;
+    };
+
+    member.content.membership == MembershipState::Join
+        && member
+            .unsigned
+            .prev_content
+            .as_ref()
+            .is_some_and(|prev| prev.membership == MembershipState::Join)
+}
+
 impl<P: RoomDataProvider, D: Decryptor> TimelineController<P, D> {
     pub(super) fn new(
         room_data_provider: P,
@@ -306,7 +455,11 @@ impl<P: RoomDataProvider, D: Decryptor> TimelineController<P, D> {
 
             TimelineFocus::Event { hide_threaded_events, .. } => {
                 let paginator = Paginator::new(room_data_provider.clone());
-                TimelineFocusKind::Event { paginator, hide_threaded_events }
+                TimelineFocusKind::Event {
+                    paginator,
+                    hide_threaded_events,
+                    joined_live: AtomicBool::new(false),
+                }
             }
 
             TimelineFocus::Thread { root_event_id, .. } => {
@@ -332,6 +485,7 @@ impl<P: RoomDataProvider, D: Decryptor> TimelineController<P, D> {
             internal_id_prefix,
             unable_to_decrypt_hook,
             is_room_encrypted,
+            settings.event_filter.clone(),
         )));
 
         let decryption_retry_task =
@@ -591,6 +745,136 @@ impl<P: RoomDataProvider, D: Decryptor> TimelineController<P, D> {
         matches!(&*self.focus, TimelineFocusKind::Live { .. })
     }
 
+    /// Has this event-focused timeline been paginated forwards up to the live
+    /// edge of the room, so that it now receives events from sync?
+    pub(super) fn has_joined_live(&self) -> bool {
+        self.focus.has_joined_live()
+    }
+
+    /// Get the pagination status of an event-focused timeline, or `None` for
+    /// the other kinds of timelines.
+    pub(super) fn focused_pagination_status(&self) -> Option<FocusedPaginationStatus> {
+        let TimelineFocusKind::Event { paginator, joined_live, .. } = &*self.focus else {
+            return None;
+        };
+
+        let is_live = joined_live.load(Ordering::SeqCst);
+
+        Some(FocusedPaginationStatus {
+            hit_timeline_start: paginator.hit_timeline_start(),
+            hit_timeline_end: is_live || paginator.hit_timeline_end(),
+            is_live,
+        })
+    }
+
+    /// Make an event-focused timeline receive the events from sync, after a
+    /// forwards pagination reached the live edge of the room.
+    ///
+    /// `cache_events` are the events of the room event cache: the ones that
+    /// follow the last event of the timeline are appended to it, so that the
+    /// events received from sync while paginating aren't lost.
+    pub(super) async fn join_live(&self, cache_events: Vec<TimelineEvent>) {
+        let TimelineFocusKind::Event { joined_live, .. } = &*self.focus else {
+            return;
+        };
+
+        // Set the flag before reading the state, so that an event received from sync
+        // in the meantime is either part of `cache_events`, or handled by the live
+        // updates; duplicates are then filtered out.
+        if joined_live.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let mut state = self.state.write().await;
+
+        let last_event_id =
+            state.items.all_remote_events().last().map(|meta| meta.event_id.clone());
+        let Some(position) = last_event_id.and_then(|last_event_id| {
+            cache_events.iter().position(|event| event.event_id().as_ref() == Some(&last_event_id))
+        }) else {
+            // The event cache doesn't overlap with the timeline; the next events from sync
+            // will be appended as they come.
+            return;
+        };
+
+        let new_events = cache_events.into_iter().skip(position + 1).collect();
+        Self::append_new_remote_events(
+            &mut state,
+            new_events,
+            RemoteEventOrigin::Cache,
+            &self.room_data_provider,
+            &self.settings,
+        )
+        .await;
+    }
+
+    /// Handle the updates from the event cache for an event-focused timeline
+    /// that has joined the live edge of the room.
+    ///
+    /// The event cache's positions don't match the timeline's, so only the
+    /// events appended at the end are added to the timeline, if they're not
+    /// there already; other updates are only used for aggregations.
+    pub(super) async fn handle_live_edge_diffs(
+        &self,
+        diffs: Vec<VectorDiff<TimelineEvent>>,
+        origin: RemoteEventOrigin,
+    ) {
+        let mut appended = Vec::new();
+        let mut others = Vec::new();
+
+        for diff in diffs {
+            match diff {
+                VectorDiff::Append { values } => appended.extend(values),
+                VectorDiff::PushBack { value } => appended.push(value),
+                diff => others.push(diff),
+            }
+        }
+
+        let mut state = self.state.write().await;
+
+        state
+            .handle_remote_aggregations(others, origin, &self.room_data_provider, &self.settings)
+            .await;
+
+        Self::append_new_remote_events(
+            &mut state,
+            appended,
+            origin,
+            &self.room_data_provider,
+            &self.settings,
+        )
+        .await;
+    }
+
+    /// Append the given events at the end of the timeline, skipping the ones
+    /// that are already in it.
+    async fn append_new_remote_events(
+        state: &mut TimelineState<P>,
+        mut events: Vec<TimelineEvent>,
+        origin: RemoteEventOrigin,
+        room_data_provider: &P,
+        settings: &TimelineSettings,
+    ) {
+        events.retain(|event| {
+            event.event_id().is_none_or(|event_id| {
+                state.items.all_remote_events().get_by_event_id(&event_id).is_none()
+            })
+        });
+
+        if events.is_empty() {
+            return;
+        }
+
+        state
+            .handle_remote_events_with_diffs(
+                vec![VectorDiff::Append { values: events.into() }],
+                origin,
+                room_data_provider,
+                settings,
+            )
+            .await;
+    }
+
     /// Is this timeline focused on a thread?
     pub(super) fn is_threaded(&self) -> bool {
         matches!(&*self.focus, TimelineFocusKind::Thread { .. })
@@ -773,6 +1057,130 @@ impl<P: RoomDataProvider, D: Decryptor> TimelineController<P, D> {
         Ok(false)
     }
 
+    /// Replace an item with a redacted placeholder, while the redaction of its
+    /// event is being sent.
+    ///
+    /// Returns the original item, to restore it if the redaction fails with
+    /// [`Self::finish_pending_redaction`], or `None` if the item couldn't be
+    /// found.
+    #[instrument(skip(self))]
+    pub(super) async fn mark_redaction_pending(
+        &self,
+        item_id: &TimelineEventItemId,
+    ) -> Option<EventTimelineItem> {
+        let mut state = self.state.write().await;
+
+        let Some((item_pos, item)) = rfind_event_by_item_id(&state.items, item_id) else {
+            warn!("Timeline item not found, can't redact it locally");
+            return None;
+        };
+
+        let original = item.inner.clone();
+        let redacted = item
+            .redact(&state.meta.room_version_rules.redaction)
+            .with_redaction_pending(true)
+            .with_local_action_error(None);
+        let new_item = TimelineItem::new(redacted, item.internal_id.to_owned());
+        state.items.replace(item_pos, new_item);
+
+        Some(original)
+    }
+
+    /// Update an item whose redaction was pending, after the redaction has
+    /// been sent.
+    ///
+    /// If the redaction failed, the `original` item is restored, with the
+    /// error surfaced as a [`LocalActionError::Redaction`].
+    #[instrument(skip(self, original))]
+    pub(super) async fn finish_pending_redaction(
+        &self,
+        item_id: &TimelineEventItemId,
+        original: EventTimelineItem,
+        result: Result<(), Arc<matrix_sdk::Error>>,
+    ) {
+        let mut state = self.state.write().await;
+
+        let Some((item_pos, item)) = rfind_event_by_item_id(&state.items, item_id) else {
+            warn!("Timeline item not found after sending a redaction");
+            return;
+        };
+
+        // The redaction may have been received from the server in the meantime, in
+        // which case the item is already up to date.
+        if !item.is_redaction_pending() {
+            return;
+        }
+
+        let new_item = match result {
+            Ok(()) => item.with_redaction_pending(false),
+            Err(error) => {
+                debug!("sending redaction failed, restoring the item");
+                original.with_local_action_error(Some(LocalActionError::Redaction { error }))
+            }
+        };
+        let new_item = TimelineItem::new(new_item, item.internal_id.to_owned());
+        state.items.replace(item_pos, new_item);
+    }
+
+    /// Roll back a local reaction that couldn't be sent, and surface the error
+    /// on the item it was reacting to.
+    ///
+    /// Returns false if the transaction id isn't the one of a local reaction.
+    #[instrument(skip(self, error))]
+    async fn roll_back_failed_reaction(
+        &self,
+        txn_id: &TransactionId,
+        error: Arc<matrix_sdk::Error>,
+    ) -> bool {
+        let reaction_id = TimelineEventItemId::TransactionId(txn_id.to_owned());
+
+        let mut state = self.state.write().await;
+        let mut tr = state.transaction();
+
+        let Some((target, aggregation)) = tr.meta.aggregations.get(&reaction_id) else {
+            return false;
+        };
+        let AggregationKind::Reaction { key, reaction_status, .. } = &aggregation.kind else {
+            return false;
+        };
+
+        let target = target.clone();
+        let key = key.clone();
+        let reaction_status = reaction_status.clone();
+
+        if let Err(err) = tr.meta.aggregations.try_remove_aggregation(&reaction_id, &mut tr.items) {
+            warn!("error when rolling back a local reaction: {err}");
+        }
+
+        if let Some((item_pos, item)) = rfind_event_by_item_id(&tr.items, &target) {
+            let new_item =
+                item.with_local_action_error(Some(LocalActionError::Reaction { key, error }));
+            let new_item = TimelineItem::new(new_item, item.internal_id.to_owned());
+            tr.items.replace(item_pos, new_item);
+        } else {
+            warn!("couldn't find the item a failed reaction was reacting to");
+        }
+
+        tr.commit();
+
+        // Release the lock before aborting the request, since the send queue will
+        // notify us about it.
+        drop(state);
+
+        // The reaction has been rolled back, make sure it's not sent later.
+        let aborted = match reaction_status {
+            ReactionStatus::LocalToLocal(Some(handle)) => handle.abort().await,
+            ReactionStatus::LocalToRemote(Some(handle)) => handle.abort().await,
+            _ => Ok(true),
+        };
+
+        if let Err(err) = aborted {
+            warn!("couldn't abort sending a failed reaction: {err}");
+        }
+
+        true
+    }
+
     /// Handle updates on events as [`VectorDiff`]s.
     pub(super) async fn handle_remote_events_with_diffs(
         &self,
@@ -794,6 +1202,28 @@ impl<P: RoomDataProvider, D: Decryptor> TimelineController<P, D> {
             .await
     }
 
+    /// Update the remote events of a pinned events timeline with the newly
+    /// loaded pinned events.
+    ///
+    /// Only the items of the events that have been pinned or unpinned are
+    /// added or removed.
+    pub(super) async fn update_pinned_events(&self, events: Vec<TimelineEvent>) {
+        let mut state = self.state.write().await;
+
+        let known_event_ids: Vec<_> =
+            state.items.all_remote_events().iter().map(|meta| meta.event_id.clone()).collect();
+        let diffs = pinned_events_diffs(&known_event_ids, events);
+
+        state
+            .handle_remote_events_with_diffs(
+                diffs,
+                RemoteEventOrigin::Pagination,
+                &self.room_data_provider,
+                &self.settings,
+            )
+            .await
+    }
+
     /// Only handle aggregations received as [`VectorDiff`]s.
     pub(super) async fn handle_remote_aggregations(
         &self,
@@ -814,6 +1244,25 @@ impl<P: RoomDataProvider, D: Decryptor> TimelineController<P, D> {
         self.state.write().await.clear();
     }
 
+    /// Replace the event filter, and add or remove the timeline items of the
+    /// known events accordingly.
+    ///
+    /// `load_event` is used to get the events that are known by the timeline,
+    /// since they're not kept in memory.
+    pub(super) async fn set_event_filter<Fut>(
+        &self,
+        event_filter: Arc<TimelineEventFilterFn>,
+        load_event: impl Fn(OwnedEventId) -> Fut,
+    ) where
+        Fut: Future<Output = Option<TimelineEvent>>,
+    {
+        self.state
+            .write()
+            .await
+            .set_event_filter(event_filter, load_event, &self.room_data_provider, &self.settings)
+            .await;
+    }
+
     /// Replaces the content of the current timeline with initial events.
     ///
     /// Also sets up read receipts and the read marker for a live timeline of a
@@ -856,6 +1305,7 @@ impl<P: RoomDataProvider, D: Decryptor> TimelineController<P, D> {
         }
 
         if track_read_markers
+            && self.settings.virtual_items.show_read_marker
             && let Some(fully_read_event_id) =
                 self.room_data_provider.load_fully_read_marker().await
         {
@@ -864,6 +1314,11 @@ impl<P: RoomDataProvider, D: Decryptor> TimelineController<P, D> {
     }
 
     pub(super) async fn handle_fully_read_marker(&self, fully_read_event_id: OwnedEventId) {
+        if !self.settings.virtual_items.show_read_marker {
+            // Without a fully-read event, no read marker item is ever inserted.
+            return;
+        }
+
         self.state.write().await.handle_fully_read_marker(fully_read_event_id);
     }
 
@@ -886,11 +1341,11 @@ impl<P: RoomDataProvider, D: Decryptor> TimelineController<P, D> {
         let sender = self.room_data_provider.own_user_id().to_owned();
         let profile = self.room_data_provider.profile_from_user_id(&sender).await;
 
-        let date_divider_mode = self.settings.date_divider_mode.clone();
+        let virtual_items = self.settings.virtual_items.clone();
 
         let mut state = self.state.write().await;
         state
-            .handle_local_event(sender, profile, date_divider_mode, txn_id, send_handle, content)
+            .handle_local_event(sender, profile, virtual_items, txn_id, send_handle, content)
             .await;
     }
 
@@ -930,8 +1385,7 @@ impl<P: RoomDataProvider, D: Decryptor> TimelineController<P, D> {
                 txn.items.remove(idx);
 
                 // Adjust the date dividers, if needs be.
-                let mut adjuster =
-                    DateDividerAdjuster::new(self.settings.date_divider_mode.clone());
+                let mut adjuster = DateDividerAdjuster::new(self.settings.virtual_items.clone());
                 adjuster.run(&mut txn.items, &mut txn.meta);
             }
 
@@ -1006,7 +1460,7 @@ impl<P: RoomDataProvider, D: Decryptor> TimelineController<P, D> {
 
             // A read marker or a date divider may have been inserted before the local echo.
             // Ensure both are up to date.
-            let mut adjuster = DateDividerAdjuster::new(self.settings.date_divider_mode.clone());
+            let mut adjuster = DateDividerAdjuster::new(self.settings.virtual_items.clone());
             adjuster.run(&mut txn.items, &mut txn.meta);
 
             txn.meta.update_read_marker(&mut txn.items);
@@ -1350,6 +1804,11 @@ impl<P: RoomDataProvider, D: Decryptor> TimelineController<P, D> {
             }
 
             RoomSendQueueUpdate::SendError { transaction_id, error, is_recoverable } => {
+                // Reactions aren't standalone items; if sending one failed, roll it back.
+                if self.roll_back_failed_reaction(&transaction_id, error.clone()).await {
+                    return;
+                }
+
                 self.update_event_send_state(
                     &transaction_id,
                     EventSendState::SendingFailed { error, is_recoverable },
@@ -1366,6 +1825,14 @@ impl<P: RoomDataProvider, D: Decryptor> TimelineController<P, D> {
                     .await;
             }
 
+            RoomSendQueueUpdate::MediaUpload { related_to, progress } => {
+                self.update_event_send_state(
+                    &related_to,
+                    EventSendState::Sending { progress: Some(progress) },
+                )
+                .await;
+            }
+
             RoomSendQueueUpdate::UploadedMedia { related_to, .. } => {
                 // TODO(bnjbvr): Do something else?
                 info!(txn_id = %related_to, "some media for a media event has been uploaded");
@@ -1594,6 +2061,24 @@ impl TimelineController {
         self.retry_event_decryption_inner(self.room().clone(), session_ids).await
     }
 
+    /// Re-fetch the encryption info of the events sent by the given users,
+    /// after their identity or devices changed, so that their shields are
+    /// up to date.
+    pub(super) async fn refresh_encryption_info(&self, user_ids: &BTreeSet<OwnedUserId>) {
+        if user_ids.is_empty() {
+            return;
+        }
+
+        let mut state = self.state.write().await;
+        refresh_encryption_info_of_senders(
+            &mut state,
+            user_ids,
+            self.room_data_provider.own_user_id(),
+            &self.room_data_provider,
+        )
+        .await;
+    }
+
     /// Combine the global (event cache) pagination status with the local state
     /// of the timeline.
     ///
@@ -756,6 +756,8 @@ mod observable_items_tests {
                     original_json: None,
                     latest_edit_json: None,
                     origin: RemoteEventOrigin::Sync,
+                    marked_utd_at: None,
+                    time_to_decrypt: None,
                 }),
                 false,
             ),
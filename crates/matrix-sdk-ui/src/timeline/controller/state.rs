@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{future::Future, sync::Arc};
+use std::{fmt, future::Future, sync::Arc};
 
 use eyeball_im::VectorDiff;
 use matrix_sdk::{deserialized_responses::TimelineEvent, send_queue::SendHandle};
@@ -36,18 +36,37 @@ use super::{
         event_item::RemoteEventOrigin,
         traits::RoomDataProvider,
     },
-    DateDividerMode, TimelineMetadata, TimelineSettings, TimelineStateTransaction,
+    TimelineConfig, TimelineMetadata, TimelineSettings, TimelineStateTransaction,
     observable_items::ObservableItems,
 };
-use crate::{timeline::controller::TimelineFocusKind, unable_to_decrypt_hook::UtdHookManager};
+use crate::{
+    timeline::{TimelineEventFilterFn, controller::TimelineFocusKind},
+    unable_to_decrypt_hook::UtdHookManager,
+};
 
-#[derive(Debug)]
 pub(in crate::timeline) struct TimelineState<P: RoomDataProvider> {
     pub items: ObservableItems,
     pub meta: TimelineMetadata,
 
     /// The kind of focus of this timeline.
     pub(super) focus: Arc<TimelineFocusKind<P>>,
+
+    /// The current event filter, controlling which events get a timeline item.
+    event_filter: Arc<TimelineEventFilterFn>,
+}
+
+#[cfg(not(tarpaulin_include))]
+impl<P: RoomDataProvider> fmt::Debug for TimelineState<P>
+where
+    TimelineFocusKind<P>: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TimelineState")
+            .field("items", &self.items)
+            .field("meta", &self.meta)
+            .field("focus", &self.focus)
+            .finish_non_exhaustive()
+    }
 }
 
 impl<P: RoomDataProvider> TimelineState<P> {
@@ -58,6 +77,7 @@ impl<P: RoomDataProvider> TimelineState<P> {
         internal_id_prefix: Option<String>,
         unable_to_decrypt_hook: Option<Arc<UtdHookManager>>,
         is_room_encrypted: bool,
+        event_filter: Arc<TimelineEventFilterFn>,
     ) -> Self {
         Self {
             items: ObservableItems::new(),
@@ -69,6 +89,7 @@ impl<P: RoomDataProvider> TimelineState<P> {
                 is_room_encrypted,
             ),
             focus,
+            event_filter,
         }
     }
 
@@ -151,14 +172,14 @@ impl<P: RoomDataProvider> TimelineState<P> {
         &mut self,
         own_user_id: OwnedUserId,
         own_profile: Option<Profile>,
-        date_divider_mode: DateDividerMode,
+        virtual_items: TimelineConfig,
         txn_id: OwnedTransactionId,
         send_handle: Option<SendHandle>,
         content: AnyMessageLikeEventContent,
     ) {
         let mut txn = self.transaction();
 
-        let mut date_divider_adjuster = DateDividerAdjuster::new(date_divider_mode);
+        let mut date_divider_adjuster = DateDividerAdjuster::new(virtual_items);
 
         let is_thread_focus = matches!(txn.focus, TimelineFocusKind::Thread { .. });
         let (in_reply_to, thread_root) =
@@ -172,6 +193,12 @@ impl<P: RoomDataProvider> TimelineState<P> {
             TimelineFocusKind::Thread { root_event_id, .. } => {
                 thread_root.as_ref().is_some_and(|r| r == root_event_id)
             }
+            TimelineFocusKind::Event { hide_threaded_events, .. }
+                if txn.focus.has_joined_live() =>
+            {
+                // The timeline has reached the live edge, so it shows the new events.
+                thread_root.is_none() || !hide_threaded_events
+            }
             TimelineFocusKind::Event { .. } | TimelineFocusKind::PinnedEvents { .. } => {
                 // Don't add new items to these timelines; aggregations are added independently
                 // of the `should_add_new_items` value.
@@ -213,8 +240,7 @@ impl<P: RoomDataProvider> TimelineState<P> {
     {
         let mut txn = self.transaction();
 
-        let mut date_divider_adjuster =
-            DateDividerAdjuster::new(settings.date_divider_mode.clone());
+        let mut date_divider_adjuster = DateDividerAdjuster::new(settings.virtual_items.clone());
 
         // Loop through all the indices, in order so we don't decrypt edits
         // before the event being edited, if both were UTD. Keep track of
@@ -300,7 +326,30 @@ impl<P: RoomDataProvider> TimelineState<P> {
         txn.commit();
     }
 
+    /// Replace the event filter, and add or remove the timeline items of the
+    /// known events accordingly.
+    pub(super) async fn set_event_filter<Fut>(
+        &mut self,
+        event_filter: Arc<TimelineEventFilterFn>,
+        load_event: impl Fn(OwnedEventId) -> Fut,
+        room_data_provider: &P,
+        settings: &TimelineSettings,
+    ) where
+        Fut: Future<Output = Option<TimelineEvent>>,
+    {
+        self.event_filter = event_filter;
+
+        let mut txn = self.transaction();
+        txn.apply_event_filter(load_event, room_data_provider, settings).await;
+        txn.commit();
+    }
+
     pub(super) fn transaction(&mut self) -> TimelineStateTransaction<'_, P> {
-        TimelineStateTransaction::new(&mut self.items, &mut self.meta, &*self.focus)
+        TimelineStateTransaction::new(
+            &mut self.items,
+            &mut self.meta,
+            &*self.focus,
+            &*self.event_filter,
+        )
     }
 }
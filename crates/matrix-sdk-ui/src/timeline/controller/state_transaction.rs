@@ -12,13 +12,17 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+};
 
 use eyeball_im::VectorDiff;
 use itertools::Itertools as _;
 use matrix_sdk::deserialized_responses::{
     ThreadSummaryStatus, TimelineEvent, TimelineEventKind, UnsignedEventLocation,
 };
+use matrix_sdk_common::serde_helpers::extract_thread_root;
 use ruma::{
     EventId, MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedTransactionId, OwnedUserId, UserId,
     events::AnySyncTimelineEvent, push::Action, serde::Raw,
@@ -37,7 +41,8 @@ use super::{
     metadata::EventMeta,
 };
 use crate::timeline::{
-    EmbeddedEvent, ThreadSummary, TimelineDetails, VirtualTimelineItem,
+    EmbeddedEvent, ThreadSummary, TimelineDetails, TimelineEventFilterFn, TimelineEventItemId,
+    VirtualTimelineItem,
     controller::TimelineFocusKind,
     event_handler::{FailedToParseEvent, RemovedItem, TimelineAction},
 };
@@ -60,6 +65,9 @@ pub(in crate::timeline) struct TimelineStateTransaction<'a, P: RoomDataProvider>
 
     /// The kind of focus of this timeline.
     pub focus: &'a TimelineFocusKind<P>,
+
+    /// The event filter, controlling which events get a timeline item.
+    event_filter: &'a TimelineEventFilterFn,
 }
 
 impl<'a, P: RoomDataProvider> TimelineStateTransaction<'a, P> {
@@ -68,6 +76,7 @@ impl<'a, P: RoomDataProvider> TimelineStateTransaction<'a, P> {
         items: &'a mut ObservableItems,
         meta: &'a mut TimelineMetadata,
         focus: &'a TimelineFocusKind<P>,
+        event_filter: &'a TimelineEventFilterFn,
     ) -> Self {
         let previous_meta = meta;
         let meta = previous_meta.clone();
@@ -79,6 +88,7 @@ impl<'a, P: RoomDataProvider> TimelineStateTransaction<'a, P> {
             previous_meta,
             meta,
             focus,
+            event_filter,
         }
     }
 
@@ -90,8 +100,7 @@ impl<'a, P: RoomDataProvider> TimelineStateTransaction<'a, P> {
         room_data_provider: &P,
         settings: &TimelineSettings,
     ) {
-        let mut date_divider_adjuster =
-            DateDividerAdjuster::new(settings.date_divider_mode.clone());
+        let mut date_divider_adjuster = DateDividerAdjuster::new(settings.virtual_items.clone());
 
         for diff in diffs {
             match diff {
@@ -253,8 +262,7 @@ impl<'a, P: RoomDataProvider> TimelineStateTransaction<'a, P> {
         room_data_provider: &P,
         settings: &TimelineSettings,
     ) {
-        let mut date_divider_adjuster =
-            DateDividerAdjuster::new(settings.date_divider_mode.clone());
+        let mut date_divider_adjuster = DateDividerAdjuster::new(settings.virtual_items.clone());
 
         for diff in diffs {
             match diff {
@@ -393,14 +401,13 @@ impl<'a, P: RoomDataProvider> TimelineStateTransaction<'a, P> {
     fn should_add_event_item(
         &self,
         room_data_provider: &P,
-        settings: &TimelineSettings,
         event: &AnySyncTimelineEvent,
         thread_root: Option<&EventId>,
         position: TimelineItemPosition,
     ) -> bool {
         let rules = room_data_provider.room_version_rules();
 
-        if !(settings.event_filter)(event, &rules) {
+        if !(self.event_filter)(event, &rules) {
             // The user filtered out the event.
             return false;
         }
@@ -432,8 +439,10 @@ impl<'a, P: RoomDataProvider> TimelineStateTransaction<'a, P> {
                 };
 
                 match origin {
-                    // Never add any item to a focused timeline when the item comes from sync.
-                    RemoteEventOrigin::Sync | RemoteEventOrigin::Unknown => false,
+                    // Only add the items coming from sync once the timeline has reached the live
+                    // edge of the room.
+                    RemoteEventOrigin::Sync => self.focus.has_joined_live(),
+                    RemoteEventOrigin::Unknown => false,
                     RemoteEventOrigin::Cache | RemoteEventOrigin::Pagination => true,
                 }
             }
@@ -633,7 +642,6 @@ impl<'a, P: RoomDataProvider> TimelineStateTransaction<'a, P> {
 
                 let should_add = self.should_add_event_item(
                     room_data_provider,
-                    settings,
                     &event,
                     thread_root.as_deref(),
                     position,
@@ -736,6 +744,99 @@ impl<'a, P: RoomDataProvider> TimelineStateTransaction<'a, P> {
         item_removed
     }
 
+    /// Evaluate the event filter again for all the remote events, after it
+    /// changed, and add or remove their timeline items accordingly.
+    ///
+    /// The events that keep their visibility are left untouched.
+    pub(super) async fn apply_event_filter<Fut>(
+        &mut self,
+        load_event: impl Fn(OwnedEventId) -> Fut,
+        room_data_provider: &P,
+        settings: &TimelineSettings,
+    ) where
+        Fut: Future<Output = Option<TimelineEvent>>,
+    {
+        let mut date_divider_adjuster = DateDividerAdjuster::new(settings.virtual_items.clone());
+
+        // Events are only added or removed at the same index, so the indices of the
+        // next events don't change.
+        for event_index in 0..self.items.all_remote_events().len() {
+            let Some(event_meta) = self.items.all_remote_events().get(event_index) else {
+                break;
+            };
+            let event_id = event_meta.event_id.clone();
+            let was_visible = event_meta.visible;
+            let timeline_item_index = event_meta.timeline_item_index;
+
+            let Some(event) = load_event(event_id.clone()).await else {
+                warn!(?event_id, "Couldn't load an event to filter it again");
+                continue;
+            };
+
+            let Ok(deserialized) = event.raw().deserialize() else {
+                // Events that failed to deserialize aren't affected by the filter.
+                continue;
+            };
+
+            let thread_root = extract_thread_root(event.raw());
+            let position =
+                TimelineItemPosition::At { event_index, origin: RemoteEventOrigin::Cache };
+            let should_add = self.should_add_event_item(
+                room_data_provider,
+                &deserialized,
+                thread_root.as_deref(),
+                position,
+            );
+
+            if should_add == was_visible {
+                continue;
+            }
+
+            if !should_add {
+                trace!(?event_id, "Hiding an event filtered out by the new filter");
+
+                if let Some(event_meta) = self.items.get_remote_event_by_event_id_mut(&event_id) {
+                    event_meta.visible = false;
+                }
+
+                if let Some(timeline_item_index) = timeline_item_index {
+                    self.items.remove(timeline_item_index);
+                    date_divider_adjuster.mark_used();
+                }
+
+                if settings.track_read_receipts {
+                    self.maybe_update_read_receipts_of_prev_event(&event_id);
+                }
+            } else if self
+                .meta
+                .aggregations
+                .is_aggregation(&TimelineEventItemId::EventId(event_id.clone()))
+            {
+                // Aggregations have already been applied to their target, they never get an
+                // item of their own.
+                if let Some(event_meta) = self.items.get_remote_event_by_event_id_mut(&event_id) {
+                    event_meta.visible = true;
+                }
+            } else {
+                trace!(?event_id, "Showing an event let through by the new filter");
+
+                // Handle the event again, as if it was inserted at the same position.
+                self.items.remove_remote_event(event_index);
+                self.handle_remote_event(
+                    event,
+                    position,
+                    room_data_provider,
+                    settings,
+                    &mut date_divider_adjuster,
+                )
+                .await;
+            }
+        }
+
+        self.adjust_date_dividers(date_divider_adjuster);
+        self.check_invariants();
+    }
+
     /// Remove one timeline item by its `event_index`.
     fn remove_timeline_item(
         &mut self,
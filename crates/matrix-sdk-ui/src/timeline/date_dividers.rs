@@ -17,12 +17,13 @@
 
 use std::{fmt::Display, sync::Arc};
 
-use chrono::{Datelike, Local, TimeZone};
+use chrono::{Datelike, FixedOffset, Local, Offset, TimeZone, Utc};
 use ruma::MilliSecondsSinceUnixEpoch;
 use tracing::{Level, error, event_enabled, instrument, trace, warn};
 
 use super::{
-    DateDividerMode, TimelineItem, TimelineItemKind, VirtualTimelineItem,
+    DateDividerMode, TimelineConfig, TimelineItem, TimelineItemKind, TimezoneOffset,
+    VirtualTimelineItem,
     controller::{ObservableItemsTransaction, TimelineMetadata},
 };
 
@@ -39,19 +40,37 @@ impl Date {
     }
 }
 
-/// Converts a timestamp since Unix Epoch to a year, month and day.
-fn timestamp_to_date(ts: MilliSecondsSinceUnixEpoch) -> Date {
-    let datetime = Local
+/// Converts a timestamp since Unix Epoch to a year, month and day, in the
+/// given timezone.
+fn timestamp_to_date(ts: MilliSecondsSinceUnixEpoch, timezone: &TimezoneOffset) -> Date {
+    match timezone {
+        TimezoneOffset::Local => timestamp_to_date_in(Local, ts),
+        TimezoneOffset::Fixed(offset) => timestamp_to_date_in(fixed_offset(*offset), ts),
+        TimezoneOffset::Provider(provider) => timestamp_to_date_in(fixed_offset(provider(ts)), ts),
+    }
+}
+
+fn timestamp_to_date_in<Tz: TimeZone>(tz: Tz, ts: MilliSecondsSinceUnixEpoch) -> Date {
+    let datetime = tz
         .timestamp_millis_opt(ts.0.into())
         // Only returns `None` if date is after Dec 31, 262143 BCE.
         .single()
         // Fallback to the current date to avoid issues with malicious
         // homeservers.
-        .unwrap_or_else(Local::now);
+        .unwrap_or_else(|| Utc::now().with_timezone(&tz));
 
     Date { year: datetime.year(), month: datetime.month(), day: datetime.day() }
 }
 
+/// Returns the offset for the given number of seconds east of UTC, falling
+/// back to UTC if it's out of bounds.
+fn fixed_offset(seconds: i32) -> FixedOffset {
+    FixedOffset::east_opt(seconds).unwrap_or_else(|| {
+        warn!(seconds, "invalid timezone offset, using UTC");
+        Utc.fix()
+    })
+}
+
 /// Algorithm ensuring that date dividers are adjusted correctly, according to
 /// new items that have been inserted.
 pub(super) struct DateDividerAdjuster {
@@ -63,7 +82,11 @@ pub(super) struct DateDividerAdjuster {
     /// mark unused manually by calling [`Self::run`].
     consumed: bool,
 
-    mode: DateDividerMode,
+    /// When to insert date dividers; `None` if they're disabled.
+    mode: Option<DateDividerMode>,
+
+    /// The timezone in which the dates are computed.
+    timezone: TimezoneOffset,
 }
 
 impl Drop for DateDividerAdjuster {
@@ -88,13 +111,14 @@ struct PrevItemDesc<'a> {
 }
 
 impl DateDividerAdjuster {
-    pub fn new(mode: DateDividerMode) -> Self {
+    pub fn new(config: TimelineConfig) -> Self {
         Self {
             ops: Default::default(),
             // The adjuster starts as consumed, and it will be marked no consumed iff it's used
             // with `mark_used`.
             consumed: true,
-            mode,
+            mode: config.date_divider_mode,
+            timezone: config.timezone,
         }
     }
 
@@ -126,6 +150,20 @@ impl DateDividerAdjuster {
         // non-decreasing order of the indices), so we must record the insert
         // position for an operation related to the previous item.
 
+        if self.mode.is_none() {
+            // Date dividers are disabled: remove all of them, if any.
+            self.ops = items
+                .iter_remotes_and_locals_regions()
+                .filter(|(_, item)| item.is_date_divider())
+                .map(|(i, _)| DateDividerOperation::Remove(i))
+                .collect();
+
+            self.process_ops(items, meta);
+            self.ops.clear();
+            self.consumed = true;
+            return;
+        }
+
         let mut prev_item: Option<PrevItemDesc<'_>> = None;
         let mut latest_event_ts = None;
 
@@ -291,14 +329,12 @@ impl DateDividerAdjuster {
             }
 
             TimelineItemKind::Virtual(VirtualTimelineItem::DateDivider(prev_ts)) => {
-                let event_date = timestamp_to_date(ts);
-
                 // The event is preceded by a date divider.
-                if timestamp_to_date(*prev_ts) != event_date {
+                if !self.is_same_date_divider_group_as(*prev_ts, ts) {
                     // The date divider is wrong. Should we replace it with the correct value, or
                     // remove it entirely?
                     if let Some(last_event_ts) = latest_event_ts
-                        && timestamp_to_date(last_event_ts) == event_date
+                        && self.is_same_date_divider_group_as(last_event_ts, ts)
                     {
                         // There's a previous event with the same date: remove the divider.
                         trace!(
@@ -518,11 +554,12 @@ impl DateDividerAdjuster {
         lhs: MilliSecondsSinceUnixEpoch,
         rhs: MilliSecondsSinceUnixEpoch,
     ) -> bool {
+        let lhs = timestamp_to_date(lhs, &self.timezone);
+        let rhs = timestamp_to_date(rhs, &self.timezone);
+
         match self.mode {
-            DateDividerMode::Daily => timestamp_to_date(lhs) == timestamp_to_date(rhs),
-            DateDividerMode::Monthly => {
-                timestamp_to_date(lhs).is_same_month_as(timestamp_to_date(rhs))
-            }
+            Some(DateDividerMode::Daily) | None => lhs == rhs,
+            Some(DateDividerMode::Monthly) => lhs.is_same_month_as(rhs),
         }
     }
 }
@@ -661,8 +698,8 @@ mod tests {
 
     use super::{super::controller::ObservableItems, DateDividerAdjuster};
     use crate::timeline::{
-        DateDividerMode, EventTimelineItem, MsgLikeContent, TimelineItemContent,
-        VirtualTimelineItem,
+        DateDividerMode, EventTimelineItem, MsgLikeContent, TimelineConfig, TimelineItemContent,
+        TimezoneOffset, VirtualTimelineItem,
         controller::TimelineMetadata,
         date_dividers::timestamp_to_date,
         event_item::{EventTimelineItemKind, RemoteEventTimelineItem},
@@ -679,6 +716,8 @@ mod tests {
             original_json: None,
             latest_edit_json: None,
             origin: crate::timeline::event_item::RemoteEventOrigin::Sync,
+            marked_utd_at: None,
+            time_to_decrypt: None,
         });
         EventTimelineItem::new(
             owned_user_id!("@alice:example.org"),
@@ -712,7 +751,7 @@ mod tests {
         );
         txn.push_back(meta.new_timeline_item(VirtualTimelineItem::ReadMarker), None);
 
-        let mut adjuster = DateDividerAdjuster::new(DateDividerMode::Daily);
+        let mut adjuster = DateDividerAdjuster::new(TimelineConfig::default());
         adjuster.run(&mut txn, &mut meta);
 
         txn.commit();
@@ -741,7 +780,10 @@ mod tests {
         let timestamp = MilliSecondsSinceUnixEpoch(uint!(42));
         let timestamp_next_day =
             MilliSecondsSinceUnixEpoch((42 + 3600 * 24 * 1000).try_into().unwrap());
-        assert_ne!(timestamp_to_date(timestamp), timestamp_to_date(timestamp_next_day));
+        assert_ne!(
+            timestamp_to_date(timestamp, &TimezoneOffset::Local),
+            timestamp_to_date(timestamp_next_day, &TimezoneOffset::Local)
+        );
 
         let event = event_with_ts(timestamp);
         txn.push_back(meta.new_timeline_item(event.clone()), None);
@@ -752,7 +794,7 @@ mod tests {
         txn.push_back(meta.new_timeline_item(VirtualTimelineItem::ReadMarker), None);
         txn.push_back(meta.new_timeline_item(event), None);
 
-        let mut adjuster = DateDividerAdjuster::new(DateDividerMode::Daily);
+        let mut adjuster = DateDividerAdjuster::new(TimelineConfig::default());
         adjuster.run(&mut txn, &mut meta);
 
         txn.commit();
@@ -776,7 +818,10 @@ mod tests {
         let timestamp = MilliSecondsSinceUnixEpoch(uint!(42));
         let timestamp_next_day =
             MilliSecondsSinceUnixEpoch((42 + 3600 * 24 * 1000).try_into().unwrap());
-        assert_ne!(timestamp_to_date(timestamp), timestamp_to_date(timestamp_next_day));
+        assert_ne!(
+            timestamp_to_date(timestamp, &TimezoneOffset::Local),
+            timestamp_to_date(timestamp_next_day, &TimezoneOffset::Local)
+        );
 
         txn.push_back(meta.new_timeline_item(event_with_ts(timestamp)), None);
         txn.push_back(meta.new_timeline_item(VirtualTimelineItem::DateDivider(timestamp)), None);
@@ -785,7 +830,7 @@ mod tests {
         txn.push_back(meta.new_timeline_item(VirtualTimelineItem::DateDivider(timestamp)), None);
         txn.push_back(meta.new_timeline_item(event_with_ts(timestamp_next_day)), None);
 
-        let mut adjuster = DateDividerAdjuster::new(DateDividerMode::Daily);
+        let mut adjuster = DateDividerAdjuster::new(TimelineConfig::default());
         adjuster.run(&mut txn, &mut meta);
 
         txn.commit();
@@ -810,14 +855,17 @@ mod tests {
         let timestamp = MilliSecondsSinceUnixEpoch(uint!(42));
         let timestamp_next_day =
             MilliSecondsSinceUnixEpoch((42 + 3600 * 24 * 1000).try_into().unwrap());
-        assert_ne!(timestamp_to_date(timestamp), timestamp_to_date(timestamp_next_day));
+        assert_ne!(
+            timestamp_to_date(timestamp, &TimezoneOffset::Local),
+            timestamp_to_date(timestamp_next_day, &TimezoneOffset::Local)
+        );
 
         txn.push_back(meta.new_timeline_item(event_with_ts(timestamp_next_day)), None);
         txn.push_back(meta.new_timeline_item(VirtualTimelineItem::DateDivider(timestamp)), None);
         txn.push_back(meta.new_timeline_item(VirtualTimelineItem::DateDivider(timestamp)), None);
         txn.push_back(meta.new_timeline_item(event_with_ts(timestamp_next_day)), None);
 
-        let mut adjuster = DateDividerAdjuster::new(DateDividerMode::Daily);
+        let mut adjuster = DateDividerAdjuster::new(TimelineConfig::default());
         adjuster.run(&mut txn, &mut meta);
 
         txn.commit();
@@ -843,7 +891,7 @@ mod tests {
         txn.push_back(meta.new_timeline_item(VirtualTimelineItem::ReadMarker), None);
         txn.push_back(meta.new_timeline_item(VirtualTimelineItem::DateDivider(timestamp)), None);
 
-        let mut adjuster = DateDividerAdjuster::new(DateDividerMode::Daily);
+        let mut adjuster = DateDividerAdjuster::new(TimelineConfig::default());
         adjuster.run(&mut txn, &mut meta);
 
         txn.commit();
@@ -869,7 +917,7 @@ mod tests {
         txn.push_back(meta.new_timeline_item(VirtualTimelineItem::DateDivider(timestamp)), None);
         txn.push_back(meta.new_timeline_item(VirtualTimelineItem::DateDivider(timestamp)), None);
 
-        let mut adjuster = DateDividerAdjuster::new(DateDividerMode::Daily);
+        let mut adjuster = DateDividerAdjuster::new(TimelineConfig::default());
         adjuster.run(&mut txn, &mut meta);
 
         txn.commit();
@@ -891,7 +939,7 @@ mod tests {
         txn.push_back(meta.new_timeline_item(VirtualTimelineItem::ReadMarker), None);
         txn.push_back(meta.new_timeline_item(event_with_ts(timestamp)), None);
 
-        let mut adjuster = DateDividerAdjuster::new(DateDividerMode::Daily);
+        let mut adjuster = DateDividerAdjuster::new(TimelineConfig::default());
         adjuster.run(&mut txn, &mut meta);
 
         txn.commit();
@@ -924,7 +972,7 @@ mod tests {
             None,
         );
 
-        let mut adjuster = DateDividerAdjuster::new(DateDividerMode::Daily);
+        let mut adjuster = DateDividerAdjuster::new(TimelineConfig::default());
         adjuster.run(&mut txn, &mut meta);
 
         txn.commit();
@@ -964,7 +1012,10 @@ mod tests {
             None,
         );
 
-        let mut adjuster = DateDividerAdjuster::new(DateDividerMode::Monthly);
+        let mut adjuster = DateDividerAdjuster::new(TimelineConfig {
+            date_divider_mode: Some(DateDividerMode::Monthly),
+            ..Default::default()
+        });
         adjuster.run(&mut txn, &mut meta);
 
         txn.commit();
@@ -12,6 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::sync::Arc;
+
 use matrix_sdk::{
     HttpError, event_cache::EventCacheError, paginators::PaginatorError, room::reply::ReplyError,
     send_queue::RoomSendQueueError,
@@ -109,6 +111,10 @@ pub enum RedactError {
     /// The local echo we tried to abort has been lost.
     #[error("Invalid state: the local echo we tried to abort has been lost.")]
     InvalidLocalEchoState,
+
+    /// Sending the redaction failed, and the redacted item has been restored.
+    #[error("Sending the redaction failed: {0}")]
+    SendFailed(Arc<matrix_sdk::Error>),
 }
 
 #[derive(Error, Debug)]
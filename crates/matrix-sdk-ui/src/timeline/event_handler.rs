@@ -38,6 +38,7 @@ use ruma::{
         },
     },
     serde::Raw,
+    time::Instant,
 };
 use tracing::{debug, error, field::debug, instrument, trace, warn};
 
@@ -752,6 +753,26 @@ impl<'a, 'o> TimelineEventHandler<'a, 'o> {
                         }),
                 };
 
+                // Keep track of how long the event couldn't be decrypted, if it's updated
+                // after a late decryption.
+                let previous = as_variant!(
+                    *position,
+                    TimelineItemPosition::UpdateAt { timeline_item_index } => timeline_item_index
+                )
+                .and_then(|idx| self.items[idx].as_event()?.as_remote());
+                let is_utd = content.is_unable_to_decrypt();
+
+                let (marked_utd_at, time_to_decrypt) = match previous {
+                    Some(previous) if is_utd => {
+                        (previous.marked_utd_at.or_else(|| Some(Instant::now())), None)
+                    }
+                    Some(previous) => (
+                        None,
+                        previous.marked_utd_at.map(|at| at.elapsed()).or(previous.time_to_decrypt),
+                    ),
+                    None => (is_utd.then(Instant::now), None),
+                };
+
                 RemoteEventTimelineItem {
                     event_id: event_id.clone(),
                     transaction_id: txn_id.clone(),
@@ -762,6 +783,8 @@ impl<'a, 'o> TimelineEventHandler<'a, 'o> {
                     original_json: Some(raw_event.clone()),
                     latest_edit_json: None,
                     origin,
+                    marked_utd_at,
+                    time_to_decrypt,
                 }
                 .into()
             }
@@ -638,6 +638,20 @@ impl EncryptedMessage {
         }
     }
 
+    /// Return our best guess at the reason why this message couldn't be
+    /// decrypted.
+    ///
+    /// This is always [`UtdCause::Unknown`] for messages that weren't
+    /// encrypted with Megolm.
+    pub fn cause(&self) -> UtdCause {
+        match self {
+            EncryptedMessage::MegolmV1AesSha2 { cause, .. } => *cause,
+            EncryptedMessage::OlmV1Curve25519AesSha2 { .. } | EncryptedMessage::Unknown => {
+                UtdCause::Unknown
+            }
+        }
+    }
+
     /// Return the ID of the Megolm session used to encrypt this message, if it
     /// was received via a Megolm session.
     pub(crate) fn session_id(&self) -> Option<&str> {
@@ -15,7 +15,7 @@
 use std::sync::Arc;
 
 use as_variant::as_variant;
-use matrix_sdk::{Error, send_queue::SendHandle};
+use matrix_sdk::{Error, TransmissionProgress, send_queue::SendHandle};
 use ruma::{EventId, OwnedEventId, OwnedTransactionId};
 
 use super::TimelineEventItemId;
@@ -66,6 +66,15 @@ impl LocalEventTimelineItem {
 pub enum EventSendState {
     /// The local event has not been sent yet.
     NotSentYet,
+    /// The local event is being sent.
+    Sending {
+        /// For a media event, the progress of the upload of its media, with
+        /// the thumbnail and the file counted together.
+        ///
+        /// Only reported when enabled with
+        /// [`SendQueue::enable_upload_progress`](matrix_sdk::send_queue::SendQueue::enable_upload_progress).
+        progress: Option<TransmissionProgress>,
+    },
     /// The local event has been sent to the server, but unsuccessfully: The
     /// sending has failed.
     SendingFailed {
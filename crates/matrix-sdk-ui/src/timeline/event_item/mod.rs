@@ -35,6 +35,7 @@ use ruma::{
     events::{AnySyncTimelineEvent, receipt::Receipt, room::message::MessageType},
     room_version_rules::RedactionRules,
     serde::Raw,
+    time::Duration,
 };
 use tracing::warn;
 use unicode_segmentation::UnicodeSegmentation;
@@ -81,6 +82,12 @@ pub struct EventTimelineItem {
     ///
     /// May be false when we don't know about the room encryption status yet.
     pub(super) is_room_encrypted: bool,
+    /// Whether the redaction of this event by the current user is still being
+    /// sent.
+    pub(super) is_redaction_pending: bool,
+    /// The last local action on this event that failed, and has been rolled
+    /// back.
+    pub(super) local_action_error: Option<LocalActionError>,
 }
 
 #[derive(Clone, Debug)]
@@ -120,7 +127,16 @@ impl EventTimelineItem {
         kind: EventTimelineItemKind,
         is_room_encrypted: bool,
     ) -> Self {
-        Self { sender, sender_profile, timestamp, content, kind, is_room_encrypted }
+        Self {
+            sender,
+            sender_profile,
+            timestamp,
+            content,
+            kind,
+            is_room_encrypted,
+            is_redaction_pending: false,
+            local_action_error: None,
+        }
     }
 
     /// If the supplied low-level [`TimelineEvent`] is suitable for use as the
@@ -193,6 +209,8 @@ impl EventTimelineItem {
             original_json: Some(raw_sync_event),
             latest_edit_json,
             origin,
+            marked_utd_at: None,
+            time_to_decrypt: None,
         }
         .into();
 
@@ -250,6 +268,33 @@ impl EventTimelineItem {
         as_variant!(&mut self.kind, EventTimelineItemKind::Remote(remote_event_item) => remote_event_item)
     }
 
+    /// Whether this event has been redacted by the current user, and the
+    /// redaction is still being sent.
+    ///
+    /// The content of the item has already been redacted; it's restored if
+    /// the redaction fails.
+    pub fn is_redaction_pending(&self) -> bool {
+        self.is_redaction_pending
+    }
+
+    /// Get the error of the last local action on this event that failed, like
+    /// sending a reaction or a redaction.
+    ///
+    /// The effect of the action has been rolled back when this is set.
+    pub fn local_action_error(&self) -> Option<&LocalActionError> {
+        self.local_action_error.as_ref()
+    }
+
+    /// Get how long this event stayed unable to decrypt in this timeline, if
+    /// it couldn't be decrypted when it was first received, but has been
+    /// decrypted since then.
+    ///
+    /// This is measured from the moment the item was first shown as a UTD,
+    /// and is meant to be used for telemetry.
+    pub fn time_to_decrypt(&self) -> Option<Duration> {
+        self.as_remote()?.time_to_decrypt
+    }
+
     /// Get the event's send state of a local echo.
     pub fn send_state(&self) -> Option<&EventSendState> {
         as_variant!(&self.kind, EventTimelineItemKind::Local(local) => &local.send_state)
@@ -534,6 +579,21 @@ impl EventTimelineItem {
         new
     }
 
+    /// Clone the current event item, and update whether its redaction is
+    /// pending.
+    pub(super) fn with_redaction_pending(&self, is_redaction_pending: bool) -> Self {
+        Self { is_redaction_pending, ..self.clone() }
+    }
+
+    /// Clone the current event item, and update the error of the last local
+    /// action that failed.
+    pub(super) fn with_local_action_error(
+        &self,
+        local_action_error: Option<LocalActionError>,
+    ) -> Self {
+        Self { local_action_error, ..self.clone() }
+    }
+
     /// Create a clone of the current item, with content that's been redacted.
     pub(super) fn redact(&self, rules: &RedactionRules) -> Self {
         let content = self.content.redact(rules);
@@ -548,6 +608,8 @@ impl EventTimelineItem {
             content,
             kind,
             is_room_encrypted: self.is_room_encrypted,
+            is_redaction_pending: false,
+            local_action_error: None,
         }
     }
 
@@ -734,6 +796,24 @@ pub enum ReactionStatus {
     RemoteToRemote(OwnedEventId),
 }
 
+/// A local action on an event that failed, and whose effect on the timeline
+/// item has been rolled back.
+#[derive(Clone, Debug)]
+pub enum LocalActionError {
+    /// Sending a reaction to the event failed; the reaction has been removed.
+    Reaction {
+        /// The key of the reaction.
+        key: String,
+        /// The error that happened when sending the reaction.
+        error: Arc<Error>,
+    },
+    /// Redacting the event failed; its content has been restored.
+    Redaction {
+        /// The error that happened when sending the redaction.
+        error: Arc<Error>,
+    },
+}
+
 /// Information about a single reaction stored in [`ReactionsByKeyBySender`].
 #[derive(Clone, Debug)]
 pub struct ReactionInfo {
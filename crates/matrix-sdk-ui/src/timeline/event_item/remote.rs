@@ -20,6 +20,7 @@ use ruma::{
     OwnedEventId, OwnedTransactionId, OwnedUserId,
     events::{AnySyncTimelineEvent, receipt::Receipt},
     serde::Raw,
+    time::{Duration, Instant},
 };
 
 /// An item for an event that was received from the homeserver.
@@ -65,6 +66,14 @@ pub(in crate::timeline) struct RemoteEventTimelineItem {
 
     /// Where we got this event from: A sync response or pagination.
     pub origin: RemoteEventOrigin,
+
+    /// When the event was first shown as unable to decrypt in this timeline,
+    /// if it's still a UTD.
+    pub marked_utd_at: Option<Instant>,
+
+    /// How long the event stayed unable to decrypt in this timeline, if it
+    /// couldn't be decrypted when it was first received.
+    pub time_to_decrypt: Option<Duration>,
 }
 
 impl RemoteEventTimelineItem {
@@ -101,6 +110,8 @@ impl fmt::Debug for RemoteEventTimelineItem {
             latest_edit_json: _,
             is_highlighted,
             origin,
+            marked_utd_at,
+            time_to_decrypt,
         } = self;
 
         f.debug_struct("RemoteEventTimelineItem")
@@ -111,6 +122,8 @@ impl fmt::Debug for RemoteEventTimelineItem {
             .field("is_highlighted", is_highlighted)
             .field("encryption_info", encryption_info)
             .field("origin", origin)
+            .field("marked_utd_at", marked_utd_at)
+            .field("time_to_decrypt", time_to_decrypt)
             .finish_non_exhaustive()
     }
 }
@@ -0,0 +1,323 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Grouping of consecutive state events, like long runs of joins and leaves,
+//! into a single item.
+//!
+//! See [`Timeline::subscribe_grouped`](super::Timeline::subscribe_grouped).
+
+use std::{collections::BTreeSet, mem, sync::Arc};
+
+use async_stream::stream;
+use eyeball_im::VectorDiff;
+use futures_core::Stream;
+use futures_util::{StreamExt as _, pin_mut, stream};
+use imbl::Vector;
+use itertools::Either;
+use ruma::events::StateEventType;
+
+use super::{TimelineItem, TimelineItemContent, TimelineUniqueId};
+
+/// Which timeline items are collapsed into groups.
+#[derive(Clone, Debug)]
+pub struct StateEventGrouping {
+    /// The types of the state events that can be grouped together.
+    ///
+    /// Membership and profile changes are `m.room.member` events.
+    pub event_types: BTreeSet<StateEventType>,
+
+    /// The minimum number of consecutive state events forming a group.
+    pub min_group_size: usize,
+}
+
+impl Default for StateEventGrouping {
+    fn default() -> Self {
+        Self { event_types: BTreeSet::from([StateEventType::RoomMember]), min_group_size: 2 }
+    }
+}
+
+impl StateEventGrouping {
+    /// Group the consecutive state events of the given types.
+    pub fn new(event_types: impl IntoIterator<Item = StateEventType>) -> Self {
+        Self { event_types: event_types.into_iter().collect(), ..Default::default() }
+    }
+
+    /// Set the minimum number of consecutive state events forming a group.
+    pub fn min_group_size(mut self, min_group_size: usize) -> Self {
+        self.min_group_size = min_group_size.max(1);
+        self
+    }
+
+    /// Whether the given item can be part of a group.
+    fn is_groupable(&self, item: &TimelineItem) -> bool {
+        let Some(event) = item.as_event() else {
+            return false;
+        };
+
+        let event_type = match event.content() {
+            TimelineItemContent::MembershipChange(_) | TimelineItemContent::ProfileChange(_) => {
+                StateEventType::RoomMember
+            }
+            TimelineItemContent::OtherState(state) => state.content().event_type(),
+            _ => return false,
+        };
+
+        self.event_types.contains(&event_type)
+    }
+}
+
+/// Consecutive state events collapsed into a single item.
+#[derive(Clone, Debug)]
+pub struct GroupedStateEvents {
+    items: Vector<Arc<TimelineItem>>,
+}
+
+impl GroupedStateEvents {
+    /// The items of the group, in timeline order.
+    pub fn items(&self) -> &Vector<Arc<TimelineItem>> {
+        &self.items
+    }
+
+    /// The number of items in the group.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Whether the group is empty; groups produced by the timeline never are.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Whether both groups contain the same items.
+    fn same_items(&self, other: &Self) -> bool {
+        self.items.len() == other.items.len()
+            && self.items.iter().zip(other.items.iter()).all(|(a, b)| Arc::ptr_eq(a, b))
+    }
+}
+
+/// An item of a timeline whose state events are grouped.
+#[derive(Clone, Debug)]
+pub enum GroupedTimelineItem {
+    /// A timeline item that isn't part of a group.
+    Item(Arc<TimelineItem>),
+
+    /// Consecutive state events collapsed into a single item.
+    Group(GroupedStateEvents),
+}
+
+impl GroupedTimelineItem {
+    /// Whether both items are the same, and thus don't need to be updated.
+    fn is_same(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Item(a), Self::Item(b)) => Arc::ptr_eq(a, b),
+            (Self::Group(a), Self::Group(b)) => a.same_items(b),
+            _ => false,
+        }
+    }
+}
+
+/// Groups the items of a timeline, and computes the updates of the grouped
+/// items when the timeline items change.
+pub(super) struct StateEventGrouper {
+    grouping: StateEventGrouping,
+
+    /// The timeline items, ungrouped.
+    items: Vector<Arc<TimelineItem>>,
+
+    /// The items that have been expanded by the user, and must not be grouped.
+    expanded: BTreeSet<TimelineUniqueId>,
+
+    /// The grouped items.
+    output: Vector<GroupedTimelineItem>,
+}
+
+impl StateEventGrouper {
+    pub(super) fn new(
+        grouping: StateEventGrouping,
+        items: Vector<Arc<TimelineItem>>,
+        expanded: BTreeSet<TimelineUniqueId>,
+    ) -> Self {
+        let output = group_items(&items, &grouping, &expanded);
+        Self { grouping, items, expanded, output }
+    }
+
+    /// The current grouped items.
+    pub(super) fn output(&self) -> &Vector<GroupedTimelineItem> {
+        &self.output
+    }
+
+    /// Apply updates of the timeline items, and return the updates of the
+    /// grouped items.
+    pub(super) fn handle_diffs(
+        &mut self,
+        diffs: Vec<VectorDiff<Arc<TimelineItem>>>,
+    ) -> Vec<VectorDiff<GroupedTimelineItem>> {
+        for diff in diffs {
+            diff.apply(&mut self.items);
+        }
+
+        self.regroup()
+    }
+
+    /// Change the set of expanded items, and return the updates of the grouped
+    /// items.
+    pub(super) fn set_expanded(
+        &mut self,
+        expanded: BTreeSet<TimelineUniqueId>,
+    ) -> Vec<VectorDiff<GroupedTimelineItem>> {
+        self.expanded = expanded;
+        self.regroup()
+    }
+
+    fn regroup(&mut self) -> Vec<VectorDiff<GroupedTimelineItem>> {
+        let output = group_items(&self.items, &self.grouping, &self.expanded);
+        let diffs = diff_items(&self.output, &output);
+        self.output = output;
+        diffs
+    }
+}
+
+/// Collapse the runs of consecutive groupable items into groups.
+///
+/// A run containing an expanded item isn't collapsed.
+fn group_items(
+    items: &Vector<Arc<TimelineItem>>,
+    grouping: &StateEventGrouping,
+    expanded: &BTreeSet<TimelineUniqueId>,
+) -> Vector<GroupedTimelineItem> {
+    let mut output = Vector::new();
+    let mut run = Vector::new();
+
+    let flush = |output: &mut Vector<GroupedTimelineItem>, run: &mut Vector<Arc<TimelineItem>>| {
+        let run = mem::take(run);
+
+        if run.len() >= grouping.min_group_size
+            && !run.iter().any(|item| expanded.contains(item.unique_id()))
+        {
+            output.push_back(GroupedTimelineItem::Group(GroupedStateEvents { items: run }));
+        } else {
+            output.extend(run.into_iter().map(GroupedTimelineItem::Item));
+        }
+    };
+
+    for item in items {
+        if grouping.is_groupable(item) {
+            run.push_back(item.clone());
+        } else {
+            flush(&mut output, &mut run);
+            output.push_back(GroupedTimelineItem::Item(item.clone()));
+        }
+    }
+
+    flush(&mut output, &mut run);
+
+    output
+}
+
+/// Compute the updates transforming `old` into `new`.
+///
+/// The common prefix and suffix are kept as is; the items in between are
+/// updated in place, then removed or inserted.
+fn diff_items(
+    old: &Vector<GroupedTimelineItem>,
+    new: &Vector<GroupedTimelineItem>,
+) -> Vec<VectorDiff<GroupedTimelineItem>> {
+    if new.is_empty() {
+        return if old.is_empty() { Vec::new() } else { vec![VectorDiff::Clear] };
+    }
+
+    let prefix = old.iter().zip(new.iter()).take_while(|(a, b)| a.is_same(b)).count();
+    let max_suffix = old.len().min(new.len()) - prefix;
+    let suffix = old
+        .iter()
+        .rev()
+        .zip(new.iter().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a.is_same(b))
+        .count();
+
+    let num_old = old.len() - prefix - suffix;
+    let num_new = new.len() - prefix - suffix;
+    let num_updated = num_old.min(num_new);
+
+    let mut diffs = Vec::new();
+
+    for index in prefix..prefix + num_updated {
+        diffs.push(VectorDiff::Set { index, value: new[index].clone() });
+    }
+
+    for _ in num_updated..num_old {
+        diffs.push(VectorDiff::Remove { index: prefix + num_updated });
+    }
+
+    for index in prefix + num_updated..prefix + num_new {
+        diffs.push(VectorDiff::Insert { index, value: new[index].clone() });
+    }
+
+    diffs
+}
+
+impl super::Timeline {
+    /// Get the current timeline items, with the consecutive state events
+    /// collapsed into groups, along with a stream of updates of those items.
+    ///
+    /// The groups are updated as new events arrive, or as older events are
+    /// paginated. A group can be expanded with
+    /// [`Self::expand_state_events_group`].
+    pub async fn subscribe_grouped(
+        &self,
+        grouping: StateEventGrouping,
+    ) -> (
+        Vector<GroupedTimelineItem>,
+        impl Stream<Item = Vec<VectorDiff<GroupedTimelineItem>>> + use<>,
+    ) {
+        let (items, items_stream) = self.subscribe().await;
+        let expanded = self.expanded_state_groups.subscribe();
+
+        let mut grouper = StateEventGrouper::new(grouping, items, expanded.get());
+        let initial_items = grouper.output().clone();
+
+        let stream = stream! {
+            let updates = stream::select(
+                items_stream.map(Either::Left),
+                expanded.map(Either::Right),
+            );
+            pin_mut!(updates);
+
+            while let Some(update) = updates.next().await {
+                let diffs = match update {
+                    Either::Left(diffs) => grouper.handle_diffs(diffs),
+                    Either::Right(expanded) => grouper.set_expanded(expanded),
+                };
+
+                if !diffs.is_empty() {
+                    yield diffs;
+                }
+            }
+        };
+
+        (initial_items, stream)
+    }
+
+    /// Expand a group of state events, replacing it with its items in the
+    /// streams returned by [`Self::subscribe_grouped`].
+    ///
+    /// The items remain expanded when new state events are added next to
+    /// them.
+    pub fn expand_state_events_group(&self, group: &GroupedStateEvents) {
+        self.expanded_state_groups.update(|expanded| {
+            expanded.extend(group.items().iter().map(|item| item.unique_id().clone()));
+        });
+    }
+}
@@ -16,10 +16,11 @@
 //!
 //! See [`Timeline`] for details.
 
-use std::{fs, path::PathBuf, sync::Arc};
+use std::{collections::BTreeSet, fmt, fs, path::PathBuf, sync::Arc};
 
 use algorithms::rfind_event_by_item_id;
 use event_item::TimelineItemHandle;
+use eyeball::SharedObservable;
 use eyeball_im::VectorDiff;
 #[cfg(feature = "unstable-msc4274")]
 use futures::SendGallery;
@@ -38,10 +39,11 @@ use matrix_sdk::{
     },
     send_queue::{RoomSendQueueError, SendHandle},
 };
+use matrix_sdk_base::{SendOutsideWasm, SyncOutsideWasm};
 use mime::Mime;
 use pinned_events_loader::PinnedEventsRoom;
 use ruma::{
-    EventId, OwnedEventId, OwnedTransactionId, UserId,
+    EventId, MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedTransactionId, UserId,
     api::client::receipt::create_receipt::v3::ReceiptType,
     events::{
         AnyMessageLikeEventContent, AnySyncTimelineEvent, Mentions,
@@ -76,6 +78,7 @@ mod event_handler;
 mod event_item;
 pub mod event_type_filter;
 pub mod futures;
+mod grouping;
 mod item;
 mod pagination;
 mod pinned_events_loader;
@@ -89,18 +92,20 @@ mod virtual_item;
 
 pub use self::{
     builder::TimelineBuilder,
-    controller::default_event_filter,
+    controller::{DefaultEventFilter, default_event_filter},
     error::*,
     event_item::{
         AnyOtherFullStateEventContent, EmbeddedEvent, EncryptedMessage, EventItemOrigin,
-        EventSendState, EventTimelineItem, InReplyToDetails, MemberProfileChange, MembershipChange,
-        Message, MsgLikeContent, MsgLikeKind, OtherState, PollResult, PollState, Profile,
-        ReactionInfo, ReactionStatus, ReactionsByKeyBySender, RoomMembershipChange,
+        EventSendState, EventTimelineItem, InReplyToDetails, LocalActionError, MemberProfileChange,
+        MembershipChange, Message, MsgLikeContent, MsgLikeKind, OtherState, PollResult, PollState,
+        Profile, ReactionInfo, ReactionStatus, ReactionsByKeyBySender, RoomMembershipChange,
         RoomPinnedEventsChange, Sticker, ThreadSummary, TimelineDetails, TimelineEventItemId,
         TimelineItemContent,
     },
     event_type_filter::TimelineEventTypeFilter,
+    grouping::{GroupedStateEvents, GroupedTimelineItem, StateEventGrouping},
     item::{TimelineItem, TimelineItemKind, TimelineUniqueId},
+    pagination::FocusedPaginationStatus,
     traits::RoomExt,
     virtual_item::VirtualTimelineItem,
 };
@@ -121,6 +126,9 @@ pub struct Timeline {
 
     /// References to long-running tasks held by the timeline.
     drop_handle: Arc<TimelineDropHandle>,
+
+    /// The items of the groups of state events expanded by the user.
+    expanded_state_groups: SharedObservable<BTreeSet<TimelineUniqueId>>,
 }
 
 /// What should the timeline focus on?
@@ -173,6 +181,67 @@ pub enum DateDividerMode {
     Monthly,
 }
 
+/// Configuration of the virtual items inserted by the timeline, like the date
+/// dividers and the read marker.
+#[derive(Debug, Clone)]
+pub struct TimelineConfig {
+    /// When to insert date dividers; `None` disables them.
+    pub date_divider_mode: Option<DateDividerMode>,
+
+    /// The timezone in which the dates of the date dividers are computed.
+    pub timezone: TimezoneOffset,
+
+    /// Whether to insert a read marker item after the fully-read event.
+    ///
+    /// This only has an effect if the timeline tracks the fully-read marker,
+    /// see [`TimelineBuilder::track_read_marker_and_receipts`].
+    pub show_read_marker: bool,
+}
+
+impl Default for TimelineConfig {
+    fn default() -> Self {
+        Self {
+            date_divider_mode: Some(DateDividerMode::Daily),
+            timezone: TimezoneOffset::Local,
+            show_read_marker: true,
+        }
+    }
+}
+
+/// The timezone in which the timeline computes dates.
+#[derive(Clone, Default)]
+pub enum TimezoneOffset {
+    /// The local timezone of the system.
+    #[default]
+    Local,
+
+    /// A fixed offset from UTC, in seconds east of UTC.
+    Fixed(i32),
+
+    /// A callback returning the offset from UTC, in seconds east of UTC, at
+    /// the given time.
+    ///
+    /// Useful for timezones with daylight saving time, or when the timezone of
+    /// the user isn't the one of the system.
+    Provider(Arc<TimezoneOffsetFn>),
+}
+
+#[cfg(not(tarpaulin_include))]
+impl fmt::Debug for TimezoneOffset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Local => f.write_str("Local"),
+            Self::Fixed(offset) => f.debug_tuple("Fixed").field(offset).finish(),
+            Self::Provider(_) => f.write_str("Provider(..)"),
+        }
+    }
+}
+
+#[cfg(not(target_family = "wasm"))]
+pub type TimezoneOffsetFn = dyn Fn(MilliSecondsSinceUnixEpoch) -> i32 + Send + Sync;
+#[cfg(target_family = "wasm")]
+pub type TimezoneOffsetFn = dyn Fn(MilliSecondsSinceUnixEpoch) -> i32;
+
 /// Configuration for sending an attachment.
 ///
 /// Like [`matrix_sdk::attachment::AttachmentConfig`], but instead of the
@@ -195,11 +264,42 @@ impl Timeline {
         self.controller.room()
     }
 
+    /// Get the root of the thread this timeline is focused on, if any.
+    pub fn thread_root(&self) -> Option<OwnedEventId> {
+        self.controller.thread_root()
+    }
+
     /// Clear all timeline items.
     pub async fn clear(&self) {
         self.controller.clear().await;
     }
 
+    /// Replace the event filter of the timeline, set initially with
+    /// [`TimelineBuilder::event_filter`].
+    ///
+    /// The events known by the timeline are filtered again from the event
+    /// cache: the items of the events that are now filtered out are removed,
+    /// and items are added for the events that are now let through. The other
+    /// items are left untouched.
+    ///
+    /// The filtered events still apply to their related events, e.g. the
+    /// reactions and edits of a message are shown even if they're filtered
+    /// out.
+    pub async fn set_event_filter<F>(&self, filter: F)
+    where
+        F: Fn(&AnySyncTimelineEvent, &RoomVersionRules) -> bool
+            + SendOutsideWasm
+            + SyncOutsideWasm
+            + 'static,
+    {
+        let event_cache = &self.event_cache;
+        self.controller
+            .set_event_filter(Arc::new(filter), |event_id| async move {
+                event_cache.find_event(&event_id).await
+            })
+            .await;
+    }
+
     /// Retry decryption of previously un-decryptable events given a list of
     /// session IDs whose keys have been imported.
     ///
@@ -431,6 +531,9 @@ impl Timeline {
     ///
     /// Only supports events for which [`EventTimelineItem::is_editable()`]
     /// returns `true`.
+    ///
+    /// Only the caption of a media can be edited, see
+    /// [`EditedContent::for_original_message`].
     #[instrument(skip(self, new_content))]
     pub async fn edit(
         &self,
@@ -454,6 +557,15 @@ impl Timeline {
             }
 
             TimelineItemHandle::Local(handle) => {
+                // Edits of a media can only change its caption, including when it's still
+                // being uploaded.
+                let new_content = match item.content.as_message() {
+                    Some(original) => new_content
+                        .for_original_message(original.msgtype())
+                        .map_err(EditError::RoomError)?,
+                    None => new_content,
+                };
+
                 // Relations are filled by the editing code itself.
                 let new_content: AnyMessageLikeEventContent = match new_content {
                     EditedContent::RoomMessage(message) => {
@@ -536,6 +648,10 @@ impl Timeline {
     /// in the corresponding `TimelineEventItem`, and using a
     /// `MediaFormat::File`.
     ///
+    /// When sent with the send queue, the local echo's send state is
+    /// [`EventSendState::Sending`] while the media is uploaded, and its
+    /// sending can be cancelled with [`Self::cancel_send`].
+    ///
     /// # Arguments
     ///
     /// * `source` - The source of the attachment to send.
@@ -580,6 +696,16 @@ impl Timeline {
 
     /// Redact an event given its [`TimelineEventItemId`] and an optional
     /// reason.
+    ///
+    /// If the event has been sent, its item is immediately replaced with a
+    /// redacted placeholder, for which
+    /// [`EventTimelineItem::is_redaction_pending`] is true until the
+    /// redaction has been sent. If sending the redaction fails, the original
+    /// item is restored, with the error available with
+    /// [`EventTimelineItem::local_action_error`].
+    ///
+    /// If the event is still in the send queue, its sending is aborted, or it
+    /// will be redacted as soon as it's been sent.
     pub async fn redact(
         &self,
         item_id: &TimelineEventItemId,
@@ -592,7 +718,20 @@ impl Timeline {
 
         match event.handle() {
             TimelineItemHandle::Remote(event_id) => {
-                self.room().redact(event_id, reason, None).await.map_err(RedactError::HttpError)?;
+                let Some(original) = self.controller.mark_redaction_pending(item_id).await else {
+                    return Err(RedactError::ItemNotFound(item_id.clone()).into());
+                };
+
+                let result = self
+                    .room()
+                    .redact(event_id, reason, None)
+                    .await
+                    .map(|_| ())
+                    .map_err(|err| Arc::new(matrix_sdk::Error::from(err)));
+
+                self.controller.finish_pending_redaction(item_id, original, result.clone()).await;
+
+                result.map_err(RedactError::SendFailed)?;
             }
             TimelineItemHandle::Local(handle) => {
                 if !handle.abort().await.map_err(RoomSendQueueError::StorageError)? {
@@ -604,6 +743,26 @@ impl Timeline {
         Ok(())
     }
 
+    /// Cancel the sending of a local echo, given its [`TimelineEventItemId`].
+    ///
+    /// For a media event, the upload of its media is interrupted. The local
+    /// echo is removed from the timeline once the sending has been aborted.
+    ///
+    /// Returns `false` if it's too late to cancel the sending, e.g. because
+    /// the event has already been sent.
+    pub async fn cancel_send(&self, item_id: &TimelineEventItemId) -> Result<bool, Error> {
+        let items = self.items().await;
+        let Some((_pos, event)) = rfind_event_by_item_id(&items, item_id) else {
+            return Err(Error::EventNotInTimeline(item_id.clone()));
+        };
+
+        let Some(handle) = event.local_echo_send_handle() else {
+            return Ok(false);
+        };
+
+        Ok(handle.abort().await.map_err(RoomSendQueueError::StorageError)?)
+    }
+
     /// Fetch unavailable details about the event with the given ID.
     ///
     /// This method only works for IDs of remote [`EventTimelineItem`]s,
@@ -21,6 +21,23 @@ use tracing::{instrument, warn};
 
 use super::Error;
 
+/// The pagination status of a timeline focused on an event.
+///
+/// Both directions are independent: a timeline can hit its start before its
+/// end, and vice versa.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FocusedPaginationStatus {
+    /// Whether backwards pagination has reached the start of the room.
+    pub hit_timeline_start: bool,
+
+    /// Whether forwards pagination has reached the live edge of the room.
+    pub hit_timeline_end: bool,
+
+    /// Whether the timeline now receives the new events from sync, after
+    /// having reached the live edge of the room.
+    pub is_live: bool,
+}
+
 impl super::Timeline {
     /// Add more events to the start of the timeline.
     ///
@@ -57,13 +74,30 @@ impl super::Timeline {
     /// Add more events to the end of the timeline.
     ///
     /// Returns whether we hit the end of the timeline.
+    ///
+    /// For a timeline focused on an event, hitting the end of the timeline
+    /// means reaching the live edge of the room: from then on, the timeline
+    /// receives the new events from sync, like a live timeline.
     #[instrument(skip_all, fields(room_id = ?self.room().room_id()))]
     pub async fn paginate_forwards(&self, num_events: u16) -> Result<bool, Error> {
-        if self.controller.is_live() {
-            Ok(true)
-        } else {
-            Ok(self.controller.focused_paginate_forwards(num_events).await?)
+        if self.controller.is_live() || self.controller.has_joined_live() {
+            return Ok(true);
+        }
+
+        let hit_end_of_timeline = self.controller.focused_paginate_forwards(num_events).await?;
+
+        if hit_end_of_timeline {
+            self.controller.join_live(self.event_cache.events().await).await;
         }
+
+        Ok(hit_end_of_timeline)
+    }
+
+    /// Get the pagination status of a timeline focused on an event.
+    ///
+    /// This will return `None` if the timeline isn't focused on an event.
+    pub fn focused_pagination_status(&self) -> Option<FocusedPaginationStatus> {
+        self.controller.focused_pagination_status()
     }
 
     /// Paginate backwards in live mode.
@@ -14,10 +14,11 @@
 
 use std::{fmt::Formatter, sync::Arc};
 
+use eyeball_im::VectorDiff;
 use futures_util::{StreamExt, stream};
 use matrix_sdk::{BoxFuture, Room, SendOutsideWasm, SyncOutsideWasm, config::RequestConfig};
 use matrix_sdk_base::deserialized_responses::TimelineEvent;
-use ruma::{EventId, MilliSecondsSinceUnixEpoch, OwnedEventId, events::relation::RelationType};
+use ruma::{EventId, OwnedEventId, events::relation::RelationType};
 use thiserror::Error;
 use tokio::sync::Mutex;
 use tracing::{debug, warn};
@@ -63,6 +64,9 @@ impl PinnedEventsLoader {
     /// This method will perform as many concurrent requests for events as
     /// `max_concurrent_requests` allows, to avoid overwhelming the server.
     ///
+    /// The events are returned in the order they've been pinned, each pinned
+    /// event being followed by its related events.
+    ///
     /// Returns `None` if the list of pinned events hasn't changed since the
     /// previous time we loaded them. May return an error if there was an
     /// issue fetching the full events.
@@ -89,7 +93,7 @@ impl PinnedEventsLoader {
 
         let request_config = Some(RequestConfig::default().retry_limit(3));
 
-        let mut loaded_events: Vec<TimelineEvent> =
+        let loaded_events: Vec<TimelineEvent> =
             stream::iter(pinned_event_ids.clone().into_iter().map(|event_id| {
                 let provider = self.room.clone();
                 let relations_filter =
@@ -111,7 +115,8 @@ impl PinnedEventsLoader {
                     }
                 }
             }))
-            .buffer_unordered(self.max_concurrent_requests)
+            // Keep the order of the pinned events.
+            .buffered(self.max_concurrent_requests)
             // Get only the `Some<Vec<_>>` results
             .flat_map(stream::iter)
             // Flatten the `Vec`s into a single one containing all their items
@@ -123,14 +128,6 @@ impl PinnedEventsLoader {
             return Err(PinnedEventsLoaderError::TimelineReloadFailed);
         }
 
-        // Sort using chronological ordering (oldest -> newest)
-        loaded_events.sort_by_key(|item| {
-            item.raw()
-                .deserialize()
-                .map(|e| e.origin_server_ts())
-                .unwrap_or_else(|_| MilliSecondsSinceUnixEpoch::now())
-        });
-
         // We've successfully loaded *some* pinned events, so we can update the list of
         // previously seen pinned events.
         *self.previous_pinned_event_ids.lock().await = pinned_event_ids;
@@ -139,6 +136,58 @@ impl PinnedEventsLoader {
     }
 }
 
+/// Compute the updates transforming the remote events of a pinned events
+/// timeline, identified by `known_event_ids`, into the newly loaded `events`.
+///
+/// The events that aren't pinned anymore are removed, and the newly pinned ones
+/// are inserted, so the other items of the timeline are left untouched. If the
+/// pinned events have been reordered, the events are replaced altogether.
+pub(super) fn pinned_events_diffs(
+    known_event_ids: &[OwnedEventId],
+    events: Vec<TimelineEvent>,
+) -> Vec<VectorDiff<TimelineEvent>> {
+    let event_ids: Vec<_> = events.iter().map(|event| event.event_id()).collect();
+    let is_loaded = |event_id: &OwnedEventId| event_ids.iter().flatten().any(|id| id == event_id);
+
+    let mut diffs = Vec::new();
+
+    // Remove the events that aren't loaded anymore, starting from the end so the
+    // indices stay valid.
+    for (index, event_id) in known_event_ids.iter().enumerate().rev() {
+        if !is_loaded(event_id) {
+            diffs.push(VectorDiff::Remove { index });
+        }
+    }
+
+    let kept_event_ids: Vec<_> =
+        known_event_ids.iter().filter(|event_id| is_loaded(event_id)).collect();
+
+    // The kept events must be in the same order as before, otherwise they can't
+    // simply be kept.
+    let kept_event_ids_in_new_order: Vec<_> =
+        event_ids.iter().flatten().filter(|event_id| known_event_ids.contains(event_id)).collect();
+
+    if kept_event_ids != kept_event_ids_in_new_order {
+        return vec![
+            VectorDiff::Clear,
+            VectorDiff::Append { values: events.into_iter().collect() },
+        ];
+    }
+
+    // Insert the new events around the kept ones.
+    let mut kept_event_ids = kept_event_ids.into_iter().peekable();
+
+    for (index, (event, event_id)) in events.into_iter().zip(event_ids).enumerate() {
+        if event_id.is_some() && kept_event_ids.peek().copied() == event_id.as_ref() {
+            kept_event_ids.next();
+        } else {
+            diffs.push(VectorDiff::Insert { index, value: event });
+        }
+    }
+
+    diffs
+}
+
 pub trait PinnedEventsRoom: SendOutsideWasm + SyncOutsideWasm {
     /// Load a single room event using the cache or network and any events
     /// related to it, if they are cached.
@@ -214,3 +263,65 @@ pub enum PinnedEventsLoaderError {
     #[error("Could not load pinned events.")]
     TimelineReloadFailed,
 }
+
+#[cfg(test)]
+mod tests {
+    use assert_matches2::assert_let;
+    use matrix_sdk_test::event_factory::EventFactory;
+    use ruma::{event_id, owned_event_id, room_id, user_id};
+
+    use super::*;
+
+    fn events(event_ids: &[&EventId]) -> Vec<TimelineEvent> {
+        let f = EventFactory::new().room(room_id!("!a:b.c")).sender(user_id!("@a:b.c"));
+        event_ids
+            .iter()
+            .map(|event_id| f.text_msg("pinned").event_id(event_id).into_event())
+            .collect()
+    }
+
+    #[test]
+    fn test_pinned_events_diffs_remove_and_insert() {
+        let known = vec![owned_event_id!("$1"), owned_event_id!("$2"), owned_event_id!("$3")];
+
+        // `$2` is unpinned, `$4` is pinned.
+        let diffs = pinned_events_diffs(
+            &known,
+            events(&[event_id!("$1"), event_id!("$3"), event_id!("$4")]),
+        );
+
+        assert_eq!(diffs.len(), 2);
+        assert_let!(VectorDiff::Remove { index: 1 } = &diffs[0]);
+        assert_let!(VectorDiff::Insert { index: 2, value } = &diffs[1]);
+        assert_eq!(value.event_id().as_deref(), Some(event_id!("$4")));
+
+        // A new event pinned in the middle.
+        let diffs = pinned_events_diffs(
+            &known,
+            events(&[event_id!("$1"), event_id!("$4"), event_id!("$2"), event_id!("$3")]),
+        );
+
+        assert_eq!(diffs.len(), 1);
+        assert_let!(VectorDiff::Insert { index: 1, value } = &diffs[0]);
+        assert_eq!(value.event_id().as_deref(), Some(event_id!("$4")));
+
+        // Nothing changed.
+        let diffs = pinned_events_diffs(
+            &known,
+            events(&[event_id!("$1"), event_id!("$2"), event_id!("$3")]),
+        );
+        assert!(diffs.is_empty());
+    }
+
+    #[test]
+    fn test_pinned_events_diffs_reorder() {
+        let known = vec![owned_event_id!("$1"), owned_event_id!("$2")];
+
+        let diffs = pinned_events_diffs(&known, events(&[event_id!("$2"), event_id!("$1")]));
+
+        assert_eq!(diffs.len(), 2);
+        assert_let!(VectorDiff::Clear = &diffs[0]);
+        assert_let!(VectorDiff::Append { values } = &diffs[1]);
+        assert_eq!(values.len(), 2);
+    }
+}
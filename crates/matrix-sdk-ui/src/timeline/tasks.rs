@@ -54,12 +54,7 @@ pub(in crate::timeline) async fn pinned_events_task<S>(
         match timeline_controller.reload_pinned_events().await {
             Ok(Some(events)) => {
                 trace!("successfully reloaded pinned events");
-                timeline_controller
-                    .replace_with_initial_remote_events(
-                        events.into_iter(),
-                        RemoteEventOrigin::Pagination,
-                    )
-                    .await;
+                timeline_controller.update_pinned_events(events).await;
             }
 
             Ok(None) => {
@@ -182,6 +177,9 @@ pub(in crate::timeline) async fn room_event_cache_updates_task(
 
                 if matches!(timeline_focus, TimelineFocus::Live { .. }) {
                     timeline_controller.handle_remote_events_with_diffs(diffs, origin).await;
+                } else if timeline_controller.has_joined_live() {
+                    // An event-focused timeline that has reached the live edge of the room.
+                    timeline_controller.handle_live_edge_diffs(diffs, origin).await;
                 } else {
                     // Only handle the remote aggregation for a non-live timeline.
                     timeline_controller.handle_remote_aggregations(diffs, origin).await;
@@ -15,6 +15,7 @@
 #![cfg(not(target_family = "wasm"))]
 
 use std::{
+    collections::BTreeSet,
     io::Cursor,
     iter,
     sync::{Arc, Mutex},
@@ -29,12 +30,13 @@ use matrix_sdk::{
     assert_next_matches_with_timeout,
     crypto::{OlmMachine, decrypt_room_key_export, types::events::UtdCause},
     deserialized_responses::{
-        AlgorithmInfo, DecryptedRoomEvent, EncryptionInfo, VerificationLevel, VerificationState,
+        AlgorithmInfo, DecryptedRoomEvent, EncryptionInfo, ShieldState, ShieldStateCode,
+        VerificationLevel, VerificationState,
     },
     test_utils::test_client_builder,
 };
 use matrix_sdk_base::deserialized_responses::{TimelineEvent, UnableToDecryptReason};
-use matrix_sdk_test::{ALICE, BOB, async_test};
+use matrix_sdk_test::{ALICE, BOB, CAROL, async_test};
 use ruma::{
     assign, event_id,
     events::room::encrypted::{
@@ -134,6 +136,9 @@ async fn test_retry_message_decryption() {
         }) = event.content()
     );
     assert_eq!(session_id, SESSION_ID);
+    assert_eq!(event.content().as_unable_to_decrypt().unwrap().cause(), UtdCause::Unknown);
+    assert!(event.time_to_decrypt().is_none());
+    let utd_unique_id = item.unique_id().clone();
 
     assert_next_matches!(stream, VectorDiff::PushFront { value } => {
         assert!(value.is_date_divider());
@@ -173,6 +178,10 @@ async fn test_retry_message_decryption() {
     assert_eq!(message.body(), "It's a secret to everybody");
     assert!(!event.is_highlighted());
 
+    // The UTD item has been updated in place.
+    assert_eq!(*item.unique_id(), utd_unique_id);
+    assert!(event.time_to_decrypt().is_some());
+
     // The message should not be re-reported as a late decryption.
     {
         let utds = hook.utds.lock().unwrap();
@@ -696,6 +705,56 @@ async fn test_retry_fetching_encryption_info() {
     assert_pending!(stream);
 }
 
+#[async_test]
+async fn test_shields_are_updated_when_sender_gets_verified() {
+    const SESSION_ID: &str = "C25PoE+4MlNidQD0YU5ibZqHawV0zZ/up7R8vYJBYTY";
+
+    // Once refreshed, the encryption info of the session says "verified".
+    let provider = TestRoomDataProvider::default().with_encryption_info(
+        SESSION_ID,
+        make_encryption_info(SESSION_ID, VerificationState::Verified),
+    );
+    let timeline = TestTimelineBuilder::new().provider(provider).room_encrypted(true).build();
+    let f = &timeline.factory;
+    let mut stream = timeline.subscribe_events().await;
+
+    // Bob and Carol send messages from devices that aren't signed yet.
+    for sender in [*BOB, *CAROL] {
+        timeline
+            .handle_live_event(TimelineEvent::from_decrypted(
+                DecryptedRoomEvent {
+                    event: f.text_msg("hi").sender(sender).into_raw(),
+                    encryption_info: make_encryption_info(
+                        SESSION_ID,
+                        VerificationState::Unverified(VerificationLevel::UnsignedDevice),
+                    ),
+                    unsigned_encryption_info: None,
+                },
+                None,
+            ))
+            .await;
+
+        let event = assert_next_matches!(stream, VectorDiff::PushBack { value } => value);
+        assert_matches!(
+            event.get_shield(false),
+            Some(ShieldState::Red { code: ShieldStateCode::UnsignedDevice, .. })
+        );
+    }
+
+    // Bob gets verified: only his message is updated.
+    let bob: BTreeSet<_> = iter::once(BOB.to_owned()).collect();
+    timeline.controller.refresh_encryption_info(&bob).await;
+
+    let event = assert_next_matches!(stream, VectorDiff::Set { index: 0, value } => value);
+    assert_eq!(event.sender(), *BOB);
+    assert_eq!(event.get_shield(false), Some(ShieldState::None));
+    assert_pending!(stream);
+
+    // Nothing is updated when the verification state doesn't change.
+    timeline.controller.refresh_encryption_info(&bob).await;
+    assert_pending!(stream);
+}
+
 fn make_encryption_info(
     session_id: &str,
     verification_state: VerificationState,
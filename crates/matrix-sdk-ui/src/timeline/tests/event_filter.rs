@@ -17,21 +17,27 @@ use std::sync::Arc;
 use assert_matches2::assert_let;
 use eyeball_im::VectorDiff;
 use matrix_sdk::deserialized_responses::TimelineEvent;
-use matrix_sdk_test::{ALICE, BOB, async_test, sync_timeline_event};
-use ruma::events::{
-    AnySyncTimelineEvent, TimelineEventType,
-    room::{
-        member::MembershipState,
-        message::{MessageType, RedactedRoomMessageEventContent},
+use matrix_sdk_test::{
+    ALICE, BOB, async_test, event_factory::PreviousMembership, sync_timeline_event,
+};
+use ruma::{
+    event_id,
+    events::{
+        AnyMessageLikeEventContent, AnySyncTimelineEvent, TimelineEventType,
+        room::{
+            member::MembershipState,
+            message::{MessageType, RedactedRoomMessageEventContent},
+        },
     },
+    room_version_rules::RoomVersionRules,
 };
-use stream_assert::assert_next_matches;
+use stream_assert::{assert_next_matches, assert_pending};
 
 use super::TestTimeline;
 use crate::timeline::{
-    AnyOtherFullStateEventContent, MsgLikeContent, MsgLikeKind, TimelineEventTypeFilter,
-    TimelineItem, TimelineItemContent, TimelineItemKind, controller::TimelineSettings,
-    tests::TestTimelineBuilder,
+    AnyOtherFullStateEventContent, DefaultEventFilter, MsgLikeContent, MsgLikeKind,
+    TimelineEventTypeFilter, TimelineItem, TimelineItemContent, TimelineItemKind,
+    controller::TimelineSettings, default_event_filter, tests::TestTimelineBuilder,
 };
 
 #[async_test]
@@ -235,7 +241,113 @@ async fn test_event_type_filter_exclude_messages() {
     assert_eq!(room_topic_items_count, 1);
 }
 
+#[async_test]
+async fn test_set_event_filter_at_runtime() {
+    let timeline = TestTimeline::new();
+    let f = &timeline.factory;
+
+    let events = vec![
+        f.text_msg("Hello").sender(&ALICE).event_id(event_id!("$1")).into_event(),
+        f.member(&BOB)
+            .display_name("Bobby")
+            .previous(PreviousMembership::new(MembershipState::Join).display_name("Bob"))
+            .event_id(event_id!("$2"))
+            .into_event(),
+        f.text_msg("Hi").sender(&BOB).event_id(event_id!("$3")).into_event(),
+        f.reaction(event_id!("$1"), "👍").sender(&BOB).event_id(event_id!("$4")).into_event(),
+    ];
+    for event in events.clone() {
+        timeline.handle_live_event(event).await;
+    }
+
+    let mut stream = timeline.subscribe().await;
+
+    // Hiding the profile changes only removes their item.
+    let filter = DefaultEventFilter::new().show_profile_changes(false);
+    timeline.set_event_filter(move |event, rules| filter.filter(event, rules), &events).await;
+
+    assert_next_matches!(stream, VectorDiff::Remove { index: 2 });
+    assert_pending!(stream);
+
+    let items = timeline.controller.items().await;
+    assert_eq!(items.len(), 3);
+    let first = items[1].as_event().unwrap();
+    assert_eq!(first.event_id().unwrap(), event_id!("$1"));
+    assert_eq!(first.content().reactions().unwrap().len(), 1);
+    assert_eq!(items[2].as_event().unwrap().event_id().unwrap(), event_id!("$3"));
+
+    // Setting the same filter again doesn't change anything.
+    let filter = DefaultEventFilter::new().show_profile_changes(false);
+    timeline.set_event_filter(move |event, rules| filter.filter(event, rules), &events).await;
+    assert_pending!(stream);
+
+    // Showing them again inserts the item back at the same place.
+    timeline.set_event_filter(default_event_filter, &events).await;
+
+    let item = assert_next_matches!(stream, VectorDiff::Insert { index: 2, value } => value);
+    let event = item.as_event().unwrap();
+    assert_eq!(event.event_id().unwrap(), event_id!("$2"));
+    assert_let!(TimelineItemContent::ProfileChange(profile) = event.content());
+    assert!(profile.displayname_change().is_some());
+    assert_pending!(stream);
+}
+
+#[async_test]
+async fn test_filtered_events_still_aggregate() {
+    let timeline = TestTimelineBuilder::new()
+        .settings(TimelineSettings {
+            event_filter: Arc::new(|event: &AnySyncTimelineEvent, rules: &RoomVersionRules| {
+                default_event_filter(event, rules) && !is_notice_event(event)
+            }),
+            ..Default::default()
+        })
+        .build();
+    let f = &timeline.factory;
+
+    let events = vec![
+        f.notice("Beep").sender(&BOB).event_id(event_id!("$1")).into_event(),
+        f.reaction(event_id!("$1"), "🤖").sender(&ALICE).event_id(event_id!("$2")).into_event(),
+        f.text_msg("Hello").sender(&ALICE).event_id(event_id!("$3")).into_event(),
+    ];
+    for event in events.clone() {
+        timeline.handle_live_event(event).await;
+    }
+
+    // The notice is hidden.
+    let items = timeline.get_event_items().await;
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].as_event().unwrap().event_id().unwrap(), event_id!("$3"));
+
+    let mut stream = timeline.subscribe().await;
+
+    // Once shown, the notice has the reaction that was received while it was
+    // hidden.
+    timeline.set_event_filter(default_event_filter, &events).await;
+
+    let item = assert_next_matches!(stream, VectorDiff::Insert { index: 1, value } => value);
+    let event = item.as_event().unwrap();
+    assert_eq!(event.event_id().unwrap(), event_id!("$1"));
+    let reactions = event.content().reactions().unwrap();
+    assert!(reactions.get("🤖").unwrap().contains_key(*ALICE));
+    assert_pending!(stream);
+}
+
 impl TestTimeline {
+    async fn set_event_filter(
+        &self,
+        filter: impl Fn(&AnySyncTimelineEvent, &RoomVersionRules) -> bool + Send + Sync + 'static,
+        events: &[TimelineEvent],
+    ) {
+        self.controller
+            .set_event_filter(Arc::new(filter), |event_id| {
+                let event =
+                    events.iter().find(|event| event.event_id().as_ref() == Some(&event_id));
+                let event = event.cloned();
+                async move { event }
+            })
+            .await;
+    }
+
     async fn get_event_items(&self) -> Vec<Arc<TimelineItem>> {
         self.controller
             .items()
@@ -284,3 +396,15 @@ fn is_room_topic_item(item: &&Arc<TimelineItem>) -> bool {
         _ => false,
     }
 }
+
+fn is_notice_event(event: &AnySyncTimelineEvent) -> bool {
+    let AnySyncTimelineEvent::MessageLike(msg) = event else {
+        return false;
+    };
+
+    matches!(
+        msg.original_content(),
+        Some(AnyMessageLikeEventContent::RoomMessage(content))
+            if matches!(content.msgtype, MessageType::Notice(_))
+    )
+}
@@ -0,0 +1,138 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::BTreeSet, sync::Arc};
+
+use assert_matches2::assert_let;
+use eyeball_im::VectorDiff;
+use futures_core::Stream;
+use futures_util::{FutureExt as _, StreamExt as _};
+use matrix_sdk_test::{ALICE, async_test};
+use ruma::{OwnedUserId, events::room::member::MembershipState, owned_user_id};
+
+use super::TestTimeline;
+use crate::timeline::{
+    GroupedTimelineItem, StateEventGrouping, TimelineItem, grouping::StateEventGrouper,
+};
+
+/// Feed all the pending updates of the timeline items to the grouper, and
+/// return the updates of the grouped items.
+fn next_grouped_diffs(
+    stream: &mut (impl Stream<Item = Vec<VectorDiff<Arc<TimelineItem>>>> + Unpin),
+    grouper: &mut StateEventGrouper,
+) -> Vec<VectorDiff<GroupedTimelineItem>> {
+    let mut diffs = Vec::new();
+    while let Some(Some(batch)) = stream.next().now_or_never() {
+        diffs.extend(grouper.handle_diffs(batch));
+    }
+    diffs
+}
+
+fn user(i: usize) -> OwnedUserId {
+    format!("@user{i}:localhost").try_into().unwrap()
+}
+
+#[async_test]
+async fn test_consecutive_joins_are_grouped() {
+    let timeline = TestTimeline::new();
+    let f = &timeline.factory;
+
+    let (items, mut stream) = timeline.controller.subscribe().await;
+    let mut grouper = StateEventGrouper::new(StateEventGrouping::default(), items, BTreeSet::new());
+    assert!(grouper.output().is_empty());
+
+    for i in 0..20 {
+        let user_id = user(i);
+        timeline.handle_live_event(f.member(&user_id)).await;
+    }
+    next_grouped_diffs(&mut stream, &mut grouper);
+
+    timeline.handle_live_event(f.text_msg("Welcome everyone!").sender(&ALICE)).await;
+
+    // The message is only appended, the group isn't touched.
+    let diffs = next_grouped_diffs(&mut stream, &mut grouper);
+    assert_let!([VectorDiff::Insert { index: 2, value }] = diffs.as_slice());
+    assert_let!(GroupedTimelineItem::Item(message) = value);
+    assert!(message.as_event().unwrap().content().is_message());
+
+    let output = grouper.output();
+    assert_eq!(output.len(), 3);
+    assert_let!(GroupedTimelineItem::Item(date_divider) = &output[0]);
+    assert!(date_divider.is_date_divider());
+    assert_let!(GroupedTimelineItem::Group(group) = &output[1]);
+    assert_eq!(group.len(), 20);
+    assert_let!(GroupedTimelineItem::Item(_) = &output[2]);
+
+    // Older joins are paginated, the group grows.
+    for i in 20..25 {
+        let user_id = user(i);
+        timeline.handle_back_paginated_event(f.member(&user_id).into_raw_timeline()).await;
+    }
+
+    let diffs = next_grouped_diffs(&mut stream, &mut grouper);
+    assert!(!diffs.is_empty());
+    // The message after the group is untouched.
+    for diff in &diffs {
+        assert_let!(VectorDiff::Set { index, .. } = diff);
+        assert!(*index < 2);
+    }
+
+    let output = grouper.output();
+    assert_eq!(output.len(), 3);
+    assert_let!(GroupedTimelineItem::Group(group) = &output[1]);
+    assert_eq!(group.len(), 25);
+    let first_sender = group.items()[0].as_event().unwrap().sender().to_owned();
+    assert_eq!(first_sender, user(24));
+
+    // Once expanded, the group is replaced by its items.
+    let expanded = group.items().iter().map(|item| item.unique_id().clone()).collect();
+    let diffs = grouper.set_expanded(expanded);
+    assert!(!diffs.is_empty());
+
+    let output = grouper.output();
+    assert_eq!(output.len(), 27);
+    assert!(output.iter().all(|item| matches!(item, GroupedTimelineItem::Item(_))));
+}
+
+#[async_test]
+async fn test_min_group_size() {
+    let timeline = TestTimeline::new();
+    let f = &timeline.factory;
+
+    let bob = owned_user_id!("@bob:localhost");
+    timeline.handle_live_event(f.member(&bob)).await;
+    timeline.handle_live_event(f.text_msg("Hi").sender(&bob)).await;
+    timeline.handle_live_event(f.member(&ALICE)).await;
+    timeline.handle_live_event(f.member(&bob).membership(MembershipState::Leave)).await;
+
+    let (items, _) = timeline.controller.subscribe().await;
+
+    // A single join isn't grouped, the two consecutive membership changes are.
+    let grouper =
+        StateEventGrouper::new(StateEventGrouping::default(), items.clone(), BTreeSet::new());
+    let output = grouper.output();
+    assert_eq!(output.len(), 4);
+    assert_let!(GroupedTimelineItem::Item(_) = &output[1]);
+    assert_let!(GroupedTimelineItem::Group(group) = &output[3]);
+    assert_eq!(group.len(), 2);
+
+    // With a bigger minimum size, nothing is grouped.
+    let grouper = StateEventGrouper::new(
+        StateEventGrouping::default().min_group_size(3),
+        items,
+        BTreeSet::new(),
+    );
+    assert_eq!(grouper.output().len(), 5);
+    assert!(grouper.output().iter().all(|item| matches!(item, GroupedTimelineItem::Item(_))));
+}
@@ -72,6 +72,7 @@ mod echo;
 mod edit;
 mod encryption;
 mod event_filter;
+mod grouping;
 mod invalid;
 mod polls;
 mod reactions;
@@ -12,6 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::sync::Arc;
+
 use assert_matches::assert_matches;
 use assert_matches2::assert_let;
 use chrono::{Datelike, TimeZone, Utc};
@@ -24,8 +26,30 @@ use ruma::{
 };
 use stream_assert::assert_next_matches;
 
-use super::TestTimeline;
-use crate::timeline::{VirtualTimelineItem, traits::RoomDataProvider as _};
+use super::{TestTimeline, TestTimelineBuilder};
+use crate::timeline::{
+    TimelineConfig, TimezoneOffset, VirtualTimelineItem, controller::TimelineSettings,
+    traits::RoomDataProvider as _,
+};
+
+/// Two hours, in milliseconds.
+const TWO_HOURS: u64 = 2 * 60 * 60 * 1000;
+
+/// The timestamp, in milliseconds, of the given hour and minute on January 1st
+/// 1970, UTC.
+fn jan_1st_utc(hour: u64, minute: u64) -> u64 {
+    (hour * 60 + minute) * 60 * 1000
+}
+
+fn timeline_with_config(config: TimelineConfig) -> TestTimeline {
+    TestTimelineBuilder::new()
+        .settings(TimelineSettings {
+            track_read_receipts: true,
+            virtual_items: config,
+            ..Default::default()
+        })
+        .build()
+}
 
 #[async_test]
 async fn test_date_divider() {
@@ -229,3 +253,127 @@ async fn test_update_read_marker() {
 
     assert!(stream.next().now_or_never().is_none());
 }
+
+#[async_test]
+async fn test_date_divider_in_timezone_with_back_pagination() {
+    let timeline = timeline_with_config(TimelineConfig {
+        timezone: TimezoneOffset::Fixed((TWO_HOURS / 1000) as i32),
+        ..Default::default()
+    });
+    let mut stream = timeline.subscribe().await;
+
+    let f = &timeline.factory;
+
+    // 23:00 UTC, i.e. 01:00 on January 2nd in the timezone.
+    timeline.handle_live_event(f.text_msg("A").sender(*ALICE).server_ts(jan_1st_utc(23, 0))).await;
+
+    assert_next_matches!(stream, VectorDiff::PushBack { value } => value);
+    let date_divider = assert_next_matches!(stream, VectorDiff::PushFront { value } => value);
+    assert!(date_divider.is_date_divider());
+
+    // 21:30 UTC, i.e. 23:30 on January 1st in the timezone: it's the same day in
+    // UTC, but not in the timezone, so a new date divider is inserted.
+    timeline
+        .handle_back_paginated_event(
+            f.text_msg("B").sender(*ALICE).server_ts(jan_1st_utc(21, 30)).into_raw_timeline(),
+        )
+        .await;
+
+    assert_next_matches!(stream, VectorDiff::PushFront { value } => value);
+    let date_divider = assert_next_matches!(stream, VectorDiff::PushFront { value } => value);
+    assert_let!(VirtualTimelineItem::DateDivider(ts) = date_divider.as_virtual().unwrap());
+    assert_eq!(u64::from(ts.0), jan_1st_utc(21, 30));
+
+    // Timeline: [date-divider, B, date-divider, A].
+    assert_eq!(timeline.len().await, 4);
+
+    // 20:00 UTC, i.e. 22:00 on January 1st in the timezone: the first date divider
+    // moves before the new event.
+    timeline
+        .handle_back_paginated_event(
+            f.text_msg("C").sender(*ALICE).server_ts(jan_1st_utc(20, 0)).into_raw_timeline(),
+        )
+        .await;
+
+    assert_next_matches!(stream, VectorDiff::PushFront { value } => value);
+    let date_divider = assert_next_matches!(stream, VectorDiff::PushFront { value } => value);
+    assert_let!(VirtualTimelineItem::DateDivider(ts) = date_divider.as_virtual().unwrap());
+    assert_eq!(u64::from(ts.0), jan_1st_utc(20, 0));
+    assert_next_matches!(stream, VectorDiff::Remove { index: 2 });
+
+    // Timeline: [date-divider, C, B, date-divider, A].
+    assert_eq!(timeline.len().await, 5);
+    assert!(stream.next().now_or_never().is_none());
+}
+
+#[async_test]
+async fn test_date_divider_midnight_rollover_in_timezone() {
+    let timeline = timeline_with_config(TimelineConfig {
+        timezone: TimezoneOffset::Provider(Arc::new(|_| (TWO_HOURS / 1000) as i32)),
+        ..Default::default()
+    });
+    let mut stream = timeline.subscribe().await;
+
+    let f = &timeline.factory;
+
+    // 23:50 on January 1st in the timezone.
+    timeline.handle_live_event(f.text_msg("A").sender(*ALICE).server_ts(jan_1st_utc(21, 50))).await;
+
+    assert_next_matches!(stream, VectorDiff::PushBack { value } => value);
+    assert_next_matches!(stream, VectorDiff::PushFront { value } => value);
+
+    // 00:10 on January 2nd in the timezone, still January 1st in UTC.
+    timeline.handle_live_event(f.text_msg("B").sender(*ALICE).server_ts(jan_1st_utc(22, 10))).await;
+
+    assert_next_matches!(stream, VectorDiff::PushBack { value } => value);
+    let date_divider =
+        assert_next_matches!(stream, VectorDiff::Insert { index: 2, value } => value);
+    assert_let!(VirtualTimelineItem::DateDivider(ts) = date_divider.as_virtual().unwrap());
+    assert_eq!(u64::from(ts.0), jan_1st_utc(22, 10));
+
+    // Another message on the same day doesn't get a date divider.
+    timeline.handle_live_event(f.text_msg("C").sender(*ALICE).server_ts(jan_1st_utc(23, 0))).await;
+
+    assert_next_matches!(stream, VectorDiff::PushBack { value } => value);
+    assert!(stream.next().now_or_never().is_none());
+}
+
+#[async_test]
+async fn test_disabled_date_dividers() {
+    let timeline =
+        timeline_with_config(TimelineConfig { date_divider_mode: None, ..Default::default() });
+    let mut stream = timeline.subscribe().await;
+
+    let f = &timeline.factory;
+
+    timeline.handle_live_event(f.text_msg("A").sender(*ALICE).server_ts(0)).await;
+    assert_next_matches!(stream, VectorDiff::PushBack { value } => value);
+
+    timeline.handle_live_event(f.text_msg("B").sender(*ALICE).server_ts(24 * 60 * 60 * 1000)).await;
+    assert_next_matches!(stream, VectorDiff::PushBack { value } => value);
+
+    // No date divider has been inserted.
+    assert!(stream.next().now_or_never().is_none());
+    assert_eq!(timeline.len().await, 2);
+}
+
+#[async_test]
+async fn test_disabled_read_marker() {
+    let timeline =
+        timeline_with_config(TimelineConfig { show_read_marker: false, ..Default::default() });
+    let mut stream = timeline.subscribe().await;
+
+    let f = &timeline.factory;
+
+    timeline.handle_live_event(f.text_msg("A").sender(*ALICE)).await;
+    let item = assert_next_matches!(stream, VectorDiff::PushBack { value } => value);
+    let event_id = item.as_event().unwrap().event_id().unwrap().to_owned();
+    assert_next_matches!(stream, VectorDiff::PushFront { value } => value);
+
+    timeline.controller.handle_fully_read_marker(event_id).await;
+
+    // A new event after the fully-read event doesn't come with a read marker.
+    timeline.handle_live_event(f.text_msg("B").sender(&BOB)).await;
+    assert_next_matches!(stream, VectorDiff::PushBack { value } => value);
+    assert!(stream.next().now_or_never().is_none());
+}
@@ -335,8 +335,11 @@ impl Decryptor for (matrix_sdk_base::crypto::OlmMachine, ruma::OwnedRoomId) {
         push_ctx: Option<&PushContext>,
     ) -> Result<TimelineEvent> {
         let (olm_machine, room_id) = self;
-        let decryption_settings =
-            DecryptionSettings { sender_device_trust_requirement: TrustRequirement::Untrusted };
+        let decryption_settings = DecryptionSettings {
+            sender_device_trust_requirement: TrustRequirement::Untrusted,
+            to_device_trust_requirements: Default::default(),
+            unwedging_policy: Default::default(),
+        };
 
         match olm_machine
             .try_decrypt_room_event(raw.cast_ref_unchecked(), room_id, &decryption_settings)
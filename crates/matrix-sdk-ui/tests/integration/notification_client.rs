@@ -947,3 +947,109 @@ async fn test_notification_client_context_filters_out_events_from_ignored_users(
 
     assert_matches!(result, NotificationStatus::EventFilteredOut);
 }
+
+#[async_test]
+async fn test_notification_client_in_order_across_rooms() {
+    let room_id1 = room_id!("!room1:example.org");
+    let room_id2 = room_id!("!room2:example.org");
+    let (client, server) = logged_in_client_with_server().await;
+
+    let event_id1 = event_id!("$event1");
+    let event_id2 = event_id!("$event2");
+    let event_id3 = event_id!("$event3");
+    let sender = user_id!("@user:example.org");
+
+    let mut sync_builder = SyncResponseBuilder::new();
+    sync_builder
+        .add_joined_room(JoinedRoomBuilder::new(room_id1))
+        .add_joined_room(JoinedRoomBuilder::new(room_id2));
+
+    // First, mock a sync so we get valid rooms for both room ids.
+    mock_sync(&server, sync_builder.build_json_sync_response(), None).await;
+    let _response = client.sync_once(SyncSettings::default()).await.unwrap();
+    server.reset().await;
+
+    // The sliding sync doesn't return any event, so the notification client falls
+    // back to `/context` for all of them.
+    Mock::given(SlidingSyncMatcher)
+        .respond_with(move |request: &Request| {
+            let partial_request: PartialSlidingSyncRequest = request.body_json().unwrap();
+            ResponseTemplate::new(200).set_body_json(json!({
+                "txn_id": partial_request.txn_id,
+                "pos": "0",
+            }))
+        })
+        .mount(&server)
+        .await;
+
+    for (room_id, event_id, body) in
+        [(room_id1, event_id1, "Hello room 1"), (room_id2, event_id2, "Hello room 2")]
+    {
+        let event_factory = EventFactory::new().room(room_id).sender(sender);
+        Mock::given(method("GET"))
+            .and(path(format!("/_matrix/client/r0/rooms/{room_id}/context/{event_id}")))
+            .and(header("authorization", "Bearer 1234"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "event": event_factory.text_msg(body).event_id(event_id).into_raw_sync(),
+                "state": [
+                    event_factory.member(sender).membership(MembershipState::Join).into_raw_timeline(),
+                ],
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+    }
+
+    // The third event can't be fetched.
+    Mock::given(method("GET"))
+        .and(path(format!("/_matrix/client/r0/rooms/{room_id1}/context/{event_id3}")))
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+            "errcode": "M_NOT_FOUND",
+            "error": "Event not found.",
+        })))
+        .mount(&server)
+        .await;
+
+    mock_encryption_state(&server, false).await;
+
+    let dummy_sync_service = Arc::new(SyncService::builder(client.clone()).build().await.unwrap());
+    let process_setup =
+        NotificationProcessSetup::SingleProcess { sync_service: dummy_sync_service };
+    let notification_client = NotificationClient::new(client, process_setup)
+        .await
+        .unwrap()
+        .with_max_concurrent_requests(2)
+        .with_time_budget(Duration::from_secs(30));
+
+    let results = notification_client
+        .get_notifications_in_order(vec![
+            (room_id2.to_owned(), event_id2.to_owned()),
+            (room_id1.to_owned(), event_id3.to_owned()),
+            (room_id1.to_owned(), event_id1.to_owned()),
+            // A duplicate is only returned once.
+            (room_id2.to_owned(), event_id2.to_owned()),
+        ])
+        .await
+        .unwrap();
+
+    // The results are in the same order as the requests, and the failure of one of
+    // them doesn't affect the others.
+    assert_eq!(results.len(), 3);
+
+    assert_eq!(results[0].0, room_id2);
+    assert_eq!(results[0].1, event_id2);
+    assert_let!(Ok(NotificationStatus::Event(item)) = &results[0].2);
+    assert_matches!(&item.event, NotificationEvent::Timeline(event) => {
+        assert_eq!(event.event_type(), TimelineEventType::RoomMessage);
+    });
+
+    assert_eq!(results[1].1, event_id3);
+    assert!(results[1].2.is_err());
+
+    assert_eq!(results[2].0, room_id1);
+    assert_eq!(results[2].1, event_id1);
+    assert_let!(Ok(NotificationStatus::Event(item)) = &results[2].2);
+    assert_matches!(&item.event, NotificationEvent::Timeline(event) => {
+        assert_eq!(event.event_type(), TimelineEventType::RoomMessage);
+    });
+}
@@ -19,8 +19,12 @@ use matrix_sdk_test::{
 use matrix_sdk_ui::{
     RoomListService,
     room_list_service::{
-        ALL_ROOMS_LIST_NAME as ALL_ROOMS, Error, RoomListLoadingState, State, SyncIndicator,
-        filters::{new_filter_fuzzy_match_room_name, new_filter_non_left, new_filter_none},
+        ALL_ROOMS_LIST_NAME as ALL_ROOMS, Error, RoomListLoadingState, RoomListSorting, State,
+        SyncIndicator,
+        filters::{
+            FilterExpr, new_filter_fuzzy_match_room_name, new_filter_non_left, new_filter_none,
+        },
+        sorters::RoomListSorting,
     },
     timeline::{RoomExt as _, TimelineItemKind, VirtualTimelineItem},
 };
@@ -2087,6 +2091,271 @@ async fn test_room_sorting() -> Result<(), Error> {
     Ok(())
 }
 
+#[async_test]
+async fn test_room_sorting_strategy() -> Result<(), Error> {
+    let (_client, server, room_list) = new_room_list_service().await?;
+
+    let sync = room_list.sync();
+    pin_mut!(sync);
+
+    let all_rooms = room_list.all_rooms().await?;
+
+    let (stream, dynamic_entries) = all_rooms.entries_with_dynamic_adapters(10);
+    pin_mut!(stream);
+
+    sync_then_assert_request_and_fake_response! {
+        [server, room_list, sync]
+        states = Init => SettingUp,
+        assert request >= {
+            "lists": {
+                ALL_ROOMS: {
+                    "ranges": [[0, 19]],
+                    "timeline_limit": 1,
+                },
+            },
+        },
+        respond with = {
+            "pos": "0",
+            "lists": {
+                ALL_ROOMS: {
+                    "count": 3,
+                },
+            },
+            "rooms": {
+                "!r0:bar.org": {
+                    "initial": true,
+                    "bump_stamp": 3,
+                    "required_state": [
+                        {
+                            "content": {
+                                "name": "Ccc"
+                            },
+                            "sender": "@example:bar.org",
+                            "state_key": "",
+                            "type": "m.room.name",
+                            "event_id": "$s0",
+                            "origin_server_ts": 3,
+                        },
+                    ],
+                },
+                "!r1:bar.org": {
+                    "initial": true,
+                    "bump_stamp": 1,
+                    "required_state": [
+                        {
+                            "content": {
+                                "name": "aaa"
+                            },
+                            "sender": "@example:bar.org",
+                            "state_key": "",
+                            "type": "m.room.name",
+                            "event_id": "$s1",
+                            "origin_server_ts": 1,
+                        },
+                    ],
+                },
+                "!r2:bar.org": {
+                    "initial": true,
+                    "bump_stamp": 2,
+                    "required_state": [
+                        {
+                            "content": {
+                                "name": "Bbb"
+                            },
+                            "sender": "@example:bar.org",
+                            "state_key": "",
+                            "type": "m.room.name",
+                            "event_id": "$s2",
+                            "origin_server_ts": 2,
+                        },
+                    ],
+                },
+            },
+        },
+    };
+
+    // Changing the sorting before a filter is set doesn't yield anything.
+    assert!(dynamic_entries.set_sorting(RoomListSorting::Recency));
+    assert_pending!(stream);
+
+    dynamic_entries.set_filter(Box::new(new_filter_non_left()));
+
+    // Rooms are sorted by recency by default.
+    assert_entries_batch! {
+        [stream]
+        reset [
+            "!r0:bar.org", // recency of 3
+            "!r2:bar.org", // recency of 2
+            "!r1:bar.org", // recency of 1
+        ];
+        end;
+    };
+    assert_pending!(stream);
+
+    // Switching the strategy yields a single reset.
+    assert!(dynamic_entries.set_sorting(RoomListSorting::Alphabetical));
+
+    assert_entries_batch! {
+        [stream]
+        reset [
+            "!r1:bar.org", // aaa
+            "!r2:bar.org", // Bbb
+            "!r0:bar.org", // Ccc
+        ];
+        end;
+    };
+    assert_pending!(stream);
+
+    sync_then_assert_request_and_fake_response! {
+        [server, room_list, sync]
+        states = SettingUp => Running,
+        assert request >= {
+            "lists": {
+                ALL_ROOMS: {
+                    "ranges": [[0, 2]],
+                    "timeline_limit": 1,
+                },
+            },
+        },
+        respond with = {
+            "pos": "1",
+            "lists": {
+                ALL_ROOMS: {
+                    "count": 3,
+                },
+            },
+            "rooms": {
+                "!r1:bar.org": {
+                    "bump_stamp": 4,
+                },
+            },
+        },
+    };
+
+    // The room doesn't move, it's only updated.
+    assert_entries_batch! {
+        [stream]
+        set [ 0 ] [ "!r1:bar.org" ];
+        end;
+    };
+    assert_pending!(stream);
+
+    Ok(())
+}
+
+#[async_test]
+async fn test_dynamic_entries_filter_expr() -> Result<(), Error> {
+    let (_client, server, room_list) = new_room_list_service().await?;
+
+    let sync = room_list.sync();
+    pin_mut!(sync);
+
+    let all_rooms = room_list.all_rooms().await?;
+
+    let (stream, dynamic_entries) = all_rooms.entries_with_dynamic_adapters(10);
+    pin_mut!(stream);
+
+    sync_then_assert_request_and_fake_response! {
+        [server, room_list, sync]
+        states = Init => SettingUp,
+        assert request >= {
+            "lists": {
+                ALL_ROOMS: {
+                    "ranges": [[0, 19]],
+                    "timeline_limit": 1,
+                },
+            },
+        },
+        respond with = {
+            "pos": "0",
+            "lists": {
+                ALL_ROOMS: {
+                    "count": 2,
+                },
+            },
+            "rooms": {
+                "!r0:bar.org": {
+                    "initial": true,
+                    "bump_stamp": 2,
+                },
+                "!r1:bar.org": {
+                    "initial": true,
+                    "bump_stamp": 1,
+                },
+            },
+        },
+    };
+
+    assert!(dynamic_entries.filter_expr().get().is_none());
+
+    let favourites = FilterExpr::NonLeft.and(FilterExpr::Favourite);
+    assert!(dynamic_entries.apply_filter(favourites.clone()).await?);
+
+    // No room is a favourite yet.
+    assert_entries_batch! {
+        [stream]
+        reset [];
+        end;
+    };
+    assert_pending!(stream);
+
+    let filter_expr = dynamic_entries.filter_expr().get().unwrap();
+    assert_eq!(filter_expr, favourites);
+    assert!(filter_expr.contains(&FilterExpr::Favourite));
+    assert!(filter_expr.contains(&FilterExpr::Unread).not());
+
+    sync_then_assert_request_and_fake_response! {
+        [server, room_list, sync]
+        states = SettingUp => Running,
+        assert request >= {
+            "lists": {
+                ALL_ROOMS: {
+                    "ranges": [[0, 1]],
+                    "timeline_limit": 1,
+                },
+            },
+        },
+        respond with = {
+            "pos": "1",
+            "lists": {
+                ALL_ROOMS: {
+                    "count": 2,
+                },
+            },
+            "rooms": {},
+            "extensions": {
+                "account_data": {
+                    "rooms": {
+                        "!r1:bar.org": [
+                            {
+                                "type": "m.tag",
+                                "content": {
+                                    "tags": {
+                                        "m.favourite": {},
+                                    },
+                                },
+                            },
+                        ],
+                    },
+                },
+            },
+        },
+    };
+
+    // The room is inserted in the filtered list, without a reset.
+    assert_entries_batch! {
+        [stream]
+        insert [ 0 ] [ "!r1:bar.org" ];
+        end;
+    };
+
+    // Setting a filter that isn't an expression clears the expression.
+    assert!(dynamic_entries.set_filter(Box::new(new_filter_non_left())));
+    assert!(dynamic_entries.filter_expr().get().is_none());
+
+    Ok(())
+}
+
 #[async_test]
 async fn test_room() -> Result<(), Error> {
     let (_, server, room_list) = new_room_list_service().await?;
@@ -19,6 +19,7 @@ use std::{
 
 use matrix_sdk::{
     assert_next_eq_with_timeout,
+    send_queue::SendQueueStatus,
     test_utils::{logged_in_client_with_server, mocks::MatrixMockServer},
 };
 use matrix_sdk_test::async_test;
@@ -211,7 +212,8 @@ async fn test_sync_service_offline_mode() {
     let mock_server = MatrixMockServer::new().await;
     let client = mock_server.client_builder().build().await;
 
-    let sync_service = SyncService::builder(client).with_offline_mode().build().await.unwrap();
+    let sync_service =
+        SyncService::builder(client.clone()).with_offline_mode().build().await.unwrap();
     let mut states = sync_service.state();
 
     Mock::given(SlidingSyncMatcher)
@@ -226,11 +228,17 @@ async fn test_sync_service_offline_mode() {
         sync_service.start().await;
         assert_next_eq!(states, State::Running);
         assert_next_eq_with_timeout!(states, State::Offline, 2000 ms, "We should have entered the offline mode");
+
+        // The send queue is paused while offline.
+        assert_eq!(client.send_queue().status(), SendQueueStatus::Offline);
     }
 
     mock_server.mock_versions().ok().expect(1..).mount().await;
 
     assert_next_eq_with_timeout!(states, State::Running, 1000 ms,  "We should have continued to sync");
+
+    // The send queue has resumed.
+    assert_eq!(client.send_queue().status(), SendQueueStatus::Running);
 }
 
 #[async_test]
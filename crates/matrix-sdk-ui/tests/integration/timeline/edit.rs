@@ -28,8 +28,8 @@ use matrix_sdk_test::{ALICE, BOB, JoinedRoomBuilder, async_test, event_factory::
 use matrix_sdk_ui::{
     Timeline,
     timeline::{
-        EditError, Error, EventSendState, MsgLikeContent, MsgLikeKind, RoomExt, TimelineDetails,
-        TimelineEventItemId, TimelineItemContent,
+        AttachmentConfig, AttachmentSource, EditError, Error, EventSendState, MsgLikeContent,
+        MsgLikeKind, RoomExt, TimelineDetails, TimelineEventItemId, TimelineItemContent,
     },
 };
 use ruma::{
@@ -41,14 +41,18 @@ use ruma::{
             UnstablePollAnswer, UnstablePollAnswers, UnstablePollStartContentBlock,
             UnstablePollStartEventContent,
         },
-        room::message::{
-            MessageType, RoomMessageEventContent, RoomMessageEventContentWithoutRelation,
-            TextMessageEventContent,
+        room::{
+            MediaSource,
+            message::{
+                ImageMessageEventContent, MessageType, RoomMessageEventContent,
+                RoomMessageEventContentWithoutRelation, TextMessageEventContent,
+            },
         },
     },
-    owned_event_id, room_id,
+    owned_event_id, owned_mxc_uri, room_id,
     serde::Raw,
 };
+use serde_json::json;
 use stream_assert::{assert_next_matches, assert_pending};
 use tokio::{task::yield_now, time::sleep};
 
@@ -1091,3 +1095,164 @@ async fn test_send_edit_non_existing_item() {
         .unwrap();
     assert_matches!(error, Error::EventNotInTimeline(_));
 }
+
+#[async_test]
+async fn test_edit_caption_of_encrypted_image() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let room_id = room_id!("!a98sd12bjh:example.org");
+    let room = server.sync_joined_room(&client, room_id).await;
+
+    server.mock_room_state_encryption().plain().mount().await;
+
+    let timeline = room.timeline().await.unwrap();
+    let (_, mut timeline_stream) =
+        timeline.subscribe_filter_map(|item| item.as_event().cloned()).await;
+
+    let file = serde_json::from_value(json!({
+        "url": "mxc://sdk.rs/encrypted",
+        "key": {
+            "kty": "oct",
+            "key_ops": ["encrypt", "decrypt"],
+            "alg": "A256CTR",
+            "k": "b50ACIv6LMn9AfMCFD1POJI_UAFWIclxAN1kWrEO2X8",
+            "ext": true,
+        },
+        "iv": "AK1wyzigZtQAAAABAAAAKK",
+        "hashes": {
+            "sha256": "foobar",
+        },
+        "v": "v2",
+    }))
+    .unwrap();
+    let mut image = ImageMessageEventContent::encrypted("caption".to_owned(), file);
+    image.filename = Some("cat.jpg".to_owned());
+
+    let f = EventFactory::new();
+    server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id).add_timeline_event(
+                f.event(RoomMessageEventContent::new(MessageType::Image(image)))
+                    .sender(client.user_id().unwrap())
+                    .event_id(event_id!("$original_event")),
+            ),
+        )
+        .await;
+
+    let item = assert_next_matches!(timeline_stream, VectorDiff::PushBack { value } => value);
+    assert!(item.is_editable());
+
+    // The edit keeps the encrypted file.
+    server
+        .mock_room_send()
+        .body_matches_partial_json(json!({
+            "m.new_content": {
+                "body": "new caption",
+                "filename": "cat.jpg",
+                "file": { "url": "mxc://sdk.rs/encrypted" },
+            },
+        }))
+        .ok(event_id!("$edit_event"))
+        .mock_once()
+        .mount()
+        .await;
+
+    timeline
+        .edit(
+            &item.identifier(),
+            EditedContent::MediaCaption {
+                caption: Some("new caption".to_owned()),
+                formatted_caption: None,
+                mentions: None,
+            },
+        )
+        .await
+        .unwrap();
+
+    // Let the send queue handle the event.
+    yield_now().await;
+
+    let edit_item =
+        assert_next_matches!(timeline_stream, VectorDiff::Set { index: 0, value } => value);
+    let edit_message = edit_item.content().as_message().unwrap();
+    assert!(edit_message.is_edited());
+    assert_let!(MessageType::Image(image) = edit_message.msgtype());
+    assert_eq!(image.caption(), Some("new caption"));
+    assert_eq!(image.filename(), "cat.jpg");
+    assert_let!(MediaSource::Encrypted(file) = &image.source);
+    assert_eq!(file.url, "mxc://sdk.rs/encrypted");
+
+    // Replacing the image with a text message isn't allowed.
+    let error = timeline
+        .edit(
+            &item.identifier(),
+            EditedContent::RoomMessage(RoomMessageEventContentWithoutRelation::text_plain(
+                "not an image",
+            )),
+        )
+        .await
+        .unwrap_err();
+    assert_matches!(error, Error::EditError(EditError::RoomError(_)));
+
+    // The response to the mocked endpoint does not generate further timeline
+    // updates, so just wait for a bit before verifying that the endpoint was
+    // called.
+    sleep(Duration::from_millis(200)).await;
+}
+
+#[async_test]
+async fn test_edit_caption_of_queued_image() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    // Disable the sending queue, so the image stays queued.
+    client.send_queue().set_enabled(false).await;
+
+    let room_id = room_id!("!a98sd12bjh:example.org");
+    let room = server.sync_joined_room(&client, room_id).await;
+
+    server.mock_room_state_encryption().plain().mount().await;
+
+    let timeline = room.timeline().await.unwrap();
+    let (_, mut timeline_stream) =
+        timeline.subscribe_filter_map(|item| item.as_event().cloned()).await;
+
+    let source = AttachmentSource::Data { bytes: vec![0; 16], filename: "cat.jpg".to_owned() };
+    timeline
+        .send_attachment(source, mime::IMAGE_JPEG, AttachmentConfig::default())
+        .use_send_queue()
+        .await
+        .unwrap();
+
+    let item = assert_next_matches!(timeline_stream, VectorDiff::PushBack { value } => value);
+    assert_matches!(item.send_state(), Some(EventSendState::NotSentYet));
+    assert_let!(MessageType::Image(image) = item.content().as_message().unwrap().msgtype());
+    assert!(image.caption().is_none());
+
+    // A new image content only changes the caption of the queued image.
+    let mut new_image = ImageMessageEventContent::plain(
+        "new caption".to_owned(),
+        owned_mxc_uri!("mxc://sdk.rs/ignored"),
+    );
+    new_image.filename = Some("ignored.jpg".to_owned());
+    timeline
+        .edit(
+            &item.identifier(),
+            EditedContent::RoomMessage(RoomMessageEventContentWithoutRelation::new(
+                MessageType::Image(new_image),
+            )),
+        )
+        .await
+        .unwrap();
+
+    let edit_item =
+        assert_next_matches!(timeline_stream, VectorDiff::Set { index: 0, value } => value);
+    assert_matches!(edit_item.send_state(), Some(EventSendState::NotSentYet));
+    assert_let!(MessageType::Image(image) = edit_item.content().as_message().unwrap().msgtype());
+    assert_eq!(image.caption(), Some("new caption"));
+    assert_eq!(image.filename(), "cat.jpg");
+
+    assert_pending!(timeline_stream);
+}
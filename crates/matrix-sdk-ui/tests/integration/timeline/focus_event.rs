@@ -24,7 +24,7 @@ use matrix_sdk_test::{
     ALICE, BOB, JoinedRoomBuilder, SyncResponseBuilder, async_test, event_factory::EventFactory,
     mocks::mock_encryption_state,
 };
-use matrix_sdk_ui::timeline::{TimelineBuilder, TimelineFocus};
+use matrix_sdk_ui::timeline::{FocusedPaginationStatus, TimelineBuilder, TimelineFocus};
 use ruma::{event_id, events::room::message::RoomMessageEventContent, room_id};
 use stream_assert::assert_pending;
 use tokio::time::sleep;
@@ -414,3 +414,111 @@ async fn test_focused_timeline_doesnt_show_local_echoes() {
     // And nothing more.
     assert_pending!(timeline_stream);
 }
+
+#[async_test]
+async fn test_focused_timeline_joins_live_after_forward_pagination() {
+    let room_id = room_id!("!a98sd12bjh:example.org");
+    let (client, server) = logged_in_client_with_server().await;
+    let sync_settings = SyncSettings::new().timeout(Duration::from_millis(3000));
+
+    let mut sync_response_builder = SyncResponseBuilder::new();
+    sync_response_builder.add_joined_room(JoinedRoomBuilder::new(room_id));
+
+    // Mark the room as joined.
+    mock_sync(&server, sync_response_builder.build_json_sync_response(), None).await;
+    let _response = client.sync_once(sync_settings.clone()).await.unwrap();
+    server.reset().await;
+
+    // Start a focused timeline on an old event.
+    let f = EventFactory::new().room(room_id);
+    let target_event = event_id!("$1");
+
+    mock_context(
+        &server,
+        room_id,
+        target_event,
+        Some("prev1".to_owned()),
+        vec![],
+        f.text_msg("old").event_id(target_event).sender(*BOB).into_event(),
+        vec![f.text_msg("older than live").event_id(event_id!("$2")).sender(*ALICE).into_event()],
+        Some("next1".to_owned()),
+        vec![],
+    )
+    .await;
+
+    mock_encryption_state(&server, false).await;
+
+    let room = client.get_room(room_id).unwrap();
+    let timeline = TimelineBuilder::new(&room)
+        .focused_on_event(target_event.to_owned(), 20)
+        .build()
+        .await
+        .unwrap();
+
+    server.reset().await;
+
+    assert_eq!(
+        timeline.focused_pagination_status(),
+        Some(FocusedPaginationStatus {
+            hit_timeline_start: false,
+            hit_timeline_end: false,
+            is_live: false,
+        })
+    );
+
+    // Paginate forwards, up to the live edge.
+    mock_messages(
+        &server,
+        "next1".to_owned(),
+        None,
+        vec![f.text_msg("latest").event_id(event_id!("$3")).sender(*BOB).into_event()],
+        vec![],
+    )
+    .await;
+
+    let hit_end = timeline.paginate_forwards(20).await.unwrap();
+    assert!(hit_end);
+    server.reset().await;
+
+    // The end has been reached, but not the start.
+    assert_eq!(
+        timeline.focused_pagination_status(),
+        Some(FocusedPaginationStatus {
+            hit_timeline_start: false,
+            hit_timeline_end: true,
+            is_live: true,
+        })
+    );
+
+    // Paginating forwards again doesn't hit the network.
+    assert!(timeline.paginate_forwards(20).await.unwrap());
+
+    // A sync brings the latest event again, a new event, and a reaction to the
+    // focused event.
+    sync_response_builder.add_joined_room(JoinedRoomBuilder::new(room_id).add_timeline_bulk([
+        f.text_msg("latest").event_id(event_id!("$3")).sender(*BOB).into(),
+        f.text_msg("live").event_id(event_id!("$4")).sender(*ALICE).into(),
+        f.reaction(target_event, "👍").sender(*ALICE).into(),
+    ]));
+
+    mock_sync(&server, sync_response_builder.build_json_sync_response(), None).await;
+    let _response = client.sync_once(sync_settings.clone()).await.unwrap();
+    server.reset().await;
+
+    // Let the timeline handle the sync updates.
+    sleep(Duration::from_millis(100)).await;
+
+    // The items are merged, without duplicates.
+    let items = timeline.items().await;
+    let events = items.iter().filter_map(|item| item.as_event()).collect::<Vec<_>>();
+    let bodies = events
+        .iter()
+        .map(|event| event.content().as_message().unwrap().body().to_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(bodies, ["old", "older than live", "latest", "live"]);
+
+    // The reaction has been aggregated on the focused event.
+    let reactions = events[0].content().reactions().cloned().unwrap_or_default();
+    assert_eq!(reactions.len(), 1);
+    let _ = reactions["👍"][*ALICE];
+}
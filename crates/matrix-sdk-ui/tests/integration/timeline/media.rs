@@ -20,7 +20,7 @@ use eyeball_im::VectorDiff;
 use futures_util::StreamExt;
 #[cfg(feature = "unstable-msc4274")]
 use matrix_sdk::attachment::{AttachmentInfo, BaseFileInfo};
-use matrix_sdk::{assert_let_timeout, test_utils::mocks::MatrixMockServer};
+use matrix_sdk::{assert_let_timeout, attachment::Thumbnail, test_utils::mocks::MatrixMockServer};
 use matrix_sdk_test::{ALICE, JoinedRoomBuilder, async_test, event_factory::EventFactory};
 use matrix_sdk_ui::timeline::{
     AttachmentConfig, AttachmentSource, EventSendState, RoomExt, TimelineFocus,
@@ -34,7 +34,7 @@ use ruma::owned_mxc_uri;
 use ruma::{
     event_id,
     events::room::{MediaSource, message::MessageType},
-    room_id,
+    room_id, uint,
 };
 use serde_json::json;
 use stream_assert::assert_pending;
@@ -271,6 +271,163 @@ async fn test_send_attachment_from_bytes() {
     assert_pending!(timeline_stream);
 }
 
+#[async_test]
+async fn test_send_attachment_reports_upload_progress() {
+    let mock = MatrixMockServer::new().await;
+    let client = mock.client_builder().build().await;
+    client.send_queue().enable_upload_progress(true);
+
+    mock.mock_authenticated_media_config().ok_default().mount().await;
+    mock.mock_room_state_encryption().plain().mount().await;
+
+    let room_id = room_id!("!a98sd12bjh:example.org");
+    let room = mock.sync_joined_room(&client, room_id).await;
+    let timeline = room.timeline().await.unwrap();
+
+    let (items, mut timeline_stream) =
+        timeline.subscribe_filter_map(|item| item.as_event().cloned()).await;
+    assert!(items.is_empty());
+
+    // The thumbnail and the file are big enough to be uploaded in several chunks.
+    let file_size = 50_000;
+    let thumbnail_size = 20_000;
+    let source =
+        AttachmentSource::Data { bytes: vec![0; file_size], filename: "test.bin".to_owned() };
+    let thumbnail = Thumbnail {
+        data: vec![0; thumbnail_size],
+        content_type: mime::IMAGE_JPEG,
+        height: uint!(13),
+        width: uint!(37),
+        size: uint!(20_000),
+    };
+
+    // Throttle the uploads, so the progress can be observed.
+    mock.mock_upload()
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_delay(Duration::from_millis(500))
+                .set_body_json(json!({ "content_uri": "mxc://sdk.rs/media" })),
+        )
+        .expect(2)
+        .mount()
+        .await;
+
+    mock.mock_room_send().ok(event_id!("$media")).mock_once().mount().await;
+
+    let config = AttachmentConfig { thumbnail: Some(thumbnail), ..Default::default() };
+    timeline.send_attachment(source, mime::TEXT_PLAIN, config).use_send_queue().await.unwrap();
+
+    assert_let_timeout!(Some(VectorDiff::PushBack { value: item }) = timeline_stream.next());
+    assert_matches!(item.send_state(), Some(EventSendState::NotSentYet));
+
+    // The thumbnail and the file uploads are reported as a single, increasing
+    // progress, until the event is sent.
+    let total = file_size + thumbnail_size;
+    let mut progresses = Vec::new();
+
+    loop {
+        assert_let_timeout!(
+            Duration::from_secs(3),
+            Some(VectorDiff::Set { index: 0, value: item }) = timeline_stream.next()
+        );
+
+        match item.send_state() {
+            Some(EventSendState::Sending { progress: Some(progress) }) => {
+                assert_eq!(progress.total, total);
+                if let Some(previous) = progresses.last() {
+                    assert!(progress.current >= *previous);
+                }
+                progresses.push(progress.current);
+            }
+
+            // Once the media is uploaded, the local echo is updated with the final MXC URI.
+            Some(EventSendState::NotSentYet) => {
+                assert_eq!(progresses.last(), Some(&total));
+            }
+
+            Some(EventSendState::Sent { event_id }) => {
+                assert_eq!(event_id, event_id!("$media"));
+                break;
+            }
+
+            state => panic!("unexpected send state: {state:?}"),
+        }
+    }
+
+    // The end of the thumbnail upload and the end of the file upload have been
+    // observed.
+    assert!(progresses.contains(&thumbnail_size));
+    assert_eq!(progresses.last(), Some(&total));
+
+    // That's all, folks!
+    assert_pending!(timeline_stream);
+}
+
+#[async_test]
+async fn test_cancel_attachment_upload() {
+    let mock = MatrixMockServer::new().await;
+    let client = mock.client_builder().build().await;
+    client.send_queue().enable_upload_progress(true);
+
+    mock.mock_authenticated_media_config().ok_default().mount().await;
+    mock.mock_room_state_encryption().plain().mount().await;
+
+    let room_id = room_id!("!a98sd12bjh:example.org");
+    let room = mock.sync_joined_room(&client, room_id).await;
+    let timeline = room.timeline().await.unwrap();
+
+    let (items, mut timeline_stream) =
+        timeline.subscribe_filter_map(|item| item.as_event().cloned()).await;
+    assert!(items.is_empty());
+
+    let source = AttachmentSource::Data { bytes: vec![0; 50_000], filename: "test.bin".to_owned() };
+
+    // The upload takes a long time…
+    mock.mock_upload()
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_delay(Duration::from_secs(10))
+                .set_body_json(json!({ "content_uri": "mxc://sdk.rs/media" })),
+        )
+        .mock_once()
+        .mount()
+        .await;
+
+    // …and the event is never sent.
+    mock.mock_room_send().ok(event_id!("$media")).never().mount().await;
+
+    timeline
+        .send_attachment(source, mime::TEXT_PLAIN, AttachmentConfig::default())
+        .use_send_queue()
+        .await
+        .unwrap();
+
+    assert_let_timeout!(Some(VectorDiff::PushBack { value: item }) = timeline_stream.next());
+    assert_matches!(item.send_state(), Some(EventSendState::NotSentYet));
+
+    // Wait for the upload to start.
+    assert_let_timeout!(Some(VectorDiff::Set { index: 0, value: item }) = timeline_stream.next());
+    assert_matches!(item.send_state(), Some(EventSendState::Sending { progress: Some(_) }));
+
+    // Cancel the sending while the media is being uploaded.
+    assert!(timeline.cancel_send(&item.identifier()).await.unwrap());
+
+    // The local echo is removed.
+    loop {
+        assert_let_timeout!(Some(diff) = timeline_stream.next());
+        match diff {
+            // There may be a few progress updates still in flight.
+            VectorDiff::Set { index: 0, value } => {
+                assert_matches!(value.send_state(), Some(EventSendState::Sending { .. }));
+            }
+            VectorDiff::Remove { index: 0 } => break,
+            diff => panic!("unexpected diff: {diff:?}"),
+        }
+    }
+
+    assert!(timeline.items().await.iter().all(|item| item.as_event().is_none()));
+}
+
 #[cfg(feature = "unstable-msc4274")]
 #[async_test]
 async fn test_send_gallery_from_bytes() {
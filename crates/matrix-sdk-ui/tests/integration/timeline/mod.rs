@@ -29,8 +29,8 @@ use matrix_sdk_test::{
 use matrix_sdk_ui::{
     Timeline,
     timeline::{
-        AnyOtherFullStateEventContent, Error, EventSendState, RedactError, RoomExt,
-        TimelineBuilder, TimelineEventItemId, TimelineItemContent, VirtualTimelineItem,
+        AnyOtherFullStateEventContent, Error, EventSendState, LocalActionError, RedactError,
+        RoomExt, TimelineBuilder, TimelineEventItemId, TimelineItemContent, VirtualTimelineItem,
     },
 };
 use ruma::{
@@ -232,6 +232,21 @@ async fn test_redact_message() {
 
     timeline.redact(&first.as_event().unwrap().identifier(), Some("inapprops")).await.unwrap();
 
+    assert_let!(Some(timeline_updates) = timeline_stream.next().await);
+    assert_eq!(timeline_updates.len(), 2);
+
+    // The item is replaced with a redacted placeholder while the redaction is sent…
+    assert_let!(VectorDiff::Set { index: 1, value: item } = &timeline_updates[0]);
+    let item = item.as_event().unwrap();
+    assert!(item.content().is_redacted());
+    assert!(item.is_redaction_pending());
+
+    // …and isn't pending anymore once it's been sent.
+    assert_let!(VectorDiff::Set { index: 1, value: item } = &timeline_updates[1]);
+    let item = item.as_event().unwrap();
+    assert!(item.content().is_redacted());
+    assert!(item.is_redaction_pending().not());
+
     // Redacting a local event works.
     timeline
         .send(RoomMessageEventContent::text_plain("i will disappear soon").into())
@@ -269,6 +284,60 @@ async fn test_redact_message() {
     assert_pending!(timeline_stream);
 }
 
+#[async_test]
+async fn test_redact_message_failed() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let room_id = room_id!("!a98sd12bjh:example.org");
+    let room = server.sync_joined_room(&client, room_id).await;
+
+    server.mock_room_state_encryption().plain().mount().await;
+
+    let timeline = room.timeline().await.unwrap();
+    let (_, mut timeline_stream) = timeline.subscribe().await;
+
+    let factory = EventFactory::new();
+    server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id).add_timeline_event(
+                factory.sender(user_id!("@a:b.com")).text_msg("buy my bitcoins bro"),
+            ),
+        )
+        .await;
+
+    assert_let!(Some(timeline_updates) = timeline_stream.next().await);
+    assert_eq!(timeline_updates.len(), 2);
+
+    assert_let!(VectorDiff::PushBack { value: first } = &timeline_updates[0]);
+    let item_id = first.as_event().unwrap().identifier();
+
+    // Redacting the event fails.
+    server.mock_room_redact().error500().named("redact").mock_once().mount().await;
+
+    let error = timeline.redact(&item_id, None).await.unwrap_err();
+    assert_matches!(error, Error::RedactError(RedactError::SendFailed(_)));
+
+    assert_let!(Some(timeline_updates) = timeline_stream.next().await);
+    assert_eq!(timeline_updates.len(), 2);
+
+    // The item is replaced with a redacted placeholder while the redaction is sent…
+    assert_let!(VectorDiff::Set { index: 1, value: item } = &timeline_updates[0]);
+    let item = item.as_event().unwrap();
+    assert!(item.content().is_redacted());
+    assert!(item.is_redaction_pending());
+
+    // …then restored after the redaction failed, with the error.
+    assert_let!(VectorDiff::Set { index: 1, value: item } = &timeline_updates[1]);
+    let item = item.as_event().unwrap();
+    assert_eq!(item.content().as_message().unwrap().body(), "buy my bitcoins bro");
+    assert!(item.is_redaction_pending().not());
+    assert_matches!(item.local_action_error(), Some(LocalActionError::Redaction { .. }));
+
+    assert_pending!(timeline_stream);
+}
+
 #[async_test]
 async fn test_redact_local_sent_message() {
     let server = MatrixMockServer::new().await;
@@ -154,20 +154,13 @@ async fn test_new_pinned_event_ids_reload_the_timeline() {
         .await
         .expect("Sync failed");
 
+    // Only the newly pinned event is added.
     assert_let!(Some(timeline_updates) = timeline_stream.next().await);
-    assert_eq!(timeline_updates.len(), 4);
-
-    assert_let!(VectorDiff::Clear = &timeline_updates[0]);
-
-    assert_let!(VectorDiff::PushBack { value } = &timeline_updates[1]);
-    assert_eq!(value.as_event().unwrap().event_id().unwrap(), event_id!("$1"));
+    assert_eq!(timeline_updates.len(), 1);
 
-    assert_let!(VectorDiff::PushBack { value } = &timeline_updates[2]);
+    assert_let!(VectorDiff::Insert { index: 2, value } = &timeline_updates[0]);
     assert_eq!(value.as_event().unwrap().event_id().unwrap(), event_id!("$2"));
 
-    assert_let!(VectorDiff::PushFront { value } = &timeline_updates[3]);
-    assert!(value.is_date_divider());
-
     assert_pending!(timeline_stream);
 
     // Reload timeline with no pinned event
@@ -178,13 +171,97 @@ async fn test_new_pinned_event_ids_reload_the_timeline() {
         .await
         .expect("Sync failed");
 
+    // The events are removed, along with the date divider.
     assert_let!(Some(timeline_updates) = timeline_stream.next().await);
-    assert_eq!(timeline_updates.len(), 1);
-    assert_let!(VectorDiff::Clear = &timeline_updates[0]);
+    assert_eq!(timeline_updates.len(), 3);
+    assert_let!(VectorDiff::Remove { index: 2 } = &timeline_updates[0]);
+    assert_let!(VectorDiff::Remove { index: 1 } = &timeline_updates[1]);
+    assert_let!(VectorDiff::Remove { index: 0 } = &timeline_updates[2]);
 
     assert_pending!(timeline_stream);
 }
 
+#[async_test]
+async fn test_pinned_events_are_ordered_as_pinned_and_updated() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+    let room_id = room_id!("!test:localhost");
+
+    let f = EventFactory::new().room(room_id).sender(*BOB);
+    let event_1 = f
+        .text_msg("in the end")
+        .event_id(event_id!("$1"))
+        .server_ts(MilliSecondsSinceUnixEpoch::now())
+        .into_raw_sync();
+    let event_2 = f
+        .text_msg("it doesn't even matter")
+        .event_id(event_id!("$2"))
+        .server_ts(MilliSecondsSinceUnixEpoch::now())
+        .into_raw_sync();
+
+    // `$2` has been pinned before `$1`.
+    mock_events_endpoint(&server, room_id, vec![event_1, event_2]).await;
+    let room = PinnedEventsSync::new(room_id)
+        .with_pinned_event_ids(vec!["$2", "$1"])
+        .mock_and_sync(&client, &server)
+        .await
+        .expect("Sync failed");
+
+    let timeline = TimelineBuilder::new(&room).pinned_events().build().await.unwrap();
+
+    let (items, mut timeline_stream) = timeline.subscribe().await;
+
+    // The items are in the order of the pinned events.
+    assert_eq!(items.len(), 2 + 1);
+    assert!(items[0].is_date_divider());
+    assert_eq!(items[1].as_event().unwrap().event_id().unwrap(), event_id!("$2"));
+    assert_eq!(items[2].as_event().unwrap().event_id().unwrap(), event_id!("$1"));
+    assert_pending!(timeline_stream);
+
+    // `$1` is edited.
+    let edited_event = f
+        .text_msg("* edited message!")
+        .edit(
+            event_id!("$1"),
+            RoomMessageEventContentWithoutRelation::text_plain("edited message!"),
+        )
+        .event_id(event_id!("$3"))
+        .server_ts(MilliSecondsSinceUnixEpoch::now())
+        .into_raw_sync();
+
+    mock_events_endpoint(&server, room_id, vec![edited_event.clone()]).await;
+    let _ = PinnedEventsSync::new(room_id)
+        .with_timeline_events(vec![edited_event])
+        .mock_and_sync(&client, &server)
+        .await
+        .expect("Sync failed");
+
+    assert_let!(Some(timeline_updates) = timeline_stream.next().await);
+    assert_eq!(timeline_updates.len(), 1);
+    assert_let!(VectorDiff::Set { index: 2, value } = &timeline_updates[0]);
+    assert_eq!(value.as_event().unwrap().content().as_message().unwrap().body(), "edited message!");
+    assert_pending!(timeline_stream);
+
+    // `$2` is unpinned.
+    let _ = PinnedEventsSync::new(room_id)
+        .with_pinned_event_ids(vec!["$1"])
+        .mock_and_sync(&client, &server)
+        .await
+        .expect("Sync failed");
+
+    // Its item is removed, the timeline isn't reloaded.
+    assert_let!(Some(timeline_updates) = timeline_stream.next().await);
+    assert_let!(VectorDiff::Remove { index: 1 } = &timeline_updates[0]);
+    assert!(!timeline_updates.iter().any(|diff| matches!(diff, VectorDiff::Clear)));
+
+    // The edited event is still there.
+    let items = timeline.items().await;
+    assert_eq!(items.len(), 1 + 1);
+    let event = items[1].as_event().unwrap();
+    assert_eq!(event.event_id().unwrap(), event_id!("$1"));
+    assert_eq!(event.content().as_message().unwrap().body(), "edited message!");
+}
+
 #[async_test]
 async fn test_max_events_to_load_is_honored() {
     let server = MatrixMockServer::new().await;
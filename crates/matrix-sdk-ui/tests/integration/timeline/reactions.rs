@@ -19,7 +19,7 @@ use eyeball_im::VectorDiff;
 use futures_util::StreamExt as _;
 use matrix_sdk::{assert_let_timeout, test_utils::mocks::MatrixMockServer};
 use matrix_sdk_test::{ALICE, JoinedRoomBuilder, async_test, event_factory::EventFactory};
-use matrix_sdk_ui::timeline::{EventSendState, ReactionStatus, RoomExt as _};
+use matrix_sdk_ui::timeline::{EventSendState, LocalActionError, ReactionStatus, RoomExt as _};
 use ruma::{event_id, events::room::message::RoomMessageEventContent, room_id};
 use serde_json::json;
 use stream_assert::assert_pending;
@@ -441,3 +441,189 @@ async fn test_local_reaction_to_local_echo() {
     tokio::time::sleep(Duration::from_millis(150)).await;
     assert_pending!(stream);
 }
+
+#[async_test]
+async fn test_reaction_send_failed() {
+    // This test checks that if sending a reaction failed, then the reaction is
+    // removed after having been displayed, and the error is surfaced on the item.
+
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+    let user_id = client.user_id().unwrap();
+
+    let room_id = room_id!("!a98sd12bjh:example.org");
+    let room = server.sync_joined_room(&client, room_id).await;
+
+    server.mock_room_state_encryption().plain().mount().await;
+
+    let timeline = room.timeline().await.unwrap();
+    let (initial_items, mut stream) = timeline.subscribe().await;
+
+    assert!(initial_items.is_empty());
+
+    let f = EventFactory::new();
+
+    let event_id = event_id!("$1");
+    server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id)
+                .add_timeline_event(f.text_msg("hello").sender(&ALICE).event_id(event_id)),
+        )
+        .await;
+
+    let item_id = {
+        assert_let_timeout!(Some(timeline_updates) = stream.next());
+        assert_eq!(timeline_updates.len(), 2);
+
+        assert_let!(VectorDiff::PushBack { value: item } = &timeline_updates[0]);
+        item.as_event().unwrap().identifier()
+    };
+
+    // Sending the reaction fails with a permanent error, after a delay.
+    server
+        .mock_room_send()
+        .respond_with(
+            ResponseTemplate::new(413)
+                .set_body_json(json!({ "errcode": "M_TOO_LARGE" }))
+                .set_delay(Duration::from_millis(150)),
+        )
+        .mock_once()
+        .named("send for the reaction")
+        .mount()
+        .await;
+
+    timeline.toggle_reaction(&item_id, "👍").await.unwrap();
+
+    // The reaction is added as a local echo…
+    {
+        assert_let_timeout!(Some(timeline_updates) = stream.next());
+        assert_eq!(timeline_updates.len(), 1);
+
+        assert_let!(VectorDiff::Set { index: 1, value: item } = &timeline_updates[0]);
+        let item = item.as_event().unwrap();
+        let reactions = item.content().reactions().cloned().unwrap_or_default();
+        assert_matches!(
+            &reactions.get("👍").unwrap().get(user_id).unwrap().status,
+            ReactionStatus::LocalToRemote(_)
+        );
+        assert!(item.local_action_error().is_none());
+    }
+
+    // …then removed, after sending it failed.
+    {
+        assert_let_timeout!(Some(timeline_updates) = stream.next());
+        assert_eq!(timeline_updates.len(), 1);
+
+        assert_let!(VectorDiff::Set { index: 1, value: item } = &timeline_updates[0]);
+        let item = item.as_event().unwrap();
+        assert!(item.content().reactions().cloned().unwrap_or_default().is_empty());
+        assert_let!(Some(LocalActionError::Reaction { key, .. }) = item.local_action_error());
+        assert_eq!(key, "👍");
+    }
+
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert_pending!(stream);
+}
+
+#[async_test]
+async fn test_local_reaction_to_local_echo_send_failed() {
+    // This test checks that a reaction to an event that's still in the send queue
+    // is rolled back if sending it failed, once the event itself has been sent.
+
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+    let user_id = client.user_id().unwrap();
+
+    let room_id = room_id!("!a98sd12bjh:example.org");
+    let room = server.sync_joined_room(&client, room_id).await;
+
+    server.mock_room_state_encryption().plain().mount().await;
+
+    let timeline = room.timeline().await.unwrap();
+    let (initial_items, mut stream) = timeline.subscribe().await;
+
+    assert!(initial_items.is_empty());
+
+    // The event is sent after a delay, so we can react to it while it's queued…
+    server
+        .mock_room_send()
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({ "event_id": "$0" }))
+                .set_delay(Duration::from_millis(300)),
+        )
+        .mock_once()
+        .named("send for the event")
+        .mount()
+        .await;
+
+    // …but sending the reaction fails with a permanent error.
+    server
+        .mock_room_send()
+        .respond_with(
+            ResponseTemplate::new(413)
+                .set_body_json(json!({ "errcode": "M_TOO_LARGE" }))
+                .set_delay(Duration::from_millis(150)),
+        )
+        .mock_once()
+        .named("send for the reaction")
+        .mount()
+        .await;
+
+    timeline.send(RoomMessageEventContent::text_plain("lol").into()).await.unwrap();
+
+    let item_id = {
+        assert_let_timeout!(Some(timeline_updates) = stream.next());
+        assert_eq!(timeline_updates.len(), 2);
+
+        assert_let!(VectorDiff::PushBack { value: item } = &timeline_updates[0]);
+        let item = item.as_event().unwrap();
+        assert!(item.is_local_echo());
+        assert_matches!(item.send_state(), Some(EventSendState::NotSentYet));
+
+        item.identifier()
+    };
+
+    let key = "🤣";
+    timeline.toggle_reaction(&item_id, key).await.unwrap();
+
+    // The reaction is added to the local echo.
+    {
+        assert_let_timeout!(Some(timeline_updates) = stream.next());
+        assert_eq!(timeline_updates.len(), 1);
+
+        assert_let!(VectorDiff::Set { index: 1, value: item } = &timeline_updates[0]);
+        let reactions = item.as_event().unwrap().content().reactions().cloned().unwrap_or_default();
+        let reaction_info = reactions.get(key).unwrap().get(user_id).unwrap();
+        assert_matches!(&reaction_info.status, ReactionStatus::LocalToLocal(..));
+    }
+
+    // The event is sent, and still has the reaction.
+    {
+        assert_let_timeout!(Duration::from_secs(2), Some(timeline_updates) = stream.next());
+        assert_eq!(timeline_updates.len(), 1);
+
+        assert_let!(VectorDiff::Set { index: 1, value: item } = &timeline_updates[0]);
+        let item = item.as_event().unwrap();
+        assert_matches!(item.send_state(), Some(EventSendState::Sent { .. }));
+        assert_eq!(item.content().reactions().cloned().unwrap_or_default().len(), 1);
+    }
+
+    // Then sending the reaction fails, and it's removed from the item.
+    {
+        assert_let_timeout!(Duration::from_secs(2), Some(timeline_updates) = stream.next());
+        assert_eq!(timeline_updates.len(), 1);
+
+        assert_let!(VectorDiff::Set { index: 1, value: item } = &timeline_updates[0]);
+        let item = item.as_event().unwrap();
+        assert!(item.content().reactions().cloned().unwrap_or_default().is_empty());
+        assert_let!(
+            Some(LocalActionError::Reaction { key: failed_key, .. }) = item.local_action_error()
+        );
+        assert_eq!(failed_key, key);
+    }
+
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert_pending!(stream);
+}
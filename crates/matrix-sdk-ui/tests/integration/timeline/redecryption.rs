@@ -12,10 +12,13 @@ use ruma::{device_id, event_id, room_id, user_id};
 use serde_json::{Value, json};
 
 // Helper function to test the redecryption of different event types.
+//
+// The assertion function is called with the UTD item, and the update that
+// followed the reception of the room key.
 async fn test_redecryption(
     event_type: &str,
     content: Value,
-    assertion_function: impl FnOnce(&VectorDiff<Arc<TimelineItem>>),
+    assertion_function: impl FnOnce(&Arc<TimelineItem>, &VectorDiff<Arc<TimelineItem>>),
 ) {
     let room_id = room_id!("!test:localhost");
 
@@ -131,9 +134,9 @@ async fn test_redecryption(
     let updates = assert_next_with_timeout!(stream);
     let utd_item = &updates[0];
 
-    assert_matches!(utd_item, VectorDiff::PushBack { value });
+    assert_matches!(utd_item, VectorDiff::PushBack { value: utd_item });
     assert!(
-        value.as_event().unwrap().content().is_unable_to_decrypt(),
+        utd_item.as_event().unwrap().content().is_unable_to_decrypt(),
         "Initially we should receive a UTD"
     );
 
@@ -153,7 +156,7 @@ async fn test_redecryption(
 
     // Let us run the assertion function with the decrypted item provided by the
     // caller.
-    assertion_function(decrypted_item);
+    assertion_function(utd_item, decrypted_item);
 }
 
 // Test ensuring that a late room key triggers a redecryption and the timeline
@@ -163,7 +166,7 @@ async fn test_redecryption_after_late_to_device() {
     test_redecryption(
         "m.room.message",
         json!({"body": "It's a secret to everybody", "msgtype": "m.text"}),
-        |decrypted_item| {
+        |_utd_item, decrypted_item| {
             assert_matches!(decrypted_item, VectorDiff::Set { index: _, value });
 
             let event = value.as_event().expect("The value should be an event");
@@ -185,9 +188,37 @@ async fn test_redecryption_after_late_to_device_unsupported_event_type() {
     test_redecryption(
         "rust-sdk.custom.event",
         json!({"body": "It's a secret to everybody"}),
-        |decrypted_item| {
+        |_utd_item, decrypted_item| {
             assert_matches!(decrypted_item, VectorDiff::Remove { index: _ });
         },
     )
     .await;
 }
+
+// Test ensuring that a UTD decrypted after a late room key is updated in place,
+// and records how long it took to decrypt it.
+#[async_test]
+async fn test_redecryption_updates_the_utd_item_in_place() {
+    test_redecryption(
+        "m.room.message",
+        json!({"body": "It's a secret to everybody", "msgtype": "m.text"}),
+        |utd_item, decrypted_item| {
+            let utd_event = utd_item.as_event().unwrap();
+            assert!(utd_event.content().as_unable_to_decrypt().is_some());
+            assert!(utd_event.time_to_decrypt().is_none());
+
+            // The date divider has been inserted before the UTD.
+            assert_matches!(decrypted_item, VectorDiff::Set { index: 1, value });
+            assert_eq!(value.unique_id(), utd_item.unique_id());
+
+            let event = value.as_event().expect("The value should be an event");
+            assert_eq!(event.event_id(), utd_event.event_id());
+            assert_eq!(
+                event.content().as_message().expect("The event should be decrypted").body(),
+                "It's a secret to everybody"
+            );
+            assert!(event.time_to_decrypt().is_some());
+        },
+    )
+    .await;
+}
@@ -59,11 +59,9 @@ async fn test_new_empty_thread() {
 
     let room = server.sync_joined_room(&client, room_id).await;
 
-    let timeline = TimelineBuilder::new(&room)
-        .with_focus(TimelineFocus::Thread { root_event_id: thread_root_event_id })
-        .build()
-        .await
-        .unwrap();
+    let timeline =
+        TimelineBuilder::new(&room).thread(thread_root_event_id.clone()).build().await.unwrap();
+    assert_eq!(timeline.thread_root(), Some(thread_root_event_id));
 
     let (items, mut timeline_stream) = timeline.subscribe().await;
 
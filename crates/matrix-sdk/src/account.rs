@@ -14,28 +14,44 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Debug,
+    sync::Arc,
+    time::Duration,
+};
+
+use eyeball::SharedObservable;
 use futures_core::Stream;
-use futures_util::{stream, StreamExt};
+use futures_util::{future::ready, stream, StreamExt};
 use matrix_sdk_base::{
+    deserialized_responses::TimelineEvent,
     media::{MediaFormat, MediaRequestParameters},
     store::StateStoreExt,
-    StateStoreDataKey, StateStoreDataValue,
+    BaseClient, RoomState, StateChanges, StateStoreDataKey, StateStoreDataValue,
 };
+use matrix_sdk_common::executor::spawn;
 use mime::Mime;
 use ruma::{
-    api::client::{
-        account::{
-            add_3pid, change_password, deactivate, delete_3pid, get_3pids,
-            request_3pid_management_token_via_email, request_3pid_management_token_via_msisdn,
+    api::{
+        client::{
+            account::{
+                add_3pid, bind_3pid, change_password, deactivate, delete_3pid, get_3pids,
+                request_3pid_management_token_via_email, request_3pid_management_token_via_msisdn,
+                request_openid_token, unbind_3pid, IdentityServerInfo, ThirdPartyIdRemovalStatus,
+            },
+            config::{get_global_account_data, set_global_account_data},
+            error::{ErrorBody, ErrorKind},
+            profile::{
+                get_avatar_url, get_display_name, get_profile, set_avatar_url, set_display_name,
+            },
+            uiaa::{self, AuthData, AuthType, UiaaResponse, UserIdentifier},
         },
-        config::{get_global_account_data, set_global_account_data},
-        error::ErrorKind,
-        profile::{
-            get_avatar_url, get_display_name, get_profile, set_avatar_url, set_display_name,
-        },
-        uiaa::AuthData,
+        error::FromHttpResponseError,
+        EndpointError, OutgoingRequest,
     },
     assign,
+    authentication::TokenType,
     events::{
         ignored_user_list::{IgnoredUser, IgnoredUserListEventContent},
         media_preview_config::{
@@ -43,19 +59,166 @@ use ruma::{
             UnstableMediaPreviewConfigEventContent,
         },
         push_rules::PushRulesEventContent,
-        room::MediaSource,
-        AnyGlobalAccountDataEventContent, GlobalAccountDataEvent, GlobalAccountDataEventContent,
-        GlobalAccountDataEventType, StaticEventContent,
+        room::{
+            member::{MembershipState, SyncRoomMemberEvent},
+            MediaSource,
+        },
+        secret_storage::{
+            default_key::SecretStorageDefaultKeyEventContent, key::SecretStorageKeyEventContent,
+        },
+        AnyGlobalAccountDataEvent, AnyGlobalAccountDataEventContent, AnySyncStateEvent,
+        GlobalAccountDataEvent, GlobalAccountDataEventContent, GlobalAccountDataEventType,
+        StaticEventContent,
     },
     push::Ruleset,
     serde::Raw,
-    thirdparty::Medium,
-    ClientSecret, MxcUri, OwnedMxcUri, OwnedRoomId, OwnedUserId, RoomId, SessionId, UInt, UserId,
+    thirdparty::{Medium, ThirdPartyIdentifier},
+    time::Instant,
+    CanonicalJsonValue, ClientSecret, MxcUri, OwnedDeviceId, OwnedMxcUri, OwnedRoomId,
+    OwnedServerName, OwnedUserId, RoomId, SessionId, UInt, UserId,
+};
+use serde::{
+    de::{DeserializeOwned, IgnoredAny},
+    Deserialize, Serialize,
 };
-use serde::Deserialize;
-use tracing::error;
+use serde_json::{json, value::RawValue as RawJsonValue, Value as JsonValue};
+use tokio::sync::Mutex;
+use tracing::{debug, error, warn};
+use url::Url;
+
+use crate::{
+    config::RequestConfig,
+    image_pack::ImagePacks,
+    invite_filter::{InvitePolicy, INVITE_POLICY_EVENT_TYPE},
+    notification_settings::PushRulesDiff,
+    sleep::sleep,
+    Client, Error, HttpError, NotificationSettingsError, Result, RumaApiError,
+    TransmissionProgress,
+};
+
+/// The profile of the logged-in user, as last observed by the client.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct OwnProfile {
+    pub display_name: Option<String>,
+    pub avatar_url: Option<OwnedMxcUri>,
+}
+
+impl OwnProfile {
+    fn into_parts(self) -> (Option<String>, Option<OwnedMxcUri>) {
+        (self.display_name, self.avatar_url)
+    }
+}
+
+/// The media preview configuration of the account, with the default values
+/// applied to the policies that are not set.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EffectiveMediaPreviewConfig {
+    /// The policy for displaying media previews in the timeline.
+    ///
+    /// Defaults to [`MediaPreviews::On`].
+    pub media_previews: MediaPreviews,
+
+    /// The policy for displaying avatars in invites.
+    ///
+    /// Defaults to [`InviteAvatars::On`].
+    pub invite_avatars: InviteAvatars,
+}
+
+impl Default for EffectiveMediaPreviewConfig {
+    fn default() -> Self {
+        Self { media_previews: MediaPreviews::On, invite_avatars: InviteAvatars::On }
+    }
+}
+
+impl From<MediaPreviewConfigEventContent> for EffectiveMediaPreviewConfig {
+    fn from(content: MediaPreviewConfigEventContent) -> Self {
+        let default = Self::default();
+        Self {
+            media_previews: content.media_previews.unwrap_or(default.media_previews),
+            invite_avatars: content.invite_avatars.unwrap_or(default.invite_avatars),
+        }
+    }
+}
+
+/// Information about the owner of the access token of the client, as returned
+/// by [`Account::whoami()`].
+#[derive(Clone, Debug)]
+pub struct WhoAmI {
+    /// The user ID the access token belongs to.
+    pub user_id: OwnedUserId,
+
+    /// The device ID the access token belongs to, if the access token is
+    /// associated with a device.
+    pub device_id: Option<OwnedDeviceId>,
+
+    /// Whether the user is a guest user.
+    pub is_guest: bool,
+}
+
+/// An OpenID token, allowing a third party to verify the identity of the
+/// user, as returned by [`Account::request_openid_token()`].
+#[derive(Clone, Debug)]
+pub struct OpenIdToken {
+    /// The access token to give to the third party.
+    pub access_token: String,
+
+    /// The type of the access token.
+    pub token_type: TokenType,
+
+    /// The homeserver domain the third party should use to verify the token.
+    pub matrix_server_name: OwnedServerName,
+
+    /// The remaining validity of the token.
+    pub expires_in: Duration,
+}
+
+/// The OpenID token cached by [`Account::request_openid_token()`].
+#[derive(Debug)]
+pub(crate) struct OpenIdTokenCache {
+    /// The last requested token, with the time it was received.
+    token: Option<(OpenIdToken, Instant)>,
+
+    /// The token is renewed when it expires in less than this duration.
+    expiry_margin: Duration,
+}
+
+impl Default for OpenIdTokenCache {
+    fn default() -> Self {
+        Self { token: None, expiry_margin: Account::OPENID_TOKEN_EXPIRY_MARGIN }
+    }
+}
+
+impl OpenIdTokenCache {
+    /// Get the cached token, if it is still valid for longer than the expiry
+    /// margin.
+    fn get(&self) -> Option<OpenIdToken> {
+        let (token, received_at) = self.token.as_ref()?;
+        let expires_in = token.expires_in.checked_sub(received_at.elapsed())?;
+
+        (expires_in > self.expiry_margin).then(|| OpenIdToken { expires_in, ..token.clone() })
+    }
+}
+
+/// The in-memory copy of the recently visited rooms of the user.
+///
+/// See [`Account::track_recently_visited_room()`].
+#[derive(Debug)]
+pub(crate) struct RecentlyVisitedRooms {
+    /// The rooms, most recent first, if they have been loaded from the store.
+    rooms: Option<Vec<OwnedRoomId>>,
+
+    /// Whether a task will write the rooms to the store.
+    has_pending_write: bool,
+
+    /// The maximum number of rooms to keep.
+    limit: usize,
+}
 
-use crate::{config::RequestConfig, Client, Error, Result};
+impl Default for RecentlyVisitedRooms {
+    fn default() -> Self {
+        Self { rooms: None, has_pending_write: false, limit: Account::VISITED_ROOMS_LIMIT }
+    }
+}
 
 /// A high-level API to manage the client owner's account.
 ///
@@ -67,10 +230,18 @@ pub struct Account {
 }
 
 impl Account {
-    /// The maximum number of visited room identifiers to keep in the state
-    /// store.
+    /// The default maximum number of visited room identifiers to keep in the
+    /// state store.
     const VISITED_ROOMS_LIMIT: usize = 20;
 
+    /// The default margin before the expiry of the OpenID token, at which it
+    /// is renewed.
+    const OPENID_TOKEN_EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+    /// The delay before writing the recently visited rooms to the store, to
+    /// batch the writes of rapid room switches.
+    const VISITED_ROOMS_WRITE_DELAY: Duration = Duration::from_millis(300);
+
     pub(crate) fn new(client: Client) -> Self {
         Self { client }
     }
@@ -98,6 +269,7 @@ impl Account {
         let request = get_display_name::v3::Request::new(user_id.to_owned());
         let request_config = self.client.request_config().force_auth();
         let response = self.client.send(request).with_request_config(request_config).await?;
+        self.update_own_profile(|profile| profile.display_name = response.displayname.clone());
         Ok(response.displayname)
     }
 
@@ -122,6 +294,7 @@ impl Account {
         let request =
             set_display_name::v3::Request::new(user_id.to_owned(), name.map(ToOwned::to_owned));
         self.client.send(request).await?;
+        self.update_own_profile(|profile| profile.display_name = name.map(ToOwned::to_owned));
         Ok(())
     }
 
@@ -154,24 +327,8 @@ impl Account {
         let config = Some(RequestConfig::new().force_auth());
 
         let response = self.client.send(request).with_request_config(config).await?;
-        if let Some(url) = response.avatar_url.clone() {
-            // If an avatar is found cache it.
-            let _ = self
-                .client
-                .state_store()
-                .set_kv_data(
-                    StateStoreDataKey::UserAvatarUrl(user_id),
-                    StateStoreDataValue::UserAvatarUrl(url),
-                )
-                .await;
-        } else {
-            // If there is no avatar the user has removed it and we uncache it.
-            let _ = self
-                .client
-                .state_store()
-                .remove_kv_data(StateStoreDataKey::UserAvatarUrl(user_id))
-                .await;
-        }
+        self.cache_avatar_url(user_id, response.avatar_url.as_deref()).await;
+        self.update_own_profile(|profile| profile.avatar_url = response.avatar_url.clone());
         Ok(response.avatar_url)
     }
 
@@ -194,6 +351,8 @@ impl Account {
         let request =
             set_avatar_url::v3::Request::new(user_id.to_owned(), url.map(ToOwned::to_owned));
         self.client.send(request).await?;
+        self.cache_avatar_url(user_id, url).await;
+        self.update_own_profile(|profile| profile.avatar_url = url.map(ToOwned::to_owned));
         Ok(())
     }
 
@@ -204,6 +363,11 @@ impl Account {
     /// If a thumbnail is requested no guarantee on the size of the image is
     /// given.
     ///
+    /// If the avatar was already downloaded in the same format, it is loaded
+    /// from the media cache, using the cached avatar URL, without any request.
+    /// Use [`Account::get_avatar_no_cache()`] to always get the latest avatar
+    /// from the homeserver.
+    ///
     /// # Arguments
     ///
     /// * `format` - The desired format of the avatar.
@@ -228,9 +392,41 @@ impl Account {
     /// # anyhow::Ok(()) };
     /// ```
     pub async fn get_avatar(&self, format: MediaFormat) -> Result<Option<Vec<u8>>> {
+        if let Some(url) = self.get_cached_avatar_url().await? {
+            let request =
+                MediaRequestParameters { source: MediaSource::Plain(url), format: format.clone() };
+            let content =
+                self.client.event_cache_store().lock().await?.get_media_content(&request).await?;
+
+            if content.is_some() {
+                return Ok(content);
+            }
+        }
+
+        self.get_avatar_inner(format, true).await
+    }
+
+    /// Get the account's avatar, if set, without using the cached avatar URL
+    /// and media.
+    ///
+    /// The downloaded avatar is still stored in the media cache, for the next
+    /// calls to [`Account::get_avatar()`].
+    ///
+    /// # Arguments
+    ///
+    /// * `format` - The desired format of the avatar.
+    pub async fn get_avatar_no_cache(&self, format: MediaFormat) -> Result<Option<Vec<u8>>> {
+        self.get_avatar_inner(format, false).await
+    }
+
+    async fn get_avatar_inner(
+        &self,
+        format: MediaFormat,
+        use_cache: bool,
+    ) -> Result<Option<Vec<u8>>> {
         if let Some(url) = self.get_avatar_url().await? {
             let request = MediaRequestParameters { source: MediaSource::Plain(url), format };
-            Ok(Some(self.client.media().get_media_content(&request, true).await?))
+            Ok(Some(self.client.media().get_media_content(&request, use_cache).await?))
         } else {
             Ok(None)
         }
@@ -269,209 +465,815 @@ impl Account {
         Ok(upload_response.content_uri)
     }
 
-    /// Get the profile of the account.
+    /// Upload and set the account's avatar, reporting the progress of the
+    /// upload.
     ///
-    /// Allows to get both the display name and avatar URL in a single call.
+    /// This is the same as [`Account::upload_avatar()`], except that the
+    /// number of bytes sent and the total size of the upload are reported in
+    /// the given observable while the content is being uploaded.
+    ///
+    /// Returns the MXC URI of the uploaded avatar.
     ///
     /// # Examples
     ///
     /// ```no_run
+    /// # use std::fs;
+    /// # use eyeball::SharedObservable;
     /// # use matrix_sdk::Client;
     /// # use url::Url;
     /// # async {
     /// # let homeserver = Url::parse("http://localhost:8080")?;
     /// # let client = Client::new(homeserver).await?;
-    /// let profile = client.account().fetch_user_profile().await?;
-    /// println!(
-    ///     "You are '{:?}' with avatar '{:?}'",
-    ///     profile.displayname, profile.avatar_url
-    /// );
-    /// # anyhow::Ok(()) };
-    /// ```
-    pub async fn fetch_user_profile(&self) -> Result<get_profile::v3::Response> {
-        let user_id = self.client.user_id().ok_or(Error::AuthenticationRequired)?;
-        self.fetch_user_profile_of(user_id).await
-    }
-
-    /// Get the profile for a given user id
+    /// let image = fs::read("/home/example/selfie.jpg")?;
+    /// let progress = SharedObservable::new(Default::default());
     ///
-    /// # Arguments
+    /// let mut progress_subscriber = progress.subscribe();
+    /// tokio::spawn(async move {
+    ///     while let Some(progress) = progress_subscriber.next().await {
+    ///         println!("Sent {} of {} bytes", progress.current, progress.total);
+    ///     }
+    /// });
     ///
-    /// * `user_id` the matrix id this function downloads the profile for
-    pub async fn fetch_user_profile_of(
+    /// client
+    ///     .account()
+    ///     .upload_avatar_with_progress(&mime::IMAGE_JPEG, image, progress)
+    ///     .await?;
+    /// # anyhow::Ok(()) };
+    /// ```
+    pub async fn upload_avatar_with_progress(
         &self,
-        user_id: &UserId,
-    ) -> Result<get_profile::v3::Response> {
-        let request = get_profile::v3::Request::new(user_id.to_owned());
-        Ok(self
+        content_type: &Mime,
+        data: Vec<u8>,
+        progress: SharedObservable<TransmissionProgress>,
+    ) -> Result<OwnedMxcUri> {
+        let upload_response = self
             .client
-            .send(request)
-            .with_request_config(RequestConfig::short_retry().force_auth())
-            .await?)
+            .media()
+            .upload(content_type, data, None)
+            .with_send_progress_observable(progress)
+            .await?;
+        self.set_avatar_url(Some(&upload_response.content_uri)).await?;
+        Ok(upload_response.content_uri)
     }
 
-    /// Change the password of the account.
-    ///
-    /// # Arguments
-    ///
-    /// * `new_password` - The new password to set.
-    ///
-    /// * `auth_data` - This request uses the [User-Interactive Authentication
-    ///   API][uiaa]. The first request needs to set this to `None` and will
-    ///   always fail with an [`UiaaResponse`]. The response will contain
-    ///   information for the interactive auth and the same request needs to be
-    ///   made but this time with some `auth_data` provided.
+    /// Upload and set the account's avatar, streaming its content from the
+    /// given reader.
     ///
-    /// # Returns
+    /// This is a convenience method for calling
+    /// [`Media::upload_from_reader()`], followed by
+    /// [`Account::set_avatar_url()`]. The avatar isn't set if reading the
+    /// content fails.
     ///
-    /// This method might return an [`ErrorKind::WeakPassword`] error if the new
-    /// password is considered insecure by the homeserver, with details about
-    /// the strength requirements in the error's message.
+    /// Returns the MXC URI of the uploaded avatar.
     ///
     /// # Examples
     ///
     /// ```no_run
     /// # use matrix_sdk::Client;
-    /// # use matrix_sdk::ruma::{
-    /// #     api::client::{
-    /// #         account::change_password::v3::{Request as ChangePasswordRequest},
-    /// #         uiaa::{AuthData, Dummy},
-    /// #     },
-    /// #     assign,
-    /// # };
     /// # use url::Url;
     /// # async {
     /// # let homeserver = Url::parse("http://localhost:8080")?;
     /// # let client = Client::new(homeserver).await?;
-    /// client.account().change_password(
-    ///     "myverysecretpassword",
-    ///     Some(AuthData::Dummy(Dummy::new())),
-    /// ).await?;
+    /// let file = tokio::fs::File::open("/home/example/selfie.jpg").await?;
+    /// let content_length = file.metadata().await?.len();
+    ///
+    /// client
+    ///     .account()
+    ///     .upload_avatar_from_reader(
+    ///         &mime::IMAGE_JPEG,
+    ///         file,
+    ///         Some(content_length),
+    ///     )
+    ///     .await?;
     /// # anyhow::Ok(()) };
     /// ```
-    /// [uiaa]: https://spec.matrix.org/v1.2/client-server-api/#user-interactive-authentication-api
-    /// [`UiaaResponse`]: ruma::api::client::uiaa::UiaaResponse
-    /// [`ErrorKind::WeakPassword`]: ruma::api::client::error::ErrorKind::WeakPassword
-    pub async fn change_password(
+    ///
+    /// [`Media::upload_from_reader()`]: crate::Media::upload_from_reader
+    #[cfg(not(target_family = "wasm"))]
+    pub async fn upload_avatar_from_reader<R>(
         &self,
-        new_password: &str,
-        auth_data: Option<AuthData>,
-    ) -> Result<change_password::v3::Response> {
-        let request = assign!(change_password::v3::Request::new(new_password.to_owned()), {
-            auth: auth_data,
-        });
-        Ok(self.client.send(request).await?)
+        content_type: &Mime,
+        reader: R,
+        content_length: Option<u64>,
+    ) -> Result<OwnedMxcUri>
+    where
+        R: tokio::io::AsyncRead + Send + Unpin + 'static,
+    {
+        let upload_response =
+            self.client.media().upload_from_reader(content_type, reader, content_length).await?;
+        self.set_avatar_url(Some(&upload_response.content_uri)).await?;
+        Ok(upload_response.content_uri)
     }
 
-    /// Deactivate this account definitively.
-    ///
-    /// # Arguments
-    ///
-    /// * `id_server` - The identity server from which to unbind the user’s
-    ///   [Third Party Identifiers][3pid].
-    ///
-    /// * `auth_data` - This request uses the [User-Interactive Authentication
-    ///   API][uiaa]. The first request needs to set this to `None` and will
-    ///   always fail with an [`UiaaResponse`]. The response will contain
-    ///   information for the interactive auth and the same request needs to be
-    ///   made but this time with some `auth_data` provided.
+    /// Get the profile of the account.
     ///
-    /// * `erase` - Whether the user would like their content to be erased as
-    ///   much as possible from the server.
+    /// Allows to get both the display name and avatar URL in a single call.
     ///
     /// # Examples
     ///
     /// ```no_run
     /// # use matrix_sdk::Client;
-    /// # use matrix_sdk::ruma::{
-    /// #     api::client::{
-    /// #         account::change_password::v3::{Request as ChangePasswordRequest},
-    /// #         uiaa::{AuthData, Dummy},
-    /// #     },
-    /// #     assign,
-    /// # };
     /// # use url::Url;
     /// # async {
     /// # let homeserver = Url::parse("http://localhost:8080")?;
     /// # let client = Client::new(homeserver).await?;
-    /// # let account = client.account();
-    /// let response = account.deactivate(None, None, false).await;
-    ///
-    /// // Proceed with UIAA.
+    /// let profile = client.account().fetch_user_profile().await?;
+    /// println!(
+    ///     "You are '{:?}' with avatar '{:?}'",
+    ///     profile.displayname, profile.avatar_url
+    /// );
     /// # anyhow::Ok(()) };
     /// ```
-    /// [3pid]: https://spec.matrix.org/v1.2/appendices/#3pid-types
-    /// [uiaa]: https://spec.matrix.org/v1.2/client-server-api/#user-interactive-authentication-api
-    /// [`UiaaResponse`]: ruma::api::client::uiaa::UiaaResponse
-    pub async fn deactivate(
-        &self,
-        id_server: Option<&str>,
-        auth_data: Option<AuthData>,
-        erase_data: bool,
-    ) -> Result<deactivate::v3::Response> {
-        let request = assign!(deactivate::v3::Request::new(), {
-            id_server: id_server.map(ToOwned::to_owned),
-            auth: auth_data,
-            erase: erase_data,
+    pub async fn fetch_user_profile(&self) -> Result<get_profile::v3::Response> {
+        let user_id = self.client.user_id().ok_or(Error::AuthenticationRequired)?;
+        let response = self.fetch_user_profile_of(user_id).await?;
+        self.update_own_profile(|profile| {
+            profile.display_name = response.displayname.clone();
+            profile.avatar_url = response.avatar_url.clone();
         });
-        Ok(self.client.send(request).await?)
+        Ok(response)
     }
 
-    /// Get the registered [Third Party Identifiers][3pid] on the homeserver of
-    /// the account.
+    /// Get the current display name and avatar URL of the account, and a
+    /// stream of their updates.
     ///
-    /// These 3PIDs may be used by the homeserver to authenticate the user
-    /// during sensitive operations.
+    /// The stream yields a new value when the profile is changed with
+    /// [`Account::set_display_name()`], [`Account::set_avatar_url()`] or
+    /// [`Account::upload_avatar()`], when it is fetched from the homeserver,
+    /// or when a sync contains a new `m.room.member` event of the logged-in
+    /// user in a joined room. A value is only yielded if it changed.
+    ///
+    /// The initial value might be incomplete if the profile wasn't fetched
+    /// yet, call [`Account::fetch_user_profile()`] to refresh it.
     ///
     /// # Examples
     ///
     /// ```no_run
+    /// # use futures_util::{pin_mut, StreamExt};
     /// # use matrix_sdk::Client;
     /// # use url::Url;
     /// # async {
     /// # let homeserver = Url::parse("http://localhost:8080")?;
     /// # let client = Client::new(homeserver).await?;
-    /// let threepids = client.account().get_3pids().await?.threepids;
+    /// let account = client.account();
+    /// let ((display_name, avatar_url), stream) =
+    ///     account.subscribe_to_profile_changes().await?;
+    /// pin_mut!(stream);
     ///
-    /// for threepid in threepids {
-    ///     println!(
-    ///         "Found 3PID '{}' of type '{}'",
-    ///         threepid.address, threepid.medium
-    ///     );
+    /// while let Some((display_name, avatar_url)) = stream.next().await {
+    ///     println!("New profile: {display_name:?}, {avatar_url:?}");
     /// }
     /// # anyhow::Ok(()) };
     /// ```
-    /// [3pid]: https://spec.matrix.org/v1.2/appendices/#3pid-types
-    pub async fn get_3pids(&self) -> Result<get_3pids::v3::Response> {
-        let request = get_3pids::v3::Request::new();
-        Ok(self.client.send(request).await?)
+    pub async fn subscribe_to_profile_changes(
+        &self,
+    ) -> Result<(
+        (Option<String>, Option<OwnedMxcUri>),
+        impl Stream<Item = (Option<String>, Option<OwnedMxcUri>)>,
+    )> {
+        // The avatar URL might be known from a previous session.
+        if let Some(avatar_url) = self.get_cached_avatar_url().await? {
+            self.client.inner.own_profile.update_if(|profile| {
+                if profile.avatar_url.is_some() {
+                    return false;
+                }
+                profile.avatar_url = Some(avatar_url);
+                true
+            });
+        }
+
+        let subscriber = self.client.inner.own_profile.subscribe();
+        let initial = subscriber.get().into_parts();
+
+        Ok((initial, subscriber.map(OwnProfile::into_parts)))
     }
 
-    /// Request a token to validate an email address as a [Third Party
-    /// Identifier][3pid].
-    ///
-    /// This is the first step in registering an email address as 3PID. Next,
-    /// call [`Account::add_3pid()`] with the same `client_secret` and the
-    /// returned `sid`.
-    ///
-    /// # Arguments
-    ///
-    /// * `client_secret` - A client-generated secret string used to protect
-    ///   this session.
-    ///
-    /// * `email` - The email address to validate.
-    ///
-    /// * `send_attempt` - The attempt number. This number needs to be
-    ///   incremented if you want to request another token for the same
-    ///   validation.
-    ///
-    /// # Returns
-    ///
-    /// * `sid` - The session ID to be used in following requests for this 3PID.
-    ///
-    /// * `submit_url` - If present, the user will submit the token to the
-    ///   client, that must send it to this URL. If not, the client will not be
-    ///   involved in the token submission.
+    /// Update the observed profile of the logged-in user with the last
+    /// `m.room.member` event of the user in the given events of a joined room.
+    pub(crate) fn handle_own_member_events(
+        &self,
+        state: &[Raw<AnySyncStateEvent>],
+        timeline: &[TimelineEvent],
+    ) {
+        let Some(user_id) = self.client.user_id() else {
+            return;
+        };
+
+        let raw_events = state
+            .iter()
+            .map(|raw| raw.cast_ref_unchecked::<SyncRoomMemberEvent>())
+            .chain(timeline.iter().map(|event| event.raw().cast_ref_unchecked()));
+
+        let mut last_profile = None;
+
+        for raw in raw_events {
+            if raw.get_field::<String>("type").ok().flatten().as_deref() != Some("m.room.member") {
+                continue;
+            }
+
+            let Ok(SyncRoomMemberEvent::Original(event)) = raw.deserialize() else {
+                continue;
+            };
+
+            if *event.state_key == *user_id && event.content.membership == MembershipState::Join {
+                last_profile = Some((event.content.displayname, event.content.avatar_url));
+            }
+        }
+
+        if let Some((display_name, avatar_url)) = last_profile {
+            self.update_own_profile(|profile| {
+                profile.display_name = display_name;
+                profile.avatar_url = avatar_url;
+            });
+        }
+    }
+
+    /// Store the avatar URL of the logged-in user, for
+    /// [`Account::get_cached_avatar_url()`].
+    async fn cache_avatar_url(&self, user_id: &UserId, url: Option<&MxcUri>) {
+        if let Some(url) = url {
+            // If an avatar is found cache it.
+            let _ = self
+                .client
+                .state_store()
+                .set_kv_data(
+                    StateStoreDataKey::UserAvatarUrl(user_id),
+                    StateStoreDataValue::UserAvatarUrl(url.to_owned()),
+                )
+                .await;
+        } else {
+            // If there is no avatar the user has removed it and we uncache it.
+            let _ = self
+                .client
+                .state_store()
+                .remove_kv_data(StateStoreDataKey::UserAvatarUrl(user_id))
+                .await;
+        }
+    }
+
+    /// Update the observed profile of the logged-in user, notifying the
+    /// subscribers if it changed.
+    fn update_own_profile(&self, f: impl FnOnce(&mut OwnProfile)) {
+        self.client.inner.own_profile.update_if(|profile| {
+            let previous = profile.clone();
+            f(profile);
+            *profile != previous
+        });
+    }
+
+    /// Get the profile for a given user id
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` the matrix id this function downloads the profile for
+    pub async fn fetch_user_profile_of(
+        &self,
+        user_id: &UserId,
+    ) -> Result<get_profile::v3::Response> {
+        let request = get_profile::v3::Request::new(user_id.to_owned());
+        Ok(self
+            .client
+            .send(request)
+            .with_request_config(RequestConfig::short_retry().force_auth())
+            .await?)
+    }
+
+    /// Get information about the owner of the access token of the client.
+    ///
+    /// This can be used to check that a restored session is still valid, or
+    /// to learn the device ID associated with the access token.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::UserIdMismatch`] if the access token belongs to a
+    /// different user than the one of the session of the client.
+    pub async fn whoami(&self) -> Result<WhoAmI> {
+        let response = self.client.whoami().await?;
+        let whoami = WhoAmI {
+            user_id: response.user_id,
+            device_id: response.device_id,
+            is_guest: response.is_guest,
+        };
+
+        if let Some(session_meta) = self.client.session_meta() {
+            if session_meta.user_id != whoami.user_id {
+                return Err(Error::UserIdMismatch {
+                    expected: session_meta.user_id.clone(),
+                    actual: whoami.user_id,
+                });
+            }
+
+            if whoami
+                .device_id
+                .as_ref()
+                .is_some_and(|device_id| *device_id != session_meta.device_id)
+            {
+                warn!(
+                    expected = ?session_meta.device_id,
+                    actual = ?whoami.device_id,
+                    "the access token belongs to a different device than the one of the session"
+                );
+            }
+        }
+
+        Ok(whoami)
+    }
+
+    /// Get an OpenID token, allowing a third party like a widget or an
+    /// integration manager to verify the identity of the user.
+    ///
+    /// The token is cached, and reused until it is about to expire.
+    ///
+    /// # Arguments
+    ///
+    /// * `force_refresh` - Whether to request a new token from the server even
+    ///   if the cached one is still valid.
+    pub async fn request_openid_token(&self, force_refresh: bool) -> Result<OpenIdToken> {
+        let user_id = self.client.user_id().ok_or(Error::AuthenticationRequired)?;
+
+        let mut cache = self.client.inner.caches.openid_token.lock().await;
+
+        if !force_refresh {
+            if let Some(token) = cache.get() {
+                return Ok(token);
+            }
+        }
+
+        let request = request_openid_token::v3::Request::new(user_id.to_owned());
+        let response = self.client.send(request).await?;
+
+        let token = OpenIdToken {
+            access_token: response.access_token,
+            token_type: response.token_type,
+            matrix_server_name: response.matrix_server_name,
+            expires_in: response.expires_in,
+        };
+        cache.token = Some((token.clone(), Instant::now()));
+
+        Ok(token)
+    }
+
+    /// Set how long before its expiry the OpenID token cached by
+    /// [`Account::request_openid_token()`] is renewed.
+    ///
+    /// The default is 1 minute.
+    pub async fn set_openid_token_expiry_margin(&self, margin: Duration) {
+        self.client.inner.caches.openid_token.lock().await.expiry_margin = margin;
+    }
+
+    /// Get the timezone of the account, as defined in [MSC4175].
+    ///
+    /// Returns `None` if the timezone isn't set.
+    ///
+    /// [MSC4175]: https://github.com/matrix-org/matrix-spec-proposals/pull/4175
+    #[cfg(feature = "unstable-msc4175")]
+    pub async fn get_timezone(&self) -> Result<Option<String>> {
+        let user_id = self.client.user_id().ok_or(Error::AuthenticationRequired)?;
+        let request = timezone::get_timezone::Request::new(user_id.to_owned());
+
+        match self.client.send(request).await {
+            Ok(response) => Ok(response.tz),
+            Err(error) if error.client_api_error_kind() == Some(&ErrorKind::NotFound) => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Set the timezone of the account, as defined in [MSC4175].
+    ///
+    /// The timezone must be an IANA timezone identifier, like `Europe/Paris`.
+    /// Only the shape of the identifier is checked, not whether it exists in
+    /// the tz database.
+    ///
+    /// # Arguments
+    ///
+    /// * `tz` - The timezone to set, or `None` to remove it.
+    ///
+    /// [MSC4175]: https://github.com/matrix-org/matrix-spec-proposals/pull/4175
+    #[cfg(feature = "unstable-msc4175")]
+    pub async fn set_timezone(&self, tz: Option<&str>) -> Result<()> {
+        let user_id = self.client.user_id().ok_or(Error::AuthenticationRequired)?.to_owned();
+
+        if let Some(tz) = tz {
+            if !is_plausible_timezone(tz) {
+                return Err(Error::InvalidTimezone(tz.to_owned()));
+            }
+
+            let request = timezone::set_timezone::Request::new(user_id, tz.to_owned());
+            self.client.send(request).await?;
+        } else {
+            let request = timezone::delete_timezone::Request::new(user_id);
+            self.client.send(request).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Get the profile for a given user id, from the in-memory cache if
+    /// possible.
+    ///
+    /// The profile is fetched from the homeserver with
+    /// [`Account::fetch_user_profile_of()`] if it isn't in the cache or if the
+    /// cached profile has expired, which happens after 5 minutes by default.
+    /// Concurrent calls for the same user only send a single request.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` the matrix id this function gets the profile for
+    pub async fn fetch_user_profile_of_cached(
+        &self,
+        user_id: &UserId,
+    ) -> Result<get_profile::v3::Response> {
+        let profiles = &self.client.inner.caches.profiles;
+
+        if let Some(profile) = profiles.lock().await.get(user_id) {
+            return Ok(profile);
+        }
+
+        self.client
+            .locks()
+            .profile_request_deduplicated_handler
+            .run(user_id.to_owned(), async move {
+                let profile = self.fetch_user_profile_of(user_id).await?;
+                profiles.lock().await.insert(user_id.to_owned(), profile);
+                Ok(())
+            })
+            .await?;
+
+        // The profile was fetched, either by this call or by a concurrent one.
+        if let Some(profile) = profiles.lock().await.get(user_id) {
+            Ok(profile)
+        } else {
+            // It has been invalidated in the meantime, fetch it again.
+            self.fetch_user_profile_of(user_id).await
+        }
+    }
+
+    /// Remove the profile of the given user from the cache used by
+    /// [`Account::fetch_user_profile_of_cached()`].
+    pub async fn invalidate_profile_cache(&self, user_id: &UserId) {
+        self.client.inner.caches.profiles.lock().await.remove(user_id);
+    }
+
+    /// Set how long the profiles stay in the cache used by
+    /// [`Account::fetch_user_profile_of_cached()`].
+    ///
+    /// This only applies to the profiles fetched from now on. The default is 5
+    /// minutes.
+    pub async fn set_profile_cache_lifetime(&self, lifetime: Duration) {
+        self.client.inner.caches.profiles.lock().await.set_lifetime(lifetime);
+    }
+
+    /// Change the password of the account.
+    ///
+    /// # Arguments
+    ///
+    /// * `new_password` - The new password to set.
+    ///
+    /// * `auth_data` - This request uses the [User-Interactive Authentication
+    ///   API][uiaa]. The first request needs to set this to `None` and will
+    ///   always fail with an [`UiaaResponse`]. The response will contain
+    ///   information for the interactive auth and the same request needs to be
+    ///   made but this time with some `auth_data` provided.
+    ///
+    /// # Returns
+    ///
+    /// This method might return an [`ErrorKind::WeakPassword`] error if the new
+    /// password is considered insecure by the homeserver, with details about
+    /// the strength requirements in the error's message.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use matrix_sdk::Client;
+    /// # use matrix_sdk::ruma::{
+    /// #     api::client::{
+    /// #         account::change_password::v3::{Request as ChangePasswordRequest},
+    /// #         uiaa::{AuthData, Dummy},
+    /// #     },
+    /// #     assign,
+    /// # };
+    /// # use url::Url;
+    /// # async {
+    /// # let homeserver = Url::parse("http://localhost:8080")?;
+    /// # let client = Client::new(homeserver).await?;
+    /// client.account().change_password(
+    ///     "myverysecretpassword",
+    ///     Some(AuthData::Dummy(Dummy::new())),
+    /// ).await?;
+    /// # anyhow::Ok(()) };
+    /// ```
+    /// [uiaa]: https://spec.matrix.org/v1.2/client-server-api/#user-interactive-authentication-api
+    /// [`UiaaResponse`]: ruma::api::client::uiaa::UiaaResponse
+    /// [`ErrorKind::WeakPassword`]: ruma::api::client::error::ErrorKind::WeakPassword
+    pub async fn change_password(
+        &self,
+        new_password: &str,
+        auth_data: Option<AuthData>,
+    ) -> Result<change_password::v3::Response> {
+        let request = assign!(change_password::v3::Request::new(new_password.to_owned()), {
+            auth: auth_data,
+        });
+        Ok(self.client.send(request).await?)
+    }
+
+    /// Change the password of the account, authenticating with its current
+    /// password.
+    ///
+    /// Contrary to [`Account::change_password()`], this method handles the
+    /// [User-Interactive Authentication API][uiaa] itself, as long as the
+    /// homeserver allows to authenticate with the `m.login.password` stage
+    /// only.
+    ///
+    /// # Arguments
+    ///
+    /// * `current_password` - The current password of the account.
+    ///
+    /// * `new_password` - The new password to set.
+    ///
+    /// # Errors
+    ///
+    /// * [`Error::WrongPassword`] if `current_password` is wrong.
+    ///
+    /// * [`Error::WeakPassword`] if the new password is considered insecure by
+    ///   the homeserver.
+    ///
+    /// * [`Error::PasswordAuthUnsupported`] if the homeserver requires another
+    ///   kind of authentication.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use matrix_sdk::Client;
+    /// # use url::Url;
+    /// # async {
+    /// # let homeserver = Url::parse("http://localhost:8080")?;
+    /// # let client = Client::new(homeserver).await?;
+    /// client
+    ///     .account()
+    ///     .change_password_with_password(
+    ///         "mycurrentpassword",
+    ///         "myverysecretpassword",
+    ///     )
+    ///     .await?;
+    /// # anyhow::Ok(()) };
+    /// ```
+    ///
+    /// [uiaa]: https://spec.matrix.org/v1.2/client-server-api/#user-interactive-authentication-api
+    pub async fn change_password_with_password(
+        &self,
+        current_password: &str,
+        new_password: &str,
+    ) -> Result<change_password::v3::Response> {
+        self.send_with_password_auth(current_password, |auth_data| {
+            assign!(change_password::v3::Request::new(new_password.to_owned()), {
+                auth: auth_data,
+            })
+        })
+        .await
+    }
+
+    /// Deactivate this account definitively.
+    ///
+    /// See [`Account::deactivate_with_password()`] to handle the
+    /// [User-Interactive Authentication API][uiaa] automatically.
+    ///
+    /// # Arguments
+    ///
+    /// * `id_server` - The identity server from which to unbind the user’s
+    ///   [Third Party Identifiers][3pid].
+    ///
+    /// * `auth_data` - This request uses the [User-Interactive Authentication
+    ///   API][uiaa]. The first request needs to set this to `None` and will
+    ///   always fail with an [`UiaaResponse`]. The response will contain
+    ///   information for the interactive auth and the same request needs to be
+    ///   made but this time with some `auth_data` provided.
+    ///
+    /// * `erase` - Whether the user would like their content to be erased as
+    ///   much as possible from the server.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use matrix_sdk::Client;
+    /// # use matrix_sdk::ruma::{
+    /// #     api::client::{
+    /// #         account::change_password::v3::{Request as ChangePasswordRequest},
+    /// #         uiaa::{AuthData, Dummy},
+    /// #     },
+    /// #     assign,
+    /// # };
+    /// # use url::Url;
+    /// # async {
+    /// # let homeserver = Url::parse("http://localhost:8080")?;
+    /// # let client = Client::new(homeserver).await?;
+    /// # let account = client.account();
+    /// let response = account.deactivate(None, None, false).await;
+    ///
+    /// // Proceed with UIAA.
+    /// # anyhow::Ok(()) };
+    /// ```
+    /// [3pid]: https://spec.matrix.org/v1.2/appendices/#3pid-types
+    /// [uiaa]: https://spec.matrix.org/v1.2/client-server-api/#user-interactive-authentication-api
+    /// [`UiaaResponse`]: ruma::api::client::uiaa::UiaaResponse
+    pub async fn deactivate(
+        &self,
+        id_server: Option<&str>,
+        auth_data: Option<AuthData>,
+        erase_data: bool,
+    ) -> Result<deactivate::v3::Response> {
+        let request = assign!(deactivate::v3::Request::new(), {
+            id_server: id_server.map(ToOwned::to_owned),
+            auth: auth_data,
+            erase: erase_data,
+        });
+        Ok(self.client.send(request).await?)
+    }
+
+    /// Deactivate this account definitively, authenticating with its
+    /// password.
+    ///
+    /// Contrary to [`Account::deactivate()`], this method handles the
+    /// [User-Interactive Authentication API][uiaa] itself, as long as the
+    /// homeserver allows to authenticate with the `m.login.password` and
+    /// `m.login.dummy` stages only.
+    ///
+    /// # Arguments
+    ///
+    /// * `password` - The password of the account.
+    ///
+    /// * `id_server` - The identity server from which to unbind the user’s
+    ///   [Third Party Identifiers][3pid].
+    ///
+    /// * `erase` - Whether the user would like their content to be erased as
+    ///   much as possible from the server.
+    ///
+    /// # Errors
+    ///
+    /// * [`Error::WrongPassword`] if `password` is wrong.
+    ///
+    /// * [`Error::PasswordAuthUnsupported`] if the homeserver requires other
+    ///   authentication stages, with the info needed to go through them with
+    ///   [`Account::deactivate()`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use matrix_sdk::Client;
+    /// # use url::Url;
+    /// # async {
+    /// # let homeserver = Url::parse("http://localhost:8080")?;
+    /// # let client = Client::new(homeserver).await?;
+    /// client
+    ///     .account()
+    ///     .deactivate_with_password("mypassword", None, false)
+    ///     .await?;
+    /// # anyhow::Ok(()) };
+    /// ```
+    /// [3pid]: https://spec.matrix.org/v1.2/appendices/#3pid-types
+    /// [uiaa]: https://spec.matrix.org/v1.2/client-server-api/#user-interactive-authentication-api
+    pub async fn deactivate_with_password(
+        &self,
+        password: &str,
+        id_server: Option<&str>,
+        erase: bool,
+    ) -> Result<deactivate::v3::Response> {
+        self.send_with_password_auth(password, |auth_data| {
+            assign!(deactivate::v3::Request::new(), {
+                id_server: id_server.map(ToOwned::to_owned),
+                auth: auth_data,
+                erase,
+            })
+        })
+        .await
+    }
+
+    /// Get the registered [Third Party Identifiers][3pid] on the homeserver of
+    /// the account.
+    ///
+    /// These 3PIDs may be used by the homeserver to authenticate the user
+    /// during sensitive operations.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use matrix_sdk::Client;
+    /// # use url::Url;
+    /// # async {
+    /// # let homeserver = Url::parse("http://localhost:8080")?;
+    /// # let client = Client::new(homeserver).await?;
+    /// let threepids = client.account().get_3pids().await?.threepids;
+    ///
+    /// for threepid in threepids {
+    ///     println!(
+    ///         "Found 3PID '{}' of type '{}'",
+    ///         threepid.address, threepid.medium
+    ///     );
+    /// }
+    /// # anyhow::Ok(()) };
+    /// ```
+    /// [3pid]: https://spec.matrix.org/v1.2/appendices/#3pid-types
+    pub async fn get_3pids(&self) -> Result<get_3pids::v3::Response> {
+        let request = get_3pids::v3::Request::new();
+        let response = self.client.send(request).await?;
+
+        self.client
+            .state_store()
+            .set_kv_data(
+                StateStoreDataKey::ThirdPartyIdentifiers,
+                StateStoreDataValue::ThirdPartyIdentifiers(response.threepids.clone()),
+            )
+            .await?;
+
+        Ok(response)
+    }
+
+    /// Get the [Third Party Identifiers][3pid] of the account returned by the
+    /// last successful call to [`Account::get_3pids()`], from the store.
+    ///
+    /// Returns `None` if they were never fetched, or if they changed since
+    /// then with [`Account::add_3pid()`] or [`Account::delete_3pid()`].
+    ///
+    /// [3pid]: https://spec.matrix.org/v1.2/appendices/#3pid-types
+    pub async fn get_cached_3pids(&self) -> Result<Option<Vec<ThirdPartyIdentifier>>> {
+        Ok(self
+            .client
+            .state_store()
+            .get_kv_data(StateStoreDataKey::ThirdPartyIdentifiers)
+            .await?
+            .and_then(|value| value.into_third_party_identifiers()))
+    }
+
+    /// Get the email addresses registered on the homeserver for the account.
+    ///
+    /// See [`Account::get_3pids()`].
+    pub async fn email_addresses(&self) -> Result<Vec<ThirdPartyIdentifier>> {
+        self.get_3pids_with_medium(Medium::Email).await
+    }
+
+    /// Get the phone numbers registered on the homeserver for the account.
+    ///
+    /// See [`Account::get_3pids()`].
+    pub async fn phone_numbers(&self) -> Result<Vec<ThirdPartyIdentifier>> {
+        self.get_3pids_with_medium(Medium::Msisdn).await
+    }
+
+    /// Whether the account has at least one validated email address on the
+    /// homeserver.
+    pub async fn has_verified_email(&self) -> Result<bool> {
+        // The homeserver only lists validated 3PIDs, but be defensive against
+        // the ones missing a validation timestamp.
+        Ok(self
+            .email_addresses()
+            .await?
+            .iter()
+            .any(|threepid| threepid.validated_at.get() > UInt::MIN))
+    }
+
+    async fn get_3pids_with_medium(&self, medium: Medium) -> Result<Vec<ThirdPartyIdentifier>> {
+        Ok(self
+            .get_3pids()
+            .await?
+            .threepids
+            .into_iter()
+            .filter(|threepid| threepid.medium == medium)
+            .collect())
+    }
+
+    /// Forget the cached [Third Party Identifiers][3pid] of the account,
+    /// after they changed on the homeserver.
+    ///
+    /// [3pid]: https://spec.matrix.org/v1.2/appendices/#3pid-types
+    async fn invalidate_cached_3pids(&self) -> Result<()> {
+        self.client.state_store().remove_kv_data(StateStoreDataKey::ThirdPartyIdentifiers).await?;
+        Ok(())
+    }
+
+    /// Request a token to validate an email address as a [Third Party
+    /// Identifier][3pid].
+    ///
+    /// This is the first step in registering an email address as 3PID. Next,
+    /// call [`Account::add_3pid()`] with the same `client_secret` and the
+    /// returned `sid`.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_secret` - A client-generated secret string used to protect
+    ///   this session.
+    ///
+    /// * `email` - The email address to validate.
+    ///
+    /// * `send_attempt` - The attempt number. This number needs to be
+    ///   incremented if you want to request another token for the same
+    ///   validation.
+    ///
+    /// # Returns
+    ///
+    /// * `sid` - The session ID to be used in following requests for this 3PID.
+    ///
+    /// * `submit_url` - If present, the user will submit the token to the
+    ///   client, that must send it to this URL with
+    ///   [`Account::submit_3pid_token()`]. If not, the client will not be
+    ///   involved in the token submission.
     ///
     /// This method might return an [`ErrorKind::ThreepidInUse`] error if the
     /// email address is already registered for this account or another, or an
@@ -545,7 +1347,8 @@ impl Account {
     /// * `sid` - The session ID to be used in following requests for this 3PID.
     ///
     /// * `submit_url` - If present, the user will submit the token to the
-    ///   client, that must send it to this URL. If not, the client will not be
+    ///   client, that must send it to this URL with
+    ///   [`Account::submit_3pid_token()`]. If not, the client will not be
     ///   involved in the token submission.
     ///
     /// This method might return an [`ErrorKind::ThreepidInUse`] error if the
@@ -595,6 +1398,98 @@ impl Account {
         Ok(self.client.send(request).await?)
     }
 
+    /// Submit the token to validate a [Third Party Identifier][3pid] to the
+    /// `submit_url` returned by [`Account::request_3pid_email_token()`] or
+    /// [`Account::request_3pid_msisdn_token()`].
+    ///
+    /// # Arguments
+    ///
+    /// * `submit_url` - The URL where the token must be sent.
+    ///
+    /// * `client_secret` - The client secret used to request the token.
+    ///
+    /// * `sid` - The session ID returned when requesting the token.
+    ///
+    /// * `token` - The token received by the user.
+    ///
+    /// # Returns
+    ///
+    /// Whether the token was valid. If it was, call [`Account::add_3pid()`]
+    /// with the same `client_secret` and `sid`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use matrix_sdk::Client;
+    /// # use matrix_sdk::ruma::{ClientSecret, uint};
+    /// # use url::Url;
+    /// # async {
+    /// # let homeserver = Url::parse("http://localhost:8080")?;
+    /// # let client = Client::new(homeserver).await?;
+    /// # let account = client.account();
+    /// # let secret = ClientSecret::parse("secret")?;
+    /// # let token = "123456";
+    /// let token_response = account
+    ///     .request_3pid_msisdn_token(&secret, "FR", "0123456789", uint!(0))
+    ///     .await?;
+    ///
+    /// if let Some(submit_url) = token_response.submit_url {
+    ///     let submit_url = Url::parse(&submit_url)?;
+    ///
+    ///     // Prompt the user for the token they received by SMS.
+    ///
+    ///     let success = account
+    ///         .submit_3pid_token(&submit_url, &secret, &token_response.sid, token)
+    ///         .await?;
+    /// }
+    /// # anyhow::Ok(()) };
+    /// ```
+    /// [3pid]: https://spec.matrix.org/v1.2/appendices/#3pid-types
+    pub async fn submit_3pid_token(
+        &self,
+        submit_url: &Url,
+        client_secret: &ClientSecret,
+        sid: &SessionId,
+        token: &str,
+    ) -> Result<bool> {
+        #[derive(Deserialize)]
+        struct SubmitTokenResponse {
+            success: bool,
+        }
+
+        let body = serde_json::to_vec(&json!({
+            "sid": sid,
+            "client_secret": client_secret,
+            "token": token,
+        }))?;
+
+        let response = self
+            .client
+            .http_client()
+            .post(submit_url.clone())
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(body)
+            .send()
+            .await?;
+
+        let status = response.status();
+        let body = response.bytes().await?;
+
+        if !status.is_success() {
+            let response = http::Response::builder()
+                .status(status)
+                .body(body)
+                .expect("Can't construct a response using the given body");
+            let error = FromHttpResponseError::Server(RumaApiError::ClientApi(
+                ruma::api::client::Error::from_http_response(response),
+            ));
+
+            return Err(HttpError::from(error).into());
+        }
+
+        Ok(serde_json::from_slice::<SubmitTokenResponse>(&body)?.success)
+    }
+
     /// Add a [Third Party Identifier][3pid] on the homeserver for this
     /// account.
     ///
@@ -635,7 +1530,9 @@ impl Account {
             assign!(add_3pid::v3::Request::new(client_secret.to_owned(), sid.to_owned()), {
                 auth: auth_data
             });
-        Ok(self.client.send(request).await?)
+        let response = self.client.send(request).await?;
+        self.invalidate_cached_3pids().await?;
+        Ok(response)
     }
 
     /// Delete a [Third Party Identifier][3pid] from the homeserver for this
@@ -695,9 +1592,114 @@ impl Account {
         let request = assign!(delete_3pid::v3::Request::new(medium, address.to_owned()), {
             id_server: id_server.map(ToOwned::to_owned),
         });
+        let response = self.client.send(request).await?;
+        self.invalidate_cached_3pids().await?;
+        Ok(response)
+    }
+
+    /// Bind a [Third Party Identifier][3pid] to an identity server, to make it
+    /// discoverable by other users.
+    ///
+    /// The 3PID must have been validated with the identity server first.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_secret` - The same client secret used to validate the 3PID.
+    ///
+    /// * `sid` - The session ID given by the identity server.
+    ///
+    /// * `id_server` - The hostname of the identity server, with an optional
+    ///   port.
+    ///
+    /// * `id_access_token` - An access token previously registered with the
+    ///   identity server.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use matrix_sdk::Client;
+    /// # use matrix_sdk::ruma::{ClientSecret, SessionId};
+    /// # use url::Url;
+    /// # async {
+    /// # let homeserver = Url::parse("http://localhost:8080")?;
+    /// # let client = Client::new(homeserver).await?;
+    /// # let account = client.account();
+    /// # let secret = ClientSecret::parse("secret")?;
+    /// # let sid = SessionId::parse("sid")?;
+    /// account
+    ///     .bind_3pid(&secret, &sid, "vector.im", "identity_access_token")
+    ///     .await?;
+    /// # anyhow::Ok(()) };
+    /// ```
+    /// [3pid]: https://spec.matrix.org/v1.2/appendices/#3pid-types
+    pub async fn bind_3pid(
+        &self,
+        client_secret: &ClientSecret,
+        sid: &SessionId,
+        id_server: &str,
+        id_access_token: &str,
+    ) -> Result<bind_3pid::v3::Response> {
+        let request = bind_3pid::v3::Request::new(
+            client_secret.to_owned(),
+            IdentityServerInfo::new(id_server.to_owned(), id_access_token.to_owned()),
+            sid.to_owned(),
+        );
         Ok(self.client.send(request).await?)
     }
 
+    /// Unbind a [Third Party Identifier][3pid] from an identity server, without
+    /// removing it from the homeserver.
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The 3PID being unbound.
+    ///
+    /// * `medium` - The type of the 3PID.
+    ///
+    /// * `id_server` - The identity server to unbind from. If not provided, the
+    ///   homeserver should unbind the 3PID from the identity server it was
+    ///   bound to previously.
+    ///
+    /// # Returns
+    ///
+    /// * [`ThirdPartyIdRemovalStatus::Success`] if the 3PID was unbound from
+    ///   the identity server.
+    ///
+    /// * [`ThirdPartyIdRemovalStatus::NoSupport`] if the homeserver failed to
+    ///   unbind the 3PID from the identity server.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use matrix_sdk::Client;
+    /// # use matrix_sdk::ruma::thirdparty::Medium;
+    /// # use matrix_sdk::ruma::api::client::account::ThirdPartyIdRemovalStatus;
+    /// # use url::Url;
+    /// # async {
+    /// # let homeserver = Url::parse("http://localhost:8080")?;
+    /// # let client = Client::new(homeserver).await?;
+    /// # let account = client.account();
+    /// match account.unbind_3pid("paul@matrix.org", Medium::Email, None).await? {
+    ///     ThirdPartyIdRemovalStatus::Success => {
+    ///         println!("3PID unbound from the Identity Server");
+    ///     }
+    ///     _ => println!("Could not unbind 3PID from the Identity Server"),
+    /// }
+    /// # anyhow::Ok(()) };
+    /// ```
+    /// [3pid]: https://spec.matrix.org/v1.2/appendices/#3pid-types
+    pub async fn unbind_3pid(
+        &self,
+        address: &str,
+        medium: Medium,
+        id_server: Option<&str>,
+    ) -> Result<ThirdPartyIdRemovalStatus> {
+        let request = assign!(unbind_3pid::v3::Request::new(medium, address.to_owned()), {
+            id_server: id_server.map(ToOwned::to_owned),
+        });
+        Ok(self.client.send(request).await?.id_server_unbind_result)
+    }
+
     /// Get the content of an account data event of statically-known type, from
     /// storage.
     ///
@@ -735,6 +1737,103 @@ impl Account {
         get_raw_content(self.client.state_store().get_account_data_event(event_type).await?)
     }
 
+    /// Observe the content of an account data event of a statically-known
+    /// type.
+    ///
+    /// This will return the current content, from storage, and a stream that
+    /// will yield the new content every time it is received from the
+    /// homeserver. Contents that can't be deserialized are skipped.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use futures_util::{pin_mut, StreamExt};
+    /// # use matrix_sdk::Client;
+    /// # async {
+    /// # let client = Client::new("http://localhost:8080".parse()?).await?;
+    /// # let account = client.account();
+    /// use matrix_sdk::ruma::events::ignored_user_list::IgnoredUserListEventContent;
+    ///
+    /// let (content, content_stream) =
+    ///     account.observe_account_data::<IgnoredUserListEventContent>().await?;
+    ///
+    /// println!("Ignored users list: {content:?}");
+    ///
+    /// pin_mut!(content_stream);
+    /// while let Some(content) = content_stream.next().await {
+    ///     println!("Ignored users list changed: {content:?}");
+    /// }
+    /// # anyhow::Ok(()) };
+    /// ```
+    pub async fn observe_account_data<C>(&self) -> Result<(Option<C>, impl Stream<Item = C>)>
+    where
+        C: GlobalAccountDataEventContent
+            + StaticEventContent<IsPrefix = ruma::events::False>
+            + DeserializeOwned,
+    {
+        let (content, stream) = self.observe_account_data_raw(C::TYPE.into()).await?;
+        let content = content.map(|raw| raw.deserialize_as_unchecked::<C>()).transpose()?;
+
+        let stream = stream.filter_map(|raw| {
+            let content = match raw.deserialize_as_unchecked::<C>() {
+                Ok(content) => Some(content),
+                Err(err) => {
+                    warn!(event_type = C::TYPE, "couldn't deserialize account data: {err}");
+                    None
+                }
+            };
+            ready(content)
+        });
+
+        Ok((content, stream))
+    }
+
+    /// Observe the content of an account data event of a given type.
+    ///
+    /// This will return the current content, from storage, and a stream that
+    /// will yield the new content every time it is received from the
+    /// homeserver.
+    ///
+    /// See [`Account::observe_account_data()`] for the statically-typed
+    /// version.
+    pub async fn observe_account_data_raw(
+        &self,
+        event_type: GlobalAccountDataEventType,
+    ) -> Result<(
+        Option<Raw<AnyGlobalAccountDataEventContent>>,
+        impl Stream<Item = Raw<AnyGlobalAccountDataEventContent>>,
+    )> {
+        let observer = self.client.observe_events::<Raw<AnyGlobalAccountDataEvent>, ()>();
+        let mut events = observer.subscribe();
+
+        // We get the initial value after creating the observer, to make sure that we
+        // don't miss an update.
+        let content = self.account_data_raw(event_type.clone()).await?;
+
+        let event_type = event_type.to_string();
+        let stream = async_stream::stream! {
+            // The observer needs to be alive for the stream to be alive.
+            let _observer = observer;
+
+            while let Some((event, ())) = events.next().await {
+                if event.get_field::<String>("type").ok().flatten().as_ref() != Some(&event_type) {
+                    continue;
+                }
+
+                match get_raw_content(Some(event)) {
+                    Ok(Some(content)) => yield content,
+                    // The account data was deleted.
+                    Ok(None) => {}
+                    Err(err) => {
+                        warn!(%event_type, "couldn't get the content of account data: {err}");
+                    }
+                }
+            }
+        };
+
+        Ok((content, stream))
+    }
+
     /// Fetch a global account data event from the server.
     ///
     /// The content from the response will not be persisted in the store.
@@ -790,6 +1889,87 @@ impl Account {
         Ok(self.fetch_account_data(C::TYPE.into()).await?.map(Raw::cast_unchecked))
     }
 
+    /// Get the content of an account data event of statically-known type, from
+    /// storage, or from the server if it is not in the store.
+    ///
+    /// This is useful before the first sync, when the store doesn't have the
+    /// account data yet.
+    ///
+    /// # Arguments
+    ///
+    /// * `persist` - Whether to save the content fetched from the server in the
+    ///   store, so the next calls to [`Account::account_data()`] return it.
+    ///   Note that the saved copy is overwritten by the next sync that receives
+    ///   this type of account data.
+    pub async fn account_data_or_fetch<C>(&self, persist: bool) -> Result<Option<Raw<C>>>
+    where
+        C: GlobalAccountDataEventContent + StaticEventContent<IsPrefix = ruma::events::False>,
+    {
+        if let Some(content) = self.account_data::<C>().await? {
+            return Ok(Some(content));
+        }
+
+        let Some(content) = self.fetch_account_data_static::<C>().await? else {
+            return Ok(None);
+        };
+
+        if persist {
+            let event_type = GlobalAccountDataEventType::from(C::TYPE);
+            let event = Raw::new(&json!({ "type": event_type, "content": content }))?;
+
+            let _sync_lock = self.client.base_client().sync_lock().lock().await;
+
+            // A sync might have received the event in the meantime, don't overwrite it.
+            if self.client.state_store().get_account_data_event(event_type.clone()).await?.is_none()
+            {
+                let mut changes = StateChanges::default();
+                changes.account_data.insert(event_type, event.cast_unchecked());
+                self.client.state_store().save_changes(&changes).await?;
+            }
+        }
+
+        Ok(Some(content))
+    }
+
+    /// Get the ID of the default secret storage key, from the
+    /// `m.secret_storage.default_key` account data in storage.
+    ///
+    /// Returns `None` if there is no default key, or if the event can't be
+    /// deserialized.
+    pub async fn secret_storage_default_key_id(&self) -> Result<Option<String>> {
+        let Some(raw_content) = self.account_data::<SecretStorageDefaultKeyEventContent>().await?
+        else {
+            return Ok(None);
+        };
+
+        // Since account data events can't always be deleted, we treat deserialization
+        // failures as secret storage being disabled.
+        Ok(raw_content.deserialize().ok().map(|content| content.key_id))
+    }
+
+    /// Whether secret storage is set up for the user, from the account data in
+    /// storage.
+    ///
+    /// Secret storage is considered set up if there is a default key, and the
+    /// `m.secret_storage.key.<id>` event it references exists and is valid.
+    pub async fn is_secret_storage_set_up(&self) -> Result<bool> {
+        let Some(key_id) = self.secret_storage_default_key_id().await? else {
+            return Ok(false);
+        };
+
+        let event_type = GlobalAccountDataEventType::SecretStorageKey(key_id);
+        let Some(raw_content) = self.account_data_raw(event_type.clone()).await? else {
+            debug!(%event_type, "the default secret storage key doesn't exist");
+            return Ok(false);
+        };
+
+        Ok(SecretStorageKeyEventContent::from_parts(&event_type.to_string(), raw_content.json())
+            .inspect_err(|err| {
+                warn!(%event_type, "the default secret storage key is invalid: {err}");
+            })
+            .is_ok())
+    }
+
     /// Set the given account data event.
     ///
     /// # Examples
@@ -821,13 +2001,123 @@ impl Account {
         content: T,
     ) -> Result<set_global_account_data::v3::Response>
     where
-        T: GlobalAccountDataEventContent,
+        T: GlobalAccountDataEventContent,
+    {
+        let own_user = self.client.user_id().ok_or(Error::AuthenticationRequired)?;
+
+        let request = set_global_account_data::v3::Request::new(own_user.to_owned(), &content)?;
+
+        Ok(self.client.send(request).await?)
+    }
+
+    /// Set the given account data event, unless its content is identical to
+    /// the one in storage.
+    ///
+    /// The contents are compared as canonical JSON, so the order of the keys
+    /// doesn't matter.
+    ///
+    /// Returns whether the content was uploaded.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use matrix_sdk::Client;
+    /// # async {
+    /// # let client = Client::new("http://localhost:8080".parse()?).await?;
+    /// # let account = client.account();
+    /// use matrix_sdk::ruma::events::ignored_user_list::IgnoredUserListEventContent;
+    ///
+    /// let content = IgnoredUserListEventContent::default();
+    /// if !account.set_account_data_if_changed(content).await? {
+    ///     println!("No user was ignored");
+    /// }
+    /// # anyhow::Ok(()) };
+    /// ```
+    pub async fn set_account_data_if_changed<T>(&self, content: T) -> Result<bool>
+    where
+        T: GlobalAccountDataEventContent,
+    {
+        let event_type = content.event_type();
+
+        if let Some(raw) = self.account_data_raw(event_type.clone()).await? {
+            if is_same_canonical_json(raw.json(), &content) {
+                debug!(%event_type, "the account data didn't change, not uploading it");
+                return Ok(false);
+            }
+        }
+
+        self.set_account_data(content).await?;
+
+        Ok(true)
+    }
+
+    /// Update the given account data event with a closure.
+    ///
+    /// The current content of the event is fetched from the server, and given
+    /// to the closure. If it returns a new content, it's uploaded, unless it's
+    /// identical to the current one.
+    ///
+    /// The concurrent updates of the same event type with this method are
+    /// applied one after the other, so they don't clobber each other.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use matrix_sdk::Client;
+    /// # async {
+    /// # let client = Client::new("http://localhost:8080".parse()?).await?;
+    /// # let account = client.account();
+    /// use matrix_sdk::ruma::{
+    ///     events::ignored_user_list::{IgnoredUser, IgnoredUserListEventContent},
+    ///     owned_user_id,
+    /// };
+    ///
+    /// account
+    ///     .update_account_data(|content: Option<IgnoredUserListEventContent>| {
+    ///         let mut content = content.unwrap_or_default();
+    ///         content
+    ///             .ignored_users
+    ///             .insert(owned_user_id!("@foo:bar.com"), IgnoredUser::new());
+    ///         Some(content)
+    ///     })
+    ///     .await?;
+    /// # anyhow::Ok(()) };
+    /// ```
+    pub async fn update_account_data<C, F>(&self, f: F) -> Result<()>
+    where
+        C: GlobalAccountDataEventContent
+            + StaticEventContent<IsPrefix = ruma::events::False>
+            + DeserializeOwned,
+        F: FnOnce(Option<C>) -> Option<C>,
     {
-        let own_user = self.client.user_id().ok_or(Error::AuthenticationRequired)?;
+        let lock = self
+            .client
+            .locks()
+            .account_data_update_locks
+            .lock()
+            .unwrap()
+            .entry(C::TYPE.into())
+            .or_default()
+            .clone();
+        let _guard = lock.lock().await;
 
-        let request = set_global_account_data::v3::Request::new(own_user.to_owned(), &content)?;
+        // We are fetching the content from the server because we currently can't rely
+        // on `/sync` giving us the correct data in a timely manner.
+        let raw_content = self.fetch_account_data_static::<C>().await?;
+        let content = raw_content.as_ref().map(|raw| raw.deserialize()).transpose()?;
 
-        Ok(self.client.send(request).await?)
+        let Some(new_content) = f(content) else {
+            return Ok(());
+        };
+
+        if raw_content.is_some_and(|raw| is_same_canonical_json(raw.json(), &new_content)) {
+            debug!(event_type = C::TYPE, "the account data didn't change, not uploading it");
+            return Ok(());
+        }
+
+        self.set_account_data(new_content).await?;
+
+        Ok(())
     }
 
     /// Set the given raw account data event.
@@ -844,17 +2134,58 @@ impl Account {
         Ok(self.client.send(request).await?)
     }
 
+    /// Delete the given account data event, as defined in [MSC3391].
+    ///
+    /// The event is also removed from the store, so [`Account::account_data`]
+    /// and [`Account::account_data_raw`] stop returning it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::AccountDataDeletionUnsupported`] if the homeserver
+    /// doesn't support MSC3391. In this case, the content of the account data
+    /// event can be replaced by an empty object instead, which is how deleted
+    /// account data events are represented.
+    ///
+    /// [MSC3391]: https://github.com/matrix-org/matrix-spec-proposals/pull/3391
+    pub async fn delete_account_data(&self, event_type: GlobalAccountDataEventType) -> Result<()> {
+        let own_user = self.client.user_id().ok_or(Error::AuthenticationRequired)?;
+
+        let request =
+            delete_global_account_data::Request::new(own_user.to_owned(), event_type.clone());
+
+        if let Err(err) = self.client.send(request).await {
+            if err.client_api_error_kind() == Some(&ErrorKind::Unrecognized) {
+                return Err(Error::AccountDataDeletionUnsupported);
+            }
+
+            return Err(err.into());
+        }
+
+        // Store the event as it would be received via the sync once deleted, with an
+        // empty content.
+        let event = Raw::new(&json!({ "type": event_type, "content": {} }))?.cast_unchecked();
+
+        let _sync_lock = self.client.base_client().sync_lock().lock().await;
+        let mut changes = StateChanges::default();
+        changes.account_data.insert(event_type, event);
+        self.client.state_store().save_changes(&changes).await?;
+
+        Ok(())
+    }
+
     /// Marks the room identified by `room_id` as a "direct chat" with each
     /// user in `user_ids`.
     ///
+    /// The duplicate rooms of the entries of those users are removed. Use
+    /// [`Account::cleanup_direct_rooms()`] to also remove the rooms that were
+    /// left.
+    ///
     /// # Arguments
     ///
     /// * `room_id` - The room ID of the direct message room.
     /// * `user_ids` - The user IDs to be associated with this direct message
     ///   room.
     pub async fn mark_as_dm(&self, room_id: &RoomId, user_ids: &[OwnedUserId]) -> Result<()> {
-        use ruma::events::direct::DirectEventContent;
-
         // This function does a read/update/store of an account data event stored on the
         // homeserver. We first fetch the existing account data event, the event
         // contains a map which gets updated by this method, finally we upload the
@@ -865,52 +2196,248 @@ impl Account {
         // as a semaphore.
         let _guard = self.client.locks().mark_as_dm_lock.lock().await;
 
-        // Now we need to mark the room as a DM for ourselves, we fetch the
-        // existing `m.direct` event and append the room to the list of DMs we
-        // have with this user.
+        // Save the update before sending it, otherwise we might end up in a state where
+        // we have a DM that isn't marked as one if the upload fails. The pending
+        // updates are retried after the next successful sync.
+        let mut pending_updates = self.pending_direct_updates().await?;
+        for user_id in user_ids {
+            let room_ids = pending_updates.entry(user_id.clone()).or_default();
+            if !room_ids.iter().any(|id| id == room_id) {
+                room_ids.push(room_id.to_owned());
+            }
+        }
 
-        // We are fetching the content from the server because we currently can't rely
-        // on `/sync` giving us the correct data in a timely manner.
-        let raw_content = self.fetch_account_data_static::<DirectEventContent>().await?;
-
-        let mut content = if let Some(raw_content) = raw_content {
-            // Log the error and pass it upwards if we fail to deserialize the m.direct
-            // event.
-            raw_content.deserialize().map_err(|err| {
-                error!("unable to deserialize m.direct event content; aborting request to mark {room_id} as dm: {err}");
-                err
-            })?
-        } else {
-            // If there was no m.direct event server-side, create a default one.
-            Default::default()
+        self.client
+            .state_store()
+            .set_kv_data(
+                StateStoreDataKey::PendingDirectUpdates,
+                StateStoreDataValue::PendingDirectUpdates(pending_updates.clone()),
+            )
+            .await?;
+
+        self.upload_direct_updates(pending_updates).await
+    }
+
+    /// Retry to upload the updates of the `m.direct` account data that
+    /// couldn't be uploaded by [`Account::mark_as_dm`].
+    pub(crate) async fn retry_pending_direct_updates(&self) {
+        // If the lock is taken, `mark_as_dm` is running and will upload the pending
+        // updates itself.
+        let Ok(_guard) = self.client.locks().mark_as_dm_lock.try_lock() else {
+            return;
         };
 
-        for user_id in user_ids {
-            content.entry(user_id.into()).or_default().push(room_id.to_owned());
+        let pending_updates = match self.pending_direct_updates().await {
+            Ok(pending_updates) => pending_updates,
+            Err(err) => {
+                error!("couldn't load the pending m.direct updates: {err}");
+                return;
+            }
+        };
+
+        if pending_updates.is_empty() {
+            return;
         }
 
-        // TODO: We should probably save the fact that we need to send this out
-        // because otherwise we might end up in a state where we have a DM that
-        // isn't marked as one.
-        self.set_account_data(content).await?;
+        debug!("retrying to upload the pending m.direct updates");
+
+        if let Err(err) = self.upload_direct_updates(pending_updates).await {
+            warn!("couldn't upload the pending m.direct updates: {err}");
+        }
+    }
+
+    /// Send a request protected by the [User-Interactive Authentication
+    /// API][uiaa], authenticating with the password of the logged-in user.
+    ///
+    /// The request is first sent without authentication data, to get the
+    /// session and the flows supported by the homeserver, then sent again for
+    /// every stage of the first flow only made of `m.login.password` and
+    /// `m.login.dummy` stages.
+    ///
+    /// [uiaa]: https://spec.matrix.org/v1.2/client-server-api/#user-interactive-authentication-api
+    async fn send_with_password_auth<R>(
+        &self,
+        password: &str,
+        make_request: impl Fn(Option<AuthData>) -> R,
+    ) -> Result<R::IncomingResponse>
+    where
+        R: OutgoingRequest<EndpointError = UiaaResponse> + Clone + Debug,
+    {
+        let user_id = self.client.user_id().ok_or(Error::AuthenticationRequired)?;
+
+        let uiaa_info = match self.client.send(make_request(None)).await {
+            // The homeserver didn't require authentication.
+            Ok(response) => return Ok(response),
+            Err(error) => match error.as_uiaa_response() {
+                Some(uiaa_info) => uiaa_info.clone(),
+                None => return Err(password_auth_error(error)),
+            },
+        };
+
+        let Some(stages) = uiaa_info
+            .flows
+            .iter()
+            .find(|flow| {
+                !flow.stages.is_empty()
+                    && flow
+                        .stages
+                        .iter()
+                        .all(|stage| matches!(stage, AuthType::Password | AuthType::Dummy))
+            })
+            .map(|flow| flow.stages.clone())
+        else {
+            return Err(Error::PasswordAuthUnsupported(Box::new(uiaa_info)));
+        };
+
+        let mut remaining_stages =
+            stages.into_iter().filter(|stage| !uiaa_info.completed.contains(stage)).peekable();
+
+        while let Some(stage) = remaining_stages.next() {
+            let auth_data = if stage == AuthType::Password {
+                let mut password_auth = uiaa::Password::new(
+                    UserIdentifier::UserIdOrLocalpart(user_id.to_string()),
+                    password.to_owned(),
+                );
+                password_auth.session = uiaa_info.session.clone();
+                AuthData::Password(password_auth)
+            } else {
+                let mut dummy = uiaa::Dummy::new();
+                dummy.session = uiaa_info.session.clone();
+                AuthData::Dummy(dummy)
+            };
+
+            match self.client.send(make_request(Some(auth_data))).await {
+                Ok(response) => return Ok(response),
+                // The stage was completed, continue with the next one.
+                Err(error)
+                    if remaining_stages.peek().is_some()
+                        && error
+                            .as_uiaa_response()
+                            .is_some_and(|info| info.auth_error.is_none()) => {}
+                Err(error) => return Err(password_auth_error(error)),
+            }
+        }
+
+        // All the stages of the flow were already completed, which shouldn't happen
+        // without sending any authentication data.
+        Err(Error::PasswordAuthUnsupported(Box::new(uiaa_info)))
+    }
+
+    /// Get the updates of the `m.direct` account data that haven't been
+    /// uploaded yet.
+    async fn pending_direct_updates(&self) -> Result<BTreeMap<OwnedUserId, Vec<OwnedRoomId>>> {
+        Ok(self
+            .client
+            .state_store()
+            .get_kv_data(StateStoreDataKey::PendingDirectUpdates)
+            .await?
+            .and_then(|value| value.into_pending_direct_updates())
+            .unwrap_or_default())
+    }
+
+    /// Merge the given updates with the `m.direct` account data on the server,
+    /// upload the result and forget about the updates once it succeeded.
+    ///
+    /// The caller must hold the `mark_as_dm_lock`.
+    async fn upload_direct_updates(
+        &self,
+        updates: BTreeMap<OwnedUserId, Vec<OwnedRoomId>>,
+    ) -> Result<()> {
+        use ruma::events::direct::DirectEventContent;
+
+        self.update_account_data(|content: Option<DirectEventContent>| {
+            // If there was no m.direct event server-side, create a default one.
+            let mut content = content.unwrap_or_default();
+
+            for (user_id, room_ids) in updates {
+                let dm_rooms = content.entry(user_id.into()).or_default();
+                dedup_room_ids(dm_rooms);
+
+                for room_id in room_ids {
+                    if !dm_rooms.contains(&room_id) {
+                        dm_rooms.push(room_id);
+                    }
+                }
+            }
+
+            Some(content)
+        })
+        .await
+        .inspect_err(|err| {
+            error!("unable to update the m.direct event content to mark rooms as dm: {err}");
+        })?;
+
+        self.client.state_store().remove_kv_data(StateStoreDataKey::PendingDirectUpdates).await?;
 
         Ok(())
     }
 
+    /// Remove the duplicate rooms and the rooms that were left from the
+    /// `m.direct` account data.
+    ///
+    /// A room is considered left if the client knows about it and the user is
+    /// neither joined nor invited. The rooms unknown to the client are kept.
+    pub async fn cleanup_direct_rooms(&self) -> Result<()> {
+        use ruma::events::direct::DirectEventContent;
+
+        let _guard = self.client.locks().mark_as_dm_lock.lock().await;
+
+        self.update_account_data(|content: Option<DirectEventContent>| {
+            let mut content = content?;
+
+            for room_ids in content.values_mut() {
+                dedup_room_ids(room_ids);
+                room_ids.retain(|room_id| {
+                    self.client.get_room(room_id).is_none_or(|room| {
+                        matches!(room.state(), RoomState::Joined | RoomState::Invited)
+                    })
+                });
+            }
+
+            content.retain(|_, room_ids| !room_ids.is_empty());
+
+            Some(content)
+        })
+        .await
+    }
+
     /// Adds the given user ID to the account's ignore list.
     pub async fn ignore_user(&self, user_id: &UserId) -> Result<()> {
+        self.ignore_users(&[user_id.to_owned()]).await
+    }
+
+    /// Adds the given user IDs to the account's ignore list, with a single
+    /// update of the account data.
+    ///
+    /// Nothing is uploaded if all the users are already ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::CantIgnoreLoggedInUser`] if the list contains the
+    /// logged-in user, in which case none of the users are ignored.
+    pub async fn ignore_users(&self, user_ids: &[OwnedUserId]) -> Result<()> {
         let own_user_id = self.client.user_id().ok_or(Error::AuthenticationRequired)?;
-        if user_id == own_user_id {
+        if user_ids.iter().any(|user_id| user_id == own_user_id) {
             return Err(Error::CantIgnoreLoggedInUser);
         }
 
         let mut ignored_user_list = self.get_ignored_user_list_event_content().await?;
-        ignored_user_list.ignored_users.insert(user_id.to_owned(), IgnoredUser::new());
 
-        self.set_account_data(ignored_user_list).await?;
+        let mut changed = false;
+        for user_id in user_ids {
+            changed |= ignored_user_list
+                .ignored_users
+                .insert(user_id.clone(), IgnoredUser::new())
+                .is_none();
+        }
+
+        // Only update account data if a user wasn't ignored yet.
+        if changed {
+            self.set_account_data_if_changed(ignored_user_list).await?;
+        }
 
         // In theory, we should also clear some caches here, because they may include
-        // events sent by the ignored user. In practice, we expect callers to
+        // events sent by the ignored users. In practice, we expect callers to
         // take care of this, or subsystems to listen to user list changes and
         // clear caches accordingly.
 
@@ -919,14 +2446,27 @@ impl Account {
 
     /// Removes the given user ID from the account's ignore list.
     pub async fn unignore_user(&self, user_id: &UserId) -> Result<()> {
+        self.unignore_users(&[user_id.to_owned()]).await
+    }
+
+    /// Removes the given user IDs from the account's ignore list, with a
+    /// single update of the account data.
+    ///
+    /// Nothing is uploaded if none of the users were ignored.
+    pub async fn unignore_users(&self, user_ids: &[OwnedUserId]) -> Result<()> {
         let mut ignored_user_list = self.get_ignored_user_list_event_content().await?;
 
-        // Only update account data if the user was ignored in the first place.
-        if ignored_user_list.ignored_users.remove(user_id).is_some() {
-            self.set_account_data(ignored_user_list).await?;
+        let mut changed = false;
+        for user_id in user_ids {
+            changed |= ignored_user_list.ignored_users.remove(user_id).is_some();
+        }
+
+        // Only update account data if a user was ignored in the first place.
+        if changed {
+            self.set_account_data_if_changed(ignored_user_list).await?;
         }
 
-        // See comment in `ignore_user`.
+        // See comment in `ignore_users`.
         Ok(())
     }
 
@@ -940,6 +2480,36 @@ impl Account {
         Ok(ignored_user_list)
     }
 
+    /// Get the list of users ignored by the user, from storage.
+    ///
+    /// Returns an empty list if the ignored user list is missing, or fails to
+    /// deserialize.
+    pub async fn ignored_users(&self) -> Result<Vec<OwnedUserId>> {
+        Ok(self.stored_ignored_user_list().await?.ignored_users.into_keys().collect())
+    }
+
+    /// Whether the given user is ignored by the user, according to the ignored
+    /// user list in storage.
+    ///
+    /// Returns `false` if the ignored user list is missing, or fails to
+    /// deserialize.
+    pub async fn is_user_ignored(&self, user_id: &UserId) -> Result<bool> {
+        Ok(self.stored_ignored_user_list().await?.ignored_users.contains_key(user_id))
+    }
+
+    /// Get the ignored user list from storage, or an empty one if it's missing
+    /// or invalid.
+    async fn stored_ignored_user_list(&self) -> Result<IgnoredUserListEventContent> {
+        let Some(raw_content) = self.account_data::<IgnoredUserListEventContent>().await? else {
+            return Ok(Default::default());
+        };
+
+        Ok(raw_content.deserialize().unwrap_or_else(|err| {
+            warn!("unable to deserialize the ignored user list, considering it empty: {err}");
+            Default::default()
+        }))
+    }
+
     /// Get the current push rules from storage.
     ///
     /// If no push rules event was found, or it fails to deserialize, a ruleset
@@ -947,141 +2517,543 @@ impl Account {
     ///
     /// Panics if called when the client is not logged in.
     pub async fn push_rules(&self) -> Result<Ruleset> {
-        Ok(self
-            .account_data::<PushRulesEventContent>()
-            .await?
-            .and_then(|r| match r.deserialize() {
-                Ok(r) => Some(r.global),
-                Err(e) => {
-                    error!("Push rules event failed to deserialize: {e}");
+        let content = self.account_data::<PushRulesEventContent>().await?;
+        Ok(self.ruleset_or_server_default(
+            content,
+            self.client.user_id().expect("The client should be logged in"),
+        ))
+    }
+
+    /// Get the push rules of the account, from storage, and a stream that
+    /// will yield the new push rules every time they are received from the
+    /// homeserver, for example when they are changed from another client.
+    ///
+    /// Like with [`Account::push_rules()`], a ruleset with the server-default
+    /// push rules is used if no push rules event was found, or if it fails to
+    /// deserialize.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use futures_util::{pin_mut, StreamExt};
+    /// # use matrix_sdk::Client;
+    /// # use url::Url;
+    /// # async {
+    /// # let homeserver = Url::parse("http://localhost:8080")?;
+    /// # let client = Client::new(homeserver).await?;
+    /// let (push_rules, push_rules_stream) =
+    ///     client.account().subscribe_to_push_rules().await?;
+    ///
+    /// println!("Keyword rules: {:?}", push_rules.content);
+    ///
+    /// pin_mut!(push_rules_stream);
+    /// while let Some(push_rules) = push_rules_stream.next().await {
+    ///     println!("Keyword rules changed: {:?}", push_rules.content);
+    /// }
+    /// # anyhow::Ok(()) };
+    /// ```
+    pub async fn subscribe_to_push_rules(&self) -> Result<(Ruleset, impl Stream<Item = Ruleset>)> {
+        let user_id = self.client.user_id().ok_or(Error::AuthenticationRequired)?.to_owned();
+
+        let observer =
+            self.client.observe_events::<Raw<GlobalAccountDataEvent<PushRulesEventContent>>, ()>();
+        let mut events = observer.subscribe();
+
+        // We get the initial value after creating the observer, to make sure that we
+        // don't miss an update.
+        let content = self.account_data::<PushRulesEventContent>().await?;
+        let push_rules = self.ruleset_or_server_default(content, &user_id);
+
+        let account = self.clone();
+        let stream = async_stream::stream! {
+            // The observer needs to be alive for the stream to be alive.
+            let _observer = observer;
+
+            while let Some((event, ())) = events.next().await {
+                let content = match get_raw_content(Some(event.clone())) {
+                    Ok(content) => content,
+                    Err(err) => {
+                        account.log_invalid_push_rules(event.json().get(), &err);
+                        None
+                    }
+                };
+                yield account.ruleset_or_server_default(content, &user_id);
+            }
+        };
+
+        Ok((push_rules, stream))
+    }
+
+    /// Deserialize the given push rules event content, or return the
+    /// server-default push rules if there is no content or if it fails to
+    /// deserialize.
+    fn ruleset_or_server_default(
+        &self,
+        content: Option<Raw<PushRulesEventContent>>,
+        user_id: &UserId,
+    ) -> Ruleset {
+        content
+            .and_then(|raw| match raw.deserialize() {
+                Ok(content) => Some(content.global),
+                Err(err) => {
+                    self.log_invalid_push_rules(raw.json().get(), &err);
                     None
                 }
             })
-            .unwrap_or_else(|| {
-                Ruleset::server_default(
-                    self.client.user_id().expect("The client should be logged in"),
-                )
-            }))
+            .unwrap_or_else(|| Ruleset::server_default(user_id))
+    }
+
+    /// Log that the given push rules payload failed to deserialize, unless it
+    /// was already logged.
+    fn log_invalid_push_rules(&self, payload: &str, err: &dyn std::error::Error) {
+        let is_new = self
+            .client
+            .inner
+            .invalid_push_rules_payloads
+            .lock()
+            .unwrap()
+            .insert(payload.to_owned());
+
+        if is_new {
+            error!("Push rules event failed to deserialize: {err}");
+        }
+    }
+
+    /// Get the differences between the current push rules from storage and
+    /// the server-default push rules.
+    ///
+    /// This is useful to show which notification settings have been changed
+    /// by the user.
+    pub async fn push_rules_diff(&self) -> Result<PushRulesDiff> {
+        let user_id = self.client.user_id().ok_or(Error::AuthenticationRequired)?;
+        let push_rules = self.push_rules().await?;
+        Ok(PushRulesDiff::new(&push_rules, &Ruleset::server_default(user_id)))
+    }
+
+    /// Get the keywords that have enabled push rules, from storage.
+    ///
+    /// Keywords that only differ in case are only returned once, since the
+    /// patterns of the push rules are matched case-insensitively.
+    pub async fn keywords(&self) -> Result<Vec<String>> {
+        let push_rules = self.push_rules().await?;
+
+        let mut keywords: Vec<String> = Vec::new();
+        for rule in push_rules.content.iter().filter(|rule| !rule.default && rule.enabled) {
+            let pattern = rule.pattern.to_lowercase();
+            if !keywords.iter().any(|keyword| keyword.to_lowercase() == pattern) {
+                keywords.push(rule.pattern.clone());
+            }
+        }
+
+        Ok(keywords)
+    }
+
+    /// Add a push rule to be notified of the messages containing the given
+    /// keyword.
+    ///
+    /// Nothing is done if there is already an enabled rule for the keyword,
+    /// regardless of its case. A disabled rule for the keyword is enabled
+    /// instead of creating a new one.
+    ///
+    /// The push rules in storage are updated, so [`Account::push_rules()`]
+    /// and [`Account::keywords()`] include the rule right away.
+    pub async fn add_keyword_rule(&self, keyword: &str) -> Result<(), NotificationSettingsError> {
+        if keyword.trim().is_empty() {
+            return Err(NotificationSettingsError::InvalidParameter("keyword".to_owned()));
+        }
+
+        let notification_settings = self.client.notification_settings().await;
+        notification_settings.add_keyword(keyword.to_owned()).await?;
+        self.save_push_rules(notification_settings.ruleset().await).await
+    }
+
+    /// Remove the push rules for the given keyword, regardless of its case.
+    ///
+    /// The push rules in storage are updated, so [`Account::push_rules()`]
+    /// and [`Account::keywords()`] don't include the rules anymore.
+    pub async fn remove_keyword_rule(
+        &self,
+        keyword: &str,
+    ) -> Result<(), NotificationSettingsError> {
+        let notification_settings = self.client.notification_settings().await;
+        notification_settings.remove_keyword(keyword).await?;
+        self.save_push_rules(notification_settings.ruleset().await).await
+    }
+
+    /// Store the given push rules as they would be received via the sync.
+    async fn save_push_rules(&self, ruleset: Ruleset) -> Result<(), NotificationSettingsError> {
+        let save = async {
+            let event = Raw::new(&json!({
+                "type": GlobalAccountDataEventType::PushRules,
+                "content": PushRulesEventContent::new(ruleset),
+            }))?
+            .cast_unchecked();
+
+            let _sync_lock = self.client.base_client().sync_lock().lock().await;
+            let mut changes = StateChanges::default();
+            changes.account_data.insert(GlobalAccountDataEventType::PushRules, event);
+            self.client.state_store().save_changes(&changes).await?;
+
+            Ok::<_, Error>(())
+        };
+
+        save.await.map_err(|error| {
+            error!("Unable to save the push rules: {error}");
+            NotificationSettingsError::UnableToSavePushRules
+        })
+    }
+
+    /// Reset the push rules to the server-default push rules.
+    ///
+    /// Unlike replacing the whole push rules account data event, this deletes
+    /// the user-defined rules and restores the enabled state and the actions
+    /// of the server-default rules one by one, using the `/pushrules`
+    /// endpoints.
+    ///
+    /// If `keep_keywords` is `true`, the rules for the user's keywords are
+    /// kept.
+    pub async fn reset_push_rules(
+        &self,
+        keep_keywords: bool,
+    ) -> Result<(), NotificationSettingsError> {
+        self.client.notification_settings().await.reset_push_rules(keep_keywords).await
+    }
+
+    /// Set whether the user is notified when they're invited to a room.
+    ///
+    /// This changes the actions of the `.m.rule.invite_for_me` server-default
+    /// push rule. See
+    /// [`NotificationSettings::set_invite_notifications_enabled`]
+    /// for more details.
+    ///
+    /// To filter out the invites sent by unknown users instead, see
+    /// [`Client::set_invite_filter`].
+    ///
+    /// [`NotificationSettings::set_invite_notifications_enabled`]: crate::notification_settings::NotificationSettings::set_invite_notifications_enabled
+    pub async fn set_invite_notifications_enabled(
+        &self,
+        enabled: bool,
+    ) -> Result<(), NotificationSettingsError> {
+        self.client.notification_settings().await.set_invite_notifications_enabled(enabled).await
     }
 
     /// Retrieves the user's recently visited room list
     pub async fn get_recently_visited_rooms(&self) -> Result<Vec<OwnedRoomId>> {
         let user_id = self.client.user_id().ok_or(Error::AuthenticationRequired)?;
-        let data = self
-            .client
-            .state_store()
-            .get_kv_data(StateStoreDataKey::RecentlyVisitedRooms(user_id))
-            .await?;
+        let mut recently_visited_rooms = self.client.inner.recently_visited_rooms.lock().await;
+        Ok(self.load_recently_visited_rooms(user_id, &mut recently_visited_rooms).await?.clone())
+    }
 
-        Ok(data
-            .map(|v| {
-                v.into_recently_visited_rooms()
-                    .expect("Session data is not a list of recently visited rooms")
-            })
-            .unwrap_or_default())
+    /// Moves/inserts the given room to the front of the recently visited list
+    ///
+    /// The list is updated in memory right away, but it is written to the
+    /// store after a short delay, so the rapid calls to this method only
+    /// result in a single write.
+    pub async fn track_recently_visited_room(&self, room_id: OwnedRoomId) -> Result<(), Error> {
+        let user_id = self.client.user_id().ok_or(Error::AuthenticationRequired)?;
+
+        // Keep the lock during the whole update, so concurrent calls don't clobber each
+        // other.
+        let mut recently_visited_rooms = self.client.inner.recently_visited_rooms.lock().await;
+        let limit = recently_visited_rooms.limit;
+        let rooms = self.load_recently_visited_rooms(user_id, &mut recently_visited_rooms).await?;
+
+        // Remove all other occurrences of the new room_id
+        rooms.retain(|r| r != &room_id);
+
+        // And insert it as the most recent
+        rooms.insert(0, room_id);
+
+        // Cap the whole list to the configured limit
+        rooms.truncate(limit);
+
+        self.schedule_recently_visited_rooms_write(user_id, &mut recently_visited_rooms);
+
+        Ok(())
+    }
+
+    /// Removes the given room from the recently visited list, if it is in it.
+    ///
+    /// This is done automatically when a room is forgotten with
+    /// [`Room::forget()`](crate::Room::forget).
+    pub async fn remove_recently_visited_room(&self, room_id: &RoomId) -> Result<(), Error> {
+        let user_id = self.client.user_id().ok_or(Error::AuthenticationRequired)?;
+
+        let mut recently_visited_rooms = self.client.inner.recently_visited_rooms.lock().await;
+        let rooms = self.load_recently_visited_rooms(user_id, &mut recently_visited_rooms).await?;
+
+        let previous_len = rooms.len();
+        rooms.retain(|r| r != room_id);
+
+        if rooms.len() != previous_len {
+            self.schedule_recently_visited_rooms_write(user_id, &mut recently_visited_rooms);
+        }
+
+        Ok(())
+    }
+
+    /// Set the maximum number of rooms kept in the recently visited list.
+    ///
+    /// The list is truncated to the new limit the next time a room is tracked
+    /// with [`Account::track_recently_visited_room()`]. The default is 20.
+    pub async fn set_recently_visited_rooms_limit(&self, limit: usize) {
+        self.client.inner.recently_visited_rooms.lock().await.limit = limit;
+    }
+
+    /// Spawn a task writing the recently visited rooms to the store after a
+    /// short delay, unless one is already pending.
+    fn schedule_recently_visited_rooms_write(
+        &self,
+        user_id: &UserId,
+        recently_visited_rooms: &mut RecentlyVisitedRooms,
+    ) {
+        if recently_visited_rooms.has_pending_write {
+            return;
+        }
+
+        recently_visited_rooms.has_pending_write = true;
+
+        // The task only holds the base client, so the write still happens if the
+        // client is dropped in the meantime.
+        spawn(write_recently_visited_rooms(
+            self.client.base_client().clone(),
+            self.client.inner.recently_visited_rooms.clone(),
+            user_id.to_owned(),
+        ));
+    }
+
+    /// Get the in-memory copy of the recently visited rooms, loading it from
+    /// the store if needed.
+    async fn load_recently_visited_rooms<'a>(
+        &self,
+        user_id: &UserId,
+        recently_visited_rooms: &'a mut RecentlyVisitedRooms,
+    ) -> Result<&'a mut Vec<OwnedRoomId>> {
+        if recently_visited_rooms.rooms.is_none() {
+            let data = self
+                .client
+                .state_store()
+                .get_kv_data(StateStoreDataKey::RecentlyVisitedRooms(user_id))
+                .await?;
+
+            recently_visited_rooms.rooms = Some(
+                data.map(|v| {
+                    v.into_recently_visited_rooms()
+                        .expect("Session data is not a list of recently visited rooms")
+                })
+                .unwrap_or_default(),
+            );
+        }
+
+        Ok(recently_visited_rooms.rooms.get_or_insert_default())
+    }
+
+    /// Get the API to manage the image pack of the user, i.e. their custom
+    /// emoticons and stickers.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use matrix_sdk::{Client, image_pack::PackImage, ruma::owned_mxc_uri};
+    /// # async {
+    /// # let client = Client::new("http://localhost:8080".parse()?).await?;
+    /// let image_packs = client.account().image_packs();
+    ///
+    /// image_packs
+    ///     .add_image("party_parrot", PackImage::new(owned_mxc_uri!("mxc://example.org/parrot")))
+    ///     .await?;
+    ///
+    /// let pack = image_packs.get().await?;
+    /// for (shortcode, image) in pack.stickers() {
+    ///     println!("{shortcode}: {}", image.url);
+    /// }
+    /// # anyhow::Ok(()) };
+    /// ```
+    pub fn image_packs(&self) -> ImagePacks {
+        ImagePacks::new(self.client.clone())
     }
 
-    /// Moves/inserts the given room to the front of the recently visited list
-    pub async fn track_recently_visited_room(&self, room_id: OwnedRoomId) -> Result<(), Error> {
-        let user_id = self.client.user_id().ok_or(Error::AuthenticationRequired)?;
-
-        // Get the previously stored recently visited rooms
-        let mut recently_visited_rooms = self.get_recently_visited_rooms().await?;
-
-        // Remove all other occurrences of the new room_id
-        recently_visited_rooms.retain(|r| r != &room_id);
+    /// Get the invite policy of the user, from storage.
+    ///
+    /// Returns the default policy, allowing all the invites, if the user
+    /// doesn't have one.
+    pub async fn invite_policy(&self) -> Result<InvitePolicy> {
+        let Some(raw_content) = self.account_data_raw(INVITE_POLICY_EVENT_TYPE.into()).await?
+        else {
+            return Ok(InvitePolicy::default());
+        };
 
-        // And insert it as the most recent
-        recently_visited_rooms.insert(0, room_id);
+        Ok(raw_content.deserialize_as_unchecked()?)
+    }
 
-        // Cap the whole list to the VISITED_ROOMS_LIMIT
-        recently_visited_rooms.truncate(Self::VISITED_ROOMS_LIMIT);
+    /// Set the invite policy of the user, in their account data.
+    ///
+    /// The policy is shared by all the clients of the user. It applies to the
+    /// invites once it's received back via the sync, at which point the
+    /// current invites are classified again.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use matrix_sdk::{Client, invite_filter::InvitePolicy};
+    /// # async {
+    /// # let client = Client::new("http://localhost:8080".parse()?).await?;
+    /// let policy = InvitePolicy::default()
+    ///     .allow_user("@*:example.org")
+    ///     .block_server("spam.example.com")
+    ///     .only_known_users(true);
+    ///
+    /// client.account().set_invite_policy(policy).await?;
+    /// # anyhow::Ok(()) };
+    /// ```
+    pub async fn set_invite_policy(&self, policy: InvitePolicy) -> Result<()> {
+        self.set_account_data_raw(
+            INVITE_POLICY_EVENT_TYPE.into(),
+            Raw::new(&policy)?.cast_unchecked(),
+        )
+        .await?;
 
-        let data = StateStoreDataValue::RecentlyVisitedRooms(recently_visited_rooms);
-        self.client
-            .state_store()
-            .set_kv_data(StateStoreDataKey::RecentlyVisitedRooms(user_id), data)
-            .await?;
         Ok(())
     }
 
-    /// Observes the media preview configuration.
+    /// Subscribe to the media preview configuration of the account.
     ///
     /// This value is linked to the [MSC 4278](https://github.com/matrix-org/matrix-spec-proposals/pull/4278) which is still in an unstable state.
     ///
-    /// This will return the initial value of the configuration and a stream
-    /// that will yield new values as they are received.
+    /// The first item of the returned stream is the current configuration,
+    /// from the store, or the default configuration if there is none. The
+    /// following items are the new configurations received from the
+    /// homeserver. The same configuration is never yielded twice in a row.
     ///
-    /// The initial value is the one that was stored in the account data
-    /// when the client was started.
-    /// and the following code is using a temporary solution until we know which
-    /// Matrix version will support the stable type.
+    /// The stable `m.media_preview_config` event takes precedence over the
+    /// unstable one, even when both are received in the same sync.
     ///
     /// # Examples
     ///
     /// ```no_run
     /// # use futures_util::{pin_mut, StreamExt};
     /// # use matrix_sdk::Client;
-    /// # use matrix_sdk::ruma::events::media_preview_config::MediaPreviews;
     /// # use url::Url;
     /// # async {
     /// # let homeserver = Url::parse("http://localhost:8080")?;
     /// # let client = Client::new(homeserver).await?;
-    /// let account = client.account();
-    ///
-    /// let (initial_config, config_stream) =
-    ///     account.observe_media_preview_config().await?;
-    ///
-    /// println!("Initial media preview config: {:?}", initial_config);
+    /// let config_stream =
+    ///     client.account().subscribe_to_media_preview_config().await?;
     ///
     /// pin_mut!(config_stream);
-    /// while let Some(new_config) = config_stream.next().await {
-    ///     println!("Updated media preview config: {:?}", new_config);
+    /// while let Some(config) = config_stream.next().await {
+    ///     println!("Media preview config: {config:?}");
     /// }
     /// # anyhow::Ok(()) };
     /// ```
-    pub async fn observe_media_preview_config(
+    pub async fn subscribe_to_media_preview_config(
         &self,
-    ) -> Result<
-        (
-            Option<MediaPreviewConfigEventContent>,
-            impl Stream<Item = MediaPreviewConfigEventContent>,
-        ),
-        Error,
-    > {
+    ) -> Result<impl Stream<Item = MediaPreviewConfigEventContent>> {
         // We need to create two observers, one for the stable event and one for the
-        // unstable and combine them into a single stream.
-        let first_observer = self
+        // unstable.
+        let stable_observer = self
             .client
             .observe_events::<GlobalAccountDataEvent<MediaPreviewConfigEventContent>, ()>();
-
-        let stream = first_observer.subscribe().map(|event| event.0.content);
-
-        let second_observer = self
+        let unstable_observer = self
             .client
             .observe_events::<GlobalAccountDataEvent<UnstableMediaPreviewConfigEventContent>, ()>();
 
-        let second_stream = second_observer.subscribe().map(|event| event.0.content.0);
-
-        let mut combined_stream = stream::select(stream, second_stream);
+        let mut updates = stream::select(
+            stable_observer.subscribe().map(|_| ()),
+            unstable_observer.subscribe().map(|_| ()),
+        );
 
-        let result_stream = async_stream::stream! {
-            // The observers need to be alive for the individual streams to be alive, so let's now
-            // create a stream that takes ownership of them.
-            let _first_observer = first_observer;
-            let _second_observer = second_observer;
+        // We get the initial value after creating the observers, to make sure that we
+        // don't miss an update.
+        let initial_config =
+            self.get_media_preview_config_event_content().await?.unwrap_or_default();
+
+        let account = self.clone();
+        let stream = async_stream::stream! {
+            // The observers need to be alive for the stream to be alive.
+            let _stable_observer = stable_observer;
+            let _unstable_observer = unstable_observer;
+
+            let mut last_config = initial_config.clone();
+            yield initial_config;
+
+            while updates.next().await.is_some() {
+                // The config is read from the store rather than from the event, so the
+                // unstable event never overrides the stable one.
+                let config = match account.get_media_preview_config_event_content().await {
+                    Ok(config) => config.unwrap_or_default(),
+                    Err(err) => {
+                        warn!("couldn't load the media preview config: {err}");
+                        continue;
+                    }
+                };
 
-            while let Some(item) = combined_stream.next().await {
-                yield item
+                // Don't yield the same config twice in a row.
+                if !is_same_media_preview_config(&config, &last_config) {
+                    last_config = config.clone();
+                    yield config;
+                }
             }
         };
 
-        // We need to get the initial value of the media preview config event
-        // we do this after creating the observers to make sure that we don't
-        // create a race condition
-        let initial_value = self.get_media_preview_config_event_content().await?;
+        Ok(stream)
+    }
+
+    /// Observes the list of users ignored by the user.
+    ///
+    /// This will return the current list of ignored users, from storage, and
+    /// a stream that will yield the new list every time it changes, be it
+    /// with [`Account::ignore_user`] or from another client of the user.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use futures_util::{pin_mut, StreamExt};
+    /// # use matrix_sdk::Client;
+    /// # use url::Url;
+    /// # async {
+    /// # let homeserver = Url::parse("http://localhost:8080")?;
+    /// # let client = Client::new(homeserver).await?;
+    /// let (ignored_users, ignored_users_stream) =
+    ///     client.account().subscribe_to_ignored_users().await?;
+    ///
+    /// println!("Ignored users: {ignored_users:?}");
+    ///
+    /// pin_mut!(ignored_users_stream);
+    /// while let Some(ignored_users) = ignored_users_stream.next().await {
+    ///     println!("Ignored users changed: {ignored_users:?}");
+    /// }
+    /// # anyhow::Ok(()) };
+    /// ```
+    pub async fn subscribe_to_ignored_users(
+        &self,
+    ) -> Result<(Vec<OwnedUserId>, impl Stream<Item = Vec<OwnedUserId>>)> {
+        let observer =
+            self.client.observe_events::<GlobalAccountDataEvent<IgnoredUserListEventContent>, ()>();
+        let mut events = observer.subscribe();
+
+        // We get the initial value after creating the observer, to make sure that we
+        // don't miss an update.
+        let ignored_users: Vec<_> =
+            self.get_ignored_user_list_event_content().await?.ignored_users.into_keys().collect();
+
+        let mut last_ignored_users = ignored_users.clone();
+        let stream = async_stream::stream! {
+            // The observer needs to be alive for the stream to be alive.
+            let _observer = observer;
+
+            while let Some((event, ())) = events.next().await {
+                let ignored_users: Vec<_> = event.content.ignored_users.into_keys().collect();
+
+                // Don't yield the same list twice in a row.
+                if ignored_users != last_ignored_users {
+                    last_ignored_users = ignored_users.clone();
+                    yield ignored_users;
+                }
+            }
+        };
 
-        Ok((initial_value, result_stream))
+        Ok((ignored_users, stream))
     }
 
     /// Fetch the media preview configuration event content from the server.
@@ -1132,6 +3104,58 @@ impl Account {
         }
     }
 
+    /// Get the media preview configuration stored in the cache, with the
+    /// default values applied to the policies that are not set.
+    ///
+    /// Like [`Account::get_media_preview_config_event_content()`], the stable
+    /// event takes precedence over the unstable one.
+    ///
+    /// This also refreshes the copy returned by
+    /// [`Account::cached_effective_media_preview_config()`].
+    pub async fn effective_media_preview_config(&self) -> Result<EffectiveMediaPreviewConfig> {
+        let config: EffectiveMediaPreviewConfig =
+            self.get_media_preview_config_event_content().await?.unwrap_or_default().into();
+        *self.client.inner.media_preview_config.write().unwrap() = Some(config.clone());
+        Ok(config)
+    }
+
+    /// Get the in-memory copy of the media preview configuration, with the
+    /// default values applied to the policies that are not set.
+    ///
+    /// This doesn't wait for the store, so it can be called when rendering
+    /// the UI. The copy is loaded from the store during the first sync, and
+    /// updated when a new configuration is received. Before that, the default
+    /// configuration is returned, unless
+    /// [`Account::effective_media_preview_config()`] has been called.
+    pub fn cached_effective_media_preview_config(&self) -> EffectiveMediaPreviewConfig {
+        self.client.inner.media_preview_config.read().unwrap().clone().unwrap_or_default()
+    }
+
+    /// Refresh the in-memory copy of the media preview configuration if it
+    /// hasn't been loaded yet, or if the given global account data events
+    /// contain a new configuration.
+    pub(crate) async fn handle_media_preview_config_update(
+        &self,
+        account_data: &[Raw<AnyGlobalAccountDataEvent>],
+    ) {
+        let is_loaded = self.client.inner.media_preview_config.read().unwrap().is_some();
+        let has_new_config = account_data.iter().any(|event| {
+            let event_type = event.get_field::<String>("type").ok().flatten();
+            event_type.as_deref().is_some_and(|event_type| {
+                event_type == MediaPreviewConfigEventContent::TYPE
+                    || event_type == UnstableMediaPreviewConfigEventContent::TYPE
+            })
+        });
+
+        if is_loaded && !has_new_config {
+            return;
+        }
+
+        if let Err(err) = self.effective_media_preview_config().await {
+            warn!("couldn't load the media preview config: {err}");
+        }
+    }
+
     /// Set the media previews display policy in the timeline.
     ///
     /// This will always use the unstable event until we know which Matrix
@@ -1144,7 +3168,7 @@ impl Account {
         // Updating the unstable account data
         let unstable_media_preview_config =
             UnstableMediaPreviewConfigEventContent::from(media_preview_config);
-        self.set_account_data(unstable_media_preview_config).await?;
+        self.set_account_data_if_changed(unstable_media_preview_config).await?;
         Ok(())
     }
 
@@ -1160,11 +3184,98 @@ impl Account {
         // Updating the unstable account data
         let unstable_media_preview_config =
             UnstableMediaPreviewConfigEventContent::from(media_preview_config);
-        self.set_account_data(unstable_media_preview_config).await?;
+        self.set_account_data_if_changed(unstable_media_preview_config).await?;
         Ok(())
     }
 }
 
+/// Whether both media preview configurations have the same policies.
+fn is_same_media_preview_config(
+    a: &MediaPreviewConfigEventContent,
+    b: &MediaPreviewConfigEventContent,
+) -> bool {
+    a.media_previews == b.media_previews && a.invite_avatars == b.invite_avatars
+}
+
+/// Convert an error of a request authenticated with a password into the
+/// specific [`Error`] variants, if possible.
+fn password_auth_error(error: HttpError) -> Error {
+    // A wrong password is reported in the UIAA response, as the authentication
+    // stage isn't completed.
+    if let Some(uiaa_info) = error.as_uiaa_response() {
+        if uiaa_info
+            .auth_error
+            .as_ref()
+            .is_some_and(|auth_error| matches!(auth_error.kind, ErrorKind::Forbidden { .. }))
+        {
+            return Error::WrongPassword;
+        }
+    }
+
+    if let Some(client_api_error) = error.as_client_api_error() {
+        if let ErrorBody::Standard { kind, message } = &client_api_error.body {
+            match kind {
+                ErrorKind::Forbidden { .. } => return Error::WrongPassword,
+                ErrorKind::WeakPassword => return Error::WeakPassword(message.clone()),
+                _ => {}
+            }
+        }
+    }
+
+    error.into()
+}
+
+/// Remove the duplicates in the given list of rooms, keeping the first
+/// occurrence of each room.
+fn dedup_room_ids(room_ids: &mut Vec<OwnedRoomId>) {
+    let mut seen = BTreeSet::new();
+    room_ids.retain(|room_id| seen.insert(room_id.clone()));
+}
+
+/// Write the recently visited rooms to the store, after
+/// [`Account::VISITED_ROOMS_WRITE_DELAY`].
+async fn write_recently_visited_rooms(
+    base_client: BaseClient,
+    recently_visited_rooms: Arc<Mutex<RecentlyVisitedRooms>>,
+    user_id: OwnedUserId,
+) {
+    sleep(Account::VISITED_ROOMS_WRITE_DELAY).await;
+
+    // Keep the lock during the write, so the writes happen in order.
+    let mut recently_visited_rooms = recently_visited_rooms.lock().await;
+    recently_visited_rooms.has_pending_write = false;
+
+    let Some(rooms) = recently_visited_rooms.rooms.clone() else {
+        return;
+    };
+
+    if let Err(err) = base_client
+        .state_store()
+        .set_kv_data(
+            StateStoreDataKey::RecentlyVisitedRooms(&user_id),
+            StateStoreDataValue::RecentlyVisitedRooms(rooms),
+        )
+        .await
+    {
+        error!("couldn't save the recently visited rooms: {err}");
+    }
+}
+
+/// Whether the given JSON and the serialized content are the same, once
+/// converted to canonical JSON.
+///
+/// Returns `false` if either of them can't be converted to canonical JSON.
+fn is_same_canonical_json(json: &RawJsonValue, content: &impl Serialize) -> bool {
+    let to_canonical_json = |value: serde_json::Result<JsonValue>| {
+        value.ok().and_then(|value| CanonicalJsonValue::try_from(value).ok())
+    };
+
+    let current = to_canonical_json(serde_json::from_str(json.get()));
+    let new = to_canonical_json(serde_json::to_value(content));
+
+    current.is_some() && current == new
+}
+
 fn get_raw_content<Ev, C>(raw: Option<Raw<Ev>>) -> Result<Option<Raw<C>>> {
     #[derive(Deserialize)]
     #[serde(bound = "C: Sized")] // Replace default Deserialize bound
@@ -1172,18 +3283,238 @@ fn get_raw_content<Ev, C>(raw: Option<Raw<Ev>>) -> Result<Option<Raw<C>>> {
         content: Raw<C>,
     }
 
-    Ok(raw
-        .map(|event| event.deserialize_as_unchecked::<GetRawContent<C>>())
-        .transpose()?
-        .map(|get_raw| get_raw.content))
+    let Some(event) = raw else {
+        return Ok(None);
+    };
+    let content = event.deserialize_as_unchecked::<GetRawContent<C>>()?.content;
+
+    // A deleted account data event has an empty content, as defined in MSC3391.
+    let is_deleted = content
+        .deserialize_as_unchecked::<BTreeMap<String, IgnoredAny>>()
+        .is_ok_and(|fields| fields.is_empty());
+
+    Ok((!is_deleted).then_some(content))
+}
+
+/// Whether the given string has the shape of an IANA timezone identifier, like
+/// `Europe/Paris` or `America/Argentina/Buenos_Aires`.
+///
+/// This doesn't check that the timezone exists in the tz database.
+#[cfg(feature = "unstable-msc4175")]
+fn is_plausible_timezone(tz: &str) -> bool {
+    let mut parts = tz.split('/');
+
+    let Some(area) = parts.next() else {
+        return false;
+    };
+    let is_area_valid = area.starts_with(|c: char| c.is_ascii_uppercase())
+        && area.chars().all(|c| c.is_ascii_alphabetic());
+
+    let mut has_location = false;
+    for location in parts {
+        if location.is_empty()
+            || !location.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+'))
+        {
+            return false;
+        }
+
+        has_location = true;
+    }
+
+    is_area_valid && has_location
+}
+
+/// The unstable endpoints to manage the timezone of a user, as defined in
+/// [MSC4175](https://github.com/matrix-org/matrix-spec-proposals/pull/4175).
+///
+/// The timezone is a custom profile field, as defined in
+/// [MSC4133](https://github.com/matrix-org/matrix-spec-proposals/pull/4133).
+#[cfg(feature = "unstable-msc4175")]
+mod timezone {
+    pub mod get_timezone {
+        use ruma::{
+            api::{request, response, Metadata},
+            metadata, OwnedUserId,
+        };
+
+        const METADATA: Metadata = metadata! {
+            method: GET,
+            rate_limited: false,
+            authentication: AccessToken,
+            history: {
+                unstable => "/_matrix/client/unstable/uk.tcpip.msc4133/profile/{user_id}/us.cloke.msc4175.tz",
+            }
+        };
+
+        /// Request type for the `get_timezone` endpoint.
+        #[request(error = ruma::api::client::Error)]
+        pub struct Request {
+            /// The ID of the user to get the timezone of.
+            #[ruma_api(path)]
+            pub user_id: OwnedUserId,
+        }
+
+        /// Response type for the `get_timezone` endpoint.
+        #[response(error = ruma::api::client::Error)]
+        #[derive(Default)]
+        pub struct Response {
+            /// The timezone of the user.
+            #[serde(rename = "us.cloke.msc4175.tz", skip_serializing_if = "Option::is_none")]
+            pub tz: Option<String>,
+        }
+
+        impl Request {
+            /// Creates a new `Request` with the given user ID.
+            pub fn new(user_id: OwnedUserId) -> Self {
+                Self { user_id }
+            }
+        }
+    }
+
+    pub mod set_timezone {
+        use ruma::{
+            api::{request, response, Metadata},
+            metadata, OwnedUserId,
+        };
+
+        const METADATA: Metadata = metadata! {
+            method: PUT,
+            rate_limited: true,
+            authentication: AccessToken,
+            history: {
+                unstable => "/_matrix/client/unstable/uk.tcpip.msc4133/profile/{user_id}/us.cloke.msc4175.tz",
+            }
+        };
+
+        /// Request type for the `set_timezone` endpoint.
+        #[request(error = ruma::api::client::Error)]
+        pub struct Request {
+            /// The ID of the user to set the timezone of.
+            #[ruma_api(path)]
+            pub user_id: OwnedUserId,
+
+            /// The IANA identifier of the timezone.
+            #[serde(rename = "us.cloke.msc4175.tz")]
+            pub tz: String,
+        }
+
+        /// Response type for the `set_timezone` endpoint.
+        #[response(error = ruma::api::client::Error)]
+        #[derive(Default)]
+        pub struct Response {}
+
+        impl Request {
+            /// Creates a new `Request` with the given user ID and timezone.
+            pub fn new(user_id: OwnedUserId, tz: String) -> Self {
+                Self { user_id, tz }
+            }
+        }
+    }
+
+    pub mod delete_timezone {
+        use ruma::{
+            api::{request, response, Metadata},
+            metadata, OwnedUserId,
+        };
+
+        const METADATA: Metadata = metadata! {
+            method: DELETE,
+            rate_limited: true,
+            authentication: AccessToken,
+            history: {
+                unstable => "/_matrix/client/unstable/uk.tcpip.msc4133/profile/{user_id}/us.cloke.msc4175.tz",
+            }
+        };
+
+        /// Request type for the `delete_timezone` endpoint.
+        #[request(error = ruma::api::client::Error)]
+        pub struct Request {
+            /// The ID of the user to remove the timezone of.
+            #[ruma_api(path)]
+            pub user_id: OwnedUserId,
+        }
+
+        /// Response type for the `delete_timezone` endpoint.
+        #[response(error = ruma::api::client::Error)]
+        #[derive(Default)]
+        pub struct Response {}
+
+        impl Request {
+            /// Creates a new `Request` with the given user ID.
+            pub fn new(user_id: OwnedUserId) -> Self {
+                Self { user_id }
+            }
+        }
+    }
+}
+
+/// The unstable endpoint to delete a global account data event, as defined in
+/// [MSC3391](https://github.com/matrix-org/matrix-spec-proposals/pull/3391).
+mod delete_global_account_data {
+    use ruma::{
+        api::{request, response, Metadata},
+        events::GlobalAccountDataEventType,
+        metadata, OwnedUserId,
+    };
+
+    const METADATA: Metadata = metadata! {
+        method: DELETE,
+        rate_limited: false,
+        authentication: AccessToken,
+        history: {
+            unstable => "/_matrix/client/unstable/org.matrix.msc3391/user/{user_id}/account_data/{event_type}",
+        }
+    };
+
+    /// Request type for the `delete_global_account_data` endpoint.
+    #[request(error = ruma::api::client::Error)]
+    pub struct Request {
+        /// The ID of the user to delete account data for.
+        #[ruma_api(path)]
+        pub user_id: OwnedUserId,
+
+        /// The event type of the account data to delete.
+        #[ruma_api(path)]
+        pub event_type: GlobalAccountDataEventType,
+    }
+
+    /// Response type for the `delete_global_account_data` endpoint.
+    #[response(error = ruma::api::client::Error)]
+    #[derive(Default)]
+    pub struct Response {}
+
+    impl Request {
+        /// Creates a new `Request` with the given user ID and event type.
+        pub fn new(user_id: OwnedUserId, event_type: GlobalAccountDataEventType) -> Self {
+            Self { user_id, event_type }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use assert_matches::assert_matches;
-    use matrix_sdk_test::async_test;
+    use std::time::Duration;
 
-    use crate::{test_utils::client::MockClientBuilder, Error};
+    use assert_matches::assert_matches;
+    use futures_util::pin_mut;
+    use matrix_sdk_test::{async_test, GlobalAccountDataTestEvent};
+    use ruma::{
+        device_id,
+        events::{
+            ignored_user_list::{IgnoredUser, IgnoredUserListEventContent},
+            GlobalAccountDataEventType,
+        },
+        owned_device_id, owned_user_id,
+        push::Ruleset,
+        user_id,
+    };
+    use serde_json::json;
+    use stream_assert::{assert_pending, assert_ready};
+
+    use crate::{
+        test_utils::{client::MockClientBuilder, mocks::MatrixMockServer},
+        Error, NotificationSettingsError,
+    };
 
     #[async_test]
     async fn test_dont_ignore_oneself() {
@@ -1195,4 +3526,549 @@ mod tests {
             Err(Error::CantIgnoreLoggedInUser)
         );
     }
+
+    #[async_test]
+    async fn test_observe_account_data() {
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().build().await;
+        let account = client.account();
+
+        let recent_emoji = |emoji: &[&str]| {
+            GlobalAccountDataTestEvent::Custom(json!({
+                "content": { "recent_emoji": emoji },
+                "type": "io.element.recent_emoji",
+            }))
+        };
+
+        server
+            .mock_sync()
+            .ok_and_run(&client, |builder| {
+                builder
+                    .add_global_account_data_event(GlobalAccountDataTestEvent::IgnoredUserList)
+                    .add_global_account_data_event(recent_emoji(&["🎉"]));
+            })
+            .await;
+
+        let (ignored_users, ignored_users_stream) =
+            account.observe_account_data::<IgnoredUserListEventContent>().await.unwrap();
+        assert_eq!(ignored_users.unwrap().ignored_users.len(), 1);
+        pin_mut!(ignored_users_stream);
+        assert_pending!(ignored_users_stream);
+
+        let (recent_emoji_content, recent_emoji_stream) =
+            account.observe_account_data_raw("io.element.recent_emoji".into()).await.unwrap();
+        assert_eq!(
+            recent_emoji_content.unwrap().get_field::<Vec<String>>("recent_emoji").unwrap(),
+            Some(vec!["🎉".to_owned()])
+        );
+        pin_mut!(recent_emoji_stream);
+        assert_pending!(recent_emoji_stream);
+
+        // Only the stream of the updated type yields a new value.
+        server
+            .mock_sync()
+            .ok_and_run(&client, |builder| {
+                builder.add_global_account_data_event(recent_emoji(&["👍", "🎉"]));
+            })
+            .await;
+
+        let content = assert_ready!(recent_emoji_stream);
+        assert_eq!(
+            content.get_field::<Vec<String>>("recent_emoji").unwrap(),
+            Some(vec!["👍".to_owned(), "🎉".to_owned()])
+        );
+        assert_pending!(recent_emoji_stream);
+        assert_pending!(ignored_users_stream);
+
+        // The typed stream yields the deserialized content.
+        server
+            .mock_sync()
+            .ok_and_run(&client, |builder| {
+                builder.add_global_account_data_event(GlobalAccountDataTestEvent::Custom(json!({
+                    "content": { "ignored_users": {} },
+                    "type": "m.ignored_user_list",
+                })));
+            })
+            .await;
+
+        let content = assert_ready!(ignored_users_stream);
+        assert!(content.ignored_users.is_empty());
+        assert_pending!(ignored_users_stream);
+        assert_pending!(recent_emoji_stream);
+    }
+
+    #[async_test]
+    async fn test_set_account_data_if_changed() {
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().build().await;
+        let account = client.account();
+
+        server
+            .mock_sync()
+            .ok_and_run(&client, |builder| {
+                builder.add_global_account_data_event(GlobalAccountDataTestEvent::Custom(json!({
+                    "content": {
+                        "ignored_users": {
+                            "@bob:localhost": {},
+                            "@alice:localhost": {},
+                        },
+                    },
+                    "type": "m.ignored_user_list",
+                })));
+            })
+            .await;
+
+        // The content is identical, nothing is uploaded.
+        {
+            let _guard = server
+                .mock_set_global_account_data(GlobalAccountDataEventType::IgnoredUserList)
+                .ok()
+                .never()
+                .mount_as_scoped()
+                .await;
+
+            let mut content = IgnoredUserListEventContent::default();
+            content.ignored_users.insert(owned_user_id!("@alice:localhost"), IgnoredUser::new());
+            content.ignored_users.insert(owned_user_id!("@bob:localhost"), IgnoredUser::new());
+            assert!(!account.set_account_data_if_changed(content).await.unwrap());
+        }
+
+        // The content changed, it is uploaded.
+        server
+            .mock_set_global_account_data(GlobalAccountDataEventType::IgnoredUserList)
+            .ok()
+            .mock_once()
+            .mount()
+            .await;
+
+        let mut content = IgnoredUserListEventContent::default();
+        content.ignored_users.insert(owned_user_id!("@alice:localhost"), IgnoredUser::new());
+        assert!(account.set_account_data_if_changed(content).await.unwrap());
+    }
+
+    #[async_test]
+    async fn test_keyword_rules() {
+        use wiremock::{
+            matchers::{method, path_regex},
+            Mock, ResponseTemplate,
+        };
+
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().build().await;
+        let account = client.account();
+
+        assert!(account.keywords().await.unwrap().is_empty());
+
+        Mock::given(method("PUT"))
+            .and(path_regex(r"/pushrules/global/content/Banana$"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+            .expect(1)
+            .mount(server.server())
+            .await;
+
+        account.add_keyword_rule("Banana").await.unwrap();
+        assert_eq!(account.keywords().await.unwrap(), ["Banana"]);
+
+        // The same keyword with a different case is already matched.
+        account.add_keyword_rule("banana").await.unwrap();
+        assert_eq!(account.keywords().await.unwrap(), ["Banana"]);
+
+        // An empty keyword is rejected.
+        assert_matches!(
+            account.add_keyword_rule(" ").await,
+            Err(NotificationSettingsError::InvalidParameter(_))
+        );
+
+        Mock::given(method("DELETE"))
+            .and(path_regex(r"/pushrules/global/content/Banana$"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+            .expect(1)
+            .mount(server.server())
+            .await;
+
+        account.remove_keyword_rule("BANANA").await.unwrap();
+        assert!(account.keywords().await.unwrap().is_empty());
+    }
+
+    #[async_test]
+    async fn test_subscribe_to_push_rules() {
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().build().await;
+        let account = client.account();
+
+        let push_rules = |content: serde_json::Value| {
+            GlobalAccountDataTestEvent::Custom(json!({
+                "content": content,
+                "type": "m.push_rules",
+            }))
+        };
+        let has_keyword =
+            |ruleset: &Ruleset| ruleset.content.iter().any(|rule| rule.pattern == "banana");
+
+        // Without push rules, the server-default push rules are used.
+        let (ruleset, stream) = account.subscribe_to_push_rules().await.unwrap();
+        assert!(!has_keyword(&ruleset));
+        pin_mut!(stream);
+        assert_pending!(stream);
+
+        // The push rules are changed by another client.
+        server
+            .mock_sync()
+            .ok_and_run(&client, |builder| {
+                builder.add_global_account_data_event(push_rules(json!({
+                    "global": {
+                        "content": [{
+                            "actions": ["notify"],
+                            "default": false,
+                            "enabled": true,
+                            "pattern": "banana",
+                            "rule_id": "banana",
+                        }],
+                    },
+                })));
+            })
+            .await;
+
+        let ruleset = assert_ready!(stream);
+        assert!(has_keyword(&ruleset));
+        assert_pending!(stream);
+
+        // A second subscriber gets the current push rules.
+        let (ruleset, other_stream) = account.subscribe_to_push_rules().await.unwrap();
+        assert!(has_keyword(&ruleset));
+        pin_mut!(other_stream);
+
+        // Invalid push rules fall back to the server-default push rules, and the error
+        // is only logged once.
+        server
+            .mock_sync()
+            .ok_and_run(&client, |builder| {
+                builder.add_global_account_data_event(push_rules(json!({ "global": 42 })));
+            })
+            .await;
+
+        assert!(!has_keyword(&assert_ready!(stream)));
+        assert!(!has_keyword(&assert_ready!(other_stream)));
+        assert!(!has_keyword(&account.push_rules().await.unwrap()));
+        assert_eq!(client.inner.invalid_push_rules_payloads.lock().unwrap().len(), 1);
+    }
+
+    #[async_test]
+    async fn test_whoami() {
+        let server = MatrixMockServer::new().await;
+        let client = server
+            .client_builder()
+            .logged_in_with_token(
+                "token".to_owned(),
+                owned_user_id!("@joe:example.org"),
+                owned_device_id!("D3V1C31D"),
+            )
+            .build()
+            .await;
+
+        server.mock_who_am_i().ok().mock_once().mount().await;
+
+        let whoami = client.account().whoami().await.unwrap();
+        assert_eq!(whoami.user_id, owned_user_id!("@joe:example.org"));
+        assert_eq!(whoami.device_id.as_deref(), Some(device_id!("D3V1C31D")));
+        assert!(!whoami.is_guest);
+
+        // The access token belongs to another user.
+        let client = server.client_builder().build().await;
+        server.mock_who_am_i().ok().mock_once().mount().await;
+
+        assert_matches!(
+            client.account().whoami().await,
+            Err(Error::UserIdMismatch { expected, actual }) => {
+                assert_eq!(expected, owned_user_id!("@example:localhost"));
+                assert_eq!(actual, owned_user_id!("@joe:example.org"));
+            }
+        );
+    }
+
+    #[async_test]
+    async fn test_request_openid_token() {
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().build().await;
+        let account = client.account();
+
+        server.mock_request_openid_token().ok("first_token").mock_once().mount().await;
+
+        let token = account.request_openid_token(false).await.unwrap();
+        assert_eq!(token.access_token, "first_token");
+        assert_eq!(token.matrix_server_name.as_str(), "example.org");
+        assert!(token.expires_in <= Duration::from_secs(3600));
+
+        // The cached token is reused.
+        let token = account.request_openid_token(false).await.unwrap();
+        assert_eq!(token.access_token, "first_token");
+
+        // Unless a refresh is forced.
+        server.mock_request_openid_token().ok("second_token").mock_once().mount().await;
+
+        let token = account.request_openid_token(true).await.unwrap();
+        assert_eq!(token.access_token, "second_token");
+
+        // Or the token expires within the margin.
+        server.mock_request_openid_token().ok("third_token").mock_once().mount().await;
+
+        account.set_openid_token_expiry_margin(Duration::from_secs(3600)).await;
+        let token = account.request_openid_token(false).await.unwrap();
+        assert_eq!(token.access_token, "third_token");
+    }
+
+    #[async_test]
+    async fn test_delete_account_data() {
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().build().await;
+        let account = client.account();
+
+        server
+            .mock_sync()
+            .ok_and_run(&client, |builder| {
+                builder.add_global_account_data_event(GlobalAccountDataTestEvent::IgnoredUserList);
+            })
+            .await;
+        assert!(account.account_data::<IgnoredUserListEventContent>().await.unwrap().is_some());
+
+        // The homeserver doesn't support MSC3391.
+        server
+            .mock_delete_global_account_data(GlobalAccountDataEventType::IgnoredUserList)
+            .unrecognized()
+            .mock_once()
+            .mount()
+            .await;
+
+        assert_matches!(
+            account.delete_account_data(GlobalAccountDataEventType::IgnoredUserList).await,
+            Err(Error::AccountDataDeletionUnsupported)
+        );
+        assert!(account.account_data::<IgnoredUserListEventContent>().await.unwrap().is_some());
+
+        // The homeserver supports MSC3391, the event is removed from the store.
+        server
+            .mock_delete_global_account_data(GlobalAccountDataEventType::IgnoredUserList)
+            .ok()
+            .mock_once()
+            .mount()
+            .await;
+
+        account.delete_account_data(GlobalAccountDataEventType::IgnoredUserList).await.unwrap();
+        assert!(account.account_data::<IgnoredUserListEventContent>().await.unwrap().is_none());
+        assert!(account
+            .account_data_raw(GlobalAccountDataEventType::IgnoredUserList)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[async_test]
+    async fn test_secret_storage_set_up() {
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().build().await;
+        let account = client.account();
+
+        // There is no default key.
+        assert_eq!(account.secret_storage_default_key_id().await.unwrap(), None);
+        assert!(!account.is_secret_storage_set_up().await.unwrap());
+
+        // The default key references a missing key.
+        server
+            .mock_sync()
+            .ok_and_run(&client, |builder| {
+                builder.add_global_account_data_event(GlobalAccountDataTestEvent::Custom(json!({
+                    "type": "m.secret_storage.default_key",
+                    "content": { "key": "my_key" },
+                })));
+            })
+            .await;
+
+        assert_eq!(
+            account.secret_storage_default_key_id().await.unwrap().as_deref(),
+            Some("my_key")
+        );
+        assert!(!account.is_secret_storage_set_up().await.unwrap());
+
+        // The key is invalid.
+        server
+            .mock_sync()
+            .ok_and_run(&client, |builder| {
+                builder.add_global_account_data_event(GlobalAccountDataTestEvent::Custom(json!({
+                    "type": "m.secret_storage.key.my_key",
+                    "content": { "algorithm": 42 },
+                })));
+            })
+            .await;
+
+        assert!(!account.is_secret_storage_set_up().await.unwrap());
+
+        // The key is valid.
+        server
+            .mock_sync()
+            .ok_and_run(&client, |builder| {
+                builder.add_global_account_data_event(GlobalAccountDataTestEvent::Custom(json!({
+                    "type": "m.secret_storage.key.my_key",
+                    "content": {
+                        "algorithm": "m.secret_storage.v1.aes-hmac-sha2",
+                        "iv": "gH2iNpiETFhApvW6/FFEJQ",
+                        "mac": "9Lw12m5SKDipNghdQXKjgpfdj1/K7HFI2brO+UWAGoM",
+                    },
+                })));
+            })
+            .await;
+
+        assert!(account.is_secret_storage_set_up().await.unwrap());
+
+        // The default key is invalid.
+        server
+            .mock_sync()
+            .ok_and_run(&client, |builder| {
+                builder.add_global_account_data_event(GlobalAccountDataTestEvent::Custom(json!({
+                    "type": "m.secret_storage.default_key",
+                    "content": {},
+                })));
+            })
+            .await;
+
+        assert_eq!(account.secret_storage_default_key_id().await.unwrap(), None);
+        assert!(!account.is_secret_storage_set_up().await.unwrap());
+    }
+
+    #[async_test]
+    async fn test_account_data_or_fetch() {
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().build().await;
+        let account = client.account();
+        let user_id = client.user_id().unwrap();
+
+        let content = json!({
+            "ignored_users": {
+                "@someone:example.org": {},
+            },
+        });
+
+        // The store is empty, the content is fetched from the server.
+        server
+            .mock_global_account_data()
+            .ok(user_id, GlobalAccountDataEventType::IgnoredUserList, content.clone())
+            .mock_once()
+            .mount()
+            .await;
+
+        let fetched = account
+            .account_data_or_fetch::<IgnoredUserListEventContent>(false)
+            .await
+            .unwrap()
+            .unwrap()
+            .deserialize()
+            .unwrap();
+        assert!(fetched.ignored_users.contains_key(user_id!("@someone:example.org")));
+        assert!(account.account_data::<IgnoredUserListEventContent>().await.unwrap().is_none());
+
+        // The fetched content is persisted.
+        server
+            .mock_global_account_data()
+            .ok(user_id, GlobalAccountDataEventType::IgnoredUserList, content)
+            .mock_once()
+            .mount()
+            .await;
+
+        account.account_data_or_fetch::<IgnoredUserListEventContent>(true).await.unwrap().unwrap();
+        let stored = account
+            .account_data::<IgnoredUserListEventContent>()
+            .await
+            .unwrap()
+            .unwrap()
+            .deserialize()
+            .unwrap();
+        assert!(stored.ignored_users.contains_key(user_id!("@someone:example.org")));
+
+        // The next call doesn't hit the server.
+        account.account_data_or_fetch::<IgnoredUserListEventContent>(true).await.unwrap().unwrap();
+    }
+
+    #[cfg(feature = "unstable-msc4175")]
+    #[test]
+    fn test_is_plausible_timezone() {
+        use super::is_plausible_timezone;
+
+        assert!(is_plausible_timezone("Europe/Paris"));
+        assert!(is_plausible_timezone("America/Argentina/Buenos_Aires"));
+        assert!(is_plausible_timezone("America/Port-au-Prince"));
+        assert!(is_plausible_timezone("Etc/GMT+5"));
+
+        assert!(!is_plausible_timezone(""));
+        assert!(!is_plausible_timezone("Paris"));
+        assert!(!is_plausible_timezone("europe/Paris"));
+        assert!(!is_plausible_timezone("Europe/"));
+        assert!(!is_plausible_timezone("Europe//Paris"));
+        assert!(!is_plausible_timezone("Europe/Pa ris"));
+    }
+
+    #[cfg(feature = "unstable-msc4175")]
+    #[async_test]
+    async fn test_timezone() {
+        use serde_json::json;
+        use wiremock::{
+            matchers::{body_json, method, path},
+            Mock, ResponseTemplate,
+        };
+
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().build().await;
+        let account = client.account();
+
+        let tz_path = "/_matrix/client/unstable/uk.tcpip.msc4133/profile/@example:localhost/us.cloke.msc4175.tz";
+
+        // The timezone isn't set.
+        {
+            let _guard = Mock::given(method("GET"))
+                .and(path(tz_path))
+                .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+                    "errcode": "M_NOT_FOUND",
+                    "error": "Profile field not found",
+                })))
+                .expect(1)
+                .mount_as_scoped(server.server())
+                .await;
+
+            assert_eq!(account.get_timezone().await.unwrap(), None);
+        }
+
+        // An invalid timezone is rejected before sending a request.
+        assert_matches!(
+            account.set_timezone(Some("Paris")).await,
+            Err(Error::InvalidTimezone(tz)) if tz == "Paris"
+        );
+
+        // The timezone is set.
+        Mock::given(method("PUT"))
+            .and(path(tz_path))
+            .and(body_json(json!({ "us.cloke.msc4175.tz": "Europe/Paris" })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+            .expect(1)
+            .mount(server.server())
+            .await;
+        account.set_timezone(Some("Europe/Paris")).await.unwrap();
+
+        Mock::given(method("GET"))
+            .and(path(tz_path))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({ "us.cloke.msc4175.tz": "Europe/Paris" })),
+            )
+            .expect(1)
+            .mount(server.server())
+            .await;
+        assert_eq!(account.get_timezone().await.unwrap().as_deref(), Some("Europe/Paris"));
+
+        // The timezone is removed.
+        Mock::given(method("DELETE"))
+            .and(path(tz_path))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+            .expect(1)
+            .mount(server.server())
+            .await;
+        account.set_timezone(None).await.unwrap();
+    }
 }
@@ -0,0 +1,350 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Computation of the application's badge count, i.e. the number displayed
+//! on the application icon, from the state of all the rooms.
+//!
+//! See [`Client::badge_counts`] and [`Client::badge_counts_stream`].
+
+use futures_core::Stream;
+use futures_util::{stream, StreamExt as _};
+use matrix_sdk_base::RoomState;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::warn;
+
+use crate::{
+    notification_settings::{IsEncrypted, IsOneToOne, NotificationSettings, RoomNotificationMode},
+    Client, Room,
+};
+
+/// Which rooms and events contribute to the badge count.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BadgeCountPolicy {
+    /// Only count the events that notify the user, according to each room's
+    /// notification mode.
+    NotificationsOnly,
+
+    /// Count all the unread messages of the rooms that aren't muted.
+    AllUnread,
+
+    /// Count the notifying events of direct rooms, and only the mentions in
+    /// the other rooms.
+    DmsAndMentions,
+}
+
+/// The badge counts computed with a [`BadgeCountPolicy`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BadgeCounts {
+    /// The number of events contributing to the badge.
+    ///
+    /// A room that's been marked as unread, or an invite, count as a single
+    /// event.
+    pub events: u64,
+
+    /// The number of rooms contributing to the badge.
+    pub rooms: u64,
+}
+
+/// The information about a single room needed to compute its contribution to
+/// the badge counts.
+#[derive(Clone, Debug)]
+struct RoomBadgeInput {
+    /// Is this an invite?
+    is_invite: bool,
+
+    /// Is this a direct room?
+    is_direct: bool,
+
+    /// The notification mode of the room.
+    mode: RoomNotificationMode,
+
+    /// Has the room been marked as unread by the user?
+    is_marked_unread: bool,
+
+    /// The number of unread messages, computed client-side.
+    unread_messages: u64,
+
+    /// The number of unread notifications, from the server for unencrypted
+    /// rooms, computed client-side otherwise.
+    ///
+    /// The server can't tell which events of an encrypted room mention the
+    /// user, so its counts can't be trusted for those.
+    notifications: u64,
+
+    /// The number of unread mentions, from the server for unencrypted rooms,
+    /// computed client-side otherwise.
+    mentions: u64,
+}
+
+impl RoomBadgeInput {
+    async fn from_room(room: &Room, modes: &ModeResolver) -> Option<Self> {
+        let is_invite = match room.state() {
+            RoomState::Joined => false,
+            RoomState::Invited => true,
+            RoomState::Left | RoomState::Knocked | RoomState::Banned => return None,
+        };
+
+        let is_direct = room.is_direct().await.unwrap_or_else(|err| {
+            warn!(room_id = ?room.room_id(), "couldn't know if the room is direct: {err}");
+            false
+        });
+        let is_encrypted = room.encryption_state().is_encrypted();
+        let mode = modes.mode(room, is_encrypted).await;
+
+        let (notifications, mentions) = if is_encrypted {
+            (room.num_unread_notifications(), room.num_unread_mentions())
+        } else {
+            let counts = room.unread_notification_counts();
+            (counts.notification_count, counts.highlight_count)
+        };
+
+        Some(Self {
+            is_invite,
+            is_direct,
+            mode,
+            is_marked_unread: room.is_marked_unread(),
+            unread_messages: room.num_unread_messages(),
+            notifications,
+            mentions,
+        })
+    }
+
+    /// The number of events this room contributes to the badge, with the
+    /// given policy.
+    fn count(&self, policy: BadgeCountPolicy) -> u64 {
+        if self.is_invite {
+            let counts = policy != BadgeCountPolicy::DmsAndMentions || self.is_direct;
+            return counts.into();
+        }
+
+        // Muted rooms never contribute to the badge, even if they've been marked as
+        // unread.
+        if self.mode == RoomNotificationMode::Mute {
+            return 0;
+        }
+
+        let notifying = match self.mode {
+            RoomNotificationMode::AllMessages => self.notifications.max(self.mentions),
+            // In encrypted rooms, the server notifies for every message since it can't
+            // look for mentions; only the client-side mentions count is reliable.
+            RoomNotificationMode::MentionsAndKeywordsOnly => self.mentions,
+            RoomNotificationMode::Mute => 0,
+        };
+
+        let count = match policy {
+            BadgeCountPolicy::NotificationsOnly => notifying,
+            BadgeCountPolicy::AllUnread => self.unread_messages.max(notifying),
+            BadgeCountPolicy::DmsAndMentions if self.is_direct => notifying,
+            BadgeCountPolicy::DmsAndMentions => self.mentions,
+        };
+
+        if count == 0 && self.is_marked_unread {
+            1
+        } else {
+            count
+        }
+    }
+}
+
+/// Resolves the notification mode of the rooms, sharing the push rules
+/// between all the rooms.
+struct ModeResolver {
+    settings: NotificationSettings,
+}
+
+impl ModeResolver {
+    async fn mode(&self, room: &Room, is_encrypted: bool) -> RoomNotificationMode {
+        if let Some(mode) =
+            self.settings.get_user_defined_room_notification_mode(room.room_id()).await
+        {
+            return mode;
+        }
+
+        // From the point of view of notification settings, a `one-to-one` room is one
+        // that involves exactly two people.
+        let is_one_to_one = IsOneToOne::from(room.active_members_count() == 2);
+        self.settings
+            .get_default_room_notification_mode(IsEncrypted::from(is_encrypted), is_one_to_one)
+            .await
+    }
+}
+
+/// Sum the contributions of the given rooms to the badge counts.
+fn sum(inputs: &[RoomBadgeInput], policy: BadgeCountPolicy) -> BadgeCounts {
+    inputs.iter().map(|input| input.count(policy)).filter(|count| *count > 0).fold(
+        BadgeCounts::default(),
+        |mut counts, count| {
+            counts.events += count;
+            counts.rooms += 1;
+            counts
+        },
+    )
+}
+
+impl Client {
+    /// Compute the badge counts for the application icon, from the state of
+    /// all the rooms, with the given policy.
+    ///
+    /// The counts take into account the notification mode of each room, the
+    /// rooms marked as unread, and the invites. For encrypted rooms, the
+    /// counts computed client-side are used instead of the server's ones,
+    /// since the server can't know which events mention the user.
+    pub async fn badge_counts(&self, policy: BadgeCountPolicy) -> BadgeCounts {
+        let modes = ModeResolver { settings: self.notification_settings().await };
+
+        let mut inputs = Vec::new();
+        for room in self.rooms() {
+            if let Some(input) = RoomBadgeInput::from_room(&room, &modes).await {
+                inputs.push(input);
+            }
+        }
+
+        sum(&inputs, policy)
+    }
+
+    /// Get a stream of the badge counts for the application icon, computed
+    /// with the given policy.
+    ///
+    /// The current counts are yielded first, then new counts are yielded
+    /// every time they change, after a sync or a change in a room's read
+    /// receipts or unread marker.
+    pub fn badge_counts_stream(&self, policy: BadgeCountPolicy) -> impl Stream<Item = BadgeCounts> {
+        let client = self.clone();
+        let room_updates = self.subscribe_to_all_room_updates();
+        let room_info_updates = self.room_info_notable_update_receiver();
+
+        let updates = stream::select(
+            stream::unfold(room_updates, |mut receiver| async move {
+                match receiver.recv().await {
+                    Ok(_) | Err(RecvError::Lagged(_)) => Some(((), receiver)),
+                    Err(RecvError::Closed) => None,
+                }
+            }),
+            stream::unfold(room_info_updates, |mut receiver| async move {
+                match receiver.recv().await {
+                    Ok(_) | Err(RecvError::Lagged(_)) => Some(((), receiver)),
+                    Err(RecvError::Closed) => None,
+                }
+            }),
+        );
+
+        async_stream::stream! {
+            let mut updates = std::pin::pin!(updates);
+            let mut previous = client.badge_counts(policy).await;
+            yield previous;
+
+            while updates.next().await.is_some() {
+                let counts = client.badge_counts(policy).await;
+                if counts != previous {
+                    previous = counts;
+                    yield counts;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sum, BadgeCountPolicy, BadgeCounts, RoomBadgeInput};
+    use crate::notification_settings::RoomNotificationMode;
+
+    fn room(mode: RoomNotificationMode) -> RoomBadgeInput {
+        RoomBadgeInput {
+            is_invite: false,
+            is_direct: false,
+            mode,
+            is_marked_unread: false,
+            unread_messages: 0,
+            notifications: 0,
+            mentions: 0,
+        }
+    }
+
+    fn counts(events: u64, rooms: u64) -> BadgeCounts {
+        BadgeCounts { events, rooms }
+    }
+
+    #[test]
+    fn test_badge_counts_policies() {
+        let rooms = [
+            // A group room where all messages notify, with a mention.
+            RoomBadgeInput {
+                unread_messages: 5,
+                notifications: 3,
+                mentions: 1,
+                ..room(RoomNotificationMode::AllMessages)
+            },
+            // A direct room.
+            RoomBadgeInput {
+                is_direct: true,
+                unread_messages: 2,
+                notifications: 2,
+                ..room(RoomNotificationMode::AllMessages)
+            },
+            // An encrypted room in mentions-only mode: the server counts all the messages
+            // as notifications, but there's no mention.
+            RoomBadgeInput {
+                unread_messages: 4,
+                notifications: 4,
+                ..room(RoomNotificationMode::MentionsAndKeywordsOnly)
+            },
+            // A muted room with a mention, that's been marked as unread.
+            RoomBadgeInput {
+                is_marked_unread: true,
+                unread_messages: 10,
+                notifications: 10,
+                mentions: 1,
+                ..room(RoomNotificationMode::Mute)
+            },
+            // A room without unread messages, that's been marked as unread.
+            RoomBadgeInput { is_marked_unread: true, ..room(RoomNotificationMode::AllMessages) },
+            // A read room.
+            room(RoomNotificationMode::AllMessages),
+            // An invite to a group room.
+            RoomBadgeInput { is_invite: true, ..room(RoomNotificationMode::AllMessages) },
+        ];
+
+        assert_eq!(sum(&rooms, BadgeCountPolicy::NotificationsOnly), counts(3 + 2 + 1 + 1, 4));
+        assert_eq!(sum(&rooms, BadgeCountPolicy::AllUnread), counts(5 + 2 + 4 + 1 + 1, 5));
+        assert_eq!(sum(&rooms, BadgeCountPolicy::DmsAndMentions), counts(1 + 2 + 1, 3));
+    }
+
+    #[test]
+    fn test_direct_invites_count_as_dms() {
+        let rooms = [RoomBadgeInput {
+            is_invite: true,
+            is_direct: true,
+            ..room(RoomNotificationMode::AllMessages)
+        }];
+
+        assert_eq!(sum(&rooms, BadgeCountPolicy::NotificationsOnly), counts(1, 1));
+        assert_eq!(sum(&rooms, BadgeCountPolicy::AllUnread), counts(1, 1));
+        assert_eq!(sum(&rooms, BadgeCountPolicy::DmsAndMentions), counts(1, 1));
+    }
+
+    #[test]
+    fn test_mentions_only_room() {
+        let rooms = [RoomBadgeInput {
+            unread_messages: 4,
+            notifications: 4,
+            mentions: 2,
+            ..room(RoomNotificationMode::MentionsAndKeywordsOnly)
+        }];
+
+        assert_eq!(sum(&rooms, BadgeCountPolicy::NotificationsOnly), counts(2, 1));
+        assert_eq!(sum(&rooms, BadgeCountPolicy::AllUnread), counts(4, 1));
+        assert_eq!(sum(&rooms, BadgeCountPolicy::DmsAndMentions), counts(2, 1));
+    }
+}
@@ -17,6 +17,8 @@ mod homeserver_config;
 
 #[cfg(feature = "sqlite")]
 use std::path::Path;
+#[cfg(feature = "e2e-encryption")]
+use std::time::Duration;
 use std::{collections::BTreeSet, fmt, sync::Arc};
 
 use homeserver_config::*;
@@ -35,7 +37,7 @@ use tracing::{debug, field::debug, instrument, Span};
 
 use super::{Client, ClientInner};
 #[cfg(feature = "e2e-encryption")]
-use crate::crypto::{CollectStrategy, TrustRequirement};
+use crate::crypto::{CollectStrategy, KeyQueryConfig, RoomKeyRotationLimits, TrustRequirement};
 #[cfg(feature = "e2e-encryption")]
 use crate::encryption::EncryptionSettings;
 #[cfg(not(target_family = "wasm"))]
@@ -109,6 +111,14 @@ pub struct ClientBuilder {
     #[cfg(feature = "e2e-encryption")]
     room_key_recipient_strategy: CollectStrategy,
     #[cfg(feature = "e2e-encryption")]
+    room_key_rotation_limits: RoomKeyRotationLimits,
+    #[cfg(feature = "e2e-encryption")]
+    verification_request_timeout: Option<Duration>,
+    #[cfg(feature = "e2e-encryption")]
+    key_query_config: Option<KeyQueryConfig>,
+    #[cfg(feature = "e2e-encryption")]
+    key_request_log_enabled: bool,
+    #[cfg(feature = "e2e-encryption")]
     decryption_settings: DecryptionSettings,
     #[cfg(feature = "e2e-encryption")]
     enable_share_history_on_invite: bool,
@@ -137,8 +147,18 @@ impl ClientBuilder {
             #[cfg(feature = "e2e-encryption")]
             room_key_recipient_strategy: Default::default(),
             #[cfg(feature = "e2e-encryption")]
+            room_key_rotation_limits: Default::default(),
+            #[cfg(feature = "e2e-encryption")]
+            verification_request_timeout: None,
+            #[cfg(feature = "e2e-encryption")]
+            key_query_config: None,
+            #[cfg(feature = "e2e-encryption")]
+            key_request_log_enabled: false,
+            #[cfg(feature = "e2e-encryption")]
             decryption_settings: DecryptionSettings {
                 sender_device_trust_requirement: TrustRequirement::Untrusted,
+                to_device_trust_requirements: Default::default(),
+                unwedging_policy: Default::default(),
             },
             #[cfg(feature = "e2e-encryption")]
             enable_share_history_on_invite: false,
@@ -447,6 +467,59 @@ impl ClientBuilder {
         self
     }
 
+    /// Set the limits on the rotation of the room keys, when sending an
+    /// encrypted message.
+    ///
+    /// These limits are merged with the rotation settings of the
+    /// `m.room.encryption` state of the rooms, always choosing the stricter
+    /// value. This can be used to force a faster rotation of the room keys than
+    /// the one the rooms ask for.
+    #[cfg(feature = "e2e-encryption")]
+    pub fn with_room_key_rotation_limits(mut self, limits: RoomKeyRotationLimits) -> Self {
+        self.room_key_rotation_limits = limits;
+        self
+    }
+
+    /// Set the time after which the verification requests time out.
+    ///
+    /// Verification requests which weren't answered in time are cancelled with
+    /// an `m.timeout` code. The time is counted from the timestamp of the
+    /// requests, so requests received again after a restart don't get more
+    /// time.
+    ///
+    /// Defaults to 10 minutes, as defined in the spec.
+    #[cfg(feature = "e2e-encryption")]
+    pub fn with_verification_request_timeout(mut self, timeout: Duration) -> Self {
+        self.verification_request_timeout = Some(timeout);
+        self
+    }
+
+    /// Set the configuration of the `/keys/query` requests sent when the
+    /// devices of the tracked users change.
+    ///
+    /// This allows to throttle and batch the key queries of busy accounts, see
+    /// [`KeyQueryConfig`] for the available options. The default configuration
+    /// queries the keys of the users as soon as their devices change.
+    #[cfg(feature = "e2e-encryption")]
+    pub fn with_key_query_config(mut self, config: KeyQueryConfig) -> Self {
+        self.key_query_config = Some(config);
+        self
+    }
+
+    /// Enable or disable the persistent log of the room key requests.
+    ///
+    /// While enabled, the room key requests we receive, the decision we took
+    /// for them, and the room key requests we send out are recorded in the
+    /// crypto store, see [`Encryption::key_request_log()`]. The log is
+    /// disabled by default, since it grows the store.
+    ///
+    /// [`Encryption::key_request_log()`]: crate::encryption::Encryption::key_request_log
+    #[cfg(feature = "e2e-encryption")]
+    pub fn with_key_request_log_enabled(mut self, enabled: bool) -> Self {
+        self.key_request_log_enabled = enabled;
+        self
+    }
+
     /// Set the trust requirement to be used when decrypting events.
     #[cfg(feature = "e2e-encryption")]
     pub fn with_decryption_settings(mut self, decryption_settings: DecryptionSettings) -> Self {
@@ -531,6 +604,10 @@ impl ClientBuilder {
             #[cfg(feature = "e2e-encryption")]
             {
                 client.room_key_recipient_strategy = self.room_key_recipient_strategy;
+                client.room_key_rotation_limits = self.room_key_rotation_limits;
+                client.verification_request_timeout = self.verification_request_timeout;
+                client.key_query_config = self.key_query_config;
+                client.key_request_log_enabled = self.key_request_log_enabled;
                 client.decryption_settings = self.decryption_settings;
             }
 
@@ -998,6 +1075,8 @@ pub(crate) mod tests {
             .server_name_or_homeserver_url(homeserver.uri())
             .with_decryption_settings(DecryptionSettings {
                 sender_device_trust_requirement: TrustRequirement::CrossSigned,
+                to_device_trust_requirements: Default::default(),
+                unwedging_policy: Default::default(),
             });
 
         let client = builder.build().await.unwrap();
@@ -1016,6 +1095,8 @@ pub(crate) mod tests {
             .server_name_or_homeserver_url(homeserver.uri())
             .with_decryption_settings(DecryptionSettings {
                 sender_device_trust_requirement: TrustRequirement::Untrusted,
+                to_device_trust_requirements: Default::default(),
+                unwedging_policy: Default::default(),
             });
 
         let client = builder.build().await.unwrap();
@@ -12,11 +12,23 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::time::Duration;
+
 use matrix_sdk_base::ttl_cache::TtlCache;
-use ruma::api::client::discovery::get_authorization_server_metadata::v1::AuthorizationServerMetadata;
+use ruma::{
+    api::client::{
+        discovery::get_authorization_server_metadata::v1::AuthorizationServerMetadata,
+        profile::get_profile,
+    },
+    OwnedUserId,
+};
 use tokio::sync::RwLock;
 
 use super::ClientServerInfo;
+use crate::account::OpenIdTokenCache;
+
+/// The default lifetime of the profiles in [`ClientCaches::profiles`].
+pub(crate) const DEFAULT_PROFILE_CACHE_LIFETIME: Duration = Duration::from_secs(5 * 60);
 
 /// A collection of in-memory data that the `Client` might want to cache to
 /// avoid hitting the homeserver every time users request the data.
@@ -25,4 +37,10 @@ pub(crate) struct ClientCaches {
     /// server.
     pub(super) server_info: RwLock<ClientServerInfo>,
     pub(crate) server_metadata: tokio::sync::Mutex<TtlCache<String, AuthorizationServerMetadata>>,
+    /// Profiles of other users, fetched with
+    /// [`Account::fetch_user_profile_of_cached()`](crate::Account::fetch_user_profile_of_cached).
+    pub(crate) profiles: tokio::sync::Mutex<TtlCache<OwnedUserId, get_profile::v3::Response>>,
+    /// The OpenID token requested with
+    /// [`Account::request_openid_token()`](crate::Account::request_openid_token).
+    pub(crate) openid_token: tokio::sync::Mutex<OpenIdTokenCache>,
 }
@@ -23,7 +23,7 @@ use std::{
     time::Duration,
 };
 
-use caches::ClientCaches;
+use caches::{ClientCaches, DEFAULT_PROFILE_CACHE_LIFETIME};
 use eyeball::{SharedObservable, Subscriber};
 use eyeball_im::{Vector, VectorDiff};
 use futures_core::Stream;
@@ -34,10 +34,12 @@ use matrix_sdk_base::{
     event_cache::store::EventCacheStoreLock,
     store::{DynStateStore, RoomLoadSettings, ServerInfo, WellKnownResponse},
     sync::{Notification, RoomUpdates},
-    BaseClient, RoomInfoNotableUpdate, RoomState, RoomStateFilter, SendOutsideWasm, SessionMeta,
-    StateStoreDataKey, StateStoreDataValue, SyncOutsideWasm,
+    BaseClient, RoomDisplayNameOverride, RoomInfoNotableUpdate, RoomState, RoomStateFilter,
+    SendOutsideWasm, SessionMeta, StateStoreDataKey, StateStoreDataValue, SyncOutsideWasm,
+};
+use matrix_sdk_common::{
+    deserialized_responses::EncryptionInfo, executor::spawn, ttl_cache::TtlCache,
 };
-use matrix_sdk_common::ttl_cache::TtlCache;
 #[cfg(feature = "e2e-encryption")]
 use ruma::events::{room::encryption::RoomEncryptionEventContent, InitialStateEvent};
 use ruma::{
@@ -66,10 +68,11 @@ use ruma::{
         FeatureFlag, MatrixVersion, OutgoingRequest, SupportedVersions,
     },
     assign,
+    events::{GlobalAccountDataEventType, StaticEventContent, ToDeviceEvent, ToDeviceEventContent},
     push::Ruleset,
     time::Instant,
     DeviceId, OwnedDeviceId, OwnedEventId, OwnedRoomId, OwnedRoomOrAliasId, OwnedServerName,
-    RoomAliasId, RoomId, RoomOrAliasId, ServerName, UInt, UserId,
+    OwnedUserId, RoomAliasId, RoomId, RoomOrAliasId, ServerName, UInt, UserId,
 };
 use serde::de::DeserializeOwned;
 use tokio::sync::{broadcast, Mutex, OnceCell, RwLock, RwLockReadGuard};
@@ -78,6 +81,7 @@ use url::Url;
 
 use self::futures::SendRequest;
 use crate::{
+    account::{EffectiveMediaPreviewConfig, OwnProfile, RecentlyVisitedRooms},
     authentication::{
         matrix::MatrixAuth, oauth::OAuth, AuthCtx, AuthData, ReloadSessionCallback,
         SaveSessionCallback,
@@ -87,14 +91,17 @@ use crate::{
     error::HttpResult,
     event_cache::EventCache,
     event_handler::{
-        EventHandler, EventHandlerContext, EventHandlerDropGuard, EventHandlerHandle,
-        EventHandlerStore, ObservableEventHandler, SyncEvent,
+        EventHandler, EventHandlerContext, EventHandlerDropGuard, EventHandlerExecution,
+        EventHandlerHandle, EventHandlerPanic, EventHandlerPriority, EventHandlerStore,
+        ObservableEventHandler, RoomEventFilter, SyncEvent, UnhandledToDeviceEvent,
+        OBSERVED_EVENTS_BUFFER_SIZE,
     },
     http_client::HttpClient,
+    invite_filter::InviteFilterState,
     latest_events::LatestEvents,
     media::MediaError,
     notification_settings::NotificationSettings,
-    room::RoomMember,
+    room::{PushContext, RoomMember},
     room_preview::RoomPreview,
     send_queue::{SendQueue, SendQueueData},
     sliding_sync::Version as SlidingSyncVersion,
@@ -166,6 +173,11 @@ pub(crate) struct ClientLocks {
     /// explanation.
     pub(crate) mark_as_dm_lock: Mutex<()>,
 
+    /// Locks ensuring that only a single update of a global account data event
+    /// type happens at once, see [`Account::update_account_data()`].
+    pub(crate) account_data_update_locks:
+        StdMutex<BTreeMap<GlobalAccountDataEventType, Arc<Mutex<()>>>>,
+
     /// Lock ensuring that only a single secret store is getting opened at the
     /// same time.
     ///
@@ -213,6 +225,10 @@ pub(crate) struct ClientLocks {
     /// internal implementation detail, see [`Self::send_single_receipt`].
     pub(crate) read_receipt_deduplicated_handler: DeduplicatingHandler<(String, OwnedEventId)>,
 
+    /// Handler to ensure that only one profile request is running at a time,
+    /// given a user.
+    pub(crate) profile_request_deduplicated_handler: DeduplicatingHandler<OwnedUserId>,
+
     #[cfg(feature = "e2e-encryption")]
     pub(crate) cross_process_crypto_store_lock:
         OnceCell<CrossProcessStoreLock<LockableCryptoStore>>,
@@ -350,6 +366,28 @@ pub(crate) struct ClientInner {
     ///
     /// [`LatestEvent`]: crate::latest_event::LatestEvent
     latest_events: OnceCell<LatestEvents>,
+
+    /// The policy used to filter the invites, and the invites it filtered
+    /// out.
+    ///
+    /// This isn't shared with sub-clients, since they don't know about all
+    /// the rooms, and couldn't classify the invites properly.
+    pub(crate) invite_filter: StdRwLock<InviteFilterState>,
+
+    /// The profile of the logged-in user, as last observed by this client.
+    pub(crate) own_profile: SharedObservable<OwnProfile>,
+
+    /// The in-memory copy of the media preview configuration, if it has been
+    /// loaded.
+    pub(crate) media_preview_config: StdRwLock<Option<EffectiveMediaPreviewConfig>>,
+
+    /// The payloads of the push rules events that failed to deserialize, to
+    /// only log the error once per payload.
+    pub(crate) invalid_push_rules_payloads: StdMutex<BTreeSet<String>>,
+
+    /// The in-memory copy of the recently visited rooms, written to the store
+    /// in batches.
+    pub(crate) recently_visited_rooms: Arc<Mutex<RecentlyVisitedRooms>>,
 }
 
 impl ClientInner {
@@ -378,6 +416,8 @@ impl ClientInner {
         let caches = ClientCaches {
             server_info: server_info.into(),
             server_metadata: Mutex::new(TtlCache::new()),
+            profiles: Mutex::new(TtlCache::with_lifetime(DEFAULT_PROFILE_CACHE_LIFETIME)),
+            openid_token: Default::default(),
         };
 
         let client = Self {
@@ -402,6 +442,11 @@ impl ClientInner {
             event_cache,
             send_queue_data: send_queue,
             latest_events,
+            invite_filter: Default::default(),
+            own_profile: Default::default(),
+            media_preview_config: Default::default(),
+            invalid_push_rules_payloads: Default::default(),
+            recently_visited_rooms: Default::default(),
             #[cfg(feature = "e2e-encryption")]
             e2ee: EncryptionData::new(encryption_settings),
             #[cfg(feature = "e2e-encryption")]
@@ -727,6 +772,15 @@ impl Client {
     /// then use [`Ctx<T>`](crate::event_handler::Ctx) to extract the context
     /// into the event handler.
     ///
+    /// The handlers are called once the sync response containing the event has
+    /// been processed: the changes it contains, e.g. to the state of the room,
+    /// are visible in the store when the handler runs. The handlers of the same
+    /// event run concurrently, use
+    /// [`add_event_handler_with_priority`](Client::add_event_handler_with_priority)
+    /// to order them. If a handler panics, the panic is reported to the hook
+    /// set with [`Client::set_event_handler_panic_hook`], and doesn't interrupt
+    /// the sync.
+    ///
     /// [`EventHandlerContext`]: crate::event_handler::EventHandlerContext
     ///
     /// # Examples
@@ -824,6 +878,83 @@ impl Client {
         self.add_event_handler_impl(handler, None)
     }
 
+    /// Register a handler for a specific event type, with the given priority.
+    ///
+    /// This method works the same way as
+    /// [`add_event_handler`][Self::add_event_handler], except that the handlers
+    /// of an event with a higher priority are called first, and the handlers
+    /// with a lower priority are only called once they have all completed. See
+    /// [`EventHandlerPriority`] for more details.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use matrix_sdk::{
+    ///     event_handler::{EventHandlerExecution, EventHandlerPriority},
+    ///     ruma::events::room::message::SyncRoomMessageEvent,
+    ///     Client,
+    /// };
+    ///
+    /// # async fn example(client: Client) {
+    /// // Log all the messages before they are handled.
+    /// client.add_event_handler_with_priority(
+    ///     |ev: SyncRoomMessageEvent| async move {
+    ///         println!("Received a message: {ev:?}");
+    ///     },
+    ///     EventHandlerPriority::HIGH,
+    /// );
+    ///
+    /// // Handle the messages one handler after the other, in registration order.
+    /// client.set_event_handler_execution(
+    ///     EventHandlerPriority::DEFAULT,
+    ///     EventHandlerExecution::Sequential,
+    /// );
+    /// client.add_event_handler(|ev: SyncRoomMessageEvent| async move {
+    ///     // Handle the message.
+    /// });
+    /// # }
+    /// ```
+    pub fn add_event_handler_with_priority<Ev, Ctx, H>(
+        &self,
+        handler: H,
+        priority: EventHandlerPriority,
+    ) -> EventHandlerHandle
+    where
+        Ev: SyncEvent + DeserializeOwned + SendOutsideWasm + 'static,
+        H: EventHandler<Ev, Ctx>,
+    {
+        self.add_prioritized_event_handler_impl(handler, None, priority)
+    }
+
+    /// Set how the event handlers with the given priority are called for an
+    /// event.
+    ///
+    /// By default, they are called concurrently.
+    pub fn set_event_handler_execution(
+        &self,
+        priority: EventHandlerPriority,
+        execution: EventHandlerExecution,
+    ) {
+        self.inner.event_handlers.set_execution(priority, execution);
+    }
+
+    /// Set the hook called when an event handler panics.
+    ///
+    /// The panics of event handlers are caught, so they don't interrupt the
+    /// sync, and logged. This hook can be used to report them differently.
+    /// It replaces the hook that was previously set, if any.
+    pub fn set_event_handler_panic_hook(
+        &self,
+        hook: impl Fn(EventHandlerPanic) + SendOutsideWasm + SyncOutsideWasm + 'static,
+    ) {
+        self.inner.event_handlers.set_panic_hook(Some(Arc::new(hook)));
+    }
+
+    /// Remove the hook set with [`Client::set_event_handler_panic_hook`].
+    pub fn remove_event_handler_panic_hook(&self) {
+        self.inner.event_handlers.set_panic_hook(None);
+    }
+
     /// Register a handler for a specific room, and event type.
     ///
     /// This method works the same way as
@@ -846,6 +977,84 @@ impl Client {
         self.add_event_handler_impl(handler, Some(room_id.to_owned()))
     }
 
+    /// Register a handler for a specific to-device event type.
+    ///
+    /// `C` is the content of the to-device events, it can be a custom content
+    /// type defined with the [`EventContent`] derive macro. The handler is
+    /// called with the events of this type that were sent unencrypted, or that
+    /// were encrypted and successfully decrypted, along with their
+    /// [`EncryptionInfo`] in the latter case. Handlers are expected to check
+    /// the encryption info, e.g. to only accept events sent by verified
+    /// devices.
+    ///
+    /// The to-device events that couldn't be decrypted, or that aren't handled
+    /// by any event handler, are passed to the handler registered with
+    /// [`Client::set_unhandled_to_device_handler`].
+    ///
+    /// See [`Client::add_event_handler`] for more details on event handlers.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use matrix_sdk::{
+    ///     deserialized_responses::{EncryptionInfo, VerificationState},
+    ///     ruma::events::{macros::EventContent, ToDeviceEvent},
+    ///     Client,
+    /// };
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[derive(Clone, Debug, Deserialize, Serialize, EventContent)]
+    /// #[ruma_event(type = "org.example.call.keys", kind = ToDevice)]
+    /// struct CallKeysEventContent {
+    ///     call_id: String,
+    ///     keys: Vec<String>,
+    /// }
+    ///
+    /// # async fn example(client: Client) {
+    /// client.add_to_device_handler(
+    ///     |ev: ToDeviceEvent<CallKeysEventContent>,
+    ///      encryption_info: Option<EncryptionInfo>| async move {
+    ///         let verified = encryption_info.is_some_and(|info| {
+    ///             matches!(info.verification_state, VerificationState::Verified)
+    ///         });
+    ///
+    ///         if verified {
+    ///             println!("Received keys for call {}", ev.content.call_id);
+    ///         }
+    ///     },
+    /// );
+    /// # }
+    /// ```
+    ///
+    /// [`EventContent`]: ruma::events::macros::EventContent
+    pub fn add_to_device_handler<C, H>(&self, handler: H) -> EventHandlerHandle
+    where
+        C: StaticEventContent + ToDeviceEventContent + SendOutsideWasm + 'static,
+        ToDeviceEvent<C>: DeserializeOwned,
+        H: EventHandler<ToDeviceEvent<C>, (Option<EncryptionInfo>,)>,
+    {
+        self.add_event_handler_impl(handler, None)
+    }
+
+    /// Set the handler of the to-device events that couldn't be decrypted, or
+    /// that aren't handled by any event handler.
+    ///
+    /// There can only be one such handler: it replaces the handler that was
+    /// previously set, if any. It can be removed with
+    /// [`Client::remove_unhandled_to_device_handler`].
+    pub fn set_unhandled_to_device_handler<H, Fut>(&self, handler: H)
+    where
+        H: Fn(UnhandledToDeviceEvent) -> Fut + SendOutsideWasm + SyncOutsideWasm + 'static,
+        Fut: Future<Output = ()> + SendOutsideWasm + 'static,
+    {
+        self.inner.event_handlers.set_unhandled_to_device_handler(handler);
+    }
+
+    /// Remove the handler set with [`Client::set_unhandled_to_device_handler`].
+    pub fn remove_unhandled_to_device_handler(&self) {
+        self.inner.event_handlers.remove_unhandled_to_device_handler();
+    }
+
     /// Observe a specific event type.
     ///
     /// `Ev` represents the kind of event that will be observed. `Ctx`
@@ -857,9 +1066,11 @@ impl Client {
     /// implements a [`Stream`]. The `Stream::Item` will be of type `(Ev,
     /// Ctx)`.
     ///
-    /// Be careful that only the most recent value can be observed. Subscribers
-    /// are notified when a new value is sent, but there is no guarantee
-    /// that they will see all values.
+    /// Be careful that only the most recent value can be observed with
+    /// [`ObservableEventHandler::subscribe`]. Subscribers are notified when a
+    /// new value is sent, but there is no guarantee that they will see all
+    /// values. Use [`ObservableEventHandler::subscribe_buffered`] to receive
+    /// all of them.
     ///
     /// # Example
     ///
@@ -922,77 +1133,146 @@ impl Client {
     /// [`EventHandlerSubscriber`]: crate::event_handler::EventHandlerSubscriber
     pub fn observe_events<Ev, Ctx>(&self) -> ObservableEventHandler<(Ev, Ctx)>
     where
-        Ev: SyncEvent + DeserializeOwned + SendOutsideWasm + SyncOutsideWasm + 'static,
-        Ctx: EventHandlerContext + SendOutsideWasm + SyncOutsideWasm + 'static,
+        Ev: SyncEvent + DeserializeOwned + Clone + SendOutsideWasm + SyncOutsideWasm + 'static,
+        Ctx: EventHandlerContext + Clone + SendOutsideWasm + SyncOutsideWasm + 'static,
     {
         self.observe_room_events_impl(None)
     }
 
-    /// Observe a specific room, and event type.
+    /// Observe a specific event type, in some rooms.
     ///
     /// This method works the same way as [`Client::observe_events`], except
-    /// that the observability will only be applied for events in the room with
-    /// the specified ID. See that method for more details.
+    /// that the observability will only be applied for events in the rooms
+    /// matching the [`RoomEventFilter`]: a room ID, a set of room IDs, or a
+    /// predicate over the [`RoomInfo`] of the room. See that method for more
+    /// details.
+    ///
+    /// Be careful that only the most recent value can be observed with
+    /// [`ObservableEventHandler::subscribe`]. Subscribers are notified when a
+    /// new value is sent, but there is no guarantee that they will see all
+    /// values. Use [`ObservableEventHandler::subscribe_buffered`] to receive
+    /// all of them.
     ///
-    /// Be careful that only the most recent value can be observed. Subscribers
-    /// are notified when a new value is sent, but there is no guarantee
-    /// that they will see all values.
+    /// # Example
+    ///
+    /// ```
+    /// use futures_util::StreamExt as _;
+    /// use matrix_sdk::{
+    ///     event_handler::RoomEventFilter,
+    ///     ruma::events::room::member::SyncRoomMemberEvent, Client, Room,
+    /// };
+    ///
+    /// # async fn example(client: Client) -> Option<()> {
+    /// // Observe the membership changes in the rooms the current user created.
+    /// let own_user_id = client.user_id()?.to_owned();
+    /// let observer = client.observe_room_events::<SyncRoomMemberEvent, Room>(
+    ///     RoomEventFilter::predicate(move |info| {
+    ///         info.creators()
+    ///             .is_some_and(|creators| creators.contains(&own_user_id))
+    ///     }),
+    /// );
+    ///
+    /// let mut subscriber = observer.subscribe_buffered();
+    ///
+    /// let (event, room) = subscriber.next().await?;
+    /// # Some(())
+    /// # }
+    /// ```
+    ///
+    /// [`RoomInfo`]: matrix_sdk_base::RoomInfo
     pub fn observe_room_events<Ev, Ctx>(
         &self,
-        room_id: &RoomId,
+        room_filter: impl Into<RoomEventFilter>,
     ) -> ObservableEventHandler<(Ev, Ctx)>
     where
-        Ev: SyncEvent + DeserializeOwned + SendOutsideWasm + SyncOutsideWasm + 'static,
-        Ctx: EventHandlerContext + SendOutsideWasm + SyncOutsideWasm + 'static,
+        Ev: SyncEvent + DeserializeOwned + Clone + SendOutsideWasm + SyncOutsideWasm + 'static,
+        Ctx: EventHandlerContext + Clone + SendOutsideWasm + SyncOutsideWasm + 'static,
     {
-        self.observe_room_events_impl(Some(room_id.to_owned()))
+        self.observe_room_events_impl(Some(room_filter.into()))
     }
 
     /// Shared implementation for `Client::observe_events` and
     /// `Client::observe_room_events`.
     fn observe_room_events_impl<Ev, Ctx>(
         &self,
-        room_id: Option<OwnedRoomId>,
+        room_filter: Option<RoomEventFilter>,
     ) -> ObservableEventHandler<(Ev, Ctx)>
     where
-        Ev: SyncEvent + DeserializeOwned + SendOutsideWasm + SyncOutsideWasm + 'static,
-        Ctx: EventHandlerContext + SendOutsideWasm + SyncOutsideWasm + 'static,
+        Ev: SyncEvent + DeserializeOwned + Clone + SendOutsideWasm + SyncOutsideWasm + 'static,
+        Ctx: EventHandlerContext + Clone + SendOutsideWasm + SyncOutsideWasm + 'static,
     {
         // The default value is `None`. It becomes `Some((Ev, Ctx))` once it has a
         // new value.
         let shared_observable = SharedObservable::new(None);
+        let sender = broadcast::Sender::new(OBSERVED_EVENTS_BUFFER_SIZE);
 
-        ObservableEventHandler::new(
-            shared_observable.clone(),
-            self.event_handler_drop_guard(self.add_event_handler_impl(
-                move |event: Ev, context: Ctx| {
-                    shared_observable.set(Some((event, context)));
+        let notify = {
+            let shared_observable = shared_observable.clone();
+            let sender = sender.clone();
 
-                    ready(())
-                },
-                room_id,
-            )),
+            move |event: Ev, context: Ctx| {
+                // Only clone the event if there's a buffered subscriber.
+                if sender.receiver_count() > 0 {
+                    let _ = sender.send((event.clone(), context.clone()));
+                }
+
+                shared_observable.set(Some((event, context)));
+
+                ready(())
+            }
+        };
+
+        let handle = match room_filter {
+            None => self.add_event_handler_impl(notify, None),
+
+            Some(room_filter) => match room_filter.as_single_room() {
+                // A single room can be filtered by the event handlers themselves.
+                Some(room_id) => self.add_event_handler_impl(notify, Some(room_id.to_owned())),
+
+                None => self.add_event_handler_impl(
+                    move |event: Ev, room: Room, context: Ctx| {
+                        if room_filter.matches(&room) {
+                            notify(event, context)
+                        } else {
+                            ready(())
+                        }
+                    },
+                    None,
+                ),
+            },
+        };
+
+        ObservableEventHandler::new(
+            shared_observable,
+            sender,
+            self.event_handler_drop_guard(handle),
         )
     }
 
-    /// Remove the event handler associated with the handle.
-    ///
-    /// Note that you **must not** call `remove_event_handler` from the
-    /// non-async part of an event handler, that is:
-    ///
-    /// ```ignore
-    /// client.add_event_handler(|ev: SomeEvent, client: Client, handle: EventHandlerHandle| {
-    ///     // ⚠ this will cause a deadlock ⚠
-    ///     client.remove_event_handler(handle);
+    /// Observe a specific to-device event type.
     ///
-    ///     async move {
-    ///         // removing the event handler here is fine
-    ///         client.remove_event_handler(handle);
-    ///     }
-    /// })
-    /// ```
+    /// This is the stream-based equivalent of
+    /// [`Client::add_to_device_handler`]: the observed events come with their
+    /// [`EncryptionInfo`], if they were encrypted. See
+    /// [`Client::observe_events`] for more details on observers.
+    pub fn observe_to_device_events<C>(
+        &self,
+    ) -> ObservableEventHandler<(ToDeviceEvent<C>, Option<EncryptionInfo>)>
+    where
+        C: StaticEventContent
+            + ToDeviceEventContent
+            + Clone
+            + SendOutsideWasm
+            + SyncOutsideWasm
+            + 'static,
+        ToDeviceEvent<C>: DeserializeOwned,
+    {
+        self.observe_events()
+    }
+
+    /// Remove the event handler associated with the handle.
     ///
-    /// Note also that handlers that remove themselves will still execute with
+    /// Note that handlers that remove themselves will still execute with
     /// events received in the same sync cycle.
     ///
     /// # Arguments
@@ -1135,6 +1415,44 @@ impl Client {
         self.inner.notification_handlers.read().await
     }
 
+    /// Set a hook to post-process, or fully override, the computed display
+    /// names of the rooms, or remove it with `None`.
+    ///
+    /// The hook is given the inputs of the computation, and the display name
+    /// computed by the SDK. It's applied to all the display names computed
+    /// afterwards, including the ones used by the room list and to render the
+    /// notifications. The display names of the known rooms are computed again
+    /// right away.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::sync::Arc;
+    ///
+    /// use matrix_sdk::{Client, RoomDisplayName, RoomDisplayNameInputs};
+    ///
+    /// # async fn example(client: Client) -> matrix_sdk::Result<()> {
+    /// // Only show the first hero in the name of the rooms without an explicit name.
+    /// client
+    ///     .set_room_display_name_override(Some(Arc::new(
+    ///         |inputs: &RoomDisplayNameInputs, computed: RoomDisplayName| match computed {
+    ///             RoomDisplayName::Calculated(_) if inputs.heroes.len() > 1 => {
+    ///                 RoomDisplayName::Calculated(format!("{} and others", inputs.heroes[0]))
+    ///             }
+    ///             computed => computed,
+    ///         },
+    ///     )))
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn set_room_display_name_override(
+        &self,
+        display_name_override: Option<Arc<dyn RoomDisplayNameOverride>>,
+    ) -> Result<()> {
+        Ok(self.base_client().set_room_display_name_override(display_name_override).await?)
+    }
+
     /// Get all the rooms the client knows about.
     ///
     /// This will return the list of joined, invited, and left rooms.
@@ -1199,6 +1517,18 @@ impl Client {
         self.base_client().get_room(room_id).map(|room| Room::new(self.clone(), room))
     }
 
+    /// Get a [`PushContext`] for the room with the given id, to evaluate the
+    /// user's push rules against the room's events locally.
+    ///
+    /// Returns `None` if the room is unknown, or if its state doesn't have all
+    /// the state events required to evaluate the push rules yet.
+    pub async fn push_context_for_room(&self, room_id: &RoomId) -> Result<Option<PushContext>> {
+        let Some(room) = self.get_room(room_id) else {
+            return Ok(None);
+        };
+        room.push_context().await
+    }
+
     /// Gets the preview of a room, whether the current user has joined it or
     /// not.
     pub async fn get_room_preview(
@@ -1323,12 +1653,20 @@ impl Client {
         let session = session.into();
         match session {
             AuthSession::Matrix(session) => {
-                Box::pin(self.matrix_auth().restore_session(session, room_load_settings)).await
+                Box::pin(self.matrix_auth().restore_session(session, room_load_settings)).await?;
             }
             AuthSession::OAuth(session) => {
-                Box::pin(self.oauth().restore_session(*session, room_load_settings)).await
+                Box::pin(self.oauth().restore_session(*session, room_load_settings)).await?;
             }
         }
+
+        // Retry the updates of the `m.direct` account data that couldn't be uploaded
+        // during the previous session, without blocking the restoration on the
+        // network.
+        let client = self.clone();
+        let _ = spawn(async move { client.account().retry_pending_direct_updates().await });
+
+        Ok(())
     }
 
     /// Refresh the access token using the authentication API used to log into
@@ -1806,6 +2144,39 @@ impl Client {
             .await
     }
 
+    /// Send the given request, replacing its body with the given streamed
+    /// body.
+    ///
+    /// The request is not retried, since the body can only be read once.
+    #[cfg(not(target_family = "wasm"))]
+    pub(crate) async fn send_with_body_stream<Request>(
+        &self,
+        request: Request,
+        body: reqwest::Body,
+        content_length: Option<u64>,
+        config: Option<RequestConfig>,
+    ) -> HttpResult<Request::IncomingResponse>
+    where
+        Request: OutgoingRequest + Debug,
+        HttpError: From<FromHttpResponseError<Request::EndpointError>>,
+    {
+        let homeserver = self.homeserver().to_string();
+        let access_token = self.access_token();
+
+        self.inner
+            .http_client
+            .send_with_body_stream(
+                request,
+                body,
+                content_length,
+                config,
+                homeserver,
+                access_token.as_deref(),
+                &self.supported_versions().await?,
+            )
+            .await
+    }
+
     fn broadcast_unknown_token(&self, soft_logout: &bool) {
         _ = self
             .inner
@@ -2922,7 +3293,7 @@ pub(crate) mod tests {
     use js_int::{uint, UInt};
     use matrix_sdk_base::{
         store::{MemoryStore, StoreConfig},
-        RoomState,
+        RoomState, StateStoreDataKey,
     };
     use matrix_sdk_test::{
         async_test, GlobalAccountDataTestEvent, JoinedRoomBuilder, StateTestEvent,
@@ -2944,10 +3315,10 @@ pub(crate) mod tests {
             ignored_user_list::IgnoredUserListEventContent,
             media_preview_config::{InviteAvatars, MediaPreviewConfigEventContent, MediaPreviews},
         },
-        owned_room_id, room_alias_id, room_id, RoomId, ServerName, UserId,
+        owned_room_id, owned_user_id, room_alias_id, room_id, RoomId, ServerName, UserId,
     };
     use serde_json::json;
-    use stream_assert::{assert_next_matches, assert_pending};
+    use stream_assert::{assert_next_eq, assert_next_matches, assert_pending};
     use tokio::{
         spawn,
         time::{sleep, timeout},
@@ -3237,6 +3608,88 @@ pub(crate) mod tests {
         assert_eq!(rooms.first().unwrap(), room_id!("!19:localhost"));
     }
 
+    #[async_test]
+    async fn test_recently_visited_rooms_limit_and_removal() {
+        let client = MockClientBuilder::new(None).build().await;
+        let account = client.account();
+
+        for n in 0..5 {
+            account
+                .track_recently_visited_room(RoomId::parse(format!("!{n}:localhost")).unwrap())
+                .await
+                .unwrap();
+        }
+        assert_eq!(account.get_recently_visited_rooms().await.unwrap().len(), 5);
+
+        // Removing a room that isn't in the list doesn't change anything.
+        account.remove_recently_visited_room(room_id!("!unknown:localhost")).await.unwrap();
+        assert_eq!(account.get_recently_visited_rooms().await.unwrap().len(), 5);
+
+        // Removing a room in the list removes it.
+        account.remove_recently_visited_room(room_id!("!2:localhost")).await.unwrap();
+        assert_eq!(
+            account.get_recently_visited_rooms().await.unwrap(),
+            [
+                room_id!("!4:localhost"),
+                room_id!("!3:localhost"),
+                room_id!("!1:localhost"),
+                room_id!("!0:localhost")
+            ]
+        );
+
+        // The new limit applies on the next tracked room.
+        account.set_recently_visited_rooms_limit(3).await;
+        assert_eq!(account.get_recently_visited_rooms().await.unwrap().len(), 4);
+
+        account.track_recently_visited_room(owned_room_id!("!5:localhost")).await.unwrap();
+        assert_eq!(
+            account.get_recently_visited_rooms().await.unwrap(),
+            [room_id!("!5:localhost"), room_id!("!4:localhost"), room_id!("!3:localhost")]
+        );
+    }
+
+    #[async_test]
+    async fn test_recently_visited_rooms_batched_writes() {
+        let client = MockClientBuilder::new(None).build().await;
+        let account = client.account();
+        let user_id = client.user_id().unwrap();
+
+        // Track rooms concurrently, none of them should be lost.
+        let rooms = (0..10).map(|n| RoomId::parse(format!("!{n}:localhost")).unwrap());
+        futures_util::future::join_all(
+            rooms.clone().map(|room_id| account.track_recently_visited_room(room_id)),
+        )
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+        let visited = account.get_recently_visited_rooms().await.unwrap();
+        assert_eq!(visited.len(), 10);
+        assert!(rooms.clone().all(|room_id| visited.contains(&room_id)));
+
+        // The rooms are not written to the store right away.
+        let stored = client
+            .state_store()
+            .get_kv_data(StateStoreDataKey::RecentlyVisitedRooms(user_id))
+            .await
+            .unwrap();
+        assert!(stored.is_none());
+
+        // But they are after a short delay.
+        sleep(Duration::from_secs(1)).await;
+
+        let stored = client
+            .state_store()
+            .get_kv_data(StateStoreDataKey::RecentlyVisitedRooms(user_id))
+            .await
+            .unwrap()
+            .unwrap()
+            .into_recently_visited_rooms()
+            .unwrap();
+        assert_eq!(stored, visited);
+    }
+
     #[async_test]
     async fn test_client_no_cycle_with_event_cache() {
         let client = MockClientBuilder::new(None).build().await;
@@ -3685,13 +4138,17 @@ pub(crate) mod tests {
             })
             .await;
 
-        let (initial_value, stream) =
-            client.account().observe_media_preview_config().await.unwrap();
-
-        let initial_value: MediaPreviewConfigEventContent = initial_value.unwrap();
-        assert_eq!(initial_value.invite_avatars, Some(InviteAvatars::Off));
-        assert_eq!(initial_value.media_previews, Some(MediaPreviews::Private));
+        let stream = client.account().subscribe_to_media_preview_config().await.unwrap();
         pin_mut!(stream);
+
+        assert_next_matches!(
+            stream,
+            MediaPreviewConfigEventContent {
+                media_previews: Some(MediaPreviews::Private),
+                invite_avatars: Some(InviteAvatars::Off),
+                ..
+            }
+        );
         assert_pending!(stream);
 
         server
@@ -3736,13 +4193,17 @@ pub(crate) mod tests {
             })
             .await;
 
-        let (initial_value, stream) =
-            client.account().observe_media_preview_config().await.unwrap();
-
-        let initial_value: MediaPreviewConfigEventContent = initial_value.unwrap();
-        assert_eq!(initial_value.invite_avatars, Some(InviteAvatars::Off));
-        assert_eq!(initial_value.media_previews, Some(MediaPreviews::Private));
+        let stream = client.account().subscribe_to_media_preview_config().await.unwrap();
         pin_mut!(stream);
+
+        assert_next_matches!(
+            stream,
+            MediaPreviewConfigEventContent {
+                media_previews: Some(MediaPreviews::Private),
+                invite_avatars: Some(InviteAvatars::Off),
+                ..
+            }
+        );
         assert_pending!(stream);
 
         server
@@ -3774,9 +4235,210 @@ pub(crate) mod tests {
         let server = MatrixMockServer::new().await;
         let client = server.client_builder().build().await;
 
-        let (initial_value, _) = client.account().observe_media_preview_config().await.unwrap();
+        let stream = client.account().subscribe_to_media_preview_config().await.unwrap();
+        pin_mut!(stream);
+
+        // The default config is yielded.
+        assert_next_matches!(
+            stream,
+            MediaPreviewConfigEventContent { media_previews: None, invite_avatars: None, .. }
+        );
+        assert_pending!(stream);
+    }
+
+    #[async_test]
+    async fn test_effective_media_preview_config() {
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().build().await;
+        let account = client.account();
+
+        // Without a config, the default values are used.
+        let config = account.cached_effective_media_preview_config();
+        assert_eq!(config.media_previews, MediaPreviews::On);
+        assert_eq!(config.invite_avatars, InviteAvatars::On);
+
+        let config = account.effective_media_preview_config().await.unwrap();
+        assert_eq!(config.media_previews, MediaPreviews::On);
+        assert_eq!(config.invite_avatars, InviteAvatars::On);
+
+        // The unset policies use the default values.
+        server
+            .mock_sync()
+            .ok_and_run(&client, |builder| {
+                builder.add_global_account_data_event(GlobalAccountDataTestEvent::Custom(json!({
+                    "content": {
+                        "media_previews": "private",
+                    },
+                    "type": "io.element.msc4278.media_preview_config"
+                })));
+            })
+            .await;
+
+        let config = account.cached_effective_media_preview_config();
+        assert_eq!(config.media_previews, MediaPreviews::Private);
+        assert_eq!(config.invite_avatars, InviteAvatars::On);
+
+        // The stable event takes precedence.
+        server
+            .mock_sync()
+            .ok_and_run(&client, |builder| {
+                builder.add_global_account_data_event(GlobalAccountDataTestEvent::Custom(json!({
+                    "content": {
+                        "invite_avatars": "off",
+                    },
+                    "type": "m.media_preview_config"
+                })));
+            })
+            .await;
+
+        let config = account.cached_effective_media_preview_config();
+        assert_eq!(config.media_previews, MediaPreviews::On);
+        assert_eq!(config.invite_avatars, InviteAvatars::Off);
+
+        let config = account.effective_media_preview_config().await.unwrap();
+        assert_eq!(config.media_previews, MediaPreviews::On);
+        assert_eq!(config.invite_avatars, InviteAvatars::Off);
+    }
+
+    #[async_test]
+    async fn test_media_preview_config_stable_and_unstable() {
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().build().await;
+
+        let stream = client.account().subscribe_to_media_preview_config().await.unwrap();
+        pin_mut!(stream);
+        assert_next_matches!(stream, MediaPreviewConfigEventContent { media_previews: None, .. });
+        assert_pending!(stream);
+
+        // Both events are received in the same sync, the stable one is used and a
+        // single update is yielded.
+        server
+            .mock_sync()
+            .ok_and_run(&client, |builder| {
+                builder
+                    .add_global_account_data_event(GlobalAccountDataTestEvent::Custom(json!({
+                        "content": {
+                            "media_previews": "off",
+                            "invite_avatars": "off"
+                        },
+                        "type": "m.media_preview_config"
+                    })))
+                    .add_global_account_data_event(GlobalAccountDataTestEvent::Custom(json!({
+                        "content": {
+                            "media_previews": "on",
+                            "invite_avatars": "on"
+                        },
+                        "type": "io.element.msc4278.media_preview_config"
+                    })));
+            })
+            .await;
+
+        assert_next_matches!(
+            stream,
+            MediaPreviewConfigEventContent {
+                media_previews: Some(MediaPreviews::Off),
+                invite_avatars: Some(InviteAvatars::Off),
+                ..
+            }
+        );
+        assert_pending!(stream);
+
+        // A later unstable event doesn't override the stable one.
+        server
+            .mock_sync()
+            .ok_and_run(&client, |builder| {
+                builder.add_global_account_data_event(GlobalAccountDataTestEvent::Custom(json!({
+                    "content": {
+                        "media_previews": "private",
+                        "invite_avatars": "on"
+                    },
+                    "type": "io.element.msc4278.media_preview_config"
+                })));
+            })
+            .await;
+        assert_pending!(stream);
+
+        // The same config isn't yielded twice.
+        server
+            .mock_sync()
+            .ok_and_run(&client, |builder| {
+                builder.add_global_account_data_event(GlobalAccountDataTestEvent::Custom(json!({
+                    "content": {
+                        "media_previews": "off",
+                        "invite_avatars": "off"
+                    },
+                    "type": "m.media_preview_config"
+                })));
+            })
+            .await;
+        assert_pending!(stream);
+    }
+
+    #[async_test]
+    async fn test_subscribe_to_ignored_users() {
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().build().await;
+
+        let ignored_user_list = |user_ids: &[&str]| {
+            let ignored_users: serde_json::Map<_, _> =
+                user_ids.iter().map(|user_id| (user_id.to_string(), json!({}))).collect();
+            GlobalAccountDataTestEvent::Custom(json!({
+                "content": { "ignored_users": ignored_users },
+                "type": "m.ignored_user_list",
+            }))
+        };
 
-        assert!(initial_value.is_none());
+        server
+            .mock_sync()
+            .ok_and_run(&client, |builder| {
+                builder.add_global_account_data_event(ignored_user_list(&["@alice:localhost"]));
+            })
+            .await;
+
+        let (ignored_users, stream) = client.account().subscribe_to_ignored_users().await.unwrap();
+        assert_eq!(ignored_users, [owned_user_id!("@alice:localhost")]);
+        pin_mut!(stream);
+        assert_pending!(stream);
+
+        // Another client ignores a user.
+        server
+            .mock_sync()
+            .ok_and_run(&client, |builder| {
+                builder.add_global_account_data_event(ignored_user_list(&[
+                    "@alice:localhost",
+                    "@bob:localhost",
+                ]));
+            })
+            .await;
+
+        assert_next_eq!(
+            stream,
+            vec![owned_user_id!("@alice:localhost"), owned_user_id!("@bob:localhost")]
+        );
+        assert_pending!(stream);
+
+        // The same list isn't yielded twice.
+        server
+            .mock_sync()
+            .ok_and_run(&client, |builder| {
+                builder.add_global_account_data_event(ignored_user_list(&[
+                    "@bob:localhost",
+                    "@alice:localhost",
+                ]));
+            })
+            .await;
+        assert_pending!(stream);
+
+        // All the users are unignored.
+        server
+            .mock_sync()
+            .ok_and_run(&client, |builder| {
+                builder.add_global_account_data_event(ignored_user_list(&[]));
+            })
+            .await;
+
+        assert_next_eq!(stream, Vec::new());
+        assert_pending!(stream);
     }
 
     #[async_test]
@@ -20,7 +20,10 @@
 //!
 //! [1]: https://spec.matrix.org/unstable/client-server-api/#server-side-key-backups
 
-use std::collections::{BTreeMap, BTreeSet};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    num::NonZeroUsize,
+};
 
 use futures_core::Stream;
 use futures_util::StreamExt;
@@ -45,19 +48,40 @@ use ruma::{
     serde::Raw,
     OwnedRoomId, RoomId, TransactionId,
 };
+use serde::{Deserialize, Serialize};
 use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
 use tracing::{error, info, instrument, trace, warn, Span};
 
 pub mod futures;
 pub(crate) mod types;
 
-pub use types::{BackupState, UploadState};
+pub use types::{BackupDownloadProgress, BackupState, UploadState};
 
 use self::futures::WaitForSteadyState;
 use crate::{
-    crypto::olm::ExportedRoomKey, encryption::BackupDownloadStrategy, Client, Error, Room,
+    crypto::olm::ExportedRoomKey,
+    encryption::{BackupDownloadSettings, BackupDownloadStrategy},
+    Client, Error, Room,
 };
 
+/// The key under which the progress of an interrupted download of the room
+/// keys from the backup is stored in the crypto store.
+const DOWNLOAD_PROGRESS_STORE_KEY: &str = "backup_download_progress";
+
+/// The progress of a download of the room keys from the backup in batches,
+/// persisted after every batch so the download can be resumed.
+#[derive(Debug, Serialize, Deserialize)]
+struct BackupDownloadSavedProgress {
+    /// The backup version the room keys are downloaded from.
+    version: String,
+    /// The last room of the last completed batch.
+    last_room_id: OwnedRoomId,
+    /// The number of room keys downloaded so far.
+    downloaded: usize,
+    /// The total number of room keys in the backup.
+    total: usize,
+}
+
 /// The backups manager for the [`Client`].
 #[derive(Debug, Clone)]
 pub struct Backups {
@@ -444,7 +468,16 @@ impl Backups {
 
     /// Download all room keys for a certain room from the server-side key
     /// backup.
-    pub async fn download_room_keys_for_room(&self, room_id: &RoomId) -> Result<(), Error> {
+    ///
+    /// This is much faster than downloading the whole key backup, and can be
+    /// used to make the events of a room decryptable as soon as possible, e.g.
+    /// when the user opens it, while the whole key backup is still being
+    /// downloaded. Room keys we already have, including the ones imported
+    /// concurrently by another download, are not imported again.
+    ///
+    /// Returns the number of room keys which were imported, which is `0` if
+    /// the backup decryption key or the backup version aren't known.
+    pub async fn download_room_keys_for_room(&self, room_id: &RoomId) -> Result<usize, Error> {
         let olm_machine = self.client.olm_machine().await;
         let olm_machine = olm_machine.as_ref().ok_or(Error::NoOlmMachine)?;
 
@@ -452,22 +485,42 @@ impl Backups {
 
         if let Some(decryption_key) = backup_keys.decryption_key {
             if let Some(version) = backup_keys.backup_version {
-                let request =
-                    get_backup_keys_for_room::v3::Request::new(version.clone(), room_id.to_owned());
-                let response = self.client.send(request).await?;
-
-                // Transform response to standard format (map of room ID -> room key).
-                let response = get_backup_keys::v3::Response::new(BTreeMap::from([(
-                    room_id.to_owned(),
-                    RoomKeyBackup::new(response.sessions),
-                )]));
-
-                self.handle_downloaded_room_keys(response, decryption_key, &version, olm_machine)
+                let result = self
+                    .download_room_keys_for_room_impl(
+                        room_id,
+                        decryption_key,
+                        &version,
+                        olm_machine,
+                    )
                     .await?;
+
+                return Ok(result.imported_count);
             }
         }
 
-        Ok(())
+        Ok(0)
+    }
+
+    /// Download and import all room keys for a certain room from the given
+    /// backup version.
+    async fn download_room_keys_for_room_impl(
+        &self,
+        room_id: &RoomId,
+        decryption_key: BackupDecryptionKey,
+        version: &str,
+        olm_machine: &OlmMachine,
+    ) -> Result<RoomKeyImportResult, Error> {
+        let request =
+            get_backup_keys_for_room::v3::Request::new(version.to_owned(), room_id.to_owned());
+        let response = self.client.send(request).await?;
+
+        // Transform response to standard format (map of room ID -> room key).
+        let response = get_backup_keys::v3::Response::new(BTreeMap::from([(
+            room_id.to_owned(),
+            RoomKeyBackup::new(response.sessions),
+        )]));
+
+        self.handle_downloaded_room_keys(response, decryption_key, version, olm_machine).await
     }
 
     /// Download a single room key from the server-side key backup.
@@ -549,7 +602,7 @@ impl Backups {
         backup_decryption_key: BackupDecryptionKey,
         backup_version: &str,
         olm_machine: &OlmMachine,
-    ) -> Result<(), Error> {
+    ) -> Result<RoomKeyImportResult, Error> {
         let mut decrypted_room_keys: Vec<_> = Vec::new();
 
         for (room_id, room_keys) in backed_up_keys.rooms {
@@ -592,28 +645,175 @@ impl Backups {
 
         // Since we can't use the usual room keys stream from the `OlmMachine`
         // we're going to send things out in our own custom broadcaster.
-        let _ = self.client.inner.e2ee.backup_state.room_keys_broadcaster.send(result);
+        let _ = self.client.inner.e2ee.backup_state.room_keys_broadcaster.send(result.clone());
 
-        Ok(())
+        Ok(result)
     }
 
     /// Download all room keys from the backup on the homeserver.
+    ///
+    /// Depending on the [`BackupDownloadSettings`], the room keys are either
+    /// downloaded in a single request, or room by room, in batches.
     async fn download_all_room_keys(
         &self,
         decryption_key: BackupDecryptionKey,
         version: String,
+        total: usize,
     ) -> Result<(), Error> {
+        let settings = self.client.inner.e2ee.encryption_settings.backup_download_settings;
+
+        if settings.batch_size.is_some() {
+            return self
+                .download_room_keys_in_batches(decryption_key, version, total, None, settings)
+                .await;
+        }
+
+        self.set_state(BackupState::Downloading(BackupDownloadProgress { downloaded: 0, total }));
+
         let request = get_backup_keys::v3::Request::new(version.clone());
         let response = self.client.send(request).await?;
 
         let olm_machine = self.client.olm_machine().await;
         let olm_machine = olm_machine.as_ref().ok_or(Error::NoOlmMachine)?;
 
-        self.handle_downloaded_room_keys(response, decryption_key, &version, olm_machine).await?;
+        let result = self
+            .handle_downloaded_room_keys(response, decryption_key, &version, olm_machine)
+            .await?;
+
+        self.set_state(BackupState::Downloading(BackupDownloadProgress {
+            downloaded: result.total_count,
+            total,
+        }));
 
         Ok(())
     }
 
+    /// Download the room keys of the rooms known to the client from the
+    /// backup, room by room, in batches.
+    ///
+    /// The progress is persisted in the crypto store after every batch, so the
+    /// download can be resumed with the `saved_progress` if it is interrupted.
+    async fn download_room_keys_in_batches(
+        &self,
+        decryption_key: BackupDecryptionKey,
+        version: String,
+        total: usize,
+        saved_progress: Option<BackupDownloadSavedProgress>,
+        settings: BackupDownloadSettings,
+    ) -> Result<(), Error> {
+        let batch_size = settings.batch_size.map_or(1, NonZeroUsize::get);
+
+        let olm_machine = self.client.olm_machine().await;
+        let olm_machine = olm_machine.as_ref().ok_or(Error::NoOlmMachine)?;
+
+        // The rooms are sorted, so the download can be resumed after the last room of
+        // the last completed batch.
+        let mut room_ids: Vec<_> =
+            self.client.rooms().iter().map(|room| room.room_id().to_owned()).collect();
+        room_ids.sort();
+
+        let mut downloaded = 0;
+
+        if let Some(saved_progress) = &saved_progress {
+            room_ids.retain(|room_id| *room_id > saved_progress.last_room_id);
+            downloaded = saved_progress.downloaded;
+        }
+
+        self.set_state(BackupState::Downloading(BackupDownloadProgress { downloaded, total }));
+
+        let mut batches = room_ids.chunks(batch_size).peekable();
+
+        while let Some(batch) = batches.next() {
+            let results: Vec<_> = futures_util::stream::iter(batch)
+                .map(|room_id| {
+                    self.download_room_keys_for_room_impl(
+                        room_id,
+                        decryption_key.clone(),
+                        &version,
+                        olm_machine,
+                    )
+                })
+                .buffer_unordered(settings.max_concurrent_requests.get())
+                .collect()
+                .await;
+
+            for result in results {
+                downloaded += result?.total_count;
+            }
+
+            let progress = BackupDownloadSavedProgress {
+                version: version.clone(),
+                last_room_id: batch.last().expect("chunks are never empty").to_owned(),
+                downloaded,
+                total,
+            };
+            olm_machine.store().set_value(DOWNLOAD_PROGRESS_STORE_KEY, &progress).await?;
+
+            trace!(downloaded, total, "Downloaded a batch of room keys from the backup");
+            self.set_state(BackupState::Downloading(BackupDownloadProgress { downloaded, total }));
+
+            if batches.peek().is_some() && !settings.batch_delay.is_zero() {
+                crate::sleep::sleep(settings.batch_delay).await;
+            }
+        }
+
+        olm_machine.store().remove_custom_value(DOWNLOAD_PROGRESS_STORE_KEY).await?;
+
+        info!(downloaded, total, "Downloaded the room keys from the backup");
+
+        Ok(())
+    }
+
+    /// Resume the download of the room keys from the backup, if a previous
+    /// download in batches was interrupted, e.g. because the app was closed.
+    async fn maybe_resume_download(&self) -> Result<(), Error> {
+        let _guard = self.client.locks().backup_modify_lock.lock().await;
+
+        let (decryption_key, version, saved_progress) = {
+            let olm_machine = self.client.olm_machine().await;
+            let olm_machine = olm_machine.as_ref().ok_or(Error::NoOlmMachine)?;
+
+            let saved_progress: Option<BackupDownloadSavedProgress> =
+                olm_machine.store().get_value(DOWNLOAD_PROGRESS_STORE_KEY).await?;
+            let Some(saved_progress) = saved_progress else { return Ok(()) };
+
+            let backup_keys = olm_machine.store().load_backup_keys().await?;
+
+            match (backup_keys.decryption_key, backup_keys.backup_version) {
+                (Some(decryption_key), Some(version)) if version == saved_progress.version => {
+                    (decryption_key, version, saved_progress)
+                }
+                _ => {
+                    // The backup changed since the download started, the progress is stale.
+                    olm_machine.store().remove_custom_value(DOWNLOAD_PROGRESS_STORE_KEY).await?;
+                    return Ok(());
+                }
+            }
+        };
+
+        info!(
+            downloaded = saved_progress.downloaded,
+            total = saved_progress.total,
+            "Resuming the download of the room keys from the backup"
+        );
+
+        let total = saved_progress.total;
+        let settings = self.client.inner.e2ee.encryption_settings.backup_download_settings;
+        let result = self
+            .download_room_keys_in_batches(
+                decryption_key,
+                version,
+                total,
+                Some(saved_progress),
+                settings,
+            )
+            .await;
+
+        self.set_state(BackupState::Enabled);
+
+        result
+    }
+
     fn room_keys_stream(
         &self,
     ) -> impl Stream<Item = Result<RoomKeyImportResult, BroadcastStreamRecvError>> {
@@ -849,21 +1049,21 @@ impl Backups {
                     .await?;
                 backup_machine.enable_backup_v1(backup_key).await?;
 
-                // If the user has set up the client to download any room keys, do so now. This
-                // is not really useful in a real scenario since the API to
-                // download room keys is not paginated.
-                //
-                // You need to download all room keys at once, parse a potentially huge JSON
-                // response and decrypt all the room keys found in the backup.
+                // If the user has set up the client to download any room keys, do so now.
                 //
-                // This doesn't work for any sizeable account.
+                // Unless a batch size is configured in the `BackupDownloadSettings`, all room
+                // keys are downloaded at once, which means parsing a potentially huge JSON
+                // response and decrypting all the room keys found in the backup. This doesn't
+                // work for any sizeable account.
                 if self.client.inner.e2ee.encryption_settings.backup_download_strategy
                     == BackupDownloadStrategy::OneShot
                 {
-                    self.set_state(BackupState::Downloading);
+                    let total =
+                        usize::try_from(u64::from(current_version.count)).unwrap_or(usize::MAX);
 
-                    if let Err(e) =
-                        self.download_all_room_keys(decryption_key, current_version.version).await
+                    if let Err(e) = self
+                        .download_all_room_keys(decryption_key, current_version.version, total)
+                        .await
                     {
                         warn!("Couldn't automatically download all room keys from backup: {e:?}");
                     }
@@ -954,7 +1154,21 @@ impl Backups {
 
         // Let us first check if we have a stored backup recovery key and a backup
         // version.
-        if !self.resume_backup_from_stored_backup_key(olm_machine).await? {
+        if self.resume_backup_from_stored_backup_key(olm_machine).await? {
+            // Resume the download of the room keys in the background, if it was
+            // interrupted.
+            if self.client.inner.e2ee.encryption_settings.backup_download_strategy
+                == BackupDownloadStrategy::OneShot
+            {
+                let this = self.clone();
+
+                matrix_sdk_common::executor::spawn(async move {
+                    if let Err(e) = this.maybe_resume_download().await {
+                        warn!("Couldn't resume the download of the room keys from backup: {e:?}");
+                    }
+                });
+            }
+        } else {
             // We didn't manage to enable backups from a stored backup recovery key, let us
             // check our secret inbox. Perhaps we can find a valid key there.
             self.maybe_resume_from_secret_inbox(olm_machine).await?;
@@ -100,6 +100,22 @@ impl Default for BackupClientState {
     }
 }
 
+/// The progress of the download of the room keys from the backup, see
+/// [`BackupState::Downloading`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BackupDownloadProgress {
+    /// The number of room keys downloaded so far.
+    pub downloaded: usize,
+
+    /// The total number of room keys in the backup, as reported by the
+    /// homeserver.
+    ///
+    /// If the room keys are downloaded room by room, only the room keys of the
+    /// rooms known to the [`Client`] are downloaded, so `downloaded` might
+    /// never reach this number.
+    pub total: usize,
+}
+
 /// The possible states of the [`Client`]'s room key backup mechanism.
 ///
 /// A local backup instance can be created either by receiving a valid backup
@@ -143,10 +159,11 @@ pub enum BackupState {
     /// The backup is enabled and room keys are actively being backed up.
     Enabled,
     /// Room keys are currently being downloaded. This state will only happen
-    /// after an `Enabling` state. The [`Client`] will attempt to download
+    /// after an `Enabling` state, or after a `Resuming` state if a previous
+    /// download was interrupted. The [`Client`] will attempt to download
     /// all room keys from the backup before transitioning into the
     /// `Enabled` state.
-    Downloading,
+    Downloading(BackupDownloadProgress),
     /// The backup is being disabled and deleted from the server. This state
     /// will happen when you call the [`Backups::disable()`] method. After it
     /// has been disabled, we're going to transition into the `Unknown` state.
@@ -0,0 +1,310 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Dehydrated devices support, as defined in [MSC3814].
+//!
+//! A dehydrated device is a virtual device living on the homeserver, which
+//! receives the room keys sent to the user while none of their real devices
+//! are online. See the documentation of the
+//! [`dehydrated_devices`](matrix_sdk_base::crypto::dehydrated_devices) module
+//! of the crypto crate for more details.
+//!
+//! [MSC3814]: https://github.com/matrix-org/matrix-spec-proposals/pull/3814
+
+use std::time::Duration;
+
+use matrix_sdk_base::crypto::{
+    dehydrated_devices::DehydrationError, store::types::DehydratedDeviceKey, OlmError,
+};
+use ruma::{
+    api::client::{
+        dehydrated_device::{delete_dehydrated_device, get_dehydrated_device, get_events},
+        error::ErrorKind,
+    },
+    assign, MilliSecondsSinceUnixEpoch, OwnedDeviceId,
+};
+use thiserror::Error;
+use tracing::{debug, info, instrument, trace};
+
+use crate::{
+    client::WeakClient, encryption::tasks::DehydratedDeviceRotationTask, Client, HttpError,
+};
+
+/// The display name of the dehydrated devices created by the SDK.
+const DEHYDRATED_DEVICE_DISPLAY_NAME: &str = "Dehydrated device";
+
+/// The key under which the time of the last rotation of the dehydrated device
+/// is stored in the crypto store.
+const LAST_ROTATION_STORE_KEY: &str = "dehydrated_device_last_rotation";
+
+/// Error type for the dehydrated devices subsystem.
+#[derive(Debug, Error)]
+pub enum DehydratedDevicesError {
+    /// A typical SDK error.
+    #[error(transparent)]
+    Sdk(#[from] crate::Error),
+
+    /// A request to the homeserver failed.
+    #[error(transparent)]
+    Http(#[from] HttpError),
+
+    /// The to-device events of the dehydrated device couldn't be handled.
+    #[error(transparent)]
+    Olm(#[from] OlmError),
+
+    /// The dehydrated device couldn't be rehydrated or created.
+    #[error(transparent)]
+    Dehydration(#[from] DehydrationError),
+}
+
+/// The result of [`DehydratedDevices::rotate_dehydrated_device()`].
+#[derive(Clone, Debug)]
+pub struct DehydratedDeviceRotation {
+    /// The number of room keys imported from the previous dehydrated device.
+    pub imported_room_keys: usize,
+
+    /// The number of secrets imported from the previous dehydrated device.
+    pub imported_secrets: usize,
+
+    /// The ID of the new dehydrated device.
+    pub device_id: OwnedDeviceId,
+}
+
+/// The dehydrated devices manager of the client.
+#[derive(Debug)]
+pub struct DehydratedDevices {
+    pub(super) client: Client,
+}
+
+impl DehydratedDevices {
+    /// Replace the dehydrated device of the user with a new one, after
+    /// importing the room keys received by the current one.
+    ///
+    /// This downloads the current dehydrated device, rehydrates it with the
+    /// given key, and imports the room keys from all the to-device events it
+    /// has received. The device is then deleted, and a new dehydrated device,
+    /// encrypted with the same key, is created and uploaded.
+    ///
+    /// If the user doesn't have a dehydrated device yet, a new one is created
+    /// and uploaded right away.
+    ///
+    /// The cross-signing keys must be available, since the new device is
+    /// signed with the self-signing key, and the current device must have been
+    /// signed with the same key to be rehydrated.
+    ///
+    /// # Arguments
+    ///
+    /// * `pickle_key` - The key used to encrypt the private parts of the
+    ///   dehydrated devices.
+    #[instrument(skip_all)]
+    pub async fn rotate_dehydrated_device(
+        &self,
+        pickle_key: &DehydratedDeviceKey,
+    ) -> Result<DehydratedDeviceRotation, DehydratedDevicesError> {
+        let olm_machine = self.client.olm_machine().await;
+        let olm_machine = olm_machine.as_ref().ok_or(crate::Error::NoOlmMachine)?;
+        let dehydrated_devices = olm_machine.dehydrated_devices();
+
+        let mut imported_room_keys = 0;
+        let mut imported_secrets = 0;
+
+        let request = get_dehydrated_device::unstable::Request::new();
+        match self.client.send(request).await {
+            Ok(response) => {
+                let device_id = response.device_id;
+                debug!(?device_id, "Rehydrating the current dehydrated device");
+
+                // Make sure that we know about the up-to-date device keys of the dehydrated
+                // device, its signature is checked during the rehydration.
+                let (request_id, request) =
+                    olm_machine.query_keys_for_users([olm_machine.user_id()]);
+                self.client.keys_query(&request_id, request.device_keys).await?;
+
+                let rehydrated = dehydrated_devices
+                    .rehydrate(pickle_key, &device_id, response.device_data)
+                    .await?;
+
+                // Resume from the last batch of events, if a previous rotation was
+                // interrupted.
+                let mut next_batch = rehydrated.saved_progress().await.map_err(OlmError::from)?;
+
+                loop {
+                    let request = assign!(get_events::unstable::Request::new(device_id.clone()), {
+                        next_batch: next_batch.take(),
+                    });
+                    let response = self.client.send(request).await?;
+                    let is_done = response.events.is_empty();
+
+                    let batch = rehydrated
+                        .receive_events(
+                            response.events,
+                            response.next_batch.as_deref(),
+                            self.client.decryption_settings(),
+                        )
+                        .await?;
+
+                    imported_room_keys += batch.room_keys.len();
+                    imported_secrets += batch.imported_secrets;
+
+                    if is_done {
+                        break;
+                    }
+
+                    next_batch = response.next_batch;
+                }
+
+                self.client.send(delete_dehydrated_device::unstable::Request::new()).await?;
+            }
+
+            Err(error) if error.client_api_error_kind() == Some(&ErrorKind::NotFound) => {
+                debug!("There is no dehydrated device yet, creating one");
+            }
+
+            Err(error) => return Err(error.into()),
+        }
+
+        let device = dehydrated_devices.create().await?;
+        let request =
+            device.keys_for_upload(DEHYDRATED_DEVICE_DISPLAY_NAME.to_owned(), pickle_key).await?;
+        let device_id = self.client.send(request).await?.device_id;
+
+        olm_machine
+            .store()
+            .set_value(LAST_ROTATION_STORE_KEY, &MilliSecondsSinceUnixEpoch::now())
+            .await
+            .map_err(OlmError::from)?;
+
+        info!(?device_id, imported_room_keys, imported_secrets, "Rotated the dehydrated device");
+
+        Ok(DehydratedDeviceRotation { imported_room_keys, imported_secrets, device_id })
+    }
+
+    /// Get the time at which the dehydrated device was last rotated by this
+    /// client, using [`DehydratedDevices::rotate_dehydrated_device()`].
+    pub async fn last_rotation_time(
+        &self,
+    ) -> Result<Option<MilliSecondsSinceUnixEpoch>, DehydratedDevicesError> {
+        let olm_machine = self.client.olm_machine().await;
+        let olm_machine = olm_machine.as_ref().ok_or(crate::Error::NoOlmMachine)?;
+
+        Ok(olm_machine.store().get_value(LAST_ROTATION_STORE_KEY).await.map_err(OlmError::from)?)
+    }
+
+    /// Rotate the dehydrated device if it wasn't rotated during the given
+    /// interval, using the pickle key cached in the crypto store.
+    ///
+    /// Nothing happens if there is no cached pickle key, see
+    /// [`DehydratedDevices::rotate_dehydrated_device()`] for the rotation
+    /// itself.
+    ///
+    /// Returns the result of the rotation, if it happened.
+    #[instrument(skip(self))]
+    pub async fn rotate_dehydrated_device_if_needed(
+        &self,
+        interval: Duration,
+    ) -> Result<Option<DehydratedDeviceRotation>, DehydratedDevicesError> {
+        let pickle_key = {
+            let olm_machine = self.client.olm_machine().await;
+            let olm_machine = olm_machine.as_ref().ok_or(crate::Error::NoOlmMachine)?;
+            olm_machine.dehydrated_devices().get_dehydrated_device_pickle_key().await?
+        };
+
+        let Some(pickle_key) = pickle_key else {
+            trace!("There is no cached pickle key, not rotating the dehydrated device");
+            return Ok(None);
+        };
+
+        if let Some(last_rotation) = self.last_rotation_time().await? {
+            let elapsed = u64::from(MilliSecondsSinceUnixEpoch::now().get())
+                .saturating_sub(u64::from(last_rotation.get()));
+
+            if Duration::from_millis(elapsed) < interval {
+                trace!(?last_rotation, "The dehydrated device was rotated recently");
+                return Ok(None);
+            }
+        }
+
+        self.rotate_dehydrated_device(&pickle_key).await.map(Some)
+    }
+
+    /// Rotate the dehydrated device periodically, in the background.
+    ///
+    /// After every sync, the dehydrated device is rotated with
+    /// [`DehydratedDevices::rotate_dehydrated_device_if_needed()`], if it
+    /// wasn't rotated during the given interval. This only happens if the
+    /// dehydrated device pickle key is cached in the crypto store.
+    ///
+    /// Calling this method again replaces the previous interval. The rotation
+    /// can be stopped with
+    /// [`DehydratedDevices::disable_automatic_rotation()`].
+    pub fn enable_automatic_rotation(&self, interval: Duration) {
+        let task =
+            DehydratedDeviceRotationTask::new(WeakClient::from_client(&self.client), interval);
+
+        // Check right away, without waiting for the next sync.
+        task.trigger_rotation();
+
+        self.client.inner.e2ee.tasks.lock().rotate_dehydrated_device = Some(task);
+    }
+
+    /// Stop the automatic rotation of the dehydrated device, enabled with
+    /// [`DehydratedDevices::enable_automatic_rotation()`].
+    pub fn disable_automatic_rotation(&self) {
+        self.client.inner.e2ee.tasks.lock().rotate_dehydrated_device = None;
+    }
+
+    /// Notify the automatic rotation task, if any, that it might be time to
+    /// rotate the dehydrated device.
+    pub(crate) fn maybe_trigger_rotation(&self) {
+        let tasks = self.client.inner.e2ee.tasks.lock();
+
+        if let Some(task) = tasks.rotate_dehydrated_device.as_ref() {
+            task.trigger_rotation();
+        }
+    }
+
+    /// Delete the dehydrated device of the user from the homeserver.
+    ///
+    /// This should be called once the room keys of the dehydrated device have
+    /// been imported, if the device isn't replaced with
+    /// [`DehydratedDevices::rotate_dehydrated_device()`].
+    ///
+    /// If the deletion succeeds, the dehydrated device pickle key cached in the
+    /// crypto store is deleted as well.
+    ///
+    /// Returns `true` if a dehydrated device was deleted, `false` if the user
+    /// didn't have one.
+    #[instrument(skip_all)]
+    pub async fn delete_dehydrated_device(&self) -> Result<bool, DehydratedDevicesError> {
+        let olm_machine = self.client.olm_machine().await;
+        let olm_machine = olm_machine.as_ref().ok_or(crate::Error::NoOlmMachine)?;
+
+        match self.client.send(delete_dehydrated_device::unstable::Request::new()).await {
+            Ok(response) => {
+                info!(device_id = ?response.device_id, "Deleted the dehydrated device");
+
+                olm_machine.dehydrated_devices().delete_dehydrated_device_pickle_key().await?;
+
+                Ok(true)
+            }
+
+            Err(error) if error.client_api_error_kind() == Some(&ErrorKind::NotFound) => {
+                debug!("There is no dehydrated device to delete");
+                Ok(false)
+            }
+
+            Err(error) => Err(error.into()),
+        }
+    }
+}
@@ -39,6 +39,8 @@ pub struct DeviceUpdates {
     pub new: BTreeMap<OwnedUserId, BTreeMap<OwnedDeviceId, Device>>,
     /// The list of changed devices.
     pub changed: BTreeMap<OwnedUserId, BTreeMap<OwnedDeviceId, Device>>,
+    /// The list of devices which have been deleted by their owner.
+    pub deleted: BTreeMap<OwnedUserId, BTreeMap<OwnedDeviceId, Device>>,
 }
 
 impl DeviceUpdates {
@@ -63,11 +65,27 @@ impl DeviceUpdates {
 
         let new = updates.new.into_iter().map(map_devices).collect();
         let changed = updates.changed.into_iter().map(map_devices).collect();
+        let deleted = updates.deleted.into_iter().map(map_devices).collect();
 
-        DeviceUpdates { new, changed }
+        DeviceUpdates { new, changed, deleted }
     }
 }
 
+/// An update about the devices of a single user, as returned by
+/// [`Encryption::devices_stream_for_user()`].
+///
+/// [`Encryption::devices_stream_for_user()`]: crate::encryption::Encryption::devices_stream_for_user
+#[derive(Clone, Debug)]
+pub struct UserDeviceUpdates {
+    /// All the devices of the user we currently know about.
+    pub devices: Vec<Device>,
+    /// The devices of the user which have been deleted since the previous
+    /// update.
+    ///
+    /// Those devices aren't part of [`UserDeviceUpdates::devices`] anymore.
+    pub deleted: Vec<Device>,
+}
+
 /// A device represents a E2EE capable client or device of an user.
 ///
 /// A `Device` is backed by [device keys] that are uploaded to the server.
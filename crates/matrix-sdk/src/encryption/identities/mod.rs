@@ -87,7 +87,7 @@
 mod devices;
 mod users;
 
-pub use devices::{Device, DeviceUpdates, UserDevices};
+pub use devices::{Device, DeviceUpdates, UserDeviceUpdates, UserDevices};
 pub use matrix_sdk_base::crypto::types::MasterPubkey;
 pub use users::{IdentityUpdates, UserIdentity};
 
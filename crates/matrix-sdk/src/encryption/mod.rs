@@ -22,8 +22,10 @@ use std::{
     collections::{BTreeMap, HashSet},
     io::{Cursor, Read, Write},
     iter,
+    num::NonZeroUsize,
     path::PathBuf,
     sync::Arc,
+    time::Duration,
 };
 
 use eyeball::{SharedObservable, Subscriber};
@@ -33,13 +35,18 @@ use futures_util::{
     stream::{self, StreamExt},
 };
 use matrix_sdk_base::crypto::{
-    store::types::{RoomKeyBundleInfo, RoomKeyInfo},
+    store::types::{RoomKeyBundleInfo, RoomKeyInfo, SenderDataUpgrade},
     types::requests::{
         OutgoingRequest, OutgoingVerificationRequest, RoomMessageRequest, ToDeviceRequest,
     },
-    CrossSigningBootstrapRequests, OlmMachine,
+    CrossSigningBootstrapRequests, KeyRequestLogEntry, KeyRequestLogFilter, OlmMachine,
+    PendingKeyQueries, RoomEventDecryptionResult, ToDeviceUtdInfo,
+};
+#[cfg(feature = "experimental-send-custom-to-device")]
+use matrix_sdk_common::deserialized_responses::WithheldCode;
+use matrix_sdk_common::{
+    deserialized_responses::TimelineEvent, executor::spawn, locks::Mutex as StdMutex,
 };
-use matrix_sdk_common::{executor::spawn, locks::Mutex as StdMutex};
 use ruma::{
     api::client::{
         keys::{
@@ -55,12 +62,14 @@ use ruma::{
     assign,
     events::{
         direct::DirectUserIdentifier,
-        room::{MediaSource, ThumbnailInfo},
+        room::{encrypted::OriginalSyncRoomEncryptedEvent, MediaSource, ThumbnailInfo},
     },
-    DeviceId, MilliSecondsSinceUnixEpoch, OwnedDeviceId, OwnedUserId, TransactionId, UserId,
+    serde::Raw,
+    DeviceId, MilliSecondsSinceUnixEpoch, OwnedDeviceId, OwnedRoomId, OwnedUserId, TransactionId,
+    UserId,
 };
 #[cfg(feature = "experimental-send-custom-to-device")]
-use ruma::{events::AnyToDeviceEventContent, serde::Raw, to_device::DeviceIdOrAllDevices};
+use ruma::{events::AnyToDeviceEventContent, to_device::DeviceIdOrAllDevices};
 use serde::Deserialize;
 use tasks::BundleReceiverTask;
 use tokio::sync::{Mutex, RwLockReadGuard};
@@ -71,8 +80,11 @@ use vodozemac::Curve25519PublicKey;
 
 use self::{
     backups::{types::BackupClientState, Backups},
+    dehydrated_devices::DehydratedDevices,
     futures::UploadEncryptedFile,
-    identities::{Device, DeviceUpdates, IdentityUpdates, UserDevices, UserIdentity},
+    identities::{
+        Device, DeviceUpdates, IdentityUpdates, UserDeviceUpdates, UserDevices, UserIdentity,
+    },
     recovery::{Recovery, RecoveryState},
     secret_storage::SecretStorage,
     tasks::{BackupDownloadTask, BackupUploadingTask, ClientTasks},
@@ -87,6 +99,7 @@ use crate::{
 };
 
 pub mod backups;
+pub mod dehydrated_devices;
 pub mod futures;
 pub mod identities;
 pub mod recovery;
@@ -179,6 +192,10 @@ pub struct EncryptionSettings {
     /// Take a look at the [`BackupDownloadStrategy`] enum for more options.
     pub backup_download_strategy: BackupDownloadStrategy,
 
+    /// Configure how all the room keys are downloaded from the backup, when
+    /// [`BackupDownloadStrategy::OneShot`] is used.
+    pub backup_download_settings: BackupDownloadSettings,
+
     /// Automatically create a backup version if no backup exists.
     pub auto_enable_backups: bool,
 }
@@ -210,6 +227,44 @@ pub enum BackupDownloadStrategy {
     Manual,
 }
 
+/// Settings for the download of all the room keys from the backup, see
+/// [`BackupDownloadStrategy::OneShot`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BackupDownloadSettings {
+    /// Download the room keys room by room, in batches of this many rooms,
+    /// instead of downloading the whole backup in a single request.
+    ///
+    /// Only the room keys of the rooms known to the client are downloaded this
+    /// way. The progress is persisted after every batch, so an interrupted
+    /// download is resumed from the last completed batch when the client is
+    /// restored.
+    ///
+    /// By default, the whole backup is downloaded in a single request.
+    pub batch_size: Option<NonZeroUsize>,
+
+    /// The delay between two batches, to avoid hitting the rate limits of the
+    /// homeserver.
+    ///
+    /// Only used if `batch_size` is set, defaults to no delay.
+    pub batch_delay: Duration,
+
+    /// The maximum number of requests sent concurrently to download a batch.
+    ///
+    /// Only used if `batch_size` is set, defaults to a single request at a
+    /// time.
+    pub max_concurrent_requests: NonZeroUsize,
+}
+
+impl Default for BackupDownloadSettings {
+    fn default() -> Self {
+        Self {
+            batch_size: None,
+            batch_delay: Duration::ZERO,
+            max_concurrent_requests: NonZeroUsize::MIN,
+        }
+    }
+}
+
 /// The verification state of our own device
 ///
 /// This enum tells us if our own user identity trusts these devices, in other
@@ -374,6 +429,34 @@ struct OAuthCrossSigningResetUiaaResetParameter {
     url: Url,
 }
 
+/// The result of [`Encryption::send_encrypted_to_device()`], listing the
+/// recipient devices which didn't get the event.
+#[cfg(feature = "experimental-send-custom-to-device")]
+#[derive(Debug, Default)]
+pub struct EncryptedToDeviceSendResult {
+    /// The devices we don't know about, they need to be fetched with a
+    /// `/keys/query` first.
+    pub unknown_devices: Vec<(OwnedUserId, OwnedDeviceId)>,
+
+    /// The devices the event couldn't be encrypted for, with the reason. This
+    /// is [`WithheldCode::NoOlm`] if no Olm session could be established,
+    /// because no one-time key could be claimed for the device.
+    pub withheld_devices: Vec<(OwnedUserId, OwnedDeviceId, WithheldCode)>,
+
+    /// The devices for which the `/sendToDevice` request failed.
+    pub failed_devices: Vec<(OwnedUserId, OwnedDeviceId)>,
+}
+
+#[cfg(feature = "experimental-send-custom-to-device")]
+impl EncryptedToDeviceSendResult {
+    /// Whether the event was sent to all the recipient devices.
+    pub fn is_success(&self) -> bool {
+        self.unknown_devices.is_empty()
+            && self.withheld_devices.is_empty()
+            && self.failed_devices.is_empty()
+    }
+}
+
 impl Client {
     pub(crate) async fn olm_machine(&self) -> RwLockReadGuard<'_, Option<OlmMachine>> {
         self.base_client().olm_machine().await
@@ -758,6 +841,57 @@ impl Encryption {
         }
     }
 
+    /// Get the users whose keys need to be queried, and the `/keys/query`
+    /// requests which are in flight.
+    ///
+    /// This is mostly useful for debugging, the key queries can be configured
+    /// with [`ClientBuilder::with_key_query_config()`].
+    ///
+    /// [`ClientBuilder::with_key_query_config()`]: crate::ClientBuilder::with_key_query_config
+    pub async fn pending_key_queries(&self) -> Result<PendingKeyQueries> {
+        let olm = self.client.olm_machine().await;
+        let olm = olm.as_ref().ok_or(Error::NoOlmMachine)?;
+
+        Ok(olm.pending_key_queries().await?)
+    }
+
+    /// Get the most recent entries of the room key request log, the newest
+    /// entries first.
+    ///
+    /// The log needs to be enabled with
+    /// [`ClientBuilder::with_key_request_log_enabled()`] for the room key
+    /// requests to be recorded.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - The maximum number of entries to return.
+    ///
+    /// * `filter` - The filter the entries need to match, the default filter
+    ///   matches all the entries.
+    ///
+    /// [`ClientBuilder::with_key_request_log_enabled()`]: crate::ClientBuilder::with_key_request_log_enabled
+    pub async fn key_request_log(
+        &self,
+        limit: usize,
+        filter: KeyRequestLogFilter,
+    ) -> Result<Vec<KeyRequestLogEntry>> {
+        let olm = self.client.olm_machine().await;
+        let olm = olm.as_ref().ok_or(Error::NoOlmMachine)?;
+
+        Ok(olm.key_request_log(limit, &filter).await?)
+    }
+
+    /// Remove the entries of the room key request log which were recorded
+    /// before the given time.
+    ///
+    /// Returns the number of removed entries.
+    pub async fn prune_key_request_log(&self, before: MilliSecondsSinceUnixEpoch) -> Result<usize> {
+        let olm = self.client.olm_machine().await;
+        let olm = olm.as_ref().ok_or(Error::NoOlmMachine)?;
+
+        Ok(olm.prune_key_request_log(before).await?)
+    }
+
     /// Get a [`Subscriber`] for the [`VerificationState`].
     ///
     /// # Examples
@@ -1045,6 +1179,92 @@ impl Encryption {
             .map(move |updates| DeviceUpdates::new(client.to_owned(), updates)))
     }
 
+    /// Returns a stream of updates about the devices of a single user.
+    ///
+    /// The stream first yields the devices of the user we currently know about,
+    /// then yields the whole, updated, list of devices every time a
+    /// `/keys/query` response adds, changes or deletes one of the devices of
+    /// the user. The devices which have been deleted are reported separately,
+    /// in [`UserDeviceUpdates::deleted`].
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose devices should be watched.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use matrix_sdk::Client;
+    /// # use ruma::user_id;
+    /// # use futures_util::{pin_mut, StreamExt};
+    /// # let client: Client = unimplemented!();
+    /// # async {
+    /// let alice = user_id!("@alice:example.org");
+    /// let devices_stream =
+    ///     client.encryption().devices_stream_for_user(alice).await?;
+    /// pin_mut!(devices_stream);
+    ///
+    /// while let Some(updates) = devices_stream.next().await {
+    ///     println!("Alice has {} devices", updates.devices.len());
+    ///
+    ///     for device in updates.deleted {
+    ///         println!("The device {} has been deleted", device.device_id());
+    ///     }
+    /// }
+    /// # anyhow::Ok(()) };
+    /// ```
+    pub async fn devices_stream_for_user(
+        &self,
+        user_id: &UserId,
+    ) -> Result<impl Stream<Item = UserDeviceUpdates>> {
+        // Subscribe before loading the current devices, so we don't miss any update
+        // happening in between.
+        let updates = {
+            let olm = self.client.olm_machine().await;
+            let olm = olm.as_ref().ok_or(Error::NoOlmMachine)?;
+            olm.store().devices_stream()
+        };
+
+        let devices = self.get_user_devices(user_id).await?.devices().collect();
+        let initial = UserDeviceUpdates { devices, deleted: Vec::new() };
+
+        let client = self.client.to_owned();
+        let user_id = user_id.to_owned();
+
+        let updates = updates.filter_map(move |mut updates| {
+            let client = client.to_owned();
+            let user_id = user_id.to_owned();
+
+            async move {
+                let deleted = updates.deleted.remove(&user_id).unwrap_or_default();
+
+                if deleted.is_empty()
+                    && !updates.new.contains_key(&user_id)
+                    && !updates.changed.contains_key(&user_id)
+                {
+                    return None;
+                }
+
+                let devices = match client.encryption().get_user_devices(&user_id).await {
+                    Ok(devices) => devices.devices().collect(),
+                    Err(e) => {
+                        warn!(?user_id, "Couldn't load the devices of the user: {e}");
+                        return None;
+                    }
+                };
+
+                let deleted = deleted
+                    .into_values()
+                    .map(|device| Device { inner: device, client: client.to_owned() })
+                    .collect();
+
+                Some(UserDeviceUpdates { devices, deleted })
+            }
+        });
+
+        Ok(stream::once(std::future::ready(initial)).chain(updates))
+    }
+
     /// Returns a stream of user identity updates, allowing users to listen for
     /// notifications about new or changed user identities.
     ///
@@ -1083,6 +1303,37 @@ impl Encryption {
             .map(move |updates| IdentityUpdates::new(client.to_owned(), updates)))
     }
 
+    /// Enable or disable the trust-on-first-use pinning of the devices of
+    /// other users.
+    ///
+    /// While enabled, the devices of a user are pinned the first time we see
+    /// them. Events sent from a new, unsigned, device of a user which has
+    /// pinned devices are then reported with
+    /// [`VerificationLevel::PinViolation`], until the new devices are pinned
+    /// with [`Encryption::pin_user_devices()`].
+    ///
+    /// Enabling the pinning pins the current devices of all the tracked users
+    /// which don't have pinned devices yet.
+    ///
+    /// [`VerificationLevel::PinViolation`]: matrix_sdk_common::deserialized_responses::VerificationLevel::PinViolation
+    pub async fn set_device_pinning_enabled(&self, enabled: bool) -> Result<()> {
+        let olm = self.client.olm_machine().await;
+        let olm = olm.as_ref().ok_or(Error::NoOlmMachine)?;
+
+        Ok(olm.store().set_device_pinning_enabled(enabled).await?)
+    }
+
+    /// Pin the current devices of the given user, acknowledging any new
+    /// devices they might have.
+    ///
+    /// See [`Encryption::set_device_pinning_enabled()`].
+    pub async fn pin_user_devices(&self, user_id: &UserId) -> Result<()> {
+        let olm = self.client.olm_machine().await;
+        let olm = olm.as_ref().ok_or(Error::NoOlmMachine)?;
+
+        Ok(olm.store().pin_user_devices(user_id).await?)
+    }
+
     /// Create and upload a new cross signing identity.
     ///
     /// # Arguments
@@ -1365,6 +1616,77 @@ impl Encryption {
         let olm = olm.as_ref().ok_or(Error::NoOlmMachine)?;
 
         let keys = olm.store().export_room_keys(predicate).await?;
+
+        Self::write_room_key_export(keys, path, passphrase).await
+    }
+
+    /// Export the E2EE keys of a single room into a file, as specified in the
+    /// [spec].
+    ///
+    /// This is a shortcut for [`Encryption::export_room_keys()`] with a
+    /// predicate selecting the keys of the given room, for example to move
+    /// them to another account. The resulting file can be imported with
+    /// [`Encryption::import_room_keys()`].
+    ///
+    /// [spec]: https://spec.matrix.org/unstable/client-server-api/#key-exports
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file path where the exported key file will be saved.
+    ///
+    /// * `passphrase` - The passphrase that will be used to encrypt the
+    ///   exported room keys.
+    ///
+    /// * `room_id` - The room whose keys should be exported.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if it isn't run on a Tokio runtime.
+    ///
+    /// This method will panic if it can't get enough randomness from the OS to
+    /// encrypt the exported keys securely.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use std::path::PathBuf;
+    /// # use matrix_sdk::{Client, ruma::room_id};
+    /// # use url::Url;
+    /// # async {
+    /// # let homeserver = Url::parse("http://localhost:8080")?;
+    /// # let mut client = Client::new(homeserver).await?;
+    /// let path = PathBuf::from("/home/example/e2e-room-keys.txt");
+    /// let room_id = room_id!("!test:localhost");
+    ///
+    /// client
+    ///     .encryption()
+    ///     .export_room_keys_for_room(path, "secret-passphrase", room_id)
+    ///     .await?;
+    /// # anyhow::Ok(()) };
+    /// ```
+    #[cfg(not(target_family = "wasm"))]
+    pub async fn export_room_keys_for_room(
+        &self,
+        path: PathBuf,
+        passphrase: &str,
+        room_id: &ruma::RoomId,
+    ) -> Result<()> {
+        let olm = self.client.olm_machine().await;
+        let olm = olm.as_ref().ok_or(Error::NoOlmMachine)?;
+
+        let keys = olm.store().export_room_keys_for_room(room_id).await?;
+
+        Self::write_room_key_export(keys, path, passphrase).await
+    }
+
+    /// Encrypt the given room keys with the passphrase, and write them to the
+    /// file at the given path.
+    #[cfg(not(target_family = "wasm"))]
+    async fn write_room_key_export(
+        keys: Vec<matrix_sdk_base::crypto::olm::ExportedRoomKey>,
+        path: PathBuf,
+        passphrase: &str,
+    ) -> Result<()> {
         let passphrase = zeroize::Zeroizing::new(passphrase.to_owned());
 
         let encrypt = move || -> Result<()> {
@@ -1517,6 +1839,139 @@ impl Encryption {
         Some(olm.store().historic_room_key_stream())
     }
 
+    /// Receive notifications of the to-device events which couldn't be
+    /// decrypted as a [`Stream`].
+    ///
+    /// This is useful to collect metrics about to-device decryption failures,
+    /// which are otherwise not visible outside of the sync processing. The
+    /// notifications tell whether the Olm session with the sender's device was
+    /// wedged, in which case the SDK automatically tries to unwedge it.
+    ///
+    /// If the reader of the stream lags too far behind, a warning will be
+    /// logged and the oldest items will be dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use matrix_sdk::Client;
+    /// # use url::Url;
+    /// # async {
+    /// # let homeserver = Url::parse("http://example.com")?;
+    /// # let client = Client::new(homeserver).await?;
+    /// use futures_util::StreamExt;
+    ///
+    /// let Some(mut utds_stream) =
+    ///     client.encryption().to_device_utds_stream().await
+    /// else {
+    ///     return Ok(());
+    /// };
+    ///
+    /// while let Some(utd_info) = utds_stream.next().await {
+    ///     println!("Couldn't decrypt a to-device event from {}", utd_info.sender);
+    /// }
+    /// # anyhow::Ok(()) };
+    /// ```
+    pub async fn to_device_utds_stream(&self) -> Option<impl Stream<Item = ToDeviceUtdInfo>> {
+        let olm = self.client.olm_machine().await;
+        let olm = olm.as_ref()?;
+
+        Some(olm.subscribe_to_to_device_utds())
+    }
+
+    /// Receive notifications of room keys whose sender data got upgraded as a
+    /// [`Stream`].
+    ///
+    /// The sender data of a room key is upgraded when the device or the
+    /// identity of its sender is discovered after the room key was received.
+    /// The encryption info of the events decrypted with those room keys, and
+    /// so their shields, should then be refreshed.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use matrix_sdk::Client;
+    /// # use url::Url;
+    /// # async {
+    /// # let homeserver = Url::parse("http://example.com")?;
+    /// # let client = Client::new(homeserver).await?;
+    /// use futures_util::StreamExt;
+    ///
+    /// let Some(mut upgrades_stream) =
+    ///     client.encryption().sender_data_upgrades_stream().await
+    /// else {
+    ///     return Ok(());
+    /// };
+    ///
+    /// while let Some(upgrades) = upgrades_stream.next().await {
+    ///     for upgrade in upgrades {
+    ///         println!(
+    ///             "The room key {} in {} got upgraded to {:?}",
+    ///             upgrade.session_id,
+    ///             upgrade.room_id,
+    ///             upgrade.new_sender_data_type
+    ///         );
+    ///     }
+    /// }
+    /// # anyhow::Ok(()) };
+    /// ```
+    pub async fn sender_data_upgrades_stream(
+        &self,
+    ) -> Option<impl Stream<Item = Vec<SenderDataUpgrade>>> {
+        let olm = self.client.olm_machine().await;
+        let olm = olm.as_ref()?;
+
+        Some(olm.store().sender_data_upgrades_stream())
+    }
+
+    /// Try to decrypt a batch of room events, in one pass.
+    ///
+    /// This is meant to retry the decryption of events which previously failed
+    /// to decrypt because of missing room keys, once the room keys have been
+    /// received, for example from the key backup, from one of our other devices
+    /// or from a dehydrated device. Every room key is loaded only once for the
+    /// whole batch, which is significantly faster than calling
+    /// [`Room::decrypt_event()`] for every event.
+    ///
+    /// # Arguments
+    ///
+    /// * `events` - The events that should be decrypted, along with the ID of
+    ///   the room they were sent to.
+    ///
+    /// Returns the events in the same order as `events`. The events that still
+    /// can't be decrypted are returned as a [`TimelineEvent`] representing the
+    /// decryption error, and the download of their room key from the key
+    /// backup is attempted, like with [`Room::decrypt_event()`]. Contrary to
+    /// the latter, no push actions are computed for the decrypted events.
+    pub async fn decrypt_room_events(
+        &self,
+        events: &[(OwnedRoomId, Raw<OriginalSyncRoomEncryptedEvent>)],
+    ) -> Result<Vec<TimelineEvent>> {
+        let olm = self.client.olm_machine().await;
+        let olm = olm.as_ref().ok_or(Error::NoOlmMachine)?;
+
+        let encrypted_events: Vec<_> = events
+            .iter()
+            .map(|(room_id, event)| (room_id.clone(), event.cast_ref().clone()))
+            .collect();
+        let results = olm
+            .try_decrypt_room_events(&encrypted_events, self.client.decryption_settings())
+            .await?;
+
+        Ok(events
+            .iter()
+            .zip(results)
+            .map(|((room_id, event), result)| match result {
+                RoomEventDecryptionResult::Decrypted(decrypted) => {
+                    TimelineEvent::from_decrypted(decrypted, None)
+                }
+                RoomEventDecryptionResult::UnableToDecrypt(utd_info) => {
+                    self.backups().maybe_download_room_key(room_id.clone(), event.clone());
+                    TimelineEvent::from_utd(event.clone().cast(), utd_info)
+                }
+            })
+            .collect())
+    }
+
     /// Get the secret storage manager of the client.
     pub fn secret_storage(&self) -> SecretStorage {
         SecretStorage { client: self.client.to_owned() }
@@ -1532,6 +1987,41 @@ impl Encryption {
         Recovery { client: self.client.to_owned() }
     }
 
+    /// Get the dehydrated devices manager of the client.
+    pub fn dehydrated_devices(&self) -> DehydratedDevices {
+        DehydratedDevices { client: self.client.to_owned() }
+    }
+
+    /// Automatically accept the verification requests coming from our own
+    /// verified devices.
+    ///
+    /// This is meant for bots and test setups running several sessions of the
+    /// same user. Once enabled, incoming self-verification requests are
+    /// accepted and driven to completion without any user interaction: SAS
+    /// verifications are confirmed as soon as the short auth string is
+    /// available, and a QR code is offered and confirmed once the other device
+    /// has scanned it, if the `qrcode` feature is enabled.
+    ///
+    /// Only requests sent by one of our own devices which is already
+    /// cross-signed by our own identity are accepted, requests from other
+    /// users are never accepted automatically.
+    ///
+    /// The verification requests and flows are still visible through the
+    /// usual event handlers and [`VerificationRequest::changes()`] streams, so
+    /// applications can log them.
+    pub fn set_auto_accept_self_verification(&self, enabled: bool) {
+        let mut tasks = self.client.inner.e2ee.tasks.lock();
+
+        if enabled {
+            if tasks.auto_accept_self_verification.is_none() {
+                let handle = self.client.add_event_handler(verification::auto_accept_event_handler);
+                tasks.auto_accept_self_verification = Some(handle);
+            }
+        } else if let Some(handle) = tasks.auto_accept_self_verification.take() {
+            self.client.remove_event_handler(handle);
+        }
+    }
+
     /// Enables the crypto-store cross-process lock.
     ///
     /// This may be required if there are multiple processes that may do writes
@@ -1809,6 +2299,87 @@ impl Encryption {
         event_type: &str,
         content: Raw<AnyToDeviceEventContent>,
     ) -> Result<Vec<(OwnedUserId, OwnedDeviceId)>> {
+        let mut result = EncryptedToDeviceSendResult::default();
+        self.encrypt_and_send_to_devices(recipient_devices, event_type, content, &mut result)
+            .await?;
+
+        let EncryptedToDeviceSendResult { withheld_devices, failed_devices, .. } = result;
+
+        Ok(withheld_devices
+            .into_iter()
+            .map(|(user_id, device_id, _)| (user_id, device_id))
+            .chain(failed_devices)
+            .collect())
+    }
+
+    /// Encrypts then send the given content to the given devices via the
+    /// `/sendToDevice` end-point, using Olm encryption.
+    ///
+    /// The Olm sessions which are missing are established first, by claiming
+    /// one-time keys for the recipient devices. The encrypted events are sent
+    /// in as few `/sendToDevice` requests as possible.
+    ///
+    /// The devices which didn't get the event, e.g. because they are unknown
+    /// or because no one-time key could be claimed for them, are reported in
+    /// the result, the event is still sent to the other devices.
+    ///
+    /// # Arguments
+    ///
+    /// * `recipients` - The user and device IDs of the recipient devices.
+    ///
+    /// * `event_type` - The type of the event to encrypt.
+    ///
+    /// * `content` - The content of the event to encrypt.
+    #[cfg(feature = "experimental-send-custom-to-device")]
+    #[instrument(skip(self, recipients, content), fields(recipient_count = recipients.len()))]
+    pub async fn send_encrypted_to_device(
+        &self,
+        recipients: &[(&UserId, &DeviceId)],
+        event_type: &str,
+        content: Raw<AnyToDeviceEventContent>,
+    ) -> Result<EncryptedToDeviceSendResult> {
+        let mut result = EncryptedToDeviceSendResult::default();
+        let mut devices = Vec::with_capacity(recipients.len());
+
+        for &(user_id, device_id) in recipients {
+            match self.get_device(user_id, device_id).await? {
+                Some(device) => devices.push(device),
+                None => {
+                    debug!(
+                        ?user_id,
+                        ?device_id,
+                        "Not sending a to-device event to an unknown device"
+                    );
+                    result.unknown_devices.push((user_id.to_owned(), device_id.to_owned()));
+                }
+            }
+        }
+
+        if !devices.is_empty() {
+            self.encrypt_and_send_to_devices(
+                devices.iter().collect(),
+                event_type,
+                content,
+                &mut result,
+            )
+            .await?;
+        }
+
+        Ok(result)
+    }
+
+    /// Encrypts the given content for the given devices and sends it, after
+    /// having established the missing Olm sessions.
+    ///
+    /// The devices which didn't get the event are added to the given `result`.
+    #[cfg(feature = "experimental-send-custom-to-device")]
+    async fn encrypt_and_send_to_devices(
+        &self,
+        recipient_devices: Vec<&Device>,
+        event_type: &str,
+        content: Raw<AnyToDeviceEventContent>,
+        result: &mut EncryptedToDeviceSendResult,
+    ) -> Result<()> {
         let users = recipient_devices.iter().map(|device| device.user_id());
 
         // Will claim one-time-key for users that needs it
@@ -1829,12 +2400,12 @@ impl Encryption {
             )
             .await?;
 
-        let mut failures: Vec<(OwnedUserId, OwnedDeviceId)> = Default::default();
-
         // Push the withhelds in the failures
-        withhelds.iter().for_each(|(d, _)| {
-            failures.push((d.user_id().to_owned(), d.device_id().to_owned()));
-        });
+        result.withheld_devices.extend(
+            withhelds
+                .into_iter()
+                .map(|(d, code)| (d.user_id().to_owned(), d.device_id().to_owned(), code)),
+        );
 
         // TODO: parallelize that? it's already grouping 250 devices per chunk.
         for request in requests {
@@ -1856,7 +2427,7 @@ impl Encryption {
                     for device_id in device_map.keys() {
                         match device_id {
                             DeviceIdOrAllDevices::DeviceId(device_id) => {
-                                failures.push((user_id.clone(), device_id.to_owned()));
+                                result.failed_devices.push((user_id.clone(), device_id.to_owned()));
                             }
                             DeviceIdOrAllDevices::AllDevices => {
                                 // Cannot happen in this case
@@ -1867,7 +2438,7 @@ impl Encryption {
             }
         }
 
-        Ok(failures)
+        Ok(())
     }
 }
 
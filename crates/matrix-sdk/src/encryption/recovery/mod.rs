@@ -113,7 +113,9 @@ use crate::{client::WeakClient, encryption::backups::BackupState, Client};
 
 pub mod futures;
 mod types;
-pub use self::types::{EnableProgress, RecoveryError, RecoveryState, Result};
+pub use self::types::{
+    EnableProgress, RecoveryError, RecoveryKey, RecoveryKeyError, RecoveryState, Result,
+};
 use self::{
     futures::{Enable, RecoverAndReset, Reset},
     types::{BackupDisabledContent, SecretStorageDisabledContent},
@@ -468,6 +470,11 @@ impl Recovery {
     /// In short, this method will turn a newly created [`Client`] into a fully
     /// end-to-end encryption enabled client.
     ///
+    /// If the input looks like a recovery key, as opposed to a passphrase, it
+    /// is validated using [`RecoveryKey::parse_flexible()`] before any request
+    /// is sent to the homeserver, and a [`RecoveryError::InvalidRecoveryKey`]
+    /// is returned if it is malformed.
+    ///
     /// # Examples
     ///
     /// ```no_run
@@ -485,6 +492,15 @@ impl Recovery {
     /// ```
     #[instrument(skip_all)]
     pub async fn recover(&self, recovery_key: &str) -> Result<()> {
+        // Give precise feedback about a mistyped recovery key, instead of a generic
+        // decryption error once the secret store is opened.
+        let parsed_key = if RecoveryKey::looks_like_recovery_key(recovery_key) {
+            Some(RecoveryKey::parse_flexible(recovery_key)?)
+        } else {
+            None
+        };
+        let recovery_key = parsed_key.as_ref().map_or(recovery_key, RecoveryKey::as_str);
+
         let store =
             self.client.encryption().secret_storage().open_secret_store(recovery_key).await?;
 
@@ -12,7 +12,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use matrix_sdk_base::crypto::store::types::RoomKeyCounts;
+use std::fmt;
+
+use matrix_sdk_base::crypto::{
+    secret_storage::{DecodeError, SecretStorageKey},
+    store::types::RoomKeyCounts,
+};
 use ruma::events::macros::EventContent;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
@@ -44,6 +49,114 @@ pub enum RecoveryError {
     /// Error in the secret storage subsystem.
     #[error(transparent)]
     SecretStorage(#[from] crate::encryption::secret_storage::SecretStorageError),
+
+    /// The given input looks like a recovery key, but it isn't a valid one.
+    #[error(transparent)]
+    InvalidRecoveryKey(#[from] RecoveryKeyError),
+}
+
+/// Error type describing why a string isn't a valid [`RecoveryKey`].
+#[derive(Debug, Error)]
+pub enum RecoveryKeyError {
+    /// The recovery key contains characters which aren't part of the Base58
+    /// alphabet.
+    #[error("The recovery key contains invalid characters")]
+    NotBase58,
+
+    /// The recovery key has an invalid length, some characters are probably
+    /// missing or duplicated.
+    #[error("The recovery key has an invalid length, expected {expected} bytes, got {actual}")]
+    InvalidLength {
+        /// The expected length of the decoded recovery key, in bytes.
+        expected: usize,
+        /// The length of the decoded recovery key, in bytes.
+        actual: usize,
+    },
+
+    /// The recovery key doesn't start with the expected prefix.
+    #[error("The recovery key doesn't start with the expected prefix")]
+    InvalidPrefix,
+
+    /// The checksum of the recovery key doesn't match, some characters are
+    /// probably mistyped.
+    #[error("The checksum of the recovery key doesn't match")]
+    InvalidChecksum,
+}
+
+/// A recovery key, i.e. a Base58-encoded secret storage key, which was checked
+/// to be well-formed.
+///
+/// This allows to give precise feedback to users typing their recovery key,
+/// before trying to open the secret store with it. Whether the recovery key is
+/// the key of the user's secret storage is only checked once the secret store
+/// is opened.
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+pub struct RecoveryKey {
+    inner: String,
+}
+
+impl RecoveryKey {
+    /// Parse a recovery key typed or pasted by the user.
+    ///
+    /// Whitespace and dashes are ignored, so the recovery key can be split in
+    /// groups of characters in any way. The Base58 encoding, the length, the
+    /// prefix and the checksum of the recovery key are validated.
+    pub fn parse_flexible(input: &str) -> Result<Self, RecoveryKeyError> {
+        let inner: String = input.chars().filter(|c| !c.is_whitespace() && *c != '-').collect();
+        let recovery_key = Self { inner };
+
+        SecretStorageKey::validate_base58(&recovery_key.inner).map_err(|error| match error {
+            DecodeError::KeyLength(expected, actual) => {
+                RecoveryKeyError::InvalidLength { expected, actual }
+            }
+            DecodeError::Prefix(..) => RecoveryKeyError::InvalidPrefix,
+            DecodeError::Parity(..) => RecoveryKeyError::InvalidChecksum,
+            _ => RecoveryKeyError::NotBase58,
+        })?;
+
+        Ok(recovery_key)
+    }
+
+    /// Check whether the given input looks like a recovery key rather than a
+    /// passphrase.
+    ///
+    /// This is a heuristic which doesn't validate the recovery key: the input
+    /// looks like a recovery key if, ignoring whitespace and dashes, it only
+    /// contains alphanumeric characters, starts with `Es` like every recovery
+    /// key, and has roughly the length of a recovery key. Use
+    /// [`RecoveryKey::parse_flexible()`] to find out why a recovery key is
+    /// invalid.
+    pub fn looks_like_recovery_key(input: &str) -> bool {
+        /// The length of a Base58-encoded recovery key.
+        const ENCODED_LENGTH: usize = 48;
+        /// The number of characters which may be missing or added by mistake.
+        const TOLERANCE: usize = 4;
+
+        let mut normalized: String =
+            input.chars().filter(|c| !c.is_whitespace() && *c != '-').collect();
+
+        let looks_like_recovery_key = normalized.starts_with("Es")
+            && normalized.chars().all(|c| c.is_ascii_alphanumeric())
+            && normalized.len().abs_diff(ENCODED_LENGTH) <= TOLERANCE;
+
+        normalized.zeroize();
+
+        looks_like_recovery_key
+    }
+
+    /// Get the normalized recovery key, without any whitespace or dashes.
+    ///
+    /// *Note*: This is the private key material of the secret storage, the
+    /// caller needs to ensure that copies of this string are zeroized.
+    pub fn as_str(&self) -> &str {
+        &self.inner
+    }
+}
+
+impl fmt::Debug for RecoveryKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecoveryKey").finish_non_exhaustive()
+    }
 }
 
 /// Enum describing the states the [`Recovery::enable()`] method can be in.
@@ -63,6 +63,7 @@
 use std::string::FromUtf8Error;
 
 use matrix_sdk_base::crypto::{
+    dehydrated_devices::DehydrationError,
     secret_storage::{DecodeError, MacError, SecretStorageKey},
     CryptoStoreError, SecretImportError,
 };
@@ -137,6 +138,16 @@ pub enum SecretStorageError {
     /// Error describing a decryption failure of a secret.
     #[error(transparent)]
     Decryption(#[from] DecryptionError),
+
+    /// The pickle key of the dehydrated devices could not be imported or
+    /// stored.
+    #[error(transparent)]
+    Dehydration(#[from] DehydrationError),
+
+    /// The name of a custom secret is empty or collides with the names
+    /// reserved by the specification.
+    #[error("The secret name `{0}` can't be used for a custom secret")]
+    InvalidCustomSecretName(String),
 }
 
 /// Error type describing decryption failures of the secret-storage system.
@@ -149,6 +160,10 @@ pub enum DecryptionError {
     /// Could not decode the secret, the secret is not valid UTF-8.
     #[error("Could not decode the secret, the secret is not valid UTF-8")]
     Utf8(#[from] FromUtf8Error),
+
+    /// Could not decode a custom secret, the secret is not valid base64.
+    #[error("Could not decode the custom secret, the secret is not valid base64")]
+    Base64(#[from] vodozemac::Base64DecodeError),
 }
 
 /// A high-level API to manage secret storage.
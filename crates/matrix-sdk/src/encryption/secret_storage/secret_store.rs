@@ -14,7 +14,10 @@
 
 use std::fmt;
 
-use matrix_sdk_base::crypto::{secret_storage::SecretStorageKey, CrossSigningKeyExport};
+use matrix_sdk_base::crypto::{
+    dehydrated_devices::DEHYDRATED_DEVICE_PICKLE_KEY_SECRET_NAME, secret_storage::SecretStorageKey,
+    store::types::DehydratedDeviceKey, CrossSigningKeyExport,
+};
 use ruma::{
     events::{
         secret::request::SecretName, secret_storage::secret::SecretEventContent,
@@ -30,7 +33,7 @@ use tracing::{
 };
 use zeroize::Zeroize;
 
-use super::{DecryptionError, Result};
+use super::{DecryptionError, Result, SecretStorageError};
 use crate::Client;
 
 #[cfg_attr(doc, aquamarine::aquamarine)]
@@ -263,6 +266,52 @@ impl SecretStore {
         Ok(())
     }
 
+    /// Store a custom secret of the application in the secret store.
+    ///
+    /// The secret is encoded as unpadded base64, encrypted with the
+    /// [`SecretStorageKey`] of this [`SecretStore`], and stored in the account
+    /// data of the user, like the well-known secrets. It can be retrieved using
+    /// [`SecretStore::get_custom_secret()`].
+    ///
+    /// # Arguments
+    ///
+    /// - `name`: The name of the secret, which serves as the event type of the
+    ///   account data event. It should be namespaced using the Java package
+    ///   naming convention, e.g. `com.example.scanner_token`. Names starting
+    ///   with `m.` are reserved by the specification and are rejected.
+    ///
+    /// - `value`: The secret itself.
+    pub async fn put_custom_secret(&self, name: &str, value: &[u8]) -> Result<()> {
+        let secret_name = custom_secret_name(name)?;
+
+        let mut secret = vodozemac::base64_encode(value);
+        let ret = self.put_secret(secret_name, &secret).await;
+        secret.zeroize();
+
+        ret
+    }
+
+    /// Retrieve a custom secret of the application from the secret store.
+    ///
+    /// This is the counterpart of [`SecretStore::put_custom_secret()`]. Secrets
+    /// written by other clients are accepted as well, whether their base64
+    /// encoding is padded or not, or uses the URL-safe alphabet.
+    ///
+    /// Returns `None` if the secret isn't stored in the secret store, or if it
+    /// wasn't encrypted with the [`SecretStorageKey`] of this [`SecretStore`].
+    pub async fn get_custom_secret(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        let secret_name = custom_secret_name(name)?;
+
+        let Some(mut secret) = self.get_secret(secret_name).await? else {
+            return Ok(None);
+        };
+
+        let value = decode_custom_secret(&secret);
+        secret.zeroize();
+
+        Ok(Some(value.map_err(DecryptionError::from)?))
+    }
+
     /// Get all the well-known private parts/keys of the [`OwnUserIdentity`] as
     /// a [`CrossSigningKeyExport`].
     ///
@@ -294,6 +343,55 @@ impl SecretStore {
         Ok(())
     }
 
+    /// Store the pickle key of the dehydrated devices in the secret store.
+    ///
+    /// The key is stored under the `org.matrix.msc3814` secret name, as defined
+    /// in [MSC3814], which allows our other devices to retrieve it using
+    /// [`SecretStore::import_dehydrated_device_pickle_key()`] and to rotate the
+    /// dehydrated device. The key is cached in the local store as well.
+    ///
+    /// [MSC3814]: https://github.com/matrix-org/matrix-spec-proposals/pull/3814
+    pub async fn export_dehydrated_device_pickle_key(
+        &self,
+        pickle_key: &DehydratedDeviceKey,
+    ) -> Result<()> {
+        let olm_machine = self.client.olm_machine().await;
+        let olm_machine = olm_machine.as_ref().ok_or(crate::Error::NoOlmMachine)?;
+
+        let mut key = pickle_key.to_base64();
+        let ret = self.put_secret(DEHYDRATED_DEVICE_PICKLE_KEY_SECRET_NAME, &key).await;
+        key.zeroize();
+        ret?;
+
+        olm_machine.dehydrated_devices().save_dehydrated_device_pickle_key(pickle_key).await?;
+
+        Ok(())
+    }
+
+    /// Retrieve the pickle key of the dehydrated devices from the secret store.
+    ///
+    /// If the secret store contains a pickle key, it is cached in the local
+    /// store, so it can be used to rotate the dehydrated device later on.
+    ///
+    /// Returns `None` if no pickle key was stored in the secret store.
+    pub async fn import_dehydrated_device_pickle_key(&self) -> Result<Option<DehydratedDeviceKey>> {
+        let olm_machine = self.client.olm_machine().await;
+        let olm_machine = olm_machine.as_ref().ok_or(crate::Error::NoOlmMachine)?;
+
+        let Some(mut secret) = self.get_secret(DEHYDRATED_DEVICE_PICKLE_KEY_SECRET_NAME).await?
+        else {
+            return Ok(None);
+        };
+
+        let pickle_key = DehydratedDeviceKey::from_base64(&secret);
+        secret.zeroize();
+        let pickle_key = pickle_key?;
+
+        olm_machine.dehydrated_devices().save_dehydrated_device_pickle_key(&pickle_key).await?;
+
+        Ok(Some(pickle_key))
+    }
+
     async fn maybe_enable_backups(&self) -> Result<()> {
         if let Some(mut secret) = self.get_secret(SecretName::RecoveryKey).await? {
             let ret = self.client.encryption().backups().maybe_enable_backups(&secret).await;
@@ -324,6 +422,7 @@ impl SecretStore {
     /// - `m.cross_signing.self_signing`: The self-signing cross-signing key.
     /// - `m.cross_signing.user_signing`: The user-signing cross-signing key.
     /// - `m.megolm_backup.v1`: The backup recovery key.
+    /// - `org.matrix.msc3814`: The pickle key of the dehydrated devices.
     ///
     /// If the `m.cross_signing.self_signing` key is successfully imported, it
     /// is used to sign our own [`Device`], marking it as verified. This step is
@@ -415,6 +514,10 @@ impl SecretStore {
             }
         }
 
+        if let Err(e) = self.import_dehydrated_device_pickle_key().await {
+            warn!("Could not import the dehydrated device pickle key from secret storage: {e:?}");
+        }
+
         self.maybe_enable_backups().await?;
 
         Ok(())
@@ -437,10 +540,48 @@ impl SecretStore {
             key.zeroize();
         }
 
+        if let Some(pickle_key) =
+            olm_machine.dehydrated_devices().get_dehydrated_device_pickle_key().await?
+        {
+            let mut key = pickle_key.to_base64();
+            self.put_secret(DEHYDRATED_DEVICE_PICKLE_KEY_SECRET_NAME, &key).await?;
+
+            key.zeroize();
+        }
+
         Ok(())
     }
 }
 
+/// Check that the given name can be used for a custom secret.
+fn custom_secret_name(name: &str) -> Result<SecretName> {
+    if name.is_empty() || name.starts_with("m.") {
+        Err(SecretStorageError::InvalidCustomSecretName(name.to_owned()))
+    } else {
+        Ok(SecretName::from(name))
+    }
+}
+
+/// Decode a base64-encoded custom secret, tolerating the padding, the URL-safe
+/// alphabet and the surrounding whitespace other clients might have used.
+fn decode_custom_secret(secret: &str) -> Result<Vec<u8>, vodozemac::Base64DecodeError> {
+    let mut normalized: String = secret
+        .trim()
+        .trim_end_matches('=')
+        .chars()
+        .map(|c| match c {
+            '-' => '+',
+            '_' => '/',
+            c => c,
+        })
+        .collect();
+
+    let decoded = vodozemac::base64_decode(&normalized);
+    normalized.zeroize();
+
+    decoded
+}
+
 impl fmt::Debug for SecretStore {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("SecretStore").field("key", &self.key).finish_non_exhaustive()
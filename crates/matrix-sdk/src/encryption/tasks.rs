@@ -31,6 +31,7 @@ use tracing::{debug, info, instrument, trace, warn};
 use crate::{
     client::WeakClient,
     encryption::backups::UploadState,
+    event_handler::EventHandlerHandle,
     executor::{spawn, JoinHandle},
     room::shared_room_history,
     Client, Room,
@@ -46,6 +47,8 @@ pub(crate) struct ClientTasks {
     pub(crate) update_recovery_state_after_backup: Option<JoinHandle<()>>,
     pub(crate) receive_historic_room_key_bundles: Option<BundleReceiverTask>,
     pub(crate) setup_e2ee: Option<JoinHandle<()>>,
+    pub(crate) rotate_dehydrated_device: Option<DehydratedDeviceRotationTask>,
+    pub(crate) auto_accept_self_verification: Option<EventHandlerHandle>,
 }
 
 pub(crate) struct BackupUploadingTask {
@@ -98,6 +101,70 @@ impl BackupUploadingTask {
     }
 }
 
+pub(crate) struct DehydratedDeviceRotationTask {
+    sender: mpsc::UnboundedSender<()>,
+    #[allow(dead_code)]
+    join_handle: JoinHandle<()>,
+}
+
+impl Drop for DehydratedDeviceRotationTask {
+    fn drop(&mut self) {
+        #[cfg(not(target_family = "wasm"))]
+        self.join_handle.abort();
+    }
+}
+
+impl DehydratedDeviceRotationTask {
+    pub(crate) fn new(client: WeakClient, interval: Duration) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+
+        let join_handle = spawn(async move {
+            Self::listen(client, interval, receiver).await;
+        });
+
+        Self { sender, join_handle }
+    }
+
+    pub(crate) fn trigger_rotation(&self) {
+        let _ = self.sender.send(());
+    }
+
+    async fn listen(
+        client: WeakClient,
+        interval: Duration,
+        mut receiver: mpsc::UnboundedReceiver<()>,
+    ) {
+        while receiver.recv().await.is_some() {
+            // Syncs might have triggered a rotation while we were busy with the previous
+            // one, a single check is enough for all of them.
+            while receiver.try_recv().is_ok() {}
+
+            if let Some(client) = client.get() {
+                Self::maybe_rotate(&client, interval).await;
+            } else {
+                trace!("Client got dropped, shutting down the task");
+                break;
+            }
+        }
+    }
+
+    #[instrument(skip(client))]
+    async fn maybe_rotate(client: &Client, interval: Duration) {
+        match client
+            .encryption()
+            .dehydrated_devices()
+            .rotate_dehydrated_device_if_needed(interval)
+            .await
+        {
+            Ok(Some(rotation)) => {
+                info!(device_id = ?rotation.device_id, "Automatically rotated the dehydrated device");
+            }
+            Ok(None) => trace!("The dehydrated device doesn't need to be rotated"),
+            Err(e) => warn!("Couldn't rotate the dehydrated device: {e:?}"),
+        }
+    }
+}
+
 /// Information about a request for a backup download for an undecryptable
 /// event.
 #[derive(Debug)]
@@ -0,0 +1,168 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Automatic acceptance of self-verification requests, see
+//! [`Encryption::set_auto_accept_self_verification()`].
+//!
+//! [`Encryption::set_auto_accept_self_verification()`]: crate::encryption::Encryption::set_auto_accept_self_verification
+
+use futures_util::StreamExt;
+use ruma::events::key::verification::request::ToDeviceKeyVerificationRequestEvent;
+use tracing::{debug, info, instrument, warn};
+
+#[cfg(feature = "qrcode")]
+use super::{QrVerification, QrVerificationState};
+use super::{
+    SasState, SasVerification, Verification, VerificationRequest, VerificationRequestState,
+};
+use crate::{executor::spawn, Client, Result};
+
+/// Event handler for incoming to-device verification requests, registered
+/// while the automatic acceptance of self-verification requests is enabled.
+pub(crate) async fn auto_accept_event_handler(
+    event: ToDeviceKeyVerificationRequestEvent,
+    client: Client,
+) {
+    if client.user_id() != Some(&*event.sender) {
+        return;
+    }
+
+    let device_id = &event.content.from_device;
+
+    // Only accept requests from devices which have been verified by our own
+    // identity, otherwise anyone with access to the account could get their
+    // device verified.
+    match client.encryption().get_device(&event.sender, device_id).await {
+        Ok(Some(device)) if device.is_cross_signed_by_owner() => {}
+        Ok(_) => {
+            debug!(
+                ?device_id,
+                "Not auto-accepting a verification request from an unverified device"
+            );
+            return;
+        }
+        Err(e) => {
+            warn!(?device_id, "Couldn't load the device of a verification request: {e:?}");
+            return;
+        }
+    }
+
+    let Some(request) = client
+        .encryption()
+        .get_verification_request(&event.sender, &event.content.transaction_id)
+        .await
+    else {
+        warn!(?device_id, "Couldn't find the verification request to auto-accept");
+        return;
+    };
+
+    spawn(async move {
+        if let Err(e) = drive_request(request).await {
+            warn!("Couldn't complete an auto-accepted verification request: {e:?}");
+        }
+    });
+}
+
+/// Accept the given request and drive it, and the verification flows it
+/// transitions into, to completion.
+#[instrument(skip_all, fields(flow_id = request.flow_id()))]
+async fn drive_request(request: VerificationRequest) -> Result<()> {
+    let mut changes = request.changes();
+
+    info!("Auto-accepting a verification request from one of our own devices");
+    request.accept().await?;
+
+    while let Some(state) = changes.next().await {
+        match state {
+            VerificationRequestState::Ready { .. } => {
+                // Offer our QR code as well, the other side can then either scan it or
+                // switch to a SAS verification.
+                #[cfg(feature = "qrcode")]
+                request.generate_qr_code().await?;
+            }
+            VerificationRequestState::Transitioned { verification } => match verification {
+                Verification::SasV1(sas) => {
+                    spawn(async move {
+                        if let Err(e) = drive_sas(sas).await {
+                            warn!("Couldn't complete an auto-accepted SAS verification: {e:?}");
+                        }
+                    });
+                }
+                #[cfg(feature = "qrcode")]
+                Verification::QrV1(qr) => {
+                    spawn(async move {
+                        if let Err(e) = drive_qr(qr).await {
+                            warn!("Couldn't complete an auto-accepted QR verification: {e:?}");
+                        }
+                    });
+                }
+            },
+            VerificationRequestState::Done | VerificationRequestState::Cancelled(_) => break,
+            VerificationRequestState::Created { .. }
+            | VerificationRequestState::Requested { .. } => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Accept the given SAS verification and confirm the short auth string as
+/// soon as it's available.
+async fn drive_sas(sas: SasVerification) -> Result<()> {
+    let mut changes = sas.changes();
+
+    if !sas.we_started() {
+        sas.accept().await?;
+    }
+
+    while let Some(state) = changes.next().await {
+        match state {
+            SasState::KeysExchanged { .. } => sas.confirm().await?,
+            SasState::Done { .. } => {
+                info!(device_id = ?sas.other_device().device_id(), "Auto-accepted SAS verification is done");
+                break;
+            }
+            SasState::Cancelled(_) => break,
+            SasState::Created { .. }
+            | SasState::Started { .. }
+            | SasState::Accepted { .. }
+            | SasState::Confirmed => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Confirm that the other side scanned our QR code, sending the
+/// `m.reciprocate` confirmation.
+#[cfg(feature = "qrcode")]
+async fn drive_qr(qr: QrVerification) -> Result<()> {
+    let mut changes = qr.changes();
+
+    while let Some(state) = changes.next().await {
+        match state {
+            QrVerificationState::Scanned => qr.confirm().await?,
+            QrVerificationState::Done { .. } => {
+                info!(device_id = ?qr.other_device().device_id(), "Auto-accepted QR verification is done");
+                break;
+            }
+            QrVerificationState::Cancelled(_) => break,
+            QrVerificationState::Started
+            | QrVerificationState::Confirmed
+            | QrVerificationState::Reciprocated => {}
+        }
+    }
+
+    Ok(())
+}
@@ -29,6 +29,7 @@
 //!   authentication string.
 //! * [`QrVerification`] - Interactive verification using QR codes.
 
+mod auto_accept;
 #[cfg(feature = "qrcode")]
 mod qrcode;
 mod requests;
@@ -37,7 +38,7 @@ mod sas;
 use as_variant::as_variant;
 pub use matrix_sdk_base::crypto::{
     format_emojis, AcceptSettings, AcceptedProtocols, CancelInfo, Emoji, EmojiShortAuthString,
-    SasState,
+    SasState, ShortAuthString,
 };
 #[cfg(feature = "qrcode")]
 pub use matrix_sdk_base::crypto::{
@@ -50,6 +51,8 @@ pub use requests::{VerificationRequest, VerificationRequestState};
 use ruma::RoomId;
 pub use sas::SasVerification;
 
+pub(crate) use self::auto_accept::auto_accept_event_handler;
+
 /// An enum over the different verification types the SDK supports.
 #[derive(Debug, Clone)]
 #[non_exhaustive]
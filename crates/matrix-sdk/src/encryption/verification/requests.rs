@@ -12,6 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::time::Duration;
+
 use futures_util::{Stream, StreamExt};
 use matrix_sdk_base::crypto::{
     CancelInfo, DeviceData, VerificationRequest as BaseVerificationRequest,
@@ -110,6 +112,22 @@ impl VerificationRequest {
         self.inner.is_ready()
     }
 
+    /// Has the verification request timed out.
+    ///
+    /// Timed out requests are cancelled with an `m.timeout` code after the next
+    /// sync, see [`ClientBuilder::with_verification_request_timeout()`].
+    ///
+    /// [`ClientBuilder::with_verification_request_timeout()`]: crate::ClientBuilder::with_verification_request_timeout
+    pub fn timed_out(&self) -> bool {
+        self.inner.timed_out()
+    }
+
+    /// Get the time left before the verification request times out, for
+    /// instance to show a countdown.
+    pub fn time_remaining(&self) -> Duration {
+        self.inner.time_remaining()
+    }
+
     /// Did we initiate the verification flow.
     pub fn we_started(&self) -> bool {
         self.inner.we_started()
@@ -14,7 +14,7 @@
 
 use futures_core::Stream;
 use matrix_sdk_base::crypto::{
-    AcceptSettings, CancelInfo, DeviceData, Emoji, Sas as BaseSas, SasState,
+    AcceptSettings, CancelInfo, DeviceData, Emoji, Sas as BaseSas, SasState, ShortAuthString,
 };
 use ruma::{events::key::verification::cancel::CancelCode, RoomId, UserId};
 
@@ -156,11 +156,30 @@ impl SasVerification {
         self.inner.emoji()
     }
 
+    /// Get the indices of the emojis of the short auth string.
+    ///
+    /// Returns seven numbers in the range from 0 to 63 inclusive, which can be
+    /// converted to an emoji using the table in the [spec]. The indices are
+    /// stable across versions of the SDK, so they can be used to look up
+    /// localized emoji descriptions, unlike the English descriptions returned
+    /// by [`SasVerification::emoji()`].
+    ///
+    /// [spec]: https://spec.matrix.org/unstable/client-server-api/#sas-method-emoji
+    pub fn emoji_indices(&self) -> Option<[u8; 7]> {
+        self.inner.emoji_index()
+    }
+
     /// Get the decimal version of the short auth string.
     pub fn decimals(&self) -> Option<(u16, u16, u16)> {
         self.inner.decimals()
     }
 
+    /// Get both the emoji indices and the decimal version of the short auth
+    /// string.
+    pub fn short_auth_string(&self) -> Option<ShortAuthString> {
+        self.inner.short_auth_string()
+    }
+
     /// Does this verification flow support emoji for the short authentication
     /// string.
     pub fn supports_emoji(&self) -> bool {
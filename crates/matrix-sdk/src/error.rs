@@ -39,7 +39,7 @@ use ruma::{
     },
     events::{room::power_levels::PowerLevelsError, tag::InvalidUserTagName},
     push::{InsertPushRuleError, RemovePushRuleError},
-    IdParseError,
+    IdParseError, OwnedUserId,
 };
 use serde_json::Error as JsonError;
 use thiserror::Error;
@@ -47,7 +47,8 @@ use url::ParseError as UrlParseError;
 
 use crate::{
     authentication::oauth::OAuthError, event_cache::EventCacheError, media::MediaError,
-    room::reply::ReplyError, sliding_sync::Error as SlidingSyncError, store_locks::LockStoreError,
+    pusher::PusherError, room::reply::ReplyError, sliding_sync::Error as SlidingSyncError,
+    store_locks::LockStoreError,
 };
 
 /// Result type of the matrix-sdk.
@@ -193,11 +194,7 @@ pub(crate) enum RetryKind {
     /// The request failed with a "transient" error, meaning it could be retried
     /// either soon, or after a given amount of time expressed in
     /// `retry_after`.
-    Transient {
-        // This is used only for attempts to retry, so on non-wasm32 code (in the `native` module).
-        #[cfg_attr(target_family = "wasm", allow(dead_code))]
-        retry_after: Option<Duration>,
-    },
+    Transient { retry_after: Option<Duration> },
 
     /// The request failed with a non-transient error, and retrying it would
     /// likely cause the same error again, so it's not worth retrying.
@@ -408,10 +405,50 @@ pub enum Error {
     #[error("can't ignore the logged-in user")]
     CantIgnoreLoggedInUser,
 
+    /// The homeserver doesn't support deleting account data.
+    #[error("the homeserver doesn't support deleting account data")]
+    AccountDataDeletionUnsupported,
+
+    /// The password used to authenticate with the homeserver is wrong.
+    #[error("the password is wrong")]
+    WrongPassword,
+
+    /// The new password was rejected by the homeserver because it doesn't
+    /// meet its strength requirements, which are described in the message.
+    #[error("the new password is too weak: {0}")]
+    WeakPassword(String),
+
+    /// The homeserver requires authentication stages other than a password to
+    /// authenticate this request.
+    ///
+    /// The given info can be used to go through the User-Interactive
+    /// Authentication manually.
+    #[error("the homeserver doesn't allow password authentication for this request")]
+    PasswordAuthUnsupported(Box<UiaaInfo>),
+
+    /// The access token of the client belongs to a different user than the
+    /// one of the session.
+    #[error("the access token belongs to {actual}, not to the logged-in user {expected}")]
+    UserIdMismatch {
+        /// The user ID of the session.
+        expected: OwnedUserId,
+        /// The user ID the access token belongs to.
+        actual: OwnedUserId,
+    },
+
+    /// The given timezone doesn't look like an IANA timezone identifier.
+    #[cfg(feature = "unstable-msc4175")]
+    #[error("the timezone `{0}` isn't a valid IANA timezone identifier")]
+    InvalidTimezone(String),
+
     /// An error happened during handling of a media subrequest.
     #[error(transparent)]
     Media(#[from] MediaError),
 
+    /// A pusher configuration is invalid.
+    #[error(transparent)]
+    Pusher(#[from] PusherError),
+
     /// An error happened while attempting to reply to an event.
     #[error(transparent)]
     ReplyError(#[from] ReplyError),
@@ -91,7 +91,7 @@ impl EventHandlerContext for Vec<Action> {
 
 /// A custom value registered with
 /// [`.add_event_handler_context`][Client::add_event_handler_context].
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Ctx<T>(pub T);
 
 impl<T: Clone + SendOutsideWasm + SyncOutsideWasm + 'static> EventHandlerContext for Ctx<T> {
@@ -15,12 +15,14 @@
 use std::{
     borrow::Borrow,
     collections::{btree_map, BTreeMap},
+    sync::Arc,
 };
 
 use ruma::{OwnedRoomId, RoomId};
 
 use super::{
-    EventHandlerFn, EventHandlerHandle, EventHandlerWrapper, HandlerKind, StaticEventTypePart,
+    EventHandlerFn, EventHandlerHandle, EventHandlerPriority, EventHandlerWrapper, HandlerKind,
+    StaticEventTypePart,
 };
 
 #[derive(Default)]
@@ -35,8 +37,17 @@ pub(super) struct EventHandlerMaps {
 }
 
 impl EventHandlerMaps {
-    pub fn add(&mut self, handle: EventHandlerHandle, handler_fn: Box<EventHandlerFn>) {
-        let wrapper = EventHandlerWrapper { handler_id: handle.handler_id, handler_fn };
+    pub fn add(
+        &mut self,
+        handle: EventHandlerHandle,
+        priority: EventHandlerPriority,
+        handler_fn: Box<EventHandlerFn>,
+    ) {
+        let wrapper = EventHandlerWrapper {
+            handler_id: handle.handler_id,
+            priority,
+            handler_fn: handler_fn.into(),
+        };
 
         match Key::new(handle) {
             Key::Kind(key) => {
@@ -74,7 +85,8 @@ impl EventHandlerMaps {
         ev_kind: HandlerKind,
         ev_type: &str,
         room_id: Option<&'a RoomId>,
-    ) -> impl Iterator<Item = (EventHandlerHandle, &'a EventHandlerFn)> + 'a {
+    ) -> impl Iterator<Item = (EventHandlerHandle, EventHandlerPriority, &'a Arc<EventHandlerFn>)> + 'a
+    {
         // Use get_key_value instead of just get to be able to access the event_type
         // from the BTreeMap key as &'static str, required for EventHandlerHandle.
         let kind_kv = self.by_kind.get_key_value(&ev_kind).map(|(_, handlers)| (None, handlers));
@@ -138,7 +150,7 @@ impl EventHandlerMaps {
                         handler_id: wrap.handler_id,
                     };
 
-                    (handle, &*wrap.handler_fn)
+                    (handle, wrap.priority, &wrap.handler_fn)
                 })
             })
     }
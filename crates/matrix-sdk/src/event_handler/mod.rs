@@ -34,9 +34,13 @@
 #[cfg(any(feature = "anyhow", feature = "eyre"))]
 use std::any::TypeId;
 use std::{
+    any::Any,
     borrow::Cow,
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet},
     fmt,
     future::Future,
+    panic::{self, AssertUnwindSafe},
     pin::Pin,
     sync::{
         atomic::{AtomicU64, Ordering::SeqCst},
@@ -51,21 +55,29 @@ use anymap2::any::CloneAny;
 use anymap2::any::CloneAnySendSync;
 use eyeball::{SharedObservable, Subscriber};
 use futures_core::Stream;
-use futures_util::stream::{FuturesUnordered, StreamExt};
+use futures_util::{
+    future::Either,
+    stream::{FuturesUnordered, StreamExt},
+    FutureExt,
+};
 use matrix_sdk_base::{
     deserialized_responses::{EncryptionInfo, TimelineEvent},
-    SendOutsideWasm, SyncOutsideWasm,
+    RoomInfo, SendOutsideWasm, SyncOutsideWasm,
+};
+use matrix_sdk_common::deserialized_responses::{
+    ProcessedToDeviceEvent, ToDeviceUnableToDecryptInfo,
 };
-use matrix_sdk_common::deserialized_responses::ProcessedToDeviceEvent;
 use pin_project_lite::pin_project;
 use ruma::{
-    events::{AnySyncStateEvent, BooleanType},
+    events::{AnySyncStateEvent, AnyToDeviceEvent, BooleanType},
     push::Action,
     serde::Raw,
-    OwnedRoomId,
+    OwnedRoomId, RoomId,
 };
 use serde::{de::DeserializeOwned, Deserialize};
 use serde_json::value::RawValue as RawJsonValue;
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
 use tracing::{debug, error, field::debug, instrument, warn};
 
 use self::maps::EventHandlerMaps;
@@ -87,6 +99,21 @@ type EventHandlerFn = dyn Fn(EventHandlerData<'_>) -> EventHandlerFut + Send + S
 #[cfg(target_family = "wasm")]
 type EventHandlerFn = dyn Fn(EventHandlerData<'_>) -> EventHandlerFut;
 
+#[cfg(not(target_family = "wasm"))]
+type EventHandlerPanicHookFn = dyn Fn(EventHandlerPanic) + Send + Sync;
+#[cfg(target_family = "wasm")]
+type EventHandlerPanicHookFn = dyn Fn(EventHandlerPanic);
+
+#[cfg(not(target_family = "wasm"))]
+type UnhandledToDeviceHandlerFn = dyn Fn(UnhandledToDeviceEvent) -> EventHandlerFut + Send + Sync;
+#[cfg(target_family = "wasm")]
+type UnhandledToDeviceHandlerFn = dyn Fn(UnhandledToDeviceEvent) -> EventHandlerFut;
+
+#[cfg(not(target_family = "wasm"))]
+type RoomInfoPredicate = dyn Fn(&RoomInfo) -> bool + Send + Sync;
+#[cfg(target_family = "wasm")]
+type RoomInfoPredicate = dyn Fn(&RoomInfo) -> bool;
+
 #[cfg(not(target_family = "wasm"))]
 type AnyMap = anymap2::Map<dyn CloneAnySendSync + Send + Sync>;
 #[cfg(target_family = "wasm")]
@@ -95,13 +122,39 @@ type AnyMap = anymap2::Map<dyn CloneAny>;
 #[derive(Default)]
 pub(crate) struct EventHandlerStore {
     handlers: RwLock<EventHandlerMaps>,
+    executions: RwLock<BTreeMap<EventHandlerPriority, EventHandlerExecution>>,
+    panic_hook: RwLock<Option<Arc<EventHandlerPanicHookFn>>>,
+    unhandled_to_device_handler: RwLock<Option<Arc<UnhandledToDeviceHandlerFn>>>,
     context: RwLock<AnyMap>,
     counter: AtomicU64,
 }
 
 impl EventHandlerStore {
-    pub fn add_handler(&self, handle: EventHandlerHandle, handler_fn: Box<EventHandlerFn>) {
-        self.handlers.write().unwrap().add(handle, handler_fn);
+    pub fn add_handler(
+        &self,
+        handle: EventHandlerHandle,
+        priority: EventHandlerPriority,
+        handler_fn: Box<EventHandlerFn>,
+    ) {
+        self.handlers.write().unwrap().add(handle, priority, handler_fn);
+    }
+
+    pub fn set_execution(&self, priority: EventHandlerPriority, execution: EventHandlerExecution) {
+        let mut executions = self.executions.write().unwrap();
+
+        if execution == EventHandlerExecution::default() {
+            executions.remove(&priority);
+        } else {
+            executions.insert(priority, execution);
+        }
+    }
+
+    fn execution(&self, priority: EventHandlerPriority) -> EventHandlerExecution {
+        self.executions.read().unwrap().get(&priority).copied().unwrap_or_default()
+    }
+
+    pub fn set_panic_hook(&self, hook: Option<Arc<EventHandlerPanicHookFn>>) {
+        *self.panic_hook.write().unwrap() = hook;
     }
 
     pub fn add_context<T>(&self, ctx: T)
@@ -115,6 +168,20 @@ impl EventHandlerStore {
         self.handlers.write().unwrap().remove(handle);
     }
 
+    pub fn set_unhandled_to_device_handler<H, Fut>(&self, handler: H)
+    where
+        H: Fn(UnhandledToDeviceEvent) -> Fut + SendOutsideWasm + SyncOutsideWasm + 'static,
+        Fut: Future<Output = ()> + SendOutsideWasm + 'static,
+    {
+        let handler_fn: Arc<UnhandledToDeviceHandlerFn> =
+            Arc::new(move |event| Box::pin(handler(event)));
+        *self.unhandled_to_device_handler.write().unwrap() = Some(handler_fn);
+    }
+
+    pub fn remove_unhandled_to_device_handler(&self) {
+        *self.unhandled_to_device_handler.write().unwrap() = None;
+    }
+
     #[cfg(test)]
     fn len(&self) -> usize {
         self.handlers.read().unwrap().len()
@@ -168,8 +235,87 @@ pub trait SyncEvent {
 }
 
 pub(crate) struct EventHandlerWrapper {
-    handler_fn: Box<EventHandlerFn>,
+    handler_fn: Arc<EventHandlerFn>,
     pub handler_id: u64,
+    pub priority: EventHandlerPriority,
+}
+
+/// The priority of an event handler.
+///
+/// When several event handlers are called for the same event, the handlers
+/// with a higher priority are called first, and the handlers with a lower
+/// priority are only called once they have all completed. The handlers with
+/// the same priority form a class, whose handlers run concurrently by default,
+/// see [`EventHandlerExecution`].
+///
+/// The handlers registered with [`Client::add_event_handler`] have the
+/// [`EventHandlerPriority::DEFAULT`] priority.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EventHandlerPriority(i32);
+
+impl EventHandlerPriority {
+    /// A priority for the handlers that must run before the default ones.
+    pub const HIGH: Self = Self(100);
+
+    /// The priority of the handlers registered without an explicit priority.
+    pub const DEFAULT: Self = Self(0);
+
+    /// A priority for the handlers that must run after the default ones.
+    pub const LOW: Self = Self(-100);
+
+    /// Create a priority with the given value, higher values being called
+    /// first.
+    pub const fn new(value: i32) -> Self {
+        Self(value)
+    }
+
+    /// The value of this priority.
+    pub const fn value(self) -> i32 {
+        self.0
+    }
+}
+
+/// How the event handlers with the same [`EventHandlerPriority`] are called for
+/// an event.
+///
+/// It can be set for a priority with [`Client::set_event_handler_execution`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EventHandlerExecution {
+    /// The handlers are called concurrently.
+    #[default]
+    Concurrent,
+
+    /// The handlers are called one after the other, in the order they were
+    /// registered in.
+    Sequential,
+}
+
+/// An event handler that panicked.
+///
+/// The panic doesn't interrupt the sync: it is reported to the hook set with
+/// [`Client::set_event_handler_panic_hook`], and the other handlers are called
+/// normally.
+#[derive(Clone, Debug)]
+pub struct EventHandlerPanic {
+    /// The handle of the event handler that panicked.
+    pub handle: EventHandlerHandle,
+
+    /// The type of the event that was being handled.
+    pub event_type: String,
+
+    /// The message of the panic, if it could be extracted from its payload.
+    pub message: Option<String>,
+}
+
+impl EventHandlerPanic {
+    fn new(handle: EventHandlerHandle, event_type: &str, payload: Box<dyn Any + Send>) -> Self {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| (*message).to_owned())
+            .or_else(|| payload.downcast_ref::<String>().cloned());
+
+        Self { handle, event_type: event_type.to_owned(), message }
+    }
 }
 
 /// Handle to remove a registered event handler by passing it to
@@ -301,6 +447,32 @@ impl<E: fmt::Debug + fmt::Display + 'static> EventHandlerResult for Result<(), E
     }
 }
 
+/// A to-device event that wasn't handled by any event handler.
+///
+/// Such events are passed to the handler registered with
+/// [`Client::set_unhandled_to_device_handler`].
+#[derive(Clone, Debug)]
+pub enum UnhandledToDeviceEvent {
+    /// An encrypted event that couldn't be decrypted.
+    UnableToDecrypt {
+        /// The encrypted event.
+        encrypted_event: Raw<AnyToDeviceEvent>,
+
+        /// Why the event couldn't be decrypted.
+        utd_info: ToDeviceUnableToDecryptInfo,
+    },
+
+    /// A decrypted or unencrypted event, whose type isn't handled by any event
+    /// handler.
+    Unknown {
+        /// The decrypted or unencrypted event.
+        raw: Raw<AnyToDeviceEvent>,
+
+        /// The encryption info of the event, if it was encrypted.
+        encryption_info: Option<EncryptionInfo>,
+    },
+}
+
 #[derive(Deserialize)]
 struct UnsignedDetails {
     redacted_because: Option<serde::de::IgnoredAny>,
@@ -313,6 +485,19 @@ impl Client {
         handler: H,
         room_id: Option<OwnedRoomId>,
     ) -> EventHandlerHandle
+    where
+        Ev: SyncEvent + DeserializeOwned + SendOutsideWasm + 'static,
+        H: EventHandler<Ev, Ctx>,
+    {
+        self.add_prioritized_event_handler_impl(handler, room_id, EventHandlerPriority::DEFAULT)
+    }
+
+    pub(crate) fn add_prioritized_event_handler_impl<Ev, Ctx, H>(
+        &self,
+        handler: H,
+        room_id: Option<OwnedRoomId>,
+        priority: EventHandlerPriority,
+    ) -> EventHandlerHandle
     where
         Ev: SyncEvent + DeserializeOwned + SendOutsideWasm + 'static,
         H: EventHandler<Ev, Ctx>,
@@ -353,7 +538,7 @@ impl Client {
         });
         let handle = EventHandlerHandle { ev_kind: Ev::KIND, ev_type, room_id, handler_id };
 
-        self.inner.event_handlers.add_handler(handle.clone(), handler_fn);
+        self.inner.event_handlers.add_handler(handle.clone(), priority, handler_fn);
 
         handle
     }
@@ -396,20 +581,53 @@ impl Client {
                 other => (&other.to_raw(), None),
             };
             let event_type = raw_event.deserialize_as_unchecked::<ExtractType<'_>>()?.event_type;
-            self.call_event_handlers(
-                None,
-                raw_event.json(),
-                HandlerKind::ToDevice,
-                &event_type,
-                encryption_info,
-                &[],
-            )
-            .await;
+            let handled = self
+                .call_event_handlers(
+                    None,
+                    raw_event.json(),
+                    HandlerKind::ToDevice,
+                    &event_type,
+                    encryption_info,
+                    &[],
+                )
+                .await;
+
+            let unhandled = match processed_to_device {
+                ProcessedToDeviceEvent::UnableToDecrypt { encrypted_event, utd_info } => {
+                    Some(UnhandledToDeviceEvent::UnableToDecrypt {
+                        encrypted_event: encrypted_event.clone(),
+                        utd_info: utd_info.clone(),
+                    })
+                }
+                ProcessedToDeviceEvent::Decrypted { .. } | ProcessedToDeviceEvent::PlainText(_)
+                    if !handled =>
+                {
+                    Some(UnhandledToDeviceEvent::Unknown {
+                        raw: raw_event.clone(),
+                        encryption_info: encryption_info.cloned(),
+                    })
+                }
+                _ => None,
+            };
+
+            if let Some(event) = unhandled {
+                self.call_unhandled_to_device_handler(event).await;
+            }
         }
 
         Ok(())
     }
 
+    async fn call_unhandled_to_device_handler(&self, event: UnhandledToDeviceEvent) {
+        let handler_fn =
+            self.inner.event_handlers.unhandled_to_device_handler.read().unwrap().clone();
+
+        // Call the handler with the lock no longer being held.
+        if let Some(handler_fn) = handler_fn {
+            handler_fn(event).await;
+        }
+    }
+
     pub(crate) async fn handle_sync_state_events(
         &self,
         room: Option<&Room>,
@@ -504,6 +722,7 @@ impl Client {
         Ok(())
     }
 
+    /// Returns whether at least one event handler was called.
     #[instrument(skip_all, fields(?event_kind, ?event_type, room_id))]
     async fn call_event_handlers(
         &self,
@@ -513,40 +732,91 @@ impl Client {
         event_type: &str,
         encryption_info: Option<&EncryptionInfo>,
         push_actions: &[Action],
-    ) {
+    ) -> bool {
         let room_id = room.map(|r| r.room_id());
         if let Some(room_id) = room_id {
             tracing::Span::current().record("room_id", debug(room_id));
         }
 
-        // Construct event handler futures
-        let mut futures: FuturesUnordered<_> = self
+        // Collect the event handlers, with the `self.event_handlers.handlers` lock
+        // no longer being held when they are called.
+        let mut handlers: Vec<_> = self
             .inner
             .event_handlers
             .handlers
             .read()
             .unwrap()
             .get_handlers(event_kind, event_type, room_id)
-            .map(|(handle, handler_fn)| {
+            .map(|(handle, priority, handler_fn)| (handle, priority, handler_fn.clone()))
+            .collect();
+
+        if handlers.is_empty() {
+            return false;
+        }
+
+        debug!(amount = handlers.len(), "Calling event handlers");
+
+        // Higher priorities first, then in the order the handlers were registered in.
+        handlers.sort_by_key(|(handle, priority, _)| (Reverse(*priority), handle.handler_id));
+
+        for class in handlers.chunk_by(|(_, a, _), (_, b, _)| a == b) {
+            let priority = class[0].1;
+
+            let handler_futures = class.iter().map(|(handle, _, handler_fn)| {
                 let data = EventHandlerData {
                     client: self.clone(),
                     room: room.cloned(),
                     raw,
                     encryption_info,
                     push_actions,
-                    handle,
+                    handle: handle.clone(),
                 };
 
-                (handler_fn)(data)
-            })
-            .collect();
+                self.call_event_handler(handler_fn, data, event_type)
+            });
+
+            match self.inner.event_handlers.execution(priority) {
+                EventHandlerExecution::Concurrent => {
+                    let mut futures: FuturesUnordered<_> = handler_futures.collect();
+                    while let Some(()) = futures.next().await {}
+                }
+                EventHandlerExecution::Sequential => {
+                    // The futures are lazy, so each handler is only called once the previous
+                    // one has completed.
+                    for future in handler_futures {
+                        future.await;
+                    }
+                }
+            }
+        }
+
+        true
+    }
 
-        if !futures.is_empty() {
-            debug!(amount = futures.len(), "Calling event handlers");
+    /// Call a single event handler, reporting its panics instead of
+    /// propagating them.
+    async fn call_event_handler(
+        &self,
+        handler_fn: &EventHandlerFn,
+        data: EventHandlerData<'_>,
+        event_type: &str,
+    ) {
+        let handle = data.handle.clone();
 
-            // Run the event handler futures with the `self.event_handlers.handlers`
-            // lock no longer being held.
-            while let Some(()) = futures.next().await {}
+        // The handler can panic when it's called, or when its future is polled.
+        let result = match panic::catch_unwind(AssertUnwindSafe(|| handler_fn(data))) {
+            Ok(future) => AssertUnwindSafe(future).catch_unwind().await,
+            Err(payload) => Err(payload),
+        };
+
+        if let Err(payload) = result {
+            let panic = EventHandlerPanic::new(handle, event_type, payload);
+            error!(handle = ?panic.handle, message = ?panic.message, "Event handler panicked");
+
+            let hook = self.inner.event_handlers.panic_hook.read().unwrap().clone();
+            if let Some(hook) = hook {
+                hook(panic);
+            }
         }
     }
 }
@@ -601,10 +871,81 @@ impl_event_handler!(A, B, C, D, E, F);
 impl_event_handler!(A, B, C, D, E, F, G);
 impl_event_handler!(A, B, C, D, E, F, G, H);
 
+/// The rooms whose events are observed by [`Client::observe_room_events`].
+#[derive(Clone)]
+pub enum RoomEventFilter {
+    /// Only observe the events of the rooms with the given IDs.
+    Rooms(BTreeSet<OwnedRoomId>),
+
+    /// Only observe the events of the rooms whose info matches the predicate,
+    /// at the time the event is received.
+    Predicate(Arc<RoomInfoPredicate>),
+}
+
+impl RoomEventFilter {
+    /// Only observe the events of the rooms whose info matches the given
+    /// predicate, e.g. the rooms the current user moderates.
+    pub fn predicate(
+        predicate: impl Fn(&RoomInfo) -> bool + SendOutsideWasm + SyncOutsideWasm + 'static,
+    ) -> Self {
+        Self::Predicate(Arc::new(predicate))
+    }
+
+    /// Returns the ID of the room, if this filter matches a single room.
+    pub(crate) fn as_single_room(&self) -> Option<&RoomId> {
+        match self {
+            Self::Rooms(room_ids) if room_ids.len() == 1 => room_ids.first().map(|id| &**id),
+            _ => None,
+        }
+    }
+
+    /// Whether the events of the given room must be observed.
+    pub(crate) fn matches(&self, room: &Room) -> bool {
+        match self {
+            Self::Rooms(room_ids) => room_ids.contains(room.room_id()),
+            Self::Predicate(predicate) => predicate(&room.clone_info()),
+        }
+    }
+}
+
+impl From<&RoomId> for RoomEventFilter {
+    fn from(room_id: &RoomId) -> Self {
+        Self::Rooms(BTreeSet::from([room_id.to_owned()]))
+    }
+}
+
+impl From<OwnedRoomId> for RoomEventFilter {
+    fn from(room_id: OwnedRoomId) -> Self {
+        Self::Rooms(BTreeSet::from([room_id]))
+    }
+}
+
+impl From<BTreeSet<OwnedRoomId>> for RoomEventFilter {
+    fn from(room_ids: BTreeSet<OwnedRoomId>) -> Self {
+        Self::Rooms(room_ids)
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+impl fmt::Debug for RoomEventFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rooms(room_ids) => f.debug_tuple("Rooms").field(room_ids).finish(),
+            Self::Predicate(_) => f.write_str("Predicate(..)"),
+        }
+    }
+}
+
+/// The number of events an [`ObservableEventHandler`] buffers for each
+/// [`BufferedEventHandlerSubscriber`], before dropping the oldest ones.
+pub(crate) const OBSERVED_EVENTS_BUFFER_SIZE: usize = 128;
+
 /// An observer of events (may be tailored to a room).
 ///
-/// Only the most recent value can be observed. Subscribers are notified when a
-/// new value is sent, but there is no guarantee that they will see all values.
+/// With [`Self::subscribe`], only the most recent value can be observed.
+/// Subscribers are notified when a new value is sent, but there is no guarantee
+/// that they will see all values. Use [`Self::subscribe_buffered`] to receive
+/// all of them.
 ///
 /// To create such observer, use [`Client::observe_events`] or
 /// [`Client::observe_room_events`].
@@ -616,6 +957,10 @@ pub struct ObservableEventHandler<T> {
     /// [`EventHandler`].
     shared_observable: SharedObservable<Option<T>>,
 
+    /// The sender of all the observed events, for the
+    /// [`BufferedEventHandlerSubscriber`]s.
+    sender: broadcast::Sender<T>,
+
     /// This type owns the [`EventHandlerDropGuard`]. As soon as this type goes
     /// out of scope, the event handler is unregistered/removed.
     ///
@@ -628,9 +973,10 @@ pub struct ObservableEventHandler<T> {
 impl<T> ObservableEventHandler<T> {
     pub(crate) fn new(
         shared_observable: SharedObservable<Option<T>>,
+        sender: broadcast::Sender<T>,
         event_handler_guard: EventHandlerDropGuard,
     ) -> Self {
-        Self { shared_observable, event_handler_guard: Arc::new(event_handler_guard) }
+        Self { shared_observable, sender, event_handler_guard: Arc::new(event_handler_guard) }
     }
 
     /// Subscribe to this observer.
@@ -645,6 +991,101 @@ impl<T> ObservableEventHandler<T> {
             Arc::downgrade(&self.event_handler_guard),
         )
     }
+
+    /// Subscribe to all the events of this observer.
+    ///
+    /// Unlike [`Self::subscribe`], the returned
+    /// [`BufferedEventHandlerSubscriber`] receives all the events observed
+    /// after it's been created. If it's not polled fast enough, up to 128
+    /// events are buffered: the oldest events are dropped after that, and
+    /// counted in
+    /// [`BufferedEventHandlerSubscriber::dropped_count`].
+    ///
+    /// The stream is closed once this observer is dropped, and the buffered
+    /// events have been received.
+    pub fn subscribe_buffered(&self) -> BufferedEventHandlerSubscriber<T>
+    where
+        T: Clone + Send + 'static,
+    {
+        BufferedEventHandlerSubscriber {
+            stream: BroadcastStream::new(self.sender.subscribe()),
+            dropped_count: 0,
+        }
+    }
+}
+
+pin_project! {
+    /// A subscriber receiving all the events of an [`ObservableEventHandler`].
+    ///
+    /// To create such subscriber, use
+    /// [`ObservableEventHandler::subscribe_buffered`].
+    pub struct BufferedEventHandlerSubscriber<T> {
+        #[pin]
+        stream: BroadcastStream<T>,
+
+        // The number of events that have been dropped, because this subscriber
+        // wasn't polled fast enough.
+        dropped_count: u64,
+    }
+}
+
+impl<T> BufferedEventHandlerSubscriber<T> {
+    /// The number of events that have been dropped so far, because this
+    /// subscriber wasn't polled fast enough.
+    pub fn dropped_count(&self) -> u64 {
+        self.dropped_count
+    }
+
+    /// Merge this subscriber with the one of another observer, e.g. of another
+    /// event type, into a single stream of events, in the order they're
+    /// received.
+    ///
+    /// Chain this method to merge more than two subscribers.
+    pub fn merge<U>(
+        self,
+        other: BufferedEventHandlerSubscriber<U>,
+    ) -> impl Stream<Item = Either<T, U>>
+    where
+        T: Clone + Send + 'static,
+        U: Clone + Send + 'static,
+    {
+        futures_util::stream::select(self.map(Either::Left), other.map(Either::Right))
+    }
+}
+
+impl<T> fmt::Debug for BufferedEventHandlerSubscriber<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BufferedEventHandlerSubscriber")
+            .field("dropped_count", &self.dropped_count)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> Stream for BufferedEventHandlerSubscriber<T>
+where
+    T: Clone + Send + 'static,
+{
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+
+        loop {
+            match this.stream.as_mut().poll_next(context) {
+                Poll::Ready(Some(Ok(value))) => return Poll::Ready(Some(value)),
+
+                // The buffer was full: the oldest events have been dropped. Count them, and
+                // return the oldest event that's still buffered.
+                Poll::Ready(Some(Err(BroadcastStreamRecvError::Lagged(count)))) => {
+                    warn!(count, "observed events have been dropped, the subscriber is too slow");
+                    *this.dropped_count += count;
+                }
+
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
 }
 
 pin_project! {
@@ -738,11 +1179,12 @@ mod tests {
         event_factory::{EventFactory, PreviousMembership},
         InvitedRoomBuilder, JoinedRoomBuilder, DEFAULT_TEST_ROOM_ID,
     };
-    use serde::Serialize;
+    use serde::{Deserialize, Serialize};
     use stream_assert::{assert_closed, assert_pending, assert_ready};
     #[cfg(target_family = "wasm")]
     wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
     use std::{
+        collections::BTreeSet,
         future,
         sync::{
             atomic::{AtomicU8, Ordering::SeqCst},
@@ -750,29 +1192,40 @@ mod tests {
         },
     };
 
+    use assert_matches::assert_matches;
     use assert_matches2::assert_let;
-    use matrix_sdk_common::{deserialized_responses::EncryptionInfo, locks::Mutex};
+    use matrix_sdk_common::{
+        deserialized_responses::{
+            AlgorithmInfo, EncryptionInfo, ToDeviceUnableToDecryptReason, VerificationState,
+        },
+        locks::Mutex,
+    };
     use matrix_sdk_test::{StateTestEvent, StrippedStateTestEvent, SyncResponseBuilder};
     use once_cell::sync::Lazy;
     use ruma::{
-        event_id,
+        device_id, event_id,
         events::{
             macros::EventContent,
             room::{
                 member::{MembershipState, OriginalSyncRoomMemberEvent, StrippedRoomMemberEvent},
-                name::OriginalSyncRoomNameEvent,
+                name::{OriginalSyncRoomNameEvent, RoomNameEventContent},
                 power_levels::OriginalSyncRoomPowerLevelsEvent,
             },
             secret_storage::key::SecretStorageKeyEvent,
             typing::SyncTypingEvent,
-            AnySyncStateEvent, AnySyncTimelineEvent, AnyToDeviceEvent,
+            AnySyncStateEvent, AnySyncTimelineEvent, AnyToDeviceEvent, ToDeviceEvent,
         },
-        room_id,
+        owned_device_id, owned_user_id, room_id,
         serde::Raw,
         user_id,
     };
     use serde_json::json;
 
+    use super::{
+        Either, EventHandlerExecution, EventHandlerPriority, ProcessedToDeviceEvent,
+        RoomEventFilter, ToDeviceUnableToDecryptInfo, UnhandledToDeviceEvent,
+        OBSERVED_EVENTS_BUFFER_SIZE,
+    };
     use crate::{
         event_handler::Ctx,
         test_utils::{logged_in_client, no_retry_test_client},
@@ -921,6 +1374,302 @@ mod tests {
         Ok(())
     }
 
+    #[async_test]
+    async fn test_add_typed_to_device_handler() -> crate::Result<()> {
+        #[derive(Clone, Debug, Deserialize, Serialize, EventContent)]
+        #[ruma_event(type = "io.element.call.encryption_keys", kind = ToDevice)]
+        struct CallEncryptionKeysEventContent {
+            call_id: String,
+        }
+
+        let client = logged_in_client(None).await;
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        client.add_to_device_handler({
+            let received = received.clone();
+            move |ev: ToDeviceEvent<CallEncryptionKeysEventContent>,
+                  encryption_info: Option<EncryptionInfo>| {
+                received.lock().push((ev.content.call_id, encryption_info));
+                future::ready(())
+            }
+        });
+
+        let unhandled = Arc::new(Mutex::new(Vec::new()));
+        client.set_unhandled_to_device_handler({
+            let unhandled = unhandled.clone();
+            move |event| {
+                unhandled.lock().push(event);
+                future::ready(())
+            }
+        });
+
+        let to_device_event = |event_type: &str, call_id: &str| {
+            Raw::new(&json!({
+                "sender": "@alice:example.com",
+                "type": event_type,
+                "content": {
+                    "call_id": call_id,
+                },
+            }))
+            .unwrap()
+            .cast_unchecked()
+        };
+        let alice_encryption_info = EncryptionInfo {
+            sender: owned_user_id!("@alice:example.com"),
+            sender_device: Some(owned_device_id!("ALICEDEVICE")),
+            algorithm_info: AlgorithmInfo::OlmV1Curve25519AesSha2 {
+                curve25519_public_key_base64: "curve25519_key".to_owned(),
+                session_id: None,
+                message_index: None,
+            },
+            verification_state: VerificationState::Verified,
+        };
+
+        client
+            .handle_sync_to_device_events(&[
+                ProcessedToDeviceEvent::Decrypted {
+                    raw: to_device_event("io.element.call.encryption_keys", "encrypted"),
+                    encryption_info: alice_encryption_info.clone(),
+                },
+                ProcessedToDeviceEvent::PlainText(to_device_event(
+                    "io.element.call.encryption_keys",
+                    "plain",
+                )),
+                ProcessedToDeviceEvent::PlainText(to_device_event("m.custom.to.device", "other")),
+                ProcessedToDeviceEvent::UnableToDecrypt {
+                    encrypted_event: to_device_event("m.room.encrypted", "utd"),
+                    utd_info: ToDeviceUnableToDecryptInfo {
+                        reason: ToDeviceUnableToDecryptReason::DecryptionFailure,
+                    },
+                },
+            ])
+            .await?;
+
+        // The handler was called exactly once for each event of its type, with the
+        // encryption info of the decrypted event.
+        let received = received.lock();
+        assert_eq!(received.len(), 2);
+
+        let (call_id, encryption_info) = &received[0];
+        assert_eq!(call_id, "encrypted");
+        assert_let!(Some(encryption_info) = encryption_info);
+        assert_eq!(encryption_info.sender, user_id!("@alice:example.com"));
+        assert_eq!(encryption_info.sender_device.as_deref(), Some(device_id!("ALICEDEVICE")));
+        assert_matches!(encryption_info.verification_state, VerificationState::Verified);
+
+        let (call_id, encryption_info) = &received[1];
+        assert_eq!(call_id, "plain");
+        assert!(encryption_info.is_none());
+
+        // The other events were passed to the handler of unhandled events.
+        let unhandled = unhandled.lock();
+        assert_eq!(unhandled.len(), 2);
+
+        assert_let!(UnhandledToDeviceEvent::Unknown { raw, encryption_info } = &unhandled[0]);
+        assert_eq!(raw.get_field::<String>("type").unwrap().as_deref(), Some("m.custom.to.device"));
+        assert!(encryption_info.is_none());
+
+        assert_let!(UnhandledToDeviceEvent::UnableToDecrypt { utd_info, .. } = &unhandled[1]);
+        assert_matches!(utd_info.reason, ToDeviceUnableToDecryptReason::DecryptionFailure);
+
+        Ok(())
+    }
+
+    #[async_test]
+    async fn test_observe_to_device_events() -> crate::Result<()> {
+        #[derive(Clone, Debug, Deserialize, Serialize, EventContent)]
+        #[ruma_event(type = "io.element.call.encryption_keys", kind = ToDevice)]
+        struct CallEncryptionKeysEventContent {
+            call_id: String,
+        }
+
+        let client = logged_in_client(None).await;
+
+        let observable = client.observe_to_device_events::<CallEncryptionKeysEventContent>();
+        let mut subscriber = observable.subscribe_buffered();
+        assert_pending!(subscriber);
+
+        let response = SyncResponseBuilder::default()
+            .add_to_device_event(json!({
+                "sender": "@alice:example.com",
+                "type": "io.element.call.encryption_keys",
+                "content": {
+                    "call_id": "call",
+                },
+            }))
+            .build_sync_response();
+        client.process_sync(response).await?;
+
+        let (event, encryption_info) = assert_ready!(subscriber);
+        assert_eq!(event.content.call_id, "call");
+        assert!(encryption_info.is_none());
+        assert_pending!(subscriber);
+
+        Ok(())
+    }
+
+    fn room_name_event(name: &str) -> StateTestEvent {
+        StateTestEvent::Custom(json!({
+            "content": {
+                "name": name,
+            },
+            "event_id": "$ev0",
+            "origin_server_ts": 1,
+            "sender": "@mnt_io:matrix.org",
+            "state_key": "",
+            "type": "m.room.name",
+        }))
+    }
+
+    #[async_test]
+    async fn test_event_handler_priorities() -> crate::Result<()> {
+        let client = logged_in_client(None).await;
+        let room_id = room_id!("!r0.matrix.org");
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+
+        client.add_event_handler_with_priority(
+            {
+                let calls = calls.clone();
+                move |_ev: OriginalSyncRoomNameEvent| {
+                    calls.lock().push("low");
+                    future::ready(())
+                }
+            },
+            EventHandlerPriority::LOW,
+        );
+        client.add_event_handler_with_priority(
+            {
+                let calls = calls.clone();
+                move |_ev: OriginalSyncRoomNameEvent, room: Room| {
+                    let calls = calls.clone();
+                    async move {
+                        // The state of the room has been updated before the handlers are called.
+                        assert_eq!(room.name().as_deref(), Some("Name 0"));
+                        let name_event =
+                            room.get_state_event_static::<RoomNameEventContent>().await.unwrap();
+                        assert!(name_event.is_some());
+
+                        calls.lock().push("high");
+                    }
+                }
+            },
+            EventHandlerPriority::HIGH,
+        );
+        client.add_event_handler({
+            let calls = calls.clone();
+            move |_ev: OriginalSyncRoomNameEvent| {
+                calls.lock().push("default");
+                future::ready(())
+            }
+        });
+
+        let response = SyncResponseBuilder::new()
+            .add_joined_room(
+                JoinedRoomBuilder::new(room_id).add_state_event(room_name_event("Name 0")),
+            )
+            .build_sync_response();
+        client.process_sync(response).await?;
+
+        // The handlers were called in priority order, not in registration order.
+        assert_eq!(*calls.lock(), ["high", "default", "low"]);
+
+        Ok(())
+    }
+
+    #[async_test]
+    async fn test_sequential_event_handlers() -> crate::Result<()> {
+        let client = logged_in_client(None).await;
+        let room_id = room_id!("!r0.matrix.org");
+
+        client.set_event_handler_execution(
+            EventHandlerPriority::DEFAULT,
+            EventHandlerExecution::Sequential,
+        );
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+
+        client.add_event_handler({
+            let calls = calls.clone();
+            move |_ev: OriginalSyncRoomNameEvent, room: Room| {
+                let calls = calls.clone();
+                calls.lock().push("first start");
+
+                async move {
+                    room.get_state_event_static::<RoomNameEventContent>().await.unwrap();
+                    calls.lock().push("first end");
+                }
+            }
+        });
+        client.add_event_handler({
+            let calls = calls.clone();
+            move |_ev: OriginalSyncRoomNameEvent| {
+                calls.lock().push("second start");
+                future::ready(())
+            }
+        });
+
+        let response = SyncResponseBuilder::new()
+            .add_joined_room(
+                JoinedRoomBuilder::new(room_id).add_state_event(room_name_event("Name 0")),
+            )
+            .build_sync_response();
+        client.process_sync(response).await?;
+
+        // The second handler was only called once the first one completed.
+        assert_eq!(*calls.lock(), ["first start", "first end", "second start"]);
+
+        Ok(())
+    }
+
+    #[async_test]
+    #[allow(dependency_on_unit_never_type_fallback)]
+    async fn test_event_handler_panic_is_reported() -> crate::Result<()> {
+        let client = logged_in_client(None).await;
+        let room_id = room_id!("!r0.matrix.org");
+
+        let panics = Arc::new(Mutex::new(Vec::new()));
+        client.set_event_handler_panic_hook({
+            let panics = panics.clone();
+            move |panic| panics.lock().push(panic)
+        });
+
+        let panicking_handle = client.add_event_handler(|_ev: OriginalSyncRoomNameEvent| async {
+            panic!("handler failure");
+        });
+
+        let called = Arc::new(AtomicU8::new(0));
+        client.add_event_handler_with_priority(
+            {
+                let called = called.clone();
+                move |_ev: OriginalSyncRoomNameEvent| {
+                    called.fetch_add(1, SeqCst);
+                    future::ready(())
+                }
+            },
+            EventHandlerPriority::LOW,
+        );
+
+        let response = SyncResponseBuilder::new()
+            .add_joined_room(
+                JoinedRoomBuilder::new(room_id).add_state_event(room_name_event("Name 0")),
+            )
+            .build_sync_response();
+        client.process_sync(response).await?;
+
+        // The panic was reported, and didn't prevent the other handler from being
+        // called.
+        assert_eq!(called.load(SeqCst), 1);
+
+        let panics = panics.lock();
+        assert_eq!(panics.len(), 1);
+        assert_eq!(panics[0].handle.handler_id, panicking_handle.handler_id);
+        assert_eq!(panics[0].event_type, "m.room.name");
+        assert_eq!(panics[0].message.as_deref(), Some("handler failure"));
+
+        Ok(())
+    }
+
     #[async_test]
     #[allow(dependency_on_unit_never_type_fallback)]
     async fn test_add_room_event_handler() -> crate::Result<()> {
@@ -1427,4 +2176,168 @@ mod tests {
 
         Ok(())
     }
+
+    fn member_event(room_index: usize, event_index: usize) -> StateTestEvent {
+        StateTestEvent::Custom(json!({
+            "content": {
+                "membership": "join",
+            },
+            "event_id": format!("$r{room_index}ev{event_index}"),
+            "origin_server_ts": event_index,
+            "sender": format!("@user{event_index}:matrix.org"),
+            "state_key": format!("@user{event_index}:matrix.org"),
+            "type": "m.room.member",
+        }))
+    }
+
+    #[async_test]
+    async fn test_observe_room_events_with_filter() -> crate::Result<()> {
+        let client = logged_in_client(None).await;
+
+        let room_id_0 = room_id!("!r0.matrix.org");
+        let room_id_1 = room_id!("!r1.matrix.org");
+
+        let observable_by_id =
+            client.observe_room_events::<OriginalSyncRoomMemberEvent, Room>(BTreeSet::from([
+                room_id_0.to_owned(),
+            ]));
+        let observable_by_predicate = client
+            .observe_room_events::<OriginalSyncRoomMemberEvent, Room>(RoomEventFilter::predicate(
+                move |info| info.room_id() == room_id_0,
+            ));
+
+        let mut subscriber_by_id = observable_by_id.subscribe_buffered();
+        let mut subscriber_by_predicate = observable_by_predicate.subscribe_buffered();
+
+        assert_pending!(subscriber_by_id);
+        assert_pending!(subscriber_by_predicate);
+
+        // Several events are received in both rooms, in the same sync.
+        let mut response_builder = SyncResponseBuilder::new();
+        let response = response_builder
+            .add_joined_room(
+                JoinedRoomBuilder::new(room_id_0)
+                    .add_state_event(member_event(0, 0))
+                    .add_state_event(member_event(0, 1)),
+            )
+            .add_joined_room(
+                JoinedRoomBuilder::new(room_id_1)
+                    .add_state_event(member_event(1, 0))
+                    .add_state_event(member_event(1, 1)),
+            )
+            .build_sync_response();
+        client.process_sync(response).await?;
+
+        // All the events of the first room are received, and only them.
+        for subscriber in [&mut subscriber_by_id, &mut subscriber_by_predicate] {
+            let (member, room) = assert_ready!(subscriber);
+            assert_eq!(member.event_id.as_str(), "$r0ev0");
+            assert_eq!(room.room_id(), room_id_0);
+
+            let (member, room) = assert_ready!(subscriber);
+            assert_eq!(member.event_id.as_str(), "$r0ev1");
+            assert_eq!(room.room_id(), room_id_0);
+
+            assert_pending!(subscriber);
+            assert_eq!(subscriber.dropped_count(), 0);
+        }
+
+        drop(observable_by_id);
+        assert_closed!(subscriber_by_id);
+
+        drop(observable_by_predicate);
+        assert_closed!(subscriber_by_predicate);
+
+        Ok(())
+    }
+
+    #[async_test]
+    async fn test_observe_room_events_buffered_drops_oldest() -> crate::Result<()> {
+        let client = logged_in_client(None).await;
+
+        let room_id = room_id!("!r0.matrix.org");
+
+        let observable = client.observe_room_events::<OriginalSyncRoomMemberEvent, ()>(room_id);
+        let mut subscriber = observable.subscribe_buffered();
+
+        let event_count = OBSERVED_EVENTS_BUFFER_SIZE + 2;
+        let mut room_builder = JoinedRoomBuilder::new(room_id);
+        for i in 0..event_count {
+            room_builder = room_builder.add_state_event(member_event(0, i));
+        }
+
+        let response =
+            SyncResponseBuilder::new().add_joined_room(room_builder).build_sync_response();
+        client.process_sync(response).await?;
+
+        // The two oldest events have been dropped.
+        let (member, ()) = assert_ready!(subscriber);
+        assert_eq!(member.event_id.as_str(), "$r0ev2");
+        assert_eq!(subscriber.dropped_count(), 2);
+
+        for _ in 3..event_count {
+            assert_ready!(subscriber);
+        }
+        assert_pending!(subscriber);
+
+        Ok(())
+    }
+
+    #[async_test]
+    async fn test_merge_buffered_subscribers() -> crate::Result<()> {
+        let client = logged_in_client(None).await;
+
+        let room_id = room_id!("!r0.matrix.org");
+
+        let names = client.observe_room_events::<OriginalSyncRoomNameEvent, ()>(room_id);
+        let members = client.observe_room_events::<OriginalSyncRoomMemberEvent, ()>(room_id);
+
+        let mut subscriber = names.subscribe_buffered().merge(members.subscribe_buffered());
+        assert_pending!(subscriber);
+
+        let response = SyncResponseBuilder::new()
+            .add_joined_room(
+                JoinedRoomBuilder::new(room_id)
+                    .add_state_event(member_event(0, 0))
+                    .add_state_event(StateTestEvent::Custom(json!({
+                        "content": {
+                            "name": "Name 0"
+                        },
+                        "event_id": "$ev0",
+                        "origin_server_ts": 1,
+                        "sender": "@mnt_io:matrix.org",
+                        "state_key": "",
+                        "type": "m.room.name",
+                    }))),
+            )
+            .build_sync_response();
+        client.process_sync(response).await?;
+
+        // Both events are received, whatever the observer they come from.
+        let mut name_received = false;
+        let mut member_received = false;
+
+        for _ in 0..2 {
+            match assert_ready!(subscriber) {
+                Either::Left((name, ())) => {
+                    assert_eq!(name.event_id.as_str(), "$ev0");
+                    name_received = true;
+                }
+                Either::Right((member, ())) => {
+                    assert_eq!(member.event_id.as_str(), "$r0ev0");
+                    member_received = true;
+                }
+            }
+        }
+
+        assert!(name_received);
+        assert!(member_received);
+        assert_pending!(subscriber);
+
+        drop(names);
+        drop(members);
+        assert_closed!(subscriber);
+
+        Ok(())
+    }
 }
@@ -15,7 +15,10 @@
 use std::{
     fmt::Debug,
     mem,
-    sync::atomic::{AtomicU64, Ordering},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
     time::Duration,
 };
 
@@ -25,7 +28,9 @@ use bytesize::ByteSize;
 use eyeball::SharedObservable;
 use http::header::CONTENT_LENGTH;
 use reqwest::{tls, Certificate};
-use ruma::api::{error::FromHttpResponseError, IncomingResponse, OutgoingRequest};
+use ruma::api::{
+    error::FromHttpResponseError, IncomingResponse, OutgoingRequest, SupportedVersions,
+};
 use tracing::{debug, info, warn};
 
 use super::{response_to_http_response, HttpClient, TransmissionProgress, DEFAULT_REQUEST_TIMEOUT};
@@ -35,6 +40,51 @@ use crate::{
 };
 
 impl HttpClient {
+    /// Send the given request, replacing its body with the given streamed
+    /// body.
+    ///
+    /// The request is not retried, since the body can only be read once.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) async fn send_with_body_stream<R>(
+        &self,
+        request: R,
+        body: reqwest::Body,
+        content_length: Option<u64>,
+        config: Option<RequestConfig>,
+        homeserver: String,
+        access_token: Option<&str>,
+        supported_versions: &SupportedVersions,
+    ) -> Result<R::IncomingResponse, HttpError>
+    where
+        R: OutgoingRequest + Debug,
+        HttpError: From<FromHttpResponseError<R::EndpointError>>,
+    {
+        let config = config.unwrap_or(self.request_config);
+
+        let request = self
+            .serialize_request(request, config, homeserver, access_token, supported_versions)
+            .map_err(HttpError::IntoHttp)?;
+
+        let mut request = reqwest::Request::try_from(request.map(|_| body))?;
+
+        // When streaming the request, reqwest / hyper doesn't know how large the body
+        // is, so it doesn't set the content-length header. Set it manually if we know
+        // it, otherwise the body is sent in chunks.
+        if let Some(content_length) = content_length {
+            request.headers_mut().insert(CONTENT_LENGTH, content_length.into());
+        }
+
+        *request.timeout_mut() = config.timeout;
+
+        // will be automatically dropped at the end of this function
+        let _handle = self.concurrent_request_semaphore.acquire().await;
+
+        let response = self.inner.execute(request).await?;
+        let response = response_to_http_response(response).await?;
+
+        R::IncomingResponse::try_from_http_response(response).map_err(HttpError::from)
+    }
+
     pub(super) async fn send_request<R>(
         &self,
         request: http::Request<Bytes>,
@@ -230,19 +280,29 @@ pub(super) async fn send_request(
     use futures_util::stream;
 
     let request = request.clone();
+
+    // The size of the body and the number of bytes reported as sent, if the
+    // progress is tracked.
+    let mut tracked_progress = None;
+
     let request = {
         let mut request = if send_progress.subscriber_count() != 0 {
             let content_length = request.body().len();
             send_progress.update(|p| p.total += content_length);
 
+            let sent = Arc::new(AtomicUsize::new(0));
+            tracked_progress = Some((content_length, sent.clone()));
+
             // Make sure any concurrent futures in the same task get a chance
             // to also add to the progress total before the first chunks are
             // pulled out of the body stream.
             tokio::task::yield_now().await;
 
+            let send_progress = send_progress.clone();
             let mut req = reqwest::Request::try_from(request.map(|body| {
                 let chunks = stream::iter(BytesChunks::new(body, 8192).map(
                     move |chunk| -> Result<_, Infallible> {
+                        sent.fetch_add(chunk.len(), Ordering::SeqCst);
                         send_progress.update(|p| p.current += chunk.len());
                         Ok(chunk)
                     },
@@ -265,6 +325,17 @@ pub(super) async fn send_request(
     };
 
     let response = client.execute(request).await?;
+
+    // The server might respond before the whole body was pulled out of the
+    // stream, report the rest of the body as sent.
+    if let Some((content_length, sent)) = tracked_progress {
+        let remaining = content_length.saturating_sub(sent.load(Ordering::SeqCst));
+
+        if remaining > 0 {
+            send_progress.update(|p| p.current += remaining);
+        }
+    }
+
     Ok(response_to_http_response(response).await?)
 }
 
@@ -0,0 +1,561 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Image packs, i.e. the custom emoticons and stickers of a user or a room.
+//!
+//! Image packs are defined by [MSC2545], which is widely implemented, but not
+//! part of the specification yet. The pack of a user is stored in their
+//! account data and can be managed with [`Account::image_packs()`]; packs can
+//! also be shared in the state of a room, see [`Room::image_pack()`].
+//!
+//! Because clients in the wild don't agree on every detail of the format, the
+//! types of this module are lenient: an image that can't be parsed is
+//! ignored instead of making the whole pack invalid, and the fields that aren't
+//! known are kept when a pack is written back.
+//!
+//! [MSC2545]: https://github.com/matrix-org/matrix-spec-proposals/pull/2545
+//! [`Account::image_packs()`]: crate::Account::image_packs
+
+use std::collections::BTreeMap;
+
+use as_variant::as_variant;
+use matrix_sdk_base::deserialized_responses::{PrivOwnedStr, RawAnySyncOrStrippedState};
+use ruma::{
+    events::{room::ImageInfo, GlobalAccountDataEventType, StateEventType},
+    serde::{PartialEqAsRefStr, Raw, StringEnum},
+    OwnedMxcUri,
+};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize};
+use serde_json::Value as JsonValue;
+
+use crate::{Client, Error, Result, Room};
+
+/// The stable type of the image pack events, in the account data and in the
+/// state of rooms.
+const IMAGE_PACK_EVENT_TYPE: &str = "m.image_pack";
+
+/// The unstable type of the account data event of the image pack of a user.
+const UNSTABLE_USER_IMAGE_PACK_EVENT_TYPE: &str = "im.ponies.user_emotes";
+
+/// The unstable type of the state events of the image packs of a room.
+const UNSTABLE_ROOM_IMAGE_PACK_EVENT_TYPE: &str = "im.ponies.room_emotes";
+
+/// How an image, or the images of a pack, can be used.
+#[derive(Clone, StringEnum, PartialEqAsRefStr, Eq)]
+#[ruma_enum(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum PackUsage {
+    /// The image can be used inline in messages, as a custom emoji.
+    Emoticon,
+
+    /// The image can be sent as a sticker.
+    Sticker,
+
+    #[doc(hidden)]
+    _Custom(PrivOwnedStr),
+}
+
+/// An image pack.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ImagePack {
+    /// The images of the pack, by shortcode.
+    ///
+    /// The shortcodes don't include the surrounding colons. Older packs,
+    /// using an `emoticons` field instead, are supported too.
+    #[serde(default, alias = "emoticons", deserialize_with = "deserialize_images")]
+    pub images: BTreeMap<String, PackImage>,
+
+    /// The metadata of the pack.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_or_default",
+        skip_serializing_if = "PackInfo::is_empty"
+    )]
+    pub pack: PackInfo,
+
+    /// The fields that are unknown to the SDK, kept as is.
+    #[serde(flatten)]
+    other: BTreeMap<String, JsonValue>,
+}
+
+impl ImagePack {
+    /// The images of this pack that can be used as emoticons, with their
+    /// shortcode.
+    pub fn emoticons(&self) -> impl Iterator<Item = (&str, &PackImage)> {
+        self.images_with_usage(PackUsage::Emoticon)
+    }
+
+    /// The images of this pack that can be sent as stickers, with their
+    /// shortcode.
+    pub fn stickers(&self) -> impl Iterator<Item = (&str, &PackImage)> {
+        self.images_with_usage(PackUsage::Sticker)
+    }
+
+    fn images_with_usage(&self, usage: PackUsage) -> impl Iterator<Item = (&str, &PackImage)> {
+        self.images
+            .iter()
+            .filter(move |(_, image)| image.has_usage(&usage, &self.pack))
+            .map(|(shortcode, image)| (shortcode.as_str(), image))
+    }
+
+    /// Add the images of another pack to this pack.
+    ///
+    /// The images whose shortcode is already used in this pack are not added.
+    /// The images of the other pack keep the usage of their pack.
+    ///
+    /// Returns the number of images that were added.
+    pub fn merge(&mut self, other: ImagePack) -> usize {
+        let mut num_added = 0;
+
+        for (shortcode, mut image) in other.images {
+            if self.images.contains_key(&shortcode) {
+                continue;
+            }
+
+            if image.usage.is_empty() {
+                image.usage = other.pack.usage.clone();
+            }
+
+            self.images.insert(shortcode, image);
+            num_added += 1;
+        }
+
+        num_added
+    }
+}
+
+/// The metadata of an [`ImagePack`].
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PackInfo {
+    /// The name of the pack.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_or_default",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub display_name: Option<String>,
+
+    /// The avatar of the pack.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_mxc_uri",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub avatar_url: Option<OwnedMxcUri>,
+
+    /// How the images of the pack can be used, if their usage isn't set.
+    ///
+    /// If it's empty, the images can be used both as emoticons and stickers.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_usage",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub usage: Vec<PackUsage>,
+
+    /// The attribution of the pack.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_or_default",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub attribution: Option<String>,
+
+    /// The fields that are unknown to the SDK, kept as is.
+    #[serde(flatten)]
+    other: BTreeMap<String, JsonValue>,
+}
+
+impl PackInfo {
+    fn is_empty(&self) -> bool {
+        self.display_name.is_none()
+            && self.avatar_url.is_none()
+            && self.usage.is_empty()
+            && self.attribution.is_none()
+            && self.other.is_empty()
+    }
+}
+
+/// An image of an [`ImagePack`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PackImage {
+    /// The MXC URI of the image.
+    pub url: OwnedMxcUri,
+
+    /// A textual representation or description of the image.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_or_default",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub body: Option<String>,
+
+    /// The metadata of the image.
+    ///
+    /// Metadata that can't be parsed is ignored.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_or_default",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub info: Option<ImageInfo>,
+
+    /// How the image can be used.
+    ///
+    /// If it's empty, the usage of the pack applies.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_usage",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub usage: Vec<PackUsage>,
+
+    /// The fields that are unknown to the SDK, kept as is.
+    #[serde(flatten)]
+    other: BTreeMap<String, JsonValue>,
+}
+
+impl PackImage {
+    /// Create a new `PackImage` with the given MXC URI.
+    pub fn new(url: OwnedMxcUri) -> Self {
+        Self { url, body: None, info: None, usage: Vec::new(), other: BTreeMap::new() }
+    }
+
+    /// Whether the image can be used in the given way, as part of the given
+    /// pack.
+    fn has_usage(&self, usage: &PackUsage, pack: &PackInfo) -> bool {
+        let usages = if self.usage.is_empty() { &pack.usage } else { &self.usage };
+        usages.is_empty() || usages.contains(usage)
+    }
+}
+
+/// Deserialize a value, or use the default value if it's invalid.
+fn deserialize_or_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned + Default,
+{
+    let value = JsonValue::deserialize(deserializer)?;
+    Ok(serde_json::from_value(value).unwrap_or_default())
+}
+
+/// Deserialize an optional MXC URI, ignoring it if it's invalid, e.g. empty.
+fn deserialize_mxc_uri<'de, D>(deserializer: D) -> Result<Option<OwnedMxcUri>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let uri: Option<OwnedMxcUri> = deserialize_or_default(deserializer)?;
+    Ok(uri.filter(|uri| uri.is_valid()))
+}
+
+/// Deserialize the usage of an image or a pack, which can be a list or a
+/// single string.
+fn deserialize_usage<'de, D>(deserializer: D) -> Result<Vec<PackUsage>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let usage = match JsonValue::deserialize(deserializer)? {
+        JsonValue::String(usage) => vec![usage.into()],
+        JsonValue::Array(values) => values
+            .into_iter()
+            .filter_map(|value| as_variant!(value, JsonValue::String))
+            .map(Into::into)
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    Ok(usage)
+}
+
+/// Deserialize the images of a pack, ignoring the ones that are invalid.
+///
+/// An image can also be a single MXC URI, and the shortcodes can be surrounded
+/// by colons, like in older packs.
+fn deserialize_images<'de, D>(deserializer: D) -> Result<BTreeMap<String, PackImage>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let JsonValue::Object(values) = JsonValue::deserialize(deserializer)? else {
+        return Ok(BTreeMap::new());
+    };
+
+    let images = values
+        .into_iter()
+        .filter_map(|(shortcode, value)| {
+            let image = match value {
+                JsonValue::String(url) => PackImage::new(url.into()),
+                value => serde_json::from_value::<PackImage>(value).ok()?,
+            };
+
+            let shortcode = shortcode.trim_matches(':');
+            (!shortcode.is_empty() && image.url.is_valid()).then(|| (shortcode.to_owned(), image))
+        })
+        .collect();
+
+    Ok(images)
+}
+
+/// A high-level API to manage the image pack of the client owner, stored in
+/// their account data.
+///
+/// The pack is fetched from the homeserver before every change, so changes
+/// made by other clients are not lost.
+#[derive(Debug, Clone)]
+pub struct ImagePacks {
+    client: Client,
+}
+
+impl ImagePacks {
+    pub(crate) fn new(client: Client) -> Self {
+        Self { client }
+    }
+
+    /// Fetch the image pack of the user from the homeserver.
+    ///
+    /// Returns an empty pack if the user doesn't have one.
+    pub async fn get(&self) -> Result<ImagePack> {
+        Ok(self.fetch().await?.map(|(_, pack)| pack).unwrap_or_default())
+    }
+
+    /// Fetch the image pack of the user, and the type of the account data event
+    /// it's stored in.
+    ///
+    /// The stable event type is preferred over the unstable one.
+    async fn fetch(&self) -> Result<Option<(GlobalAccountDataEventType, ImagePack)>> {
+        let account = self.client.account();
+
+        for event_type in [IMAGE_PACK_EVENT_TYPE, UNSTABLE_USER_IMAGE_PACK_EVENT_TYPE] {
+            let event_type = GlobalAccountDataEventType::from(event_type);
+
+            if let Some(raw) = account.fetch_account_data(event_type.clone()).await? {
+                return Ok(Some((event_type, raw.deserialize_as_unchecked()?)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Update the image pack of the user with the given function.
+    ///
+    /// The pack is written back to the account data event it was read from;
+    /// if the user didn't have a pack yet, it's created with the unstable
+    /// event type, which is the one supported by most clients.
+    ///
+    /// Returns the updated pack.
+    pub async fn update(&self, f: impl FnOnce(&mut ImagePack)) -> Result<ImagePack> {
+        let (event_type, mut pack) = self
+            .fetch()
+            .await?
+            .unwrap_or_else(|| (UNSTABLE_USER_IMAGE_PACK_EVENT_TYPE.into(), ImagePack::default()));
+
+        f(&mut pack);
+        self.write(event_type, &pack).await?;
+
+        Ok(pack)
+    }
+
+    /// Write the image pack of the user to the account data event of the given
+    /// type.
+    async fn write(&self, event_type: GlobalAccountDataEventType, pack: &ImagePack) -> Result<()> {
+        let content = Raw::new(pack)?.cast_unchecked();
+        self.client.account().set_account_data_raw(event_type, content).await?;
+        Ok(())
+    }
+
+    /// Replace the image pack of the user.
+    pub async fn set(&self, pack: ImagePack) -> Result<()> {
+        self.update(|current| *current = pack).await?;
+        Ok(())
+    }
+
+    /// Add an image to the pack of the user, replacing the image with the same
+    /// shortcode, if any.
+    pub async fn add_image(&self, shortcode: impl Into<String>, image: PackImage) -> Result<()> {
+        let shortcode = shortcode.into();
+        self.update(|pack| {
+            pack.images.insert(shortcode, image);
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Remove an image from the pack of the user.
+    ///
+    /// Returns `false` if the pack didn't contain an image with that shortcode,
+    /// in which case the pack isn't written back.
+    pub async fn remove_image(&self, shortcode: &str) -> Result<bool> {
+        let Some((event_type, mut pack)) = self.fetch().await? else {
+            return Ok(false);
+        };
+
+        if pack.images.remove(shortcode).is_none() {
+            return Ok(false);
+        }
+
+        self.write(event_type, &pack).await?;
+
+        Ok(true)
+    }
+
+    /// Import the images of an image pack of a room into the pack of the user.
+    ///
+    /// The images whose shortcode is already used in the pack of the user are
+    /// not imported, see [`ImagePack::merge()`].
+    ///
+    /// Returns the number of images that were imported, or an error if the room
+    /// doesn't have an image pack with the given state key.
+    pub async fn import_room_pack(&self, room: &Room, state_key: &str) -> Result<usize> {
+        let room_pack = room.image_pack(state_key).await?.ok_or(Error::InsufficientData)?;
+
+        let mut num_imported = 0;
+        self.update(|pack| num_imported = pack.merge(room_pack)).await?;
+
+        Ok(num_imported)
+    }
+}
+
+impl Room {
+    /// Get the image pack of this room with the given state key, from the
+    /// local store.
+    ///
+    /// The stable event type is preferred over the unstable one. Returns
+    /// `None` if the room doesn't have such an image pack.
+    pub async fn image_pack(&self, state_key: &str) -> Result<Option<ImagePack>> {
+        for event_type in [IMAGE_PACK_EVENT_TYPE, UNSTABLE_ROOM_IMAGE_PACK_EVENT_TYPE] {
+            let Some(raw) =
+                self.get_state_event(StateEventType::from(event_type), state_key).await?
+            else {
+                continue;
+            };
+
+            let pack: Option<ImagePack> = match raw {
+                RawAnySyncOrStrippedState::Sync(raw) => raw.get_field("content")?,
+                RawAnySyncOrStrippedState::Stripped(raw) => raw.get_field("content")?,
+            };
+
+            if pack.is_some() {
+                return Ok(pack);
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches2::assert_let;
+    use ruma::{mxc_uri, owned_mxc_uri, uint};
+    use serde_json::{from_value, json, to_value};
+
+    use super::{ImagePack, PackImage, PackUsage};
+
+    #[test]
+    fn test_deserialize_lenient_image_pack() {
+        let pack = from_value::<ImagePack>(json!({
+            "images": {
+                "valid": {
+                    "url": "mxc://example.org/valid",
+                    "body": "A valid image",
+                    "info": { "mimetype": "image/png", "w": 128, "h": 128 },
+                    "usage": ["sticker", "unknown_usage"],
+                },
+                ":colons:": {
+                    "url": "mxc://example.org/colons",
+                    "usage": "emoticon",
+                },
+                "invalid_info": {
+                    "url": "mxc://example.org/invalid_info",
+                    "info": { "w": "a lot" },
+                },
+                "url_only": "mxc://example.org/url_only",
+                "missing_url": { "body": "No URL" },
+                "invalid_url": { "url": "" },
+            },
+            "pack": {
+                "display_name": "My pack",
+                "avatar_url": "",
+                "usage": ["emoticon"],
+                "custom_field": true,
+            },
+        }))
+        .unwrap();
+
+        assert_eq!(
+            pack.images.keys().map(String::as_str).collect::<Vec<_>>(),
+            ["colons", "invalid_info", "url_only", "valid"]
+        );
+
+        let valid = &pack.images["valid"];
+        assert_eq!(valid.url, mxc_uri!("mxc://example.org/valid"));
+        assert_eq!(valid.body.as_deref(), Some("A valid image"));
+        assert_let!(Some(info) = &valid.info);
+        assert_eq!(info.width, Some(uint!(128)));
+        assert_eq!(valid.usage, [PackUsage::Sticker, PackUsage::from("unknown_usage")]);
+
+        assert_eq!(pack.images["colons"].usage, [PackUsage::Emoticon]);
+        assert!(pack.images["invalid_info"].info.is_none());
+        assert_eq!(pack.images["url_only"].url, mxc_uri!("mxc://example.org/url_only"));
+
+        assert_eq!(pack.pack.display_name.as_deref(), Some("My pack"));
+        assert!(pack.pack.avatar_url.is_none());
+
+        // The images without usage use the usage of the pack.
+        assert_eq!(pack.stickers().map(|(shortcode, _)| shortcode).collect::<Vec<_>>(), ["valid"]);
+        assert_eq!(
+            pack.emoticons().map(|(shortcode, _)| shortcode).collect::<Vec<_>>(),
+            ["colons", "invalid_info", "url_only"]
+        );
+
+        // The unknown fields are kept.
+        let json = to_value(&pack).unwrap();
+        assert_eq!(json["pack"]["custom_field"], true);
+    }
+
+    #[test]
+    fn test_deserialize_legacy_image_pack() {
+        let pack = from_value::<ImagePack>(json!({
+            "emoticons": {
+                ":wave:": { "url": "mxc://example.org/wave" },
+            },
+        }))
+        .unwrap();
+
+        assert_eq!(pack.images["wave"].url, mxc_uri!("mxc://example.org/wave"));
+
+        // The pack is written back with the current format.
+        let json = to_value(&pack).unwrap();
+        assert_eq!(json, json!({ "images": { "wave": { "url": "mxc://example.org/wave" } } }));
+    }
+
+    #[test]
+    fn test_merge_image_packs() {
+        let mut pack = ImagePack::default();
+        pack.images.insert("wave".to_owned(), PackImage::new(owned_mxc_uri!("mxc://a.b/wave")));
+
+        let mut room_pack = ImagePack::default();
+        room_pack.pack.usage = vec![PackUsage::Sticker];
+        room_pack
+            .images
+            .insert("wave".to_owned(), PackImage::new(owned_mxc_uri!("mxc://c.d/wave")));
+        room_pack.images.insert("cat".to_owned(), PackImage::new(owned_mxc_uri!("mxc://c.d/cat")));
+
+        assert_eq!(pack.merge(room_pack), 1);
+
+        // The existing image is kept.
+        assert_eq!(pack.images["wave"].url, mxc_uri!("mxc://a.b/wave"));
+        // The imported image keeps the usage of its pack.
+        assert_eq!(pack.images["cat"].usage, [PackUsage::Sticker]);
+    }
+}
@@ -0,0 +1,916 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Client-side filtering of the invites, to protect the user against invite
+//! spam.
+//!
+//! The invites are filtered with two policies:
+//!
+//! - the [`InvitePolicy`] of the user, stored in their account data and thus
+//!   shared by all their clients, see [`Account::set_invite_policy`],
+//! - the [`InviteFilterPolicy`] of this client, see
+//!   [`Client::set_invite_filter`].
+//!
+//! See [`Client::filtered_invites`] to get the filtered invites, and
+//! [`Client::evaluate_invite`] to know why an invite is filtered.
+//!
+//! [`Account::set_invite_policy`]: crate::Account::set_invite_policy
+
+use std::collections::BTreeSet;
+
+use matrix_sdk_base::RoomState;
+use matrix_sdk_common::executor::spawn;
+use ruma::{
+    events::{room::member::MembershipState, AnyGlobalAccountDataEvent},
+    serde::Raw,
+    OwnedRoomId, OwnedUserId, RoomId, UserId,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::{Client, Room};
+
+/// The type of the account data event containing the [`InvitePolicy`] of the
+/// user.
+pub(crate) const INVITE_POLICY_EVENT_TYPE: &str = "org.matrix.msc4155.invite_permission_config";
+
+/// The invite policy of the user, stored in their account data, as defined by
+/// [MSC4155].
+///
+/// The rules are globs matching the ID of the inviter, or its server name,
+/// where `*` matches any number of characters and `?` matches a single
+/// character. The rules about users take precedence over the rules about
+/// servers; for the same kind of rules, allowing takes precedence over
+/// ignoring, which takes precedence over blocking.
+///
+/// The invites that are ignored are only hidden, while the ones that are
+/// blocked are also rejected if [`InviteFilterPolicy::reject_on_server`] is
+/// set.
+///
+/// [MSC4155]: https://github.com/matrix-org/matrix-spec-proposals/pull/4155
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InvitePolicy {
+    /// The users whose invites are always allowed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_users: Vec<String>,
+
+    /// The users whose invites are hidden.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ignored_users: Vec<String>,
+
+    /// The users whose invites are blocked.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub blocked_users: Vec<String>,
+
+    /// The servers whose users' invites are allowed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_servers: Vec<String>,
+
+    /// The servers whose users' invites are hidden.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ignored_servers: Vec<String>,
+
+    /// The servers whose users' invites are blocked.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub blocked_servers: Vec<String>,
+
+    /// Block the invites sent by users who don't share a room with the user,
+    /// unless they are allowed by another rule.
+    ///
+    /// This isn't part of MSC4155, so it's only applied by the clients using
+    /// this SDK.
+    #[serde(
+        default,
+        rename = "org.matrix.rust_sdk.only_known_users",
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    pub only_known_users: bool,
+}
+
+impl InvitePolicy {
+    /// Always allow the invites from the users matching the given glob.
+    pub fn allow_user(mut self, glob: impl Into<String>) -> Self {
+        self.allowed_users.push(glob.into());
+        self
+    }
+
+    /// Hide the invites from the users matching the given glob.
+    pub fn ignore_user(mut self, glob: impl Into<String>) -> Self {
+        self.ignored_users.push(glob.into());
+        self
+    }
+
+    /// Block the invites from the users matching the given glob.
+    pub fn block_user(mut self, glob: impl Into<String>) -> Self {
+        self.blocked_users.push(glob.into());
+        self
+    }
+
+    /// Allow the invites from the users of the servers matching the given
+    /// glob.
+    pub fn allow_server(mut self, glob: impl Into<String>) -> Self {
+        self.allowed_servers.push(glob.into());
+        self
+    }
+
+    /// Hide the invites from the users of the servers matching the given glob.
+    pub fn ignore_server(mut self, glob: impl Into<String>) -> Self {
+        self.ignored_servers.push(glob.into());
+        self
+    }
+
+    /// Block the invites from the users of the servers matching the given
+    /// glob.
+    pub fn block_server(mut self, glob: impl Into<String>) -> Self {
+        self.blocked_servers.push(glob.into());
+        self
+    }
+
+    /// Block the invites sent by users who don't share a room with the user,
+    /// unless they are allowed by another rule.
+    pub fn only_known_users(mut self, only_known_users: bool) -> Self {
+        self.only_known_users = only_known_users;
+        self
+    }
+
+    /// Find the rule applying to the given inviter, if any.
+    fn matching_rule(&self, inviter: &UserId) -> Option<InviteDecisionReason> {
+        let find = |globs: &[String], candidate: &str| {
+            globs.iter().find(|glob| glob_matches(glob, candidate)).cloned()
+        };
+
+        let user_rules = [
+            (&self.allowed_users, InviteRuleAction::Allow),
+            (&self.ignored_users, InviteRuleAction::Ignore),
+            (&self.blocked_users, InviteRuleAction::Block),
+        ];
+        for (globs, action) in user_rules {
+            if let Some(rule) = find(globs, inviter.as_str()) {
+                return Some(InviteDecisionReason::UserRule { rule, action });
+            }
+        }
+
+        let server_rules = [
+            (&self.allowed_servers, InviteRuleAction::Allow),
+            (&self.ignored_servers, InviteRuleAction::Ignore),
+            (&self.blocked_servers, InviteRuleAction::Block),
+        ];
+        for (globs, action) in server_rules {
+            if let Some(rule) = find(globs, inviter.server_name().as_str()) {
+                return Some(InviteDecisionReason::ServerRule { rule, action });
+            }
+        }
+
+        None
+    }
+}
+
+/// Whether `candidate` matches the given glob, where `*` matches any number of
+/// characters and `?` matches a single character.
+fn glob_matches(glob: &str, candidate: &str) -> bool {
+    let glob: Vec<char> = glob.chars().collect();
+    let candidate: Vec<char> = candidate.chars().collect();
+
+    let (mut g, mut c) = (0, 0);
+    // The position of the last `*` in the glob, and of the character of the
+    // candidate it's matching against, to backtrack on a mismatch.
+    let mut last_star = None;
+
+    while c < candidate.len() {
+        match glob.get(g) {
+            Some('*') => {
+                last_star = Some((g, c));
+                g += 1;
+            }
+            Some(&ch) if ch == '?' || ch == candidate[c] => {
+                g += 1;
+                c += 1;
+            }
+            _ => {
+                let Some((star_g, star_c)) = last_star else {
+                    return false;
+                };
+
+                // Let the `*` match one more character.
+                last_star = Some((star_g, star_c + 1));
+                g = star_g + 1;
+                c = star_c + 1;
+            }
+        }
+    }
+
+    glob[g..].iter().all(|&ch| ch == '*')
+}
+
+/// The policy used to filter the invites received by the user.
+///
+/// By default, all the invites are shown.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InviteFilterPolicy {
+    /// Hide the invites sent by users who don't share a room with the user,
+    /// and who aren't part of [`Self::known_users`].
+    pub hide_unknown_inviters: bool,
+
+    /// Users whose invites are always shown, e.g. the contacts of the user,
+    /// as known by the application.
+    pub known_users: BTreeSet<OwnedUserId>,
+
+    /// Reject the filtered invites on the server, instead of only hiding them.
+    ///
+    /// The invites ignored by the [`InvitePolicy`] of the user are never
+    /// rejected.
+    pub reject_on_server: bool,
+}
+
+impl InviteFilterPolicy {
+    /// Hide the invites sent by users who don't share a room with the user,
+    /// except for the given known users.
+    pub fn hide_unknown_inviters(
+        mut self,
+        known_users: impl IntoIterator<Item = OwnedUserId>,
+    ) -> Self {
+        self.hide_unknown_inviters = true;
+        self.known_users = known_users.into_iter().collect();
+        self
+    }
+
+    /// Reject the filtered invites on the server, instead of only hiding
+    /// them.
+    pub fn reject_on_server(mut self, reject_on_server: bool) -> Self {
+        self.reject_on_server = reject_on_server;
+        self
+    }
+
+    /// Evaluate an invite sent by `inviter` with this policy and the invite
+    /// policy of the user, given whether the inviter shares a room with the
+    /// user.
+    fn evaluate(
+        &self,
+        invite_policy: &InvitePolicy,
+        inviter: &UserId,
+        shares_room: bool,
+    ) -> InviteEvaluation {
+        if let Some(reason) = invite_policy.matching_rule(inviter) {
+            let classification = match &reason {
+                InviteDecisionReason::UserRule { action: InviteRuleAction::Allow, .. }
+                | InviteDecisionReason::ServerRule { action: InviteRuleAction::Allow, .. } => {
+                    InviteClassification::Shown
+                }
+                _ => InviteClassification::Filtered,
+            };
+
+            return InviteEvaluation { classification, reason };
+        }
+
+        if self.known_users.contains(inviter) {
+            return InviteEvaluation::shown(InviteDecisionReason::KnownUser);
+        }
+
+        if !self.hide_unknown_inviters && !invite_policy.only_known_users {
+            return InviteEvaluation::shown(InviteDecisionReason::NoMatchingRule);
+        }
+
+        if shares_room {
+            InviteEvaluation::shown(InviteDecisionReason::SharedRoom)
+        } else {
+            InviteEvaluation {
+                classification: InviteClassification::Filtered,
+                reason: InviteDecisionReason::UnknownInviter,
+            }
+        }
+    }
+
+    /// Whether the evaluation of an invite from `inviter` depends on the rooms
+    /// shared with the user.
+    fn needs_shared_rooms(&self, invite_policy: &InvitePolicy, inviter: &UserId) -> bool {
+        (self.hide_unknown_inviters || invite_policy.only_known_users)
+            && !self.known_users.contains(inviter)
+            && invite_policy.matching_rule(inviter).is_none()
+    }
+}
+
+/// How an invite is classified by the [`InviteFilterPolicy`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InviteClassification {
+    /// The invite is shown to the user.
+    Shown,
+
+    /// The invite is hidden from the room list and the notifications. It can
+    /// still be accessed with [`Client::filtered_invites`].
+    Filtered,
+}
+
+/// The action of a rule of the [`InvitePolicy`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InviteRuleAction {
+    /// The invite is allowed.
+    Allow,
+
+    /// The invite is hidden, but never rejected.
+    Ignore,
+
+    /// The invite is hidden, and rejected if
+    /// [`InviteFilterPolicy::reject_on_server`] is set.
+    Block,
+}
+
+/// Why an invite is classified the way it is, see [`Client::evaluate_invite`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InviteDecisionReason {
+    /// The room isn't an invite.
+    NotAnInvite,
+
+    /// The inviter couldn't be determined, so the invite is shown.
+    InviterUnavailable,
+
+    /// The inviter matches a rule about users of the [`InvitePolicy`].
+    UserRule {
+        /// The glob that matched the ID of the inviter.
+        rule: String,
+
+        /// The action of the rule.
+        action: InviteRuleAction,
+    },
+
+    /// The server of the inviter matches a rule about servers of the
+    /// [`InvitePolicy`].
+    ServerRule {
+        /// The glob that matched the server name of the inviter.
+        rule: String,
+
+        /// The action of the rule.
+        action: InviteRuleAction,
+    },
+
+    /// The inviter is one of the [`InviteFilterPolicy::known_users`].
+    KnownUser,
+
+    /// The inviter shares a room with the user.
+    SharedRoom,
+
+    /// The inviter doesn't share a room with the user, and the policies only
+    /// allow the invites from known users.
+    UnknownInviter,
+
+    /// No rule applies to the invite, so it's shown.
+    NoMatchingRule,
+}
+
+/// The result of the evaluation of an invite, see [`Client::evaluate_invite`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InviteEvaluation {
+    /// How the invite is classified.
+    pub classification: InviteClassification,
+
+    /// Why the invite is classified this way.
+    pub reason: InviteDecisionReason,
+}
+
+impl InviteEvaluation {
+    fn shown(reason: InviteDecisionReason) -> Self {
+        Self { classification: InviteClassification::Shown, reason }
+    }
+
+    /// Whether the invite can be rejected on the server, if it's filtered.
+    ///
+    /// The invites ignored by the [`InvitePolicy`] are only hidden.
+    fn can_be_rejected(&self) -> bool {
+        !matches!(
+            self.reason,
+            InviteDecisionReason::UserRule { action: InviteRuleAction::Ignore, .. }
+                | InviteDecisionReason::ServerRule { action: InviteRuleAction::Ignore, .. }
+        )
+    }
+}
+
+/// Client-wide state of the invite filter.
+#[derive(Debug, Default)]
+pub(crate) struct InviteFilterState {
+    policy: InviteFilterPolicy,
+
+    /// The invited rooms that have been filtered out.
+    filtered: BTreeSet<OwnedRoomId>,
+}
+
+impl Client {
+    /// Set the policy used to filter the invites received by the user.
+    ///
+    /// The filtered invites aren't surfaced in the room list and in the
+    /// notifications, but remain accessible with
+    /// [`Client::filtered_invites`]. They are only rejected on the server if
+    /// [`InviteFilterPolicy::reject_on_server`] is set.
+    ///
+    /// The current invites are classified again with the new policy.
+    pub async fn set_invite_filter(&self, policy: InviteFilterPolicy) {
+        self.inner.invite_filter.write().unwrap().policy = policy;
+        self.classify_invites_again().await;
+    }
+
+    /// Classify all the current invites again, after one of the policies
+    /// changed.
+    async fn classify_invites_again(&self) {
+        for room in self.invited_rooms() {
+            self.classify_and_remember_invite(&room, true).await;
+        }
+    }
+
+    /// Classify all the current invites again if the given global account
+    /// data events contain a new [`InvitePolicy`].
+    pub(crate) async fn handle_invite_policy_update(
+        &self,
+        account_data: &[Raw<AnyGlobalAccountDataEvent>],
+    ) {
+        let has_new_policy = account_data.iter().any(|event| {
+            event.get_field::<String>("type").ok().flatten().as_deref()
+                == Some(INVITE_POLICY_EVENT_TYPE)
+        });
+
+        if has_new_policy {
+            debug!("the invite policy changed, classifying the invites again");
+            self.classify_invites_again().await;
+        }
+    }
+
+    /// Get the policy used to filter the invites received by the user.
+    pub fn invite_filter(&self) -> InviteFilterPolicy {
+        self.inner.invite_filter.read().unwrap().policy.clone()
+    }
+
+    /// Get the invites that have been filtered out by the
+    /// [`InviteFilterPolicy`].
+    pub fn filtered_invites(&self) -> Vec<Room> {
+        let filtered = self.inner.invite_filter.read().unwrap().filtered.clone();
+        filtered
+            .iter()
+            .filter_map(|room_id| self.get_room(room_id))
+            .filter(|room| room.state() == RoomState::Invited)
+            .collect()
+    }
+
+    /// Whether the invite to the given room has been filtered out by the
+    /// [`InviteFilterPolicy`].
+    ///
+    /// This only uses the classification made when the invite was received,
+    /// or when the policy was last changed; see [`Client::classify_invite`]
+    /// for the up-to-date classification of an invite.
+    pub fn is_invite_filtered(&self, room_id: &RoomId) -> bool {
+        self.inner.invite_filter.read().unwrap().filtered.contains(room_id)
+            && self.get_room(room_id).is_some_and(|room| room.state() == RoomState::Invited)
+    }
+
+    /// Classify the invite to the given room, with the current
+    /// [`InviteFilterPolicy`] and [`InvitePolicy`].
+    ///
+    /// Rooms that aren't invites are always shown.
+    pub async fn classify_invite(&self, room: &Room) -> InviteClassification {
+        self.evaluate_invite(room).await.classification
+    }
+
+    /// Evaluate the invite to the given room with the current
+    /// [`InviteFilterPolicy`] and [`InvitePolicy`], to know how it's
+    /// classified and why.
+    ///
+    /// This can be used to explain to the user why an invite was hidden.
+    /// Rooms that aren't invites are always shown.
+    pub async fn evaluate_invite(&self, room: &Room) -> InviteEvaluation {
+        if room.state() != RoomState::Invited {
+            return InviteEvaluation::shown(InviteDecisionReason::NotAnInvite);
+        }
+
+        let inviter = match room.invite_details().await {
+            Ok(invite) => invite.invitee.event().sender().to_owned(),
+            Err(err) => {
+                // Better show an invite than lose it.
+                warn!(room_id = ?room.room_id(), "couldn't get the invite details: {err}");
+                return InviteEvaluation::shown(InviteDecisionReason::InviterUnavailable);
+            }
+        };
+
+        let invite_policy = match self.account().invite_policy().await {
+            Ok(invite_policy) => invite_policy,
+            Err(err) => {
+                warn!("couldn't load the invite policy: {err}");
+                InvitePolicy::default()
+            }
+        };
+        let policy = self.invite_filter();
+
+        let shares_room = policy.needs_shared_rooms(&invite_policy, &inviter)
+            && self.shares_joined_room_with(&inviter).await;
+
+        policy.evaluate(&invite_policy, &inviter, shares_room)
+    }
+
+    /// Classify a new invite, remember whether it's filtered, and reject it
+    /// on the server if the policy says so.
+    pub(crate) async fn apply_invite_filter(&self, room: &Room) {
+        self.classify_and_remember_invite(room, false).await;
+    }
+
+    /// Classify the invite to the given room, and remember whether it's
+    /// filtered.
+    ///
+    /// If the invite is filtered, it's rejected on the server if the policy
+    /// says so, and if it wasn't filtered before or the policy has just
+    /// changed.
+    async fn classify_and_remember_invite(&self, room: &Room, policy_changed: bool) {
+        let evaluation = self.evaluate_invite(room).await;
+
+        let reject_on_server = {
+            let mut state = self.inner.invite_filter.write().unwrap();
+            match evaluation.classification {
+                InviteClassification::Shown => {
+                    state.filtered.remove(room.room_id());
+                    false
+                }
+                InviteClassification::Filtered => {
+                    let newly_filtered = state.filtered.insert(room.room_id().to_owned());
+                    (newly_filtered || policy_changed)
+                        && state.policy.reject_on_server
+                        && evaluation.can_be_rejected()
+                }
+            }
+        };
+
+        if reject_on_server {
+            debug!(room_id = ?room.room_id(), "rejecting a filtered invite");
+
+            let room = room.clone();
+            // Don't block the sync while the request is sent.
+            let _ = spawn(async move {
+                if let Err(err) = room.leave().await {
+                    warn!(room_id = ?room.room_id(), "couldn't reject a filtered invite: {err}");
+                }
+            });
+        }
+    }
+
+    /// Whether the user shares a joined room with the given user.
+    async fn shares_joined_room_with(&self, user_id: &UserId) -> bool {
+        for room in self.joined_rooms() {
+            match room.get_member_no_sync(user_id).await {
+                Ok(Some(member)) if *member.membership() == MembershipState::Join => return true,
+                Ok(_) => {}
+                Err(err) => {
+                    warn!(room_id = ?room.room_id(), "couldn't get a room member: {err}");
+                }
+            }
+        }
+
+        false
+    }
+}
+
+#[cfg(all(test, not(target_family = "wasm")))]
+mod tests {
+    use std::time::Duration;
+
+    use matrix_sdk_base::RoomState;
+    use matrix_sdk_test::{
+        async_test, event_factory::EventFactory, GlobalAccountDataTestEvent, InvitedRoomBuilder,
+        JoinedRoomBuilder,
+    };
+    use ruma::{room_id, user_id, OwnedUserId, RoomId, UserId};
+    use serde_json::json;
+
+    use super::{
+        glob_matches, InviteClassification, InviteDecisionReason, InviteFilterPolicy, InvitePolicy,
+        InviteRuleAction, INVITE_POLICY_EVENT_TYPE,
+    };
+    use crate::{test_utils::mocks::MatrixMockServer, Client};
+
+    /// Sync an invite from `inviter` to `room_id`, along with the given invite
+    /// policy.
+    async fn sync_invite(
+        server: &MatrixMockServer,
+        client: &Client,
+        room_id: &RoomId,
+        inviter: &UserId,
+        invite_policy: InvitePolicy,
+    ) {
+        let own_user_id = client.user_id().unwrap().to_owned();
+        let f = EventFactory::new().room(room_id);
+
+        server
+            .mock_sync()
+            .ok_and_run(client, |builder| {
+                builder
+                    .add_global_account_data_event(GlobalAccountDataTestEvent::Custom(json!({
+                        "type": INVITE_POLICY_EVENT_TYPE,
+                        "content": invite_policy,
+                    })))
+                    .add_invited_room(
+                        InvitedRoomBuilder::new(room_id)
+                            .add_state_event(f.member(inviter).invited(&own_user_id).into_raw()),
+                    );
+            })
+            .await;
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("@alice:localhost", "@alice:localhost"));
+        assert!(!glob_matches("@alice:localhost", "@alice:localhost.org"));
+        assert!(glob_matches("*", ""));
+        assert!(glob_matches("*.example.org", "matrix.example.org"));
+        assert!(!glob_matches("*.example.org", "example.org"));
+        assert!(glob_matches("@*:*.org", "@bob:example.org"));
+        assert!(glob_matches("@user?:localhost", "@user1:localhost"));
+        assert!(!glob_matches("@user?:localhost", "@user10:localhost"));
+        assert!(glob_matches("a*b*c", "aXbYbZc"));
+        assert!(!glob_matches("a*b*c", "aXbYbZ"));
+    }
+
+    #[test]
+    fn test_invite_classification() {
+        let alice = user_id!("@alice:localhost");
+        let bob = user_id!("@bob:localhost");
+        let no_rules = InvitePolicy::default();
+
+        // By default, all the invites are shown.
+        let policy = InviteFilterPolicy::default();
+        let evaluation = policy.evaluate(&no_rules, alice, false);
+        assert_eq!(evaluation.classification, InviteClassification::Shown);
+        assert_eq!(evaluation.reason, InviteDecisionReason::NoMatchingRule);
+
+        let policy = InviteFilterPolicy::default().hide_unknown_inviters([bob.to_owned()]);
+
+        // Unknown inviters are filtered, unless they share a room with the user.
+        let evaluation = policy.evaluate(&no_rules, alice, false);
+        assert_eq!(evaluation.classification, InviteClassification::Filtered);
+        assert_eq!(evaluation.reason, InviteDecisionReason::UnknownInviter);
+        let evaluation = policy.evaluate(&no_rules, alice, true);
+        assert_eq!(evaluation.classification, InviteClassification::Shown);
+        assert_eq!(evaluation.reason, InviteDecisionReason::SharedRoom);
+
+        // Known users are always shown.
+        let evaluation = policy.evaluate(&no_rules, bob, false);
+        assert_eq!(evaluation.classification, InviteClassification::Shown);
+        assert_eq!(evaluation.reason, InviteDecisionReason::KnownUser);
+        assert!(!policy.needs_shared_rooms(&no_rules, bob));
+    }
+
+    #[test]
+    fn test_invite_policy_rules() {
+        let alice = user_id!("@alice:localhost");
+        let spammer = user_id!("@spammer:spam.org");
+        let policy = InviteFilterPolicy::default();
+
+        // The rules about users take precedence over the rules about servers.
+        let invite_policy = InvitePolicy::default()
+            .block_server("*.org")
+            .allow_user("@spammer:*")
+            .ignore_user("@alice:*");
+
+        let evaluation = policy.evaluate(&invite_policy, spammer, false);
+        assert_eq!(evaluation.classification, InviteClassification::Shown);
+        assert_eq!(
+            evaluation.reason,
+            InviteDecisionReason::UserRule {
+                rule: "@spammer:*".to_owned(),
+                action: InviteRuleAction::Allow
+            }
+        );
+
+        let evaluation = policy.evaluate(&invite_policy, alice, false);
+        assert_eq!(evaluation.classification, InviteClassification::Filtered);
+        assert!(!evaluation.can_be_rejected());
+
+        // Allowing takes precedence over blocking.
+        let invite_policy = InvitePolicy::default().block_server("*").allow_server("localhost");
+
+        let evaluation = policy.evaluate(&invite_policy, alice, false);
+        assert_eq!(evaluation.classification, InviteClassification::Shown);
+        let evaluation = policy.evaluate(&invite_policy, spammer, false);
+        assert_eq!(evaluation.classification, InviteClassification::Filtered);
+        assert_eq!(
+            evaluation.reason,
+            InviteDecisionReason::ServerRule {
+                rule: "*".to_owned(),
+                action: InviteRuleAction::Block
+            }
+        );
+        assert!(evaluation.can_be_rejected());
+
+        // A matching rule doesn't need the shared rooms.
+        let invite_policy = invite_policy.only_known_users(true);
+        assert!(!policy.needs_shared_rooms(&invite_policy, alice));
+        let invite_policy = InvitePolicy::default().only_known_users(true);
+        assert!(policy.needs_shared_rooms(&invite_policy, alice));
+    }
+
+    #[test]
+    fn test_serialize_invite_policy() {
+        let invite_policy = InvitePolicy::default().block_server("spam.org");
+        assert_eq!(
+            serde_json::to_value(&invite_policy).unwrap(),
+            json!({
+                "blocked_servers": ["spam.org"],
+            })
+        );
+
+        let invite_policy: InvitePolicy = serde_json::from_value(json!({
+            "allowed_users": ["@alice:localhost"],
+            "org.matrix.rust_sdk.only_known_users": true,
+            "unknown": "field",
+        }))
+        .unwrap();
+        assert_eq!(
+            invite_policy,
+            InvitePolicy::default().allow_user("@alice:localhost").only_known_users(true)
+        );
+    }
+
+    #[async_test]
+    async fn test_filtered_invites() {
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().build().await;
+        let own_user_id = client.user_id().unwrap().to_owned();
+
+        let friend = user_id!("@friend:localhost");
+        let spammer = user_id!("@spammer:localhost");
+        let joined_room_id = room_id!("!joined:localhost");
+        let friend_invite_id = room_id!("!friend:localhost");
+        let spam_invite_id = room_id!("!spam:localhost");
+
+        let joined = EventFactory::new().room(joined_room_id);
+        let friend_invite = EventFactory::new().room(friend_invite_id);
+        let spam_invite = EventFactory::new().room(spam_invite_id);
+
+        server
+            .mock_sync()
+            .ok_and_run(&client, |builder| {
+                builder
+                    .add_joined_room(JoinedRoomBuilder::new(joined_room_id).add_state_bulk([
+                        joined.member(&own_user_id).into_raw(),
+                        joined.member(friend).into_raw(),
+                    ]))
+                    .add_invited_room(InvitedRoomBuilder::new(friend_invite_id).add_state_event(
+                        friend_invite.member(friend).invited(&own_user_id).into_raw(),
+                    ))
+                    .add_invited_room(InvitedRoomBuilder::new(spam_invite_id).add_state_event(
+                        spam_invite.member(spammer).invited(&own_user_id).into_raw(),
+                    ));
+            })
+            .await;
+
+        // Nothing is filtered by default.
+        assert!(client.filtered_invites().is_empty());
+
+        client.set_invite_filter(InviteFilterPolicy::default().hide_unknown_inviters([])).await;
+
+        // Only the invite from the user who doesn't share a room with us is filtered.
+        let filtered = client.filtered_invites();
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].room_id(), spam_invite_id);
+        assert!(client.is_invite_filtered(spam_invite_id));
+        assert!(!client.is_invite_filtered(friend_invite_id));
+        assert!(!client.is_invite_filtered(joined_room_id));
+
+        // An invite that was filtered is shown again once the inviter is known.
+        client
+            .set_invite_filter(
+                InviteFilterPolicy::default().hide_unknown_inviters([spammer.to_owned()]),
+            )
+            .await;
+        assert!(client.filtered_invites().is_empty());
+        assert!(!client.is_invite_filtered(spam_invite_id));
+    }
+
+    #[async_test]
+    async fn test_invite_policy_allows_known_users() {
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().build().await;
+        let own_user_id = client.user_id().unwrap().to_owned();
+
+        let friend = user_id!("@friend:localhost");
+        let stranger = user_id!("@stranger:localhost");
+        let joined_room_id = room_id!("!joined:localhost");
+        let friend_invite_id = room_id!("!friend:localhost");
+        let stranger_invite_id = room_id!("!stranger:localhost");
+
+        let joined = EventFactory::new().room(joined_room_id);
+        server
+            .sync_room(
+                &client,
+                JoinedRoomBuilder::new(joined_room_id).add_state_bulk([
+                    joined.member(&own_user_id).into_raw(),
+                    joined.member(friend).into_raw(),
+                ]),
+            )
+            .await;
+
+        let invite_policy = InvitePolicy::default().only_known_users(true);
+        sync_invite(&server, &client, friend_invite_id, friend, invite_policy.clone()).await;
+        sync_invite(&server, &client, stranger_invite_id, stranger, invite_policy).await;
+
+        // The invite from the user sharing a room with us is shown.
+        let friend_invite = client.get_room(friend_invite_id).unwrap();
+        let evaluation = client.evaluate_invite(&friend_invite).await;
+        assert_eq!(evaluation.classification, InviteClassification::Shown);
+        assert_eq!(evaluation.reason, InviteDecisionReason::SharedRoom);
+        assert!(!client.is_invite_filtered(friend_invite_id));
+
+        // The one from a stranger is hidden.
+        let stranger_invite = client.get_room(stranger_invite_id).unwrap();
+        let evaluation = client.evaluate_invite(&stranger_invite).await;
+        assert_eq!(evaluation.classification, InviteClassification::Filtered);
+        assert_eq!(evaluation.reason, InviteDecisionReason::UnknownInviter);
+        assert!(client.is_invite_filtered(stranger_invite_id));
+
+        // Joined rooms aren't invites.
+        let joined_room = client.get_room(joined_room_id).unwrap();
+        let evaluation = client.evaluate_invite(&joined_room).await;
+        assert_eq!(evaluation.reason, InviteDecisionReason::NotAnInvite);
+    }
+
+    #[async_test]
+    async fn test_invite_policy_blocks_server() {
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().build().await;
+
+        let alice = user_id!("@alice:localhost");
+        let spammer = user_id!("@spammer:spam.org");
+        let alice_invite_id = room_id!("!alice:localhost");
+        let spam_invite_id = room_id!("!spam:localhost");
+
+        let invite_policy = InvitePolicy::default().block_server("spam.org");
+        sync_invite(&server, &client, alice_invite_id, alice, invite_policy.clone()).await;
+        sync_invite(&server, &client, spam_invite_id, spammer, invite_policy).await;
+
+        assert_eq!(client.account().invite_policy().await.unwrap().blocked_servers, ["spam.org"]);
+
+        let filtered = client.filtered_invites();
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].room_id(), spam_invite_id);
+
+        let evaluation = client.evaluate_invite(&filtered[0]).await;
+        assert_eq!(
+            evaluation.reason,
+            InviteDecisionReason::ServerRule {
+                rule: "spam.org".to_owned(),
+                action: InviteRuleAction::Block
+            }
+        );
+
+        // The invite is shown again once the policy is lifted.
+        server
+            .mock_sync()
+            .ok_and_run(&client, |builder| {
+                builder.add_global_account_data_event(GlobalAccountDataTestEvent::Custom(json!({
+                    "type": INVITE_POLICY_EVENT_TYPE,
+                    "content": {},
+                })));
+            })
+            .await;
+        assert!(client.filtered_invites().is_empty());
+    }
+
+    #[async_test]
+    async fn test_invite_policy_reject_or_hide() {
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().build().await;
+        client.set_invite_filter(InviteFilterPolicy::default().reject_on_server(true)).await;
+
+        let ignored: OwnedUserId = user_id!("@ignored:localhost").to_owned();
+        let blocked: OwnedUserId = user_id!("@blocked:localhost").to_owned();
+        let ignored_invite_id = room_id!("!ignored:localhost");
+        let blocked_invite_id = room_id!("!blocked:localhost");
+
+        // Only the blocked invite is rejected.
+        server.mock_room_leave().ok(blocked_invite_id).expect(1).mount().await;
+        server.mock_room_forget().ok().mount().await;
+
+        let invite_policy =
+            InvitePolicy::default().ignore_user(ignored.as_str()).block_user(blocked.as_str());
+        sync_invite(&server, &client, ignored_invite_id, &ignored, invite_policy.clone()).await;
+        sync_invite(&server, &client, blocked_invite_id, &blocked, invite_policy).await;
+
+        // The ignored invite is only hidden.
+        assert!(client.is_invite_filtered(ignored_invite_id));
+        assert_eq!(client.get_room(ignored_invite_id).unwrap().state(), RoomState::Invited);
+
+        // The blocked invite is rejected in the background.
+        let mut rejected = false;
+        for _ in 0..50 {
+            if client.get_room(blocked_invite_id).is_none_or(|room| room.state() == RoomState::Left)
+            {
+                rejected = true;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(rejected, "the blocked invite should have been rejected");
+    }
+}
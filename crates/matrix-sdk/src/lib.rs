@@ -26,9 +26,9 @@ pub use matrix_sdk_base::{
     deserialized_responses,
     store::{self, DynStateStore, MemoryStore, StateStoreExt},
     ComposerDraft, ComposerDraftType, EncryptionState, PredecessorRoom, QueueWedgeError,
-    Room as BaseRoom, RoomCreateWithCreatorEventContent, RoomDisplayName, RoomHero, RoomInfo,
-    RoomMember as BaseRoomMember, RoomMemberships, RoomState, SessionMeta, StateChanges,
-    StateStore, StoreError, SuccessorRoom, ThreadingSupport,
+    Room as BaseRoom, RoomCreateWithCreatorEventContent, RoomDisplayName, RoomDisplayNameInputs,
+    RoomDisplayNameOverride, RoomHero, RoomInfo, RoomMember as BaseRoomMember, RoomMemberships,
+    RoomState, SessionMeta, StateChanges, StateStore, StoreError, SuccessorRoom, ThreadingSupport,
 };
 pub use matrix_sdk_common::*;
 pub use reqwest;
@@ -36,6 +36,7 @@ pub use reqwest;
 mod account;
 pub mod attachment;
 pub mod authentication;
+pub mod badge_count;
 mod client;
 pub mod config;
 mod deduplicating_handler;
@@ -45,6 +46,8 @@ mod error;
 pub mod event_cache;
 pub mod event_handler;
 mod http_client;
+pub mod image_pack;
+pub mod invite_filter;
 pub mod latest_events;
 pub mod media;
 pub mod notification_settings;
@@ -65,7 +68,7 @@ pub mod sync;
 #[cfg(feature = "experimental-widgets")]
 pub mod widget;
 
-pub use account::Account;
+pub use account::{Account, EffectiveMediaPreviewConfig, OpenIdToken, WhoAmI};
 pub use authentication::{AuthApi, AuthSession, SessionTokens};
 pub use client::{
     sanitize_server_name, Client, ClientBuildError, ClientBuilder, LoopCtrl, SessionChange,
@@ -38,7 +38,12 @@ use ruma::{
 #[cfg(not(target_family = "wasm"))]
 use tempfile::{Builder as TempFileBuilder, NamedTempFile, TempDir};
 #[cfg(not(target_family = "wasm"))]
-use tokio::{fs::File as TokioFile, io::AsyncWriteExt};
+use tokio::{
+    fs::File as TokioFile,
+    io::{AsyncRead, AsyncWriteExt},
+};
+#[cfg(not(target_family = "wasm"))]
+use tokio_util::io::ReaderStream;
 
 use crate::{
     attachment::Thumbnail, client::futures::SendMediaUploadRequest, config::RequestConfig, Client,
@@ -214,13 +219,81 @@ impl Media {
         SendMediaUploadRequest::new(request)
     }
 
+    /// Upload some media to the server, streaming its content from the given
+    /// reader.
+    ///
+    /// Contrary to [`Media::upload()`], the whole content doesn't need to be
+    /// loaded in memory. Since the content can only be read once, the request
+    /// is not retried if it fails.
+    ///
+    /// # Arguments
+    ///
+    /// * `content_type` - The type of the media, this will be used as the
+    ///   content-type header.
+    ///
+    /// * `reader` - The reader of the content of the media.
+    ///
+    /// * `content_length` - The size of the content of the media, if known. It
+    ///   is used to compute a reasonable timeout for the request, otherwise the
+    ///   request doesn't time out.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use matrix_sdk::Client;
+    /// # use url::Url;
+    /// # use mime;
+    /// # async {
+    /// # let homeserver = Url::parse("http://localhost:8080")?;
+    /// # let mut client = Client::new(homeserver).await?;
+    /// let file = tokio::fs::File::open("/home/example/my-cat.jpg").await?;
+    /// let content_length = file.metadata().await?.len();
+    ///
+    /// let response = client
+    ///     .media()
+    ///     .upload_from_reader(&mime::IMAGE_JPEG, file, Some(content_length))
+    ///     .await?;
+    ///
+    /// println!("Cat URI: {}", response.content_uri);
+    /// # anyhow::Ok(()) };
+    /// ```
+    #[cfg(not(target_family = "wasm"))]
+    pub async fn upload_from_reader<R>(
+        &self,
+        content_type: &Mime,
+        reader: R,
+        content_length: Option<u64>,
+    ) -> Result<media::create_content::v3::Response>
+    where
+        R: AsyncRead + Send + Unpin + 'static,
+    {
+        let request_config = self
+            .client
+            .request_config()
+            .timeout(content_length.map(Self::reasonable_upload_timeout_for_size));
+
+        // The body of the request is replaced by the content of the reader.
+        let request = assign!(media::create_content::v3::Request::new(Vec::new()), {
+            content_type: Some(content_type.essence_str().to_owned()),
+        });
+        let body = reqwest::Body::wrap_stream(ReaderStream::new(reader));
+
+        Ok(self
+            .client
+            .send_with_body_stream(request, body, content_length, Some(request_config))
+            .await?)
+    }
+
     /// Returns a reasonable upload timeout for an upload, based on the size of
     /// the data to be uploaded.
     pub(crate) fn reasonable_upload_timeout(data: &[u8]) -> Duration {
-        std::cmp::max(
-            Duration::from_secs(data.len() as u64 / DEFAULT_UPLOAD_SPEED),
-            MIN_UPLOAD_REQUEST_TIMEOUT,
-        )
+        Self::reasonable_upload_timeout_for_size(data.len() as u64)
+    }
+
+    /// Returns a reasonable upload timeout for an upload, based on the size in
+    /// bytes of the data to be uploaded.
+    fn reasonable_upload_timeout_for_size(size: u64) -> Duration {
+        std::cmp::max(Duration::from_secs(size / DEFAULT_UPLOAD_SPEED), MIN_UPLOAD_REQUEST_TIMEOUT)
     }
 
     /// Preallocates an MXC URI for a media that will be uploaded soon.
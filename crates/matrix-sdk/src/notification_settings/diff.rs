@@ -0,0 +1,224 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Differences between a ruleset and the server-default ruleset.
+
+use ruma::push::{Action, RuleKind, Ruleset};
+
+/// A server-default push rule whose actions have been changed.
+#[derive(Clone, Debug)]
+pub struct ChangedPushRuleActions {
+    /// The kind of the rule.
+    pub kind: RuleKind,
+
+    /// The id of the rule.
+    pub rule_id: String,
+
+    /// The actions of the rule in the server-default ruleset.
+    pub default_actions: Vec<Action>,
+
+    /// The current actions of the rule.
+    pub actions: Vec<Action>,
+}
+
+/// The differences between the user's push rules and the server-default push
+/// rules.
+///
+/// Server-default rules that are missing from the user's ruleset, for example
+/// because the homeserver doesn't support them yet, aren't reported, since
+/// they can't be restored by the client.
+#[derive(Clone, Debug, Default)]
+pub struct PushRulesDiff {
+    /// The rules defined by the user, e.g. for a room or a keyword.
+    pub added: Vec<(RuleKind, String)>,
+
+    /// The server-default rules that are enabled by default, but have been
+    /// disabled.
+    pub disabled_defaults: Vec<(RuleKind, String)>,
+
+    /// The server-default rules that are disabled by default, but have been
+    /// enabled.
+    pub enabled_defaults: Vec<(RuleKind, String)>,
+
+    /// The server-default rules whose actions have been changed.
+    pub changed_actions: Vec<ChangedPushRuleActions>,
+}
+
+impl PushRulesDiff {
+    /// Compute the differences between the `current` ruleset and the
+    /// `defaults` ruleset.
+    pub(crate) fn new(current: &Ruleset, defaults: &Ruleset) -> Self {
+        let mut diff = Self::default();
+
+        for (kind, rule_id, is_server_default, enabled, actions) in rules(current) {
+            if !is_server_default {
+                diff.added.push((kind, rule_id.to_owned()));
+                continue;
+            }
+
+            // This is a server-default rule that isn't part of the spec, there's nothing
+            // to compare it to.
+            let Some(default_rule) = defaults.get(kind.clone(), rule_id) else {
+                continue;
+            };
+
+            match (default_rule.enabled(), enabled) {
+                (true, false) => diff.disabled_defaults.push((kind.clone(), rule_id.to_owned())),
+                (false, true) => diff.enabled_defaults.push((kind.clone(), rule_id.to_owned())),
+                _ => {}
+            }
+
+            if !same_actions(default_rule.actions(), actions) {
+                diff.changed_actions.push(ChangedPushRuleActions {
+                    kind,
+                    rule_id: rule_id.to_owned(),
+                    default_actions: default_rule.actions().to_owned(),
+                    actions: actions.to_owned(),
+                });
+            }
+        }
+
+        diff
+    }
+
+    /// Whether the ruleset is the same as the server-default ruleset.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.disabled_defaults.is_empty()
+            && self.enabled_defaults.is_empty()
+            && self.changed_actions.is_empty()
+    }
+}
+
+/// Whether two lists of actions are the same.
+///
+/// Actions can contain arbitrary JSON in custom tweaks, so they're compared by
+/// their serialized form.
+fn same_actions(a: &[Action], b: &[Action]) -> bool {
+    match (serde_json::to_value(a), serde_json::to_value(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Iterate over all the rules of a ruleset, in priority order.
+///
+/// Yields the kind, id, whether it's a server-default rule, whether it's
+/// enabled and the actions of each rule.
+fn rules(ruleset: &Ruleset) -> impl Iterator<Item = (RuleKind, &str, bool, bool, &[Action])> {
+    let override_ = ruleset.override_.iter().map(|r| {
+        (RuleKind::Override, r.rule_id.as_str(), r.default, r.enabled, r.actions.as_slice())
+    });
+    let content = ruleset.content.iter().map(|r| {
+        (RuleKind::Content, r.rule_id.as_str(), r.default, r.enabled, r.actions.as_slice())
+    });
+    let room = ruleset
+        .room
+        .iter()
+        .map(|r| (RuleKind::Room, r.rule_id.as_str(), r.default, r.enabled, r.actions.as_slice()));
+    let sender = ruleset.sender.iter().map(|r| {
+        (RuleKind::Sender, r.rule_id.as_str(), r.default, r.enabled, r.actions.as_slice())
+    });
+    let underride = ruleset.underride.iter().map(|r| {
+        (RuleKind::Underride, r.rule_id.as_str(), r.default, r.enabled, r.actions.as_slice())
+    });
+
+    override_.chain(content).chain(room).chain(sender).chain(underride)
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches2::assert_matches;
+    use matrix_sdk_test::notification_settings::get_server_default_ruleset;
+    use ruma::{
+        push::{
+            Action, NewPatternedPushRule, NewPushRule, NewSimplePushRule, PredefinedOverrideRuleId,
+            PredefinedUnderrideRuleId, RuleKind,
+        },
+        room_id,
+    };
+
+    use super::{same_actions, PushRulesDiff};
+
+    #[test]
+    fn test_no_diff_with_server_default() {
+        let defaults = get_server_default_ruleset();
+        assert!(PushRulesDiff::new(&defaults, &defaults).is_empty());
+    }
+
+    #[test]
+    fn test_diff_categories() {
+        let defaults = get_server_default_ruleset();
+        let mut ruleset = defaults.clone();
+
+        // A room rule and a keyword.
+        let room_id = room_id!("!room:localhost");
+        ruleset
+            .insert(
+                NewPushRule::Room(NewSimplePushRule::new(room_id.to_owned(), vec![])),
+                None,
+                None,
+            )
+            .unwrap();
+        ruleset
+            .insert(
+                NewPushRule::Content(NewPatternedPushRule::new(
+                    "banana".to_owned(),
+                    "banana".to_owned(),
+                    vec![Action::Notify],
+                )),
+                None,
+                None,
+            )
+            .unwrap();
+
+        // A rule that's enabled by default is disabled, and one that's disabled by
+        // default is enabled.
+        ruleset
+            .set_enabled(RuleKind::Override, PredefinedOverrideRuleId::IsRoomMention, false)
+            .unwrap();
+        ruleset.set_enabled(RuleKind::Override, PredefinedOverrideRuleId::Master, true).unwrap();
+
+        // The actions of a default rule are changed.
+        ruleset
+            .set_actions(RuleKind::Underride, PredefinedUnderrideRuleId::Message, vec![])
+            .unwrap();
+
+        let diff = PushRulesDiff::new(&ruleset, &defaults);
+
+        assert_eq!(
+            diff.added,
+            [(RuleKind::Content, "banana".to_owned()), (RuleKind::Room, room_id.to_string())]
+        );
+        assert_eq!(
+            diff.disabled_defaults,
+            [(RuleKind::Override, PredefinedOverrideRuleId::IsRoomMention.to_string())]
+        );
+        assert_eq!(
+            diff.enabled_defaults,
+            [(RuleKind::Override, PredefinedOverrideRuleId::Master.to_string())]
+        );
+        assert_matches!(diff.changed_actions.as_slice(), [changed]);
+        assert_eq!(changed.kind, RuleKind::Underride);
+        assert_eq!(changed.rule_id, PredefinedUnderrideRuleId::Message.as_str());
+        assert!(changed.actions.is_empty());
+        assert!(same_actions(
+            &changed.default_actions,
+            defaults
+                .get(RuleKind::Underride, PredefinedUnderrideRuleId::Message)
+                .unwrap()
+                .actions()
+        ));
+    }
+}
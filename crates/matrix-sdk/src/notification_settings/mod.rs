@@ -22,7 +22,10 @@ use ruma::{
         delete_pushrule, set_pushrule, set_pushrule_actions, set_pushrule_enabled,
     },
     events::push_rules::PushRulesEvent,
-    push::{Action, NewPushRule, PredefinedUnderrideRuleId, RuleKind, Ruleset, Tweak},
+    push::{
+        Action, NewPushRule, PredefinedOverrideRuleId, PredefinedUnderrideRuleId, RuleKind,
+        Ruleset, Tweak,
+    },
     RoomId,
 };
 use tokio::sync::{
@@ -34,11 +37,13 @@ use tracing::{debug, error};
 use self::{command::Command, rule_commands::RuleCommands, rules::Rules};
 
 mod command;
+mod diff;
 mod rule_commands;
 mod rules;
 
 pub use matrix_sdk_base::notification_settings::RoomNotificationMode;
 
+pub use self::diff::{ChangedPushRuleActions, PushRulesDiff};
 use crate::{
     config::RequestConfig, error::NotificationSettingsError, event_handler::EventHandlerDropGuard,
     Client, Result,
@@ -189,6 +194,56 @@ impl NotificationSettings {
         Ok(())
     }
 
+    /// Get whether the user is notified when they're invited to a room.
+    pub async fn is_invite_notifications_enabled(&self) -> bool {
+        let rules = self.rules.read().await;
+        rules
+            .ruleset
+            .get(RuleKind::Override, PredefinedOverrideRuleId::InviteForMe)
+            .is_some_and(|rule| rule.enabled() && rule.actions().iter().any(Action::should_notify))
+    }
+
+    /// Set whether the user is notified when they're invited to a room.
+    ///
+    /// Disabling the notifications clears the actions of the
+    /// `.m.rule.invite_for_me` push rule, so that it still prevents the
+    /// lower priority rules from notifying for invites. Enabling them restores
+    /// its server-default actions, and enables the rule if needed.
+    pub async fn set_invite_notifications_enabled(
+        &self,
+        enabled: bool,
+    ) -> Result<(), NotificationSettingsError> {
+        let rules = self.rules.read().await.clone();
+        let rule_kind = RuleKind::Override;
+        let rule_id = PredefinedOverrideRuleId::InviteForMe.as_str();
+
+        let actions = if enabled {
+            let user_id = self.client.user_id().expect("The client should be logged in");
+            Ruleset::server_default(user_id)
+                .get(rule_kind.clone(), rule_id)
+                .map(|rule| rule.actions().to_owned())
+                .unwrap_or_else(|| {
+                    vec![Action::Notify, Action::SetTweak(Tweak::Sound("default".into()))]
+                })
+        } else {
+            vec![]
+        };
+
+        let mut rule_commands = RuleCommands::new(rules.clone().ruleset);
+        rule_commands.set_rule_actions(rule_kind.clone(), rule_id, actions)?;
+
+        if enabled && !rules.is_enabled(rule_kind.clone(), rule_id)? {
+            rule_commands.set_rule_enabled(rule_kind, rule_id, true)?;
+        }
+
+        self.run_server_commands(&rule_commands).await?;
+
+        let rules = &mut *self.rules.write().await;
+        rules.apply(rule_commands);
+
+        Ok(())
+    }
+
     /// Set the default notification mode for a type of room.
     ///
     /// # Arguments
@@ -415,6 +470,11 @@ impl NotificationSettings {
         }
     }
 
+    /// Get a copy of the current push rules.
+    pub(crate) async fn ruleset(&self) -> Ruleset {
+        self.rules.read().await.ruleset.clone()
+    }
+
     /// Get the keywords which have enabled rules.
     pub async fn enabled_keywords(&self) -> IndexSet<String> {
         self.rules.read().await.enabled_keywords()
@@ -481,6 +541,41 @@ impl NotificationSettings {
         Ok(())
     }
 
+    /// Get the differences between the current push rules and the
+    /// server-default push rules.
+    pub async fn push_rules_diff(&self) -> PushRulesDiff {
+        let user_id = self.client.user_id().expect("The client should be logged in");
+        let defaults = Ruleset::server_default(user_id);
+        PushRulesDiff::new(&self.rules.read().await.ruleset, &defaults)
+    }
+
+    /// Reset the push rules to the server-default push rules.
+    ///
+    /// The user-defined rules are deleted, and the server-default rules are
+    /// re-enabled or disabled and get their default actions back, rule by
+    /// rule. If `keep_keywords` is `true`, the keyword rules are kept.
+    pub async fn reset_push_rules(
+        &self,
+        keep_keywords: bool,
+    ) -> Result<(), NotificationSettingsError> {
+        let rules = self.rules.read().await.clone();
+        let diff = self.push_rules_diff().await;
+
+        if diff.is_empty() {
+            return Ok(());
+        }
+
+        let mut rule_commands = RuleCommands::new(rules.ruleset);
+        rule_commands.reset_to_server_default(diff, keep_keywords)?;
+
+        self.run_server_commands(&rule_commands).await?;
+
+        let rules = &mut *self.rules.write().await;
+        rules.apply(rule_commands);
+
+        Ok(())
+    }
+
     /// Convert commands into requests to the server, and run them.
     async fn run_server_commands(
         &self,
@@ -594,7 +689,7 @@ mod tests {
     use stream_assert::{assert_next_eq, assert_pending};
     use tokio_stream::wrappers::BroadcastStream;
     use wiremock::{
-        matchers::{header, method, path, path_regex},
+        matchers::{body_json, header, method, path, path_regex},
         Mock, MockServer, ResponseTemplate,
     };
 
@@ -852,7 +947,7 @@ mod tests {
         let settings = NotificationSettings::new(client, ruleset);
 
         Mock::given(method("PUT"))
-            .and(path("/_matrix/client/r0/pushrules/global/override/.m.rule.reaction/enabled"))
+            .and(path("/_matrix/client/r0/pushrules/global/override/.m.rule.master/enabled"))
             .respond_with(ResponseTemplate::new(200))
             .expect(1)
             .mount(&server)
@@ -1682,4 +1777,131 @@ mod tests {
 
         assert_matches!(result, Err(NotificationSettingsError::InvalidParameter(_)));
     }
+
+    #[async_test]
+    async fn test_reset_push_rules() {
+        let server = MockServer::start().await;
+        let client = logged_in_client(Some(server.uri())).await;
+        let room_id = get_test_room_id();
+
+        // Mangle the server-default ruleset.
+        let mut ruleset = build_ruleset(vec![(RuleKind::Room, &room_id, false)]);
+        ruleset
+            .insert(
+                NewPushRule::Content(NewPatternedPushRule::new(
+                    "banana".to_owned(),
+                    "banana".to_owned(),
+                    vec![Action::Notify],
+                )),
+                None,
+                None,
+            )
+            .unwrap();
+        ruleset.set_enabled(RuleKind::Override, PredefinedOverrideRuleId::Master, true).unwrap();
+        ruleset
+            .set_enabled(RuleKind::Override, PredefinedOverrideRuleId::IsRoomMention, false)
+            .unwrap();
+        ruleset
+            .set_actions(RuleKind::Underride, PredefinedUnderrideRuleId::Message, vec![])
+            .unwrap();
+
+        let settings = NotificationSettings::new(client, ruleset);
+
+        let diff = settings.push_rules_diff().await;
+        assert_eq!(diff.added.len(), 2);
+        assert_eq!(diff.disabled_defaults.len(), 1);
+        assert_eq!(diff.enabled_defaults.len(), 1);
+        assert_eq!(diff.changed_actions.len(), 1);
+
+        // The rules are reset one by one, and the keyword rule is kept.
+        Mock::given(method("DELETE"))
+            .and(path_regex(r"^/_matrix/client/r0/pushrules/global/room/.*"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/_matrix/client/r0/pushrules/global/content/banana"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path(
+                "/_matrix/client/r0/pushrules/global/override/.m.rule.is_room_mention/enabled",
+            ))
+            .and(body_json(json!({ "enabled": true })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path("/_matrix/client/r0/pushrules/global/override/.m.rule.master/enabled"))
+            .and(body_json(json!({ "enabled": false })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path("/_matrix/client/r0/pushrules/global/underride/.m.rule.message/actions"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        settings.reset_push_rules(true).await.unwrap();
+
+        // Only the keyword differs from the server-default ruleset now.
+        let diff = settings.push_rules_diff().await;
+        assert_eq!(diff.added, [(RuleKind::Content, "banana".to_owned())]);
+        assert!(diff.disabled_defaults.is_empty());
+        assert!(diff.enabled_defaults.is_empty());
+        assert!(diff.changed_actions.is_empty());
+        assert_eq!(
+            settings.get_default_room_notification_mode(IsEncrypted::No, IsOneToOne::No).await,
+            RoomNotificationMode::AllMessages
+        );
+    }
+
+    #[async_test]
+    async fn test_set_invite_notifications_enabled() {
+        let server = MockServer::start().await;
+        let client = logged_in_client(Some(server.uri())).await;
+        let settings = NotificationSettings::new(client, get_server_default_ruleset());
+
+        assert!(settings.is_invite_notifications_enabled().await);
+
+        // Disabling the notifications clears the actions of the rule.
+        Mock::given(method("PUT"))
+            .and(path("/_matrix/client/r0/pushrules/global/override/.m.rule.invite_for_me/actions"))
+            .and(body_json(json!({ "actions": [] })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .named("clear_actions")
+            .mount(&server)
+            .await;
+
+        settings.set_invite_notifications_enabled(false).await.unwrap();
+        assert!(!settings.is_invite_notifications_enabled().await);
+        server.verify().await;
+        server.reset().await;
+
+        // Enabling them restores the server-default actions.
+        let default_actions = get_server_default_ruleset()
+            .get(RuleKind::Override, PredefinedOverrideRuleId::InviteForMe)
+            .unwrap()
+            .actions()
+            .to_owned();
+        Mock::given(method("PUT"))
+            .and(path("/_matrix/client/r0/pushrules/global/override/.m.rule.invite_for_me/actions"))
+            .and(body_json(json!({ "actions": default_actions })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .named("restore_actions")
+            .mount(&server)
+            .await;
+
+        settings.set_invite_notifications_enabled(true).await.unwrap();
+        assert!(settings.is_invite_notifications_enabled().await);
+    }
 }
@@ -6,7 +6,7 @@ use ruma::{
     RoomId,
 };
 
-use super::command::Command;
+use super::{command::Command, PushRulesDiff};
 use crate::NotificationSettingsError;
 
 /// A `RuleCommand` allows to generate a list of `Command` needed to modify a
@@ -194,6 +194,37 @@ impl RuleCommands {
         });
         Ok(())
     }
+
+    /// Revert all the differences with the server-default ruleset.
+    ///
+    /// If `keep_keywords` is `true`, the user-defined `Content` rules are kept.
+    pub(crate) fn reset_to_server_default(
+        &mut self,
+        diff: PushRulesDiff,
+        keep_keywords: bool,
+    ) -> Result<(), NotificationSettingsError> {
+        for (kind, rule_id) in diff.added {
+            if keep_keywords && kind == RuleKind::Content {
+                continue;
+            }
+            self.delete_rule(kind, rule_id)?;
+        }
+
+        // The rules are reset one by one, without the special handling of the mention
+        // rules in `set_rule_enabled`, since each of the legacy rules is reset too.
+        for (kind, rule_id) in diff.disabled_defaults {
+            self.set_enabled_internal(kind, &rule_id, true)?;
+        }
+        for (kind, rule_id) in diff.enabled_defaults {
+            self.set_enabled_internal(kind, &rule_id, false)?;
+        }
+
+        for changed in diff.changed_actions {
+            self.set_rule_actions(changed.kind, &changed.rule_id, changed.default_actions)?;
+        }
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
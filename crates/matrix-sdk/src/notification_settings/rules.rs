@@ -225,8 +225,16 @@ impl Rules {
     }
 
     /// The rules for a keyword, if any.
+    ///
+    /// The patterns of the push rules are matched case-insensitively, so the
+    /// rules for the keyword with a different case are also returned.
     pub(crate) fn keyword_rules(&self, keyword: &str) -> Vec<&PatternedPushRule> {
-        self.ruleset.content.iter().filter(|r| !r.default && r.pattern == keyword).collect()
+        let keyword = keyword.to_lowercase();
+        self.ruleset
+            .content
+            .iter()
+            .filter(|r| !r.default && r.pattern.to_lowercase() == keyword)
+            .collect()
     }
 
     /// Get whether a rule is enabled.
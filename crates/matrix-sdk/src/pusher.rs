@@ -15,10 +15,433 @@
 
 //! High-level pusher API.
 
-use ruma::api::client::push::{set_pusher, PusherIds};
+use ruma::{
+    api::client::push::{
+        get_pushers,
+        set_pusher::{self, v3::PusherAction},
+        PusherIds, PusherInit, PusherKind,
+    },
+    push::{HttpPusherData, PushFormat},
+};
+use serde_json::{Map as JsonMap, Value as JsonValue};
+use url::Url;
 
 use crate::{Client, Result};
 
+/// The maximum length of a pushkey, in bytes, as defined in the spec.
+const MAX_PUSHKEY_LENGTH: usize = 512;
+
+/// The maximum length of an app id, in characters, as defined in the spec.
+const MAX_APP_ID_LENGTH: usize = 64;
+
+/// An error that happened when validating a pusher configuration, before
+/// sending it to the homeserver.
+#[derive(Debug, thiserror::Error)]
+pub enum PusherError {
+    /// A required field was empty.
+    #[error("the pusher's {0} must not be empty")]
+    EmptyField(&'static str),
+
+    /// A field was longer than allowed by the spec.
+    #[error("the pusher's {field} is too long: {length} > {max}")]
+    FieldTooLong {
+        /// The name of the field.
+        field: &'static str,
+        /// The length of the field.
+        length: usize,
+        /// The maximum length of the field.
+        max: usize,
+    },
+
+    /// The push gateway URL couldn't be parsed.
+    #[error("invalid push gateway URL: {0}")]
+    InvalidUrl(#[from] url::ParseError),
+
+    /// The push gateway URL doesn't use the `https` or `http` scheme.
+    #[error("the push gateway URL must use the https or http scheme, not {0}")]
+    UnsupportedUrlScheme(String),
+
+    /// The push gateway URL doesn't point to the `/_matrix/push/v1/notify`
+    /// endpoint.
+    #[error("the push gateway URL must point to /_matrix/push/v1/notify")]
+    InvalidUrlPath,
+
+    /// The push format isn't defined by the spec, so homeservers can't be
+    /// expected to support it.
+    #[error("unsupported push format: {0}")]
+    UnsupportedFormat(String),
+
+    /// The default payload isn't a JSON object.
+    #[error("the default payload must be a JSON object")]
+    InvalidDefaultPayload,
+
+    /// The web push endpoint isn't a valid `https` URL.
+    #[error("the web push endpoint must be an https URL")]
+    InvalidWebPushEndpoint,
+}
+
+/// The web push subscription of a browser, for push gateways supporting web
+/// push, like Sygnal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebPushData {
+    /// The push endpoint of the subscription.
+    pub endpoint: String,
+
+    /// The authentication secret of the subscription, base64-encoded.
+    pub auth: String,
+
+    /// Only send the notifications for events, not the ones that only
+    /// update the unread counts.
+    pub events_only: bool,
+
+    /// Only keep the last notification of each room in the push service,
+    /// when the browser is offline.
+    pub only_last_per_room: bool,
+}
+
+impl WebPushData {
+    /// Create a new web push subscription with the given endpoint and
+    /// authentication secret.
+    ///
+    /// The p256dh public key of the subscription is the pushkey of the
+    /// pusher.
+    pub fn new(endpoint: impl Into<String>, auth: impl Into<String>) -> Self {
+        Self {
+            endpoint: endpoint.into(),
+            auth: auth.into(),
+            events_only: false,
+            only_last_per_room: false,
+        }
+    }
+
+    /// Only send the notifications for events, not the ones that only update
+    /// the unread counts.
+    pub fn events_only(mut self, events_only: bool) -> Self {
+        self.events_only = events_only;
+        self
+    }
+
+    /// Only keep the last notification of each room in the push service, when
+    /// the browser is offline.
+    pub fn only_last_per_room(mut self, only_last_per_room: bool) -> Self {
+        self.only_last_per_room = only_last_per_room;
+        self
+    }
+}
+
+/// The data of an HTTP pusher, sent by the homeserver to the push gateway
+/// along with each notification.
+///
+/// This can be converted to and from [`HttpPusherData`], keeping the fields
+/// it doesn't know about.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PusherData {
+    url: String,
+    format: Option<PushFormat>,
+    default_payload: Option<JsonValue>,
+    web_push: Option<WebPushData>,
+    other: JsonMap<String, JsonValue>,
+}
+
+impl PusherData {
+    /// Create new pusher data, for the push gateway at the given URL, i.e.
+    /// ending with `/_matrix/push/v1/notify`.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            format: None,
+            default_payload: None,
+            web_push: None,
+            other: JsonMap::new(),
+        }
+    }
+
+    /// Set the format of the notifications sent to the push gateway.
+    ///
+    /// Only [`PushFormat::EventIdOnly`] is defined by the spec; by default,
+    /// the full notification is sent.
+    pub fn format(mut self, format: PushFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    /// Only send the event id and room id to the push gateway, not the event's
+    /// content.
+    pub fn event_id_only(self) -> Self {
+        self.format(PushFormat::EventIdOnly)
+    }
+
+    /// Add to the default payload sent by the push gateway with each
+    /// notification, e.g. the `aps` dictionary for APNs.
+    ///
+    /// The payload must be a JSON object. It's merged recursively with the
+    /// payload set previously, so it can be built in several steps.
+    pub fn default_payload(mut self, payload: JsonValue) -> Self {
+        match &mut self.default_payload {
+            Some(existing) => merge_json(existing, payload),
+            None => self.default_payload = Some(payload),
+        }
+        self
+    }
+
+    /// Set the web push subscription, for push gateways supporting web push.
+    pub fn web_push(mut self, web_push: WebPushData) -> Self {
+        self.web_push = Some(web_push);
+        self
+    }
+
+    /// Check that the data is valid, according to the spec.
+    fn validate(&self) -> Result<(), PusherError> {
+        let url = Url::parse(&self.url)?;
+        if !matches!(url.scheme(), "https" | "http") {
+            return Err(PusherError::UnsupportedUrlScheme(url.scheme().to_owned()));
+        }
+        if url.path() != "/_matrix/push/v1/notify" {
+            return Err(PusherError::InvalidUrlPath);
+        }
+
+        match &self.format {
+            None | Some(PushFormat::EventIdOnly) => {}
+            Some(format) => return Err(PusherError::UnsupportedFormat(format.to_string())),
+        }
+
+        if self.default_payload.as_ref().is_some_and(|payload| !payload.is_object()) {
+            return Err(PusherError::InvalidDefaultPayload);
+        }
+
+        if let Some(web_push) = &self.web_push {
+            if Url::parse(&web_push.endpoint).map_or(true, |url| url.scheme() != "https") {
+                return Err(PusherError::InvalidWebPushEndpoint);
+            }
+            if web_push.auth.is_empty() {
+                return Err(PusherError::EmptyField("web push auth secret"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl From<PusherData> for HttpPusherData {
+    fn from(value: PusherData) -> Self {
+        let mut data = HttpPusherData::new(value.url);
+        data.format = value.format;
+        data.data = value.other.into_iter().collect();
+
+        if let Some(payload) = value.default_payload {
+            data.data.insert("default_payload".to_owned(), payload);
+        }
+
+        if let Some(web_push) = value.web_push {
+            data.data.insert("endpoint".to_owned(), web_push.endpoint.into());
+            data.data.insert("auth".to_owned(), web_push.auth.into());
+            if web_push.events_only {
+                data.data.insert("events_only".to_owned(), true.into());
+            }
+            if web_push.only_last_per_room {
+                data.data.insert("only_last_per_room".to_owned(), true.into());
+            }
+        }
+
+        data
+    }
+}
+
+impl From<HttpPusherData> for PusherData {
+    fn from(value: HttpPusherData) -> Self {
+        let mut other: JsonMap<String, JsonValue> = value.data.into_iter().collect();
+
+        let default_payload = other.remove("default_payload");
+
+        let web_push = match (other.get("endpoint"), other.get("auth")) {
+            (Some(JsonValue::String(endpoint)), Some(JsonValue::String(auth))) => {
+                let web_push = WebPushData::new(endpoint.clone(), auth.clone())
+                    .events_only(other.get("events_only") == Some(&JsonValue::Bool(true)))
+                    .only_last_per_room(
+                        other.get("only_last_per_room") == Some(&JsonValue::Bool(true)),
+                    );
+
+                for key in ["endpoint", "auth", "events_only", "only_last_per_room"] {
+                    other.remove(key);
+                }
+
+                Some(web_push)
+            }
+            _ => None,
+        };
+
+        Self { url: value.url, format: value.format, default_payload, web_push, other }
+    }
+}
+
+/// Merge the `patch` JSON value into the `target` one.
+///
+/// Objects are merged recursively, any other value replaces the previous one.
+fn merge_json(target: &mut JsonValue, patch: JsonValue) {
+    match (target, patch) {
+        (JsonValue::Object(target), JsonValue::Object(patch)) => {
+            for (key, value) in patch {
+                match target.get_mut(&key) {
+                    Some(existing) => merge_json(existing, value),
+                    None => {
+                        target.insert(key, value);
+                    }
+                }
+            }
+        }
+        (target, patch) => *target = patch,
+    }
+}
+
+/// The configuration of an HTTP pusher, to be registered with
+/// [`Pusher::register`].
+#[derive(Debug, Clone)]
+pub struct HttpPusherConfig {
+    ids: PusherIds,
+    data: PusherData,
+    app_display_name: String,
+    device_display_name: String,
+    lang: String,
+    profile_tag: Option<String>,
+    append: bool,
+}
+
+impl HttpPusherConfig {
+    /// Create a new configuration for an HTTP pusher.
+    ///
+    /// - `pushkey` identifies this device for the push gateway, e.g. the device
+    ///   token for APNs or FCM.
+    /// - `app_id` is the reverse-DNS style identifier of the application.
+    /// - `url` is the URL of the push gateway's notification endpoint, i.e.
+    ///   ending with `/_matrix/push/v1/notify`.
+    ///
+    /// The language defaults to `en`, and the pusher replaces any other pusher
+    /// with the same pushkey and app id, for other users.
+    pub fn new(
+        pushkey: impl Into<String>,
+        app_id: impl Into<String>,
+        url: impl Into<String>,
+        app_display_name: impl Into<String>,
+        device_display_name: impl Into<String>,
+    ) -> Self {
+        Self {
+            ids: PusherIds::new(pushkey.into(), app_id.into()),
+            data: PusherData::new(url),
+            app_display_name: app_display_name.into(),
+            device_display_name: device_display_name.into(),
+            lang: "en".to_owned(),
+            profile_tag: None,
+            append: false,
+        }
+    }
+
+    /// Set the preferred language for receiving notifications, e.g. `en` or
+    /// `en-US`.
+    pub fn lang(mut self, lang: impl Into<String>) -> Self {
+        self.lang = lang.into();
+        self
+    }
+
+    /// Set the profile tag, to select which set of device-specific rules this
+    /// pusher executes.
+    pub fn profile_tag(mut self, profile_tag: impl Into<String>) -> Self {
+        self.profile_tag = Some(profile_tag.into());
+        self
+    }
+
+    /// Only send the event id and room id to the push gateway, not the event's
+    /// content.
+    ///
+    /// This is the format to use when the notification content is fetched by
+    /// the application itself, e.g. to decrypt it.
+    pub fn event_id_only(mut self) -> Self {
+        self.data = self.data.event_id_only();
+        self
+    }
+
+    /// Add to the default payload sent to the push gateway, along with the
+    /// notification.
+    ///
+    /// This is used by push gateways for iOS, e.g. to set the `aps` dictionary
+    /// of the notification. See [`PusherData::default_payload`].
+    pub fn default_payload(mut self, payload: JsonValue) -> Self {
+        self.data = self.data.default_payload(payload);
+        self
+    }
+
+    /// Replace the data sent to the push gateway, including its URL.
+    pub fn data(mut self, data: PusherData) -> Self {
+        self.data = data;
+        self
+    }
+
+    /// Whether the homeserver should keep other pushers with the same pushkey
+    /// and app id, for other users.
+    ///
+    /// Defaults to `false`, i.e. other pushers are removed.
+    pub fn append(mut self, append: bool) -> Self {
+        self.append = append;
+        self
+    }
+
+    /// Check that the configuration is valid, according to the spec.
+    fn validate(&self) -> Result<(), PusherError> {
+        if self.ids.pushkey.is_empty() {
+            return Err(PusherError::EmptyField("pushkey"));
+        }
+        if self.ids.pushkey.len() > MAX_PUSHKEY_LENGTH {
+            return Err(PusherError::FieldTooLong {
+                field: "pushkey",
+                length: self.ids.pushkey.len(),
+                max: MAX_PUSHKEY_LENGTH,
+            });
+        }
+
+        if self.ids.app_id.is_empty() {
+            return Err(PusherError::EmptyField("app id"));
+        }
+        let app_id_length = self.ids.app_id.chars().count();
+        if app_id_length > MAX_APP_ID_LENGTH {
+            return Err(PusherError::FieldTooLong {
+                field: "app id",
+                length: app_id_length,
+                max: MAX_APP_ID_LENGTH,
+            });
+        }
+
+        if self.app_display_name.is_empty() {
+            return Err(PusherError::EmptyField("app display name"));
+        }
+        if self.device_display_name.is_empty() {
+            return Err(PusherError::EmptyField("device display name"));
+        }
+        if self.lang.is_empty() {
+            return Err(PusherError::EmptyField("language"));
+        }
+
+        self.data.validate()
+    }
+
+    /// Convert this configuration into the request to send to the homeserver.
+    fn into_request(self) -> set_pusher::v3::Request {
+        let pusher = PusherInit {
+            ids: self.ids,
+            kind: PusherKind::Http(self.data.into()),
+            app_display_name: self.app_display_name,
+            device_display_name: self.device_display_name,
+            profile_tag: self.profile_tag,
+            lang: self.lang,
+        };
+
+        let mut request = set_pusher::v3::Request::post(pusher.into());
+        if let PusherAction::Post(post_data) = &mut request.action {
+            post_data.append = self.append;
+        }
+
+        request
+    }
+}
+
 /// A high-level API to interact with the pusher API.
 ///
 /// All the methods in this struct send a request to the homeserver.
@@ -46,22 +469,68 @@ impl Pusher {
         self.client.send(request).await?;
         Ok(())
     }
+
+    /// Lists all the pushers registered for the current user, on all their
+    /// devices.
+    pub async fn list(&self) -> Result<Vec<ruma::api::client::push::Pusher>> {
+        let response = self.client.send(get_pushers::v3::Request::new()).await?;
+        Ok(response.pushers)
+    }
+
+    /// Registers an HTTP pusher, after validating its configuration.
+    ///
+    /// If a pusher with the same pushkey and app id already exists for the
+    /// current user, it's updated with the new configuration.
+    pub async fn register(&self, config: HttpPusherConfig) -> Result<()> {
+        config.validate()?;
+        self.client.send(config.into_request()).await?;
+        Ok(())
+    }
+
+    /// Registers an HTTP pusher, replacing any other pusher registered with
+    /// the same app id for the current user, e.g. one with a stale pushkey
+    /// left over from a previous installation of the application.
+    ///
+    /// The new pusher is registered first, with the `append` flag unset, so
+    /// that the homeserver removes the pushers with the same pushkey for
+    /// other users; the other pushers for the same app id are then deleted.
+    /// If registering the new pusher fails, the previous ones are kept.
+    pub async fn replace(&self, config: HttpPusherConfig) -> Result<()> {
+        let config = config.append(false);
+        config.validate()?;
+
+        let ids = config.ids.clone();
+        self.client.send(config.into_request()).await?;
+
+        for pusher in self.list().await? {
+            if pusher.ids.app_id == ids.app_id && pusher.ids.pushkey != ids.pushkey {
+                self.delete(pusher.ids).await?;
+            }
+        }
+
+        Ok(())
+    }
 }
 
 // The http mocking library is not supported for wasm32
 #[cfg(all(test, not(target_family = "wasm")))]
 mod tests {
+    use assert_matches2::assert_matches;
     use matrix_sdk_test::{async_test, test_json};
     use ruma::{
         api::client::push::{PusherIds, PusherInit, PusherKind},
-        push::HttpPusherData,
+        push::{HttpPusherData, PushFormat},
     };
+    use serde_json::json;
     use wiremock::{
-        matchers::{method, path},
+        matchers::{body_partial_json, method, path},
         Mock, MockServer, ResponseTemplate,
     };
 
-    use crate::test_utils::logged_in_client;
+    use super::{HttpPusherConfig, PusherData, PusherError, WebPushData};
+    use crate::{test_utils::logged_in_client, Error};
+
+    const GATEWAY_URL: &str = "https://push.example.org/_matrix/push/v1/notify";
 
     async fn mock_api(server: MockServer) {
         Mock::given(method("POST"))
@@ -105,4 +574,262 @@ mod tests {
 
         assert!(response.is_ok());
     }
+
+    fn pusher_json(pushkey: &str, app_id: &str) -> serde_json::Value {
+        json!({
+            "pushkey": pushkey,
+            "kind": "http",
+            "app_id": app_id,
+            "app_display_name": "Element",
+            "device_display_name": "Phone",
+            "lang": "en",
+            "data": {
+                "url": GATEWAY_URL,
+                "format": "event_id_only",
+            },
+        })
+    }
+
+    #[async_test]
+    async fn test_register_http_pusher() {
+        let server = MockServer::start().await;
+        let client = logged_in_client(Some(server.uri())).await;
+
+        Mock::given(method("POST"))
+            .and(path("_matrix/client/r0/pushers/set"))
+            .and(body_partial_json(json!({
+                "pushkey": "token",
+                "kind": "http",
+                "app_id": "org.example.app.ios",
+                "lang": "fr",
+                "data": {
+                    "url": GATEWAY_URL,
+                    "format": "event_id_only",
+                    "default_payload": { "aps": { "mutable-content": 1 } },
+                },
+                "append": true,
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EMPTY))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config =
+            HttpPusherConfig::new("token", "org.example.app.ios", GATEWAY_URL, "Element", "Phone")
+                .lang("fr")
+                .event_id_only()
+                .default_payload(json!({ "aps": { "mutable-content": 1 } }))
+                .append(true);
+
+        client.pusher().register(config).await.unwrap();
+    }
+
+    #[async_test]
+    async fn test_invalid_pusher_config_is_rejected_locally() {
+        let server = MockServer::start().await;
+        let client = logged_in_client(Some(server.uri())).await;
+
+        // Nothing must reach the homeserver.
+        Mock::given(method("POST"))
+            .and(path("_matrix/client/r0/pushers/set"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EMPTY))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let pusher = client.pusher();
+
+        let config = HttpPusherConfig::new("token", "app", "ftp://push.example.org/", "a", "b");
+        assert_matches!(
+            pusher.register(config).await,
+            Err(Error::Pusher(PusherError::UnsupportedUrlScheme(scheme)))
+        );
+        assert_eq!(scheme, "ftp");
+
+        let config =
+            HttpPusherConfig::new("token", "app", "https://push.example.org/notify", "a", "b");
+        assert_matches!(
+            pusher.register(config).await,
+            Err(Error::Pusher(PusherError::InvalidUrlPath))
+        );
+
+        let config = HttpPusherConfig::new("", "app", GATEWAY_URL, "a", "b");
+        assert_matches!(
+            pusher.register(config).await,
+            Err(Error::Pusher(PusherError::EmptyField("pushkey")))
+        );
+
+        let config = HttpPusherConfig::new("token", "a".repeat(65), GATEWAY_URL, "a", "b");
+        assert_matches!(
+            pusher.replace(config).await,
+            Err(Error::Pusher(PusherError::FieldTooLong { field: "app id", length: 65, max: 64 }))
+        );
+    }
+
+    #[async_test]
+    async fn test_list_pushers() {
+        let server = MockServer::start().await;
+        let client = logged_in_client(Some(server.uri())).await;
+
+        Mock::given(method("GET"))
+            .and(path("_matrix/client/r0/pushers"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "pushers": [pusher_json("token", "org.example.app")],
+            })))
+            .mount(&server)
+            .await;
+
+        let pushers = client.pusher().list().await.unwrap();
+        assert_eq!(pushers.len(), 1);
+
+        let pusher = &pushers[0];
+        assert_eq!(pusher.ids.pushkey, "token");
+        assert_eq!(pusher.ids.app_id, "org.example.app");
+        assert_matches!(&pusher.kind, PusherKind::Http(data));
+        assert_eq!(data.url, GATEWAY_URL);
+        assert_eq!(data.format, Some(PushFormat::EventIdOnly));
+    }
+
+    #[async_test]
+    async fn test_replace_pusher_deletes_stale_pushers() {
+        let server = MockServer::start().await;
+        let client = logged_in_client(Some(server.uri())).await;
+
+        // The new pusher is registered without `append`.
+        Mock::given(method("POST"))
+            .and(path("_matrix/client/r0/pushers/set"))
+            .and(body_partial_json(json!({
+                "pushkey": "new-token",
+                "kind": "http",
+                "app_id": "org.example.app",
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EMPTY))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path("_matrix/client/r0/pushers"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "pushers": [
+                    pusher_json("old-token", "org.example.app"),
+                    pusher_json("new-token", "org.example.app"),
+                    pusher_json("other-token", "org.example.other_app"),
+                ],
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        // Only the stale pusher for the same app id is deleted.
+        Mock::given(method("POST"))
+            .and(path("_matrix/client/r0/pushers/set"))
+            .and(body_partial_json(json!({
+                "pushkey": "old-token",
+                "app_id": "org.example.app",
+                "kind": null,
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EMPTY))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config =
+            HttpPusherConfig::new("new-token", "org.example.app", GATEWAY_URL, "Element", "Phone")
+                .append(true);
+
+        client.pusher().replace(config).await.unwrap();
+    }
+
+    #[test]
+    fn test_pusher_data_round_trip_element_ios() {
+        let json = json!({
+            "url": "https://matrix.org/_matrix/push/v1/notify",
+            "format": "event_id_only",
+            "default_payload": {
+                "aps": {
+                    "mutable-content": 1,
+                    "content-available": 1,
+                    "alert": { "loc-key": "Notification", "loc-args": [] },
+                },
+            },
+        });
+
+        // The payload can be built in several steps.
+        let data = PusherData::new("https://matrix.org/_matrix/push/v1/notify")
+            .event_id_only()
+            .default_payload(json!({ "aps": { "mutable-content": 1, "content-available": 1 } }))
+            .default_payload(json!({
+                "aps": { "alert": { "loc-key": "Notification", "loc-args": [] } },
+            }));
+        data.validate().unwrap();
+
+        let http_data = HttpPusherData::from(data.clone());
+        assert_eq!(serde_json::to_value(&http_data).unwrap(), json);
+
+        let parsed: HttpPusherData = serde_json::from_value(json).unwrap();
+        assert_eq!(PusherData::from(parsed), data);
+    }
+
+    #[test]
+    fn test_pusher_data_round_trip_element_android() {
+        let json = json!({
+            "url": "https://matrix.org/_matrix/push/v1/notify",
+            "format": "event_id_only",
+        });
+
+        let data = PusherData::new("https://matrix.org/_matrix/push/v1/notify").event_id_only();
+        data.validate().unwrap();
+
+        let http_data = HttpPusherData::from(data.clone());
+        assert_eq!(serde_json::to_value(&http_data).unwrap(), json);
+
+        let parsed: HttpPusherData = serde_json::from_value(json).unwrap();
+        assert_eq!(PusherData::from(parsed), data);
+    }
+
+    #[test]
+    fn test_pusher_data_round_trip_web_push() {
+        let json = json!({
+            "url": GATEWAY_URL,
+            "endpoint": "https://fcm.googleapis.com/fcm/send/abcdef",
+            "auth": "c2VjcmV0",
+            "events_only": true,
+            "brand": "Element",
+        });
+
+        let parsed: HttpPusherData = serde_json::from_value(json.clone()).unwrap();
+        let data = PusherData::from(parsed);
+        data.validate().unwrap();
+
+        assert_eq!(
+            data.web_push,
+            Some(
+                WebPushData::new("https://fcm.googleapis.com/fcm/send/abcdef", "c2VjcmV0")
+                    .events_only(true)
+            )
+        );
+
+        // Unknown fields are kept.
+        let http_data = HttpPusherData::from(data);
+        assert_eq!(serde_json::to_value(&http_data).unwrap(), json);
+    }
+
+    #[test]
+    fn test_invalid_pusher_data() {
+        let data = PusherData::new(GATEWAY_URL).format(PushFormat::from("full"));
+        assert_matches!(data.validate(), Err(PusherError::UnsupportedFormat(format)));
+        assert_eq!(format, "full");
+
+        let data = PusherData::new(GATEWAY_URL).default_payload(json!(["aps"]));
+        assert_matches!(data.validate(), Err(PusherError::InvalidDefaultPayload));
+
+        let data = PusherData::new(GATEWAY_URL)
+            .web_push(WebPushData::new("http://push.example.org/send", "c2VjcmV0"));
+        assert_matches!(data.validate(), Err(PusherError::InvalidWebPushEndpoint));
+
+        let data = PusherData::new(GATEWAY_URL)
+            .web_push(WebPushData::new("https://push.example.org/send", ""));
+        assert_matches!(data.validate(), Err(PusherError::EmptyField("web push auth secret")));
+    }
 }
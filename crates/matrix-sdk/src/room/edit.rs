@@ -66,6 +66,41 @@ pub enum EditedContent {
     },
 }
 
+impl EditedContent {
+    /// Adapts the new content to the `msgtype` of the original
+    /// `m.room.message` it's editing.
+    ///
+    /// Only the caption of a media can be edited: a new
+    /// [`EditedContent::RoomMessage`] for a media is turned into the
+    /// equivalent [`EditedContent::MediaCaption`], so the file, the thumbnail
+    /// and their encryption info are preserved. A media can't be replaced
+    /// with another `msgtype`, and a text message can't be replaced with a
+    /// media.
+    pub fn for_original_message(self, original: &MessageType) -> Result<Self, EditError> {
+        let Self::RoomMessage(new_content) = self else {
+            return Ok(self);
+        };
+
+        if !is_media(original) && !is_media(&new_content.msgtype) {
+            return Ok(Self::RoomMessage(new_content));
+        }
+
+        let incompatible = || EditError::IncompatibleEditType {
+            target: original.msgtype().to_owned(),
+            new_content: "room message with another msgtype",
+        };
+
+        if original.msgtype() != new_content.msgtype.msgtype() {
+            return Err(incompatible());
+        }
+
+        let RoomMessageEventContentWithoutRelation { msgtype, mentions, .. } = new_content;
+        let (caption, formatted_caption) = media_caption(msgtype).ok_or_else(incompatible)?;
+
+        Ok(Self::MediaCaption { caption, formatted_caption, mentions })
+    }
+}
+
 #[cfg(not(tarpaulin_include))]
 impl std::fmt::Debug for EditedContent {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -145,6 +180,14 @@ async fn make_edit_event<S: EventSource>(
         return Err(EditError::NotAuthor);
     }
 
+    // Edits of a media can only change its caption.
+    let new_content = match &message_like_event {
+        AnySyncMessageLikeEvent::RoomMessage(SyncMessageLikeEvent::Original(original)) => {
+            new_content.for_original_message(&original.content.msgtype)?
+        }
+        _ => new_content,
+    };
+
     match new_content {
         EditedContent::RoomMessage(new_content) => {
             // Handle edits of m.room.message.
@@ -239,6 +282,41 @@ macro_rules! set_caption {
     };
 }
 
+/// Whether the message type is a media, which caption can be edited.
+fn is_media(msgtype: &MessageType) -> bool {
+    match msgtype {
+        MessageType::Audio(_)
+        | MessageType::File(_)
+        | MessageType::Image(_)
+        | MessageType::Video(_) => true,
+        #[cfg(feature = "unstable-msc4274")]
+        MessageType::Gallery(_) => true,
+        _ => false,
+    }
+}
+
+/// Gets the caption and formatted caption of a media message type, or `None`
+/// if it's not a media.
+fn media_caption(msgtype: MessageType) -> Option<(Option<String>, Option<FormattedBody>)> {
+    match msgtype {
+        MessageType::Audio(event) => {
+            Some((event.caption().map(ToOwned::to_owned), event.formatted))
+        }
+        MessageType::File(event) => Some((event.caption().map(ToOwned::to_owned), event.formatted)),
+        #[cfg(feature = "unstable-msc4274")]
+        MessageType::Gallery(event) => {
+            Some(((!event.body.is_empty()).then_some(event.body), event.formatted))
+        }
+        MessageType::Image(event) => {
+            Some((event.caption().map(ToOwned::to_owned), event.formatted))
+        }
+        MessageType::Video(event) => {
+            Some((event.caption().map(ToOwned::to_owned), event.formatted))
+        }
+        _ => None,
+    }
+}
+
 /// Sets the caption of a [`RoomMessageEventContent`].
 ///
 /// Returns true if the event represented a media event (and thus the captions
@@ -292,7 +370,13 @@ mod tests {
     use ruma::{
         event_id,
         events::{
-            room::message::{MessageType, Relation, RoomMessageEventContentWithoutRelation},
+            room::{
+                message::{
+                    ImageMessageEventContent, MessageType, Relation,
+                    RoomMessageEventContentWithoutRelation,
+                },
+                MediaSource,
+            },
             AnyMessageLikeEventContent, AnySyncTimelineEvent, Mentions,
         },
         owned_mxc_uri, owned_user_id, user_id, EventId, OwnedEventId,
@@ -585,6 +669,76 @@ mod tests {
         assert_eq!(mentions.user_ids.into_iter().collect::<Vec<_>>(), vec![mentioned_user_id]);
     }
 
+    #[async_test]
+    async fn test_edit_media_with_room_message_keeps_the_source() {
+        let event_id = event_id!("$1");
+        let own_user_id = user_id!("@me:saucisse.bzh");
+
+        let filename = "rickroll.gif";
+
+        let mut cache = TestEventCache::default();
+        let f = EventFactory::new();
+        cache.events.insert(
+            event_id.to_owned(),
+            f.image(filename.to_owned(), owned_mxc_uri!("mxc://sdk.rs/rickroll"))
+                .caption(Some("caption".to_owned()), None)
+                .event_id(event_id)
+                .sender(own_user_id)
+                .into(),
+        );
+
+        // The new content points to another file, which is ignored: only the caption
+        // is updated.
+        let mut image = ImageMessageEventContent::plain(
+            "Best joke ever".to_owned(),
+            owned_mxc_uri!("mxc://sdk.rs/another"),
+        );
+        image.filename = Some("another.gif".to_owned());
+        let new_content = RoomMessageEventContentWithoutRelation::new(MessageType::Image(image));
+
+        let edit_event =
+            make_edit_event(cache, own_user_id, event_id, EditedContent::RoomMessage(new_content))
+                .await
+                .unwrap();
+
+        assert_let!(AnyMessageLikeEventContent::RoomMessage(msg) = edit_event);
+        assert_let!(Some(Relation::Replacement(repl)) = msg.relates_to);
+        assert_let!(MessageType::Image(new_image) = repl.new_content.msgtype);
+        assert_eq!(new_image.filename(), filename);
+        assert_eq!(new_image.caption(), Some("Best joke ever"));
+        assert_let!(MediaSource::Plain(uri) = new_image.source);
+        assert_eq!(uri, "mxc://sdk.rs/rickroll");
+    }
+
+    #[async_test]
+    async fn test_edit_media_with_another_msgtype() {
+        let event_id = event_id!("$1");
+        let own_user_id = user_id!("@me:saucisse.bzh");
+
+        let mut cache = TestEventCache::default();
+        let f = EventFactory::new();
+        cache.events.insert(
+            event_id.to_owned(),
+            f.image("rickroll.gif".to_owned(), owned_mxc_uri!("mxc://sdk.rs/rickroll"))
+                .event_id(event_id)
+                .sender(own_user_id)
+                .into(),
+        );
+
+        let new_content = RoomMessageEventContentWithoutRelation::text_plain("not an image");
+
+        assert_let!(
+            Err(EditError::IncompatibleEditType { target, .. }) = make_edit_event(
+                cache,
+                own_user_id,
+                event_id,
+                EditedContent::RoomMessage(new_content)
+            )
+            .await
+        );
+        assert_eq!(target, "m.image");
+    }
+
     #[async_test]
     async fn test_make_edit_event_success_with_response() {
         let event_id = event_id!("$1");
@@ -14,6 +14,8 @@
 
 //! High-level room API
 
+#[cfg(feature = "e2e-encryption")]
+use std::io::Cursor;
 use std::{
     borrow::Borrow,
     collections::{BTreeMap, HashMap},
@@ -33,7 +35,9 @@ use http::StatusCode;
 #[cfg(feature = "e2e-encryption")]
 pub use identity_status_changes::IdentityStatusChanges;
 #[cfg(feature = "e2e-encryption")]
-use matrix_sdk_base::crypto::{IdentityStatusChange, RoomIdentityProvider, UserIdentity};
+use matrix_sdk_base::crypto::{
+    CollectStrategy, IdentityStatusChange, RoomIdentityProvider, UserIdentity,
+};
 pub use matrix_sdk_base::store::ThreadStatus;
 #[cfg(feature = "e2e-encryption")]
 use matrix_sdk_base::{crypto::RoomEventDecryptionResult, deserialized_responses::EncryptionInfo};
@@ -47,22 +51,25 @@ use matrix_sdk_base::{
     ComposerDraft, EncryptionState, RoomInfoNotableUpdateReasons, RoomMemberships, SendOutsideWasm,
     StateChanges, StateStoreDataKey, StateStoreDataValue,
 };
-#[cfg(feature = "e2e-encryption")]
-use matrix_sdk_common::BoxFuture;
 use matrix_sdk_common::{
     deserialized_responses::TimelineEvent,
     executor::{spawn, JoinHandle},
     timeout::timeout,
 };
+#[cfg(feature = "e2e-encryption")]
+use matrix_sdk_common::{deserialized_responses::WithheldCode, BoxFuture};
 use mime::Mime;
 use reply::Reply;
 #[cfg(feature = "unstable-msc4274")]
 use ruma::events::room::message::GalleryItemType;
 #[cfg(feature = "e2e-encryption")]
 use ruma::events::{
-    room::encrypted::OriginalSyncRoomEncryptedEvent, AnySyncMessageLikeEvent, AnySyncTimelineEvent,
-    SyncMessageLikeEvent,
+    room::{encrypted::OriginalSyncRoomEncryptedEvent, EncryptedFile},
+    sticker::StickerMediaSource,
+    AnySyncMessageLikeEvent, AnySyncTimelineEvent, SyncMessageLikeEvent,
 };
+#[cfg(feature = "e2e-encryption")]
+use ruma::OwnedDeviceId;
 use ruma::{
     api::client::{
         config::{set_global_account_data, set_room_account_data},
@@ -112,6 +119,7 @@ use ruma::{
             ImageInfo, MediaSource, ThumbnailInfo,
         },
         space::{child::SpaceChildEventContent, parent::SpaceParentEventContent},
+        sticker::StickerEventContent,
         tag::{TagInfo, TagName},
         typing::SyncTypingEvent,
         AnyRoomAccountDataEvent, AnyRoomAccountDataEventContent, AnyTimelineEvent, EmptyStateKey,
@@ -124,8 +132,8 @@ use ruma::{
     push::{Action, PushConditionRoomCtx, Ruleset},
     serde::Raw,
     time::Instant,
-    EventId, Int, MatrixToUri, MatrixUri, MxcUri, OwnedEventId, OwnedRoomId, OwnedServerName,
-    OwnedTransactionId, OwnedUserId, RoomId, TransactionId, UInt, UserId,
+    EventId, Int, MatrixToUri, MatrixUri, MxcUri, OwnedEventId, OwnedMxcUri, OwnedRoomId,
+    OwnedServerName, OwnedTransactionId, OwnedUserId, RoomId, TransactionId, UInt, UserId,
 };
 use serde::de::DeserializeOwned;
 use thiserror::Error;
@@ -220,6 +228,45 @@ impl PushContext {
     pub fn for_event<T>(&self, event: &Raw<T>) -> Vec<Action> {
         self.push_rules.get_actions(event, &self.push_condition_room_ctx).to_owned()
     }
+
+    /// Evaluate the push rules against a given event, returning the rule that
+    /// matched along with its actions.
+    ///
+    /// This uses the same evaluation as [`Self::for_event`], and as the one
+    /// used during sync.
+    pub fn evaluate<T>(&self, event: &Raw<T>) -> EventPushActions {
+        match self.push_rules.get_match(event, &self.push_condition_room_ctx) {
+            Some(rule) => EventPushActions {
+                rule_id: Some(rule.rule_id().to_owned()),
+                actions: rule.actions().to_owned(),
+            },
+            None => EventPushActions::default(),
+        }
+    }
+}
+
+/// The result of evaluating the push rules against an event.
+#[derive(Clone, Debug, Default)]
+pub struct EventPushActions {
+    /// The id of the push rule that matched the event, if any.
+    pub rule_id: Option<String>,
+
+    /// The actions of the push rule that matched the event.
+    ///
+    /// Empty if no rule matched, or if the matching rule doesn't notify.
+    pub actions: Vec<Action>,
+}
+
+impl EventPushActions {
+    /// Whether the event should trigger a notification.
+    pub fn should_notify(&self) -> bool {
+        self.actions.iter().any(Action::should_notify)
+    }
+
+    /// Whether the event should be highlighted.
+    pub fn is_highlight(&self) -> bool {
+        self.actions.iter().any(Action::is_highlight)
+    }
 }
 
 macro_rules! make_media_type {
@@ -1612,6 +1659,54 @@ impl Room {
         }
     }
 
+    /// Set the strategy used to collect the devices which receive the room
+    /// keys of this room, overriding the client-wide
+    /// [room key recipient strategy] for this room only.
+    ///
+    /// This can be used to only share the room keys with cross-signed devices
+    /// in sensitive rooms, with [`CollectStrategy::IdentityBasedStrategy`].
+    /// The strategy is persisted in the crypto store, and used the next time a
+    /// room key is shared. Passing `None` removes the override.
+    ///
+    /// [room key recipient strategy]: crate::ClientBuilder::with_room_key_recipient_strategy
+    #[cfg(feature = "e2e-encryption")]
+    pub async fn set_room_key_recipient_strategy(
+        &self,
+        strategy: Option<CollectStrategy>,
+    ) -> Result<()> {
+        let machine = self.client.olm_machine().await;
+        let machine = machine.as_ref().ok_or(Error::NoOlmMachine)?;
+
+        machine.set_room_sharing_strategy(self.room_id(), strategy).await?;
+
+        Ok(())
+    }
+
+    /// Get the devices of the room members which won't receive the room key
+    /// the next time a message is sent in this room, grouped by user, and
+    /// alongside the reason why they are excluded.
+    ///
+    /// This can be used to warn the user that some devices of the other
+    /// members won't be able to decrypt their messages.
+    #[cfg(feature = "e2e-encryption")]
+    pub async fn devices_withheld_from_room_key(
+        &self,
+    ) -> Result<BTreeMap<OwnedUserId, BTreeMap<OwnedDeviceId, WithheldCode>>> {
+        let withheld =
+            self.client.base_client().devices_withheld_from_room_key(self.room_id()).await?;
+
+        let mut devices: BTreeMap<_, BTreeMap<_, _>> = BTreeMap::new();
+
+        for (device, code) in withheld {
+            devices
+                .entry(device.user_id().to_owned())
+                .or_default()
+                .insert(device.device_id().to_owned(), code);
+        }
+
+        Ok(devices)
+    }
+
     /// Ban the user with `UserId` from this room.
     ///
     /// # Arguments
@@ -2253,6 +2348,100 @@ impl Room {
         SendAttachment::new(self, filename.into(), content_type, data, config)
     }
 
+    /// Send a sticker to this room.
+    ///
+    /// The image of the sticker must already be in the media repository, like
+    /// the images of an [`ImagePack`]. In an encrypted room, the image and its
+    /// thumbnail are downloaded, and uploaded again encrypted, so the sticker
+    /// doesn't reference unencrypted media.
+    ///
+    /// # Arguments
+    ///
+    /// * `image_info` - The metadata of the image.
+    ///
+    /// * `url` - The MXC URI of the image.
+    ///
+    /// * `body` - A textual representation or description of the sticker.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use matrix_sdk::{Client, ruma::{events::room::ImageInfo, mxc_uri, room_id}};
+    /// # use url::Url;
+    /// # async {
+    /// # let homeserver = Url::parse("http://localhost:8080")?;
+    /// # let client = Client::new(homeserver).await?;
+    /// # let room_id = room_id!("!test:localhost");
+    /// let pack = client.account().image_packs().get().await?;
+    ///
+    /// if let Some(room) = client.get_room(&room_id) {
+    ///     if let Some((shortcode, image)) = pack.stickers().next() {
+    ///         let info = image.info.clone().unwrap_or_else(ImageInfo::new);
+    ///         let body = image.body.clone().unwrap_or_else(|| shortcode.to_owned());
+    ///
+    ///         room.send_sticker(info, image.url.clone(), body).await?;
+    ///     }
+    /// }
+    /// # anyhow::Ok(()) };
+    /// ```
+    ///
+    /// [`ImagePack`]: crate::image_pack::ImagePack
+    #[instrument(skip_all)]
+    pub async fn send_sticker(
+        &self,
+        image_info: ImageInfo,
+        url: OwnedMxcUri,
+        body: impl Into<String>,
+    ) -> Result<send_message_event::v3::Response> {
+        self.ensure_room_joined()?;
+
+        #[cfg(feature = "e2e-encryption")]
+        if self.latest_encryption_state().await?.is_encrypted() {
+            let (image_info, file) =
+                self.reupload_encrypted_sticker(image_info, url.clone()).await?;
+
+            let mut content = StickerEventContent::new(body.into(), image_info, url);
+            content.source = StickerMediaSource::Encrypted(Box::new(file));
+
+            return self.send(content).await;
+        }
+
+        self.send(StickerEventContent::new(body.into(), image_info, url)).await
+    }
+
+    /// Download the image of a sticker and its thumbnail, and upload them again
+    /// encrypted.
+    ///
+    /// Returns the image info with the encrypted thumbnail, and the encrypted
+    /// image.
+    #[cfg(feature = "e2e-encryption")]
+    async fn reupload_encrypted_sticker(
+        &self,
+        mut image_info: ImageInfo,
+        url: OwnedMxcUri,
+    ) -> Result<(ImageInfo, EncryptedFile)> {
+        let media = self.client.media();
+
+        let request =
+            MediaRequestParameters { source: MediaSource::Plain(url), format: MediaFormat::File };
+        let data = media.get_media_content(&request, true).await?;
+        let file = self.client.upload_encrypted_file(&mut Cursor::new(data)).await?;
+
+        // A thumbnail that is already encrypted can be reused as is.
+        if let Some(MediaSource::Plain(thumbnail_url)) = &image_info.thumbnail_source {
+            let request = MediaRequestParameters {
+                source: MediaSource::Plain(thumbnail_url.clone()),
+                format: MediaFormat::File,
+            };
+            let data = media.get_media_content(&request, true).await?;
+            let thumbnail_file = self.client.upload_encrypted_file(&mut Cursor::new(data)).await?;
+
+            image_info.thumbnail_source = Some(MediaSource::Encrypted(Box::new(thumbnail_file)));
+        }
+
+        Ok((image_info, file))
+    }
+
     /// Prepare and send an attachment to this room.
     ///
     /// This will upload the given data that the reader produces using the
@@ -3038,6 +3227,22 @@ impl Room {
         Ok(self.push_context().await?.map(|ctx| ctx.for_event(event)))
     }
 
+    /// Evaluate the user's push rules against the given event with the current
+    /// room state, returning the rule that matched along with its actions.
+    ///
+    /// The event can be any event of this room, including the decrypted
+    /// payload of an encrypted event, as long as it contains its `type`,
+    /// `content` and `sender` fields.
+    ///
+    /// Returns `None` if the current room state doesn't have all the state
+    /// events required to evaluate the push rules.
+    pub async fn push_actions_for_event<T>(
+        &self,
+        event: &Raw<T>,
+    ) -> Result<Option<EventPushActions>> {
+        Ok(self.push_context().await?.map(|ctx| ctx.evaluate(event)))
+    }
+
     /// The membership details of the (latest) invite for the logged-in user in
     /// this room.
     pub async fn invite_details(&self) -> Result<Invite> {
@@ -3120,6 +3325,10 @@ impl Room {
 
         self.client.base_client().forget_room(self.inner.room_id()).await?;
 
+        if let Err(e) = self.client.account().remove_recently_visited_room(self.room_id()).await {
+            warn!(room_id = ?self.room_id(), "failed to remove room from the recently visited rooms: {e}");
+        }
+
         Ok(())
     }
 
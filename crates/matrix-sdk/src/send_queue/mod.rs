@@ -36,6 +36,12 @@
 //! control all the room send queues:
 //!
 //! - enable/disable them all at once with [`SendQueue::set_enabled()`].
+//! - pause them all while the network is unavailable, with
+//!   [`SendQueue::set_network_available()`].
+//! - back off automatically after transient failures, instead of disabling the
+//!   failing room, with [`SendQueue::set_automatic_backoff()`].
+//! - observe the client-wide status with [`SendQueue::status_stream()`].
+//! - smooth out bursts of events with [`SendQueue::set_pacing_policy()`].
 //! - get notifications about send errors with [`SendQueue::subscribe_errors`].
 //! - reload all unsent events that had been persisted in storage using
 //!   [`SendQueue::respawn_tasks_for_rooms_with_unsent_requests()`]. It is
@@ -57,6 +63,11 @@
 //! this, the send queue may send such an event, using the dependency system
 //! described below.
 //!
+//! The send handle also allows observing the precise sending status of the
+//! request with [`SendHandle::subscribe_to_status()`], as a
+//! [`SendRequestStatus`]: whether it's queued, being sent (and at which
+//! attempt), waiting for a retry, wedged, sent, or aborted.
+//!
 //! # Dependency system
 //!
 //! The send queue includes a simple dependency system, where a
@@ -127,17 +138,32 @@
 //! The rest of the process is then similar to that of uploading a file without
 //! a thumbnail. The only difference is that there's a thumbnail source (MXC ID)
 //! remembered and fixed up into the media event, just before sending it.
+//!
+//! Since the media contents, the queued and dependent requests, and the MXC IDs
+//! of the uploaded parts are all persisted, an upload interrupted by a restart
+//! of the application resumes where it stopped, once the room's send queue is
+//! respawned (see [`SendQueue::respawn_tasks_for_rooms_with_unsent_requests`]).
+//! After the event has been sent, the cached media contents are renamed to use
+//! their final MXC IDs, and stop being exempted from the media retention
+//! policy, so they can be cleaned up like any other cached media.
+//!
+//! Once enabled with [`SendQueue::enable_upload_progress`], the progress of
+//! the uploads is reported with [`RoomSendQueueUpdate::MediaUpload`]; the
+//! thumbnail and the file uploads are reported as a single progress.
 
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, HashMap, VecDeque},
     str::FromStr as _,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc, RwLock,
     },
+    time::Duration,
 };
 
 use as_variant::as_variant;
+use eyeball::{SharedObservable, Subscriber};
+use futures_core::Stream;
 #[cfg(feature = "unstable-msc4274")]
 use matrix_sdk_base::store::FinishGalleryItemInfo;
 use matrix_sdk_base::{
@@ -151,7 +177,10 @@ use matrix_sdk_base::{
     store_locks::LockStoreError,
     RoomState, StoreError,
 };
-use matrix_sdk_common::executor::{spawn, JoinHandle};
+use matrix_sdk_common::{
+    executor::{spawn, JoinHandle},
+    sleep::sleep,
+};
 use mime::Mime;
 use ruma::{
     events::{
@@ -177,11 +206,21 @@ use crate::{
     config::RequestConfig,
     error::RetryKind,
     room::{edit::EditedContent, WeakRoom},
-    Client, Media, Room,
+    Client, Media, Room, TransmissionProgress,
 };
 
+mod pacing;
+mod pause;
+mod status;
 mod upload;
 
+use pacing::Pacer;
+pub use pacing::{PacingPolicy, RateLimit};
+pub use pause::SendQueueStatus;
+use pause::{ramp_up_delay, PauseDecision, SendQueuePause};
+use status::StatusTracker;
+pub use status::{SendRequestStatus, SendingStep};
+
 /// A client-wide send queue, for all the rooms known by a client.
 pub struct SendQueue {
     client: Client,
@@ -242,10 +281,12 @@ impl SendQueue {
 
         let owned_room_id = room_id.to_owned();
         let room_q = RoomSendQueue::new(
-            self.is_enabled(),
+            data.pause.clone(),
+            data.pacer.clone(),
             data.global_update_sender.clone(),
             data.error_sender.clone(),
             data.is_dropping.clone(),
+            data.report_media_upload_progress.clone(),
             &self.client,
             owned_room_id.clone(),
         );
@@ -267,7 +308,7 @@ impl SendQueue {
     pub async fn set_enabled(&self, enabled: bool) {
         debug!(?enabled, "setting global send queue enablement");
 
-        self.data().globally_enabled.store(enabled, Ordering::SeqCst);
+        self.data().pause.set_globally_enabled(enabled);
 
         // Wake up individual rooms we already know about.
         for room in self.data().rooms.read().unwrap().values() {
@@ -282,7 +323,92 @@ impl SendQueue {
     /// Returns whether the send queue is enabled, at a client-wide
     /// granularity.
     pub fn is_enabled(&self) -> bool {
-        self.data().globally_enabled.load(Ordering::SeqCst)
+        self.data().pause.is_globally_enabled()
+    }
+
+    /// Let the send queue know whether the network is available or not.
+    ///
+    /// While the network is unavailable, all the rooms' send queues are paused:
+    /// no request is attempted, and the requests stay in the queue. Once the
+    /// network is available again, the rooms resume sending by themselves,
+    /// after a small random delay so they don't all hit the homeserver at the
+    /// same time.
+    ///
+    /// Unlike [`Self::set_enabled`], this doesn't change whether the send queue
+    /// is enabled or not.
+    pub fn set_network_available(&self, available: bool) {
+        if !self.data().pause.set_offline(!available) {
+            return;
+        }
+
+        debug!(?available, "network availability changed");
+
+        if available {
+            // Wake up the rooms, so they resume sending.
+            for room in self.data().rooms.read().unwrap().values() {
+                room.inner.notifier.notify_one();
+            }
+        }
+    }
+
+    /// Enable or disable automatic backoff, for the entire client.
+    ///
+    /// By default, when sending a request fails with a recoverable error, the
+    /// room's send queue is disabled, and it must be manually re-enabled.
+    ///
+    /// With automatic backoff, transient failures (server errors,
+    /// rate-limiting, network failures) instead pause all the rooms for a
+    /// while, and sending resumes by itself after an exponentially growing
+    /// delay, or the delay indicated by the homeserver. Unrecoverable errors
+    /// still wedge the failing request, but don't disable the room's send
+    /// queue anymore, so the next requests can be sent.
+    pub fn set_automatic_backoff(&self, enabled: bool) {
+        self.data().pause.set_automatic_backoff(enabled);
+    }
+
+    /// Set the policy used to pace the events sent by all the rooms.
+    ///
+    /// When a room, or all the rooms together, have sent as many events as
+    /// allowed by the policy, the next events are delayed until they can be
+    /// sent, instead of being rejected by the homeserver's rate limits. Events
+    /// are never reordered within a room.
+    ///
+    /// Regardless of the policy, when the homeserver indicates how long to wait
+    /// before sending again, no event is sent before this delay has elapsed.
+    ///
+    /// This can be changed at any time, and applies to the next events to be
+    /// sent.
+    pub fn set_pacing_policy(&self, policy: PacingPolicy) {
+        self.data().pacer.set_policy(policy);
+    }
+
+    /// Returns the policy used to pace the events sent by all the rooms.
+    pub fn pacing_policy(&self) -> PacingPolicy {
+        self.data().pacer.policy()
+    }
+
+    /// Enable or disable the reporting of the progress of media uploads, with
+    /// [`RoomSendQueueUpdate::MediaUpload`], for the entire client.
+    ///
+    /// This is disabled by default.
+    pub fn enable_upload_progress(&self, enabled: bool) {
+        self.data().report_media_upload_progress.store(enabled, Ordering::SeqCst);
+    }
+
+    /// Returns whether the progress of media uploads is reported.
+    pub fn is_upload_progress_enabled(&self) -> bool {
+        self.data().report_media_upload_progress.load(Ordering::SeqCst)
+    }
+
+    /// Returns the current client-wide status of the send queue.
+    pub fn status(&self) -> SendQueueStatus {
+        self.data().pause.status()
+    }
+
+    /// Returns a stream of the client-wide status of the send queue, starting
+    /// with the current status.
+    pub fn status_stream(&self) -> impl Stream<Item = SendQueueStatus> {
+        self.data().pause.subscribe()
     }
 
     /// Subscribe to all updates for all rooms.
@@ -314,9 +440,25 @@ pub struct SendQueueRoomError {
     /// An error that's recoverable will disable the room's send queue, while an
     /// unrecoverable error will be parked, until the user decides to do
     /// something about it.
+    ///
+    /// With [`SendQueue::set_automatic_backoff`], a recoverable error pauses
+    /// all the rooms for a while instead, and the room's send queue isn't
+    /// disabled.
     pub is_recoverable: bool,
 }
 
+/// Returns the delay the homeserver asked to wait for before retrying a
+/// request, if any.
+fn retry_after(err: &crate::Error) -> Option<Duration> {
+    match err {
+        crate::Error::Http(http_err) => {
+            as_variant!(http_err.retry_kind(), RetryKind::Transient { retry_after } => retry_after)
+                .flatten()
+        }
+        _ => None,
+    }
+}
+
 impl Client {
     /// Returns a [`SendQueue`] that handles sending, retrying and not
     /// forgetting about requests that are to be sent.
@@ -329,11 +471,11 @@ pub(super) struct SendQueueData {
     /// Mapping of room to their unique send queue.
     rooms: RwLock<BTreeMap<OwnedRoomId, RoomSendQueue>>,
 
-    /// Is the whole mechanism enabled or disabled?
-    ///
-    /// This is only kept in memory to initialize new room queues with an
-    /// initial enablement state.
-    globally_enabled: AtomicBool,
+    /// Client-wide enablement, connectivity and backoff state.
+    pause: Arc<SendQueuePause>,
+
+    /// Client-wide pacing of the events sent by all the rooms.
+    pacer: Arc<Pacer>,
 
     /// Global sender to send [`SendQueueUpdate`].
     ///
@@ -345,6 +487,9 @@ pub(super) struct SendQueueData {
 
     /// Are we currently dropping the Client?
     is_dropping: Arc<AtomicBool>,
+
+    /// Should the progress of media uploads be reported?
+    report_media_upload_progress: Arc<AtomicBool>,
 }
 
 impl SendQueueData {
@@ -355,10 +500,12 @@ impl SendQueueData {
 
         Self {
             rooms: Default::default(),
-            globally_enabled: AtomicBool::new(globally_enabled),
+            pause: Arc::new(SendQueuePause::new(globally_enabled)),
+            pacer: Default::default(),
             global_update_sender,
             error_sender,
             is_dropping: Arc::new(false.into()),
+            report_media_upload_progress: Arc::new(false.into()),
         }
     }
 }
@@ -401,10 +548,12 @@ impl std::fmt::Debug for RoomSendQueue {
 
 impl RoomSendQueue {
     fn new(
-        globally_enabled: bool,
+        pause: Arc<SendQueuePause>,
+        pacer: Arc<Pacer>,
         global_update_sender: broadcast::Sender<SendQueueUpdate>,
         global_error_sender: broadcast::Sender<SendQueueRoomError>,
         is_dropping: Arc<AtomicBool>,
+        report_media_upload_progress: Arc<AtomicBool>,
         client: &Client,
         room_id: OwnedRoomId,
     ) -> Self {
@@ -414,17 +563,22 @@ impl RoomSendQueue {
         let notifier = Arc::new(Notify::new());
 
         let weak_room = WeakRoom::new(WeakClient::from_client(client), room_id);
-        let locally_enabled = Arc::new(AtomicBool::new(globally_enabled));
+        let locally_enabled = Arc::new(AtomicBool::new(pause.is_globally_enabled()));
+        let statuses = StatusTracker::default();
 
         let task = spawn(Self::sending_task(
             weak_room.clone(),
             queue.clone(),
             notifier.clone(),
+            statuses.clone(),
+            pause,
+            pacer,
             global_update_sender.clone(),
             update_sender.clone(),
             locally_enabled.clone(),
             global_error_sender,
             is_dropping,
+            report_media_upload_progress,
         ));
 
         Self {
@@ -436,6 +590,7 @@ impl RoomSendQueue {
                 queue,
                 notifier,
                 locally_enabled,
+                statuses,
             }),
         }
     }
@@ -472,6 +627,8 @@ impl RoomSendQueue {
         let transaction_id = self.inner.queue.push(content.clone().into(), created_at).await?;
         trace!(%transaction_id, "manager sends a raw event to the background task");
 
+        self.inner.statuses.track(&transaction_id, SendRequestStatus::Queued);
+
         self.inner.notifier.notify_one();
 
         let send_handle = SendHandle {
@@ -543,11 +700,15 @@ impl RoomSendQueue {
         room: WeakRoom,
         queue: QueueStorage,
         notifier: Arc<Notify>,
+        statuses: StatusTracker,
+        pause: Arc<SendQueuePause>,
+        pacer: Arc<Pacer>,
         global_update_sender: broadcast::Sender<SendQueueUpdate>,
         update_sender: broadcast::Sender<RoomSendQueueUpdate>,
         locally_enabled: Arc<AtomicBool>,
         global_error_sender: broadcast::Sender<SendQueueRoomError>,
         is_dropping: Arc<AtomicBool>,
+        report_media_upload_progress: Arc<AtomicBool>,
     ) {
         trace!("spawned the sending task");
 
@@ -564,6 +725,10 @@ impl RoomSendQueue {
 
         let room_id = room.room_id();
 
+        // The instants at which the last events have been sent in this room, for
+        // pacing.
+        let mut room_sends = VecDeque::new();
+
         loop {
             // A request to shut down should be preferred above everything else.
             if is_dropping.load(Ordering::SeqCst) {
@@ -589,6 +754,28 @@ impl RoomSendQueue {
                 continue;
             }
 
+            match pause.decide() {
+                PauseDecision::Proceed => {}
+
+                PauseDecision::WaitForWakeUp => {
+                    trace!("network is unavailable, sleeping");
+                    // Wait for an explicit wakeup, e.g. when the network is back.
+                    notifier.notified().await;
+                    // Don't let all the rooms resume at the same time.
+                    sleep(ramp_up_delay(room_id)).await;
+                    continue;
+                }
+
+                PauseDecision::WaitFor(delay) => {
+                    trace!(?delay, "backing off after transient failures, sleeping");
+                    tokio::select! {
+                        _ = sleep(delay) => {}
+                        _ = notifier.notified() => {}
+                    }
+                    continue;
+                }
+            }
+
             let (queued_request, cancel_upload_rx) = match queue.peek_next_to_send().await {
                 Ok(Some(request)) => request,
 
@@ -608,6 +795,17 @@ impl RoomSendQueue {
             let txn_id = queued_request.transaction_id.clone();
             trace!(txn_id = %txn_id, "received a request to send!");
 
+            if matches!(queued_request.kind, QueuedRequestKind::Event { .. }) {
+                if let Err(delay) = pacer.try_acquire(&mut room_sends) {
+                    trace!(txn_id = %txn_id, ?delay, "pacing the sending of events, sleeping");
+                    // Put the request back, and try again later. Since it's at the head of the
+                    // queue, it will be picked again first, so ordering is preserved.
+                    queue.mark_as_not_being_sent(&txn_id).await;
+                    sleep(delay).await;
+                    continue;
+                }
+            }
+
             let related_txn_id = as_variant!(&queued_request.kind, QueuedRequestKind::MediaUpload { related_to, .. } => related_to.clone());
 
             let Some(room) = room.get() else {
@@ -618,11 +816,67 @@ impl RoomSendQueue {
                 continue;
             };
 
-            match Self::handle_request(&room, queued_request, cancel_upload_rx).await {
+            // The status is observed with the transaction id of the event, for media
+            // uploads.
+            let status_txn_id = related_txn_id.clone().unwrap_or_else(|| txn_id.clone());
+            let step = if related_txn_id.is_some() {
+                SendingStep::UploadingMedia
+            } else {
+                SendingStep::SendingEvent
+            };
+            let attempt = statuses.start_attempt(&status_txn_id, &txn_id, step);
+            trace!(txn_id = %txn_id, attempt, ?step, "starting attempt");
+
+            let report_progress =
+                related_txn_id.is_some() && report_media_upload_progress.load(Ordering::SeqCst);
+
+            let progress = SharedObservable::new(TransmissionProgress::default());
+            // Observing the progress changes how the request's body is sent, so only do it
+            // when it's reported.
+            let mut progress_subscriber = report_progress.then(|| progress.subscribe());
+
+            let request = Self::handle_request(&room, queued_request, cancel_upload_rx, progress);
+            tokio::pin!(request);
+
+            // Report the progress of media uploads while they're running.
+            let result = loop {
+                tokio::select! {
+                    biased;
+
+                    res = &mut request => break res,
+
+                    Some(progress) = async { progress_subscriber.as_mut()?.next().await },
+                        if report_progress =>
+                    {
+                        send_update(
+                            &global_update_sender,
+                            &update_sender,
+                            room_id,
+                            RoomSendQueueUpdate::MediaUpload {
+                                related_to: status_txn_id.clone(),
+                                progress: statuses.media_upload_progress(
+                                    &status_txn_id,
+                                    &txn_id,
+                                    progress,
+                                ),
+                            },
+                        );
+                    }
+                }
+            };
+
+            match result {
                 Ok(Some(parent_key)) => match queue.mark_as_sent(&txn_id, parent_key.clone()).await
                 {
                     Ok(()) => match parent_key {
                         SentRequestKey::Event(event_id) => {
+                            pause.record_success();
+
+                            statuses.finish(
+                                &txn_id,
+                                SendRequestStatus::Sent { event_id: event_id.clone() },
+                            );
+
                             send_update(
                                 &global_update_sender,
                                 &update_sender,
@@ -632,6 +886,11 @@ impl RoomSendQueue {
                         }
 
                         SentRequestKey::Media(media_info) => {
+                            pause.record_success();
+
+                            // The media event itself still needs to be sent.
+                            statuses.set(&status_txn_id, SendRequestStatus::Queued);
+
                             send_update(
                                 &global_update_sender,
                                 &update_sender,
@@ -673,16 +932,39 @@ impl RoomSendQueue {
                         _ => false,
                     };
 
-                    // Disable the queue for this room after any kind of error happened.
-                    locally_enabled.store(false, Ordering::SeqCst);
+                    if let Some(delay) = retry_after(&err) {
+                        // The homeserver asked us to slow down: don't send anything, in any room,
+                        // before the delay has elapsed.
+                        pacer.honour_retry_after(delay);
+                    }
+
+                    let automatic_backoff = pause.is_automatic_backoff_enabled();
+
+                    if !automatic_backoff {
+                        // Disable the queue for this room after any kind of error happened.
+                        locally_enabled.store(false, Ordering::SeqCst);
+                    }
+
+                    let mut dropped_reactions = Vec::new();
 
                     if is_recoverable {
-                        warn!(txn_id = %txn_id, error = ?err, "Recoverable error when sending request: {err}, disabling send queue");
+                        // With automatic backoff, pause all the rooms for a while, instead of
+                        // disabling this room's queue.
+                        let until = automatic_backoff
+                            .then(|| pause.record_transient_failure(retry_after(&err)));
+
+                        if until.is_some() {
+                            warn!(txn_id = %txn_id, error = ?err, "Recoverable error when sending request: {err}, backing off");
+                        } else {
+                            warn!(txn_id = %txn_id, error = ?err, "Recoverable error when sending request: {err}, disabling send queue");
+                        }
 
                         // In this case, we intentionally keep the request in the queue, but mark it
                         // as not being sent anymore.
                         queue.mark_as_not_being_sent(&txn_id).await;
 
+                        statuses.set(&status_txn_id, SendRequestStatus::WaitingRetry { until });
+
                         // Let observers know about a failure *after* we've
                         // marked the item as not being sent anymore. Otherwise,
                         // there's a possible race where a caller might try to
@@ -692,11 +974,26 @@ impl RoomSendQueue {
                         warn!(txn_id = %txn_id, error = ?err, "Unrecoverable error when sending request: {err}");
 
                         // Mark the request as wedged, so it's not picked at any future point.
+                        let reason = QueueWedgeError::from(&err);
                         if let Err(storage_error) =
-                            queue.mark_as_wedged(&txn_id, QueueWedgeError::from(&err)).await
+                            queue.mark_as_wedged(&txn_id, reason.clone()).await
                         {
                             warn!("unable to mark request as wedged: {storage_error}");
                         }
+
+                        statuses.set(&status_txn_id, SendRequestStatus::Wedged { reason });
+
+                        // Reactions to the wedged event would wait forever for it to be sent;
+                        // drop them. Edits and redactions are kept, since they're applied to
+                        // the local echo, should the event be unwedged later.
+                        match queue.drop_reactions_to_wedged(&status_txn_id).await {
+                            Ok(dropped) => dropped_reactions = dropped,
+                            Err(storage_error) => {
+                                warn!(
+                                    "unable to drop reactions to a wedged request: {storage_error}"
+                                );
+                            }
+                        }
                     }
 
                     let error = Arc::new(err);
@@ -717,6 +1014,15 @@ impl RoomSendQueue {
                             is_recoverable,
                         },
                     );
+
+                    for transaction_id in dropped_reactions {
+                        send_update(
+                            &global_update_sender,
+                            &update_sender,
+                            room_id,
+                            RoomSendQueueUpdate::CancelledLocalEvent { transaction_id },
+                        );
+                    }
                 }
             }
         }
@@ -731,6 +1037,7 @@ impl RoomSendQueue {
         room: &Room,
         request: QueuedRequest,
         cancel_upload_rx: Option<oneshot::Receiver<()>>,
+        progress: SharedObservable<TransmissionProgress>,
     ) -> Result<Option<SentRequestKey>, crate::Error> {
         match request.kind {
             QueuedRequestKind::Event { content } => {
@@ -782,14 +1089,19 @@ impl RoomSendQueue {
                             .client()
                             .upload_encrypted_file(&mut cursor)
                             .with_request_config(RequestConfig::short_retry())
+                            .with_send_progress_observable(progress)
                             .await?;
                         MediaSource::Encrypted(Box::new(encrypted_file))
                     } else {
                         trace!("upload will be in clear text (room without encryption)");
                         let request_config = RequestConfig::short_retry()
                             .timeout(Media::reasonable_upload_timeout(&data));
-                        let res =
-                            room.client().media().upload(&mime, data, Some(request_config)).await?;
+                        let res = room
+                            .client()
+                            .media()
+                            .upload(&mime, data, Some(request_config))
+                            .with_send_progress_observable(progress)
+                            .await?;
                         MediaSource::Plain(res.content_uri)
                     };
 
@@ -797,8 +1109,12 @@ impl RoomSendQueue {
                     let media_source = {
                         let request_config = RequestConfig::short_retry()
                             .timeout(Media::reasonable_upload_timeout(&data));
-                        let res =
-                            room.client().media().upload(&mime, data, Some(request_config)).await?;
+                        let res = room
+                            .client()
+                            .media()
+                            .upload(&mime, data, Some(request_config))
+                            .with_send_progress_observable(progress)
+                            .await?;
                         MediaSource::Plain(res.content_uri)
                     };
 
@@ -929,6 +1245,11 @@ struct RoomSendQueueInner {
     /// running off the network)?
     locally_enabled: Arc<AtomicBool>,
 
+    /// The sending status of each request in the queue.
+    ///
+    /// See [`SendHandle::subscribe_to_status`].
+    statuses: StatusTracker,
+
     /// Handle to the actual sending task. Unused, but kept alive along this
     /// data structure.
     _task: JoinHandle<()>,
@@ -1183,18 +1504,32 @@ impl QueueStorage {
     /// Cancel a sending command for an event that has been sent with
     /// [`Self::push`] with the given transaction id.
     ///
-    /// Returns whether the given transaction has been effectively removed. If
-    /// false, this either means that the transaction id was unrelated to
-    /// this queue, or that the request was sent before we cancelled it.
+    /// If the event is being sent at the moment, and `with_redaction` is
+    /// true, the intent to redact it is saved, so that it's redacted as soon
+    /// as the server has accepted it.
+    ///
+    /// Returns `None` if the given transaction hasn't been removed, which means
+    /// that the transaction id was unrelated to this queue, that the request
+    /// was sent before we cancelled it, or that it's being sent and
+    /// `with_redaction` was false. Otherwise, returns the transaction ids of
+    /// the local echoes of dependent requests (i.e. reactions) that have been
+    /// removed along the event.
     async fn cancel_event(
         &self,
         transaction_id: &TransactionId,
-    ) -> Result<bool, RoomSendQueueStorageError> {
+        with_redaction: bool,
+    ) -> Result<Option<Vec<OwnedTransactionId>>, RoomSendQueueStorageError> {
         let guard = self.store.lock().await;
 
         if guard.being_sent.as_ref().map(|info| info.transaction_id.as_ref())
             == Some(transaction_id)
         {
+            if !with_redaction {
+                // The server may accept the event at any point now, and we can't take it back
+                // without a redaction.
+                return Ok(None);
+            }
+
             // Save the intent to redact the event.
             guard
                 .client()?
@@ -1208,16 +1543,71 @@ impl QueueStorage {
                 )
                 .await?;
 
-            return Ok(true);
+            return Ok(Some(Vec::new()));
         }
 
-        let removed = guard
-            .client()?
-            .state_store()
-            .remove_send_queue_request(&self.room_id, transaction_id)
-            .await?;
+        let client = guard.client()?;
+        let store = client.state_store();
+
+        if !store.remove_send_queue_request(&self.room_id, transaction_id).await? {
+            return Ok(None);
+        }
+
+        // Get rid of the dependent requests too, since they would never be applied
+        // otherwise.
+        let removed_local_echoes =
+            self.remove_dependent_requests_of(store, transaction_id, |_| true).await?;
+
+        Ok(Some(removed_local_echoes))
+    }
+
+    /// Drop the pending reactions to a request that's been wedged.
+    ///
+    /// Returns the transaction ids of the local echoes of the reactions that
+    /// have been dropped.
+    async fn drop_reactions_to_wedged(
+        &self,
+        transaction_id: &TransactionId,
+    ) -> Result<Vec<OwnedTransactionId>, RoomSendQueueStorageError> {
+        let guard = self.store.lock().await;
+        let client = guard.client()?;
+
+        self.remove_dependent_requests_of(client.state_store(), transaction_id, |kind| {
+            matches!(kind, DependentQueuedRequestKind::ReactEvent { .. })
+        })
+        .await
+    }
+
+    /// Remove the dependent requests of the given parent transaction id that
+    /// match the given predicate.
+    ///
+    /// Returns the transaction ids of the removed requests that had their own
+    /// local echo, i.e. reactions.
+    async fn remove_dependent_requests_of(
+        &self,
+        store: &DynStateStore,
+        parent_transaction_id: &TransactionId,
+        predicate: impl Fn(&DependentQueuedRequestKind) -> bool,
+    ) -> Result<Vec<OwnedTransactionId>, RoomSendQueueStorageError> {
+        let mut removed_local_echoes = Vec::new();
+
+        for dependent in store.load_dependent_queued_requests(&self.room_id).await? {
+            if dependent.parent_transaction_id != parent_transaction_id
+                || !predicate(&dependent.kind)
+            {
+                continue;
+            }
+
+            store
+                .remove_dependent_queued_request(&self.room_id, &dependent.own_transaction_id)
+                .await?;
+
+            if matches!(dependent.kind, DependentQueuedRequestKind::ReactEvent { .. }) {
+                removed_local_echoes.push(dependent.own_transaction_id.into());
+            }
+        }
 
-        Ok(removed)
+        Ok(removed_local_echoes)
     }
 
     /// Replace an event that has been sent with [`Self::push`] with the given
@@ -1565,6 +1955,18 @@ impl QueueStorage {
 
         let local_requests =
             store.load_send_queue_requests(&self.room_id).await?.into_iter().filter_map(|queued| {
+                if queued.as_event().is_some() {
+                    // After a restart, seed the status of the request from what's been
+                    // persisted.
+                    room.inner.statuses.track_if_missing(
+                        &queued.transaction_id,
+                        match &queued.error {
+                            Some(reason) => SendRequestStatus::Wedged { reason: reason.clone() },
+                            None => SendRequestStatus::Queued,
+                        },
+                    );
+                }
+
                 Some(LocalEcho {
                     transaction_id: queued.transaction_id.clone(),
                     content: match queued.kind {
@@ -1621,6 +2023,10 @@ impl QueueStorage {
                     file_upload,
                     thumbnail_info,
                 } => {
+                    room.inner
+                        .statuses
+                        .track_if_missing(&dep.own_transaction_id, SendRequestStatus::Queued);
+
                     // Materialize as an event local echo.
                     Some(LocalEcho {
                         transaction_id: dep.own_transaction_id.clone().into(),
@@ -2136,6 +2542,19 @@ pub enum RoomSendQueueUpdate {
         event_id: OwnedEventId,
     },
 
+    /// The upload of the media attached to an event made progress.
+    ///
+    /// Only reported when enabled with [`SendQueue::enable_upload_progress`].
+    /// When the media has a thumbnail, the progress covers both the thumbnail
+    /// and the file uploads.
+    MediaUpload {
+        /// The media event this upload relates to.
+        related_to: OwnedTransactionId,
+
+        /// The progress of the upload, in bytes.
+        progress: TransmissionProgress,
+    },
+
     /// A media has been successfully uploaded.
     UploadedMedia {
         /// The media event this uploaded media relates to.
@@ -2240,6 +2659,25 @@ struct MediaHandles {
     upload_file_txn: OwnedTransactionId,
 }
 
+/// Options to configure how [`SendHandle::abort_with_options`] aborts the
+/// sending of an event.
+#[derive(Clone, Copy, Debug)]
+pub struct AbortOptions {
+    /// If the event is being sent at the time it's aborted, should it be
+    /// redacted once the server has accepted it?
+    ///
+    /// If false, an event that's being sent can't be aborted anymore.
+    ///
+    /// Defaults to true.
+    pub abort_with_redaction: bool,
+}
+
+impl Default for AbortOptions {
+    fn default() -> Self {
+        Self { abort_with_redaction: true }
+    }
+}
+
 /// A handle to manipulate an event that was scheduled to be sent to a room.
 #[derive(Clone, Debug)]
 pub struct SendHandle {
@@ -2270,16 +2708,49 @@ impl SendHandle {
 
     /// Aborts the sending of the event, if it wasn't sent yet.
     ///
+    /// If the event is being sent at the moment, it will be redacted as soon
+    /// as the server has accepted it. See [`Self::abort_with_options`] to
+    /// configure this behavior.
+    ///
     /// Returns true if the sending could be aborted, false if not (i.e. the
     /// event had already been sent).
-    #[instrument(skip(self), fields(room_id = %self.room.inner.room.room_id(), txn_id = %self.transaction_id))]
     pub async fn abort(&self) -> Result<bool, RoomSendQueueStorageError> {
+        self.abort_with_options(AbortOptions::default()).await
+    }
+
+    /// Aborts the sending of the event, if it wasn't sent yet, with the given
+    /// options.
+    ///
+    /// This covers all the phases of the sending:
+    ///
+    /// - if the request is still in the queue, it's removed along with its
+    ///   dependent requests (e.g. reactions to the local echo),
+    /// - if a media attached to the event is being uploaded, the upload is
+    ///   aborted,
+    /// - if the event itself is being sent, it will be redacted as soon as the
+    ///   server has accepted it, if [`AbortOptions::abort_with_redaction`] is
+    ///   set; otherwise, the event can't be aborted anymore.
+    ///
+    /// In all the successful cases, a
+    /// [`RoomSendQueueUpdate::CancelledLocalEvent`] is emitted immediately,
+    /// and the status of the request becomes
+    /// [`SendRequestStatus::Aborted`].
+    ///
+    /// Returns true if the sending could be aborted, false if not (i.e. the
+    /// event had already been sent).
+    #[instrument(skip(self), fields(room_id = %self.room.inner.room.room_id(), txn_id = %self.transaction_id))]
+    pub async fn abort_with_options(
+        &self,
+        options: AbortOptions,
+    ) -> Result<bool, RoomSendQueueStorageError> {
         trace!("received an abort request");
 
         let queue = &self.room.inner.queue;
 
         for handles in &self.media_handles {
             if queue.abort_upload(&self.transaction_id, handles).await? {
+                self.room.inner.statuses.finish(&self.transaction_id, SendRequestStatus::Aborted);
+
                 // Propagate a cancelled update.
                 self.room.send_update(RoomSendQueueUpdate::CancelledLocalEvent {
                     transaction_id: self.transaction_id.clone(),
@@ -2293,14 +2764,23 @@ impl SendHandle {
             // code path below, that handles aborting sending of an event.
         }
 
-        if queue.cancel_event(&self.transaction_id).await? {
+        if let Some(removed_local_echoes) =
+            queue.cancel_event(&self.transaction_id, options.abort_with_redaction).await?
+        {
             trace!("successful abort");
 
+            self.room.inner.statuses.finish(&self.transaction_id, SendRequestStatus::Aborted);
+
             // Propagate a cancelled update too.
             self.room.send_update(RoomSendQueueUpdate::CancelledLocalEvent {
                 transaction_id: self.transaction_id.clone(),
             });
 
+            // The dependent local echoes are gone too.
+            for transaction_id in removed_local_echoes {
+                self.room.send_update(RoomSendQueueUpdate::CancelledLocalEvent { transaction_id });
+            }
+
             Ok(true)
         } else {
             debug!("local echo didn't exist anymore, can't abort");
@@ -2395,6 +2875,18 @@ impl SendHandle {
         }
     }
 
+    /// Subscribe to the sending status of this request.
+    ///
+    /// The current status is available with [`Subscriber::get`], and new
+    /// statuses are emitted as the request progresses through the send queue,
+    /// including the upload steps of a media event.
+    ///
+    /// Returns `None` if the request isn't in the send queue anymore, i.e. it
+    /// has been sent or aborted already.
+    pub fn subscribe_to_status(&self) -> Option<Subscriber<SendRequestStatus>> {
+        self.room.inner.statuses.subscribe(&self.transaction_id)
+    }
+
     /// Unwedge a local echo identified by its transaction identifier and try to
     /// resend it.
     pub async fn unwedge(&self) -> Result<(), RoomSendQueueError> {
@@ -2422,6 +2914,8 @@ impl SendHandle {
             }
         }
 
+        room.statuses.set(&self.transaction_id, SendRequestStatus::Queued);
+
         // Wake up the queue, in case the room was asleep before unwedging the request.
         room.notifier.notify_one();
 
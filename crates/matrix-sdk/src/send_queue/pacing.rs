@@ -0,0 +1,217 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Pacing of the events sent by the send queue, to smooth out bursts instead
+//! of hitting the homeserver's rate limits.
+
+use std::{collections::VecDeque, sync::Mutex, time::Duration};
+
+use ruma::time::Instant;
+
+/// A maximum number of events that can be sent during a sliding period of
+/// time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RateLimit {
+    /// The maximum number of events sent during any `period`.
+    pub max_events: u32,
+
+    /// The duration of the sliding window.
+    pub period: Duration,
+}
+
+impl RateLimit {
+    /// Create a new [`RateLimit`] allowing `max_events` events to be sent
+    /// during any `period`.
+    pub fn new(max_events: u32, period: Duration) -> Self {
+        Self { max_events, period }
+    }
+
+    /// How long to wait before sending a new event, given the instants at
+    /// which the previous events have been sent, oldest first.
+    ///
+    /// Forgets about the sends that happened outside the sliding window.
+    fn delay(&self, sends: &mut VecDeque<Instant>, now: Instant) -> Option<Duration> {
+        while sends.front().is_some_and(|sent_at| now.duration_since(*sent_at) >= self.period) {
+            sends.pop_front();
+        }
+
+        if (sends.len() as u64) < u64::from(self.max_events) {
+            return None;
+        }
+
+        // Wait until enough sends have left the sliding window.
+        let excess = sends.len() - self.max_events as usize;
+        sends.get(excess).map(|sent_at| (*sent_at + self.period).saturating_duration_since(now))
+    }
+}
+
+/// The policy used to pace the events sent by the send queue.
+///
+/// By default, no pacing happens.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PacingPolicy {
+    /// The rate limit for the events sent in a single room.
+    pub per_room: Option<RateLimit>,
+
+    /// The rate limit for the events sent in all the rooms.
+    pub global: Option<RateLimit>,
+}
+
+impl PacingPolicy {
+    /// Limit the rate at which events are sent in each room.
+    pub fn per_room(mut self, limit: RateLimit) -> Self {
+        self.per_room = Some(limit);
+        self
+    }
+
+    /// Limit the rate at which events are sent across all the rooms.
+    pub fn global(mut self, limit: RateLimit) -> Self {
+        self.global = Some(limit);
+        self
+    }
+}
+
+#[derive(Default)]
+struct PacerInner {
+    policy: PacingPolicy,
+
+    /// The instants at which the last events have been sent, in all rooms.
+    global_sends: VecDeque<Instant>,
+
+    /// The homeserver asked us not to send anything before this instant.
+    not_before: Option<Instant>,
+}
+
+/// Client-wide state for pacing the events sent by all the rooms.
+#[derive(Default)]
+pub(super) struct Pacer {
+    inner: Mutex<PacerInner>,
+}
+
+impl Pacer {
+    pub fn policy(&self) -> PacingPolicy {
+        self.inner.lock().unwrap().policy
+    }
+
+    pub fn set_policy(&self, policy: PacingPolicy) {
+        self.inner.lock().unwrap().policy = policy;
+    }
+
+    /// Try to reserve a slot for sending an event in a room, given the
+    /// previous sends in this room.
+    ///
+    /// On success, the send is recorded, both for the room and globally.
+    /// Otherwise, returns how long to wait before trying again.
+    pub fn try_acquire(&self, room_sends: &mut VecDeque<Instant>) -> Result<(), Duration> {
+        let mut inner = self.inner.lock().unwrap();
+        let now = Instant::now();
+
+        let not_before = inner.not_before.and_then(|not_before| {
+            let delay = not_before.saturating_duration_since(now);
+            (!delay.is_zero()).then_some(delay)
+        });
+
+        let policy = inner.policy;
+        let per_room = policy.per_room.and_then(|limit| limit.delay(room_sends, now));
+        let global = policy.global.and_then(|limit| limit.delay(&mut inner.global_sends, now));
+
+        if let Some(delay) = [not_before, per_room, global].into_iter().flatten().max() {
+            return Err(delay);
+        }
+
+        inner.not_before = None;
+
+        if policy.per_room.is_some() {
+            room_sends.push_back(now);
+        }
+        if policy.global.is_some() {
+            inner.global_sends.push_back(now);
+        }
+
+        Ok(())
+    }
+
+    /// The homeserver asked us to wait for `delay` before sending anything
+    /// again.
+    pub fn honour_retry_after(&self, delay: Duration) {
+        let mut inner = self.inner.lock().unwrap();
+        let not_before = Instant::now() + delay;
+        inner.not_before = Some(inner.not_before.map_or(not_before, |prev| prev.max(not_before)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::VecDeque, time::Duration};
+
+    use assert_matches2::assert_matches;
+
+    use super::{Pacer, PacingPolicy, RateLimit};
+
+    #[test]
+    fn test_no_pacing_by_default() {
+        let pacer = Pacer::default();
+        let mut room_sends = VecDeque::new();
+
+        for _ in 0..100 {
+            assert!(pacer.try_acquire(&mut room_sends).is_ok());
+        }
+
+        // Nothing is recorded when there's no policy.
+        assert!(room_sends.is_empty());
+    }
+
+    #[test]
+    fn test_per_room_and_global_limits() {
+        let pacer = Pacer::default();
+        pacer.set_policy(
+            PacingPolicy::default()
+                .per_room(RateLimit::new(2, Duration::from_secs(10)))
+                .global(RateLimit::new(3, Duration::from_secs(10))),
+        );
+
+        let mut room1 = VecDeque::new();
+        let mut room2 = VecDeque::new();
+
+        // Two events in the first room, then it's paced.
+        assert!(pacer.try_acquire(&mut room1).is_ok());
+        assert!(pacer.try_acquire(&mut room1).is_ok());
+        assert_matches!(pacer.try_acquire(&mut room1), Err(delay));
+        assert!(delay > Duration::from_secs(9));
+
+        // One more event in the second room, then the global limit kicks in.
+        assert!(pacer.try_acquire(&mut room2).is_ok());
+        assert!(pacer.try_acquire(&mut room2).is_err());
+
+        // Lifting the policy at runtime lifts the pacing.
+        pacer.set_policy(PacingPolicy::default());
+        assert!(pacer.try_acquire(&mut room1).is_ok());
+        assert!(pacer.try_acquire(&mut room2).is_ok());
+    }
+
+    #[test]
+    fn test_retry_after_is_honoured() {
+        let pacer = Pacer::default();
+        let mut room_sends = VecDeque::new();
+
+        pacer.honour_retry_after(Duration::from_secs(5));
+        assert_matches!(pacer.try_acquire(&mut room_sends), Err(delay));
+        assert!(delay > Duration::from_secs(4));
+
+        // A shorter delay doesn't shorten the previous one.
+        pacer.honour_retry_after(Duration::from_secs(1));
+        assert_matches!(pacer.try_acquire(&mut room_sends), Err(delay));
+        assert!(delay > Duration::from_secs(4));
+    }
+}
@@ -0,0 +1,349 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Client-wide pausing of the send queue, while the network is unavailable or
+//! the homeserver is failing with transient errors.
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::BuildHasher as _,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use eyeball::{SharedObservable, Subscriber};
+use ruma::{
+    time::{Instant, SystemTime},
+    MilliSecondsSinceUnixEpoch, RoomId,
+};
+
+/// The delay before retrying after the first transient failure.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+
+/// The maximum delay between two attempts, when backing off.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// The maximum delay a room's queue waits before resuming, after the network
+/// came back, so that all the rooms don't hit the server at the same time.
+const MAX_RAMP_UP_DELAY: Duration = Duration::from_millis(250);
+
+/// The client-wide status of the send queue.
+///
+/// This can be observed with [`super::SendQueue::status_stream`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum SendQueueStatus {
+    /// Requests are sent as soon as they're queued.
+    Running,
+
+    /// The send queue has been disabled with [`super::SendQueue::set_enabled`],
+    /// and nothing will be sent until it's enabled again.
+    Disabled,
+
+    /// The network is unavailable; sending will resume automatically once
+    /// it's back.
+    ///
+    /// See [`super::SendQueue::set_network_available`].
+    Offline,
+
+    /// The homeserver has been failing with transient errors (server errors,
+    /// rate-limiting, timeouts); sending is paused for all the rooms, and
+    /// will resume automatically.
+    ///
+    /// This only happens if automatic backoff has been enabled with
+    /// [`super::SendQueue::set_automatic_backoff`].
+    BackingOff {
+        /// When sending will resume.
+        until: MilliSecondsSinceUnixEpoch,
+
+        /// The number of transient failures that happened in a row.
+        consecutive_failures: u32,
+    },
+}
+
+/// What a room's sending task should do before sending its next request.
+pub(super) enum PauseDecision {
+    /// Send the next request.
+    Proceed,
+
+    /// Wait until the room's queue is explicitly woken up.
+    WaitForWakeUp,
+
+    /// Wait for the given duration, or until the room's queue is explicitly
+    /// woken up, whichever comes first.
+    WaitFor(Duration),
+}
+
+#[derive(Default)]
+struct PauseInner {
+    /// Has the network been reported as unavailable?
+    offline: bool,
+
+    /// The number of transient failures that happened in a row.
+    consecutive_failures: u32,
+
+    /// If backing off, the instant at which sending may resume.
+    backoff_until: Option<Instant>,
+}
+
+/// Shared state deciding whether the rooms' send queues may send requests,
+/// at a client-wide granularity.
+pub(super) struct SendQueuePause {
+    inner: Mutex<PauseInner>,
+
+    /// Is the whole mechanism enabled or disabled?
+    ///
+    /// This is only kept in memory to initialize new room queues with an
+    /// initial enablement state.
+    globally_enabled: AtomicBool,
+
+    /// Should transient failures pause all the rooms, instead of disabling
+    /// the failing room's queue?
+    automatic_backoff: AtomicBool,
+
+    /// The observable status of the send queue, derived from the rest of the
+    /// state.
+    status: SharedObservable<SendQueueStatus>,
+}
+
+impl SendQueuePause {
+    pub fn new(globally_enabled: bool) -> Self {
+        let status =
+            if globally_enabled { SendQueueStatus::Running } else { SendQueueStatus::Disabled };
+
+        Self {
+            inner: Default::default(),
+            globally_enabled: AtomicBool::new(globally_enabled),
+            automatic_backoff: AtomicBool::new(false),
+            status: SharedObservable::new(status),
+        }
+    }
+
+    /// Subscribe to the status of the send queue.
+    pub fn subscribe(&self) -> Subscriber<SendQueueStatus> {
+        self.status.subscribe_reset()
+    }
+
+    /// Get the current status of the send queue.
+    pub fn status(&self) -> SendQueueStatus {
+        self.status.get()
+    }
+
+    pub fn is_globally_enabled(&self) -> bool {
+        self.globally_enabled.load(Ordering::SeqCst)
+    }
+
+    pub fn set_globally_enabled(&self, enabled: bool) {
+        let inner = self.inner.lock().unwrap();
+        self.globally_enabled.store(enabled, Ordering::SeqCst);
+        self.update_status(&inner);
+    }
+
+    pub fn set_automatic_backoff(&self, enabled: bool) {
+        self.automatic_backoff.store(enabled, Ordering::SeqCst);
+    }
+
+    pub fn is_automatic_backoff_enabled(&self) -> bool {
+        self.automatic_backoff.load(Ordering::SeqCst)
+    }
+
+    /// Record whether the network is available or not.
+    ///
+    /// Returns whether this changed anything.
+    pub fn set_offline(&self, offline: bool) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+
+        if inner.offline == offline {
+            return false;
+        }
+
+        inner.offline = offline;
+
+        if !offline {
+            // The network is back: any pending backoff was likely caused by the network
+            // being down, so start afresh.
+            inner.consecutive_failures = 0;
+            inner.backoff_until = None;
+        }
+
+        self.update_status(&inner);
+        true
+    }
+
+    /// Decide whether a room may send its next request now.
+    pub fn decide(&self) -> PauseDecision {
+        let mut inner = self.inner.lock().unwrap();
+
+        if inner.offline {
+            return PauseDecision::WaitForWakeUp;
+        }
+
+        if let Some(until) = inner.backoff_until {
+            let now = Instant::now();
+            if now < until {
+                return PauseDecision::WaitFor(until - now);
+            }
+
+            // The backoff period has elapsed; try again. The failure counter is only reset
+            // after a successful request, so that a new failure backs off for longer.
+            inner.backoff_until = None;
+            self.update_status(&inner);
+        }
+
+        PauseDecision::Proceed
+    }
+
+    /// Record that a request has been successfully sent.
+    pub fn record_success(&self) {
+        let mut inner = self.inner.lock().unwrap();
+
+        if inner.consecutive_failures == 0 && inner.backoff_until.is_none() {
+            return;
+        }
+
+        inner.consecutive_failures = 0;
+        inner.backoff_until = None;
+        self.update_status(&inner);
+    }
+
+    /// Record that a request failed with a transient error, and pause all the
+    /// rooms for a while.
+    ///
+    /// If the server indicated how long to wait with `retry_after`, this is
+    /// respected; otherwise, the delay grows exponentially with the number of
+    /// consecutive failures.
+    ///
+    /// Returns when sending will resume.
+    pub fn record_transient_failure(
+        &self,
+        retry_after: Option<Duration>,
+    ) -> MilliSecondsSinceUnixEpoch {
+        let mut inner = self.inner.lock().unwrap();
+
+        inner.consecutive_failures = inner.consecutive_failures.saturating_add(1);
+
+        let delay = retry_after.unwrap_or_else(|| {
+            let exponent = (inner.consecutive_failures - 1).min(16);
+            INITIAL_BACKOFF.saturating_mul(1 << exponent).min(MAX_BACKOFF)
+        });
+
+        inner.backoff_until = Some(Instant::now() + delay);
+        self.update_status(&inner);
+
+        timestamp_in(delay)
+    }
+
+    fn update_status(&self, inner: &PauseInner) {
+        let status = if !self.is_globally_enabled() {
+            SendQueueStatus::Disabled
+        } else if inner.offline {
+            SendQueueStatus::Offline
+        } else if let Some(until) = inner.backoff_until {
+            SendQueueStatus::BackingOff {
+                until: timestamp_in(until.saturating_duration_since(Instant::now())),
+                consecutive_failures: inner.consecutive_failures,
+            }
+        } else {
+            SendQueueStatus::Running
+        };
+
+        self.status.set_if_not_eq(status);
+    }
+}
+
+/// Returns a random delay to wait before a room resumes sending, after the
+/// network came back.
+pub(super) fn ramp_up_delay(room_id: &RoomId) -> Duration {
+    // A `RandomState` is seeded randomly every time it's created, which is enough
+    // to spread the rooms over the ramp-up period.
+    let hash = RandomState::new().hash_one(room_id);
+    let max = MAX_RAMP_UP_DELAY.as_millis() as u64;
+    Duration::from_millis(hash % max)
+}
+
+/// Returns the timestamp at `delay` from now.
+fn timestamp_in(delay: Duration) -> MilliSecondsSinceUnixEpoch {
+    MilliSecondsSinceUnixEpoch::from_system_time(SystemTime::now() + delay)
+        .unwrap_or_else(MilliSecondsSinceUnixEpoch::now)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use assert_matches2::assert_matches;
+
+    use super::{PauseDecision, SendQueuePause, SendQueueStatus, INITIAL_BACKOFF, MAX_BACKOFF};
+
+    #[test]
+    fn test_backoff_grows_exponentially() {
+        let pause = SendQueuePause::new(true);
+        assert_eq!(pause.status(), SendQueueStatus::Running);
+
+        pause.record_transient_failure(None);
+        assert_matches!(pause.decide(), PauseDecision::WaitFor(delay));
+        assert!(delay <= INITIAL_BACKOFF);
+        assert_matches!(
+            pause.status(),
+            SendQueueStatus::BackingOff { consecutive_failures: 1, .. }
+        );
+
+        pause.record_transient_failure(None);
+        assert_matches!(pause.decide(), PauseDecision::WaitFor(delay));
+        assert!(delay > INITIAL_BACKOFF);
+        assert!(delay <= INITIAL_BACKOFF * 2);
+
+        for _ in 0..20 {
+            pause.record_transient_failure(None);
+        }
+        assert_matches!(pause.decide(), PauseDecision::WaitFor(delay));
+        assert!(delay <= MAX_BACKOFF);
+
+        // A success resets everything.
+        pause.record_success();
+        assert_matches!(pause.decide(), PauseDecision::Proceed);
+        assert_eq!(pause.status(), SendQueueStatus::Running);
+    }
+
+    #[test]
+    fn test_retry_after_is_respected() {
+        let pause = SendQueuePause::new(true);
+
+        pause.record_transient_failure(Some(Duration::from_secs(10)));
+        assert_matches!(pause.decide(), PauseDecision::WaitFor(delay));
+        assert!(delay > Duration::from_secs(9));
+    }
+
+    #[test]
+    fn test_offline_takes_precedence() {
+        let pause = SendQueuePause::new(true);
+
+        pause.record_transient_failure(None);
+        assert!(pause.set_offline(true));
+        assert!(!pause.set_offline(true));
+        assert_matches!(pause.decide(), PauseDecision::WaitForWakeUp);
+        assert_eq!(pause.status(), SendQueueStatus::Offline);
+
+        // Coming back online clears the backoff.
+        assert!(pause.set_offline(false));
+        assert_matches!(pause.decide(), PauseDecision::Proceed);
+        assert_eq!(pause.status(), SendQueueStatus::Running);
+
+        // A disabled queue is reported as such.
+        pause.set_globally_enabled(false);
+        assert_eq!(pause.status(), SendQueueStatus::Disabled);
+    }
+}
@@ -0,0 +1,301 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Fine-grained tracking of the sending status of each queued request.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use eyeball::{SharedObservable, Subscriber};
+use matrix_sdk_base::store::QueueWedgeError;
+use ruma::{MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedTransactionId, TransactionId};
+
+use crate::TransmissionProgress;
+
+/// Which step of sending a request is being performed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SendingStep {
+    /// A media (file or thumbnail) attached to the event is being uploaded.
+    UploadingMedia,
+
+    /// The event itself is being sent.
+    SendingEvent,
+}
+
+/// The sending status of a single request that's been pushed into a
+/// [`super::RoomSendQueue`].
+///
+/// The status can be observed with [`super::SendHandle::subscribe_to_status`].
+#[derive(Clone, Debug)]
+pub enum SendRequestStatus {
+    /// The request is waiting in the queue, and will be sent when it's its
+    /// turn.
+    Queued,
+
+    /// The request is being sent at the moment.
+    SendingAttempt {
+        /// The number of the current attempt, starting at 1.
+        attempt: u32,
+
+        /// When did this attempt start?
+        started_at: MilliSecondsSinceUnixEpoch,
+
+        /// Which step of the request is being performed.
+        ///
+        /// Media events go through one or several
+        /// [`SendingStep::UploadingMedia`] steps, before the event
+        /// itself is sent.
+        step: SendingStep,
+    },
+
+    /// The last attempt failed with a recoverable error; the request is kept
+    /// in the queue and will be retried.
+    WaitingRetry {
+        /// When the request will be retried at the earliest.
+        ///
+        /// `None` if the retry will only happen after the room's send queue
+        /// has been re-enabled.
+        until: Option<MilliSecondsSinceUnixEpoch>,
+    },
+
+    /// The request failed with an unrecoverable error, and won't be retried
+    /// unless it's manually unwedged.
+    Wedged {
+        /// The reason why the request has been wedged.
+        reason: QueueWedgeError,
+    },
+
+    /// The event has been sent to the server.
+    Sent {
+        /// The event id returned by the server.
+        event_id: OwnedEventId,
+    },
+
+    /// The request has been aborted by the user, with
+    /// [`super::SendHandle::abort`].
+    ///
+    /// If the event was being sent at the time, it may still reach the
+    /// server, in which case it will be redacted.
+    Aborted,
+}
+
+/// Per-request bookkeeping for the [`StatusTracker`].
+struct TrackedRequest {
+    /// The observable status for this request.
+    status: SharedObservable<SendRequestStatus>,
+
+    /// The number of attempts made for each underlying request, keyed by the
+    /// underlying request's transaction id.
+    attempts: HashMap<OwnedTransactionId, u32>,
+
+    /// The sizes of the media attached to this request, if any.
+    media_sizes: Option<MediaUploadSizes>,
+}
+
+/// The sizes of the media attached to an event, to report the progress of
+/// the thumbnail and file uploads as a single progress.
+#[derive(Clone, Debug)]
+pub(super) struct MediaUploadSizes {
+    /// The transaction id of the thumbnail upload request, along with the
+    /// size of the thumbnail, if there's one.
+    pub thumbnail: Option<(OwnedTransactionId, usize)>,
+
+    /// The size of the file.
+    pub file: usize,
+}
+
+/// Keeps track of the [`SendRequestStatus`] of all the requests in a room's
+/// send queue.
+///
+/// Statuses are keyed by the transaction id observers know about, i.e. the
+/// transaction id of the event for a media upload, not the one of the upload
+/// requests.
+///
+/// This is only kept in memory: after a restart, statuses are re-seeded from
+/// the persisted queued requests (see [`super::QueueStorage::local_echoes`]).
+#[derive(Clone, Default)]
+pub(super) struct StatusTracker {
+    requests: Arc<Mutex<HashMap<OwnedTransactionId, TrackedRequest>>>,
+}
+
+impl StatusTracker {
+    /// Start tracking a request with the given initial status, or reset the
+    /// status of an already tracked request.
+    pub fn track(&self, transaction_id: &TransactionId, status: SendRequestStatus) {
+        let mut requests = self.requests.lock().unwrap();
+
+        if let Some(tracked) = requests.get(transaction_id) {
+            tracked.status.set(status);
+        } else {
+            requests.insert(
+                transaction_id.to_owned(),
+                TrackedRequest {
+                    status: SharedObservable::new(status),
+                    attempts: Default::default(),
+                    media_sizes: None,
+                },
+            );
+        }
+    }
+
+    /// Start tracking a request with the given initial status, unless it's
+    /// already tracked.
+    pub fn track_if_missing(&self, transaction_id: &TransactionId, status: SendRequestStatus) {
+        self.requests.lock().unwrap().entry(transaction_id.to_owned()).or_insert_with(|| {
+            TrackedRequest {
+                status: SharedObservable::new(status),
+                attempts: Default::default(),
+                media_sizes: None,
+            }
+        });
+    }
+
+    /// Subscribe to the status of a request, if it's still tracked.
+    pub fn subscribe(
+        &self,
+        transaction_id: &TransactionId,
+    ) -> Option<Subscriber<SendRequestStatus>> {
+        self.requests.lock().unwrap().get(transaction_id).map(|tracked| tracked.status.subscribe())
+    }
+
+    /// Mark a new attempt at sending the underlying request `request_txn`, on
+    /// behalf of the request observed as `transaction_id`.
+    ///
+    /// Returns the number of the attempt.
+    pub fn start_attempt(
+        &self,
+        transaction_id: &TransactionId,
+        request_txn: &TransactionId,
+        step: SendingStep,
+    ) -> u32 {
+        let mut requests = self.requests.lock().unwrap();
+
+        let tracked = requests.entry(transaction_id.to_owned()).or_insert_with(|| TrackedRequest {
+            status: SharedObservable::new(SendRequestStatus::Queued),
+            attempts: Default::default(),
+            media_sizes: None,
+        });
+
+        let attempt = tracked.attempts.entry(request_txn.to_owned()).or_default();
+        *attempt += 1;
+        let attempt = *attempt;
+
+        tracked.status.set(SendRequestStatus::SendingAttempt {
+            attempt,
+            started_at: MilliSecondsSinceUnixEpoch::now(),
+            step,
+        });
+
+        attempt
+    }
+
+    /// Update the status of a tracked request.
+    ///
+    /// Does nothing if the request isn't tracked anymore.
+    pub fn set(&self, transaction_id: &TransactionId, status: SendRequestStatus) {
+        if let Some(tracked) = self.requests.lock().unwrap().get(transaction_id) {
+            tracked.status.set(status);
+        }
+    }
+
+    /// Remember the sizes of the media attached to a tracked request.
+    ///
+    /// Does nothing if the request isn't tracked anymore.
+    pub fn set_media_sizes(&self, transaction_id: &TransactionId, sizes: MediaUploadSizes) {
+        if let Some(tracked) = self.requests.lock().unwrap().get_mut(transaction_id) {
+            tracked.media_sizes = Some(sizes);
+        }
+    }
+
+    /// Compute the progress of uploading all the media attached to the request
+    /// observed as `transaction_id`, given the progress of the underlying
+    /// upload request `request_txn`.
+    ///
+    /// If the sizes of the media aren't known (e.g. after a restart), the
+    /// progress of the underlying upload request is returned as is.
+    pub fn media_upload_progress(
+        &self,
+        transaction_id: &TransactionId,
+        request_txn: &TransactionId,
+        progress: TransmissionProgress,
+    ) -> TransmissionProgress {
+        let requests = self.requests.lock().unwrap();
+
+        let Some(sizes) = requests.get(transaction_id).and_then(|t| t.media_sizes.as_ref()) else {
+            return progress;
+        };
+
+        match &sizes.thumbnail {
+            // The thumbnail is uploaded first, then the file.
+            Some((thumbnail_txn, _)) if thumbnail_txn == request_txn => TransmissionProgress {
+                current: progress.current,
+                total: progress.total + sizes.file,
+            },
+            Some((_, thumbnail_size)) => TransmissionProgress {
+                current: thumbnail_size + progress.current,
+                total: thumbnail_size + progress.total,
+            },
+            None => progress,
+        }
+    }
+
+    /// Set the terminal status of a request, and stop tracking it.
+    ///
+    /// Existing subscribers will still observe the terminal status.
+    pub fn finish(&self, transaction_id: &TransactionId, status: SendRequestStatus) {
+        if let Some(tracked) = self.requests.lock().unwrap().remove(transaction_id) {
+            tracked.status.set(status);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ruma::TransactionId;
+
+    use super::{MediaUploadSizes, SendRequestStatus, StatusTracker};
+    use crate::TransmissionProgress;
+
+    #[test]
+    fn test_media_upload_progress_combines_thumbnail_and_file() {
+        let statuses = StatusTracker::default();
+        let event_txn = TransactionId::new();
+        let thumbnail_txn = TransactionId::new();
+        let file_txn = TransactionId::new();
+
+        let progress = |current, total| TransmissionProgress { current, total };
+
+        // Without known sizes, the progress is reported as is.
+        statuses.track(&event_txn, SendRequestStatus::Queued);
+        let p = statuses.media_upload_progress(&event_txn, &file_txn, progress(5, 10));
+        assert_eq!((p.current, p.total), (5, 10));
+
+        statuses.set_media_sizes(
+            &event_txn,
+            MediaUploadSizes { thumbnail: Some((thumbnail_txn.clone(), 10)), file: 90 },
+        );
+
+        // The thumbnail is uploaded first.
+        let p = statuses.media_upload_progress(&event_txn, &thumbnail_txn, progress(5, 10));
+        assert_eq!((p.current, p.total), (5, 100));
+
+        // Then the file.
+        let p = statuses.media_upload_progress(&event_txn, &file_txn, progress(0, 90));
+        assert_eq!((p.current, p.total), (10, 100));
+        let p = statuses.media_upload_progress(&event_txn, &file_txn, progress(90, 90));
+        assert_eq!((p.current, p.total), (100, 100));
+    }
+}
@@ -46,13 +46,13 @@ use ruma::{
 };
 use tracing::{debug, error, instrument, trace, warn, Span};
 
-use super::{QueueStorage, RoomSendQueue, RoomSendQueueError};
+use super::{status::MediaUploadSizes, QueueStorage, RoomSendQueue, RoomSendQueueError};
 use crate::{
     attachment::{AttachmentConfig, Thumbnail},
     room::edit::update_media_caption,
     send_queue::{
         LocalEcho, LocalEchoContent, MediaHandles, RoomSendQueueStorageError, RoomSendQueueUpdate,
-        SendHandle,
+        SendHandle, SendRequestStatus,
     },
     Client, Media, Room,
 };
@@ -218,6 +218,9 @@ impl RoomSendQueue {
 
         let file_media_request = Media::make_local_file_media_request(&upload_file_txn);
 
+        let file_size = data.len();
+        let thumbnail_size = config.thumbnail.as_ref().map(|thumbnail| thumbnail.data.len());
+
         let MediaCacheResult { upload_thumbnail_txn, event_thumbnail_info, queue_thumbnail_info } =
             RoomSendQueue::cache_media(&room, data, config.thumbnail.take(), &file_media_request)
                 .await?;
@@ -258,6 +261,15 @@ impl RoomSendQueue {
 
         trace!("manager sends a media to the background task");
 
+        self.inner.statuses.track(&send_event_txn, SendRequestStatus::Queued);
+        self.inner.statuses.set_media_sizes(
+            &send_event_txn,
+            MediaUploadSizes {
+                thumbnail: upload_thumbnail_txn.clone().zip(thumbnail_size),
+                file: file_size,
+            },
+        );
+
         self.inner.notifier.notify_one();
 
         let send_handle = SendHandle {
@@ -391,6 +403,8 @@ impl RoomSendQueue {
 
         trace!("manager sends a gallery to the background task");
 
+        self.inner.statuses.track(&send_event_txn, SendRequestStatus::Queued);
+
         self.inner.notifier.notify_one();
 
         let send_handle = SendHandle {
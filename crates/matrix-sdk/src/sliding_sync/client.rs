@@ -176,6 +176,7 @@ impl SlidingSyncResponseProcessor {
         {
             // Some new keys might have been received, so trigger a backup if needed.
             self.client.encryption().backups().maybe_trigger_backup();
+            self.client.encryption().dehydrated_devices().maybe_trigger_rotation();
 
             to_device_events
         } else {
@@ -155,6 +155,8 @@ impl Client {
         // Some new keys might have been received, so trigger a backup if needed.
         #[cfg(feature = "e2e-encryption")]
         self.encryption().backups().maybe_trigger_backup();
+        #[cfg(feature = "e2e-encryption")]
+        self.encryption().dehydrated_devices().maybe_trigger_rotation();
 
         self.call_sync_response_handlers(&response).await?;
 
@@ -175,7 +177,11 @@ impl Client {
         let BaseSyncResponse { rooms, presence, account_data, to_device, notifications } = response;
 
         let now = Instant::now();
+        // Refresh the media preview config first, so the event handlers see the new
+        // value.
+        self.account().handle_media_preview_config_update(account_data).await;
         self.handle_sync_events(HandlerKind::GlobalAccountData, None, account_data).await?;
+        self.handle_invite_policy_update(account_data).await;
         self.handle_sync_events(HandlerKind::Presence, None, presence).await?;
         self.handle_sync_to_device_events(to_device).await?;
 
@@ -202,6 +208,8 @@ impl Client {
                 ambiguity_changes: _,
             } = room_info;
 
+            self.account().handle_own_member_events(state, &timeline.events);
+
             let room = Some(&room);
             self.handle_sync_events(HandlerKind::RoomAccountData, room, account_data).await?;
             self.handle_sync_state_events(room, state).await?;
@@ -236,6 +244,9 @@ impl Client {
                 continue;
             };
 
+            // Classify the invite before it's surfaced anywhere.
+            self.apply_invite_filter(&room).await;
+
             self.send_room_update(room_id, || RoomUpdate::Invited {
                 room: room.clone(),
                 updates: room_info.clone(),
@@ -268,6 +279,11 @@ impl Client {
         let mut futures = Vec::new();
         for handler in &*self.notification_handlers().await {
             for (room_id, room_notifications) in notifications {
+                // Filtered invites must not notify the user.
+                if self.is_invite_filtered(room_id) {
+                    continue;
+                }
+
                 let Some(room) = self.get_room(room_id) else {
                     warn!(?room_id, "Can't call notification handler, room not found");
                     continue;
@@ -287,6 +303,10 @@ impl Client {
 
         debug!("Ran notification handlers in {:?}", now.elapsed());
 
+        // The homeserver is reachable, retry the updates of the `m.direct` account data
+        // that couldn't be uploaded before.
+        self.account().retry_pending_direct_updates().await;
+
         Ok(())
     }
 
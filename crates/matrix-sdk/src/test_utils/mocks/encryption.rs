@@ -50,6 +50,9 @@ use crate::{
     Client,
 };
 
+const DEHYDRATED_DEVICE_PATH: &str =
+    r"^/_matrix/client/unstable/org.matrix.msc3814.v1/dehydrated_device$";
+
 /// Stores pending to-device messages for each user and device.
 /// To be used with [`MatrixMockServer::capture_put_to_device_traffic`].
 pub type PendingToDeviceMessages =
@@ -272,6 +275,95 @@ impl MatrixMockServer {
             .await;
     }
 
+    /// Mock up the dehydrated device endpoints of [MSC3814], behaving like a
+    /// homeserver storing a single dehydrated device for the user.
+    ///
+    /// The device keys of the uploaded dehydrated devices are served back for
+    /// incoming `keys/query`, so this should be used together with
+    /// [`MatrixMockServer::mock_crypto_endpoints_preset`]. The dehydrated
+    /// devices never receive any to-device events.
+    ///
+    /// [MSC3814]: https://github.com/matrix-org/matrix-spec-proposals/pull/3814
+    pub async fn mock_dehydrated_device_endpoints(&self) {
+        let dehydrated_device: Arc<Mutex<Option<(OwnedDeviceId, serde_json::Value)>>> =
+            Default::default();
+
+        Mock::given(method("GET"))
+            .and(path_regex(DEHYDRATED_DEVICE_PATH))
+            .respond_with({
+                let dehydrated_device = dehydrated_device.clone();
+                move |_: &Request| match &*dehydrated_device.lock().unwrap() {
+                    Some((device_id, device_data)) => {
+                        ResponseTemplate::new(200).set_body_json(json!({
+                            "device_id": device_id,
+                            "device_data": device_data,
+                        }))
+                    }
+                    None => ResponseTemplate::new(404).set_body_json(json!({
+                        "errcode": "M_NOT_FOUND",
+                        "error": "No dehydrated device",
+                    })),
+                }
+            })
+            .mount(&self.server)
+            .await;
+
+        Mock::given(method("PUT"))
+            .and(path_regex(DEHYDRATED_DEVICE_PATH))
+            .respond_with({
+                let dehydrated_device = dehydrated_device.clone();
+                let keys = self.keys.clone();
+                move |req: &Request| {
+                    #[derive(Debug, serde::Deserialize)]
+                    struct Parameters {
+                        device_id: OwnedDeviceId,
+                        device_data: serde_json::Value,
+                        device_keys: Raw<DeviceKeys>,
+                    }
+
+                    let params: Parameters = req.body_json().unwrap();
+                    let device_keys = params.device_keys.deserialize().unwrap();
+
+                    keys.lock()
+                        .unwrap()
+                        .device
+                        .entry(device_keys.user_id)
+                        .or_default()
+                        .insert(params.device_id.to_string(), params.device_keys);
+
+                    *dehydrated_device.lock().unwrap() =
+                        Some((params.device_id.clone(), params.device_data));
+
+                    ResponseTemplate::new(200)
+                        .set_body_json(json!({ "device_id": params.device_id }))
+                }
+            })
+            .mount(&self.server)
+            .await;
+
+        Mock::given(method("DELETE"))
+            .and(path_regex(DEHYDRATED_DEVICE_PATH))
+            .respond_with(move |_: &Request| match dehydrated_device.lock().unwrap().take() {
+                Some((device_id, _)) => {
+                    ResponseTemplate::new(200).set_body_json(json!({ "device_id": device_id }))
+                }
+                None => ResponseTemplate::new(404).set_body_json(json!({
+                    "errcode": "M_NOT_FOUND",
+                    "error": "No dehydrated device",
+                })),
+            })
+            .mount(&self.server)
+            .await;
+
+        Mock::given(method("POST"))
+            .and(path_regex(
+                r"^/_matrix/client/unstable/org.matrix.msc3814.v1/dehydrated_device/.*/events$",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "events": [] })))
+            .mount(&self.server)
+            .await;
+    }
+
     /// Creates a response handler for mocking encrypted to-device message
     /// requests.
     ///
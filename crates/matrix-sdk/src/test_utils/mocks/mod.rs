@@ -1083,6 +1083,14 @@ impl MatrixMockServer {
         self.mock_endpoint(mock, WhoAmIEndpoint).expect_default_access_token()
     }
 
+    /// Creates a prebuilt mock for the endpoint used to request an OpenID
+    /// token.
+    pub fn mock_request_openid_token(&self) -> MockEndpoint<'_, RequestOpenIdTokenEndpoint> {
+        let mock = Mock::given(method("POST"))
+            .and(path_regex(r"^/_matrix/client/v3/user/.*/openid/request_token"));
+        self.mock_endpoint(mock, RequestOpenIdTokenEndpoint).expect_default_access_token()
+    }
+
     /// Creates a prebuilt mock for the endpoint used to publish end-to-end
     /// encryption keys.
     pub fn mock_upload_keys(&self) -> MockEndpoint<'_, UploadKeysEndpoint> {
@@ -1211,6 +1219,29 @@ impl MatrixMockServer {
         self.mock_endpoint(mock, ReadMarkersEndpoint).expect_default_access_token()
     }
 
+    /// Create a prebuilt mock for the endpoint used to set global account
+    /// data.
+    pub fn mock_set_global_account_data(
+        &self,
+        data_type: GlobalAccountDataEventType,
+    ) -> MockEndpoint<'_, SetGlobalAccountDataEndpoint> {
+        let mock = Mock::given(method("PUT"))
+            .and(path_regex(format!("^/_matrix/client/v3/user/[^/]*/account_data/{data_type}$")));
+        self.mock_endpoint(mock, SetGlobalAccountDataEndpoint).expect_default_access_token()
+    }
+
+    /// Create a prebuilt mock for the unstable endpoint used to delete global
+    /// account data, as defined in MSC3391.
+    pub fn mock_delete_global_account_data(
+        &self,
+        data_type: GlobalAccountDataEventType,
+    ) -> MockEndpoint<'_, DeleteGlobalAccountDataEndpoint> {
+        let mock = Mock::given(method("DELETE")).and(path_regex(format!(
+            "^/_matrix/client/unstable/org.matrix.msc3391/user/[^/]*/account_data/{data_type}$"
+        )));
+        self.mock_endpoint(mock, DeleteGlobalAccountDataEndpoint).expect_default_access_token()
+    }
+
     /// Create a prebuilt mock for the endpoint used to set room account data.
     pub fn mock_set_room_account_data(
         &self,
@@ -3027,6 +3058,22 @@ impl<'a> MockEndpoint<'a, WhoAmIEndpoint> {
     }
 }
 
+/// A prebuilt mock for `POST /user/{userId}/openid/request_token` request.
+pub struct RequestOpenIdTokenEndpoint;
+
+impl<'a> MockEndpoint<'a, RequestOpenIdTokenEndpoint> {
+    /// Returns a successful response with the given access token, valid for
+    /// one hour.
+    pub fn ok(self, access_token: &str) -> MatrixMock<'a> {
+        self.respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "access_token": access_token,
+            "token_type": "Bearer",
+            "matrix_server_name": "example.org",
+            "expires_in": 3600,
+        })))
+    }
+}
+
 /// A prebuilt mock for `POST /keys/upload` request.
 pub struct UploadKeysEndpoint;
 
@@ -3429,6 +3476,42 @@ impl<'a> MockEndpoint<'a, ReadMarkersEndpoint> {
     }
 }
 
+/// A prebuilt mock for `PUT /user/{userId}/account_data/{type}` request.
+pub struct SetGlobalAccountDataEndpoint;
+
+impl<'a> MockEndpoint<'a, SetGlobalAccountDataEndpoint> {
+    /// Ensures that the body of the request is a superset of the provided
+    /// `body` parameter.
+    pub fn body_matches_partial_json(self, body: Value) -> Self {
+        Self { mock: self.mock.and(body_partial_json(body)), ..self }
+    }
+
+    /// Returns a successful empty response.
+    pub fn ok(self) -> MatrixMock<'a> {
+        self.respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+    }
+}
+
+/// A prebuilt mock for the unstable
+/// `DELETE /user/{userId}/account_data/{type}` request.
+pub struct DeleteGlobalAccountDataEndpoint;
+
+impl<'a> MockEndpoint<'a, DeleteGlobalAccountDataEndpoint> {
+    /// Returns a successful empty response.
+    pub fn ok(self) -> MatrixMock<'a> {
+        self.respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+    }
+
+    /// Returns a `M_UNRECOGNIZED` response, as returned by homeservers that
+    /// don't support the endpoint.
+    pub fn unrecognized(self) -> MatrixMock<'a> {
+        self.respond_with(ResponseTemplate::new(404).set_body_json(json!({
+            "errcode": "M_UNRECOGNIZED",
+            "error": "Unrecognized request",
+        })))
+    }
+}
+
 /// A prebuilt mock for `PUT /user/{userId}/rooms/{roomId}/account_data/{type}`
 /// request.
 pub struct RoomAccountDataEndpoint;
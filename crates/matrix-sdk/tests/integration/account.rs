@@ -1,7 +1,38 @@
-use matrix_sdk_test::async_test;
-use serde_json::json;
+use std::{
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use assert_matches2::assert_matches;
+use eyeball::SharedObservable;
+use futures_util::{future::join, pin_mut};
+use matrix_sdk::{
+    image_pack::PackImage, media::MediaFormat, test_utils::mocks::MatrixMockServer, Error,
+    TransmissionProgress,
+};
+use matrix_sdk_test::{
+    async_test, event_factory::EventFactory, GlobalAccountDataTestEvent, JoinedRoomBuilder,
+    StateTestEvent,
+};
+use ruma::{
+    api::client::{account::ThirdPartyIdRemovalStatus, error::ErrorKind},
+    events::{
+        ignored_user_list::{IgnoredUser, IgnoredUserListEventContent},
+        GlobalAccountDataEventType,
+    },
+    mxc_uri, owned_mxc_uri, owned_user_id, room_id,
+    thirdparty::Medium,
+    user_id, ClientSecret, SessionId,
+};
+use serde_json::{json, Value as JsonValue};
+use stream_assert::{assert_next_eq, assert_pending};
+use tokio::io::{AsyncRead, ReadBuf};
+use url::Url;
 use wiremock::{
-    matchers::{method, path},
+    matchers::{body_json, body_partial_json, method, path, path_regex},
     Mock, Request, ResponseTemplate,
 };
 
@@ -59,3 +90,904 @@ async fn test_account_deactivation() {
         assert!(client.account().deactivate(None, None, true).await.is_ok());
     }
 }
+
+#[async_test]
+async fn test_image_pack_round_trip() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let room_id = room_id!("!emotes:localhost");
+    let room = server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id).add_state_event(StateTestEvent::Custom(json!({
+                "content": {
+                    "images": {
+                        "cat": { "url": "mxc://localhost/cat", "body": "A cat" },
+                        "wave": { "url": "mxc://localhost/other_wave" },
+                    },
+                    "pack": { "display_name": "Room pack", "usage": ["sticker"] },
+                },
+                "event_id": "$room_pack",
+                "origin_server_ts": 151800140,
+                "sender": "@example:localhost",
+                "state_key": "main",
+                "type": "im.ponies.room_emotes",
+            }))),
+        )
+        .await;
+
+    // The homeserver only knows the unstable event type, and stores what it
+    // receives.
+    let stored = Arc::new(Mutex::new(json!({
+        "images": {
+            ":wave:": { "url": "mxc://localhost/wave", "usage": "emoticon" },
+        },
+        "pack": { "display_name": "My pack" },
+        "custom_field": "custom value",
+    })));
+
+    server
+        .mock_global_account_data()
+        .not_found(client.user_id().unwrap(), GlobalAccountDataEventType::from("m.image_pack"))
+        .mount()
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/v3/user/.*/account_data/im.ponies.user_emotes$"))
+        .respond_with({
+            let stored = stored.clone();
+            move |_: &Request| ResponseTemplate::new(200).set_body_json(&*stored.lock().unwrap())
+        })
+        .mount(server.server())
+        .await;
+
+    server
+        .mock_set_global_account_data("im.ponies.user_emotes".into())
+        .respond_with({
+            let stored = stored.clone();
+            move |request: &Request| {
+                *stored.lock().unwrap() = request.body_json::<JsonValue>().unwrap();
+                ResponseTemplate::new(200).set_body_json(json!({}))
+            }
+        })
+        .expect(3)
+        .mount()
+        .await;
+
+    let image_packs = client.account().image_packs();
+
+    let pack = image_packs.get().await.unwrap();
+    assert_eq!(pack.images["wave"].url, mxc_uri!("mxc://localhost/wave"));
+    assert_eq!(pack.pack.display_name.as_deref(), Some("My pack"));
+
+    // Add an image.
+    let mut party = PackImage::new(owned_mxc_uri!("mxc://localhost/party"));
+    party.body = Some("Party".to_owned());
+    image_packs.add_image("party", party).await.unwrap();
+
+    let pack = image_packs.get().await.unwrap();
+    assert_eq!(pack.images.len(), 2);
+    assert_eq!(pack.images["party"].body.as_deref(), Some("Party"));
+    // The unknown fields survived the edit.
+    assert_eq!(stored.lock().unwrap()["custom_field"], "custom value");
+
+    // Import the pack of the room, without overriding the existing image.
+    let num_imported = image_packs.import_room_pack(&room, "main").await.unwrap();
+    assert_eq!(num_imported, 1);
+
+    let pack = image_packs.get().await.unwrap();
+    assert_eq!(pack.images["wave"].url, mxc_uri!("mxc://localhost/wave"));
+    assert_eq!(pack.images["cat"].url, mxc_uri!("mxc://localhost/cat"));
+    assert_eq!(pack.stickers().map(|(shortcode, _)| shortcode).collect::<Vec<_>>(), ["cat"]);
+
+    // Remove an image.
+    assert!(image_packs.remove_image("wave").await.unwrap());
+    // Removing an image that doesn't exist doesn't write the pack.
+    assert!(!image_packs.remove_image("wave").await.unwrap());
+
+    let pack = image_packs.get().await.unwrap();
+    assert_eq!(pack.images.keys().map(String::as_str).collect::<Vec<_>>(), ["cat", "party"]);
+}
+
+#[async_test]
+async fn test_ignore_and_unignore_users() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+    let account = client.account();
+    let own_user_id = client.user_id().unwrap().to_owned();
+
+    let alice = owned_user_id!("@alice:localhost");
+    let bob = owned_user_id!("@bob:localhost");
+    let carol = owned_user_id!("@carol:localhost");
+
+    server
+        .mock_sync()
+        .ok_and_run(&client, |builder| {
+            builder.add_global_account_data_event(GlobalAccountDataTestEvent::Custom(json!({
+                "type": "m.ignored_user_list",
+                "content": {
+                    "ignored_users": {
+                        "@alice:localhost": {},
+                    },
+                },
+            })));
+        })
+        .await;
+
+    {
+        // Nothing is uploaded when the users are already ignored, or when the list
+        // contains the logged-in user.
+        let _guard = server
+            .mock_set_global_account_data(GlobalAccountDataEventType::IgnoredUserList)
+            .ok()
+            .never()
+            .mount_as_scoped()
+            .await;
+
+        account.ignore_users(&[alice.clone()]).await.unwrap();
+        account.unignore_users(&[bob.clone()]).await.unwrap();
+        assert_matches!(
+            account.ignore_users(&[bob.clone(), own_user_id]).await,
+            Err(Error::CantIgnoreLoggedInUser)
+        );
+    }
+
+    {
+        // All the users are ignored at once.
+        let _guard = server
+            .mock_set_global_account_data(GlobalAccountDataEventType::IgnoredUserList)
+            .body_matches_partial_json(json!({
+                "ignored_users": {
+                    "@alice:localhost": {},
+                    "@bob:localhost": {},
+                    "@carol:localhost": {},
+                },
+            }))
+            .ok()
+            .mock_once()
+            .mount_as_scoped()
+            .await;
+
+        account.ignore_users(&[alice.clone(), bob.clone(), carol.clone()]).await.unwrap();
+    }
+
+    server
+        .mock_sync()
+        .ok_and_run(&client, |builder| {
+            builder.add_global_account_data_event(GlobalAccountDataTestEvent::Custom(json!({
+                "type": "m.ignored_user_list",
+                "content": {
+                    "ignored_users": {
+                        "@alice:localhost": {},
+                        "@bob:localhost": {},
+                        "@carol:localhost": {},
+                    },
+                },
+            })));
+        })
+        .await;
+
+    {
+        // The users are unignored at once.
+        let _guard = server
+            .mock_set_global_account_data(GlobalAccountDataEventType::IgnoredUserList)
+            .body_matches_partial_json(json!({
+                "ignored_users": {
+                    "@bob:localhost": {},
+                },
+            }))
+            .ok()
+            .mock_once()
+            .mount_as_scoped()
+            .await;
+
+        account.unignore_users(&[alice, carol]).await.unwrap();
+    }
+}
+
+#[async_test]
+async fn test_is_user_ignored() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+    let account = client.account();
+
+    let alice = owned_user_id!("@alice:localhost");
+    let bob = owned_user_id!("@bob:localhost");
+
+    // Nobody is ignored when there is no ignored user list.
+    assert!(account.ignored_users().await.unwrap().is_empty());
+    assert!(!account.is_user_ignored(&alice).await.unwrap());
+
+    server
+        .mock_sync()
+        .ok_and_run(&client, |builder| {
+            builder.add_global_account_data_event(GlobalAccountDataTestEvent::Custom(json!({
+                "type": "m.ignored_user_list",
+                "content": {
+                    "ignored_users": {
+                        "@alice:localhost": {},
+                    },
+                },
+            })));
+        })
+        .await;
+
+    assert_eq!(account.ignored_users().await.unwrap(), [alice.clone()]);
+    assert!(account.is_user_ignored(&alice).await.unwrap());
+    assert!(!account.is_user_ignored(&bob).await.unwrap());
+
+    // A malformed ignored user list is considered empty.
+    server
+        .mock_sync()
+        .ok_and_run(&client, |builder| {
+            builder.add_global_account_data_event(GlobalAccountDataTestEvent::Custom(json!({
+                "type": "m.ignored_user_list",
+                "content": {
+                    "ignored_users": ["@alice:localhost"],
+                },
+            })));
+        })
+        .await;
+
+    assert!(account.ignored_users().await.unwrap().is_empty());
+    assert!(!account.is_user_ignored(&alice).await.unwrap());
+}
+
+#[async_test]
+async fn test_update_account_data() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+    let account = client.account();
+
+    // The homeserver stores what it receives.
+    let stored = Arc::new(Mutex::new(json!({
+        "ignored_users": {
+            "@alice:localhost": {},
+        },
+    })));
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/v3/user/.*/account_data/m.ignored_user_list$"))
+        .respond_with({
+            let stored = stored.clone();
+            move |_: &Request| {
+                // Let the concurrent updates race.
+                ResponseTemplate::new(200)
+                    .set_body_json(&*stored.lock().unwrap())
+                    .set_delay(Duration::from_millis(50))
+            }
+        })
+        .mount(server.server())
+        .await;
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/v3/user/.*/account_data/m.ignored_user_list$"))
+        .respond_with({
+            let stored = stored.clone();
+            move |request: &Request| {
+                *stored.lock().unwrap() = request.body_json().unwrap();
+                ResponseTemplate::new(200).set_body_json(json!({}))
+            }
+        })
+        .expect(2)
+        .mount(server.server())
+        .await;
+
+    let ignore = |user_id| {
+        account.update_account_data(move |content: Option<IgnoredUserListEventContent>| {
+            let mut content = content.unwrap_or_default();
+            content.ignored_users.insert(user_id, IgnoredUser::new());
+            Some(content)
+        })
+    };
+
+    // The concurrent updates don't clobber each other.
+    let (bob_result, carol_result) =
+        join(ignore(owned_user_id!("@bob:localhost")), ignore(owned_user_id!("@carol:localhost")))
+            .await;
+    bob_result.unwrap();
+    carol_result.unwrap();
+
+    assert_eq!(
+        *stored.lock().unwrap(),
+        json!({
+            "ignored_users": {
+                "@alice:localhost": {},
+                "@bob:localhost": {},
+                "@carol:localhost": {},
+            },
+        })
+    );
+
+    // Nothing is uploaded when the closure doesn't return a new content.
+    account
+        .update_account_data(|content: Option<IgnoredUserListEventContent>| {
+            assert_eq!(content.unwrap().ignored_users.len(), 3);
+            None
+        })
+        .await
+        .unwrap();
+
+    // Nothing is uploaded when the content didn't change either.
+    ignore(owned_user_id!("@alice:localhost")).await.unwrap();
+}
+
+#[async_test]
+async fn test_change_password_with_password() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+    let account = client.account();
+    let user_id = client.user_id().unwrap().to_string();
+
+    // The homeserver checks the current password, and rejects short new
+    // passwords.
+    let responder = |flows: JsonValue| {
+        let user_id = user_id.clone();
+
+        move |req: &Request| {
+            let uiaa_info = json!({
+                "flows": flows,
+                "params": {},
+                "session": "uiaa_session",
+            });
+
+            let body: JsonValue = req.body_json().unwrap();
+            let Some(auth) = body.get("auth") else {
+                return ResponseTemplate::new(401).set_body_json(uiaa_info);
+            };
+
+            assert_eq!(auth["type"], "m.login.password");
+            assert_eq!(auth["session"], "uiaa_session");
+            assert_eq!(auth["identifier"]["type"], "m.id.user");
+            assert_eq!(auth["identifier"]["user"], user_id);
+
+            if auth["password"] != "current_password" {
+                let mut uiaa_info = uiaa_info;
+                uiaa_info["errcode"] = "M_FORBIDDEN".into();
+                uiaa_info["error"] = "Invalid password".into();
+                return ResponseTemplate::new(401).set_body_json(uiaa_info);
+            }
+
+            if body["new_password"].as_str().unwrap().len() < 8 {
+                return ResponseTemplate::new(400).set_body_json(json!({
+                    "errcode": "M_WEAK_PASSWORD",
+                    "error": "Password too short",
+                }));
+            }
+
+            ResponseTemplate::new(200).set_body_json(json!({}))
+        }
+    };
+
+    {
+        let _scope = Mock::given(method("POST"))
+            .and(path_regex(r"/account/password$"))
+            .respond_with(responder(json!([
+                { "stages": ["m.login.email.identity"] },
+                { "stages": ["m.login.password"] },
+            ])))
+            .expect(6)
+            .mount_as_scoped(server.server())
+            .await;
+
+        account.change_password_with_password("current_password", "new_password").await.unwrap();
+
+        let result = account.change_password_with_password("wrong_password", "new_password").await;
+        assert_matches!(result, Err(Error::WrongPassword));
+
+        let result = account.change_password_with_password("current_password", "short").await;
+        assert_matches!(result, Err(Error::WeakPassword(message)));
+        assert_eq!(message, "Password too short");
+    }
+
+    // The homeserver requires another stage after the password, the request is not
+    // retried.
+    let _scope = Mock::given(method("POST"))
+        .and(path_regex(r"/account/password$"))
+        .respond_with(responder(json!([
+            { "stages": ["m.login.password", "m.login.email.identity"] },
+        ])))
+        .expect(1)
+        .mount_as_scoped(server.server())
+        .await;
+
+    let result = account.change_password_with_password("current_password", "new_password").await;
+    assert_matches!(result, Err(Error::PasswordAuthUnsupported(uiaa_info)));
+    assert_eq!(uiaa_info.session.as_deref(), Some("uiaa_session"));
+}
+
+#[async_test]
+async fn test_deactivate_with_password() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+    let account = client.account();
+
+    // The homeserver requires the password stage, then the dummy stage.
+    {
+        let _scope = Mock::given(method("POST"))
+            .and(path_regex(r"/account/deactivate$"))
+            .respond_with(|req: &Request| {
+                let body: JsonValue = req.body_json().unwrap();
+                assert_eq!(body["erase"], true);
+
+                let completed = match body["auth"]["type"].as_str() {
+                    None => json!([]),
+                    Some("m.login.password") => {
+                        assert_eq!(body["auth"]["password"], "password");
+                        assert_eq!(body["auth"]["session"], "uiaa_session");
+                        json!(["m.login.password"])
+                    }
+                    Some("m.login.dummy") => {
+                        assert_eq!(body["auth"]["session"], "uiaa_session");
+                        return ResponseTemplate::new(200).set_body_json(json!({
+                            "id_server_unbind_result": "no-support",
+                        }));
+                    }
+                    Some(stage) => panic!("unexpected stage: {stage}"),
+                };
+
+                ResponseTemplate::new(401).set_body_json(json!({
+                    "flows": [{ "stages": ["m.login.password", "m.login.dummy"] }],
+                    "completed": completed,
+                    "params": {},
+                    "session": "uiaa_session",
+                }))
+            })
+            .expect(3)
+            .mount_as_scoped(server.server())
+            .await;
+
+        account.deactivate_with_password("password", None, true).await.unwrap();
+    }
+
+    // The homeserver requires a stage that isn't supported, the info is returned to
+    // the caller.
+    let _scope = Mock::given(method("POST"))
+        .and(path_regex(r"/account/deactivate$"))
+        .respond_with(ResponseTemplate::new(401).set_body_json(json!({
+            "flows": [{ "stages": ["m.login.email.identity"] }],
+            "params": {},
+            "session": "uiaa_session",
+        })))
+        .expect(1)
+        .mount_as_scoped(server.server())
+        .await;
+
+    let result = account.deactivate_with_password("password", None, true).await;
+    assert_matches!(result, Err(Error::PasswordAuthUnsupported(uiaa_info)));
+    assert_eq!(uiaa_info.flows.len(), 1);
+    assert_eq!(uiaa_info.session.as_deref(), Some("uiaa_session"));
+}
+
+#[async_test]
+async fn test_submit_3pid_token() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+    let account = client.account();
+
+    let client_secret = ClientSecret::parse("client_secret").unwrap();
+    let sid = SessionId::parse("session_id").unwrap();
+    let submit_url = Url::parse(&format!("{}/submit_token", server.server().uri())).unwrap();
+
+    Mock::given(method("POST"))
+        .and(path("/submit_token"))
+        .and(body_json(json!({
+            "sid": "session_id",
+            "client_secret": "client_secret",
+            "token": "123456",
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "success": true })))
+        .expect(1)
+        .mount(server.server())
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/submit_token"))
+        .and(body_partial_json(json!({ "token": "000000" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "success": false })))
+        .expect(1)
+        .mount(server.server())
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/submit_token"))
+        .and(body_partial_json(json!({ "token": "expired" })))
+        .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+            "errcode": "M_THREEPID_AUTH_FAILED",
+            "error": "The session has expired",
+        })))
+        .expect(1)
+        .mount(server.server())
+        .await;
+
+    assert!(account.submit_3pid_token(&submit_url, &client_secret, &sid, "123456").await.unwrap());
+    assert!(!account.submit_3pid_token(&submit_url, &client_secret, &sid, "000000").await.unwrap());
+
+    let error =
+        account.submit_3pid_token(&submit_url, &client_secret, &sid, "expired").await.unwrap_err();
+    assert_eq!(error.client_api_error_kind(), Some(&ErrorKind::ThreepidAuthFailed));
+}
+
+#[async_test]
+async fn test_bind_and_unbind_3pid() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+    let account = client.account();
+
+    let client_secret = ClientSecret::parse("client_secret").unwrap();
+    let sid = SessionId::parse("session_id").unwrap();
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"/account/3pid/bind$"))
+        .and(body_json(json!({
+            "client_secret": "client_secret",
+            "id_server": "example.org",
+            "id_access_token": "identity_token",
+            "sid": "session_id",
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(server.server())
+        .await;
+
+    account.bind_3pid(&client_secret, &sid, "example.org", "identity_token").await.unwrap();
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"/account/3pid/unbind$"))
+        .and(body_json(json!({
+            "address": "alice@example.org",
+            "medium": "email",
+            "id_server": "example.org",
+        })))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({ "id_server_unbind_result": "success" })),
+        )
+        .expect(1)
+        .mount(server.server())
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"/account/3pid/unbind$"))
+        .and(body_json(json!({
+            "address": "alice@example.org",
+            "medium": "email",
+        })))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({ "id_server_unbind_result": "no-support" })),
+        )
+        .expect(1)
+        .mount(server.server())
+        .await;
+
+    let status =
+        account.unbind_3pid("alice@example.org", Medium::Email, Some("example.org")).await.unwrap();
+    assert_matches!(status, ThirdPartyIdRemovalStatus::Success);
+
+    let status = account.unbind_3pid("alice@example.org", Medium::Email, None).await.unwrap();
+    assert_matches!(status, ThirdPartyIdRemovalStatus::NoSupport);
+}
+
+#[async_test]
+async fn test_3pids_accessors_and_cache() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+    let account = client.account();
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"/account/3pid$"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "threepids": [
+                {
+                    "address": "alice@example.org",
+                    "medium": "email",
+                    "added_at": 1_535_176_800_000_u64,
+                    "validated_at": 1_535_176_800_000_u64,
+                },
+                {
+                    "address": "33123456789",
+                    "medium": "msisdn",
+                    "added_at": 1_535_336_848_756_u64,
+                    "validated_at": 1_535_337_848_756_u64,
+                },
+            ],
+        })))
+        .mount(server.server())
+        .await;
+
+    // Nothing is cached before the first request.
+    assert!(account.get_cached_3pids().await.unwrap().is_none());
+
+    let emails = account.email_addresses().await.unwrap();
+    assert_eq!(emails.len(), 1);
+    assert_eq!(emails[0].address, "alice@example.org");
+
+    let phone_numbers = account.phone_numbers().await.unwrap();
+    assert_eq!(phone_numbers.len(), 1);
+    assert_eq!(phone_numbers[0].address, "33123456789");
+
+    assert!(account.has_verified_email().await.unwrap());
+
+    let cached = account.get_cached_3pids().await.unwrap().unwrap();
+    assert_eq!(cached.len(), 2);
+
+    // The cache is invalidated when a 3PID is deleted.
+    Mock::given(method("POST"))
+        .and(path_regex(r"/account/3pid/delete$"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({ "id_server_unbind_result": "no-support" })),
+        )
+        .expect(1)
+        .mount(server.server())
+        .await;
+
+    account.delete_3pid("33123456789", Medium::Msisdn, None).await.unwrap();
+    assert!(account.get_cached_3pids().await.unwrap().is_none());
+
+    // And when a 3PID is added.
+    account.get_3pids().await.unwrap();
+    assert!(account.get_cached_3pids().await.unwrap().is_some());
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"/account/3pid/add$"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(server.server())
+        .await;
+
+    let client_secret = ClientSecret::parse("client_secret").unwrap();
+    let sid = SessionId::parse("session_id").unwrap();
+    account.add_3pid(&client_secret, &sid, None).await.unwrap();
+    assert!(account.get_cached_3pids().await.unwrap().is_none());
+}
+
+#[async_test]
+async fn test_subscribe_to_profile_changes() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+    let account = client.account();
+    let user_id = client.user_id().unwrap().to_owned();
+
+    let (initial, updates) = account.subscribe_to_profile_changes().await.unwrap();
+    assert_eq!(initial, (None, None));
+    pin_mut!(updates);
+    assert_pending!(updates);
+
+    // The profile is updated when it's changed locally.
+    Mock::given(method("PUT"))
+        .and(path_regex(r"/profile/.*/displayname$"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .mount(server.server())
+        .await;
+    Mock::given(method("PUT"))
+        .and(path_regex(r"/profile/.*/avatar_url$"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .mount(server.server())
+        .await;
+
+    account.set_display_name(Some("Alice")).await.unwrap();
+    assert_next_eq!(updates, (Some("Alice".to_owned()), None));
+
+    let avatar_url = owned_mxc_uri!("mxc://localhost/alice");
+    account.set_avatar_url(Some(&avatar_url)).await.unwrap();
+    assert_next_eq!(updates, (Some("Alice".to_owned()), Some(avatar_url.clone())));
+
+    // Setting the same value again doesn't yield an update.
+    account.set_display_name(Some("Alice")).await.unwrap();
+    assert_pending!(updates);
+
+    // The profile is updated when it's changed from another device.
+    let f = EventFactory::new();
+    server
+        .mock_sync()
+        .ok_and_run(&client, |builder| {
+            builder.add_joined_room(
+                JoinedRoomBuilder::new(room_id!("!room:localhost"))
+                    .add_timeline_event(f.member(&user_id).display_name("Alice 2")),
+            );
+        })
+        .await;
+    assert_next_eq!(updates, (Some("Alice 2".to_owned()), None));
+
+    // The membership changes of other users are ignored.
+    server
+        .mock_sync()
+        .ok_and_run(&client, |builder| {
+            builder.add_joined_room(
+                JoinedRoomBuilder::new(room_id!("!room:localhost"))
+                    .add_timeline_event(f.member(user_id!("@bob:localhost")).display_name("Bob")),
+            );
+        })
+        .await;
+    assert_pending!(updates);
+}
+
+#[async_test]
+async fn test_get_avatar_uses_the_media_cache() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+    let account = client.account();
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"/profile/.*/avatar_url$"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({ "avatar_url": "mxc://localhost/avatar" })),
+        )
+        .named("get_avatar_url")
+        .expect(2)
+        .mount(server.server())
+        .await;
+
+    server.mock_authed_media_download().ok_image().named("download").expect(2).mount().await;
+
+    // The first call fetches the avatar URL and downloads the avatar.
+    let avatar = account.get_avatar(MediaFormat::File).await.unwrap().unwrap();
+
+    // The second call with the same format doesn't make any request.
+    let cached_avatar = account.get_avatar(MediaFormat::File).await.unwrap().unwrap();
+    assert_eq!(cached_avatar, avatar);
+
+    // The cache can be bypassed.
+    let refetched_avatar = account.get_avatar_no_cache(MediaFormat::File).await.unwrap().unwrap();
+    assert_eq!(refetched_avatar, avatar);
+}
+
+#[async_test]
+async fn test_upload_avatar_from_reader() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+    let account = client.account();
+
+    let mxc_uri = mxc_uri!("mxc://localhost/avatar");
+    let data = b"hello world".to_vec();
+
+    // The content is streamed, and the avatar is set with the returned URI.
+    {
+        let (receiver, upload_mock) = server.mock_upload().ok_with_capture(mxc_uri);
+        let _upload_guard =
+            upload_mock.expect_mime_type("image/png").expect(1).mount_as_scoped().await;
+        let _set_avatar_guard = Mock::given(method("PUT"))
+            .and(path_regex(r"/profile/.*/avatar_url$"))
+            .and(body_json(json!({ "avatar_url": mxc_uri })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+            .expect(1)
+            .mount_as_scoped(server.server())
+            .await;
+
+        let uri = account
+            .upload_avatar_from_reader(
+                &mime::IMAGE_PNG,
+                std::io::Cursor::new(data.clone()),
+                Some(data.len() as u64),
+            )
+            .await
+            .unwrap();
+        assert_eq!(uri, mxc_uri.to_owned());
+        assert_eq!(receiver.await.unwrap(), data);
+    }
+
+    // An error of the reader aborts the upload, and the avatar isn't set.
+    struct FailingReader {
+        sent: bool,
+    }
+
+    impl AsyncRead for FailingReader {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            if self.sent {
+                return Poll::Ready(Err(io::Error::other("reader failed")));
+            }
+
+            self.sent = true;
+            buf.put_slice(b"hello");
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    server.mock_upload().ok(mxc_uri).mount().await;
+    Mock::given(method("PUT"))
+        .and(path_regex(r"/profile/.*/avatar_url$"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(0)
+        .mount(server.server())
+        .await;
+
+    account
+        .upload_avatar_from_reader(&mime::IMAGE_PNG, FailingReader { sent: false }, None)
+        .await
+        .unwrap_err();
+}
+
+#[async_test]
+async fn test_upload_avatar_with_progress() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let mxc_uri = mxc_uri!("mxc://localhost/avatar");
+    let data = vec![0; 20_000];
+
+    server.mock_upload().ok(mxc_uri).expect(1).mount().await;
+    Mock::given(method("PUT"))
+        .and(path_regex(r"/profile/.*/avatar_url$"))
+        .and(body_json(json!({ "avatar_url": mxc_uri })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(server.server())
+        .await;
+
+    let progress = SharedObservable::new(TransmissionProgress::default());
+    // The progress is only reported when it is observed.
+    let _subscriber = progress.subscribe();
+
+    let uri = client
+        .account()
+        .upload_avatar_with_progress(&mime::IMAGE_PNG, data.clone(), progress.clone())
+        .await
+        .unwrap();
+    assert_eq!(uri, mxc_uri.to_owned());
+
+    // The whole content is reported as sent.
+    let final_progress = progress.get();
+    assert_eq!(final_progress.total, data.len());
+    assert_eq!(final_progress.current, data.len());
+}
+
+#[async_test]
+async fn test_fetch_user_profile_of_cached() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+    let account = client.account();
+
+    let user_id = user_id!("@alice:localhost");
+
+    // Concurrent calls are coalesced, and the following calls use the cache.
+    {
+        let _guard = Mock::given(method("GET"))
+            .and(path("/_matrix/client/v3/profile/@alice:localhost"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "displayname": "Alice",
+            })))
+            .expect(1)
+            .mount_as_scoped(server.server())
+            .await;
+
+        let (first, second) = join(
+            account.fetch_user_profile_of_cached(user_id),
+            account.fetch_user_profile_of_cached(user_id),
+        )
+        .await;
+        assert_eq!(first.unwrap().displayname.as_deref(), Some("Alice"));
+        assert_eq!(second.unwrap().displayname.as_deref(), Some("Alice"));
+
+        let profile = account.fetch_user_profile_of_cached(user_id).await.unwrap();
+        assert_eq!(profile.displayname.as_deref(), Some("Alice"));
+    }
+
+    // The plain method bypasses the cache, and so does a call after an
+    // invalidation.
+    Mock::given(method("GET"))
+        .and(path("/_matrix/client/v3/profile/@alice:localhost"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "displayname": "Alice Margatroid",
+        })))
+        .expect(2)
+        .mount(server.server())
+        .await;
+
+    let profile = account.fetch_user_profile_of(user_id).await.unwrap();
+    assert_eq!(profile.displayname.as_deref(), Some("Alice Margatroid"));
+
+    let profile = account.fetch_user_profile_of_cached(user_id).await.unwrap();
+    assert_eq!(profile.displayname.as_deref(), Some("Alice"));
+
+    account.invalidate_profile_cache(user_id).await;
+    let profile = account.fetch_user_profile_of_cached(user_id).await.unwrap();
+    assert_eq!(profile.displayname.as_deref(), Some("Alice Margatroid"));
+}
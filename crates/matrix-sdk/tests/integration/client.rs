@@ -13,7 +13,7 @@ use matrix_sdk::{
     },
     Client, Error, MemoryStore, StateChanges, StateStore,
 };
-use matrix_sdk_base::{sync::RoomUpdates, RoomState};
+use matrix_sdk_base::{sync::RoomUpdates, RoomState, StateStoreDataKey};
 use matrix_sdk_common::executor::spawn;
 use matrix_sdk_test::{
     async_test, sync_state_event,
@@ -26,7 +26,8 @@ use matrix_sdk_test::{
         sync_events::PINNED_EVENTS,
         TAG,
     },
-    GlobalAccountDataTestEvent, JoinedRoomBuilder, SyncResponseBuilder, DEFAULT_TEST_ROOM_ID,
+    GlobalAccountDataTestEvent, JoinedRoomBuilder, LeftRoomBuilder, SyncResponseBuilder,
+    DEFAULT_TEST_ROOM_ID,
 };
 use ruma::{
     api::client::{
@@ -41,7 +42,7 @@ use ruma::{
     event_id,
     events::{
         direct::{DirectEventContent, OwnedDirectUserIdentifier},
-        AnyInitialStateEvent,
+        AnyInitialStateEvent, GlobalAccountDataEventType,
     },
     room_id,
     serde::Raw,
@@ -577,6 +578,180 @@ async fn test_marking_room_as_dm_fails_if_undeserializable() {
     server.verify().await;
 }
 
+// Check that a room that couldn't be marked as a DM is marked after the next
+// sync, without clobbering the changes made on the server in the meantime.
+#[async_test]
+async fn test_marking_room_as_dm_is_retried_after_failure() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+    let own_user_id = client.user_id().unwrap().to_owned();
+
+    let bob = user_id!("@bob:example.com");
+    let room_id = room_id!("!dm:example.com");
+
+    server
+        .mock_global_account_data()
+        .ok(&own_user_id, GlobalAccountDataEventType::Direct, json!({}))
+        .mock_once()
+        .mount()
+        .await;
+    server
+        .mock_set_global_account_data(GlobalAccountDataEventType::Direct)
+        .error500()
+        .mock_once()
+        .named("failing m.direct PUT")
+        .mount()
+        .await;
+
+    let result = client.account().mark_as_dm(room_id, &[bob.to_owned()]).await;
+    assert!(result.is_err());
+
+    // The update is kept until it's uploaded.
+    let pending_updates =
+        client.state_store().get_kv_data(StateStoreDataKey::PendingDirectUpdates).await.unwrap();
+    assert!(pending_updates.is_some());
+
+    // Another DM was added on the server in the meantime.
+    server
+        .mock_global_account_data()
+        .ok(
+            &own_user_id,
+            GlobalAccountDataEventType::Direct,
+            json!({ "@alice:example.com": ["!alice:example.com"] }),
+        )
+        .mock_once()
+        .mount()
+        .await;
+    server
+        .mock_set_global_account_data(GlobalAccountDataEventType::Direct)
+        .body_matches_partial_json(json!({
+            "@alice:example.com": ["!alice:example.com"],
+            "@bob:example.com": ["!dm:example.com"],
+        }))
+        .ok()
+        .mock_once()
+        .named("m.direct PUT retry")
+        .mount()
+        .await;
+
+    // The update is uploaded after the next successful sync.
+    server.mock_sync().ok_and_run(&client, |_| {}).await;
+
+    let pending_updates =
+        client.state_store().get_kv_data(StateStoreDataKey::PendingDirectUpdates).await.unwrap();
+    assert!(pending_updates.is_none());
+
+    // It's not uploaded again.
+    server.mock_sync().ok_and_run(&client, |_| {}).await;
+}
+
+// Check that marking a room as a DM removes the duplicate rooms of the entry.
+#[async_test]
+async fn test_marking_room_as_dm_removes_duplicates() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+    let own_user_id = client.user_id().unwrap().to_owned();
+
+    let bob = user_id!("@bob:example.com");
+
+    server
+        .mock_global_account_data()
+        .ok(
+            &own_user_id,
+            GlobalAccountDataEventType::Direct,
+            json!({
+                "@bob:example.com": ["!old:example.com", "!old:example.com"],
+                "@alice:example.com": ["!alice:example.com", "!alice:example.com"],
+            }),
+        )
+        .mock_once()
+        .mount()
+        .await;
+
+    let put_direct_content_matcher = |request: &Request| {
+        let content: DirectEventContent = request.body_json().unwrap();
+
+        let bob_entry = content.get(&OwnedDirectUserIdentifier::from(bob.to_owned())).unwrap();
+        let alice_entry = content
+            .get(&OwnedDirectUserIdentifier::from(user_id!("@alice:example.com").to_owned()))
+            .unwrap();
+
+        // Only the entry of bob is updated.
+        bob_entry == &[room_id!("!old:example.com"), room_id!("!new:example.com")]
+            && alice_entry.len() == 2
+    };
+
+    Mock::given(method("PUT"))
+        .and(path("_matrix/client/v3/user/@example:localhost/account_data/m.direct"))
+        .and(put_direct_content_matcher)
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .named("m.direct account data PUT")
+        .mount(server.server())
+        .await;
+
+    client.account().mark_as_dm(room_id!("!new:example.com"), &[bob.to_owned()]).await.unwrap();
+}
+
+// Check that cleaning up the DM rooms removes the duplicates and the rooms that
+// were left, but keeps the unknown rooms.
+#[async_test]
+async fn test_cleanup_direct_rooms() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+    let own_user_id = client.user_id().unwrap().to_owned();
+
+    let bob = user_id!("@bob:example.com");
+    let alice = user_id!("@alice:example.com");
+
+    server.sync_room(&client, JoinedRoomBuilder::new(room_id!("!joined:example.com"))).await;
+    server.sync_room(&client, LeftRoomBuilder::new(room_id!("!left:example.com"))).await;
+    server.sync_room(&client, LeftRoomBuilder::new(room_id!("!alice_left:example.com"))).await;
+
+    server
+        .mock_global_account_data()
+        .ok(
+            &own_user_id,
+            GlobalAccountDataEventType::Direct,
+            json!({
+                "@bob:example.com": [
+                    "!left:example.com",
+                    "!joined:example.com",
+                    "!unknown:example.com",
+                    "!joined:example.com",
+                ],
+                "@alice:example.com": ["!alice_left:example.com"],
+            }),
+        )
+        .mock_once()
+        .mount()
+        .await;
+
+    let put_direct_content_matcher = |request: &Request| {
+        let content: DirectEventContent = request.body_json().unwrap();
+
+        let bob_entry = content.get(&OwnedDirectUserIdentifier::from(bob.to_owned()));
+
+        // The entry of alice is removed, since it's empty.
+        content.len() == 1
+            && !content.contains_key(&OwnedDirectUserIdentifier::from(alice.to_owned()))
+            && bob_entry.is_some_and(|room_ids| {
+                room_ids == &[room_id!("!joined:example.com"), room_id!("!unknown:example.com")]
+            })
+    };
+
+    Mock::given(method("PUT"))
+        .and(path("_matrix/client/v3/user/@example:localhost/account_data/m.direct"))
+        .and(put_direct_content_matcher)
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .named("m.direct account data PUT")
+        .mount(server.server())
+        .await;
+
+    client.account().cleanup_direct_rooms().await.unwrap();
+}
+
 #[cfg(feature = "e2e-encryption")]
 #[async_test]
 async fn test_get_own_device() {
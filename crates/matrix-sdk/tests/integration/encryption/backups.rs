@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{fs::File, io::Write, sync::Arc, time::Duration};
+use std::{fs::File, io::Write, num::NonZeroUsize, sync::Arc, time::Duration};
 
 use anyhow::Result;
 use assert_matches::assert_matches;
@@ -26,9 +26,9 @@ use matrix_sdk::{
         types::EventEncryptionAlgorithm,
     },
     encryption::{
-        backups::{futures::SteadyStateError, BackupState, UploadState},
+        backups::{futures::SteadyStateError, BackupDownloadProgress, BackupState, UploadState},
         secret_storage::SecretStore,
-        BackupDownloadStrategy, EncryptionSettings,
+        BackupDownloadSettings, BackupDownloadStrategy, EncryptionSettings,
     },
     test_utils::{
         client::mock_session_tokens, no_retry_test_client_with_server,
@@ -1146,6 +1146,37 @@ async fn test_enable_from_secret_storage_manual_download() {
     assert_eq!(client.encryption().backups().state(), BackupState::Unknown);
 }
 
+/// The response of the `/room_keys/keys/{roomId}` endpoint, containing a single
+/// room key for the `!DovneieKSTkdHKpIXy:morpheus.localhost` room.
+fn room_keys_for_room_response() -> Value {
+    json!({
+        "sessions": {
+            "64H7XKokIx0ASkYDHZKlT5zd/Zccz/cQspPNdvnNULA": {
+                "first_message_index": 0,
+                "forwarded_count": 0,
+                "is_verified": true,
+                "session_data": {
+                    "ciphertext": "UaxxJxPZN5jqhSoFw59s83KlK0k77KJRxowPUC3P2/bS+TIBXw2y\
+                                   qMHCpv01s+8mE95XU6RZO2/elktHiW1/mzx/2vqb4pFuARtj3rxF\
+                                   zCBO7cpVhmrSU6uKW9KH2HirZMZzyXLqr3v6xoOTe5roIF5scPR0\
+                                   cWxPcS/4+BZz4xGhGCVuTPFjWDszY1/iz4JAVosAF7XZLGh7aVhF\
+                                   +ciDDoaaqwkD2nnMUlGEl2uchWuZv7v2q9Pmmd+qzRCdLx5c+GK3\
+                                   OyT8qCSxubOvuSruwTliBl++drlMnh4vRO8UKPTuMNvEN89YKiSC\
+                                   MVzXVDCS6tnjligxUENYkyUqYCKdASLDFs1cCXJDED16oQGonkU8\
+                                   Lf7ccGg6XboJCmJfobrmDc3s/9IymtKaxquA2Vw2pW8Otoy4x9PK\
+                                   17xHLo2nT2nf3Amp6xaCYx+tblGkLIqw8H3YZZVPVuKAVpPdAhgC\
+                                   +aJA9n8qow3BLcCJSdGRMSV9MquidGgbEA/DCd6Eq3jokshcXR4v\
+                                   Ma5nT4CokeZ6OdAtMWgZSaGltyNNoc+b6hk6AqcYaoMslG58DC32\
+                                   EVSiFFwtSpKx7I6+J+hlV813Vx6IK0DoqTcYyVm4kFMvKnIoyAKJ\
+                                   yoCSik4NQpL7DcokDhs56UJ1LcDgQTnGLqhH2Q",
+                    "ephemeral": "+KmnQw7ECkCD+s2Hc0hhntT8n9zTLJvFHgX7g3XKBjs",
+                    "mac": "xdzih3IkRv4"
+                }
+            }
+        }
+    })
+}
+
 #[async_test]
 async fn test_enable_from_secret_storage_and_manual_download() {
     let room_id = room_id!("!DovneieKSTkdHKpIXy:morpheus.localhost");
@@ -1170,32 +1201,7 @@ async fn test_enable_from_secret_storage_and_manual_download() {
     Mock::given(method("GET"))
         .and(path("/_matrix/client/r0/room_keys/keys/!DovneieKSTkdHKpIXy:morpheus.localhost"))
         .and(header("authorization", "Bearer 1234"))
-        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
-            "sessions": {
-                "64H7XKokIx0ASkYDHZKlT5zd/Zccz/cQspPNdvnNULA": {
-                    "first_message_index": 0,
-                    "forwarded_count": 0,
-                    "is_verified": true,
-                    "session_data": {
-                        "ciphertext": "UaxxJxPZN5jqhSoFw59s83KlK0k77KJRxowPUC3P2/bS+TIBXw2y\
-                                       qMHCpv01s+8mE95XU6RZO2/elktHiW1/mzx/2vqb4pFuARtj3rxF\
-                                       zCBO7cpVhmrSU6uKW9KH2HirZMZzyXLqr3v6xoOTe5roIF5scPR0\
-                                       cWxPcS/4+BZz4xGhGCVuTPFjWDszY1/iz4JAVosAF7XZLGh7aVhF\
-                                       +ciDDoaaqwkD2nnMUlGEl2uchWuZv7v2q9Pmmd+qzRCdLx5c+GK3\
-                                       OyT8qCSxubOvuSruwTliBl++drlMnh4vRO8UKPTuMNvEN89YKiSC\
-                                       MVzXVDCS6tnjligxUENYkyUqYCKdASLDFs1cCXJDED16oQGonkU8\
-                                       Lf7ccGg6XboJCmJfobrmDc3s/9IymtKaxquA2Vw2pW8Otoy4x9PK\
-                                       17xHLo2nT2nf3Amp6xaCYx+tblGkLIqw8H3YZZVPVuKAVpPdAhgC\
-                                       +aJA9n8qow3BLcCJSdGRMSV9MquidGgbEA/DCd6Eq3jokshcXR4v\
-                                       Ma5nT4CokeZ6OdAtMWgZSaGltyNNoc+b6hk6AqcYaoMslG58DC32\
-                                       EVSiFFwtSpKx7I6+J+hlV813Vx6IK0DoqTcYyVm4kFMvKnIoyAKJ\
-                                       yoCSik4NQpL7DcokDhs56UJ1LcDgQTnGLqhH2Q",
-                        "ephemeral": "+KmnQw7ECkCD+s2Hc0hhntT8n9zTLJvFHgX7g3XKBjs",
-                        "mac": "xdzih3IkRv4"
-                    }
-                }
-            }
-        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(room_keys_for_room_response()))
         .expect(1)
         .mount(&server)
         .await;
@@ -1203,12 +1209,13 @@ async fn test_enable_from_secret_storage_and_manual_download() {
     let room_key_stream = client.encryption().backups().room_keys_for_room_stream(room_id);
     pin_mut!(room_key_stream);
 
-    client
+    let imported_count = client
         .encryption()
         .backups()
         .download_room_keys_for_room(room_id)
         .await
         .expect("We should be able to download room keys for a certain room");
+    assert_eq!(imported_count, 1);
 
     if let Some(Ok(room_keys)) = room_key_stream.next().now_or_never().flatten() {
         let (_, room_key_set) = room_keys.first_key_value().unwrap();
@@ -1263,6 +1270,149 @@ async fn test_enable_from_secret_storage_and_manual_download() {
     server.verify().await;
 }
 
+#[async_test]
+async fn test_enable_from_secret_storage_and_download_in_batches() {
+    const SECRET_STORE_KEY: &str = "mypassphrase";
+    const KEY_ID: &str = "yJWwBm2Ts8jHygTBslKpABFyykavhhfA";
+
+    let user_id = user_id!("@example2:morpheus.localhost");
+    let room_id = room_id!("!DovneieKSTkdHKpIXy:morpheus.localhost");
+
+    let session = matrix_session_example2();
+    let (builder, server) = test_client_builder_with_server().await;
+    let encryption_settings = EncryptionSettings {
+        backup_download_strategy: BackupDownloadStrategy::OneShot,
+        backup_download_settings: BackupDownloadSettings {
+            batch_size: NonZeroUsize::new(1),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let client = builder
+        .request_config(RequestConfig::new().disable_retry())
+        .with_encryption_settings(encryption_settings)
+        .build()
+        .await
+        .unwrap();
+
+    client.restore_session(session).await.unwrap();
+
+    mock_secret_store_with_backup_key(user_id, KEY_ID, &server).await;
+
+    let sync = SyncResponseBuilder::new()
+        .add_joined_room(JoinedRoomBuilder::new(room_id))
+        .build_json_sync_response();
+    mock_sync(&server, sync, None).await;
+
+    client.sync_once(Default::default()).await.expect("We should be able to sync with the server");
+
+    let store = client
+        .encryption()
+        .secret_storage()
+        .open_secret_store(SECRET_STORE_KEY)
+        .await
+        .expect("We should be able to open our secret store");
+
+    mock_query_key_backup(&server).await;
+
+    // The room keys are downloaded room by room, not all at once.
+    Mock::given(method("GET"))
+        .and(path("_matrix/client/r0/room_keys/keys"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(0)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/_matrix/client/r0/room_keys/keys/!DovneieKSTkdHKpIXy:morpheus.localhost"))
+        .and(header("authorization", "Bearer 1234"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(room_keys_for_room_response()))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let state_stream = client.encryption().backups().state_stream();
+    pin_mut!(state_stream);
+
+    store
+        .import_secrets()
+        .await
+        .expect("We should be able to import our secrets from the secret store");
+
+    // The progress of the download is reported through the backup state.
+    let mut states = Vec::new();
+    while let Some(Some(Ok(state))) = state_stream.next().now_or_never() {
+        states.push(state);
+    }
+    assert!(states
+        .contains(&BackupState::Downloading(BackupDownloadProgress { downloaded: 1, total: 1 })));
+    assert_eq!(client.encryption().backups().state(), BackupState::Enabled);
+
+    let olm_machine = client.olm_machine_for_testing().await;
+    let session = olm_machine
+        .as_ref()
+        .unwrap()
+        .store()
+        .get_inbound_group_session(room_id, "64H7XKokIx0ASkYDHZKlT5zd/Zccz/cQspPNdvnNULA")
+        .await
+        .unwrap();
+    assert!(session.is_some());
+
+    server.verify().await;
+}
+
+#[async_test]
+async fn test_concurrent_downloads_of_room_keys_for_room_dont_import_duplicates() {
+    let room_id = room_id!("!DovneieKSTkdHKpIXy:morpheus.localhost");
+
+    let session = matrix_session_example2();
+    let (builder, server) = test_client_builder_with_server().await;
+    let encryption_settings = EncryptionSettings {
+        backup_download_strategy: BackupDownloadStrategy::Manual,
+        ..Default::default()
+    };
+    let client = builder
+        .request_config(RequestConfig::new().disable_retry())
+        .with_encryption_settings(encryption_settings)
+        .build()
+        .await
+        .unwrap();
+
+    client.restore_session(session).await.unwrap();
+
+    init_client_secret_storage_and_backup(&client, &server).await;
+
+    Mock::given(method("GET"))
+        .and(path("/_matrix/client/r0/room_keys/keys/!DovneieKSTkdHKpIXy:morpheus.localhost"))
+        .and(header("authorization", "Bearer 1234"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(room_keys_for_room_response()))
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    // When the room keys of the same room are downloaded twice at the same time
+    let backups = client.encryption().backups();
+    let (first, second) = futures_util::join!(
+        backups.download_room_keys_for_room(room_id),
+        backups.download_room_keys_for_room(room_id),
+    );
+
+    // Then the room key is only imported once
+    assert_eq!(first.unwrap() + second.unwrap(), 1);
+
+    let olm_machine = client.olm_machine_for_testing().await;
+    let session = olm_machine
+        .as_ref()
+        .unwrap()
+        .store()
+        .get_inbound_group_session(room_id, "64H7XKokIx0ASkYDHZKlT5zd/Zccz/cQspPNdvnNULA")
+        .await
+        .unwrap();
+    assert!(session.is_some());
+
+    server.verify().await;
+}
+
 #[async_test]
 async fn test_enable_from_secret_storage_and_download_after_utd() {
     let room_id = room_id!("!DovneieKSTkdHKpIXy:morpheus.localhost");
@@ -0,0 +1,276 @@
+use std::time::Duration;
+
+use assert_matches::assert_matches;
+use matrix_sdk::{
+    encryption::dehydrated_devices::DehydratedDevicesError, test_utils::mocks::MatrixMockServer,
+};
+use matrix_sdk_base::crypto::{
+    dehydrated_devices::DehydrationError, store::types::DehydratedDeviceKey,
+};
+use matrix_sdk_common::{sleep::sleep, timeout::timeout};
+use matrix_sdk_test::async_test;
+use ruma::{owned_device_id, owned_user_id};
+use serde_json::json;
+use wiremock::{
+    matchers::{method, path_regex},
+    Mock, ResponseTemplate,
+};
+
+const DEHYDRATED_DEVICE_PATH: &str =
+    r"^/_matrix/client/unstable/org.matrix.msc3814.v1/dehydrated_device$";
+
+#[async_test]
+async fn test_rotate_dehydrated_device_without_existing_device() {
+    let server = MatrixMockServer::new().await;
+    server.mock_crypto_endpoints_preset().await;
+
+    let user_id = owned_user_id!("@alice:example.org");
+    let device_id = owned_device_id!("4L1C3");
+    let alice = server.client_builder_for_crypto_end_to_end(&user_id, &device_id).build().await;
+    alice.encryption().bootstrap_cross_signing(None).await.unwrap();
+
+    Mock::given(method("GET"))
+        .and(path_regex(DEHYDRATED_DEVICE_PATH))
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+            "errcode": "M_NOT_FOUND",
+            "error": "No dehydrated device",
+        })))
+        .expect(1)
+        .mount(server.server())
+        .await;
+
+    Mock::given(method("PUT"))
+        .and(path_regex(DEHYDRATED_DEVICE_PATH))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "device_id": "DEHYDRATED",
+        })))
+        .expect(1)
+        .mount(server.server())
+        .await;
+
+    let pickle_key = DehydratedDeviceKey::new().unwrap();
+    let rotation = alice
+        .encryption()
+        .dehydrated_devices()
+        .rotate_dehydrated_device(&pickle_key)
+        .await
+        .unwrap();
+
+    assert_eq!(rotation.imported_room_keys, 0);
+    assert_eq!(rotation.device_id, owned_device_id!("DEHYDRATED"));
+}
+
+#[async_test]
+async fn test_rotate_existing_dehydrated_device() {
+    let server = MatrixMockServer::new().await;
+    server.mock_crypto_endpoints_preset().await;
+    server.mock_dehydrated_device_endpoints().await;
+
+    let user_id = owned_user_id!("@alice:example.org");
+    let device_id = owned_device_id!("4L1C3");
+    let alice = server.client_builder_for_crypto_end_to_end(&user_id, &device_id).build().await;
+    alice.encryption().bootstrap_cross_signing(None).await.unwrap();
+
+    let pickle_key = DehydratedDeviceKey::new().unwrap();
+    let dehydrated_devices = alice.encryption().dehydrated_devices();
+
+    // There's no dehydrated device yet, one is created.
+    let first_rotation = dehydrated_devices.rotate_dehydrated_device(&pickle_key).await.unwrap();
+    assert_eq!(first_rotation.imported_room_keys, 0);
+
+    // The existing dehydrated device is cross-signed, so it's rehydrated and
+    // replaced.
+    let second_rotation = dehydrated_devices.rotate_dehydrated_device(&pickle_key).await.unwrap();
+    assert_eq!(second_rotation.imported_room_keys, 0);
+    assert_ne!(second_rotation.device_id, first_rotation.device_id);
+}
+
+#[async_test]
+async fn test_rotate_dehydrated_device_with_unsigned_device() {
+    let server = MatrixMockServer::new().await;
+    server.mock_crypto_endpoints_preset().await;
+
+    let user_id = owned_user_id!("@alice:example.org");
+    let device_id = owned_device_id!("4L1C3");
+    let alice = server.client_builder_for_crypto_end_to_end(&user_id, &device_id).build().await;
+    alice.encryption().bootstrap_cross_signing(None).await.unwrap();
+
+    // Create a dehydrated device, without uploading its device keys.
+    let pickle_key = DehydratedDeviceKey::new().unwrap();
+    let current_device = {
+        let olm_machine = alice.olm_machine_for_testing().await;
+        let device = olm_machine.as_ref().unwrap().dehydrated_devices().create().await.unwrap();
+        device.keys_for_upload("Dehydrated device".to_owned(), &pickle_key).await.unwrap()
+    };
+
+    Mock::given(method("GET"))
+        .and(path_regex(DEHYDRATED_DEVICE_PATH))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "device_id": current_device.device_id,
+            "device_data": current_device.device_data,
+        })))
+        .expect(1)
+        .mount(server.server())
+        .await;
+
+    // The device isn't replaced.
+    Mock::given(method("DELETE"))
+        .and(path_regex(DEHYDRATED_DEVICE_PATH))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "device_id": current_device.device_id,
+        })))
+        .expect(0)
+        .mount(server.server())
+        .await;
+
+    Mock::given(method("PUT"))
+        .and(path_regex(DEHYDRATED_DEVICE_PATH))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "device_id": "NEW_DEHYDRATED",
+        })))
+        .expect(0)
+        .mount(server.server())
+        .await;
+
+    let result =
+        alice.encryption().dehydrated_devices().rotate_dehydrated_device(&pickle_key).await;
+
+    assert_matches!(
+        result,
+        Err(DehydratedDevicesError::Dehydration(DehydrationError::DeviceSignatureMismatch))
+    );
+}
+
+#[async_test]
+async fn test_delete_dehydrated_device() {
+    let server = MatrixMockServer::new().await;
+    server.mock_crypto_endpoints_preset().await;
+    server.mock_dehydrated_device_endpoints().await;
+
+    let user_id = owned_user_id!("@alice:example.org");
+    let device_id = owned_device_id!("4L1C3");
+    let alice = server.client_builder_for_crypto_end_to_end(&user_id, &device_id).build().await;
+    alice.encryption().bootstrap_cross_signing(None).await.unwrap();
+
+    let pickle_key = DehydratedDeviceKey::new().unwrap();
+    let dehydrated_devices = alice.encryption().dehydrated_devices();
+
+    // There's nothing to delete yet.
+    assert!(!dehydrated_devices.delete_dehydrated_device().await.unwrap());
+
+    dehydrated_devices.rotate_dehydrated_device(&pickle_key).await.unwrap();
+
+    {
+        let olm_machine = alice.olm_machine_for_testing().await;
+        let olm_machine = olm_machine.as_ref().unwrap();
+        olm_machine
+            .dehydrated_devices()
+            .save_dehydrated_device_pickle_key(&pickle_key)
+            .await
+            .unwrap();
+    }
+
+    // The dehydrated device is deleted, along with the cached pickle key.
+    assert!(dehydrated_devices.delete_dehydrated_device().await.unwrap());
+
+    let cached_pickle_key = {
+        let olm_machine = alice.olm_machine_for_testing().await;
+        let olm_machine = olm_machine.as_ref().unwrap();
+        olm_machine.dehydrated_devices().get_dehydrated_device_pickle_key().await.unwrap()
+    };
+    assert!(cached_pickle_key.is_none());
+
+    // The device is gone.
+    assert!(!dehydrated_devices.delete_dehydrated_device().await.unwrap());
+}
+
+#[async_test]
+async fn test_rotate_dehydrated_device_if_needed() {
+    let server = MatrixMockServer::new().await;
+    server.mock_crypto_endpoints_preset().await;
+    server.mock_dehydrated_device_endpoints().await;
+
+    let user_id = owned_user_id!("@alice:example.org");
+    let device_id = owned_device_id!("4L1C3");
+    let alice = server.client_builder_for_crypto_end_to_end(&user_id, &device_id).build().await;
+    alice.encryption().bootstrap_cross_signing(None).await.unwrap();
+
+    let dehydrated_devices = alice.encryption().dehydrated_devices();
+    let interval = Duration::from_secs(60 * 60);
+
+    // There is no cached pickle key, nothing happens.
+    assert!(dehydrated_devices
+        .rotate_dehydrated_device_if_needed(interval)
+        .await
+        .unwrap()
+        .is_none());
+    assert!(dehydrated_devices.last_rotation_time().await.unwrap().is_none());
+
+    {
+        let olm_machine = alice.olm_machine_for_testing().await;
+        let olm_machine = olm_machine.as_ref().unwrap();
+        olm_machine
+            .dehydrated_devices()
+            .save_dehydrated_device_pickle_key(&DehydratedDeviceKey::new().unwrap())
+            .await
+            .unwrap();
+    }
+
+    // The dehydrated device was never rotated, so it is now.
+    let rotation = dehydrated_devices.rotate_dehydrated_device_if_needed(interval).await.unwrap();
+    assert!(rotation.is_some());
+    assert!(dehydrated_devices.last_rotation_time().await.unwrap().is_some());
+
+    // It was rotated during the interval, so it isn't rotated again.
+    assert!(dehydrated_devices
+        .rotate_dehydrated_device_if_needed(interval)
+        .await
+        .unwrap()
+        .is_none());
+
+    // It is once the interval has elapsed.
+    let rotation =
+        dehydrated_devices.rotate_dehydrated_device_if_needed(Duration::ZERO).await.unwrap();
+    assert!(rotation.is_some());
+}
+
+#[async_test]
+async fn test_automatic_dehydrated_device_rotation() {
+    let server = MatrixMockServer::new().await;
+    server.mock_crypto_endpoints_preset().await;
+    server.mock_dehydrated_device_endpoints().await;
+
+    let user_id = owned_user_id!("@alice:example.org");
+    let device_id = owned_device_id!("4L1C3");
+    let alice = server.client_builder_for_crypto_end_to_end(&user_id, &device_id).build().await;
+    alice.encryption().bootstrap_cross_signing(None).await.unwrap();
+
+    {
+        let olm_machine = alice.olm_machine_for_testing().await;
+        let olm_machine = olm_machine.as_ref().unwrap();
+        olm_machine
+            .dehydrated_devices()
+            .save_dehydrated_device_pickle_key(&DehydratedDeviceKey::new().unwrap())
+            .await
+            .unwrap();
+    }
+
+    let dehydrated_devices = alice.encryption().dehydrated_devices();
+    dehydrated_devices.enable_automatic_rotation(Duration::from_secs(60 * 60));
+
+    server.mock_sync().ok_and_run(&alice, |_| {}).await;
+
+    // Wait for the background task to rotate the dehydrated device.
+    timeout(
+        async {
+            while dehydrated_devices.last_rotation_time().await.unwrap().is_none() {
+                sleep(Duration::from_millis(50)).await;
+            }
+        },
+        Duration::from_secs(5),
+    )
+    .await
+    .expect("The dehydrated device should have been rotated in the background");
+
+    dehydrated_devices.disable_automatic_rotation();
+}
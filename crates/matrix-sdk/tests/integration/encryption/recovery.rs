@@ -21,7 +21,7 @@ use matrix_sdk::{
     config::RequestConfig,
     encryption::{
         backups::BackupState,
-        recovery::{EnableProgress, RecoveryState},
+        recovery::{EnableProgress, RecoveryError, RecoveryKey, RecoveryKeyError, RecoveryState},
         BackupDownloadStrategy, CrossSigningResetAuthType,
     },
     test_utils::{
@@ -55,6 +55,7 @@ async fn test_client(user_id: &UserId) -> (Client, wiremock::MockServer) {
         .with_encryption_settings(matrix_sdk::encryption::EncryptionSettings {
             auto_enable_cross_signing: true,
             backup_download_strategy: BackupDownloadStrategy::Manual,
+            backup_download_settings: Default::default(),
             auto_enable_backups: true,
         })
         .build()
@@ -164,6 +165,67 @@ async fn test_recovery_status_server_unavailable() {
     assert_eq!(client.encryption().recovery().state(), RecoveryState::Unknown);
 }
 
+#[async_test]
+async fn test_recover_with_malformed_recovery_key() {
+    let (client, server) = logged_in_client_with_server().await;
+    client.encryption().wait_for_e2ee_initialization_tasks().await;
+
+    // The secret store isn't opened if the recovery key is malformed.
+    Mock::given(method("GET"))
+        .and(path_regex(r"_matrix/client/r0/user/.*/account_data/.*"))
+        .respond_with(ResponseTemplate::new(404))
+        .expect(0)
+        .mount(&server)
+        .await;
+
+    let recovery = client.encryption().recovery();
+
+    assert_let!(
+        Err(RecoveryError::InvalidRecoveryKey(RecoveryKeyError::InvalidChecksum)) =
+            recovery.recover("EsTj 3yST y93F SLpB jJsz eAXc 2XzA ygD3 w69H fGaN TKBj jXEe").await
+    );
+    assert_let!(
+        Err(RecoveryError::InvalidRecoveryKey(RecoveryKeyError::InvalidLength {
+            expected: 35,
+            actual: 32
+        })) = recovery.recover("EsTj-3yST-y93F-SLpB-jJsz-eAXc-2XzA-ygD3-w69H-fGaN-TKBj").await
+    );
+    assert_let!(
+        Err(RecoveryError::InvalidRecoveryKey(RecoveryKeyError::NotBase58)) =
+            recovery.recover("EsTj 3yST y93F SLpB jJsz eAXc 2XzA ygD3 w69H fGaN TKBj jXE0").await
+    );
+    assert_let!(
+        Err(RecoveryError::InvalidRecoveryKey(RecoveryKeyError::InvalidPrefix)) =
+            recovery.recover("EsUK 2XMz Q91X MHMN dsnA 6YDR pvsE X2dd qzUF hASF 8FFp 2KYc").await
+    );
+
+    server.verify().await;
+}
+
+#[test]
+fn test_recovery_key_parsing() {
+    let recovery_key = RecoveryKey::parse_flexible(
+        " EsTj-3yST-y93F-SLpB-jJsz-eAXc-2XzA-ygD3-w69H-fGaN-TKBj-jXEd\n",
+    )
+    .expect("Whitespace and dashes should be ignored");
+    assert_eq!(recovery_key.as_str(), "EsTj3ySTy93FSLpBjJszeAXc2XzAygD3w69HfGaNTKBjjXEd");
+
+    assert!(RecoveryKey::looks_like_recovery_key(
+        "EsTj 3yST y93F SLpB jJsz eAXc 2XzA ygD3 w69H fGaN TKBj jXEd"
+    ));
+    // Recovery keys with typos still look like recovery keys.
+    assert!(RecoveryKey::looks_like_recovery_key(
+        "EsTj 3yST y93F SLpB jJsz eAXc 2XzA ygD3 w69H fGaN TKBj"
+    ));
+    assert!(RecoveryKey::looks_like_recovery_key(
+        "EsTj 3yST y93F SLpB jJsz eAXc 2XzA ygD3 w69H fGaN TKBj jXE0"
+    ));
+
+    assert!(!RecoveryKey::looks_like_recovery_key("It's a secret to everybody"));
+    assert!(!RecoveryKey::looks_like_recovery_key("correct horse battery staple"));
+    assert!(!RecoveryKey::looks_like_recovery_key(""));
+}
+
 #[async_test]
 async fn test_recovery_status_secret_storage_set_up() {
     const KEY_ID: &str = "yJWwBm2Ts8jHygTBslKpABFyykavhhfA";
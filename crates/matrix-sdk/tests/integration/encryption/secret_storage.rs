@@ -3,10 +3,13 @@ use std::sync::{Arc, Mutex};
 use assert_matches::assert_matches;
 use matrix_sdk::{
     authentication::matrix::MatrixSession,
-    encryption::secret_storage::SecretStorageError,
+    encryption::secret_storage::{DecryptionError, SecretStorageError},
     test_utils::{client::mock_session_tokens, no_retry_test_client_with_server},
 };
-use matrix_sdk_base::SessionMeta;
+use matrix_sdk_base::{
+    crypto::{dehydrated_devices::DehydrationError, store::types::DehydratedDeviceKey},
+    SessionMeta,
+};
 use matrix_sdk_test::async_test;
 use ruma::{
     device_id,
@@ -663,3 +666,191 @@ async fn test_is_secret_storage_enabled() {
         );
     }
 }
+
+#[async_test]
+async fn test_dehydrated_device_pickle_key_in_secret_store() {
+    let (client, server) = logged_in_client_with_server().await;
+
+    mock_secret_store_key(
+        &server,
+        client.user_id().unwrap(),
+        "bmur2d9ypPUH1msSwCxQOJkuKRmJI55e",
+        "xv5b6/p3ExEw++wTyfSHEg==",
+        "ujBBbXahnTAMkmPUX2/0+VTfUh63pGyVRuBcDMgmJC8=",
+    )
+    .await;
+
+    // Act like a homeserver storing the secret in the account data.
+    let secret_content: Arc<Mutex<Option<serde_json::Value>>> = Mutex::new(None).into();
+
+    Mock::given(method("GET"))
+        .and(path("_matrix/client/r0/user/@example:localhost/account_data/org.matrix.msc3814"))
+        .and(header("authorization", "Bearer 1234"))
+        .respond_with({
+            let secret_content = secret_content.clone();
+            move |_: &wiremock::Request| match secret_content.lock().unwrap().clone() {
+                Some(content) => ResponseTemplate::new(200).set_body_json(content),
+                None => ResponseTemplate::new(404).set_body_json(json!({
+                    "errcode": "M_NOT_FOUND",
+                    "error": "Account data not found"
+                })),
+            }
+        })
+        .named("org.matrix.msc3814 account data GET")
+        .mount(&server)
+        .await;
+
+    Mock::given(method("PUT"))
+        .and(path("_matrix/client/r0/user/@example:localhost/account_data/org.matrix.msc3814"))
+        .and(header("authorization", "Bearer 1234"))
+        .respond_with({
+            let secret_content = secret_content.clone();
+            move |request: &wiremock::Request| {
+                *secret_content.lock().unwrap() = Some(request.body_json().unwrap());
+                ResponseTemplate::new(200).set_body_json(json!({}))
+            }
+        })
+        .named("org.matrix.msc3814 account data PUT")
+        .mount(&server)
+        .await;
+
+    let secret_store = client
+        .encryption()
+        .secret_storage()
+        .open_secret_store(SECRET_STORE_KEY)
+        .await
+        .expect("We should be able to open our secret store");
+
+    // There is no pickle key in the secret store yet.
+    assert!(secret_store.import_dehydrated_device_pickle_key().await.unwrap().is_none());
+
+    let pickle_key = DehydratedDeviceKey::new().unwrap();
+    secret_store
+        .export_dehydrated_device_pickle_key(&pickle_key)
+        .await
+        .expect("We should be able to store the pickle key in the secret store");
+
+    // The pickle key is stored as unpadded base64.
+    let secret = secret_store.get_secret("org.matrix.msc3814").await.unwrap().unwrap();
+    assert_eq!(secret, pickle_key.to_base64());
+
+    // Forget about the locally cached pickle key, it is retrieved from the secret
+    // store and cached again.
+    let dehydrated_devices =
+        client.olm_machine_for_testing().await.as_ref().unwrap().dehydrated_devices();
+    dehydrated_devices.delete_dehydrated_device_pickle_key().await.unwrap();
+
+    let imported_key = secret_store
+        .import_dehydrated_device_pickle_key()
+        .await
+        .unwrap()
+        .expect("The pickle key should be found in the secret store");
+    assert_eq!(imported_key.to_base64(), pickle_key.to_base64());
+
+    let cached_key = dehydrated_devices.get_dehydrated_device_pickle_key().await.unwrap().unwrap();
+    assert_eq!(cached_key.to_base64(), pickle_key.to_base64());
+
+    // A secret with the wrong length is rejected.
+    secret_store.put_secret("org.matrix.msc3814", "AAAAAAAAAAAAAAAAAAAAAA").await.unwrap();
+
+    assert_matches!(
+        secret_store.import_dehydrated_device_pickle_key().await,
+        Err(SecretStorageError::Dehydration(DehydrationError::PickleKeyLength(16)))
+    );
+}
+
+#[async_test]
+async fn test_custom_secret_in_secret_store() {
+    let (client, server) = logged_in_client_with_server().await;
+
+    mock_secret_store_key(
+        &server,
+        client.user_id().unwrap(),
+        "bmur2d9ypPUH1msSwCxQOJkuKRmJI55e",
+        "xv5b6/p3ExEw++wTyfSHEg==",
+        "ujBBbXahnTAMkmPUX2/0+VTfUh63pGyVRuBcDMgmJC8=",
+    )
+    .await;
+
+    // Act like a homeserver storing the secret in the account data.
+    let secret_content: Arc<Mutex<Option<serde_json::Value>>> = Mutex::new(None).into();
+
+    Mock::given(method("GET"))
+        .and(path("_matrix/client/r0/user/@example:localhost/account_data/com.example.token"))
+        .and(header("authorization", "Bearer 1234"))
+        .respond_with({
+            let secret_content = secret_content.clone();
+            move |_: &wiremock::Request| match secret_content.lock().unwrap().clone() {
+                Some(content) => ResponseTemplate::new(200).set_body_json(content),
+                None => ResponseTemplate::new(404).set_body_json(json!({
+                    "errcode": "M_NOT_FOUND",
+                    "error": "Account data not found"
+                })),
+            }
+        })
+        .named("com.example.token account data GET")
+        .mount(&server)
+        .await;
+
+    Mock::given(method("PUT"))
+        .and(path("_matrix/client/r0/user/@example:localhost/account_data/com.example.token"))
+        .and(header("authorization", "Bearer 1234"))
+        .respond_with({
+            let secret_content = secret_content.clone();
+            move |request: &wiremock::Request| {
+                *secret_content.lock().unwrap() = Some(request.body_json().unwrap());
+                ResponseTemplate::new(200).set_body_json(json!({}))
+            }
+        })
+        .named("com.example.token account data PUT")
+        .mount(&server)
+        .await;
+
+    let secret_store = client
+        .encryption()
+        .secret_storage()
+        .open_secret_store(SECRET_STORE_KEY)
+        .await
+        .expect("We should be able to open our secret store");
+
+    // There is no such secret in the secret store yet.
+    assert!(secret_store.get_custom_secret("com.example.token").await.unwrap().is_none());
+
+    let value = b"\x00\x01It's a secret to everybody\xff";
+    secret_store
+        .put_custom_secret("com.example.token", value)
+        .await
+        .expect("We should be able to store a custom secret in the secret store");
+
+    // The secret is stored as unpadded base64.
+    let secret = secret_store.get_secret("com.example.token").await.unwrap().unwrap();
+    assert!(!secret.ends_with('='));
+
+    let stored = secret_store.get_custom_secret("com.example.token").await.unwrap();
+    assert_eq!(stored.as_deref(), Some(&value[..]));
+
+    // Secrets written by other clients with padded, URL-safe base64 are accepted as
+    // well.
+    secret_store.put_secret("com.example.token", " _-8=\n").await.unwrap();
+    let stored = secret_store.get_custom_secret("com.example.token").await.unwrap();
+    assert_eq!(stored.as_deref(), Some(&[0xff, 0xef][..]));
+
+    // A secret which isn't base64 is rejected.
+    secret_store.put_secret("com.example.token", "It's a secret").await.unwrap();
+    assert_matches!(
+        secret_store.get_custom_secret("com.example.token").await,
+        Err(SecretStorageError::Decryption(DecryptionError::Base64(_)))
+    );
+
+    // The names reserved by the specification can't be used.
+    assert_matches!(
+        secret_store.put_custom_secret("m.cross_signing.master", value).await,
+        Err(SecretStorageError::InvalidCustomSecretName(name)) => {
+            assert_eq!(name, "m.cross_signing.master");
+        }
+    );
+    assert_matches!(
+        secret_store.get_custom_secret("").await,
+        Err(SecretStorageError::InvalidCustomSecretName(_))
+    );
+}
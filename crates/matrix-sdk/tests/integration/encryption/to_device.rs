@@ -6,11 +6,11 @@ use assert_matches::assert_matches;
 use assert_matches2::assert_let;
 use matrix_sdk::test_utils::mocks::MatrixMockServer;
 use matrix_sdk_common::{
-    deserialized_responses::{AlgorithmInfo, EncryptionInfo},
+    deserialized_responses::{AlgorithmInfo, EncryptionInfo, WithheldCode},
     locks::Mutex,
 };
 use matrix_sdk_test::{async_test, test_json};
-use ruma::{events::AnyToDeviceEvent, serde::Raw};
+use ruma::{device_id, events::AnyToDeviceEvent, serde::Raw};
 use serde_json::json;
 use wiremock::{
     matchers::{method, path_regex},
@@ -125,6 +125,72 @@ async fn test_encrypt_and_send_to_device_report_failures_server() {
     assert_eq!(bob_device_id.to_owned(), failure.1);
 }
 
+#[async_test]
+async fn test_send_encrypted_to_device_establishes_sessions() {
+    let matrix_mock_server = MatrixMockServer::new().await;
+    matrix_mock_server.mock_crypto_endpoints_preset().await;
+
+    let (alice, bob) = matrix_mock_server.set_up_alice_and_bob_for_encryption().await;
+    let carl = matrix_mock_server.set_up_carl_for_encryption(&alice, &bob).await;
+
+    let bob_user_id = bob.user_id().unwrap();
+    let bob_device_id = bob.device_id().unwrap();
+    let carl_user_id = carl.user_id().unwrap();
+    let carl_device_id = carl.device_id().unwrap();
+    let unknown_device_id = device_id!("UNKNOWN");
+
+    // No Olm session can be established with Carl.
+    matrix_mock_server.exhaust_one_time_keys(carl_user_id.to_owned(), carl_device_id.to_owned());
+
+    let content_raw = Raw::new(&json!({
+        "keys": [
+            {
+                "index": 0,
+                "key": "rQuVUQs2sHV8Z2rjhmW+aQ=="
+            }
+        ],
+        "device_id": "VYTOIDPHBO",
+        "call_id": "",
+        "sent_ts": 1000
+    }))
+    .unwrap()
+    .cast_unchecked();
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/.*/sendToDevice/m.room.encrypted/.*"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EMPTY))
+        // A single request for Bob's device.
+        .expect(1)
+        .named("send_to_device")
+        .mount(matrix_mock_server.server())
+        .await;
+
+    let result = alice
+        .encryption()
+        .send_encrypted_to_device(
+            &[
+                (bob_user_id, bob_device_id),
+                (carl_user_id, carl_device_id),
+                (bob_user_id, unknown_device_id),
+            ],
+            "call.keys",
+            content_raw,
+        )
+        .await
+        .unwrap();
+
+    assert!(!result.is_success());
+    assert_eq!(
+        result.unknown_devices,
+        vec![(bob_user_id.to_owned(), unknown_device_id.to_owned())]
+    );
+    assert_eq!(
+        result.withheld_devices,
+        vec![(carl_user_id.to_owned(), carl_device_id.to_owned(), WithheldCode::NoOlm)]
+    );
+    assert!(result.failed_devices.is_empty());
+}
+
 #[async_test]
 async fn test_to_device_event_handler_olm_encryption_info() {
     // ===========
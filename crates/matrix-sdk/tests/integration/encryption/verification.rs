@@ -1,12 +1,28 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
 use assert_matches2::assert_matches;
-use futures_util::FutureExt;
+use futures_util::{pin_mut, FutureExt};
 use matrix_sdk::{
-    encryption::VerificationState,
-    test_utils::{logged_in_client_with_server, mocks::MatrixMockServer},
+    assert_next_with_timeout,
+    encryption::{
+        verification::{VerificationRequest, VerificationRequestState},
+        VerificationState,
+    },
+    test_utils::{
+        logged_in_client_with_server,
+        mocks::{encryption::PendingToDeviceMessages, MatrixMockServer},
+    },
     Client,
 };
+use matrix_sdk_common::{sleep::sleep, timeout::timeout};
 use matrix_sdk_test::async_test;
-use ruma::{owned_device_id, owned_user_id, user_id};
+use ruma::{
+    device_id, events::key::verification::VerificationMethod, owned_device_id, owned_user_id,
+    user_id,
+};
 use serde_json::json;
 use wiremock::{
     matchers::{body_json, method, path},
@@ -242,6 +258,145 @@ async fn test_unchecked_mutual_verification() {
     assert!(alice_bob_device.is_verified_with_cross_signing());
 }
 
+#[async_test]
+async fn test_devices_stream_for_user() {
+    let server = MatrixMockServer::new().await;
+    server.mock_crypto_endpoints_preset().await;
+
+    let user_id = owned_user_id!("@alice:example.org");
+    let device_id = owned_device_id!("4L1C3");
+    let alice = server.client_builder_for_crypto_end_to_end(&user_id, &device_id).build().await;
+
+    // Have Alice upload her device keys.
+    server.mock_sync().ok_and_run(&alice, |_builder| {}).await;
+
+    let stream = alice.encryption().devices_stream_for_user(&user_id).await.unwrap();
+    pin_mut!(stream);
+
+    // The stream starts with the devices we already know about.
+    let updates = assert_next_with_timeout!(stream);
+    let device_ids: Vec<_> = updates.devices.iter().map(|d| d.device_id().to_owned()).collect();
+    assert_eq!(device_ids, [device_id.clone()]);
+    assert!(updates.deleted.is_empty());
+
+    // Alice logs in on a new device, the stream yields the whole list of devices.
+    server.set_up_new_device_for_encryption(&alice, device_id!("ALICE2"), vec![]).await;
+
+    let updates = assert_next_with_timeout!(stream);
+    let mut device_ids: Vec<_> = updates.devices.iter().map(|d| d.device_id().to_owned()).collect();
+    device_ids.sort();
+    assert_eq!(device_ids, [device_id, owned_device_id!("ALICE2")]);
+    assert!(updates.deleted.is_empty());
+}
+
+/// Wait until the spawned task driving the given request made it ready.
+async fn wait_until_ready(request: &VerificationRequest) {
+    timeout(
+        async {
+            while !request.is_ready() {
+                sleep(Duration::from_millis(10)).await;
+            }
+        },
+        Duration::from_secs(5),
+    )
+    .await
+    .expect("the verification request should have been accepted");
+}
+
+#[async_test]
+async fn test_auto_accept_self_verification() {
+    let server = MatrixMockServer::new().await;
+    server.mock_crypto_endpoints_preset().await;
+
+    let user_id = owned_user_id!("@alice:example.org");
+    let device_id = owned_device_id!("4L1C3");
+    let alice = server.client_builder_for_crypto_end_to_end(&user_id, &device_id).build().await;
+    bootstrap_cross_signing(&alice).await;
+
+    let alice2 =
+        server.set_up_new_device_for_encryption(&alice, device_id!("ALICE2"), vec![]).await;
+
+    // Have the first device sign the new one, and pick up the signature.
+    let alice_alice2_device =
+        alice.encryption().get_device(&user_id, device_id!("ALICE2")).await.unwrap().unwrap();
+    alice_alice2_device.verify().await.unwrap();
+
+    server
+        .mock_sync()
+        .ok_and_run(&alice, |builder| {
+            builder.add_change_device(&user_id);
+        })
+        .await;
+
+    let alice_alice2_device =
+        alice.encryption().get_device(&user_id, device_id!("ALICE2")).await.unwrap().unwrap();
+    assert!(alice_alice2_device.is_cross_signed_by_owner());
+
+    alice.encryption().set_auto_accept_self_verification(true);
+
+    let queue: Arc<Mutex<PendingToDeviceMessages>> = Default::default();
+    let _guard = server.capture_put_to_device_traffic(&user_id, queue.clone()).await;
+
+    // The new device requests a verification with the first one.
+    let alice2_alice_device =
+        alice2.encryption().get_device(&user_id, &device_id).await.unwrap().unwrap();
+    let alice2_request = alice2_alice_device
+        .request_verification_with_methods(vec![VerificationMethod::SasV1])
+        .await
+        .unwrap();
+
+    server.sync_back_pending_to_device_messages(queue.clone(), &alice).await;
+
+    // The request is accepted automatically.
+    let alice_request = alice
+        .encryption()
+        .get_verification_request(&user_id, alice2_request.flow_id())
+        .await
+        .expect("alice should have received the verification request");
+    wait_until_ready(&alice_request).await;
+
+    // And the new device sees it as accepted as well.
+    server.sync_back_pending_to_device_messages(queue, &alice2).await;
+    assert!(alice2_request.is_ready());
+}
+
+#[async_test]
+async fn test_auto_accept_self_verification_ignores_unverified_devices() {
+    let server = MatrixMockServer::new().await;
+    server.mock_crypto_endpoints_preset().await;
+
+    let user_id = owned_user_id!("@alice:example.org");
+    let device_id = owned_device_id!("4L1C3");
+    let alice = server.client_builder_for_crypto_end_to_end(&user_id, &device_id).build().await;
+    bootstrap_cross_signing(&alice).await;
+
+    // The new device isn't signed by our identity.
+    let alice2 =
+        server.set_up_new_device_for_encryption(&alice, device_id!("ALICE2"), vec![]).await;
+
+    alice.encryption().set_auto_accept_self_verification(true);
+
+    let queue: Arc<Mutex<PendingToDeviceMessages>> = Default::default();
+    let _guard = server.capture_put_to_device_traffic(&user_id, queue.clone()).await;
+
+    let alice2_alice_device =
+        alice2.encryption().get_device(&user_id, &device_id).await.unwrap().unwrap();
+    let alice2_request = alice2_alice_device
+        .request_verification_with_methods(vec![VerificationMethod::SasV1])
+        .await
+        .unwrap();
+
+    server.sync_back_pending_to_device_messages(queue, &alice).await;
+
+    // The request is left untouched, waiting for the user.
+    let alice_request = alice
+        .encryption()
+        .get_verification_request(&user_id, alice2_request.flow_id())
+        .await
+        .expect("alice should have received the verification request");
+    assert_matches!(alice_request.state(), VerificationRequestState::Requested { .. });
+}
+
 #[async_test]
 async fn test_request_user_identity() {
     let (client, server) = logged_in_client_with_server().await;
@@ -1,14 +1,23 @@
 use assert_matches2::assert_matches;
-use matrix_sdk::{config::SyncSettings, sync::Notification};
+use matrix_sdk::{config::SyncSettings, sync::Notification, Client};
 use matrix_sdk_base::deserialized_responses::RawAnySyncOrStrippedTimelineEvent;
 use matrix_sdk_test::{
     async_test, event_factory::EventFactory, stripped_state_event, sync_state_event, test_json,
-    InvitedRoomBuilder, JoinedRoomBuilder, SyncResponseBuilder,
+    GlobalAccountDataTestEvent, InvitedRoomBuilder, JoinedRoomBuilder, SyncResponseBuilder,
 };
-use ruma::{event_id, events::StateEventType, room_id, serde::Raw, user_id, OwnedRoomId};
+use ruma::{
+    event_id,
+    events::StateEventType,
+    push::{NewPushRule, NewSimplePushRule, Ruleset},
+    room_id,
+    serde::Raw,
+    user_id, OwnedRoomId, RoomId,
+};
+use serde_json::{json, Value as JsonValue};
 use stream_assert::{assert_pending, assert_ready};
 use tokio::sync::mpsc;
 use tokio_stream::wrappers::ReceiverStream;
+use wiremock::MockServer;
 
 use crate::{logged_in_client_with_server, mock_sync};
 
@@ -143,3 +152,160 @@ async fn test_notifications_invite() {
 
     assert_pending!(receiver_stream);
 }
+
+/// Sync a joined room where the current user is named "example", with the
+/// given push rules, if any.
+async fn sync_room_for_push_rules(
+    client: &Client,
+    server: &MockServer,
+    room_id: &RoomId,
+    push_rules: Option<Ruleset>,
+) {
+    let user_id = client.user_id().unwrap();
+
+    let mut sync_builder = SyncResponseBuilder::new();
+    sync_builder.add_joined_room(JoinedRoomBuilder::new(room_id).add_state_bulk([
+        Raw::new(&*test_json::POWER_LEVELS).unwrap().cast_unchecked(),
+        sync_state_event!({
+            "content": {
+                "displayname": "example",
+                "membership": "join"
+            },
+            "event_id": "$join_example",
+            "origin_server_ts": 151800140,
+            "sender": user_id,
+            "state_key": user_id,
+            "type": "m.room.member",
+        }),
+    ]));
+
+    if let Some(push_rules) = push_rules {
+        sync_builder.add_global_account_data_event(GlobalAccountDataTestEvent::Custom(json!({
+            "type": "m.push_rules",
+            "content": { "global": push_rules },
+        })));
+    }
+
+    mock_sync(server, sync_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::default()).await.unwrap();
+    server.reset().await;
+}
+
+fn text_message(body: &str, mentioned_user_ids: &[&str]) -> Raw<JsonValue> {
+    Raw::new(&json!({
+        "type": "m.room.message",
+        "event_id": "$message",
+        "origin_server_ts": 152037280,
+        "sender": "@bob:example.com",
+        "content": {
+            "msgtype": "m.text",
+            "body": body,
+            "m.mentions": { "user_ids": mentioned_user_ids },
+        },
+    }))
+    .unwrap()
+}
+
+#[async_test]
+async fn test_push_actions_for_mention() {
+    let (client, server) = logged_in_client_with_server().await;
+    let room_id = room_id!("!joined_room:localhost");
+    let user_id = client.user_id().unwrap();
+
+    sync_room_for_push_rules(&client, &server, room_id, None).await;
+
+    let room = client.get_room(room_id).unwrap();
+
+    // A mention of the current user is highlighted.
+    let actions = room
+        .push_actions_for_event(&text_message("Hey you", &[user_id.as_str()]))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(actions.rule_id.as_deref(), Some(".m.rule.is_user_mention"));
+    assert!(actions.should_notify());
+    assert!(actions.is_highlight());
+
+    // Another message notifies, but isn't highlighted.
+    let actions =
+        room.push_actions_for_event(&text_message("Hey all", &[])).await.unwrap().unwrap();
+    assert_eq!(actions.rule_id.as_deref(), Some(".m.rule.message"));
+    assert!(actions.should_notify());
+    assert!(!actions.is_highlight());
+
+    // The context from the client gives the same results.
+    let push_ctx = client.push_context_for_room(room_id).await.unwrap().unwrap();
+    let actions = push_ctx.evaluate(&text_message("Hey you", &[user_id.as_str()]));
+    assert_eq!(actions.rule_id.as_deref(), Some(".m.rule.is_user_mention"));
+
+    // There's no context for unknown rooms.
+    assert!(client.push_context_for_room(room_id!("!unknown:localhost")).await.unwrap().is_none());
+}
+
+#[async_test]
+async fn test_push_actions_with_room_mode_override() {
+    let (client, server) = logged_in_client_with_server().await;
+    let room_id = room_id!("!joined_room:localhost");
+    let user_id = client.user_id().unwrap();
+
+    // The room is muted with a room rule, like in the "mentions and keywords only"
+    // mode.
+    let mut push_rules = Ruleset::server_default(user_id);
+    push_rules
+        .insert(NewPushRule::Room(NewSimplePushRule::new(room_id.to_owned(), vec![])), None, None)
+        .unwrap();
+
+    sync_room_for_push_rules(&client, &server, room_id, Some(push_rules)).await;
+
+    let room = client.get_room(room_id).unwrap();
+
+    let actions =
+        room.push_actions_for_event(&text_message("Hey all", &[])).await.unwrap().unwrap();
+    assert_eq!(actions.rule_id.as_deref(), Some(room_id.as_str()));
+    assert!(!actions.should_notify());
+
+    // Mentions still take precedence over the room rule.
+    let actions = room
+        .push_actions_for_event(&text_message("Hey you", &[user_id.as_str()]))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(actions.rule_id.as_deref(), Some(".m.rule.is_user_mention"));
+    assert!(actions.is_highlight());
+}
+
+#[async_test]
+async fn test_push_actions_for_decrypted_event() {
+    let (client, server) = logged_in_client_with_server().await;
+    let room_id = room_id!("!joined_room:localhost");
+    let user_id = client.user_id().unwrap();
+
+    sync_room_for_push_rules(&client, &server, room_id, None).await;
+
+    let room = client.get_room(room_id).unwrap();
+
+    // Before decryption, only the generic rule for encrypted events matches.
+    let encrypted = Raw::new(&json!({
+        "type": "m.room.encrypted",
+        "event_id": "$message",
+        "origin_server_ts": 152037280,
+        "sender": "@bob:example.com",
+        "content": {
+            "algorithm": "m.megolm.v1.aes-sha2",
+            "ciphertext": "AwgAEpABhetEzzZzyYrxtEVUtlJnZtJcURBlQUQJ9irVeklCTs06LwgTMQj61PMUS4Vy",
+            "device_id": "KIUVQQSDTM",
+            "sender_key": "LvryVyoCjdONdBCi2vvoSbI34yTOx7YrCFACUEKoXnc",
+            "session_id": "64H7XKokIx0ASkYDHZKlT5zd/Zccz/cQspPNdvnNULA",
+        },
+    }))
+    .unwrap();
+    let actions = room.push_actions_for_event(&encrypted).await.unwrap().unwrap();
+    assert_eq!(actions.rule_id.as_deref(), Some(".m.rule.encrypted"));
+    assert!(!actions.is_highlight());
+
+    // The decrypted payload supplied by the caller is evaluated as any other event.
+    let decrypted = text_message("Hey you", &[user_id.as_str()]);
+    let actions = room.push_actions_for_event(&decrypted).await.unwrap().unwrap();
+    assert_eq!(actions.rule_id.as_deref(), Some(".m.rule.is_user_mention"));
+    assert!(actions.is_highlight());
+}
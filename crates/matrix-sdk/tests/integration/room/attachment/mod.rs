@@ -8,10 +8,10 @@ use matrix_sdk::{
 };
 use matrix_sdk_test::{async_test, event_factory::EventFactory, ALICE, DEFAULT_TEST_ROOM_ID};
 use ruma::{
-    event_id,
+    assign, event_id,
     events::{
-        room::{message::ReplyWithinThread, MediaSource},
-        Mentions,
+        room::{message::ReplyWithinThread, ImageInfo, MediaSource},
+        Mentions, MessageLikeEventType,
     },
     mxc_uri, owned_mxc_uri, owned_user_id, uint,
 };
@@ -98,6 +98,84 @@ async fn test_room_attachment_send_in_encrypted_room_has_binary_mime_type() {
     assert_eq!(expected_event_id, response.event_id);
 }
 
+#[async_test]
+async fn test_room_send_sticker() {
+    let mock = MatrixMockServer::new().await;
+
+    let expected_event_id = event_id!("$h29iv0s8:example.com");
+
+    mock.mock_room_send()
+        .for_type(MessageLikeEventType::Sticker)
+        .body_matches_partial_json(json!({
+            "body": "A cat",
+            "url": "mxc://example.com/cat",
+            "info": {
+                "mimetype": "image/png",
+            }
+        }))
+        .ok(expected_event_id)
+        .mock_once()
+        .mount()
+        .await;
+
+    let client = mock.client_builder().build().await;
+    let room = mock.sync_joined_room(&client, &DEFAULT_TEST_ROOM_ID).await;
+    mock.mock_room_state_encryption().plain().mount().await;
+
+    let info = assign!(ImageInfo::new(), { mimetype: Some("image/png".to_owned()) });
+    let response =
+        room.send_sticker(info, owned_mxc_uri!("mxc://example.com/cat"), "A cat").await.unwrap();
+
+    assert_eq!(expected_event_id, response.event_id);
+}
+
+#[cfg(feature = "e2e-encryption")]
+#[async_test]
+async fn test_room_send_sticker_in_encrypted_room() {
+    let mock = MatrixMockServer::new().await;
+
+    mock.mock_authenticated_media_config().ok_default().mount().await;
+
+    let expected_event_id = event_id!("$h29iv0s8:example.com");
+
+    // The sticker is encrypted, as any other event in the room.
+    mock.mock_room_send()
+        .for_type(MessageLikeEventType::RoomEncrypted)
+        .ok(expected_event_id)
+        .mock_once()
+        .mount()
+        .await;
+
+    // The image and its thumbnail are downloaded…
+    mock.mock_authed_media_download().ok_image().expect(2).mount().await;
+
+    // … and uploaded again, encrypted.
+    mock.mock_upload()
+        .expect_mime_type("application/octet-stream")
+        .ok(mxc_uri!("mxc://example.com/encrypted"))
+        .expect(2)
+        .mount()
+        .await;
+
+    // Needed for the message to be sent in an encrypted room
+    mock.mock_get_members().ok(Vec::new()).mock_once().mount().await;
+
+    let client = mock.client_builder().build().await;
+    let room = mock.sync_joined_room(&client, &DEFAULT_TEST_ROOM_ID).await;
+    mock.mock_room_state_encryption().encrypted().mount().await;
+
+    let info = assign!(ImageInfo::new(), {
+        mimetype: Some("image/jpeg".to_owned()),
+        thumbnail_source: Some(MediaSource::Plain(owned_mxc_uri!("mxc://example.com/cat_thumbnail"))),
+    });
+    let response = room
+        .send_sticker(info, owned_mxc_uri!("mxc://example.com/cat"), "A cat")
+        .await
+        .expect("Failed to send sticker");
+
+    assert_eq!(expected_event_id, response.event_id);
+}
+
 #[async_test]
 async fn test_room_attachment_send_info() {
     let mock = MatrixMockServer::new().await;
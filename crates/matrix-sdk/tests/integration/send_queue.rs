@@ -1,4 +1,4 @@
-use std::{ops::Not as _, sync::Arc, time::Duration};
+use std::{ops::Not as _, pin::pin, sync::Arc, time::Duration};
 
 use as_variant::as_variant;
 use assert_matches2::{assert_let, assert_matches};
@@ -10,8 +10,9 @@ use matrix_sdk::{
     media::{MediaFormat, MediaRequestParameters, MediaThumbnailSettings},
     room::reply::Reply,
     send_queue::{
-        LocalEcho, LocalEchoContent, RoomSendQueue, RoomSendQueueError, RoomSendQueueStorageError,
-        RoomSendQueueUpdate, SendHandle, SendQueueUpdate,
+        AbortOptions, LocalEcho, LocalEchoContent, PacingPolicy, RateLimit, RoomSendQueue,
+        RoomSendQueueError, RoomSendQueueStorageError, RoomSendQueueUpdate, SendHandle,
+        SendQueueStatus, SendQueueUpdate, SendRequestStatus, SendingStep,
     },
     test_utils::mocks::{MatrixMock, MatrixMockServer},
     Client, MemoryStore,
@@ -36,13 +37,14 @@ use ruma::{
             },
             MediaSource,
         },
-        AnyMessageLikeEventContent, Mentions, MessageLikeEventContent as _,
+        AnyMessageLikeEventContent, Mentions, MessageLikeEventContent as _, MessageLikeEventType,
     },
     mxc_uri, owned_mxc_uri, owned_user_id, room_id,
     serde::Raw,
     uint, MxcUri, OwnedEventId, OwnedTransactionId, TransactionId,
 };
 use serde_json::json;
+use stream_assert::{assert_next_eq, assert_pending};
 use tokio::{
     sync::{broadcast::Receiver, Mutex},
     task::yield_now,
@@ -580,6 +582,111 @@ async fn test_error_then_locally_reenabling() {
     assert!(watch.is_empty());
 }
 
+#[async_test]
+async fn test_send_status_through_transient_failure() {
+    let mock = MatrixMockServer::new().await;
+
+    // Mark the room as joined.
+    let room_id = room_id!("!a:b.c");
+    let client = mock.client_builder().build().await;
+    let room = mock.sync_joined_room(&client, room_id).await;
+
+    let q = room.send_queue();
+
+    mock.mock_room_state_encryption().plain().mount().await;
+
+    // Start with a disabled room send queue, so the request stays queued.
+    q.set_enabled(false);
+
+    let handle = q.send(RoomMessageEventContent::text_plain("1").into()).await.unwrap();
+    let mut status = handle.subscribe_to_status().expect("the request must be tracked");
+    assert_matches!(status.get(), SendRequestStatus::Queued);
+
+    // The first attempt fails with a transient error.
+    let lock = Arc::new(Mutex::new(()));
+    let lock_guard = lock.lock().await;
+    let mock_lock = lock.clone();
+
+    let scoped_send = mock
+        .mock_room_send()
+        .respond_with(move |_req: &Request| {
+            // Wait for the signal from the main thread that we can process this query.
+            let mock_lock = mock_lock.clone();
+            std::thread::spawn(move || {
+                tokio::runtime::Runtime::new().unwrap().block_on(async {
+                    drop(mock_lock.lock().await);
+                });
+            })
+            .join()
+            .unwrap();
+
+            ResponseTemplate::new(500)
+        })
+        .expect(3)
+        .mount_as_scoped()
+        .await;
+
+    q.set_enabled(true);
+
+    assert_let!(Ok(Some(first_attempt)) = timeout(Duration::from_secs(1), status.next()).await);
+    assert_matches!(
+        first_attempt,
+        SendRequestStatus::SendingAttempt { attempt: 1, step: SendingStep::SendingEvent, .. }
+    );
+
+    drop(lock_guard);
+
+    // The exponential backoff used when retrying a request introduces a bit of
+    // non-determinism, so wait for a large amount of time.
+    assert_let!(Ok(Some(waiting)) = timeout(Duration::from_secs(10), status.next()).await);
+    assert_matches!(waiting, SendRequestStatus::WaitingRetry { until: None });
+    assert!(!q.is_enabled());
+
+    drop(scoped_send);
+
+    // The second attempt succeeds.
+    let lock = Arc::new(Mutex::new(()));
+    let lock_guard = lock.lock().await;
+    let mock_lock = lock.clone();
+
+    mock.mock_room_send()
+        .respond_with(move |_req: &Request| {
+            // Wait for the signal from the main thread that we can process this query.
+            let mock_lock = mock_lock.clone();
+            std::thread::spawn(move || {
+                tokio::runtime::Runtime::new().unwrap().block_on(async {
+                    drop(mock_lock.lock().await);
+                });
+            })
+            .join()
+            .unwrap();
+
+            ResponseTemplate::new(200).set_body_json(json!({
+                "event_id": "$42",
+            }))
+        })
+        .expect(1)
+        .mount()
+        .await;
+
+    q.set_enabled(true);
+
+    assert_let!(Ok(Some(second_attempt)) = timeout(Duration::from_secs(1), status.next()).await);
+    assert_matches!(
+        second_attempt,
+        SendRequestStatus::SendingAttempt { attempt: 2, step: SendingStep::SendingEvent, .. }
+    );
+
+    drop(lock_guard);
+
+    assert_let!(Ok(Some(sent)) = timeout(Duration::from_secs(1), status.next()).await);
+    assert_let!(SendRequestStatus::Sent { event_id } = sent);
+    assert_eq!(event_id, event_id!("$42"));
+
+    // The request isn't tracked anymore.
+    assert!(handle.subscribe_to_status().is_none());
+}
+
 #[async_test]
 async fn test_error_then_globally_reenabling() {
     let mock = MatrixMockServer::new().await;
@@ -1360,6 +1467,197 @@ async fn test_abort_or_edit_after_send() {
     assert!(watch.is_empty());
 }
 
+#[async_test]
+async fn test_abort_queued_event_removes_dependents() {
+    let mock = MatrixMockServer::new().await;
+
+    // Mark the room as joined.
+    let room_id = room_id!("!a:b.c");
+    let client = mock.client_builder().build().await;
+    let room = mock.sync_joined_room(&client, room_id).await;
+
+    let q = room.send_queue();
+    let mut global_watch = client.send_queue().subscribe();
+
+    let (local_echoes, mut watch) = q.subscribe().await.unwrap();
+    assert!(local_echoes.is_empty());
+    assert!(watch.is_empty());
+
+    // Nothing will be sent.
+    mock.mock_room_send().ok(event_id!("$1")).expect(0).mount().await;
+
+    // Keep the request in the queue.
+    q.set_enabled(false);
+
+    let handle = q.send(RoomMessageEventContent::text_plain("oops").into()).await.unwrap();
+    handle.react("👍".to_owned()).await.unwrap().expect("the reaction was queued");
+
+    let (txn, _) = assert_update!((global_watch, watch) => local echo { body = "oops" });
+    let reaction_txn =
+        assert_update!((global_watch, watch) => local reaction { key = "👍", parent = txn });
+
+    let status = handle.subscribe_to_status().expect("the request must be tracked");
+
+    // Aborting the queued event also aborts the reaction to it.
+    assert!(handle.abort().await.unwrap());
+    assert_update!((global_watch, watch) => cancelled { txn = txn });
+    assert_update!((global_watch, watch) => cancelled { txn = reaction_txn });
+    assert!(watch.is_empty());
+
+    assert_matches!(status.get(), SendRequestStatus::Aborted);
+
+    let (local_echoes, _) = q.subscribe().await.unwrap();
+    assert!(local_echoes.is_empty());
+
+    // Re-enabling the queue doesn't send anything.
+    q.set_enabled(true);
+    sleep(Duration::from_millis(50)).await;
+    assert!(watch.is_empty());
+}
+
+#[async_test]
+async fn test_abort_while_being_sent_without_redaction() {
+    let mock = MatrixMockServer::new().await;
+
+    // Mark the room as joined.
+    let room_id = room_id!("!a:b.c");
+    let client = mock.client_builder().build().await;
+    let room = mock.sync_joined_room(&client, room_id).await;
+
+    let q = room.send_queue();
+    let mut global_watch = client.send_queue().subscribe();
+
+    let (local_echoes, mut watch) = q.subscribe().await.unwrap();
+    assert!(local_echoes.is_empty());
+    assert!(watch.is_empty());
+
+    let lock = Arc::new(Mutex::new(()));
+    let lock_guard = lock.lock().await;
+
+    let mock_lock = lock.clone();
+
+    mock.mock_room_state_encryption().plain().mount().await;
+
+    mock.mock_room_send()
+        .respond_with(move |_req: &Request| {
+            // Wait for the signal from the main thread that we can process this query.
+            let mock_lock = mock_lock.clone();
+            std::thread::spawn(move || {
+                tokio::runtime::Runtime::new().unwrap().block_on(async {
+                    drop(mock_lock.lock().await);
+                });
+            })
+            .join()
+            .unwrap();
+
+            ResponseTemplate::new(200).set_body_json(json!({
+                "event_id": "$1",
+            }))
+        })
+        .expect(1)
+        .mount()
+        .await;
+
+    // No redaction must happen.
+    mock.mock_room_redact().ok(event_id!("$2")).expect(0).mount().await;
+
+    let handle = q.send(RoomMessageEventContent::text_plain("yo").into()).await.unwrap();
+
+    let (txn, _) = assert_update!((global_watch, watch) => local echo { body = "yo" });
+    assert!(watch.is_empty());
+
+    // Let the background task start now.
+    yield_now().await;
+
+    // While the item is being sent, it can't be aborted without a redaction.
+    assert!(handle
+        .abort_with_options(AbortOptions { abort_with_redaction: false })
+        .await
+        .unwrap()
+        .not());
+    assert!(watch.is_empty());
+
+    // Let the server process the response.
+    drop(lock_guard);
+
+    // The event is sent normally.
+    assert_update!((global_watch, watch) => sent { txn = txn, event_id = event_id!("$1") });
+    assert!(watch.is_empty());
+}
+
+#[async_test]
+async fn test_abort_while_being_sent_and_server_accepts() {
+    let mock = MatrixMockServer::new().await;
+
+    // Mark the room as joined.
+    let room_id = room_id!("!a:b.c");
+    let client = mock.client_builder().build().await;
+    let room = mock.sync_joined_room(&client, room_id).await;
+
+    let q = room.send_queue();
+    let mut global_watch = client.send_queue().subscribe();
+
+    let (local_echoes, mut watch) = q.subscribe().await.unwrap();
+    assert!(local_echoes.is_empty());
+    assert!(watch.is_empty());
+
+    let lock = Arc::new(Mutex::new(()));
+    let lock_guard = lock.lock().await;
+
+    let mock_lock = lock.clone();
+
+    mock.mock_room_state_encryption().plain().mount().await;
+
+    mock.mock_room_send()
+        .respond_with(move |_req: &Request| {
+            // Wait for the signal from the main thread that we can process this query.
+            let mock_lock = mock_lock.clone();
+            std::thread::spawn(move || {
+                tokio::runtime::Runtime::new().unwrap().block_on(async {
+                    drop(mock_lock.lock().await);
+                });
+            })
+            .join()
+            .unwrap();
+
+            ResponseTemplate::new(200).set_body_json(json!({
+                "event_id": "$1",
+            }))
+        })
+        .expect(1)
+        .mount()
+        .await;
+
+    // The server accepted the event after the abort, so it gets redacted.
+    mock.mock_room_redact().ok(event_id!("$2")).expect(1).mount().await;
+
+    let handle = q.send(RoomMessageEventContent::text_plain("yo").into()).await.unwrap();
+
+    let (txn, _) = assert_update!((global_watch, watch) => local echo { body = "yo" });
+    assert!(watch.is_empty());
+
+    let status = handle.subscribe_to_status().expect("the request must be tracked");
+
+    // Let the background task start now.
+    yield_now().await;
+
+    // The local echo disappears immediately.
+    assert!(handle.abort().await.unwrap());
+    assert_update!((global_watch, watch) => cancelled { txn = txn });
+    assert_matches!(status.get(), SendRequestStatus::Aborted);
+
+    // Now the server response arrives.
+    drop(lock_guard);
+
+    assert_update!((global_watch, watch) => sent { txn = txn, event_id = event_id!("$1") });
+
+    // Give some time to the queue to send the redaction.
+    sleep(Duration::from_millis(100)).await;
+
+    // The status stays terminal.
+    assert_matches!(status.get(), SendRequestStatus::Aborted);
+}
+
 #[async_test]
 async fn test_abort_while_being_sent_and_fails() {
     let mock = MatrixMockServer::new().await;
@@ -1626,22 +1924,231 @@ async fn test_no_network_access_error_is_recoverable() {
 }
 
 #[async_test]
-async fn test_reloading_rooms_with_unsent_events() {
-    let store = Arc::new(MemoryStore::new());
+async fn test_network_unavailable_pauses_all_rooms() {
+    let mock = MatrixMockServer::new().await;
 
+    // Mark the room as joined.
     let room_id = room_id!("!a:b.c");
-    let room_id2 = room_id!("!d:e.f");
+    let client = mock.client_builder().build().await;
+    let room = mock.sync_joined_room(&client, room_id).await;
 
-    let server = wiremock::MockServer::start().await;
-    let mock = MatrixMockServer::from_server(server);
+    let q = room.send_queue();
+    let mut global_watch = client.send_queue().subscribe();
 
-    let client = mock
-        .client_builder()
-        .on_builder(|builder| {
-            builder.store_config(
-                StoreConfig::new("cross-process-store-locks-holder-name".to_owned())
-                    .state_store(store.clone()),
-            )
+    let (local_echoes, mut watch) = q.subscribe().await.unwrap();
+    assert!(local_echoes.is_empty());
+    assert!(watch.is_empty());
+
+    let mut status = pin!(client.send_queue().status_stream());
+    assert_next_eq!(status, SendQueueStatus::Running);
+
+    let lock = Arc::new(Mutex::new(0));
+    let lock_guard = lock.lock().await;
+
+    let mock_lock = lock.clone();
+
+    mock.mock_room_state_encryption().plain().mount().await;
+
+    mock.mock_room_send()
+        .respond_with(move |_req: &Request| {
+            // Wait for the signal from the main thread that we can process this query.
+            let mock_lock = mock_lock.clone();
+            let event_id = std::thread::spawn(move || {
+                tokio::runtime::Runtime::new().unwrap().block_on(async {
+                    let mut event_id = mock_lock.lock().await;
+                    let ret = *event_id;
+                    *event_id += 1;
+                    ret
+                })
+            })
+            .join()
+            .unwrap();
+
+            ResponseTemplate::new(200).set_body_json(json!({
+                "event_id": format!("${event_id}"),
+            }))
+        })
+        .expect(3)
+        .mount()
+        .await;
+
+    q.send(RoomMessageEventContent::text_plain("1").into()).await.unwrap();
+    let (txn1, _) = assert_update!((global_watch, watch) => local echo { body = "1" });
+
+    // Wait for the first request to be in flight, then lose the network.
+    sleep(Duration::from_millis(50)).await;
+    client.send_queue().set_network_available(false);
+    assert_next_eq!(status, SendQueueStatus::Offline);
+
+    // Queue more messages while offline.
+    q.send(RoomMessageEventContent::text_plain("2").into()).await.unwrap();
+    q.send(RoomMessageEventContent::text_plain("3").into()).await.unwrap();
+    let (txn2, _) = assert_update!((global_watch, watch) => local echo { body = "2" });
+    let (txn3, _) = assert_update!((global_watch, watch) => local echo { body = "3" });
+
+    // The request that was in flight goes through.
+    drop(lock_guard);
+    assert_update!((global_watch, watch) => sent { txn = txn1, event_id = event_id!("$0") });
+
+    // But nothing else is attempted while offline.
+    sleep(Duration::from_millis(300)).await;
+    assert!(watch.is_empty());
+    assert_eq!(*lock.lock().await, 1);
+
+    // The queue itself hasn't been disabled.
+    assert!(q.is_enabled());
+    assert!(client.send_queue().is_enabled());
+
+    // Once the network is back, the remaining messages are sent, in order.
+    client.send_queue().set_network_available(true);
+    assert_next_eq!(status, SendQueueStatus::Running);
+
+    assert_update!((global_watch, watch) => sent { txn = txn2, event_id = event_id!("$1") });
+    assert_update!((global_watch, watch) => sent { txn = txn3, event_id = event_id!("$2") });
+    assert!(watch.is_empty());
+    assert_pending!(status);
+}
+
+#[async_test]
+async fn test_automatic_backoff_after_transient_error() {
+    let mock = MatrixMockServer::new().await;
+
+    // Mark the room as joined.
+    let room_id = room_id!("!a:b.c");
+    let client = mock.client_builder().build().await;
+    let room = mock.sync_joined_room(&client, room_id).await;
+
+    client.send_queue().set_automatic_backoff(true);
+
+    let q = room.send_queue();
+    let mut global_watch = client.send_queue().subscribe();
+
+    let (local_echoes, mut watch) = q.subscribe().await.unwrap();
+    assert!(local_echoes.is_empty());
+    assert!(watch.is_empty());
+
+    mock.mock_room_state_encryption().plain().mount().await;
+
+    // The server fails with a transient error (including the HTTP client's own
+    // retries).
+    let scoped_send = mock.mock_room_send().error500().expect(3).mount_as_scoped().await;
+
+    let handle = q.send(RoomMessageEventContent::text_plain("1").into()).await.unwrap();
+    let (txn, _) = assert_update!((global_watch, watch) => local echo { body = "1" });
+
+    let send_status = handle.subscribe_to_status().expect("the request must be tracked");
+
+    // The error is reported as recoverable…
+    assert_update!((global_watch, watch) => error { recoverable = true, txn = txn });
+
+    // …but instead of disabling the room's queue, all the rooms back off for a
+    // while.
+    assert!(q.is_enabled());
+    assert_matches!(
+        client.send_queue().status(),
+        SendQueueStatus::BackingOff { consecutive_failures: 1, .. }
+    );
+    assert_matches!(send_status.get(), SendRequestStatus::WaitingRetry { until: Some(_) });
+
+    // The server recovers.
+    drop(scoped_send);
+    mock.mock_room_send().ok(event_id!("$42")).expect(1).mount().await;
+
+    // The request is retried by itself, after the backoff period.
+    assert_update!((global_watch, watch) => sent { txn = txn, event_id = event_id!("$42") });
+    assert_eq!(client.send_queue().status(), SendQueueStatus::Running);
+    assert!(watch.is_empty());
+}
+
+#[async_test]
+async fn test_pacing_policy_smooths_out_bursts() {
+    let mock = MatrixMockServer::new().await;
+
+    // Mark the room as joined.
+    let room_id = room_id!("!a:b.c");
+    let client = mock.client_builder().build().await;
+    let room = mock.sync_joined_room(&client, room_id).await;
+
+    let period = Duration::from_secs(1);
+    client
+        .send_queue()
+        .set_pacing_policy(PacingPolicy::default().per_room(RateLimit::new(5, period)));
+
+    let q = room.send_queue();
+    let mut global_watch = client.send_queue().subscribe();
+
+    let (local_echoes, mut watch) = q.subscribe().await.unwrap();
+    assert!(local_echoes.is_empty());
+
+    mock.mock_room_state_encryption().plain().mount().await;
+
+    // Record when each message reaches the server.
+    let received = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let mock_received = received.clone();
+
+    mock.mock_room_send()
+        .respond_with(move |req: &Request| {
+            let body: serde_json::Value = req.body_json().unwrap();
+            let body = body["body"].as_str().unwrap().to_owned();
+
+            let mut received = mock_received.lock().unwrap();
+            received.push((std::time::Instant::now(), body));
+
+            ResponseTemplate::new(200).set_body_json(json!({
+                "event_id": format!("${}", received.len()),
+            }))
+        })
+        .expect(20)
+        .mount()
+        .await;
+
+    // Send a burst of messages.
+    for i in 0..20 {
+        q.send(RoomMessageEventContent::text_plain(i.to_string()).into()).await.unwrap();
+        assert_update!((global_watch, watch) => local echo { body = i.to_string() });
+    }
+
+    // All the messages are eventually sent.
+    for _ in 0..20 {
+        assert_let!(
+            Ok(Ok(RoomSendQueueUpdate::SentEvent { .. })) =
+                timeout(Duration::from_secs(2), watch.recv()).await
+        );
+    }
+
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 20);
+
+    // They were sent in order…
+    for (i, (_, body)) in received.iter().enumerate() {
+        assert_eq!(*body, i.to_string());
+    }
+
+    // …and never more than 5 during any period (with a bit of leeway for the
+    // timers' precision).
+    for window in received.windows(6) {
+        let elapsed = window[5].0.duration_since(window[0].0);
+        assert!(elapsed >= period - Duration::from_millis(50), "{elapsed:?} between 6 sends");
+    }
+}
+
+#[async_test]
+async fn test_reloading_rooms_with_unsent_events() {
+    let store = Arc::new(MemoryStore::new());
+
+    let room_id = room_id!("!a:b.c");
+    let room_id2 = room_id!("!d:e.f");
+
+    let server = wiremock::MockServer::start().await;
+    let mock = MatrixMockServer::from_server(server);
+
+    let client = mock
+        .client_builder()
+        .on_builder(|builder| {
+            builder.store_config(
+                StoreConfig::new("cross-process-store-locks-holder-name".to_owned())
+                    .state_store(store.clone()),
+            )
         })
         .build()
         .await;
@@ -1836,6 +2343,121 @@ async fn test_reactions() {
     assert!(watch.is_empty());
 }
 
+#[async_test]
+async fn test_reaction_to_queued_event_uses_sent_event_id() {
+    let mock = MatrixMockServer::new().await;
+
+    // Mark the room as joined.
+    let room_id = room_id!("!a:b.c");
+    let client = mock.client_builder().build().await;
+    let room = mock.sync_joined_room(&client, room_id).await;
+
+    let q = room.send_queue();
+    let mut global_watch = client.send_queue().subscribe();
+
+    let (local_echoes, mut watch) = q.subscribe().await.unwrap();
+    assert!(local_echoes.is_empty());
+    assert!(watch.is_empty());
+
+    mock.mock_room_state_encryption().plain().mount().await;
+
+    mock.mock_room_send()
+        .for_type(MessageLikeEventType::RoomMessage)
+        .ok(event_id!("$1"))
+        .expect(1)
+        .mount()
+        .await;
+
+    // The reaction must target the event id of the sent event, not the transaction
+    // id of its local echo.
+    mock.mock_room_send()
+        .for_type(MessageLikeEventType::Reaction)
+        .body_matches_partial_json(json!({
+            "m.relates_to": {
+                "rel_type": "m.annotation",
+                "event_id": "$1",
+                "key": "👍",
+            }
+        }))
+        .ok(event_id!("$2"))
+        .expect(1)
+        .mount()
+        .await;
+
+    // Pretend we're offline: nothing is sent.
+    q.set_enabled(false);
+
+    let handle = q.send(RoomMessageEventContent::text_plain("hello").into()).await.unwrap();
+    handle.react("👍".to_owned()).await.unwrap().expect("the reaction was queued");
+
+    let (txn, _) = assert_update!((global_watch, watch) => local echo { body = "hello" });
+    let reaction_txn =
+        assert_update!((global_watch, watch) => local reaction { key = "👍", parent = txn });
+    assert!(watch.is_empty());
+
+    // Back online: the event is sent first, then the reaction to it.
+    q.set_enabled(true);
+
+    assert_update!((global_watch, watch) => sent { txn = txn, event_id = event_id!("$1") });
+    assert_update!((global_watch, watch) => sent { txn = reaction_txn, event_id = event_id!("$2") });
+    assert!(watch.is_empty());
+}
+
+#[async_test]
+async fn test_reactions_are_dropped_when_event_is_wedged() {
+    let mock = MatrixMockServer::new().await;
+
+    // Mark the room as joined.
+    let room_id = room_id!("!a:b.c");
+    let client = mock.client_builder().build().await;
+    let room = mock.sync_joined_room(&client, room_id).await;
+
+    let q = room.send_queue();
+    let mut global_watch = client.send_queue().subscribe();
+
+    let (local_echoes, mut watch) = q.subscribe().await.unwrap();
+    assert!(local_echoes.is_empty());
+    assert!(watch.is_empty());
+
+    mock.mock_room_state_encryption().plain().mount().await;
+
+    // The event is rejected with an unrecoverable error, and no reaction is ever
+    // sent.
+    mock.mock_room_send()
+        .for_type(MessageLikeEventType::RoomMessage)
+        .error_too_large()
+        .expect(1)
+        .mount()
+        .await;
+    mock.mock_room_send()
+        .for_type(MessageLikeEventType::Reaction)
+        .ok(event_id!("$2"))
+        .expect(0)
+        .mount()
+        .await;
+
+    q.set_enabled(false);
+
+    let handle = q.send(RoomMessageEventContent::text_plain("hello").into()).await.unwrap();
+    handle.react("👍".to_owned()).await.unwrap().expect("the reaction was queued");
+
+    let (txn, _) = assert_update!((global_watch, watch) => local echo { body = "hello" });
+    let reaction_txn =
+        assert_update!((global_watch, watch) => local reaction { key = "👍", parent = txn });
+
+    q.set_enabled(true);
+
+    // The event gets wedged, and the reaction to it is dropped.
+    assert_update!((global_watch, watch) => error { recoverable = false, txn = txn });
+    assert_update!((global_watch, watch) => cancelled { txn = reaction_txn });
+    assert!(watch.is_empty());
+
+    // Only the wedged event remains as a local echo.
+    let (local_echoes, _) = q.subscribe().await.unwrap();
+    assert_eq!(local_echoes.len(), 1);
+    assert_eq!(local_echoes[0].transaction_id, txn);
+}
+
 #[async_test]
 async fn test_media_uploads() {
     let mock = MatrixMockServer::new().await;
@@ -2625,6 +3247,120 @@ async fn test_media_upload_retry() {
     assert!(watch.is_empty());
 }
 
+#[cfg(feature = "sqlite")]
+#[async_test]
+async fn test_media_upload_resumes_after_restart() {
+    use tempfile::tempdir;
+
+    let dir = tempdir().unwrap();
+    let mock = MatrixMockServer::new().await;
+
+    // Mark the room as joined.
+    let room_id = room_id!("!a:b.c");
+    let client = mock
+        .client_builder()
+        .on_builder(|builder| builder.sqlite_store(dir.path(), None))
+        .build()
+        .await;
+    let room = mock.sync_joined_room(&client, room_id).await;
+
+    let q = room.send_queue();
+    let (local_echoes, mut watch) = q.subscribe().await.unwrap();
+    assert!(local_echoes.is_empty());
+
+    let mut global_watch = client.send_queue().subscribe();
+
+    mock.mock_authenticated_media_config().ok_default().mount().await;
+    mock.mock_room_state_encryption().plain().mount().await;
+
+    // The thumbnail is uploaded, but uploading the file fails.
+    mock.mock_upload()
+        .expect_mime_type("image/jpeg")
+        .ok(mxc_uri!("mxc://sdk.rs/thumbnail"))
+        .mock_once()
+        .mount()
+        .await;
+    mock.mock_upload().expect_mime_type("image/jpeg").error500().expect(3).mount().await;
+
+    let (_handle, filename) = queue_attachment_with_thumbnail(&q).await;
+
+    let (event_txn, _send_handle, content) =
+        assert_update!((global_watch, watch) => local echo event);
+    assert_let!(MessageType::Image(img_content) = content.msgtype);
+    assert_eq!(img_content.body, filename);
+
+    assert_update!((global_watch, watch) => uploaded {
+        related_to = event_txn,
+        mxc = mxc_uri!("mxc://sdk.rs/thumbnail")
+    });
+    assert_update!((global_watch, watch) => error { recoverable = true, txn = event_txn });
+
+    mock.verify_and_reset().await;
+
+    {
+        // Kill the client, let it close background tasks.
+        drop(watch);
+        drop(global_watch);
+        drop(q);
+        drop(room);
+        drop(client);
+        sleep(Duration::from_secs(1)).await;
+    }
+
+    // Create a new client over the same store: the upload is resumed where it
+    // stopped, i.e. only the file is uploaded, and the event refers to both the
+    // file and the thumbnail uploaded before the restart.
+    mock.mock_authenticated_media_config().ok_default().mount().await;
+    mock.mock_room_state_encryption().plain().mount().await;
+    mock.mock_upload()
+        .expect_mime_type("image/jpeg")
+        .ok(mxc_uri!("mxc://sdk.rs/media"))
+        .expect(1)
+        .mount()
+        .await;
+    mock.mock_room_send()
+        .body_matches_partial_json(json!({
+            "url": "mxc://sdk.rs/media",
+            "info": {
+                "thumbnail_url": "mxc://sdk.rs/thumbnail",
+            },
+        }))
+        .ok(event_id!("$1"))
+        .expect(1)
+        .mount()
+        .await;
+
+    let new_client = mock
+        .client_builder()
+        .on_builder(|builder| builder.sqlite_store(dir.path(), None))
+        .build()
+        .await;
+    let mut global_watch = new_client.send_queue().subscribe();
+
+    new_client.send_queue().respawn_tasks_for_rooms_with_unsent_requests().await;
+
+    // The event is eventually sent, without any intervention.
+    loop {
+        let update = timeout(Duration::from_secs(5), global_watch.recv())
+            .await
+            .expect("the event should have been sent")
+            .unwrap();
+
+        if let RoomSendQueueUpdate::SentEvent { transaction_id, event_id } = update.update {
+            assert_eq!(transaction_id, event_txn);
+            assert_eq!(event_id, event_id!("$1"));
+            break;
+        }
+    }
+
+    // The local echo is gone.
+    let room = new_client.get_room(room_id).unwrap();
+    let (local_echoes, _) = room.send_queue().subscribe().await.unwrap();
+    assert!(local_echoes.is_empty());
+
+    mock.verify_and_reset().await;
+}
+
 #[async_test]
 async fn test_media_upload_retry_with_520_http_status_code() {
     let mock = MatrixMockServer::new().await;
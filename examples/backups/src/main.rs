@@ -83,7 +83,10 @@ async fn listen_for_backup_state_changes(client: Client) {
             BackupState::Enabling => println!("Trying to enable backups"),
             BackupState::Resuming => println!("Trying to resume backups"),
             BackupState::Enabled => println!("Backups have been successfully enabled"),
-            BackupState::Downloading => println!("Downloading the room keys from the backup"),
+            BackupState::Downloading(progress) => println!(
+                "Downloading the room keys from the backup: {}/{}",
+                progress.downloaded, progress.total
+            ),
             BackupState::Disabling => println!("Disabling the backup"),
             BackupState::Creating => println!("Trying to create a new backup"),
         }
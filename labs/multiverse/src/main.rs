@@ -510,6 +510,7 @@ async fn configure_client(cli: Cli) -> Result<Client> {
         .with_encryption_settings(EncryptionSettings {
             auto_enable_cross_signing: true,
             backup_download_strategy: BackupDownloadStrategy::AfterDecryptionFailure,
+            backup_download_settings: Default::default(),
             auto_enable_backups: true,
         })
         .with_enable_share_history_on_invite(true);
@@ -149,7 +149,7 @@ impl DefaultRecoveryView {
             (BackupState::Creating, _)
             | (BackupState::Enabling, _)
             | (BackupState::Resuming, _)
-            | (BackupState::Downloading, _)
+            | (BackupState::Downloading(_), _)
             | (BackupState::Disabling, _) => {}
         }
     }
@@ -285,7 +285,7 @@ impl Widget for &mut DefaultRecoveryView {
             | (BackupState::Resuming, _) => ListItem::new("Key storage [x]").dim(),
             (BackupState::Enabled, true) => ListItem::new("Key storage [x]"),
             (BackupState::Enabled, false) => ListItem::new("Key storage [x]"),
-            (BackupState::Downloading, _) | (BackupState::Disabling, _) => {
+            (BackupState::Downloading(_), _) | (BackupState::Disabling, _) => {
                 ListItem::new("Key storage [ ]").dim()
             }
         };
@@ -914,6 +914,7 @@ async fn test_secret_gossip_after_interactive_verification() -> Result<()> {
         auto_enable_cross_signing: true,
         auto_enable_backups: true,
         backup_download_strategy: BackupDownloadStrategy::OneShot,
+        backup_download_settings: Default::default(),
     };
 
     let first_client = SyncTokenAwareClient::new(